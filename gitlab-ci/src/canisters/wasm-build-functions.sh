@@ -19,6 +19,7 @@ wasm_canister_list=(
     mem-utils-test-canister
     memory-test-canister
     nan_canonicalized
+    nan_canonicalized_simd
     nns-ui-canister
     panics
     pmap_canister
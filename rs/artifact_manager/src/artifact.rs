@@ -3,9 +3,9 @@
 use ic_consensus_message::ConsensusMessageHashable;
 use ic_ecdsa_object::ecdsa_msg_hash;
 use ic_types::{
-    artifact::*, consensus::certification::CertificationMessageHash,
-    consensus::ecdsa::EcdsaMessageAttribute, crypto::CryptoHashOf, messages::SignedRequestBytes,
-    CountBytes,
+    artifact::*, canister_http::CanisterHttpResponseShare,
+    consensus::certification::CertificationMessageHash, consensus::ecdsa::EcdsaMessageAttribute,
+    crypto::CryptoHashOf, messages::SignedRequestBytes, CountBytes,
 };
 use serde::{Deserialize, Serialize};
 
@@ -164,3 +164,33 @@ impl ArtifactKind for EcdsaArtifact {
         }
     }
 }
+
+/// The `ArtifactKind` of canister HTTP response shares.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct CanisterHttpArtifact;
+
+/// `CanisterHttpArtifact` implements the `ArtifactKind` trait.
+impl ArtifactKind for CanisterHttpArtifact {
+    const TAG: ArtifactTag = ArtifactTag::CanisterHttpArtifact;
+    type Id = CanisterHttpResponseId;
+    type Message = CanisterHttpResponseShare;
+    type SerializeAs = CanisterHttpResponseShare;
+    type Attribute = CanisterHttpResponseAttribute;
+    type Filter = ();
+
+    /// The function converts a `CanisterHttpResponseShare` into an advert for
+    /// a `CanisterHttpArtifact`.
+    fn message_to_advert(msg: &CanisterHttpResponseShare) -> Advert<CanisterHttpArtifact> {
+        let size = bincode::serialize(msg).unwrap().len();
+        let hash = ic_crypto::crypto_hash(msg);
+        Advert {
+            id: hash.clone(),
+            attribute: CanisterHttpResponseAttribute {
+                id: msg.content.id,
+                registry_version: msg.content.registry_version,
+            },
+            size,
+            integrity_hash: hash.get(),
+        }
+    }
+}
@@ -5,14 +5,15 @@ use crate::processors::ArtifactProcessorManager;
 use ic_interfaces::{
     artifact_manager::{AdvertMismatchError, ArtifactAcceptance, ArtifactClient, OnArtifactError},
     artifact_pool::{ArtifactPoolError, ReplicaVersionMismatch, UnvalidatedArtifact},
+    canister_http::{CanisterHttpGossip, CanisterHttpPool},
     certification::{CertificationPool, CertifierGossip},
     consensus::ConsensusGossip,
     consensus_pool::{ConsensusPool, ConsensusPoolCache},
     dkg::{DkgGossip, DkgPool},
     ecdsa::{EcdsaGossip, EcdsaPool},
     gossip_pool::{
-        CertificationGossipPool, ConsensusGossipPool, DkgGossipPool, EcdsaGossipPool,
-        IngressGossipPool,
+        CanisterHttpGossipPool, CertificationGossipPool, ConsensusGossipPool, DkgGossipPool,
+        EcdsaGossipPool, IngressGossipPool,
     },
     ingress_pool::IngressPool,
     time_source::TimeSource,
@@ -21,6 +22,7 @@ use ic_logger::{debug, ReplicaLogger};
 use ic_types::{
     artifact,
     artifact::*,
+    canister_http::CanisterHttpResponseShare,
     chunkable::*,
     consensus::{
         certification::CertificationMessage, dkg::Message as DkgMessage, ConsensusMessage,
@@ -667,3 +669,61 @@ impl<Pool: EcdsaPool + EcdsaGossipPool + Send + Sync> ArtifactClient<EcdsaArtifa
         Box::new(SingleChunked::Ecdsa)
     }
 }
+
+/// The canister HTTP client.
+pub struct CanisterHttpClient<Pool> {
+    canister_http_pool: Arc<RwLock<Pool>>,
+    canister_http_gossip: Arc<dyn CanisterHttpGossip>,
+}
+
+impl<Pool> CanisterHttpClient<Pool> {
+    pub fn new<T: CanisterHttpGossip + 'static>(
+        canister_http_pool: Arc<RwLock<Pool>>,
+        gossip: T,
+    ) -> Self {
+        Self {
+            canister_http_pool,
+            canister_http_gossip: Arc::new(gossip),
+        }
+    }
+}
+
+impl<Pool: CanisterHttpPool + CanisterHttpGossipPool + Send + Sync>
+    ArtifactClient<CanisterHttpArtifact> for CanisterHttpClient<Pool>
+{
+    fn check_artifact_acceptance(
+        &self,
+        msg: CanisterHttpResponseShare,
+        _peer_id: &NodeId,
+    ) -> Result<ArtifactAcceptance<CanisterHttpResponseShare>, ArtifactPoolError> {
+        Ok(ArtifactAcceptance::AcceptedForProcessing(msg))
+    }
+
+    fn has_artifact(&self, msg_id: &CanisterHttpResponseId) -> bool {
+        self.canister_http_pool.read().unwrap().contains(msg_id)
+    }
+
+    fn get_validated_by_identifier(
+        &self,
+        msg_id: &CanisterHttpResponseId,
+    ) -> Option<CanisterHttpResponseShare> {
+        self.canister_http_pool
+            .read()
+            .unwrap()
+            .get_validated_by_identifier(msg_id)
+    }
+
+    fn get_priority_function(
+        &self,
+    ) -> Option<PriorityFn<CanisterHttpResponseId, CanisterHttpResponseAttribute>> {
+        let canister_http_pool = &*self.canister_http_pool.read().unwrap();
+        Some(
+            self.canister_http_gossip
+                .get_priority_function(canister_http_pool),
+        )
+    }
+
+    fn get_chunk_tracker(&self, _id: &CanisterHttpResponseId) -> Box<dyn Chunkable + Send + Sync> {
+        Box::new(SingleChunked::CanisterHttp)
+    }
+}
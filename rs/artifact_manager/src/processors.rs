@@ -5,6 +5,10 @@ use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use ic_interfaces::{
     artifact_manager::{ArtifactProcessor, ProcessingResult},
     artifact_pool::UnvalidatedArtifact,
+    canister_http::{
+        CanisterHttpChangeAction, CanisterHttpGossip, CanisterHttpPoolManager,
+        MutableCanisterHttpPool,
+    },
     certification,
     certification::{Certifier, CertifierGossip, MutableCertificationPool},
     consensus::{Consensus, ConsensusGossip},
@@ -23,6 +27,7 @@ use ic_metrics::MetricsRegistry;
 use ic_types::consensus::HasRank;
 use ic_types::{
     artifact::*,
+    canister_http::CanisterHttpResponseShare,
     consensus::{certification::CertificationMessage, dkg, ConsensusMessage},
     malicious_flags::MaliciousFlags,
     messages::SignedIngress,
@@ -866,3 +871,115 @@ impl<PoolEcdsa: MutableEcdsaPool + Send + Sync + 'static> ArtifactProcessor<Ecds
         (adverts, changed)
     }
 }
+
+/// Canister HTTP `OnStateChange` client.
+pub struct CanisterHttpProcessor<PoolCanisterHttp> {
+    canister_http_pool: Arc<RwLock<PoolCanisterHttp>>,
+    client: Box<dyn CanisterHttpPoolManager>,
+    /// The invalidated artifacts counter.
+    invalidated_artifacts: IntCounter,
+    log: ReplicaLogger,
+}
+
+impl<PoolCanisterHttp: MutableCanisterHttpPool + Send + Sync + 'static>
+    CanisterHttpProcessor<PoolCanisterHttp>
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn build<
+        C: CanisterHttpPoolManager + 'static,
+        G: CanisterHttpGossip + 'static,
+        S: Fn(AdvertSendRequest<CanisterHttpArtifact>) + Send + 'static,
+        F: FnOnce() -> (C, G),
+    >(
+        send_advert: S,
+        setup: F,
+        time_source: Arc<SysTimeSource>,
+        canister_http_pool: Arc<RwLock<PoolCanisterHttp>>,
+        metrics_registry: MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> (
+        clients::CanisterHttpClient<PoolCanisterHttp>,
+        ArtifactProcessorManager<CanisterHttpArtifact>,
+    ) {
+        let (pool_manager, canister_http_gossip) = setup();
+        let client = Self {
+            canister_http_pool: canister_http_pool.clone(),
+            client: Box::new(pool_manager),
+            invalidated_artifacts: metrics_registry.int_counter(
+                "canister_http_invalidated_artifacts",
+                "The number of invalidated canister http artifacts",
+            ),
+            log,
+        };
+        let manager = ArtifactProcessorManager::new(
+            time_source,
+            metrics_registry,
+            BoxOrArcClient::BoxClient(Box::new(client)),
+            send_advert,
+        );
+        (
+            clients::CanisterHttpClient::new(canister_http_pool, canister_http_gossip),
+            manager,
+        )
+    }
+}
+
+impl<PoolCanisterHttp: MutableCanisterHttpPool + Send + Sync + 'static>
+    ArtifactProcessor<CanisterHttpArtifact> for CanisterHttpProcessor<PoolCanisterHttp>
+{
+    /// The method processes changes in the canister HTTP pool. Every node
+    /// adverts the shares it signed itself to all its peers, so the shares of
+    /// other nodes are not relayed.
+    fn process_changes(
+        &self,
+        _time_source: &dyn TimeSource,
+        artifacts: Vec<UnvalidatedArtifact<CanisterHttpResponseShare>>,
+    ) -> (
+        Vec<AdvertSendRequest<CanisterHttpArtifact>>,
+        ProcessingResult,
+    ) {
+        {
+            let mut canister_http_pool = self.canister_http_pool.write().unwrap();
+            for artifact in artifacts {
+                canister_http_pool.insert(artifact)
+            }
+        }
+
+        let mut adverts = Vec::new();
+        let change_set = {
+            let canister_http_pool = self.canister_http_pool.read().unwrap();
+            let change_set = self.client.on_state_change(&*canister_http_pool);
+            for change_action in change_set.iter() {
+                match change_action {
+                    CanisterHttpChangeAction::AddToValidated(share, _) => {
+                        adverts.push(CanisterHttpArtifact::message_to_advert_send_request(
+                            share,
+                            AdvertClass::Critical,
+                        ))
+                    }
+                    CanisterHttpChangeAction::HandleInvalid(id, reason) => {
+                        self.invalidated_artifacts.inc();
+                        warn!(
+                            self.log,
+                            "Invalid canister http share ({:?}): {:?}", reason, id
+                        );
+                    }
+                    _ => (),
+                }
+            }
+            change_set
+        };
+
+        let changed = if !change_set.is_empty() {
+            ProcessingResult::StateChanged
+        } else {
+            ProcessingResult::StateUnchanged
+        };
+
+        self.canister_http_pool
+            .write()
+            .unwrap()
+            .apply_changes(change_set);
+        (adverts, changed)
+    }
+}
@@ -13,6 +13,7 @@ use ic_test_utilities::{
     types::messages::SignedIngressBuilder,
 };
 use ic_types::batch::SelfValidatingPayload;
+use ic_types::canister_http::CanisterHttpPayload;
 use ic_types::{
     batch::{BatchPayload, IngressPayload, XNetPayload},
     consensus::{dkg, Block, BlockProposal, HasHeight, Payload, Rank},
@@ -58,7 +59,12 @@ fn prepare(pool: &mut ConsensusPoolImpl, num: usize) {
         block.payload = Payload::new(
             ic_crypto::crypto_hash,
             (
-                BatchPayload::new(ingress, xnet, self_validating),
+                BatchPayload::new(
+                    ingress,
+                    xnet,
+                    self_validating,
+                    CanisterHttpPayload::default(),
+                ),
                 dkg::Dealings::new_empty(parent.payload.as_ref().dkg_interval_start_height()),
                 None,
             )
@@ -0,0 +1,258 @@
+//! The canister HTTP pool holds the shares, i.e. the signed metadata of the
+//! responses to canister HTTP requests, that the nodes of the subnet exchange
+//! to agree on the responses. It also holds the responses this node received
+//! itself, so that it can include them in a block once enough nodes signed
+//! them.
+use crate::metrics::{PoolMetrics, POOL_TYPE_UNVALIDATED, POOL_TYPE_VALIDATED};
+use ic_interfaces::artifact_pool::{UnvalidatedArtifact, ValidatedArtifact};
+use ic_interfaces::canister_http::{
+    CanisterHttpChangeAction, CanisterHttpChangeSet, CanisterHttpPool, MutableCanisterHttpPool,
+};
+use ic_interfaces::gossip_pool::{CanisterHttpGossipPool, GossipPool};
+use ic_metrics::MetricsRegistry;
+use ic_types::artifact::CanisterHttpResponseId;
+use ic_types::canister_http::{CanisterHttpResponse, CanisterHttpResponseShare};
+use ic_types::crypto::CryptoHashOf;
+use ic_types::time::current_time;
+use ic_types::CountBytes;
+use std::collections::BTreeMap;
+
+const POOL_CANISTER_HTTP: &str = "canister_http";
+const POOL_CANISTER_HTTP_CONTENT: &str = "canister_http_content";
+
+/// Size of a share, excluding the variable length signature.
+const SHARE_SIZE_BYTES: usize = std::mem::size_of::<CanisterHttpResponseShare>();
+
+/// The in-memory pool of canister HTTP response shares and responses.
+pub struct CanisterHttpPoolImpl {
+    validated: BTreeMap<CanisterHttpResponseId, ValidatedArtifact<CanisterHttpResponseShare>>,
+    unvalidated: BTreeMap<CanisterHttpResponseId, UnvalidatedArtifact<CanisterHttpResponseShare>>,
+    content: BTreeMap<CryptoHashOf<CanisterHttpResponse>, CanisterHttpResponse>,
+    validated_metrics: PoolMetrics,
+    unvalidated_metrics: PoolMetrics,
+    content_metrics: PoolMetrics,
+}
+
+impl CanisterHttpPoolImpl {
+    pub fn new(metrics_registry: MetricsRegistry) -> Self {
+        Self {
+            validated: BTreeMap::new(),
+            unvalidated: BTreeMap::new(),
+            content: BTreeMap::new(),
+            validated_metrics: PoolMetrics::new(
+                metrics_registry.clone(),
+                POOL_CANISTER_HTTP,
+                POOL_TYPE_VALIDATED,
+            ),
+            unvalidated_metrics: PoolMetrics::new(
+                metrics_registry.clone(),
+                POOL_CANISTER_HTTP,
+                POOL_TYPE_UNVALIDATED,
+            ),
+            content_metrics: PoolMetrics::new(
+                metrics_registry,
+                POOL_CANISTER_HTTP_CONTENT,
+                POOL_TYPE_VALIDATED,
+            ),
+        }
+    }
+
+    fn insert_validated(&mut self, share: CanisterHttpResponseShare) {
+        self.validated_metrics.observe_insert(SHARE_SIZE_BYTES);
+        let replaced = self.validated.insert(
+            ic_crypto::crypto_hash(&share),
+            ValidatedArtifact {
+                msg: share,
+                timestamp: current_time(),
+            },
+        );
+        if replaced.is_some() {
+            self.validated_metrics.observe_duplicate(SHARE_SIZE_BYTES);
+        }
+    }
+
+    fn remove_unvalidated(&mut self, id: &CanisterHttpResponseId) {
+        if self.unvalidated.remove(id).is_some() {
+            self.unvalidated_metrics.observe_remove(SHARE_SIZE_BYTES);
+        }
+    }
+}
+
+impl CanisterHttpPool for CanisterHttpPoolImpl {
+    fn get_validated_shares(&self) -> Box<dyn Iterator<Item = &CanisterHttpResponseShare> + '_> {
+        Box::new(self.validated.values().map(|artifact| artifact.as_ref()))
+    }
+
+    fn get_unvalidated_shares(&self) -> Box<dyn Iterator<Item = &CanisterHttpResponseShare> + '_> {
+        Box::new(self.unvalidated.values().map(|artifact| &artifact.message))
+    }
+
+    fn get_response_content_by_hash(
+        &self,
+        hash: &CryptoHashOf<CanisterHttpResponse>,
+    ) -> Option<CanisterHttpResponse> {
+        self.content.get(hash).cloned()
+    }
+}
+
+impl MutableCanisterHttpPool for CanisterHttpPoolImpl {
+    fn insert(&mut self, artifact: UnvalidatedArtifact<CanisterHttpResponseShare>) {
+        self.unvalidated_metrics.observe_insert(SHARE_SIZE_BYTES);
+        let replaced = self
+            .unvalidated
+            .insert(ic_crypto::crypto_hash(&artifact.message), artifact);
+        if replaced.is_some() {
+            self.unvalidated_metrics.observe_duplicate(SHARE_SIZE_BYTES);
+        }
+    }
+
+    /// Applies the provided change set atomically.
+    ///
+    /// # Panics
+    ///
+    /// It panics if we pass a hash for an artifact to be moved into the
+    /// validated section, but it cannot be found in the unvalidated
+    /// section.
+    fn apply_changes(&mut self, change_set: CanisterHttpChangeSet) {
+        for action in change_set {
+            match action {
+                CanisterHttpChangeAction::AddToValidated(share, content) => {
+                    let size = content.count_bytes();
+                    self.content_metrics.observe_insert(size);
+                    if let Some(replaced) = self
+                        .content
+                        .insert(share.content.content_hash.clone(), content)
+                    {
+                        self.content_metrics
+                            .observe_duplicate(replaced.count_bytes());
+                    }
+                    self.insert_validated(share);
+                }
+                CanisterHttpChangeAction::MoveToValidated(id) => {
+                    let artifact = self
+                        .unvalidated
+                        .remove(&id)
+                        .expect("Unvalidated artifact was not found.");
+                    self.unvalidated_metrics.observe_remove(SHARE_SIZE_BYTES);
+                    self.insert_validated(artifact.message);
+                }
+                CanisterHttpChangeAction::RemoveValidated(id) => {
+                    if self.validated.remove(&id).is_some() {
+                        self.validated_metrics.observe_remove(SHARE_SIZE_BYTES);
+                    }
+                }
+                CanisterHttpChangeAction::RemoveUnvalidated(id) => self.remove_unvalidated(&id),
+                CanisterHttpChangeAction::RemoveContent(hash) => {
+                    if let Some(removed) = self.content.remove(&hash) {
+                        self.content_metrics.observe_remove(removed.count_bytes());
+                    }
+                }
+                CanisterHttpChangeAction::HandleInvalid(id, _) => self.remove_unvalidated(&id),
+            }
+        }
+    }
+}
+
+impl GossipPool<CanisterHttpResponseShare, CanisterHttpChangeSet> for CanisterHttpPoolImpl {
+    type MessageId = CanisterHttpResponseId;
+    type Filter = ();
+
+    fn contains(&self, id: &Self::MessageId) -> bool {
+        self.unvalidated.contains_key(id) || self.validated.contains_key(id)
+    }
+
+    fn get_validated_by_identifier(
+        &self,
+        id: &Self::MessageId,
+    ) -> Option<CanisterHttpResponseShare> {
+        self.validated
+            .get(id)
+            .map(|artifact| artifact.as_ref())
+            .cloned()
+    }
+
+    fn get_all_validated_by_filter(
+        &self,
+        _filter: Self::Filter,
+    ) -> Box<dyn Iterator<Item = CanisterHttpResponseShare>> {
+        unimplemented!()
+    }
+}
+
+impl CanisterHttpGossipPool for CanisterHttpPoolImpl {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ic_test_utilities::{consensus::fake::FakeSigner, mock_time, types::ids::node_test_id};
+    use ic_types::{
+        canister_http::{CanisterHttpResponseContent, CanisterHttpResponseMetadata},
+        consensus::BasicSignature,
+        crypto::Signed,
+        messages::CallbackId,
+        RegistryVersion,
+    };
+
+    fn make_response(id: u64) -> CanisterHttpResponse {
+        CanisterHttpResponse {
+            id: CallbackId::from(id),
+            timeout: mock_time(),
+            content: CanisterHttpResponseContent::Success(vec![1, 2, 3]),
+        }
+    }
+
+    fn make_share(response: &CanisterHttpResponse, node: u64) -> CanisterHttpResponseShare {
+        Signed {
+            content: CanisterHttpResponseMetadata {
+                id: response.id,
+                timeout: response.timeout,
+                content_hash: ic_crypto::crypto_hash(response),
+                registry_version: RegistryVersion::from(1),
+            },
+            signature: BasicSignature::fake(node_test_id(node)),
+        }
+    }
+
+    #[test]
+    fn test_canister_http_pool_add_move_and_remove() {
+        let mut pool = CanisterHttpPoolImpl::new(MetricsRegistry::new());
+        let response = make_response(1);
+        let own_share = make_share(&response, 0);
+        let peer_share = make_share(&response, 1);
+        let peer_share_id = ic_crypto::crypto_hash(&peer_share);
+
+        pool.apply_changes(vec![CanisterHttpChangeAction::AddToValidated(
+            own_share.clone(),
+            response.clone(),
+        )]);
+        pool.insert(UnvalidatedArtifact {
+            message: peer_share.clone(),
+            peer_id: node_test_id(1),
+            timestamp: mock_time(),
+        });
+        assert_eq!(pool.get_validated_shares().count(), 1);
+        assert_eq!(pool.get_unvalidated_shares().count(), 1);
+        assert_eq!(
+            pool.get_response_content_by_hash(&own_share.content.content_hash),
+            Some(response)
+        );
+
+        pool.apply_changes(vec![CanisterHttpChangeAction::MoveToValidated(
+            peer_share_id.clone(),
+        )]);
+        assert_eq!(pool.get_validated_shares().count(), 2);
+        assert_eq!(pool.get_unvalidated_shares().count(), 0);
+        assert!(pool.contains(&peer_share_id));
+
+        pool.apply_changes(vec![
+            CanisterHttpChangeAction::RemoveValidated(ic_crypto::crypto_hash(&own_share)),
+            CanisterHttpChangeAction::RemoveValidated(peer_share_id),
+            CanisterHttpChangeAction::RemoveContent(own_share.content.content_hash.clone()),
+        ]);
+        assert_eq!(pool.get_validated_shares().count(), 0);
+        assert_eq!(
+            pool.get_response_content_by_hash(&own_share.content.content_hash),
+            None
+        );
+    }
+}
@@ -0,0 +1,474 @@
+//! The Bitcoin functionality of a subnet: maintains the UTXO set of the
+//! tracked Bitcoin network in the [`BitcoinState`] and answers the Bitcoin
+//! calls of the management canister.
+pub mod test_utils;
+
+use bitcoin::{
+    consensus::{deserialize, serialize},
+    Address, Block, BlockHash, OutPoint, Transaction, TxOut,
+};
+use ic_replicated_state::bitcoin_state::{
+    address_of, BitcoinState, BlockTree, OutgoingTransaction, UtxoSet, MAX_OUTGOING_TRANSACTIONS,
+};
+use ic_types::{
+    ic00::{BitcoinGetUtxosResponse, BitcoinOutPoint, BitcoinUtxo},
+    Time,
+};
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
+
+/// Transactions submitted through `bitcoin_send_transaction` are dropped from
+/// the state once they are older than this.
+pub const OUTGOING_TRANSACTION_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Errors that occur when adding a block to the state.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InsertBlockError {
+    /// The block cannot be decoded.
+    Malformed(String),
+    /// The parent of the block is not part of the unstable blocks.
+    UnknownParent(BlockHash),
+    /// The block is already part of the unstable blocks.
+    AlreadyKnown(BlockHash),
+}
+
+/// Errors returned by the Bitcoin calls of the management canister.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BitcoinCallError {
+    /// The address cannot be parsed or belongs to another network.
+    MalformedAddress(String),
+    /// The transaction cannot be decoded.
+    MalformedTransaction(String),
+    /// Too many transactions are waiting to be sent to the Bitcoin network.
+    QueueFull,
+}
+
+impl std::fmt::Display for BitcoinCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedAddress(address) => write!(f, "Malformed address: {}", address),
+            Self::MalformedTransaction(err) => write!(f, "Malformed transaction: {}", err),
+            Self::QueueFull => write!(
+                f,
+                "Too many transactions waiting to be sent, at most {} are allowed",
+                MAX_OUTGOING_TRANSACTIONS
+            ),
+        }
+    }
+}
+
+/// Adds the consensus-encoded `blocks` (parents before children) to the
+/// unstable blocks of the state and moves the blocks that became stable into
+/// the UTXO set. Blocks that cannot be added are skipped.
+pub fn process_blocks(state: &mut BitcoinState, blocks: &[Vec<u8>]) -> Vec<InsertBlockError> {
+    let mut errors = vec![];
+    for block in blocks {
+        let result = deserialize::<Block>(block)
+            .map_err(|err| InsertBlockError::Malformed(err.to_string()))
+            .and_then(|block| insert_block(state, block));
+        if let Err(err) = result {
+            errors.push(err);
+        }
+    }
+    errors
+}
+
+/// Adds `block` to the unstable blocks of the state and moves the blocks that
+/// became stable into the UTXO set.
+pub fn insert_block(state: &mut BitcoinState, block: Block) -> Result<(), InsertBlockError> {
+    let hash = block.block_hash();
+    if state.unstable_blocks.find_mut(&hash).is_some() {
+        return Err(InsertBlockError::AlreadyKnown(hash));
+    }
+    let parent = state
+        .unstable_blocks
+        .find_mut(&block.header.prev_blockhash)
+        .ok_or(InsertBlockError::UnknownParent(block.header.prev_blockhash))?;
+    parent.children.push(BlockTree::new(block));
+
+    while let Some(stable) = pop_stable_block(state) {
+        state.stable_height += 1;
+        apply_block(&mut state.utxos, &stable, state.stable_height);
+    }
+    Ok(())
+}
+
+// A child of the root becomes stable once its subtree is at least
+// `stability_threshold` blocks deep and at least `stability_threshold` blocks
+// deeper than the subtree of any of its siblings. In that case the child
+// becomes the new root, its siblings are dropped, and its block is returned.
+fn pop_stable_block(state: &mut BitcoinState) -> Option<Block> {
+    let threshold = state.stability_threshold;
+    let tree = &mut state.unstable_blocks;
+    let depths: Vec<u32> = tree.children.iter().map(|child| child.depth()).collect();
+    let (index, depth) = depths
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(index, depth)| (*depth, std::cmp::Reverse(*index)))?;
+    let stable = depth >= threshold
+        && depths
+            .iter()
+            .enumerate()
+            .all(|(i, d)| i == index || depth - d >= threshold);
+    if !stable {
+        return None;
+    }
+    let child = tree.children.swap_remove(index);
+    *tree = child;
+    Some(tree.root.clone())
+}
+
+// Removes the outputs spent by `block` from the UTXO set and adds the outputs
+// it creates.
+fn apply_block(utxos: &mut UtxoSet, block: &Block, height: u32) {
+    for tx in block.txdata.iter() {
+        for outpoint in spent_outputs(tx) {
+            utxos.remove(outpoint);
+        }
+        for (outpoint, output) in created_outputs(tx) {
+            utxos.insert(outpoint, output.clone(), height);
+        }
+    }
+}
+
+// Returns the outputs spent by `tx`.
+fn spent_outputs(tx: &Transaction) -> impl Iterator<Item = &OutPoint> {
+    let inputs = if tx.is_coin_base() {
+        &[][..]
+    } else {
+        &tx.input[..]
+    };
+    inputs.iter().map(|input| &input.previous_output)
+}
+
+// Returns the spendable outputs created by `tx`.
+fn created_outputs(tx: &Transaction) -> impl Iterator<Item = (OutPoint, &TxOut)> {
+    let txid = tx.txid();
+    tx.output
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| !output.script_pubkey.is_provably_unspendable())
+        .map(move |(vout, output)| (OutPoint::new(txid, vout as u32), output))
+}
+
+/// Returns the hash of the latest stable block, i.e. the anchor the Bitcoin
+/// adapter is asked for successors of.
+pub fn anchor_hash(state: &BitcoinState) -> BlockHash {
+    state.unstable_blocks.root.block_hash()
+}
+
+/// Returns the height of the tip of the main chain.
+pub fn tip_height(state: &BitcoinState) -> u32 {
+    state.stable_height + state.unstable_blocks.main_chain().len() as u32 - 1
+}
+
+/// Returns the outputs that belong to `address` and have at least
+/// `min_confirmations` confirmations on the main chain, most recent first.
+pub fn get_utxos(
+    state: &BitcoinState,
+    address: &str,
+    min_confirmations: u32,
+) -> Result<BitcoinGetUtxosResponse, BitcoinCallError> {
+    let address = normalize_address(state, address)?;
+    let main_chain = state.unstable_blocks.main_chain();
+    let tip_height = state.stable_height + main_chain.len() as u32 - 1;
+    let confirmed = |height: u32| tip_height - height + 1 >= min_confirmations;
+
+    let mut utxos: BTreeMap<OutPoint, (TxOut, u32)> = state
+        .utxos
+        .get_by_address(&address)
+        .filter(|(_, (_, height))| confirmed(*height))
+        .collect();
+
+    // The outputs of the root are already part of the UTXO set.
+    for (block, height) in main_chain.iter().zip(state.stable_height..).skip(1) {
+        if !confirmed(height) {
+            break;
+        }
+        for tx in block.txdata.iter() {
+            for outpoint in spent_outputs(tx) {
+                utxos.remove(outpoint);
+            }
+            for (outpoint, output) in created_outputs(tx) {
+                if address_of(&output.script_pubkey, state.network).as_ref() == Some(&address) {
+                    utxos.insert(outpoint, (output.clone(), height));
+                }
+            }
+        }
+    }
+
+    let mut utxos: Vec<BitcoinUtxo> = utxos
+        .into_iter()
+        .map(|(outpoint, (output, height))| BitcoinUtxo {
+            outpoint: BitcoinOutPoint {
+                txid: serialize(&outpoint.txid),
+                vout: outpoint.vout,
+            },
+            value: output.value,
+            height,
+        })
+        .collect();
+    utxos.sort_by(|a, b| b.height.cmp(&a.height).then(a.outpoint.cmp(&b.outpoint)));
+
+    Ok(BitcoinGetUtxosResponse { utxos, tip_height })
+}
+
+/// Returns the sum of the outputs that belong to `address` and have at least
+/// `min_confirmations` confirmations on the main chain.
+pub fn get_balance(
+    state: &BitcoinState,
+    address: &str,
+    min_confirmations: u32,
+) -> Result<u64, BitcoinCallError> {
+    Ok(get_utxos(state, address, min_confirmations)?
+        .utxos
+        .iter()
+        .map(|utxo| utxo.value)
+        .sum())
+}
+
+/// Queues the consensus-encoded `transaction` for being sent to the Bitcoin
+/// network. At most [`MAX_OUTGOING_TRANSACTIONS`] transactions can be queued
+/// at the same time.
+pub fn send_transaction(
+    state: &mut BitcoinState,
+    transaction: Vec<u8>,
+    time: Time,
+) -> Result<(), BitcoinCallError> {
+    deserialize::<Transaction>(&transaction)
+        .map_err(|err| BitcoinCallError::MalformedTransaction(err.to_string()))?;
+    if state.outgoing_transactions.len() >= MAX_OUTGOING_TRANSACTIONS {
+        return Err(BitcoinCallError::QueueFull);
+    }
+    state
+        .outgoing_transactions
+        .push(OutgoingTransaction { transaction, time });
+    Ok(())
+}
+
+/// Drops the outgoing transactions that are older than
+/// [`OUTGOING_TRANSACTION_TTL`].
+pub fn remove_expired_transactions(state: &mut BitcoinState, time: Time) {
+    state
+        .outgoing_transactions
+        .retain(|tx| tx.time + OUTGOING_TRANSACTION_TTL > time);
+}
+
+// Parses `address` and renders it the way the UTXO set indexes it, which
+// makes e.g. testnet addresses usable on regtest.
+fn normalize_address(state: &BitcoinState, address: &str) -> Result<String, BitcoinCallError> {
+    use bitcoin::Network;
+    let parsed = Address::from_str(address)
+        .map_err(|_| BitcoinCallError::MalformedAddress(address.to_string()))?;
+    let compatible = match (parsed.network, state.network) {
+        (Network::Bitcoin, Network::Bitcoin) => true,
+        (Network::Bitcoin, _) | (_, Network::Bitcoin) => false,
+        _ => true,
+    };
+    if !compatible {
+        return Err(BitcoinCallError::MalformedAddress(address.to_string()));
+    }
+    Ok(Address {
+        network: state.network,
+        payload: parsed.payload,
+    }
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{random_address, BlockBuilder, TransactionBuilder};
+    use bitcoin::{blockdata::constants::genesis_block, Network};
+
+    fn regtest_state(stability_threshold: u32) -> BitcoinState {
+        let mut state = BitcoinState::new(Network::Regtest);
+        state.stability_threshold = stability_threshold;
+        state
+    }
+
+    fn insert(state: &mut BitcoinState, block: &Block) {
+        assert_eq!(process_blocks(state, &[serialize(block)]), vec![]);
+    }
+
+    #[test]
+    fn blocks_become_stable_after_threshold() {
+        let mut state = regtest_state(2);
+        let address = random_address(1);
+        let genesis = genesis_block(Network::Regtest);
+
+        let block_1 = BlockBuilder::child_of(&genesis)
+            .with_transaction(
+                TransactionBuilder::coinbase()
+                    .with_output(&address, 1000)
+                    .build(),
+            )
+            .build();
+        insert(&mut state, &block_1);
+        assert_eq!(state.stable_height, 0);
+        assert!(state.utxos.is_empty());
+
+        let block_2 = BlockBuilder::child_of(&block_1).build();
+        insert(&mut state, &block_2);
+        assert_eq!(state.stable_height, 1);
+        assert_eq!(state.unstable_blocks.root, block_1);
+        assert_eq!(
+            state
+                .utxos
+                .get_by_address(&address.to_string())
+                .map(|(_, (output, _))| output.value)
+                .collect::<Vec<_>>(),
+            vec![1000]
+        );
+    }
+
+    #[test]
+    fn forks_delay_stability() {
+        let mut state = regtest_state(2);
+        let genesis = genesis_block(Network::Regtest);
+
+        let block_1 = BlockBuilder::child_of(&genesis).build();
+        let fork_1 = BlockBuilder::child_of(&genesis).build();
+        insert(&mut state, &block_1);
+        insert(&mut state, &fork_1);
+
+        // Both branches have the same depth, nothing is stable.
+        let block_2 = BlockBuilder::child_of(&block_1).build();
+        let fork_2 = BlockBuilder::child_of(&fork_1).build();
+        insert(&mut state, &block_2);
+        insert(&mut state, &fork_2);
+        assert_eq!(state.stable_height, 0);
+
+        // The first branch is two blocks deeper than the fork.
+        let block_3 = BlockBuilder::child_of(&block_2).build();
+        let block_4 = BlockBuilder::child_of(&block_3).build();
+        insert(&mut state, &block_3);
+        assert_eq!(state.stable_height, 0);
+        insert(&mut state, &block_4);
+        assert_eq!(state.stable_height, 3);
+        assert_eq!(state.unstable_blocks.root, block_3);
+        assert_eq!(state.unstable_blocks.children.len(), 1);
+    }
+
+    #[test]
+    fn rejects_unknown_and_duplicate_blocks() {
+        let mut state = regtest_state(2);
+        let genesis = genesis_block(Network::Regtest);
+        let block_1 = BlockBuilder::child_of(&genesis).build();
+        let block_2 = BlockBuilder::child_of(&block_1).build();
+
+        assert_eq!(
+            process_blocks(&mut state, &[serialize(&block_2)]),
+            vec![InsertBlockError::UnknownParent(block_1.block_hash())]
+        );
+        insert(&mut state, &block_1);
+        assert_eq!(
+            process_blocks(&mut state, &[serialize(&block_1)]),
+            vec![InsertBlockError::AlreadyKnown(block_1.block_hash())]
+        );
+        assert_eq!(
+            process_blocks(&mut state, &[vec![1, 2, 3]]).len(),
+            1,
+            "malformed blocks are reported"
+        );
+    }
+
+    #[test]
+    fn get_utxos_respects_min_confirmations() {
+        let mut state = regtest_state(2);
+        let address_1 = random_address(1);
+        let address_2 = random_address(2);
+        let genesis = genesis_block(Network::Regtest);
+
+        let coinbase = TransactionBuilder::coinbase()
+            .with_output(&address_1, 1000)
+            .build();
+        let block_1 = BlockBuilder::child_of(&genesis)
+            .with_transaction(coinbase.clone())
+            .build();
+        let spend = TransactionBuilder::new()
+            .with_input(OutPoint::new(coinbase.txid(), 0))
+            .with_output(&address_2, 600)
+            .with_output(&address_1, 400)
+            .build();
+        let block_2 = BlockBuilder::child_of(&block_1)
+            .with_transaction(spend)
+            .build();
+        insert(&mut state, &block_1);
+        insert(&mut state, &block_2);
+
+        // Block 1 has two confirmations, block 2 has one.
+        assert_eq!(tip_height(&state), 2);
+        assert_eq!(get_balance(&state, &address_1.to_string(), 0), Ok(400));
+        assert_eq!(get_balance(&state, &address_2.to_string(), 1), Ok(600));
+        assert_eq!(get_balance(&state, &address_1.to_string(), 2), Ok(1000));
+        assert_eq!(get_balance(&state, &address_2.to_string(), 2), Ok(0));
+        assert_eq!(get_balance(&state, &address_1.to_string(), 3), Ok(0));
+
+        let response = get_utxos(&state, &address_1.to_string(), 1).unwrap();
+        assert_eq!(response.tip_height, 2);
+        assert_eq!(response.utxos.len(), 1);
+        assert_eq!(response.utxos[0].value, 400);
+        assert_eq!(response.utxos[0].height, 2);
+    }
+
+    #[test]
+    fn get_utxos_rejects_malformed_addresses() {
+        let state = regtest_state(2);
+        assert!(matches!(
+            get_utxos(&state, "not an address", 0),
+            Err(BitcoinCallError::MalformedAddress(_))
+        ));
+        let mainnet_address = Address {
+            network: Network::Bitcoin,
+            payload: random_address(1).payload,
+        };
+        assert!(matches!(
+            get_balance(&state, &mainnet_address.to_string(), 0),
+            Err(BitcoinCallError::MalformedAddress(_))
+        ));
+    }
+
+    #[test]
+    fn outgoing_transactions_expire() {
+        let mut state = regtest_state(2);
+        let time = Time::from_nanos_since_unix_epoch(1_000_000_000);
+        let tx = TransactionBuilder::coinbase()
+            .with_output(&random_address(1), 1)
+            .build();
+
+        assert!(matches!(
+            send_transaction(&mut state, vec![1, 2, 3], time),
+            Err(BitcoinCallError::MalformedTransaction(_))
+        ));
+        send_transaction(&mut state, serialize(&tx), time).unwrap();
+        assert_eq!(state.outgoing_transactions.len(), 1);
+
+        remove_expired_transactions(&mut state, time + OUTGOING_TRANSACTION_TTL);
+        assert!(state.outgoing_transactions.is_empty());
+    }
+
+    #[test]
+    fn outgoing_transactions_are_capped() {
+        let mut state = regtest_state(2);
+        let time = Time::from_nanos_since_unix_epoch(1_000_000_000);
+        let tx = serialize(
+            &TransactionBuilder::coinbase()
+                .with_output(&random_address(1), 1)
+                .build(),
+        );
+
+        for _ in 0..MAX_OUTGOING_TRANSACTIONS {
+            send_transaction(&mut state, tx.clone(), time).unwrap();
+        }
+        assert_eq!(
+            send_transaction(&mut state, tx.clone(), time),
+            Err(BitcoinCallError::QueueFull)
+        );
+
+        // Expired transactions make room for new ones.
+        let later = time + OUTGOING_TRANSACTION_TTL;
+        remove_expired_transactions(&mut state, later);
+        send_transaction(&mut state, tx, later).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+//! Helpers for building regtest blocks and transactions in tests.
+use bitcoin::{
+    blockdata::{opcodes, script::Builder},
+    Address, Block, BlockHeader, Network, OutPoint, Script, Transaction, TxIn, TxOut,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// The compact target of regtest blocks, which about every second hash meets.
+const REGTEST_BITS: u32 = 0x207fffff;
+
+// Makes the coinbase transactions, and hence the blocks, of the builders unique.
+static COINBASE_NONCE: AtomicU32 = AtomicU32::new(0);
+
+/// Returns a regtest P2SH address that is unique for every `seed`.
+pub fn random_address(seed: i64) -> Address {
+    Address::p2sh(
+        &Builder::new().push_int(seed).into_script(),
+        Network::Regtest,
+    )
+}
+
+/// Builds a regtest block on top of a given block.
+pub struct BlockBuilder {
+    prev: BlockHeader,
+    transactions: Vec<Transaction>,
+}
+
+impl BlockBuilder {
+    pub fn child_of(prev: &Block) -> Self {
+        Self {
+            prev: prev.header,
+            transactions: vec![],
+        }
+    }
+
+    /// Adds a transaction to the block. Unless the first transaction added is
+    /// a coinbase transaction, one that pays to no address is prepended.
+    pub fn with_transaction(mut self, transaction: Transaction) -> Self {
+        self.transactions.push(transaction);
+        self
+    }
+
+    /// Builds the block and mines it, i.e. finds a nonce that satisfies the
+    /// regtest proof of work.
+    pub fn build(mut self) -> Block {
+        if !self
+            .transactions
+            .first()
+            .map_or(false, |tx| tx.is_coin_base())
+        {
+            self.transactions
+                .insert(0, TransactionBuilder::coinbase().build());
+        }
+        let mut block = Block {
+            header: BlockHeader {
+                version: 1,
+                prev_blockhash: self.prev.block_hash(),
+                merkle_root: Default::default(),
+                time: self.prev.time + 1,
+                bits: REGTEST_BITS,
+                nonce: 0,
+            },
+            txdata: self.transactions,
+        };
+        block.header.merkle_root = block.merkle_root();
+        let target = block.header.target();
+        while block.header.validate_pow(&target).is_err() {
+            block.header.nonce += 1;
+        }
+        block
+    }
+}
+
+/// Builds a transaction.
+pub struct TransactionBuilder {
+    inputs: Vec<TxIn>,
+    outputs: Vec<TxOut>,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self {
+            inputs: vec![],
+            outputs: vec![],
+        }
+    }
+
+    /// Starts a coinbase transaction.
+    pub fn coinbase() -> Self {
+        let nonce = COINBASE_NONCE.fetch_add(1, Ordering::SeqCst);
+        Self {
+            inputs: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(nonce as i64).into_script(),
+                sequence: 0xffffffff,
+                witness: vec![],
+            }],
+            outputs: vec![],
+        }
+    }
+
+    pub fn with_input(mut self, previous_output: OutPoint) -> Self {
+        self.inputs.push(TxIn {
+            previous_output,
+            script_sig: Script::new(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        });
+        self
+    }
+
+    pub fn with_output(mut self, address: &Address, value: u64) -> Self {
+        self.outputs.push(TxOut {
+            value,
+            script_pubkey: address.script_pubkey(),
+        });
+        self
+    }
+
+    pub fn build(mut self) -> Transaction {
+        if self.outputs.is_empty() {
+            // An unspendable output, so that the transaction is well-formed.
+            self.outputs.push(TxOut {
+                value: 0,
+                script_pubkey: Builder::new()
+                    .push_opcode(opcodes::all::OP_RETURN)
+                    .into_script(),
+            });
+        }
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: self.inputs,
+            output: self.outputs,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! A [`BitcoinAdapterClient`] that serves blocks read from a file instead of
+//! connecting to the Bitcoin network, which allows running the Bitcoin
+//! integration against e.g. pre-generated regtest blocks.
+use bitcoin::{
+    consensus::{deserialize, serialize},
+    hashes::hex::FromHex,
+    Block,
+};
+use ic_interfaces::bitcoin_adapter_client::{
+    BitcoinAdapterClient, BitcoinAdapterClientError, GetSuccessorsRequest, GetSuccessorsResponse,
+};
+use std::{
+    collections::BTreeSet,
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::Mutex,
+};
+
+struct FileBlock {
+    // The consensus-encoded hash of the block and of its parent.
+    hash: Vec<u8>,
+    prev_hash: Vec<u8>,
+    // The consensus-encoded block.
+    bytes: Vec<u8>,
+}
+
+/// Serves the blocks of a file and records the transactions it is asked to
+/// send, without sending them anywhere.
+pub struct BitcoinBlockFileAdapterClient {
+    blocks: Vec<FileBlock>,
+    sent_transactions: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BitcoinBlockFileAdapterClient {
+    /// Reads the blocks from `path`, which contains one hex-encoded block per
+    /// line, as printed by `bitcoin-cli getblock <hash> 0`. Empty lines are
+    /// ignored.
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        let blocks = std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                Vec::<u8>::from_hex(line).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+            })
+            .collect::<Result<_, _>>()?;
+        Self::from_blocks(blocks)
+    }
+
+    /// Serves the given consensus-encoded blocks.
+    pub fn from_blocks(blocks: Vec<Vec<u8>>) -> std::io::Result<Self> {
+        let blocks = blocks
+            .into_iter()
+            .map(|bytes| {
+                let block: Block =
+                    deserialize(&bytes).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+                Ok(FileBlock {
+                    hash: serialize(&block.block_hash()),
+                    prev_hash: serialize(&block.header.prev_blockhash),
+                    bytes,
+                })
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok(Self {
+            blocks,
+            sent_transactions: Mutex::new(vec![]),
+        })
+    }
+
+    /// Returns the transactions passed to `send_transaction()` so far.
+    pub fn sent_transactions(&self) -> Vec<Vec<u8>> {
+        self.sent_transactions.lock().unwrap().clone()
+    }
+}
+
+impl BitcoinAdapterClient for BitcoinBlockFileAdapterClient {
+    /// Returns the blocks of the file that extend the anchor or a processed
+    /// block and that are not processed yet, in file order.
+    fn get_successors(
+        &self,
+        request: GetSuccessorsRequest,
+    ) -> Result<GetSuccessorsResponse, BitcoinAdapterClientError> {
+        let mut known: BTreeSet<Vec<u8>> = request.processed_block_hashes.into_iter().collect();
+        known.insert(request.anchor);
+
+        let mut response = GetSuccessorsResponse::default();
+        for block in self.blocks.iter() {
+            if !known.contains(&block.hash) && known.contains(&block.prev_hash) {
+                known.insert(block.hash.clone());
+                response.blocks.push(block.bytes.clone());
+            }
+        }
+        Ok(response)
+    }
+
+    fn send_transaction(&self, transaction: Vec<u8>) -> Result<(), BitcoinAdapterClientError> {
+        self.sent_transactions.lock().unwrap().push(transaction);
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+mod file_adapter_client;
 mod metrics;
 mod payload_builder;
+pub use file_adapter_client::BitcoinBlockFileAdapterClient;
 pub use payload_builder::BitcoinPayloadBuilder;
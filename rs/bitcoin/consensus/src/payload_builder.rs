@@ -1,18 +1,36 @@
 use crate::metrics::BitcoinPayloadBuilderMetrics;
+use bitcoin::{consensus::deserialize, consensus::serialize, Block, BlockHash, Network};
+use ic_btc_validation::{validate_header, HeaderStore, StoredHeader};
 use ic_interfaces::{
-    self_validating_payload::{SelfValidatingPayloadBuilder, SelfValidatingPayloadValidationError},
+    bitcoin_adapter_client::{
+        BitcoinAdapterClient, BitcoinAdapterClientError, GetSuccessorsRequest,
+    },
+    self_validating_payload::{
+        InvalidSelfValidatingPayload, SelfValidatingPayloadBuilder,
+        SelfValidatingPayloadValidationError, SelfValidatingTransientValidationError,
+    },
     state_manager::{StateManager, StateManagerError},
+    validation::ValidationError,
 };
-use ic_logger::{log, ReplicaLogger};
+use ic_logger::{log, warn, ReplicaLogger};
 use ic_metrics::{MetricsRegistry, Timer};
-use ic_replicated_state::ReplicatedState;
+use ic_replicated_state::{
+    bitcoin_state::{BitcoinState, BlockTree},
+    ReplicatedState,
+};
 use ic_types::{
     batch::{SelfValidatingPayload, ValidationContext},
     CountBytes, Height, NumBytes,
 };
-use std::sync::Arc;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
 use thiserror::Error;
 
+#[cfg(test)]
+mod tests;
+
 const BUILD_PAYLOAD_STATUS_SUCCESS: &str = "success";
 const VALIDATION_STATUS_VALID: &str = "valid";
 
@@ -21,6 +39,8 @@ const VALIDATION_STATUS_VALID: &str = "valid";
 enum GetPayloadError {
     #[error("Error retrieving state at height {0}: {1}")]
     GetStateFailed(Height, StateManagerError),
+    #[error("Error retrieving blocks from the Bitcoin adapter: {0:?}")]
+    GetSuccessorsFailed(BitcoinAdapterClientError),
 }
 
 impl GetPayloadError {
@@ -28,6 +48,7 @@ impl GetPayloadError {
     fn log_level(&self) -> slog::Level {
         match self {
             Self::GetStateFailed(..) => slog::Level::Warning,
+            Self::GetSuccessorsFailed(..) => slog::Level::Warning,
         }
     }
 
@@ -35,12 +56,24 @@ impl GetPayloadError {
     fn to_label_value(&self) -> &str {
         match self {
             Self::GetStateFailed(..) => "GetStateFailed",
+            Self::GetSuccessorsFailed(..) => "GetSuccessorsFailed",
         }
     }
 }
 
+/// Builds and validates the Bitcoin blocks of `SelfValidatingPayloads`.
+///
+/// The builder asks the Bitcoin adapter for the blocks that extend the blocks
+/// the subnet knows about (those in the state at the certified height plus
+/// those in past payloads) and includes the ones with a valid header. It also
+/// hands every transaction submitted through `bitcoin_send_transaction` over
+/// to the adapter, once.
 pub struct BitcoinPayloadBuilder {
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    adapter_client: Arc<dyn BitcoinAdapterClient>,
+    // Outgoing transactions that were handed to the adapter and are still
+    // part of the state.
+    sent_transactions: Mutex<BTreeSet<Vec<u8>>>,
     metrics: Arc<BitcoinPayloadBuilderMetrics>,
     log: ReplicaLogger,
 }
@@ -48,11 +81,14 @@ pub struct BitcoinPayloadBuilder {
 impl BitcoinPayloadBuilder {
     pub fn new(
         state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        adapter_client: Arc<dyn BitcoinAdapterClient>,
         metrics_registry: &MetricsRegistry,
         log: ReplicaLogger,
     ) -> Self {
         Self {
             state_manager,
+            adapter_client,
+            sent_transactions: Mutex::new(BTreeSet::new()),
             metrics: Arc::new(BitcoinPayloadBuilderMetrics::new(metrics_registry)),
             log,
         }
@@ -61,20 +97,170 @@ impl BitcoinPayloadBuilder {
     fn get_self_validating_payload_impl(
         &self,
         validation_context: &ValidationContext,
-        _past_payloads: &[&SelfValidatingPayload],
-        _byte_limit: NumBytes,
+        past_payloads: &[&SelfValidatingPayload],
+        byte_limit: NumBytes,
     ) -> Result<SelfValidatingPayload, GetPayloadError> {
         // Retrieve the `ReplicatedState` required by `validation_context`.
-        let _state = self
+        let state = self
             .state_manager
             .get_state_at(validation_context.certified_height)
             .map_err(|e| GetPayloadError::GetStateFailed(validation_context.certified_height, e))?
             .take();
+        let bitcoin = match state.bitcoin.as_ref() {
+            Some(bitcoin) => bitcoin,
+            None => return Ok(SelfValidatingPayload::default()),
+        };
+
+        self.send_transactions(bitcoin);
+
+        let mut headers = BlockHeaders::new(bitcoin, past_payloads);
+        let request = GetSuccessorsRequest {
+            anchor: serialize(&headers.initial_hash),
+            processed_block_hashes: headers
+                .headers
+                .keys()
+                .filter(|hash| **hash != headers.initial_hash)
+                .map(serialize)
+                .collect(),
+        };
+        let response = self
+            .adapter_client
+            .get_successors(request)
+            .map_err(GetPayloadError::GetSuccessorsFailed)?;
+
+        let mut payload = SelfValidatingPayload::default();
+        let mut size = 0;
+        for block in response.blocks {
+            if size + block.len() > byte_limit.get() as usize {
+                break;
+            }
+            // The children of an invalid block are rejected as well, as
+            // their parent is unknown.
+            match headers.insert(&block) {
+                Ok(()) => {
+                    size += block.len();
+                    payload.bitcoin_blocks.push(block);
+                }
+                Err(err) => warn!(self.log, "Ignoring Bitcoin block: {:?}", err),
+            }
+        }
+
+        Ok(payload)
+    }
+
+    // Hands the outgoing transactions that were not sent yet to the adapter.
+    fn send_transactions(&self, bitcoin: &BitcoinState) {
+        let mut sent = self.sent_transactions.lock().unwrap();
+        let outgoing: BTreeSet<&Vec<u8>> = bitcoin
+            .outgoing_transactions
+            .iter()
+            .map(|tx| &tx.transaction)
+            .collect();
+        sent.retain(|tx| outgoing.contains(tx));
+
+        for transaction in outgoing {
+            if sent.contains(transaction) {
+                continue;
+            }
+            match self.adapter_client.send_transaction(transaction.clone()) {
+                Ok(()) => {
+                    sent.insert(transaction.clone());
+                }
+                // Retried when building the next payload.
+                Err(err) => warn!(self.log, "Failed to send Bitcoin transaction: {:?}", err),
+            }
+        }
+    }
+}
+
+// The headers of the blocks a payload may build on: the unstable blocks in
+// the state (the latest stable block being the initial one) and the blocks in
+// past payloads.
+struct BlockHeaders {
+    network: Network,
+    initial_hash: BlockHash,
+    headers: HashMap<BlockHash, StoredHeader>,
+}
 
-        // TODO(EXC-784): read the requests from the state.
-        // TODO(EXC-785): send request to bitcoin adapter and get response.
+impl BlockHeaders {
+    fn new(bitcoin: &BitcoinState, past_payloads: &[&SelfValidatingPayload]) -> Self {
+        let mut headers = Self {
+            network: bitcoin.network,
+            initial_hash: bitcoin.unstable_blocks.root.block_hash(),
+            headers: HashMap::new(),
+        };
+        headers.add_tree(&bitcoin.unstable_blocks, bitcoin.stable_height);
+        // Past payloads come in descending height order and were validated
+        // before, on top of a possibly older state.
+        for payload in past_payloads.iter().rev() {
+            for block in payload.bitcoin_blocks.iter() {
+                let _ = headers.insert(block);
+            }
+        }
+        headers
+    }
+
+    fn add_tree(&mut self, tree: &BlockTree, height: u32) {
+        self.headers.insert(
+            tree.root.block_hash(),
+            StoredHeader {
+                header: tree.root.header,
+                height,
+            },
+        );
+        for child in tree.children.iter() {
+            self.add_tree(child, height + 1);
+        }
+    }
+
+    // Validates the consensus-encoded `block` and adds its header.
+    fn insert(&mut self, block: &[u8]) -> Result<(), InvalidSelfValidatingPayload> {
+        let block: Block = deserialize(block)
+            .map_err(|err| InvalidSelfValidatingPayload::InvalidBitcoinBlock(err.to_string()))?;
+        let hash = block.block_hash();
+        if self.headers.contains_key(&hash) {
+            return Err(InvalidSelfValidatingPayload::UnexpectedBitcoinBlock(
+                format!("Block {} is already known", hash),
+            ));
+        }
+        let height = match self.headers.get(&block.header.prev_blockhash) {
+            Some(parent) => parent.height + 1,
+            None => {
+                return Err(InvalidSelfValidatingPayload::UnexpectedBitcoinBlock(
+                    format!("The parent of block {} is unknown", hash),
+                ))
+            }
+        };
+        if !block.check_merkle_root() {
+            return Err(InvalidSelfValidatingPayload::InvalidBitcoinBlock(format!(
+                "Block {} has an invalid merkle root",
+                hash
+            )));
+        }
+        validate_header(&self.network, self, &block.header).map_err(|err| {
+            InvalidSelfValidatingPayload::InvalidBitcoinBlock(format!(
+                "Block {} has an invalid header: {:?}",
+                hash, err
+            ))
+        })?;
+        self.headers.insert(
+            hash,
+            StoredHeader {
+                header: block.header,
+                height,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl HeaderStore for BlockHeaders {
+    fn get_header(&self, hash: &BlockHash) -> Option<&StoredHeader> {
+        self.headers.get(hash)
+    }
 
-        Ok(SelfValidatingPayload {})
+    fn get_initial_hash(&self) -> BlockHash {
+        self.initial_hash
     }
 }
 
@@ -112,17 +298,37 @@ impl SelfValidatingPayloadBuilder for BitcoinPayloadBuilder {
 
     fn validate_self_validating_payload(
         &self,
-        _payload: &SelfValidatingPayload,
-        _validation_context: &ValidationContext,
-        _past_payloads: &[&SelfValidatingPayload],
+        payload: &SelfValidatingPayload,
+        validation_context: &ValidationContext,
+        past_payloads: &[&SelfValidatingPayload],
     ) -> Result<NumBytes, SelfValidatingPayloadValidationError> {
+        if payload.is_empty() {
+            return Ok(0.into());
+        }
         let timer = Timer::start();
 
-        // TODO(EXC-786): Validate the payload. For now we rubberstamp all payloads as
-        // valid.
+        let state = self
+            .state_manager
+            .get_state_at(validation_context.certified_height)
+            .map_err(|_| {
+                ValidationError::Transient(
+                    SelfValidatingTransientValidationError::StateUnavailable(
+                        validation_context.certified_height,
+                    ),
+                )
+            })?
+            .take();
+        let bitcoin = state.bitcoin.as_ref().ok_or(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::BitcoinDisabled,
+        ))?;
+
+        let mut headers = BlockHeaders::new(bitcoin, past_payloads);
+        for block in payload.bitcoin_blocks.iter() {
+            headers.insert(block).map_err(ValidationError::Permanent)?;
+        }
 
         self.metrics
             .observe_validate_duration(VALIDATION_STATUS_VALID, timer);
-        Ok(0.into())
+        Ok(NumBytes::from(payload.count_bytes() as u64))
     }
 }
@@ -0,0 +1,224 @@
+use super::*;
+use crate::BitcoinBlockFileAdapterClient;
+use bitcoin::{blockdata::constants::genesis_block, hashes::hex::ToHex};
+use ic_btc_canister::test_utils::{random_address, BlockBuilder, TransactionBuilder};
+use ic_interfaces::state_manager::Labeled;
+use ic_logger::replica_logger::no_op_logger;
+use ic_replicated_state::bitcoin_state::OutgoingTransaction;
+use ic_test_utilities::{
+    mock_time, state::ReplicatedStateBuilder, state_manager::MockStateManager,
+};
+use ic_types::RegistryVersion;
+use std::io::Write;
+
+fn validation_context() -> ValidationContext {
+    ValidationContext {
+        registry_version: RegistryVersion::from(1),
+        certified_height: Height::from(1),
+        time: mock_time(),
+    }
+}
+
+// Returns a chain of `n` regtest blocks on top of the genesis block.
+fn chain(n: usize) -> Vec<Block> {
+    let mut blocks: Vec<Block> = vec![];
+    for _ in 0..n {
+        let parent = blocks
+            .last()
+            .cloned()
+            .unwrap_or_else(|| genesis_block(Network::Regtest));
+        blocks.push(BlockBuilder::child_of(&parent).build());
+    }
+    blocks
+}
+
+fn encode(blocks: &[Block]) -> Vec<Vec<u8>> {
+    blocks.iter().map(serialize).collect()
+}
+
+fn state_manager(bitcoin: Option<BitcoinState>) -> Arc<MockStateManager> {
+    let mut state = ReplicatedStateBuilder::new().build();
+    state.bitcoin = bitcoin;
+    let state = Arc::new(state);
+    let mut state_manager = MockStateManager::new();
+    state_manager
+        .expect_get_state_at()
+        .returning(move |height| Ok(Labeled::new(height, Arc::clone(&state))));
+    Arc::new(state_manager)
+}
+
+fn payload_builder(
+    bitcoin: Option<BitcoinState>,
+    adapter_client: Arc<dyn BitcoinAdapterClient>,
+) -> BitcoinPayloadBuilder {
+    BitcoinPayloadBuilder::new(
+        state_manager(bitcoin),
+        adapter_client,
+        &MetricsRegistry::new(),
+        no_op_logger(),
+    )
+}
+
+fn payload(blocks: &[Block]) -> SelfValidatingPayload {
+    SelfValidatingPayload {
+        bitcoin_blocks: encode(blocks),
+    }
+}
+
+#[test]
+fn builds_valid_payload_from_block_file() {
+    let blocks = chain(3);
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for block in encode(&blocks) {
+        writeln!(file, "{}", block.to_hex()).unwrap();
+    }
+    let adapter_client = BitcoinBlockFileAdapterClient::from_file(file.path()).unwrap();
+    let builder = payload_builder(
+        Some(BitcoinState::new(Network::Regtest)),
+        Arc::new(adapter_client),
+    );
+
+    let payload =
+        builder.get_self_validating_payload(&validation_context(), &[], NumBytes::from(1 << 20));
+
+    assert_eq!(payload.bitcoin_blocks, encode(&blocks));
+    assert_eq!(
+        builder
+            .validate_self_validating_payload(&payload, &validation_context(), &[])
+            .unwrap(),
+        NumBytes::from(payload.count_bytes() as u64)
+    );
+}
+
+#[test]
+fn builds_empty_payload_if_bitcoin_is_disabled() {
+    let adapter_client = BitcoinBlockFileAdapterClient::from_blocks(encode(&chain(1))).unwrap();
+    let builder = payload_builder(None, Arc::new(adapter_client));
+
+    let payload =
+        builder.get_self_validating_payload(&validation_context(), &[], NumBytes::from(1 << 20));
+
+    assert!(payload.is_empty());
+}
+
+#[test]
+fn skips_blocks_of_past_payloads() {
+    let blocks = chain(3);
+    let adapter_client = BitcoinBlockFileAdapterClient::from_blocks(encode(&blocks)).unwrap();
+    let builder = payload_builder(
+        Some(BitcoinState::new(Network::Regtest)),
+        Arc::new(adapter_client),
+    );
+    let past_payload = payload(&blocks[..1]);
+
+    let payload = builder.get_self_validating_payload(
+        &validation_context(),
+        &[&past_payload],
+        NumBytes::from(1 << 20),
+    );
+
+    assert_eq!(payload.bitcoin_blocks, encode(&blocks[1..]));
+    assert!(builder
+        .validate_self_validating_payload(&payload, &validation_context(), &[&past_payload])
+        .is_ok());
+}
+
+#[test]
+fn respects_byte_limit() {
+    let blocks = chain(3);
+    let adapter_client = BitcoinBlockFileAdapterClient::from_blocks(encode(&blocks)).unwrap();
+    let builder = payload_builder(
+        Some(BitcoinState::new(Network::Regtest)),
+        Arc::new(adapter_client),
+    );
+    let byte_limit = serialize(&blocks[0]).len() + serialize(&blocks[1]).len();
+
+    let payload = builder.get_self_validating_payload(
+        &validation_context(),
+        &[],
+        NumBytes::from(byte_limit as u64),
+    );
+
+    assert_eq!(payload.bitcoin_blocks, encode(&blocks[..2]));
+}
+
+#[test]
+fn rejects_invalid_payloads() {
+    let blocks = chain(2);
+    let adapter_client = BitcoinBlockFileAdapterClient::from_blocks(vec![]).unwrap();
+    let builder = payload_builder(
+        Some(BitcoinState::new(Network::Regtest)),
+        Arc::new(adapter_client),
+    );
+    let validate = |payload: &SelfValidatingPayload| {
+        builder.validate_self_validating_payload(payload, &validation_context(), &[])
+    };
+
+    // The parent of the second block is missing.
+    assert!(matches!(
+        validate(&payload(&blocks[1..])),
+        Err(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::UnexpectedBitcoinBlock(_)
+        ))
+    ));
+    // The first block is included twice.
+    assert!(matches!(
+        validate(&payload(&[blocks[0].clone(), blocks[0].clone()])),
+        Err(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::UnexpectedBitcoinBlock(_)
+        ))
+    ));
+    // The block is cut short.
+    let mut truncated = payload(&blocks[..1]);
+    truncated.bitcoin_blocks[0].pop();
+    assert!(matches!(
+        validate(&truncated),
+        Err(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::InvalidBitcoinBlock(_)
+        ))
+    ));
+    // The transactions do not match the merkle root of the header.
+    let mut tampered = blocks[0].clone();
+    tampered.txdata.push(TransactionBuilder::coinbase().build());
+    assert!(matches!(
+        validate(&payload(&[tampered])),
+        Err(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::InvalidBitcoinBlock(_)
+        ))
+    ));
+}
+
+#[test]
+fn rejects_blocks_if_bitcoin_is_disabled() {
+    let adapter_client = BitcoinBlockFileAdapterClient::from_blocks(vec![]).unwrap();
+    let builder = payload_builder(None, Arc::new(adapter_client));
+
+    assert!(matches!(
+        builder.validate_self_validating_payload(&payload(&chain(1)), &validation_context(), &[]),
+        Err(ValidationError::Permanent(
+            InvalidSelfValidatingPayload::BitcoinDisabled
+        ))
+    ));
+}
+
+#[test]
+fn sends_outgoing_transactions_once() {
+    let transaction = serialize(
+        &TransactionBuilder::coinbase()
+            .with_output(&random_address(1), 1000)
+            .build(),
+    );
+    let mut bitcoin = BitcoinState::new(Network::Regtest);
+    bitcoin.outgoing_transactions.push(OutgoingTransaction {
+        transaction: transaction.clone(),
+        time: mock_time(),
+    });
+    let adapter_client = Arc::new(BitcoinBlockFileAdapterClient::from_blocks(vec![]).unwrap());
+    let builder = payload_builder(Some(bitcoin), adapter_client.clone());
+
+    for _ in 0..2 {
+        builder.get_self_validating_payload(&validation_context(), &[], NumBytes::from(1 << 20));
+    }
+
+    assert_eq!(adapter_client.sent_transactions(), vec![transaction]);
+}
@@ -1,6 +1,6 @@
 mod constants;
 mod header;
 
-pub use crate::header::{validate_header, ValidateHeaderError};
+pub use crate::header::{validate_header, HeaderStore, StoredHeader, ValidateHeaderError};
 
 type BlockHeight = u32;
@@ -0,0 +1,142 @@
+use ic_error_types::RejectCode;
+use ic_interfaces::canister_http::{CanisterHttpAdapterClient, CanisterHttpAdapterClientError};
+use ic_types::{
+    canister_http::{
+        CanisterHttpAdapterResponse, CanisterHttpReject, CanisterHttpRequest,
+        CANISTER_HTTP_TIMEOUT_INTERVAL,
+    },
+    ic00::{CanisterHttpResponsePayload, HttpHeader, HttpMethod},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+use tokio::runtime::Handle;
+
+/// Maximum number of requests that may be in flight at the same time.
+const MAX_IN_FLIGHT_REQUESTS: usize = 500;
+
+/// Executes canister HTTP requests on the given tokio runtime.
+///
+/// Requests are sent asynchronously and their responses are buffered until
+/// they are picked up via `try_receive()`.
+pub struct CanisterHttpAdapterClientImpl {
+    rt_handle: Handle,
+    client: reqwest::Client,
+    in_flight: Arc<AtomicUsize>,
+    tx: Mutex<Sender<CanisterHttpAdapterResponse>>,
+    rx: Mutex<Receiver<CanisterHttpAdapterResponse>>,
+}
+
+impl CanisterHttpAdapterClientImpl {
+    pub fn new(rt_handle: Handle) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(CANISTER_HTTP_TIMEOUT_INTERVAL)
+            .build()
+            .expect("Failed to build canister http client");
+        let (tx, rx) = channel();
+        Self {
+            rt_handle,
+            client,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
+impl CanisterHttpAdapterClient for CanisterHttpAdapterClientImpl {
+    fn send(&self, request: CanisterHttpRequest) -> Result<(), CanisterHttpAdapterClientError> {
+        if self.in_flight.fetch_add(1, Ordering::SeqCst) >= MAX_IN_FLIGHT_REQUESTS {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return Err(CanisterHttpAdapterClientError::Busy(request));
+        }
+
+        let client = self.client.clone();
+        let in_flight = Arc::clone(&self.in_flight);
+        let tx = self.tx.lock().unwrap().clone();
+        self.rt_handle.spawn(async move {
+            let response = CanisterHttpAdapterResponse {
+                id: request.id,
+                timeout: request.timeout,
+                content: execute(client, request).await,
+            };
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            // The receiver only goes away together with the client.
+            let _ = tx.send(response);
+        });
+        Ok(())
+    }
+
+    fn try_receive(&self) -> Option<CanisterHttpAdapterResponse> {
+        self.rx.lock().unwrap().try_recv().ok()
+    }
+}
+
+// Executes the request and reads the response, as long as it does not exceed
+// `max_response_bytes` (headers included).
+async fn execute(
+    client: reqwest::Client,
+    request: CanisterHttpRequest,
+) -> Result<CanisterHttpResponsePayload, CanisterHttpReject> {
+    let method = match request.method {
+        HttpMethod::GET => reqwest::Method::GET,
+        HttpMethod::POST => reqwest::Method::POST,
+        HttpMethod::HEAD => reqwest::Method::HEAD,
+    };
+    let mut builder = client.request(method, &request.url);
+    for header in request.headers {
+        builder = builder.header(header.name, header.value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+
+    let mut response = builder.send().await.map_err(|err| CanisterHttpReject {
+        reject_code: RejectCode::SysTransient,
+        message: format!("Failed to connect: {}", err),
+    })?;
+
+    let too_large = || CanisterHttpReject {
+        reject_code: RejectCode::SysFatal,
+        message: format!(
+            "Http response exceeds size limit of {} bytes",
+            request.max_response_bytes
+        ),
+    };
+    let status = response.status().as_u16() as u64;
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| HttpHeader {
+            name: name.as_str().to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).to_string(),
+        })
+        .collect::<Vec<_>>();
+    let mut size = headers
+        .iter()
+        .map(|header| (header.name.len() + header.value.len()) as u64)
+        .sum::<u64>();
+    if size > request.max_response_bytes {
+        return Err(too_large());
+    }
+
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await.map_err(|err| CanisterHttpReject {
+        reject_code: RejectCode::SysTransient,
+        message: format!("Failed to read response body: {}", err),
+    })? {
+        size += chunk.len() as u64;
+        if size > request.max_response_bytes {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(CanisterHttpResponsePayload {
+        status,
+        headers,
+        body,
+    })
+}
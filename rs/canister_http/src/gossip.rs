@@ -0,0 +1,50 @@
+use ic_interfaces::{
+    canister_http::{CanisterHttpGossip, CanisterHttpPool},
+    state_manager::StateManager,
+};
+use ic_replicated_state::ReplicatedState;
+use ic_types::artifact::{
+    CanisterHttpResponseAttribute, CanisterHttpResponseId, Priority, PriorityFn,
+};
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Computes the priority of the canister HTTP response shares advertised by
+/// the peers, based on the requests in the latest state.
+pub struct CanisterHttpGossipImpl {
+    state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+}
+
+impl CanisterHttpGossipImpl {
+    pub fn new(state_manager: Arc<dyn StateManager<State = ReplicatedState>>) -> Self {
+        Self { state_manager }
+    }
+}
+
+impl CanisterHttpGossip for CanisterHttpGossipImpl {
+    /// Shares for pending requests are fetched and shares for requests that
+    /// were already answered are dropped. Shares for requests this node does
+    /// not know about yet, because its state is behind the one of the peer,
+    /// are stashed.
+    fn get_priority_function(
+        &self,
+        _canister_http_pool: &dyn CanisterHttpPool,
+    ) -> PriorityFn<CanisterHttpResponseId, CanisterHttpResponseAttribute> {
+        let state = self.state_manager.get_latest_state().take();
+        let manager = &state.metadata.subnet_call_context_manager;
+        let pending: BTreeSet<_> = manager
+            .canister_http_request_contexts
+            .keys()
+            .cloned()
+            .collect();
+        let next_callback_id = manager.next_callback_id();
+        Box::new(move |_, attribute| {
+            if pending.contains(&attribute.id) {
+                Priority::Fetch
+            } else if attribute.id < next_callback_id {
+                Priority::Drop
+            } else {
+                Priority::Stash
+            }
+        })
+    }
+}
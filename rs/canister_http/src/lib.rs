@@ -0,0 +1,55 @@
+//! Canister HTTP requests: the pool manager that hands the requests in the
+//! replicated state to the adapter and signs the responses, the payload
+//! builder that includes the responses enough nodes agreed upon in blocks, and
+//! the replica-side client of the adapter that executes the requests.
+mod adapter_client;
+mod gossip;
+mod metrics;
+mod payload_builder;
+mod pool_manager;
+pub use adapter_client::CanisterHttpAdapterClientImpl;
+pub use gossip::CanisterHttpGossipImpl;
+pub use payload_builder::CanisterHttpPayloadBuilderImpl;
+pub use pool_manager::CanisterHttpPoolManagerImpl;
+
+use ic_interfaces::{
+    crypto::{BasicSigVerifier, BasicSigner},
+    registry::RegistryClient,
+};
+use ic_registry_client::helper::subnet::SubnetRegistry;
+use ic_types::{canister_http::CanisterHttpResponseMetadata, NodeId, RegistryVersion, SubnetId};
+use std::collections::BTreeSet;
+
+/// The crypto functionality needed to sign and verify canister HTTP response
+/// shares.
+pub trait CanisterHttpCrypto:
+    BasicSigner<CanisterHttpResponseMetadata>
+    + BasicSigVerifier<CanisterHttpResponseMetadata>
+    + Send
+    + Sync
+{
+}
+
+// Blanket implementation of CanisterHttpCrypto for all types that fulfill
+// requirements
+impl<T> CanisterHttpCrypto for T where
+    T: BasicSigner<CanisterHttpResponseMetadata>
+        + BasicSigVerifier<CanisterHttpResponseMetadata>
+        + Send
+        + Sync
+{
+}
+
+// Returns the nodes of the subnet at the given registry version, or `None` if
+// the membership is not available at that version.
+pub(crate) fn get_subnet_members(
+    registry_client: &dyn RegistryClient,
+    subnet_id: SubnetId,
+    registry_version: RegistryVersion,
+) -> Option<BTreeSet<NodeId>> {
+    registry_client
+        .get_node_ids_on_subnet(subnet_id, registry_version)
+        .ok()
+        .flatten()
+        .map(|nodes| nodes.into_iter().collect())
+}
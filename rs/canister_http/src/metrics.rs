@@ -0,0 +1,73 @@
+use ic_metrics::{buckets::decimal_buckets, MetricsRegistry, Timer};
+use prometheus::{HistogramVec, IntCounterVec};
+
+const LABEL_STATUS: &str = "status";
+const METRIC_BUILD_PAYLOAD_DURATION: &str = "canister_http_builder_build_payload_duration_seconds";
+const METRIC_VALIDATE_PAYLOAD_DURATION: &str =
+    "canister_http_builder_validate_payload_duration_seconds";
+const METRIC_ADAPTER_REQUESTS: &str = "canister_http_pool_manager_adapter_requests_total";
+
+pub struct CanisterHttpPayloadBuilderMetrics {
+    // Records the time it took to build the payload, by status.
+    build_payload_duration: HistogramVec,
+    // Records the time it took to validate a payload, by status.
+    validate_payload_duration: HistogramVec,
+}
+
+impl CanisterHttpPayloadBuilderMetrics {
+    pub fn new(metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            build_payload_duration: metrics_registry.histogram_vec(
+                METRIC_BUILD_PAYLOAD_DURATION,
+                "The time it took to build the payload, by status.",
+                // 0.1ms - 5s
+                decimal_buckets(-4, 0),
+                &[LABEL_STATUS],
+            ),
+            validate_payload_duration: metrics_registry.histogram_vec(
+                METRIC_VALIDATE_PAYLOAD_DURATION,
+                "The time it took to validate a payload, by status.",
+                // 0.1ms - 5s
+                decimal_buckets(-4, 0),
+                &[LABEL_STATUS],
+            ),
+        }
+    }
+
+    // Records the status and duration of a `get_canister_http_payload()` call.
+    pub fn observe_build_duration(&self, status: &str, timer: Timer) {
+        self.build_payload_duration
+            .with_label_values(&[status])
+            .observe(timer.elapsed());
+    }
+
+    // Records the status and duration of a `validate_canister_http_payload()`
+    // call.
+    pub fn observe_validate_duration(&self, status: &str, timer: Timer) {
+        self.validate_payload_duration
+            .with_label_values(&[status])
+            .observe(timer.elapsed());
+    }
+}
+
+pub struct CanisterHttpPoolManagerMetrics {
+    // Counts the requests handed to the adapter, by status.
+    adapter_requests: IntCounterVec,
+}
+
+impl CanisterHttpPoolManagerMetrics {
+    pub fn new(metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            adapter_requests: metrics_registry.int_counter_vec(
+                METRIC_ADAPTER_REQUESTS,
+                "The number of requests handed to the adapter, by status.",
+                &[LABEL_STATUS],
+            ),
+        }
+    }
+
+    // Records the status of a request handed to the adapter.
+    pub fn observe_adapter_request(&self, status: &str) {
+        self.adapter_requests.with_label_values(&[status]).inc();
+    }
+}
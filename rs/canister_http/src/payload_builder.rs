@@ -0,0 +1,559 @@
+use crate::{get_subnet_members, metrics::CanisterHttpPayloadBuilderMetrics, CanisterHttpCrypto};
+use ic_interfaces::{
+    canister_http::{
+        CanisterHttpPayloadBuilder, CanisterHttpPayloadValidationError, CanisterHttpPool,
+        CanisterHttpTransientValidationError, InvalidCanisterHttpPayload,
+    },
+    registry::RegistryClient,
+    state_manager::{StateManager, StateManagerError},
+    validation::ValidationError,
+};
+use ic_logger::{log, ReplicaLogger};
+use ic_metrics::{MetricsRegistry, Timer};
+use ic_replicated_state::ReplicatedState;
+use ic_types::{
+    batch::ValidationContext,
+    canister_http::{
+        CanisterHttpPayload, CanisterHttpResponseMetadata, CanisterHttpResponseShare,
+        CanisterHttpResponseWithConsensus, CANISTER_HTTP_TIMEOUT_INTERVAL,
+    },
+    consensus::{get_faults_tolerated, BasicSignatureBatch},
+    crypto::Signed,
+    messages::CallbackId,
+    CountBytes, Height, NumBytes, SubnetId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    mem::size_of,
+    sync::{Arc, RwLock},
+};
+use thiserror::Error;
+
+const BUILD_PAYLOAD_STATUS_SUCCESS: &str = "success";
+const VALIDATION_STATUS_VALID: &str = "valid";
+
+// Internal error type, to simplify error handling.
+#[derive(Error, Debug)]
+enum GetPayloadError {
+    #[error("Error retrieving state at height {0}: {1}")]
+    GetStateFailed(Height, StateManagerError),
+}
+
+impl GetPayloadError {
+    // Returns the desired log level to be used with this `Error`.
+    fn log_level(&self) -> slog::Level {
+        match self {
+            Self::GetStateFailed(..) => slog::Level::Warning,
+        }
+    }
+
+    // Maps the `Error` to a `status` label value.
+    fn to_label_value(&self) -> &str {
+        match self {
+            Self::GetStateFailed(..) => "GetStateFailed",
+        }
+    }
+}
+
+/// Builds and validates `CanisterHttpPayloads`.
+///
+/// A response is only included once more than a third of the nodes of the
+/// subnet (i.e. at least one honest node) signed the same metadata; the
+/// signatures are included as proof. Requests whose timeout has passed before
+/// that are answered with a reject.
+pub struct CanisterHttpPayloadBuilderImpl {
+    canister_http_pool: Arc<RwLock<dyn CanisterHttpPool>>,
+    state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    crypto: Arc<dyn CanisterHttpCrypto>,
+    registry_client: Arc<dyn RegistryClient>,
+    subnet_id: SubnetId,
+    metrics: Arc<CanisterHttpPayloadBuilderMetrics>,
+    log: ReplicaLogger,
+}
+
+impl CanisterHttpPayloadBuilderImpl {
+    pub fn new(
+        canister_http_pool: Arc<RwLock<dyn CanisterHttpPool>>,
+        state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        crypto: Arc<dyn CanisterHttpCrypto>,
+        registry_client: Arc<dyn RegistryClient>,
+        subnet_id: SubnetId,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> Self {
+        Self {
+            canister_http_pool,
+            state_manager,
+            crypto,
+            registry_client,
+            subnet_id,
+            metrics: Arc::new(CanisterHttpPayloadBuilderMetrics::new(metrics_registry)),
+            log,
+        }
+    }
+
+    fn get_canister_http_payload_impl(
+        &self,
+        validation_context: &ValidationContext,
+        past_payloads: &[&CanisterHttpPayload],
+        byte_limit: NumBytes,
+    ) -> Result<CanisterHttpPayload, GetPayloadError> {
+        // Retrieve the `ReplicatedState` required by `validation_context`.
+        let state = self
+            .state_manager
+            .get_state_at(validation_context.certified_height)
+            .map_err(|e| GetPayloadError::GetStateFailed(validation_context.certified_height, e))?
+            .take();
+        let contexts = &state
+            .metadata
+            .subnet_call_context_manager
+            .canister_http_request_contexts;
+
+        let delivered = delivered_ids(past_payloads);
+        let byte_limit = byte_limit.get() as usize;
+        let mut payload = CanisterHttpPayload::default();
+        let mut size = 0;
+
+        // The requests that timed out are answered with a reject.
+        for (id, context) in contexts.iter() {
+            if delivered.contains(id)
+                || context.time + CANISTER_HTTP_TIMEOUT_INTERVAL > validation_context.time
+            {
+                continue;
+            }
+            if size + size_of::<CallbackId>() > byte_limit {
+                return Ok(payload);
+            }
+            size += size_of::<CallbackId>();
+            payload.timeouts.push(*id);
+        }
+        let timed_out: BTreeSet<CallbackId> = payload.timeouts.iter().cloned().collect();
+
+        // Group the shares of the pending requests by the metadata they sign.
+        let canister_http_pool = self.canister_http_pool.read().unwrap();
+        let mut shares: HashMap<&CanisterHttpResponseMetadata, Vec<&CanisterHttpResponseShare>> =
+            HashMap::new();
+        for share in canister_http_pool.get_validated_shares() {
+            let id = share.content.id;
+            if contexts.contains_key(&id) && !delivered.contains(&id) && !timed_out.contains(&id) {
+                shares.entry(&share.content).or_default().push(share);
+            }
+        }
+
+        let mut responses = BTreeMap::new();
+        for (metadata, shares) in shares {
+            if responses.contains_key(&metadata.id) {
+                continue;
+            }
+            let members = match get_subnet_members(
+                &*self.registry_client,
+                self.subnet_id,
+                metadata.registry_version,
+            ) {
+                Some(members) => members,
+                None => continue,
+            };
+            let signatures_map: BTreeMap<_, _> = shares
+                .into_iter()
+                .filter(|share| members.contains(&share.signature.signer))
+                .map(|share| (share.signature.signer, share.signature.signature.clone()))
+                .collect();
+            if signatures_map.len() < get_faults_tolerated(members.len()) + 1 {
+                continue;
+            }
+            // Only the nodes that received the response themselves can
+            // include it.
+            let content =
+                match canister_http_pool.get_response_content_by_hash(&metadata.content_hash) {
+                    Some(content) => content,
+                    None => continue,
+                };
+            responses.insert(
+                metadata.id,
+                CanisterHttpResponseWithConsensus {
+                    content,
+                    proof: Signed {
+                        content: metadata.clone(),
+                        signature: BasicSignatureBatch { signatures_map },
+                    },
+                },
+            );
+        }
+
+        for response in responses.into_values() {
+            let response_size = response.count_bytes();
+            if size + response_size > byte_limit {
+                break;
+            }
+            size += response_size;
+            payload.responses.push(response);
+        }
+
+        Ok(payload)
+    }
+
+    // Checks that the proof of the response is signed by enough nodes of the
+    // subnet and matches the response.
+    fn validate_proof(
+        &self,
+        response: &CanisterHttpResponseWithConsensus,
+    ) -> Result<(), CanisterHttpPayloadValidationError> {
+        let id = response.content.id;
+        let metadata = &response.proof.content;
+        if metadata.id != id || metadata.timeout != response.content.timeout {
+            return Err(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::InvalidMetadata(id),
+            ));
+        }
+        if ic_crypto::crypto_hash(&response.content) != metadata.content_hash {
+            return Err(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::ContentHashMismatch(id),
+            ));
+        }
+
+        let members = get_subnet_members(
+            &*self.registry_client,
+            self.subnet_id,
+            metadata.registry_version,
+        )
+        .ok_or(ValidationError::Transient(
+            CanisterHttpTransientValidationError::RegistryUnavailable(metadata.registry_version),
+        ))?;
+        let signatures_map = &response.proof.signature.signatures_map;
+        if let Some(signer) = signatures_map
+            .keys()
+            .find(|signer| !members.contains(signer))
+        {
+            return Err(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::SignerNotMember(id, *signer),
+            ));
+        }
+        let threshold = get_faults_tolerated(members.len()) + 1;
+        if signatures_map.len() < threshold {
+            return Err(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::NotEnoughSignatures {
+                    id,
+                    expected: threshold,
+                    received: signatures_map.len(),
+                },
+            ));
+        }
+        for (signer, signature) in signatures_map.iter() {
+            self.crypto
+                .verify_basic_sig(signature, metadata, *signer, metadata.registry_version)
+                .map_err(|err| {
+                    ValidationError::Permanent(InvalidCanisterHttpPayload::InvalidSignature(
+                        id, err,
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the ids of the requests answered by the given payloads.
+fn delivered_ids(past_payloads: &[&CanisterHttpPayload]) -> BTreeSet<CallbackId> {
+    past_payloads
+        .iter()
+        .flat_map(|payload| {
+            payload
+                .responses
+                .iter()
+                .map(|response| response.content.id)
+                .chain(payload.timeouts.iter().cloned())
+        })
+        .collect()
+}
+
+impl CanisterHttpPayloadBuilder for CanisterHttpPayloadBuilderImpl {
+    fn get_canister_http_payload(
+        &self,
+        validation_context: &ValidationContext,
+        past_payloads: &[&CanisterHttpPayload],
+        byte_limit: NumBytes,
+    ) -> CanisterHttpPayload {
+        let timer = Timer::start();
+        let payload = match self.get_canister_http_payload_impl(
+            validation_context,
+            past_payloads,
+            byte_limit,
+        ) {
+            Ok(payload) => {
+                self.metrics
+                    .observe_build_duration(BUILD_PAYLOAD_STATUS_SUCCESS, timer);
+                payload
+            }
+
+            Err(e) => {
+                log!(self.log, e.log_level(), "{}", e);
+                self.metrics
+                    .observe_build_duration(e.to_label_value(), timer);
+
+                CanisterHttpPayload::default()
+            }
+        };
+
+        debug_assert!(payload.count_bytes() <= byte_limit.get() as usize);
+        payload
+    }
+
+    fn validate_canister_http_payload(
+        &self,
+        payload: &CanisterHttpPayload,
+        validation_context: &ValidationContext,
+        past_payloads: &[&CanisterHttpPayload],
+    ) -> Result<NumBytes, CanisterHttpPayloadValidationError> {
+        if payload.is_empty() {
+            return Ok(0.into());
+        }
+        let timer = Timer::start();
+
+        let state = self
+            .state_manager
+            .get_state_at(validation_context.certified_height)
+            .map_err(|_| {
+                ValidationError::Transient(CanisterHttpTransientValidationError::StateUnavailable(
+                    validation_context.certified_height,
+                ))
+            })?
+            .take();
+        let contexts = &state
+            .metadata
+            .subnet_call_context_manager
+            .canister_http_request_contexts;
+
+        let mut delivered = delivered_ids(past_payloads);
+        for response in payload.responses.iter() {
+            let id = response.content.id;
+            let context = contexts.get(&id).ok_or(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::UnknownCallbackId(id),
+            ))?;
+            if !delivered.insert(id) {
+                return Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::DuplicateResponse(id),
+                ));
+            }
+            if response.content.timeout != context.time + CANISTER_HTTP_TIMEOUT_INTERVAL {
+                return Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::InvalidTimeout(id),
+                ));
+            }
+            self.validate_proof(response)?;
+        }
+        for id in payload.timeouts.iter() {
+            let context = contexts.get(id).ok_or(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::UnknownCallbackId(*id),
+            ))?;
+            if !delivered.insert(*id) {
+                return Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::DuplicateResponse(*id),
+                ));
+            }
+            if validation_context.time < context.time + CANISTER_HTTP_TIMEOUT_INTERVAL {
+                return Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::TimeoutNotReached(*id),
+                ));
+            }
+        }
+
+        self.metrics
+            .observe_validate_duration(VALIDATION_STATUS_VALID, timer);
+        Ok(NumBytes::from(payload.count_bytes() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_artifact_pool::canister_http_pool::CanisterHttpPoolImpl;
+    use ic_interfaces::state_manager::Labeled;
+    use ic_replicated_state::metadata_state::subnet_call_context_manager::CanisterHttpRequestContext;
+    use ic_test_utilities::{
+        crypto::CryptoReturningOk,
+        mock_time,
+        registry::{setup_registry, SubnetRecordBuilder},
+        state::ReplicatedStateBuilder,
+        state_manager::MockStateManager,
+        types::{
+            ids::{node_test_id, subnet_test_id},
+            messages::RequestBuilder,
+        },
+        with_test_replica_logger,
+    };
+    use ic_types::{
+        canister_http::{CanisterHttpResponse, CanisterHttpResponseContent},
+        crypto::{BasicSig, BasicSigOf},
+        ic00::HttpMethod,
+        RegistryVersion,
+    };
+
+    // The subnet consists of four nodes, hence two signatures are required.
+    const SUBNET_SIZE: u64 = 4;
+
+    fn test_validation_context() -> ValidationContext {
+        ValidationContext {
+            registry_version: RegistryVersion::from(1),
+            certified_height: Height::from(0),
+            time: mock_time(),
+        }
+    }
+
+    // Runs `test` with a payload builder for a state with a single pending
+    // canister HTTP request, made at `mock_time()`.
+    fn with_payload_builder(test: impl FnOnce(CallbackId, CanisterHttpPayloadBuilderImpl)) {
+        with_test_replica_logger(|log| {
+            let subnet_id = subnet_test_id(0);
+            let committee: Vec<_> = (0..SUBNET_SIZE).map(node_test_id).collect();
+            let registry = setup_registry(
+                subnet_id,
+                vec![(1, SubnetRecordBuilder::from(&committee).build())],
+            );
+
+            let mut state = ReplicatedStateBuilder::default().build();
+            let manager = &mut state.metadata.subnet_call_context_manager;
+            let id = manager.next_callback_id();
+            manager.push_http_request(CanisterHttpRequestContext {
+                request: RequestBuilder::default().build(),
+                url: "https://example.com".to_string(),
+                max_response_bytes: 1024,
+                headers: vec![],
+                body: None,
+                http_method: HttpMethod::GET,
+                transform_method_name: None,
+                time: mock_time(),
+            });
+            let mut state_manager = MockStateManager::new();
+            state_manager
+                .expect_get_state_at()
+                .return_const(Ok(Labeled::new(Height::from(0), Arc::new(state))));
+
+            let metrics_registry = MetricsRegistry::new();
+            let pool = CanisterHttpPoolImpl::new(metrics_registry.clone());
+            test(
+                id,
+                CanisterHttpPayloadBuilderImpl::new(
+                    Arc::new(RwLock::new(pool)),
+                    Arc::new(state_manager),
+                    Arc::new(CryptoReturningOk::default()),
+                    registry,
+                    subnet_id,
+                    &metrics_registry,
+                    log,
+                ),
+            )
+        })
+    }
+
+    // Returns a response to the request with the given id, signed by the
+    // given number of nodes.
+    fn signed_response(id: CallbackId, signers: u64) -> CanisterHttpResponseWithConsensus {
+        let content = CanisterHttpResponse {
+            id,
+            timeout: mock_time() + CANISTER_HTTP_TIMEOUT_INTERVAL,
+            content: CanisterHttpResponseContent::Success(vec![1, 2, 3]),
+        };
+        let metadata = CanisterHttpResponseMetadata {
+            id,
+            timeout: content.timeout,
+            content_hash: ic_crypto::crypto_hash(&content),
+            registry_version: RegistryVersion::from(1),
+        };
+        let signatures_map = (0..signers)
+            .map(|i| (node_test_id(i), BasicSigOf::new(BasicSig(vec![]))))
+            .collect();
+        CanisterHttpResponseWithConsensus {
+            content,
+            proof: Signed {
+                content: metadata,
+                signature: BasicSignatureBatch { signatures_map },
+            },
+        }
+    }
+
+    #[test]
+    fn validate_accepts_response_signed_by_enough_nodes() {
+        with_payload_builder(|id, payload_builder| {
+            let payload = CanisterHttpPayload {
+                responses: vec![signed_response(id, 2)],
+                timeouts: vec![],
+            };
+            assert!(payload_builder
+                .validate_canister_http_payload(&payload, &test_validation_context(), &[])
+                .is_ok());
+        });
+    }
+
+    #[test]
+    fn validate_rejects_response_without_enough_signatures() {
+        with_payload_builder(|id, payload_builder| {
+            let payload = CanisterHttpPayload {
+                responses: vec![signed_response(id, 1)],
+                timeouts: vec![],
+            };
+            match payload_builder.validate_canister_http_payload(
+                &payload,
+                &test_validation_context(),
+                &[],
+            ) {
+                Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::NotEnoughSignatures {
+                        expected: 2,
+                        received: 1,
+                        ..
+                    },
+                )) => {}
+                result => panic!("Unexpected validation result: {:?}", result),
+            }
+        });
+    }
+
+    #[test]
+    fn validate_rejects_response_with_modified_content() {
+        with_payload_builder(|id, payload_builder| {
+            let mut response = signed_response(id, 2);
+            response.content.content = CanisterHttpResponseContent::Success(vec![4, 5, 6]);
+            let payload = CanisterHttpPayload {
+                responses: vec![response],
+                timeouts: vec![],
+            };
+            match payload_builder.validate_canister_http_payload(
+                &payload,
+                &test_validation_context(),
+                &[],
+            ) {
+                Err(ValidationError::Permanent(
+                    InvalidCanisterHttpPayload::ContentHashMismatch(_),
+                )) => {}
+                result => panic!("Unexpected validation result: {:?}", result),
+            }
+        });
+    }
+
+    #[test]
+    fn validate_rejects_timeout_that_was_not_reached() {
+        with_payload_builder(|id, payload_builder| {
+            let payload = CanisterHttpPayload {
+                responses: vec![],
+                timeouts: vec![id],
+            };
+            match payload_builder.validate_canister_http_payload(
+                &payload,
+                &test_validation_context(),
+                &[],
+            ) {
+                Err(ValidationError::Permanent(InvalidCanisterHttpPayload::TimeoutNotReached(
+                    _,
+                ))) => {}
+                result => panic!("Unexpected validation result: {:?}", result),
+            }
+
+            let validation_context = ValidationContext {
+                time: mock_time() + CANISTER_HTTP_TIMEOUT_INTERVAL,
+                ..test_validation_context()
+            };
+            assert!(payload_builder
+                .validate_canister_http_payload(&payload, &validation_context, &[])
+                .is_ok());
+        });
+    }
+}
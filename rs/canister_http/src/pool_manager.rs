@@ -0,0 +1,345 @@
+use crate::{get_subnet_members, metrics::CanisterHttpPoolManagerMetrics, CanisterHttpCrypto};
+use ic_error_types::RejectCode;
+use ic_interfaces::{
+    canister_http::{
+        CanisterHttpAdapterClient, CanisterHttpAdapterClientError, CanisterHttpChangeAction,
+        CanisterHttpChangeSet, CanisterHttpPool, CanisterHttpPoolManager,
+    },
+    execution_environment::QueryHandler,
+    registry::RegistryClient,
+    state_manager::StateManager,
+};
+use ic_logger::{warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_replicated_state::{
+    metadata_state::subnet_call_context_manager::CanisterHttpRequestContext, ReplicatedState,
+};
+use ic_types::{
+    canister_http::{
+        CanisterHttpAdapterResponse, CanisterHttpReject, CanisterHttpRequest, CanisterHttpResponse,
+        CanisterHttpResponseContent, CanisterHttpResponseMetadata, CanisterHttpResponseShare,
+        CANISTER_HTTP_TIMEOUT_INTERVAL, MAX_CANISTER_HTTP_RESPONSE_BYTES,
+    },
+    consensus::BasicSignature,
+    crypto::Signed,
+    ic00::{CanisterHttpResponsePayload, Payload, IC_00},
+    ingress::WasmResult,
+    messages::{CallbackId, UserQuery},
+    NodeId, SubnetId, UserId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+
+const ADAPTER_REQUEST_STATUS_SENT: &str = "sent";
+const ADAPTER_REQUEST_STATUS_BUSY: &str = "busy";
+const ADAPTER_REQUEST_STATUS_UNAVAILABLE: &str = "unavailable";
+
+/// Manages the canister HTTP pool.
+///
+/// The pool manager hands every canister HTTP request it finds in the latest
+/// state to the adapter exactly once, applies the transform function of the
+/// calling canister to the response and signs its metadata. It validates the
+/// shares of the other nodes and purges the shares of the requests that were
+/// answered.
+pub struct CanisterHttpPoolManagerImpl {
+    state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    query_handler: Arc<dyn QueryHandler<State = ReplicatedState>>,
+    adapter_client: Arc<dyn CanisterHttpAdapterClient>,
+    crypto: Arc<dyn CanisterHttpCrypto>,
+    registry_client: Arc<dyn RegistryClient>,
+    node_id: NodeId,
+    subnet_id: SubnetId,
+    // Requests that were handed to the adapter and were not answered yet.
+    requested: Mutex<BTreeSet<CallbackId>>,
+    metrics: CanisterHttpPoolManagerMetrics,
+    log: ReplicaLogger,
+}
+
+impl CanisterHttpPoolManagerImpl {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        query_handler: Arc<dyn QueryHandler<State = ReplicatedState>>,
+        adapter_client: Arc<dyn CanisterHttpAdapterClient>,
+        crypto: Arc<dyn CanisterHttpCrypto>,
+        registry_client: Arc<dyn RegistryClient>,
+        node_id: NodeId,
+        subnet_id: SubnetId,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> Self {
+        Self {
+            state_manager,
+            query_handler,
+            adapter_client,
+            crypto,
+            registry_client,
+            node_id,
+            subnet_id,
+            requested: Mutex::new(BTreeSet::new()),
+            metrics: CanisterHttpPoolManagerMetrics::new(metrics_registry),
+            log,
+        }
+    }
+
+    // Hands the requests that were not handed to the adapter yet over to it.
+    fn send_requests(&self, contexts: &BTreeMap<CallbackId, CanisterHttpRequestContext>) {
+        let mut requested = self.requested.lock().unwrap();
+        requested.retain(|id| contexts.contains_key(id));
+        for (id, context) in contexts.iter() {
+            if requested.contains(id) {
+                continue;
+            }
+            let request = CanisterHttpRequest {
+                id: *id,
+                timeout: context.time + CANISTER_HTTP_TIMEOUT_INTERVAL,
+                url: context.url.clone(),
+                method: context.http_method,
+                headers: context.headers.clone(),
+                body: context.body.clone(),
+                max_response_bytes: context.max_response_bytes,
+            };
+            match self.adapter_client.send(request) {
+                Ok(()) => {
+                    self.metrics
+                        .observe_adapter_request(ADAPTER_REQUEST_STATUS_SENT);
+                    requested.insert(*id);
+                }
+                // Retried on the next state change.
+                Err(CanisterHttpAdapterClientError::Busy(_)) => {
+                    self.metrics
+                        .observe_adapter_request(ADAPTER_REQUEST_STATUS_BUSY);
+                }
+                Err(CanisterHttpAdapterClientError::Unavailable(request)) => {
+                    warn!(
+                        self.log,
+                        "Canister http adapter unavailable, request {} not sent", request.id
+                    );
+                    self.metrics
+                        .observe_adapter_request(ADAPTER_REQUEST_STATUS_UNAVAILABLE);
+                }
+            }
+        }
+    }
+
+    // Signs the metadata of the responses the adapter has received in the
+    // meantime.
+    fn sign_responses(
+        &self,
+        contexts: &BTreeMap<CallbackId, CanisterHttpRequestContext>,
+        state: Arc<ReplicatedState>,
+    ) -> CanisterHttpChangeSet {
+        let mut change_set = Vec::new();
+        while let Some(response) = self.adapter_client.try_receive() {
+            let context = match contexts.get(&response.id) {
+                Some(context) => context,
+                None => continue,
+            };
+            let response = CanisterHttpResponse {
+                id: response.id,
+                timeout: context.time + CANISTER_HTTP_TIMEOUT_INTERVAL,
+                content: self.transform(context, response, Arc::clone(&state)),
+            };
+            let metadata = CanisterHttpResponseMetadata {
+                id: response.id,
+                timeout: response.timeout,
+                content_hash: ic_crypto::crypto_hash(&response),
+                registry_version: self.registry_client.get_latest_version(),
+            };
+            match self
+                .crypto
+                .sign_basic(&metadata, self.node_id, metadata.registry_version)
+            {
+                Ok(signature) => {
+                    let share = Signed {
+                        content: metadata,
+                        signature: BasicSignature {
+                            signature,
+                            signer: self.node_id,
+                        },
+                    };
+                    change_set.push(CanisterHttpChangeAction::AddToValidated(share, response));
+                }
+                Err(err) => warn!(
+                    self.log,
+                    "Failed to sign canister http response {}: {:?}", response.id, err
+                ),
+            }
+        }
+        change_set
+    }
+
+    // Validates the shares received from the peers. Shares for requests this
+    // node does not know about yet, or signed at a registry version it does
+    // not have yet, are left in the unvalidated section.
+    fn validate_shares(
+        &self,
+        canister_http_pool: &dyn CanisterHttpPool,
+        contexts: &BTreeMap<CallbackId, CanisterHttpRequestContext>,
+        next_callback_id: CallbackId,
+    ) -> CanisterHttpChangeSet {
+        let mut signed: BTreeSet<(CallbackId, NodeId)> = canister_http_pool
+            .get_validated_shares()
+            .map(|share| (share.content.id, share.signature.signer))
+            .collect();
+        let mut change_set = Vec::new();
+        for share in canister_http_pool.get_unvalidated_shares() {
+            let share_id = ic_crypto::crypto_hash(share);
+            let context = match contexts.get(&share.content.id) {
+                Some(context) => context,
+                None if share.content.id < next_callback_id => {
+                    change_set.push(CanisterHttpChangeAction::RemoveUnvalidated(share_id));
+                    continue;
+                }
+                None => continue,
+            };
+            let members = match get_subnet_members(
+                &*self.registry_client,
+                self.subnet_id,
+                share.content.registry_version,
+            ) {
+                Some(members) => members,
+                None => continue,
+            };
+            if let Err(reason) = self.check_share(share, context, &members, &signed) {
+                change_set.push(CanisterHttpChangeAction::HandleInvalid(share_id, reason));
+                continue;
+            }
+            signed.insert((share.content.id, share.signature.signer));
+            change_set.push(CanisterHttpChangeAction::MoveToValidated(share_id));
+        }
+        change_set
+    }
+
+    fn check_share(
+        &self,
+        share: &CanisterHttpResponseShare,
+        context: &CanisterHttpRequestContext,
+        members: &BTreeSet<NodeId>,
+        signed: &BTreeSet<(CallbackId, NodeId)>,
+    ) -> Result<(), String> {
+        let signer = share.signature.signer;
+        if !members.contains(&signer) {
+            return Err(format!("Signer {} is not a member of the subnet", signer));
+        }
+        if signed.contains(&(share.content.id, signer)) {
+            return Err(format!(
+                "Signer {} already signed a response to request {}",
+                signer, share.content.id
+            ));
+        }
+        if share.content.timeout != context.time + CANISTER_HTTP_TIMEOUT_INTERVAL {
+            return Err(format!(
+                "Timeout {} does not match the one of request {}",
+                share.content.timeout, share.content.id
+            ));
+        }
+        self.crypto
+            .verify_basic_sig(
+                &share.signature.signature,
+                &share.content,
+                signer,
+                share.content.registry_version,
+            )
+            .map_err(|err| format!("Invalid signature: {:?}", err))
+    }
+
+    // Removes the shares and responses of the requests that were answered.
+    fn purge_shares(
+        &self,
+        canister_http_pool: &dyn CanisterHttpPool,
+        contexts: &BTreeMap<CallbackId, CanisterHttpRequestContext>,
+    ) -> CanisterHttpChangeSet {
+        let mut change_set = Vec::new();
+        for share in canister_http_pool.get_validated_shares() {
+            if contexts.contains_key(&share.content.id) {
+                continue;
+            }
+            if share.signature.signer == self.node_id {
+                change_set.push(CanisterHttpChangeAction::RemoveContent(
+                    share.content.content_hash.clone(),
+                ));
+            }
+            change_set.push(CanisterHttpChangeAction::RemoveValidated(
+                ic_crypto::crypto_hash(share),
+            ));
+        }
+        change_set
+    }
+
+    // Applies the transform function of the calling canister, if any, to the
+    // response received from the adapter.
+    fn transform(
+        &self,
+        context: &CanisterHttpRequestContext,
+        response: CanisterHttpAdapterResponse,
+        state: Arc<ReplicatedState>,
+    ) -> CanisterHttpResponseContent {
+        let payload = match response.content {
+            Ok(payload) => payload,
+            Err(reject) => return CanisterHttpResponseContent::Reject(reject),
+        };
+        let method_name = match &context.transform_method_name {
+            Some(method_name) => method_name.clone(),
+            None => return CanisterHttpResponseContent::Success(payload.encode()),
+        };
+
+        let query = UserQuery {
+            source: UserId::from(IC_00.get()),
+            receiver: context.request.sender,
+            method_name,
+            method_payload: payload.encode(),
+            ingress_expiry: 0,
+            nonce: None,
+        };
+        match self.query_handler.query(query, state, vec![]) {
+            Ok(WasmResult::Reply(data)) => {
+                if data.len() as u64 > MAX_CANISTER_HTTP_RESPONSE_BYTES {
+                    return reject(
+                        RejectCode::SysFatal,
+                        format!(
+                            "Transformed http response exceeds limit: {}",
+                            MAX_CANISTER_HTTP_RESPONSE_BYTES
+                        ),
+                    );
+                }
+                match CanisterHttpResponsePayload::decode(&data) {
+                    Ok(_) => CanisterHttpResponseContent::Success(data),
+                    Err(err) => reject(
+                        RejectCode::CanisterError,
+                        format!("Failed to decode transformed http response: {}", err),
+                    ),
+                }
+            }
+            Ok(WasmResult::Reject(message)) => reject(RejectCode::CanisterReject, message),
+            Err(err) => reject(err.reject_code(), err.description().to_string()),
+        }
+    }
+}
+
+fn reject(reject_code: RejectCode, message: String) -> CanisterHttpResponseContent {
+    CanisterHttpResponseContent::Reject(CanisterHttpReject {
+        reject_code,
+        message,
+    })
+}
+
+impl CanisterHttpPoolManager for CanisterHttpPoolManagerImpl {
+    fn on_state_change(&self, canister_http_pool: &dyn CanisterHttpPool) -> CanisterHttpChangeSet {
+        let state = self.state_manager.get_latest_state().take();
+        let manager = &state.metadata.subnet_call_context_manager;
+        let contexts = &manager.canister_http_request_contexts;
+
+        self.send_requests(contexts);
+        let mut change_set = self.sign_responses(contexts, Arc::clone(&state));
+        change_set.extend(self.validate_shares(
+            canister_http_pool,
+            contexts,
+            manager.next_callback_id(),
+        ));
+        change_set.extend(self.purge_shares(canister_http_pool, contexts));
+        change_set
+    }
+}
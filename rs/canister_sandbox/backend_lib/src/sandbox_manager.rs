@@ -120,6 +120,7 @@ impl Execution {
                 wasm_result,
                 num_instructions_left,
                 instance_stats,
+                reads_transient_state,
             },
             deltas,
             // This field isn't used, but we want to ensure that it is not
@@ -171,6 +172,7 @@ impl Execution {
                     wasm_result,
                     num_instructions_left,
                     instance_stats,
+                    reads_transient_state,
                 };
                 self.sandbox_manager.controller.execution_finished(
                     protocol::ctlsvc::ExecutionFinishedRequest {
@@ -189,6 +191,7 @@ impl Execution {
                     wasm_result: Err(err),
                     num_instructions_left,
                     instance_stats,
+                    reads_transient_state,
                 };
 
                 self.sandbox_manager.controller.execution_finished(
@@ -135,6 +135,8 @@ mod tests {
             user_test_id(0).get(),
             CanisterStatusView::Running,
             SubnetType::Application,
+            0,
+            None,
         )
     }
 
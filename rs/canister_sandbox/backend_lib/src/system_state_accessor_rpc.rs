@@ -12,7 +12,7 @@ use ic_system_api::SystemStateAccessor;
 use ic_types::{
     messages::{CallContextId, CallbackId},
     methods::Callback,
-    ComputeAllocation, Cycles, NumBytes,
+    ComputeAllocation, Cycles, NumBytes, PrincipalId, Time,
 };
 
 use std::sync::Arc;
@@ -118,6 +118,18 @@ impl SystemStateAccessor for SystemStateAccessorRPC {
         }
     }
 
+    fn is_controller(&self, principal_id: &PrincipalId) -> bool {
+        let reply = self.make_call(protocol::syscall::Request::IsController(
+            protocol::syscall::IsControllerRequest {
+                principal_id: *principal_id,
+            },
+        ));
+        match reply {
+            protocol::syscall::Reply::IsController(rep) => rep.result,
+            _ => unimplemented!(),
+        }
+    }
+
     fn set_certified_data(&self, data: Vec<u8>) {
         let reply = self.make_call(protocol::syscall::Request::SetCertifiedData(
             protocol::syscall::SetCertifiedDataRequest { data },
@@ -128,6 +140,26 @@ impl SystemStateAccessor for SystemStateAccessorRPC {
         }
     }
 
+    fn set_global_timer(&self, timer: Option<Time>) -> Option<Time> {
+        let reply = self.make_call(protocol::syscall::Request::SetGlobalTimer(
+            protocol::syscall::SetGlobalTimerRequest { timer },
+        ));
+        match reply {
+            protocol::syscall::Reply::SetGlobalTimer(rep) => rep.previous_timer,
+            _ => unimplemented!(),
+        }
+    }
+
+    fn append_canister_log(&self, time: Time, content: Vec<u8>) {
+        let reply = self.make_call(protocol::syscall::Request::AppendCanisterLog(
+            protocol::syscall::AppendCanisterLogRequest { time, content },
+        ));
+        match reply {
+            protocol::syscall::Reply::AppendCanisterLog(_rep) => {}
+            _ => unimplemented!(),
+        }
+    }
+
     fn register_callback(&self, callback: Callback) -> CallbackId {
         let reply = self.make_call(protocol::syscall::Request::RegisterCallback(
             protocol::syscall::RegisterCallbackRequest { callback },
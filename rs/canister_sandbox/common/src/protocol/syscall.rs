@@ -3,7 +3,7 @@ use ic_replicated_state::{canister_state::system_state::CanisterStatus, StateErr
 use ic_types::{
     messages::{CallContextId, CallbackId},
     methods::Callback,
-    CanisterId, ComputeAllocation, Cycles, NumBytes, NumInstructions, PrincipalId,
+    CanisterId, ComputeAllocation, Cycles, NumBytes, NumInstructions, PrincipalId, Time,
 };
 use serde::{Deserialize, Serialize};
 
@@ -145,6 +145,15 @@ pub struct CanisterCyclesRefundRequest {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CanisterCyclesRefundReply {}
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IsControllerRequest {
+    pub principal_id: PrincipalId,
+}
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IsControllerReply {
+    pub result: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SetCertifiedDataRequest {
     pub data: Vec<u8>,
@@ -152,6 +161,23 @@ pub struct SetCertifiedDataRequest {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SetCertifiedDataReply {}
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetGlobalTimerRequest {
+    pub timer: Option<Time>,
+}
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetGlobalTimerReply {
+    pub previous_timer: Option<Time>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppendCanisterLogRequest {
+    pub time: Time,
+    pub content: Vec<u8>,
+}
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppendCanisterLogReply {}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct RegisterCallbackRequest {
     pub callback: Callback,
@@ -202,7 +228,10 @@ pub enum Request {
     CanisterCyclesBalance(CanisterCyclesBalanceRequest),
     CanisterCyclesWithdraw(CanisterCyclesWithdrawRequest),
     CanisterCyclesRefund(CanisterCyclesRefundRequest),
+    IsController(IsControllerRequest),
     SetCertifiedData(SetCertifiedDataRequest),
+    SetGlobalTimer(SetGlobalTimerRequest),
+    AppendCanisterLog(AppendCanisterLogRequest),
     RegisterCallback(RegisterCallbackRequest),
     UnregisterCallback(UnregisterCallbackRequest),
     PushOutputMessage(PushOutputMessageRequest),
@@ -215,7 +244,10 @@ pub enum Reply {
     CanisterCyclesBalance(CanisterCyclesBalanceReply),
     CanisterCyclesWithdraw(CanisterCyclesWithdrawReply),
     CanisterCyclesRefund(CanisterCyclesRefundReply),
+    IsController(IsControllerReply),
     SetCertifiedData(SetCertifiedDataReply),
+    SetGlobalTimer(SetGlobalTimerReply),
+    AppendCanisterLog(AppendCanisterLogReply),
     RegisterCallback(RegisterCallbackReply),
     UnregisterCallback(UnregisterCallbackReply),
     PushOutputMessage(PushOutputMessageReply),
@@ -312,6 +312,7 @@ mod tests {
                             accessed_pages: 0,
                             dirty_pages: 0,
                         },
+                        reads_transient_state: false,
                     },
                     state: None,
                     execute_total_duration: std::time::Duration::from_secs(0),
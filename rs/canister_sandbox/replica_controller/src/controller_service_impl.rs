@@ -141,10 +141,22 @@ impl ControllerService for ControllerServiceImpl {
                             system_state_accessor.canister_cycles_refund(req.cycles);
                             Reply::CanisterCyclesRefund(CanisterCyclesRefundReply {})
                         }
+                        Request::IsController(req) => {
+                            let result = system_state_accessor.is_controller(&req.principal_id);
+                            Reply::IsController(IsControllerReply { result })
+                        }
                         Request::SetCertifiedData(req) => {
                             system_state_accessor.set_certified_data(req.data);
                             Reply::SetCertifiedData(SetCertifiedDataReply {})
                         }
+                        Request::SetGlobalTimer(req) => {
+                            let previous_timer = system_state_accessor.set_global_timer(req.timer);
+                            Reply::SetGlobalTimer(SetGlobalTimerReply { previous_timer })
+                        }
+                        Request::AppendCanisterLog(req) => {
+                            system_state_accessor.append_canister_log(req.time, req.content);
+                            Reply::AppendCanisterLog(AppendCanisterLogReply {})
+                        }
                         Request::RegisterCallback(req) => {
                             let result = system_state_accessor.register_callback(req.callback);
                             Reply::RegisterCallback(RegisterCallbackReply { result })
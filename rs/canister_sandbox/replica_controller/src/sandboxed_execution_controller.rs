@@ -289,6 +289,7 @@ impl SandboxedExecutionController {
                             accessed_pages: 0,
                             dirty_pages: 0,
                         },
+                        reads_transient_state: false,
                     },
                     execution_state,
                     system_state_accessor,
@@ -21,7 +21,7 @@ use ic_types::{
     messages::{CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response},
     user_error::RejectCode,
     xnet::StreamHeader,
-    CryptoHashOfPartialState, Cycles, Funds,
+    CryptoHashOfPartialState, Cycles, Funds, Time,
 };
 use serde::{Deserialize, Serialize};
 use serde_cbor::value::Value;
@@ -32,6 +32,8 @@ const CERTIFICATION_VERSION_3: u32 = 3;
 /// Added optional `Request::cycles_payment` and `Response::cycles_refund`
 /// fields that are not yet populated.
 const CERTIFICATION_VERSION_4: u32 = 4;
+/// Added optional `Request::deadline` field for best-effort calls.
+const CERTIFICATION_VERSION_7: u32 = 7;
 
 //
 // Tests for exact binary encoding
@@ -200,6 +202,100 @@ fn canonical_encoding_request_u128() {
     );
 }
 
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Request(
+///     Request {
+///         receiver: canister_test_id(1),
+///         sender: canister_test_id(2),
+///         sender_reply_callback: CallbackId::from(3),
+///         payment: Cycles::new(4),
+///         method_name: "test".to_string(),
+///         method_payload: vec![6],
+///         deadline: Some(Time::from_nanos_since_unix_epoch(7)),
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    00                         # field_index(RequestOrResponse::request)
+///    A7                         # map(7)
+///       00                      # field_index(Request::receiver)
+///       4A                      # bytes(10)
+///          00000000000000010101 # "\x00\x00\x00\x00\x00\x00\x00\x01\x01\x01"
+///       01                      # field_index(Request::sender)
+///       4A                      # bytes(10)
+///          00000000000000020101 # "\x00\x00\x00\x00\x00\x00\x00\x02\x01\x01"
+///       02                      # field_index(Request::sender_reply_callback)
+///       03                      # unsigned(3)
+///       03                      # field_index(Request::payment)
+///       A1                      # map(1)
+///          00                   # field_index(Funds::cycles)
+///          A1                   # map(1)
+///             00                # field_index(Cycles::raw)
+///             04                # unsigned(4)
+///       04                      # field_index(Request::method_name)
+///       64                      # text(4)
+///          74657374             # "test"
+///       05                      # field_index(Request::method_payload)
+///       41                      # bytes(1)
+///          06                   # "\x06"
+///       07                      # field_index(Request::deadline)
+///       07                      # unsigned(7)
+/// Used http://cbor.me/ for printing the human friendly output.
+/// ```
+#[test]
+fn canonical_encoding_request_with_deadline() {
+    let request = RequestOrResponse::Request(
+        RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .sender_reply_callback(CallbackId::from(3))
+            .payment(Cycles::new(4))
+            .method_name("test".to_string())
+            .method_payload(vec![6])
+            .deadline(Some(Time::from_nanos_since_unix_epoch(7)))
+            .build(),
+    );
+
+    assert_eq!(
+        "A1 00 A7 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 41 06 07 07",
+        as_hex(&encode_message(&request, CERTIFICATION_VERSION_7))
+    );
+}
+
+#[test]
+fn request_deadline_is_only_encoded_from_certification_version_7() {
+    let request_with_deadline = RequestOrResponse::Request(
+        RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .deadline(Some(Time::from_nanos_since_unix_epoch(7)))
+            .build(),
+    );
+    let request_without_deadline = RequestOrResponse::Request(
+        RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .build(),
+    );
+
+    for certification_version in 0..CERTIFICATION_VERSION_7 {
+        assert_eq!(
+            encode_message(&request_without_deadline, certification_version),
+            encode_message(&request_with_deadline, certification_version)
+        );
+    }
+    assert_ne!(
+        encode_message(&request_without_deadline, CERTIFICATION_VERSION_7),
+        encode_message(&request_with_deadline, CERTIFICATION_VERSION_7)
+    );
+}
+
 /// Canonical CBOR encoding of:
 ///
 /// ```no_run
@@ -570,7 +666,7 @@ fn invalid_request_extra_field() {
             assert_matches!(
                 res,
                 Err(ProxyDecodeError::CborDecodeError(err))
-                    if err.to_string().contains("expected field index 0 <= i < 7")
+                    if err.to_string().contains("expected field index 0 <= i < 8")
             );
         }
     }
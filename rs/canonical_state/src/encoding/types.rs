@@ -50,6 +50,8 @@ pub struct Request {
     pub method_payload: Bytes,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_payment: Option<Cycles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
 }
 
 /// Canonical representation of `ic_types::messages::Response`.
@@ -190,6 +192,13 @@ impl From<(&ic_types::messages::Request, u32)> for Request {
             method_name: request.method_name.clone(),
             method_payload: request.method_payload.clone(),
             cycles_payment: None,
+            deadline: if certification_version > 6 {
+                request
+                    .deadline
+                    .map(|deadline| deadline.as_nanos_since_unix_epoch())
+            } else {
+                None
+            },
         }
     }
 }
@@ -211,6 +220,9 @@ impl TryFrom<Request> for ic_types::messages::Request {
             payment,
             method_name: request.method_name,
             method_payload: request.method_payload,
+            deadline: request
+                .deadline
+                .map(ic_types::Time::from_nanos_since_unix_epoch),
         })
     }
 }
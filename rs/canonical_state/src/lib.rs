@@ -10,6 +10,7 @@
 //!      fields that are not yet populated.
 //!   5. Added support for custom canister metadata sections.
 //!   6. Encoding of canister metadata sections.
+//!   7. Added optional `Request::deadline` field for best-effort calls.
 
 pub mod encoding;
 pub mod hash_tree;
@@ -37,7 +38,7 @@ pub use visitor::{Control, Visitor};
 /// For virtually all certification version changes must be bumped at least one
 /// release before bumping `CURRENT_CERTIFICATION_VERSION` in order to ensure
 /// forwards compatibility in the case of a replica downgrade.
-pub const MAX_SUPPORTED_CERTIFICATION_VERSION: u32 = 6;
+pub const MAX_SUPPORTED_CERTIFICATION_VERSION: u32 = 7;
 
 /// The Canonical State certification version that should be used for newly
 /// computed states.
@@ -21,12 +21,17 @@ pub(crate) const MAX_CUSTOM_SECTION_SIZE: NumBytes = NumBytes::new(65536);
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct FeatureFlags {
     pub api_cycles_u128_flag: FeatureStatus,
+    /// Accept modules using the fixed-width SIMD proposal (`v128`
+    /// instructions). Bulk memory instructions are always accepted since
+    /// they are metered by their length.
+    pub wasm_simd: FeatureStatus,
 }
 
 impl Default for FeatureFlags {
     fn default() -> Self {
         Self {
             api_cycles_u128_flag: FeatureStatus::Enabled,
+            wasm_simd: FeatureStatus::Disabled,
         }
     }
 }
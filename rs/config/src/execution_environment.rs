@@ -37,6 +37,19 @@ const SUBNET_MEMORY_CAPACITY: NumBytes = NumBytes::new(300 * GB);
 /// memory can succeed.
 pub(crate) const SUBNET_HEAP_DELTA_CAPACITY: NumBytes = NumBytes::new(200 * GB);
 
+/// The maximum depth of a query call graph, i.e. the maximum number of nested
+/// calls a composite query can make, counting from the query sent by the
+/// user.
+const MAX_QUERY_CALL_DEPTH: usize = 6;
+
+/// The total number of instructions that all executions in a single query call
+/// graph can use together. Each individual execution is still limited by the
+/// per-message query limit.
+const MAX_QUERY_CALL_GRAPH_INSTRUCTIONS: NumInstructions = NumInstructions::new(50_000_000_000);
+
+/// The total size of the query results kept in the query cache of a replica.
+const QUERY_CACHE_CAPACITY: NumBytes = NumBytes::new(100 * 1024 * 1024);
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default)]
 pub struct Config {
@@ -72,6 +85,20 @@ pub struct Config {
 
     /// Indicates whether canisters sandboxing is enabled or not.
     pub canister_sandboxing_flag: FeatureStatus,
+
+    /// Indicates whether canisters may use the Wasm SIMD instructions.
+    pub wasm_simd: FeatureStatus,
+
+    /// The maximum depth of the call graph of a (composite) query.
+    pub max_query_call_depth: usize,
+
+    /// The maximum number of instructions that all executions of a (composite)
+    /// query call graph can use together.
+    pub max_query_call_graph_instructions: NumInstructions,
+
+    /// The maximum total size of the results kept in the query cache. Setting
+    /// it to zero disables the cache.
+    pub query_cache_capacity: NumBytes,
 }
 
 impl Default for Config {
@@ -96,6 +123,10 @@ impl Default for Config {
             max_controllers: 10,
             // Change this value to enable/disable canister sandboxing by default.
             canister_sandboxing_flag: FeatureStatus::Disabled,
+            wasm_simd: FeatureStatus::Disabled,
+            max_query_call_depth: MAX_QUERY_CALL_DEPTH,
+            max_query_call_graph_instructions: MAX_QUERY_CALL_GRAPH_INSTRUCTIONS,
+            query_cache_capacity: QUERY_CACHE_CAPACITY,
         }
     }
 }
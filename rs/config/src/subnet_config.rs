@@ -210,6 +210,13 @@ pub struct CyclesAccountManagerConfig {
 
     /// How often to charge canisters for memory and compute allocations.
     pub duration_between_allocation_charges: Duration,
+
+    /// Baseline fee for every canister HTTP request.
+    pub http_request_baseline_fee: Cycles,
+
+    /// Fee for every byte of a canister HTTP request, as well as for every
+    /// byte of the maximum response size the canister specified.
+    pub http_request_per_byte_fee: Cycles,
 }
 
 impl CyclesAccountManagerConfig {
@@ -234,6 +241,8 @@ impl CyclesAccountManagerConfig {
             // 4 SDR per GiB per year => 4e12 Cycles per year
             gib_storage_per_second_fee: Cycles::new(127_000),
             duration_between_allocation_charges: Duration::from_secs(10),
+            http_request_baseline_fee: Cycles::new(400_000_000),
+            http_request_per_byte_fee: Cycles::new(100_000),
         }
     }
 
@@ -250,6 +259,8 @@ impl CyclesAccountManagerConfig {
             ingress_byte_reception_fee: Cycles::new(0),
             gib_storage_per_second_fee: Cycles::new(0),
             duration_between_allocation_charges: Duration::from_secs(10),
+            http_request_baseline_fee: Cycles::new(0),
+            http_request_per_byte_fee: Cycles::new(0),
         }
     }
 }
@@ -32,6 +32,7 @@ use ic_metrics::MetricsRegistry;
 use ic_registry_subnet_type::SubnetType;
 use ic_state_manager::StateManagerImpl;
 use ic_test_utilities::{
+    canister_http_payload_builder::FakeCanisterHttpPayloadBuilder,
     consensus::{fake::*, make_genesis, MockConsensusCache},
     crypto::temp_crypto_component_with_fake_registry,
     cycles_account_manager::CyclesAccountManagerBuilder,
@@ -46,6 +47,7 @@ use ic_test_utilities::{
 };
 use ic_types::{
     batch::{BatchPayload, IngressPayload, SelfValidatingPayload, ValidationContext, XNetPayload},
+    canister_http::CanisterHttpPayload,
     consensus::certification::*,
     consensus::*,
     crypto::Signed,
@@ -146,6 +148,7 @@ where
             ingress_manager,
             Arc::new(FakeXNetPayloadBuilder::new()),
             Arc::new(FakeSelfValidatingPayloadBuilder::new()),
+            Arc::new(FakeCanisterHttpPayloadBuilder::new()),
             metrics_registry,
             no_op_logger(),
         ));
@@ -248,7 +251,12 @@ fn add_past_blocks(
         block.payload = Payload::new(
             ic_crypto::crypto_hash,
             (
-                BatchPayload::new(ingress, xnet, self_validating),
+                BatchPayload::new(
+                    ingress,
+                    xnet,
+                    self_validating,
+                    CanisterHttpPayload::default(),
+                ),
                 dkg::Dealings::new_empty(block.payload.as_ref().dkg_interval_start_height()),
                 None,
             )
@@ -313,7 +321,12 @@ fn validate_payload_benchmark(criterion: &mut Criterion) {
                 let payload = Payload::new(
                     ic_crypto::crypto_hash,
                     (
-                        BatchPayload::new(ingress, xnet, self_validating),
+                        BatchPayload::new(
+                            ingress,
+                            xnet,
+                            self_validating,
+                            CanisterHttpPayload::default(),
+                        ),
                         dkg::Dealings::new_empty(tip.payload.as_ref().dkg_interval_start_height()),
                         None,
                     )
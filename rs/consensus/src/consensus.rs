@@ -49,6 +49,7 @@ use crate::consensus::{
 };
 use ic_config::consensus::ConsensusConfig;
 use ic_interfaces::{
+    canister_http::CanisterHttpPayloadBuilder,
     consensus::{Consensus, ConsensusGossip},
     consensus_pool::ConsensusPool,
     dkg::DkgPool,
@@ -134,6 +135,7 @@ impl ConsensusImpl {
         ingress_selector: Arc<dyn IngressSelector>,
         xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
         self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+        canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
         dkg_pool: Arc<RwLock<dyn DkgPool>>,
         ecdsa_pool: Arc<RwLock<dyn EcdsaPool>>,
         dkg_key_manager: Arc<Mutex<DkgKeyManager>>,
@@ -152,6 +154,7 @@ impl ConsensusImpl {
             ingress_selector.clone(),
             xnet_payload_builder,
             self_validating_payload_builder,
+            canister_http_payload_builder,
             metrics_registry.clone(),
             logger.clone(),
         ));
@@ -584,6 +587,7 @@ pub fn setup(
     ingress_selector: Arc<dyn IngressSelector>,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
     dkg_pool: Arc<RwLock<dyn DkgPool>>,
     ecdsa_pool: Arc<RwLock<dyn EcdsaPool>>,
     dkg_key_manager: Arc<Mutex<DkgKeyManager>>,
@@ -617,6 +621,7 @@ pub fn setup(
             ingress_selector,
             xnet_payload_builder,
             self_validating_payload_builder,
+            canister_http_payload_builder,
             dkg_pool,
             ecdsa_pool,
             dkg_key_manager,
@@ -644,6 +649,7 @@ mod tests {
     use ic_registry_subnet_type::SubnetType;
     use ic_test_artifact_pool::ingress_pool::TestIngressPool;
     use ic_test_utilities::{
+        canister_http_payload_builder::FakeCanisterHttpPayloadBuilder,
         ingress_selector::FakeIngressSelector,
         message_routing::FakeMessageRouting,
         registry::{FakeLocalStoreCertifiedTimeReader, SubnetRecordBuilder},
@@ -708,6 +714,7 @@ mod tests {
             Arc::new(FakeIngressSelector::new()),
             Arc::new(FakeXNetPayloadBuilder::new()),
             Arc::new(FakeSelfValidatingPayloadBuilder::new()),
+            Arc::new(FakeCanisterHttpPayloadBuilder::new()),
             dkg_pool,
             ecdsa_pool,
             Arc::new(Mutex::new(DkgKeyManager::new(
@@ -16,18 +16,20 @@ use ic_interfaces::{
 use ic_logger::{debug, info, trace, warn, ReplicaLogger};
 use ic_protobuf::log::consensus_log_entry::v1::ConsensusLogEntry;
 use ic_protobuf::registry::crypto::v1::PublicKey as PublicKeyProto;
+use ic_registry_client::helper::subnet::SubnetRegistry;
 use ic_replicated_state::{metadata_state::subnet_call_context_manager::*, ReplicatedState};
 use ic_types::{
-    crypto::threshold_sig::ni_dkg::{
-        NiDkgId, NiDkgTag, NiDkgTargetSubnet::Remote, NiDkgTranscript,
+    consensus::ecdsa::CompletedSignature,
+    crypto::{
+        canister_threshold_sig::EcdsaPublicKey,
+        threshold_sig::ni_dkg::{NiDkgId, NiDkgTag, NiDkgTargetSubnet::Remote, NiDkgTranscript},
     },
-    ic00::SetupInitialDKGResponse,
+    ic00::{EcdsaKeyId, SetupInitialDKGResponse},
     messages::{CallbackId, Response},
     CountBytes, ReplicaVersion,
 };
-use secp256k1::{Message, Secp256k1, SecretKey};
 use std::collections::BTreeMap;
-use std::time::Duration;
+use std::convert::TryFrom;
 
 /// Deliver all finalized blocks from
 /// `message_routing.expected_batch_height` to `finalized_height` via
@@ -92,7 +94,6 @@ pub fn deliver_batches(
                         &*state_manager,
                         block.context.certified_height,
                         summary.dkg.transcripts_for_new_subnets(),
-                        block.context.time,
                         log,
                     );
                 }
@@ -121,6 +122,18 @@ pub fn deliver_batches(
                     }
                 }
 
+                if !block.payload.is_summary() {
+                    if let Some(ecdsa) = &block.payload.as_ref().as_data().ecdsa {
+                        consensus_responses.append(
+                            &mut generate_responses_to_sign_with_ecdsa_calls(
+                                &ecdsa.signature_agreements,
+                            ),
+                        );
+                    }
+                }
+                let ecdsa_subnet_public_keys =
+                    get_ecdsa_subnet_public_keys(pool, registry_client, subnet_id, &block, log);
+
                 let block_hash = get_block_hash_string(&block);
                 let block_height = block.height().get();
 
@@ -140,6 +153,7 @@ pub fn deliver_batches(
                     registry_version: block.context.registry_version,
                     time: block.context.time,
                     consensus_responses,
+                    ecdsa_subnet_public_keys,
                 };
                 let batch_height = batch.batch_number.get();
                 let ingress_count = batch.payload.ingress.message_count();
@@ -196,7 +210,6 @@ pub fn generate_responses_to_subnet_calls(
     state_manager: &dyn StateManager<State = ReplicatedState>,
     certified_height: Height,
     transcripts_for_new_subnets: &BTreeMap<NiDkgId, Result<NiDkgTranscript, String>>,
-    batch_time: Time,
     log: &ReplicaLogger,
 ) -> Vec<Response> {
     let mut consensus_responses = Vec::<Response>::new();
@@ -211,15 +224,6 @@ pub fn generate_responses_to_subnet_calls(
             transcripts_for_new_subnets,
             log,
         ));
-        let sign_with_mock_ecdsa_contexts = &state
-            .get_ref()
-            .metadata
-            .subnet_call_context_manager
-            .sign_with_mock_ecdsa_contexts;
-        consensus_responses.append(&mut generate_responses_to_sign_with_mock_ecdsa_calls(
-            sign_with_mock_ecdsa_contexts,
-            batch_time,
-        ));
     }
     consensus_responses
 }
@@ -315,33 +319,93 @@ pub fn generate_responses_to_setup_initial_dkg_calls(
     consensus_responses
 }
 
-const MOCK_ECDSA_DELAY_MILLIS: u64 = 30000;
-/// This function creates responses to the SignWithMockECDSA system calls with
-/// the computed MOCK(!) signature.
-pub fn generate_responses_to_sign_with_mock_ecdsa_calls(
-    contexts: &BTreeMap<CallbackId, SignWithEcdsaContext>,
-    batch_time: Time,
+/// This function returns the responses to the SignWithECDSA system calls
+/// whose signatures were agreed upon in the given block and not yet
+/// delivered to execution.
+pub fn generate_responses_to_sign_with_ecdsa_calls(
+    signature_agreements: &BTreeMap<ic_types::consensus::ecdsa::RequestId, CompletedSignature>,
 ) -> Vec<Response> {
-    let mut consensus_responses = Vec::<Response>::new();
-    if contexts.is_empty() {
-        return consensus_responses;
-    }
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
+    signature_agreements
+        .values()
+        .filter_map(|signature| match signature {
+            CompletedSignature::Unreported(response) => Some(response.clone()),
+            CompletedSignature::ReportedToExecution => None,
+        })
+        .collect()
+}
 
-    for (callback_id, context) in contexts.iter() {
-        if batch_time > context.batch_time + Duration::from_millis(MOCK_ECDSA_DELAY_MILLIS) {
-            let hash = Message::from_slice(&context.message_hash).unwrap();
-            let sig = secp.sign(&hash, &secret_key);
-            let response_payload = messages::Payload::Data(sig.serialize_compact().to_vec());
-            consensus_responses.push(Response {
-                originator: CanisterId::ic_00(),
-                respondent: CanisterId::ic_00(),
-                originator_reply_callback: *callback_id,
-                refund: Cycles::zero(),
-                response_payload,
-            });
+/// This function returns the ECDSA public keys of the subnet, derived from
+/// the key transcript of the summary block that governs the given block.
+/// The keys are indexed by the key ids of the subnet's ECDSA config. The
+/// subnet holds a single key transcript, so all configured key ids map to
+/// the same public key.
+fn get_ecdsa_subnet_public_keys(
+    pool: &PoolReader<'_>,
+    registry_client: &dyn RegistryClient,
+    subnet_id: SubnetId,
+    block: &Block,
+    log: &ReplicaLogger,
+) -> BTreeMap<EcdsaKeyId, EcdsaPublicKey> {
+    let mut public_keys = BTreeMap::new();
+    let summary_block = match pool.dkg_summary_block(block) {
+        Some(summary_block) => summary_block,
+        None => return public_keys,
+    };
+    let transcript = match summary_block
+        .payload
+        .as_ref()
+        .as_summary()
+        .ecdsa
+        .as_ref()
+        .and_then(|ecdsa| ecdsa.current_ecdsa_transcript.as_ref())
+    {
+        Some(transcript) => transcript.clone().into_base_type(),
+        None => return public_keys,
+    };
+    let key_ids: Vec<EcdsaKeyId> = registry_client
+        .get_ecdsa_config(subnet_id, block.context.registry_version)
+        .ok()
+        .flatten()
+        .flatten()
+        .map(|config| config.key_ids)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|key_id| match EcdsaKeyId::try_from(key_id) {
+            Ok(key_id) => Some(key_id),
+            Err(err) => {
+                warn!(
+                    every_n_seconds => 5,
+                    log,
+                    "Failed to parse ECDSA key id of subnet {}: {:?}",
+                    subnet_id,
+                    err
+                );
+                None
+            }
+        })
+        .collect();
+    if key_ids.is_empty() {
+        warn!(
+            every_n_seconds => 5,
+            log,
+            "No ECDSA key id found for subnet {} at registry version {}",
+            subnet_id,
+            block.context.registry_version
+        );
+        return public_keys;
+    }
+    match ic_crypto::get_tecdsa_master_public_key(&transcript) {
+        Ok(public_key) => {
+            for key_id in key_ids {
+                public_keys.insert(key_id, public_key.clone());
+            }
         }
+        Err(err) => warn!(
+            every_n_seconds => 5,
+            log,
+            "Failed to get the ECDSA master public key: {:?}",
+            err
+        ),
     }
-    consensus_responses
+    public_keys
 }
@@ -603,6 +603,7 @@ mod tests {
                     payment: Cycles::zero(),
                     method_name: "".to_string(),
                     method_payload: vec![],
+                    deadline: None,
                 },
                 nodes_in_target_subnet: BTreeSet::new(),
                 target_id: TARGET_ID,
@@ -651,7 +652,6 @@ mod tests {
             &state_manager,
             Height::from(1),
             &transcripts_for_new_subnets,
-            ic_test_utilities::mock_time(),
             &no_op_logger(),
         );
         assert_eq!(result.len(), 1);
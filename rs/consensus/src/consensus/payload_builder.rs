@@ -2,6 +2,7 @@
 
 use crate::consensus::metrics::PayloadBuilderMetrics;
 use ic_interfaces::{
+    canister_http::CanisterHttpPayloadBuilder,
     consensus::{
         PayloadBuilderError, PayloadPermanentError, PayloadTransientError, PayloadValidationError,
     },
@@ -18,6 +19,7 @@ use ic_registry_client::helper::subnet::SubnetRegistry;
 use ic_types::{
     artifact::IngressMessageId,
     batch::{BatchPayload, SelfValidatingPayload, ValidationContext, XNetPayload},
+    canister_http::CanisterHttpPayload,
     consensus::{BlockPayload, Payload},
     crypto::CryptoHashOf,
     messages::MAX_XNET_PAYLOAD_IN_BYTES,
@@ -96,6 +98,7 @@ pub struct PayloadBuilderImpl {
     ingress_selector: Arc<dyn IngressSelector>,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
     metrics: PayloadBuilderMetrics,
     ingress_payload_cache: RwLock<IngressPayloadCache>,
     logger: ReplicaLogger,
@@ -109,6 +112,7 @@ impl PayloadBuilderImpl {
         ingress_selector: Arc<dyn IngressSelector>,
         xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
         self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+        canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
         metrics: MetricsRegistry,
         logger: ReplicaLogger,
     ) -> Self {
@@ -118,6 +122,7 @@ impl PayloadBuilderImpl {
             ingress_selector,
             xnet_payload_builder,
             self_validating_payload_builder,
+            canister_http_payload_builder,
             metrics: PayloadBuilderMetrics::new(metrics),
             ingress_payload_cache: RwLock::new(BTreeMap::new()),
             logger,
@@ -147,7 +152,7 @@ impl PayloadBuilder for PayloadBuilderImpl {
             None => context.time,
             Some((_, time, _)) => *time,
         };
-        let (past_ingress, past_xnet, past_self_validating) =
+        let (past_ingress, past_xnet, past_self_validating, past_canister_http) =
             split_past_payloads(&mut ingress_payload_cache, past_payloads);
         self.metrics
             .past_payloads_length
@@ -156,10 +161,20 @@ impl PayloadBuilder for PayloadBuilderImpl {
         let ingress_query = IngressSets::new(past_ingress, min_block_time);
 
         // We enforce the block_payload limit in the following way:
-        // On a block with even height, we fill up the block with xnet messages.
-        // If there is space left, we fill it is ingress messages.
+        // Responses to canister HTTP requests are included first, as they are
+        // bounded by the size of a single inter-canister message.
+        // On a block with even height, we then fill up the block with xnet
+        // messages. If there is space left, we fill it is ingress messages.
         // On odd blocks, we prioritize ingress over xnet.
         let max_block_payload_size = self.get_max_block_payload_size_bytes(context)?;
+        let canister_http = self
+            .canister_http_payload_builder
+            .get_canister_http_payload(context, &past_canister_http, MAX_XNET_PAYLOAD_IN_BYTES);
+        let max_block_payload_size = NumBytes::new(
+            max_block_payload_size
+                .get()
+                .saturating_sub(canister_http.count_bytes() as u64),
+        );
         let get_ingress_payload = |byte_limit| {
             self.ingress_selector.get_ingress_payload(
                 ingress_pool,
@@ -199,6 +214,7 @@ impl PayloadBuilder for PayloadBuilderImpl {
             ingress,
             xnet,
             self_validating,
+            canister_http,
         })
     }
 
@@ -218,7 +234,7 @@ impl PayloadBuilder for PayloadBuilderImpl {
             None => context.time,
             Some((_, time, _)) => *time,
         };
-        let (past_ingress, past_xnet, past_self_validating) =
+        let (past_ingress, past_xnet, past_self_validating, past_canister_http) =
             split_past_payloads(&mut ingress_payload_cache, past_payloads);
         self.metrics
             .ingress_payload_cache_size
@@ -242,17 +258,25 @@ impl PayloadBuilder for PayloadBuilderImpl {
             &past_xnet,
         )?;
 
-        // The size of both payloads together must not exceed the block payload size.
+        let canister_http_size = self
+            .canister_http_payload_builder
+            .validate_canister_http_payload(
+                &batch_payload.canister_http,
+                context,
+                &past_canister_http,
+            )?;
+
+        // The size of the payloads together must not exceed the block payload size.
         // NOTE: We MUST NOT use xnet.count_bytes() here, as it may not be
         // deterministic and could lead to divergence.
-        if xnet_size + NumBytes::from(batch_payload.ingress.count_bytes() as u64)
-            > max_block_payload_size
-        {
+        let payload_size = xnet_size
+            + NumBytes::from(batch_payload.ingress.count_bytes() as u64)
+            + canister_http_size;
+        if payload_size > max_block_payload_size {
             return Err(ValidationError::Permanent(
                 PayloadPermanentError::PayloadTooBig {
                     expected: max_block_payload_size,
-                    received: xnet_size
-                        + NumBytes::from(batch_payload.ingress.count_bytes() as u64),
+                    received: payload_size,
                 },
             ));
         }
@@ -306,9 +330,9 @@ impl PayloadBuilderImpl {
     }
 }
 
-/// Split past_payloads into past_ingress and past_xnet payloads. The
-/// past_ingress is actually a list of HashSet of MessageIds taken from the
-/// ingress_payload_cache.
+/// Split past_payloads into past_ingress, past_xnet, past_self_validating and
+/// past_canister_http payloads. The past_ingress is actually a list of HashSet
+/// of MessageIds taken from the ingress_payload_cache.
 #[allow(clippy::type_complexity)]
 fn split_past_payloads<'a, 'b>(
     ingress_payload_cache: &'a mut IngressPayloadCache,
@@ -317,6 +341,7 @@ fn split_past_payloads<'a, 'b>(
     Vec<Arc<HashSet<IngressMessageId>>>,
     Vec<&'b XNetPayload>,
     Vec<&'b SelfValidatingPayload>,
+    Vec<&'b CanisterHttpPayload>,
 ) {
     let past_xnet: Vec<_> = past_payloads
         .iter()
@@ -353,6 +378,16 @@ fn split_past_payloads<'a, 'b>(
             }
         })
         .collect();
+    let past_canister_http: Vec<_> = past_payloads
+        .iter()
+        .filter_map(|(_, _, payload)| {
+            if payload.is_summary() {
+                None
+            } else {
+                Some(&payload.as_ref().as_data().batch.canister_http)
+            }
+        })
+        .collect();
     // We assume that 'past_payloads' comes in descending heights, following the
     // block parent traversal order.
     if let Some((min_height, _, _)) = past_payloads.last() {
@@ -370,14 +405,22 @@ fn split_past_payloads<'a, 'b>(
             }
         }
     }
-    (past_ingress, past_xnet, past_self_validating)
+    (
+        past_ingress,
+        past_xnet,
+        past_self_validating,
+        past_canister_http,
+    )
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::consensus::mocks::{dependencies, dependencies_with_subnet_params, Dependencies};
-    use ic_interfaces::self_validating_payload::NoOpSelfValidatingPayloadBuilder;
+    use ic_interfaces::{
+        canister_http::NoOpCanisterHttpPayloadBuilder,
+        self_validating_payload::NoOpSelfValidatingPayloadBuilder,
+    };
     use ic_logger::replica_logger::no_op_logger;
     use ic_test_artifact_pool::ingress_pool::TestIngressPool;
     use ic_test_utilities::{
@@ -415,6 +458,7 @@ mod test {
         let xnet_payload_builder =
             FakeXNetPayloadBuilder::make(certified_streams.drain(..).collect());
         let self_validating_payload_builder = NoOpSelfValidatingPayloadBuilder {};
+        let canister_http_payload_builder = NoOpCanisterHttpPayloadBuilder {};
 
         PayloadBuilderImpl::new(
             subnet_test_id(0),
@@ -422,6 +466,7 @@ mod test {
             Arc::new(ingress_selector),
             Arc::new(xnet_payload_builder),
             Arc::new(self_validating_payload_builder),
+            Arc::new(canister_http_payload_builder),
             MetricsRegistry::new(),
             no_op_logger(),
         )
@@ -7,6 +7,7 @@ use crate::consensus::{
     crypto::ConsensusCrypto, metrics::EcdsaPayloadMetrics, pool_reader::PoolReader,
 };
 use ic_interfaces::{
+    crypto::ThresholdEcdsaSigVerifier,
     ecdsa::EcdsaPool,
     registry::RegistryClient,
     state_manager::{StateManager, StateManagerError},
@@ -28,12 +29,15 @@ use ic_types::{
                 IDkgDealers, IDkgReceivers, IDkgTranscript, IDkgTranscriptId,
                 IDkgTranscriptOperation, IDkgTranscriptParams,
             },
-            ExtendedDerivationPath, PreSignatureQuadruple, ThresholdEcdsaSigInputs,
+            ExtendedDerivationPath, PreSignatureQuadruple, ThresholdEcdsaCombinedSignature,
+            ThresholdEcdsaSigInputs, ThresholdEcdsaSigShare,
         },
         AlgorithmId,
     },
+    ic00::{Payload as _, SignWithECDSAReply},
+    messages::{self, CallbackId, Response},
     registry::RegistryClientError,
-    Height, NodeId, RegistryVersion, SubnetId,
+    CanisterId, Cycles, Height, NodeId, RegistryVersion, SubnetId,
 };
 use phantom_newtype::Id;
 use std::collections::{BTreeMap, BTreeSet};
@@ -138,9 +142,25 @@ pub(crate) fn create_summary_payload(
                     panic!("ECDSA payload exists but previous summary is not found")
                 });
             let summary = ecdsa::EcdsaSummaryPayload {
-                current_ecdsa_transcript: previous_summary.next_ecdsa_transcript.clone(),
+                // Keep using the current transcript until a new one is ready.
+                current_ecdsa_transcript: previous_summary
+                    .next_ecdsa_transcript
+                    .clone()
+                    .or_else(|| previous_summary.current_ecdsa_transcript.clone()),
                 next_ecdsa_transcript: None,
                 ongoing_signatures: payload.ongoing_signatures.clone(),
+                // All agreements were delivered with the blocks before the
+                // summary at the latest.
+                signature_agreements: payload
+                    .signature_agreements
+                    .keys()
+                    .map(|request_id| {
+                        (
+                            request_id.clone(),
+                            ecdsa::CompletedSignature::ReportedToExecution,
+                        )
+                    })
+                    .collect(),
                 // TODO: carrying over available_quadruples is assuming unchanged
                 // membership. This problem has to be addressed when membership changes.
                 available_quadruples: payload.available_quadruples.clone(),
@@ -209,7 +229,7 @@ pub(crate) fn create_data_payload(
                     &mut next_unused_transcript_id,
                 )?;
                 let payload = ecdsa::EcdsaDataPayload {
-                    signature_agreements: BTreeMap::new(),
+                    signature_agreements: ecdsa_summary.signature_agreements.clone(),
                     ongoing_signatures: ecdsa_summary.ongoing_signatures.clone(),
                     available_quadruples: ecdsa_summary.available_quadruples.clone(),
                     quadruples_in_creation,
@@ -232,7 +252,7 @@ pub(crate) fn create_data_payload(
                             )
                         });
                 let summary = summary_block.payload.as_ref().as_summary();
-                let _ecdsa_summary = summary.ecdsa.as_ref().unwrap_or_else(|| {
+                let ecdsa_summary = summary.ecdsa.as_ref().unwrap_or_else(|| {
                     panic!("ecdsa payload exists but previous summary is not found")
                 });
                 let (summary_registry_version, node_ids) =
@@ -244,9 +264,11 @@ pub(crate) fn create_data_payload(
                 let mut payload = prev_payload.clone();
                 let count = update_signing_requests(
                     log.clone(),
+                    crypto,
                     ecdsa_pool.clone(),
                     state_manager,
                     context,
+                    ecdsa_summary.current_ecdsa_transcript.as_ref(),
                     &mut payload,
                 )?;
                 // quadruples are consumed, need to produce more
@@ -395,41 +417,105 @@ fn start_making_new_quadruples(
     Ok(())
 }
 
-// Try to comibine signature shares in the ECDSA pool and return
-// an interator of new full signatures constructed.
-// TODO: also pass in signatures we are looking for to avoid traversing
-// everything.
+/// Try to combine the validated signature shares in the ECDSA pool into full
+/// signatures for the given ongoing signing requests.
 fn combine_signatures(
-    _ecdsa_pool: Arc<RwLock<dyn EcdsaPool>>,
-) -> Box<dyn Iterator<Item = (ecdsa::RequestId, ecdsa::EcdsaSignature)>> {
-    Box::new(std::iter::empty())
+    crypto: &dyn ConsensusCrypto,
+    ecdsa_pool: &dyn EcdsaPool,
+    ongoing_signatures: &BTreeMap<ecdsa::RequestId, ThresholdEcdsaSigInputs>,
+) -> Vec<(ecdsa::RequestId, ThresholdEcdsaCombinedSignature)> {
+    let mut shares_by_request: BTreeMap<
+        &ecdsa::RequestId,
+        BTreeMap<NodeId, ThresholdEcdsaSigShare>,
+    > = BTreeMap::new();
+    for (_, share) in ecdsa_pool.validated().signature_shares() {
+        if ongoing_signatures.contains_key(&share.request_id) {
+            shares_by_request
+                .entry(&share.request_id)
+                .or_default()
+                .insert(share.signer_id, share.share.clone());
+        }
+    }
+    shares_by_request
+        .into_iter()
+        .filter_map(|(request_id, shares)| {
+            let inputs = ongoing_signatures.get(request_id)?;
+            if shares.len() < inputs.reconstruction_threshold().get() as usize {
+                return None;
+            }
+            ThresholdEcdsaSigVerifier::combine_sig_shares(crypto, inputs, &shares)
+                .ok()
+                .map(|signature| (request_id.clone(), signature))
+        })
+        .collect()
 }
 
 /// Update data fields related to signing requests in the ECDSA payload:
 ///
+/// - Mark the signatures delivered with the parent block as reported, and
+/// drop the ones whose requests are no longer in the certified state.
 /// - Check if new signatures have been produced, and add them to
-/// signature agreements.
+/// signature agreements together with the response to deliver.
 /// - Check if there are new signing requests, and start to work on them.
 ///
 /// Return the number of new signing requests that are worked on (or
 /// equivalently, the number of quadruples that are consumed).
 fn update_signing_requests(
     log: ReplicaLogger,
+    crypto: &dyn ConsensusCrypto,
     ecdsa_pool: Arc<RwLock<dyn EcdsaPool>>,
     state_manager: &dyn StateManager<State = ReplicatedState>,
     context: &ValidationContext,
+    ecdsa_transcript: Option<&ecdsa::UnmaskedTranscript>,
     payload: &mut ecdsa::EcdsaDataPayload,
 ) -> Result<usize, EcdsaPayloadError> {
+    let state = state_manager.get_state_at(context.certified_height)?;
+    let callback_ids: BTreeMap<ecdsa::RequestId, CallbackId> = state
+        .get_ref()
+        .metadata
+        .subnet_call_context_manager
+        .sign_with_ecdsa_contexts
+        .iter()
+        .map(|(callback_id, context)| (request_id_of(context), *callback_id))
+        .collect();
+    payload
+        .signature_agreements
+        .retain(|request_id, _| callback_ids.contains_key(request_id));
+    for signature in payload.signature_agreements.values_mut() {
+        *signature = ecdsa::CompletedSignature::ReportedToExecution;
+    }
+
     // Check if new signatures have been produced
-    for (request_id, signature) in combine_signatures(ecdsa_pool) {
-        if payload.ongoing_signatures.remove(&request_id).is_none() {
-            warn!(
+    let new_signatures = combine_signatures(
+        crypto,
+        ecdsa_pool.read().unwrap().deref(),
+        &payload.ongoing_signatures,
+    );
+    for (request_id, signature) in new_signatures {
+        payload.ongoing_signatures.remove(&request_id);
+        match callback_ids.get(&request_id) {
+            Some(callback_id) => {
+                let response = Response {
+                    originator: CanisterId::ic_00(),
+                    respondent: CanisterId::ic_00(),
+                    originator_reply_callback: *callback_id,
+                    refund: Cycles::zero(),
+                    response_payload: messages::Payload::Data(
+                        SignWithECDSAReply {
+                            signature: signature.signature,
+                        }
+                        .encode(),
+                    ),
+                };
+                payload
+                    .signature_agreements
+                    .insert(request_id, ecdsa::CompletedSignature::Unreported(response));
+            }
+            None => warn!(
                 log,
-                "ECDSA signing request {:?} is not found in payload but we have a signature for it",
+                "ECDSA signing request {:?} is not found in the state but we have a signature for it",
                 request_id
-            );
-        } else {
-            payload.signature_agreements.insert(request_id, signature);
+            ),
         }
     }
     // Get the set of new signing requests that we have not signed, and are
@@ -443,7 +529,7 @@ fn update_signing_requests(
         state_manager,
         &existing_requests,
         &mut payload.available_quadruples,
-        None,
+        ecdsa_transcript,
         context.certified_height,
     )?;
     let mut count = 0;
@@ -454,6 +540,12 @@ fn update_signing_requests(
     Ok(count)
 }
 
+/// The id of the signing request of a context. It is just the
+/// pseudo_random_id, which is guaranteed to be always unique.
+fn request_id_of(context: &SignWithEcdsaContext) -> ecdsa::RequestId {
+    ecdsa::RequestId::from(context.pseudo_random_id.to_vec())
+}
+
 // Return new signing requests initiated from canisters.
 fn get_new_signing_requests(
     state_manager: &dyn StateManager<State = ReplicatedState>,
@@ -471,11 +563,7 @@ fn get_new_signing_requests(
     let new_requests = contexts
         .iter()
         .filter_map(|(_callback_id, context)| {
-            let SignWithEcdsaContext {
-                pseudo_random_id, ..
-            } = context;
-            // request_id is just pseudo_random_id which is guaranteed to be always unique.
-            let request_id = ecdsa::RequestId::from(pseudo_random_id.to_vec());
+            let request_id = request_id_of(context);
             if !existing_requests.contains(&request_id) {
                 Some((request_id, context))
             } else {
@@ -715,26 +803,14 @@ pub fn validate_data_payload(_payload: ecdsa::EcdsaDataPayload) -> Result<(), Ec
 
 /// Helper to build threshold signature inputs from the context and
 /// the pre-signature quadruple
-/// TODO: PrincipalId, key transcript, etc need to figured out
 fn build_signature_inputs(
     context: &SignWithEcdsaContext,
     quadruple: &PreSignatureQuadruple,
     key_transcript: &ecdsa::UnmaskedTranscript,
 ) -> Result<ThresholdEcdsaSigInputs, ThresholdEcdsaSigInputsCreationError> {
-    // TODO: decide the appropriate conversion
-    fn from_le_bytes(arr: &[u8]) -> Vec<u32> {
-        let len = arr.len() / 4;
-        let mut result = Vec::with_capacity(len);
-        for i in 0..len {
-            let mut dst = [0, 0, 0, 0];
-            dst.copy_from_slice(&arr[(i * 4)..(i * 4 + 4)]);
-            result.push(u32::from_le_bytes(dst));
-        }
-        result
-    }
     let extended_derivation_path = ExtendedDerivationPath {
         caller: context.request.sender.into(),
-        bip32_derivation_path: from_le_bytes(&context.derivation_path),
+        derivation_path: context.derivation_path.clone(),
     };
     ThresholdEcdsaSigInputs::new(
         &extended_derivation_path,
@@ -764,13 +840,15 @@ mod tests {
         },
     };
     use ic_types::crypto::canister_threshold_sig::idkg::IDkgTranscriptId;
-    use ic_types::{messages::CallbackId, Height, RegistryVersion};
+    use ic_types::ic00::{EcdsaCurve, EcdsaKeyId};
+    use ic_types::{Height, RegistryVersion};
     use std::collections::BTreeSet;
     use std::sync::Arc;
 
     fn empty_ecdsa_summary_payload(subnet_id: SubnetId) -> ecdsa::EcdsaSummaryPayload {
         ecdsa::EcdsaSummaryPayload {
             ongoing_signatures: BTreeMap::new(),
+            signature_agreements: BTreeMap::new(),
             current_ecdsa_transcript: None,
             next_ecdsa_transcript: None,
             available_quadruples: BTreeMap::new(),
@@ -797,6 +875,7 @@ mod tests {
         let quadruples_to_create_in_advance = 5;
         let ecdsa_config = EcdsaConfig {
             quadruples_to_create_in_advance,
+            key_ids: vec![],
         };
         let mut next_unused_transcript_id = IDkgTranscriptId::new(subnet_id, 10);
         // Success case
@@ -852,6 +931,10 @@ mod tests {
                 CallbackId::from(1),
                 SignWithEcdsaContext {
                     request: RequestBuilder::new().build(),
+                    key_id: EcdsaKeyId {
+                        curve: EcdsaCurve::Secp256k1,
+                        name: "secp256k1".to_string(),
+                    },
                     pseudo_random_id,
                     message_hash: vec![],
                     derivation_path: vec![],
@@ -6,6 +6,7 @@ use ic_artifact_pool::{
 use ic_config::artifact_pool::ArtifactPoolConfig;
 use ic_consensus::{consensus::ConsensusImpl, dkg};
 use ic_interfaces::{
+    canister_http::CanisterHttpPayloadBuilder,
     certification::Certifier,
     certified_stream_store::CertifiedStreamStore,
     ingress_manager::IngressSelector,
@@ -20,6 +21,7 @@ use ic_metrics::MetricsRegistry;
 use ic_replicated_state::ReplicatedState;
 use ic_test_artifact_pool::ingress_pool::TestIngressPool;
 use ic_test_utilities::{
+    canister_http_payload_builder::FakeCanisterHttpPayloadBuilder,
     ingress_selector::FakeIngressSelector, message_routing::FakeMessageRouting,
     self_validating_payload_builder::FakeSelfValidatingPayloadBuilder,
     state_manager::FakeStateManager, xnet_payload_builder::FakeXNetPayloadBuilder,
@@ -139,6 +141,7 @@ pub struct ConsensusDependencies {
     pub(crate) xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     pub(crate) ingress_selector: Arc<dyn IngressSelector>,
     pub(crate) self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    pub(crate) canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
     pub consensus_pool: Arc<RwLock<ConsensusPoolImpl>>,
     pub dkg_pool: Arc<RwLock<dkg_pool::DkgPoolImpl>>,
     pub ecdsa_pool: Arc<RwLock<ecdsa_pool::EcdsaPoolImpl>>,
@@ -182,6 +185,7 @@ impl ConsensusDependencies {
             ingress_selector: Arc::new(FakeIngressSelector::new()),
             xnet_payload_builder: Arc::new(xnet_payload_builder),
             self_validating_payload_builder: Arc::new(FakeSelfValidatingPayloadBuilder::new()),
+            canister_http_payload_builder: Arc::new(FakeCanisterHttpPayloadBuilder::new()),
             state_manager,
             metrics_registry,
             replica_config,
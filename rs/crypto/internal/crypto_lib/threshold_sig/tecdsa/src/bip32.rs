@@ -42,6 +42,26 @@ impl DerivationPath {
         // this is a stopgap until proper BIP32 support:
 
         let curve = EccCurve::new(curve_type);
+        let s = curve.hash_to_scalar(
+            1,
+            &self.digest()?,
+            "ic-crypto-tecdsa-path-derivation".as_bytes(),
+        )?;
+
+        Ok(s[0])
+    }
+
+    /// Returns the chain code of the key derived with this path. Like the
+    /// tweak, it is derived from a digest of the path, but with a separate
+    /// domain separator, so that it does not reveal anything about the tweak.
+    pub fn derive_chain_key(&self) -> ThresholdEcdsaResult<[u8; 32]> {
+        let mut sha256 = Sha256::new();
+        sha256.write("ic-crypto-tecdsa-chain-key".as_bytes());
+        sha256.write(&self.digest()?);
+        Ok(sha256.finish())
+    }
+
+    fn digest(&self) -> ThresholdEcdsaResult<[u8; 32]> {
         let mut sha256 = Sha256::new();
 
         for elem in &self.path {
@@ -59,12 +79,6 @@ impl DerivationPath {
             }
         }
 
-        let s = curve.hash_to_scalar(
-            1,
-            &sha256.finish(),
-            "ic-crypto-tecdsa-path-derivation".as_bytes(),
-        )?;
-
-        Ok(s[0])
+        Ok(sha256.finish())
     }
 }
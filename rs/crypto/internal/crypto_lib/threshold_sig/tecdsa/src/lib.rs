@@ -48,7 +48,7 @@ pub use seed::*;
 pub use transcript::*;
 pub use xmd::*;
 
-pub use bip32::DerivationPath;
+pub use bip32::{DerivationIndex, DerivationPath};
 pub use sign::{ThresholdEcdsaCombinedSigInternal, ThresholdEcdsaSigShareInternal};
 
 /// Create MEGa encryption keypair
@@ -279,10 +279,14 @@ pub fn privately_verify_dealing(
 
 impl From<&ExtendedDerivationPath> for DerivationPath {
     fn from(extended_derivation_path: &ExtendedDerivationPath) -> Self {
-        Self::new_with_principal(
-            extended_derivation_path.caller,
-            &extended_derivation_path.bip32_derivation_path,
-        )
+        let mut path = Vec::with_capacity(1 + extended_derivation_path.derivation_path.len());
+        path.push(DerivationIndex::Generalized(
+            extended_derivation_path.caller.to_vec(),
+        ));
+        for index in &extended_derivation_path.derivation_path {
+            path.push(DerivationIndex::Generalized(index.clone()));
+        }
+        Self::new_arbitrary(path)
     }
 }
 
@@ -535,4 +539,4 @@ pub fn verify_threshold_signature(
     Ok(())
 }
 
-pub use sign::derive_public_key;
+pub use sign::{derive_public_key, derive_public_key_from_master, get_master_public_key};
@@ -304,30 +304,67 @@ impl ThresholdEcdsaCombinedSigInternal {
     }
 }
 
-pub fn derive_public_key(
-    key_transcript: &IDkgTranscriptInternal,
-    derivation_path: &DerivationPath,
-    algorithm_id: AlgorithmId,
-) -> ThresholdEcdsaResult<EcdsaPublicKey> {
-    let curve_type = match algorithm_id {
+fn curve_type_of(algorithm_id: AlgorithmId) -> ThresholdEcdsaResult<EccCurveType> {
+    match algorithm_id {
         AlgorithmId::ThresholdEcdsaSecp256k1 => Ok(EccCurveType::K256),
         _ => Err(ThresholdEcdsaError::InvalidArguments(format!(
             "Unknown algorithm {:?}",
             algorithm_id
         ))),
-    }?;
+    }
+}
 
-    // Compute tweak
+fn tweak_public_key(
+    master_public_key: &EccPoint,
+    derivation_path: &DerivationPath,
+    curve_type: EccCurveType,
+) -> ThresholdEcdsaResult<EccPoint> {
     let key_tweak = derivation_path.derive_tweak(curve_type)?;
-
-    let master_public_key = key_transcript.constant_term();
     let tweak_g = EccCurve::new(curve_type)
         .generator_g()?
         .scalar_mul(&key_tweak)?;
-    let public_key = tweak_g.add_points(&master_public_key)?;
+    tweak_g.add_points(master_public_key)
+}
+
+pub fn derive_public_key(
+    key_transcript: &IDkgTranscriptInternal,
+    derivation_path: &DerivationPath,
+    algorithm_id: AlgorithmId,
+) -> ThresholdEcdsaResult<EcdsaPublicKey> {
+    let curve_type = curve_type_of(algorithm_id)?;
+    let public_key =
+        tweak_public_key(&key_transcript.constant_term(), derivation_path, curve_type)?;
 
     Ok(EcdsaPublicKey {
         algorithm_id,
         public_key: public_key.serialize(),
     })
 }
+
+/// Returns the (underived) master public key of a key transcript.
+pub fn get_master_public_key(
+    key_transcript: &IDkgTranscriptInternal,
+    algorithm_id: AlgorithmId,
+) -> ThresholdEcdsaResult<EcdsaPublicKey> {
+    curve_type_of(algorithm_id)?;
+    Ok(EcdsaPublicKey {
+        algorithm_id,
+        public_key: key_transcript.constant_term().serialize(),
+    })
+}
+
+/// Derives a public key from a master public key, as returned by
+/// [`get_master_public_key`], without access to the key transcript.
+pub fn derive_public_key_from_master(
+    master_public_key: &EcdsaPublicKey,
+    derivation_path: &DerivationPath,
+) -> ThresholdEcdsaResult<EcdsaPublicKey> {
+    let curve_type = curve_type_of(master_public_key.algorithm_id)?;
+    let master_public_key_point = EccPoint::deserialize(curve_type, &master_public_key.public_key)?;
+    let public_key = tweak_public_key(&master_public_key_point, derivation_path, curve_type)?;
+
+    Ok(EcdsaPublicKey {
+        algorithm_id: master_public_key.algorithm_id,
+        public_key: public_key.serialize(),
+    })
+}
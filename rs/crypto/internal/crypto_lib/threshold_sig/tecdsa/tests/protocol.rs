@@ -187,3 +187,19 @@ fn should_basic_signing_protocol_work() -> Result<(), ThresholdEcdsaError> {
 
     Ok(())
 }
+
+#[test]
+fn should_derive_distinct_chain_keys_for_distinct_paths() -> Result<(), ThresholdEcdsaError> {
+    let path = DerivationPath::new_bip32(&[1, 2, 3]);
+
+    assert_eq!(path.derive_chain_key()?, path.derive_chain_key()?);
+    assert_ne!(
+        path.derive_chain_key()?,
+        DerivationPath::new_bip32(&[1, 2, 4]).derive_chain_key()?
+    );
+    assert!(DerivationPath::new_bip32(&[1 << 31])
+        .derive_chain_key()
+        .is_err());
+
+    Ok(())
+}
@@ -534,6 +534,13 @@ impl SignatureProtocolExecution {
             self.setup.setup.alg,
         )?;
 
+        // Deriving the key from the master public key alone must agree
+        let master_pk = get_master_public_key(&self.setup.key.transcript, self.setup.setup.alg)?;
+        assert_eq!(
+            derive_public_key_from_master(&master_pk, &self.derivation_path)?,
+            pk
+        );
+
         use k256::ecdsa::signature::{Signature, Verifier};
 
         let vk = k256::ecdsa::VerifyingKey::from_sec1_bytes(&pk.public_key)
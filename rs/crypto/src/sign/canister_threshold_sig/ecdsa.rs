@@ -1,15 +1,17 @@
 //! Implementations of ThresholdEcdsaSigner
 use ic_crypto_internal_csp::api::{CspThresholdEcdsaSigVerifier, CspThresholdEcdsaSigner};
 use ic_types::crypto::canister_threshold_sig::error::{
-    ThresholdEcdsaCombineSigSharesError, ThresholdEcdsaSignShareError,
+    ThresholdEcdsaCombineSigSharesError, ThresholdEcdsaGetPublicKeyError,
+    ThresholdEcdsaSignShareError,
 };
 use ic_types::crypto::canister_threshold_sig::idkg::{IDkgReceivers, IDkgTranscript};
 use ic_types::crypto::canister_threshold_sig::{
-    ThresholdEcdsaCombinedSignature, ThresholdEcdsaSigInputs, ThresholdEcdsaSigShare,
+    EcdsaPublicKey, ExtendedDerivationPath, ThresholdEcdsaCombinedSignature,
+    ThresholdEcdsaSigInputs, ThresholdEcdsaSigShare,
 };
 use ic_types::{NodeId, NodeIndex};
 use std::collections::{BTreeMap, BTreeSet};
-use tecdsa::{IDkgTranscriptInternal, ThresholdEcdsaSigShareInternal};
+use tecdsa::{DerivationPath, IDkgTranscriptInternal, ThresholdEcdsaSigShareInternal};
 
 pub fn sign_share<C: CspThresholdEcdsaSigner>(
     csp_client: &C,
@@ -82,6 +84,42 @@ pub fn combine_sig_shares<C: CspThresholdEcdsaSigVerifier>(
     })
 }
 
+/// Returns the master public key of the threshold ECDSA key held by
+/// `key_transcript`.
+pub fn get_tecdsa_master_public_key(
+    key_transcript: &IDkgTranscript,
+) -> Result<EcdsaPublicKey, ThresholdEcdsaGetPublicKeyError> {
+    let internal_transcript = IDkgTranscriptInternal::deserialize(
+        &key_transcript.internal_transcript_raw,
+    )
+    .map_err(|e| ThresholdEcdsaGetPublicKeyError::SerializationError {
+        internal_error: format!("{:?}", e),
+    })?;
+    tecdsa::get_master_public_key(&internal_transcript, key_transcript.algorithm_id)
+        .map_err(|e| ThresholdEcdsaGetPublicKeyError::InvalidArgument(format!("{:?}", e)))
+}
+
+/// Derives the public key of `derivation_path` from a master public key as
+/// returned by [`get_tecdsa_master_public_key`].
+pub fn derive_tecdsa_public_key(
+    master_public_key: &EcdsaPublicKey,
+    derivation_path: &ExtendedDerivationPath,
+) -> Result<EcdsaPublicKey, ThresholdEcdsaGetPublicKeyError> {
+    tecdsa::derive_public_key_from_master(master_public_key, &DerivationPath::from(derivation_path))
+        .map_err(|e| ThresholdEcdsaGetPublicKeyError::InvalidArgument(format!("{:?}", e)))
+}
+
+/// Returns the chain code of the public key that [`derive_tecdsa_public_key`]
+/// derives for `derivation_path`.
+pub fn derive_tecdsa_chain_code(
+    derivation_path: &ExtendedDerivationPath,
+) -> Result<Vec<u8>, ThresholdEcdsaGetPublicKeyError> {
+    DerivationPath::from(derivation_path)
+        .derive_chain_key()
+        .map(|chain_key| chain_key.to_vec())
+        .map_err(|e| ThresholdEcdsaGetPublicKeyError::InvalidArgument(format!("{:?}", e)))
+}
+
 fn ensure_self_was_receiver(
     self_node_id: &NodeId,
     receivers: &BTreeSet<NodeId>,
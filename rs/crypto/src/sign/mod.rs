@@ -5,6 +5,9 @@ use crate::sign::basic_sig::{BasicSignVerifierByPublicKeyInternal, BasicSignerIn
 use crate::sign::multi_sig::MultiSigVerifierInternal;
 use crate::sign::multi_sig::MultiSignerInternal;
 use crate::sign::threshold_sig::{ThresholdSigVerifierInternal, ThresholdSignerInternal};
+pub use canister_threshold_sig::ecdsa::{
+    derive_tecdsa_chain_code, derive_tecdsa_public_key, get_tecdsa_master_public_key,
+};
 use ic_crypto_internal_csp::types::{CspPublicKey, CspSignature};
 use ic_crypto_internal_csp::CryptoServiceProvider;
 use ic_interfaces::crypto::{
@@ -20,8 +23,8 @@ use ic_types::crypto::canister_threshold_sig::error::{
 };
 use ic_types::crypto::canister_threshold_sig::idkg::IDkgTranscript;
 use ic_types::crypto::canister_threshold_sig::{
-    EcdsaPublicKey, ThresholdEcdsaCombinedSignature, ThresholdEcdsaSigInputs,
-    ThresholdEcdsaSigShare,
+    EcdsaPublicKey, ExtendedDerivationPath, ThresholdEcdsaCombinedSignature,
+    ThresholdEcdsaSigInputs, ThresholdEcdsaSigShare,
 };
 use ic_types::crypto::threshold_sig::errors::threshold_sign_error::ThresholdSignError;
 use ic_types::crypto::threshold_sig::ni_dkg::DkgId;
@@ -546,7 +549,16 @@ impl<C: CryptoServiceProvider> ThresholdEcdsaSigVerifier for CryptoComponentFatC
         debug!(logger;
             crypto.description => "start",
         );
-        let result = canister_threshold_sig::mocks::get_public_key(canister_id, key_transcript);
+        let result = canister_threshold_sig::ecdsa::get_tecdsa_master_public_key(&key_transcript)
+            .and_then(|master_public_key| {
+                canister_threshold_sig::ecdsa::derive_tecdsa_public_key(
+                    &master_public_key,
+                    &ExtendedDerivationPath {
+                        caller: canister_id,
+                        derivation_path: vec![],
+                    },
+                )
+            });
         debug!(logger;
             crypto.description => "end",
             crypto.is_ok => result.is_ok(),
@@ -447,7 +447,7 @@ fn should_create_signature_share_successfully_with_new_key() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -485,7 +485,7 @@ fn should_fail_create_signature_if_not_receiver() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -545,7 +545,7 @@ fn should_fail_create_signature_share_without_kappa_times_lambda_loaded() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -605,7 +605,7 @@ fn should_fail_create_signature_share_without_key_times_lambda_loaded() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -665,7 +665,7 @@ fn should_verify_sig_share_successfully() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -707,7 +707,7 @@ fn should_combine_sig_shares_successfully() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -759,7 +759,7 @@ fn should_fail_combine_sig_shares_with_insufficient_shares() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -815,7 +815,7 @@ fn should_verify_combined_sig_successfully() {
     let inputs = {
         let derivation_path = ExtendedDerivationPath {
             caller: PrincipalId::new_user_test_id(1),
-            bip32_derivation_path: vec![],
+            derivation_path: vec![],
         };
 
         let hashed_message = rng.gen::<[u8; 32]>();
@@ -944,12 +944,32 @@ fn should_run_retain_active_transcripts() {
 }
 
 #[test]
-fn should_run_get_public_key() {
-    let crypto_components = temp_crypto_components_for(&[NODE_1]);
-    let key_transcript = fake_transcript();
-    let result = crypto_for(NODE_1, &crypto_components)
-        .get_public_key(PrincipalId::new_user_test_id(1), key_transcript);
-    assert!(result.is_ok());
+fn should_derive_public_key_from_master_public_key() {
+    let subnet_size = thread_rng().gen_range(1, 10);
+    let env = CanisterThresholdSigTestEnvironment::new(subnet_size);
+    let key_transcript = generate_key_transcript(&env, AlgorithmId::ThresholdEcdsaSecp256k1);
+    let caller = PrincipalId::new_user_test_id(1);
+    let node_id = *env.crypto_components.keys().next().unwrap();
+
+    let public_key = crypto_for(node_id, &env.crypto_components)
+        .get_public_key(caller, key_transcript.clone())
+        .expect("failed to get public key");
+    let master_public_key = ic_crypto::get_tecdsa_master_public_key(&key_transcript)
+        .expect("failed to get master public key");
+    let derive = |derivation_path: Vec<Vec<u8>>| {
+        ic_crypto::derive_tecdsa_public_key(
+            &master_public_key,
+            &ExtendedDerivationPath {
+                caller,
+                derivation_path,
+            },
+        )
+        .expect("failed to derive public key")
+    };
+
+    assert_eq!(derive(vec![]), public_key);
+    assert_ne!(derive(vec![vec![1, 2, 3]]), public_key);
+    assert_ne!(master_public_key, public_key);
 }
 
 fn fake_params_for(node_id: NodeId) -> IDkgTranscriptParams {
@@ -1090,7 +1110,7 @@ fn fake_sig_inputs(nodes: &BTreeSet<NodeId>) -> ThresholdEcdsaSigInputs {
 
     let derivation_path = ExtendedDerivationPath {
         caller: PrincipalId::new_user_test_id(1),
-        bip32_derivation_path: vec![],
+        derivation_path: vec![],
     };
 
     ThresholdEcdsaSigInputs::new(
@@ -21,7 +21,8 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{CanisterState, SystemState};
 use ic_types::{
     ic00::{
-        CanisterIdRecord, InstallCodeArgs, Method, Payload, SetControllerArgs, UpdateSettingsArgs,
+        CanisterIdRecord, CanisterSnapshotArgs, InstallChunkedCodeArgs, InstallCodeArgs, Method,
+        Payload, SetControllerArgs, TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
     },
     messages::{
         is_subnet_message, Request, Response, SignedIngressContent,
@@ -135,6 +136,18 @@ impl CyclesAccountManager {
         self.config.xnet_byte_transmission_fee * Cycles::from(payload_size.get())
     }
 
+    /// Returns the fee for a canister HTTP request in [`Cycles`], given the
+    /// size of the request and the maximum size of its response.
+    pub fn http_request_fee(
+        &self,
+        request_size: NumBytes,
+        response_size_limit: NumBytes,
+    ) -> Cycles {
+        self.config.http_request_baseline_fee
+            + self.config.http_request_per_byte_fee
+                * Cycles::from(request_size.get() + response_size_limit.get())
+    }
+
     /// Returns the freezing threshold for this canister in Cycles.
     pub fn freeze_threshold_cycles(
         &self,
@@ -316,10 +329,25 @@ impl CyclesAccountManager {
                 | Ok(Method::CanisterStatus)
                 | Ok(Method::DeleteCanister)
                 | Ok(Method::UninstallCode)
-                | Ok(Method::StopCanister) => match CanisterIdRecord::decode(ingress.arg()) {
+                | Ok(Method::StopCanister)
+                | Ok(Method::ListCanisterSnapshots)
+                | Ok(Method::ClearChunkStore)
+                | Ok(Method::StoredChunks) => match CanisterIdRecord::decode(ingress.arg()) {
                     Ok(record) => Some(record.get_canister_id()),
                     Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
                 },
+                Ok(Method::TakeCanisterSnapshot) => {
+                    match TakeCanisterSnapshotArgs::decode(ingress.arg()) {
+                        Ok(record) => Some(record.get_canister_id()),
+                        Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
+                    }
+                }
+                Ok(Method::LoadCanisterSnapshot) | Ok(Method::DeleteCanisterSnapshot) => {
+                    match CanisterSnapshotArgs::decode(ingress.arg()) {
+                        Ok(record) => Some(record.get_canister_id()),
+                        Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
+                    }
+                }
                 Ok(Method::UpdateSettings) => match UpdateSettingsArgs::decode(ingress.arg()) {
                     Ok(record) => Some(record.get_canister_id()),
                     Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
@@ -332,13 +360,28 @@ impl CyclesAccountManager {
                     Ok(record) => Some(record.get_canister_id()),
                     Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
                 },
+                Ok(Method::InstallChunkedCode) => {
+                    match InstallChunkedCodeArgs::decode(ingress.arg()) {
+                        Ok(record) => Some(record.get_canister_id()),
+                        Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
+                    }
+                }
+                Ok(Method::UploadChunk) => match UploadChunkArgs::decode(ingress.arg()) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => return Err(IngressInductionCostError::InvalidSubnetPayload),
+                },
                 Ok(Method::CreateCanister)
                 | Ok(Method::SetupInitialDKG)
                 | Ok(Method::DepositCycles)
                 | Ok(Method::RawRand)
                 | Ok(Method::SignWithECDSA)
-                | Ok(Method::GetMockECDSAPublicKey)
-                | Ok(Method::SignWithMockECDSA)
+                | Ok(Method::HttpRequest)
+                | Ok(Method::BitcoinGetBalance)
+                | Ok(Method::BitcoinGetUtxos)
+                | Ok(Method::BitcoinSendTransaction)
+                | Ok(Method::ECDSAPublicKey)
+                | Ok(Method::FetchCanisterLogs)
+                | Ok(Method::CanisterInfo)
                 | Err(_) => {
                     return Err(IngressInductionCostError::UnknownSubnetMethod);
                 }
@@ -622,10 +665,15 @@ impl CyclesAccountManager {
     /// Charges a canister for its resource allocation and usage for the
     /// duration specified. If fees were successfully charged, then returns
     /// Ok(CanisterState) else returns Err(CanisterState).
+    ///
+    /// `snapshots_memory_usage` is the memory taken by the snapshots of the
+    /// canister. It is charged on top of the memory allocation or usage of the
+    /// canister itself.
     pub fn charge_canister_for_resource_allocation_and_usage(
         &self,
         log: &ReplicaLogger,
         canister: &mut CanisterState,
+        snapshots_memory_usage: NumBytes,
         duration_between_blocks: Duration,
     ) -> Result<(), CanisterOutOfCyclesError> {
         let bytes_to_charge = match canister.memory_allocation() {
@@ -634,7 +682,7 @@ impl CyclesAccountManager {
             MemoryAllocation::Reserved(bytes) => bytes,
             // The canister uses best-effort memory allocation, so charge based on current usage.
             MemoryAllocation::BestEffort => canister.memory_usage(self.own_subnet_type),
-        };
+        } + snapshots_memory_usage;
         if let Err(err) = self.charge_for_memory(
             &mut canister.system_state,
             bytes_to_charge,
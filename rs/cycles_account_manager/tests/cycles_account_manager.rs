@@ -64,6 +64,7 @@ fn test_can_charge_application_subnets() {
                         .charge_canister_for_resource_allocation_and_usage(
                             &log,
                             &mut canister,
+                            NumBytes::from(0),
                             duration,
                         )
                         .unwrap();
@@ -275,6 +276,35 @@ fn verify_no_cycles_charged_for_message_execution_on_system_subnets() {
     assert_eq!(system_state.cycles_balance, INITIAL_CYCLES);
 }
 
+#[test]
+fn http_request_fee_scales_with_request_and_response_size() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let config = SubnetConfigs::default()
+        .own_subnet_config(SubnetType::Application)
+        .cycles_account_manager_config;
+
+    assert_eq!(
+        cycles_account_manager.http_request_fee(NumBytes::from(0), NumBytes::from(0)),
+        config.http_request_baseline_fee
+    );
+    assert_eq!(
+        cycles_account_manager.http_request_fee(NumBytes::from(100), NumBytes::from(1_000)),
+        config.http_request_baseline_fee + config.http_request_per_byte_fee * Cycles::from(1_100)
+    );
+}
+
+#[test]
+fn no_http_request_fee_on_system_subnets() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::System)
+        .build();
+
+    assert_eq!(
+        cycles_account_manager.http_request_fee(NumBytes::from(100), NumBytes::from(1_000)),
+        Cycles::from(0)
+    );
+}
+
 #[test]
 fn canister_charge_for_memory_until_zero_works() {
     let mut system_state = SystemStateBuilder::new().build();
@@ -480,6 +510,7 @@ fn charging_removes_canisters_with_insufficient_balance() {
             .charge_canister_for_resource_allocation_and_usage(
                 &log,
                 &mut canister,
+                NumBytes::from(0),
                 Duration::from_secs(1),
             )
             .unwrap();
@@ -497,6 +528,7 @@ fn charging_removes_canisters_with_insufficient_balance() {
             .charge_canister_for_resource_allocation_and_usage(
                 &log,
                 &mut canister,
+                NumBytes::from(0),
                 Duration::from_secs(1),
             )
             .unwrap_err();
@@ -514,12 +546,61 @@ fn charging_removes_canisters_with_insufficient_balance() {
             .charge_canister_for_resource_allocation_and_usage(
                 &log,
                 &mut canister,
+                NumBytes::from(0),
                 Duration::from_secs(1),
             )
             .unwrap_err();
     })
 }
 
+#[test]
+fn charging_includes_snapshots_memory_usage() {
+    with_test_replica_logger(|log| {
+        let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+        let duration = Duration::from_secs(1);
+        let memory_allocation = NumBytes::from(1 << 30);
+        let snapshots_memory_usage = NumBytes::from(1 << 20);
+        let expected_fee = cycles_account_manager
+            .memory_cost(memory_allocation + snapshots_memory_usage, duration);
+
+        let mut canister = new_canister_state(
+            canister_test_id(1),
+            canister_test_id(11).get(),
+            expected_fee,
+            NumSeconds::from(0),
+        );
+        canister.system_state.memory_allocation =
+            MemoryAllocation::try_from(memory_allocation).unwrap();
+        cycles_account_manager
+            .charge_canister_for_resource_allocation_and_usage(
+                &log,
+                &mut canister,
+                snapshots_memory_usage,
+                duration,
+            )
+            .unwrap();
+        assert_eq!(canister.system_state.cycles_balance, Cycles::from(0));
+
+        // Without enough cycles to also pay for the snapshots, charging fails.
+        let mut canister = new_canister_state(
+            canister_test_id(1),
+            canister_test_id(11).get(),
+            expected_fee - Cycles::from(1),
+            NumSeconds::from(0),
+        );
+        canister.system_state.memory_allocation =
+            MemoryAllocation::try_from(memory_allocation).unwrap();
+        cycles_account_manager
+            .charge_canister_for_resource_allocation_and_usage(
+                &log,
+                &mut canister,
+                snapshots_memory_usage,
+                duration,
+            )
+            .unwrap_err();
+    })
+}
+
 #[test]
 fn cycles_withdraw_no_threshold() {
     let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
@@ -18,13 +18,14 @@ use ic_test_utilities::types::messages::SignedIngressBuilder;
 use ic_types::{
     artifact::SignedIngress,
     batch::{Batch, BatchPayload, IngressPayload, SelfValidatingPayload, XNetPayload},
+    canister_http::CanisterHttpPayload,
     ingress::{IngressStatus, WasmResult},
     messages::{CanisterInstallMode, MessageId},
     time::UNIX_EPOCH,
     CanisterId, CryptoHashOfState, Randomness, RegistryVersion,
 };
 use setup::setup;
-use std::{convert::TryFrom, sync::Arc, thread::sleep, time::Duration};
+use std::{collections::BTreeMap, convert::TryFrom, sync::Arc, thread::sleep, time::Duration};
 use wabt::wat2wasm;
 
 fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -> Batch {
@@ -37,11 +38,13 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
                 stream_slices: Default::default(),
             },
             self_validating: SelfValidatingPayload::default(),
+            canister_http: CanisterHttpPayload::default(),
         },
         randomness: Randomness::from([0; 32]),
         registry_version: RegistryVersion::from(1),
         time: UNIX_EPOCH,
         consensus_responses: vec![],
+        ecdsa_subnet_public_keys: BTreeMap::new(),
     }
 }
 
@@ -55,11 +58,13 @@ fn build_batch_with_full_state_hash(message_routing: &dyn MessageRouting) -> Bat
                 stream_slices: Default::default(),
             },
             self_validating: SelfValidatingPayload::default(),
+            canister_http: CanisterHttpPayload::default(),
         },
         randomness: Randomness::from([0; 32]),
         registry_version: RegistryVersion::from(1),
         time: UNIX_EPOCH,
         consensus_responses: vec![],
+        ecdsa_subnet_public_keys: BTreeMap::new(),
     }
 }
 
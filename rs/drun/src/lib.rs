@@ -34,6 +34,7 @@ use ic_test_utilities::{
 };
 use ic_types::{
     batch::{Batch, BatchPayload, IngressPayload, SelfValidatingPayload, XNetPayload},
+    canister_http::CanisterHttpPayload,
     ingress::{IngressStatus, WasmResult},
     messages::{MessageId, SignedIngress},
     replica_config::ReplicaConfig,
@@ -313,11 +314,13 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
                 stream_slices: Default::default(),
             },
             self_validating: SelfValidatingPayload::default(),
+            canister_http: CanisterHttpPayload::default(),
         },
         randomness: Randomness::from([0; 32]),
         registry_version: RegistryVersion::from(1),
         time: mock_time(),
         consensus_responses: vec![],
+        ecdsa_subnet_public_keys: BTreeMap::new(),
     }
 }
 /// Block till the given ingress message has finished executing and
@@ -27,6 +27,9 @@ pub struct WasmExecutionOutput {
     pub wasm_result: Result<Option<WasmResult>, HypervisorError>,
     pub num_instructions_left: NumInstructions,
     pub instance_stats: InstanceStats,
+    /// True if the execution read the time, the cycle balance of the canister
+    /// or the data certificate.
+    pub reads_transient_state: bool,
 }
 
 pub struct InstanceRunResult {
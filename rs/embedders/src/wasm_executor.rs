@@ -202,6 +202,7 @@ impl WasmExecutor {
                             accessed_pages: 0,
                             dirty_pages: 0,
                         },
+                        reads_transient_state: false,
                     },
                     execution_state,
                     system_state_accessor,
@@ -365,6 +366,7 @@ pub fn process<A: SystemStateAccessor>(
                         accessed_pages: 0,
                         dirty_pages: 0,
                     },
+                    reads_transient_state: false,
                 },
                 None,
                 Err(system_api),
@@ -376,6 +378,22 @@ pub fn process<A: SystemStateAccessor>(
 
     let num_instructions_left = instance.get_num_instructions();
     let instance_stats = instance.get_stats();
+    let reads_transient_state = instance.store_data_mut().system_api.reads_transient_state();
+
+    // A message that modified or grew the Wasm heap while it is above the Wasm
+    // memory limit fails as if it trapped, so that none of its changes are
+    // applied. Growing the heap does not necessarily dirty any pages.
+    let heap_size = instance.heap_size();
+    let run_result = match run_result {
+        Ok(run_result) if !run_result.dirty_pages.is_empty() || heap_size > wasm_memory.size => {
+            instance
+                .store_data_mut()
+                .system_api
+                .check_wasm_memory_limit(heap_size)
+                .map(|()| run_result)
+        }
+        run_result => run_result,
+    };
 
     // Has the side effect up deallocating memory if message failed and
     // returning cycles from a request that wasn't sent.
@@ -422,6 +440,7 @@ pub fn process<A: SystemStateAccessor>(
             wasm_result,
             num_instructions_left,
             instance_stats,
+            reads_transient_state,
         },
         memory_deltas,
         Ok(instance),
@@ -116,7 +116,8 @@ use ic_wasm_types::{BinaryEncodedWasm, WasmInstrumentationError};
 use parity_wasm::builder;
 use parity_wasm::elements::{
     BlockType, BulkInstruction, ExportEntry, FuncBody, FunctionType, GlobalEntry, GlobalType,
-    InitExpr, Instruction, Instructions, Internal, Local, Module, Section, Type, ValueType,
+    InitExpr, Instruction, Instructions, Internal, Local, Module, Section, SimdInstruction, Type,
+    ValueType,
 };
 use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
@@ -136,12 +137,15 @@ fn instruction_to_mnemonic(i: &Instruction) -> String {
 
 /// The metering can be configured by providing a cost-per-instruction table and
 /// the default cost for an instruction in case it's not present in the cost
-/// table.
+/// table. SIMD instructions that are not present in the cost table have their
+/// own default cost, as they operate on 128-bit values.
 pub struct InstructionCostTable {
     // mapping of instruction mnemonic to its cost
     instruction_cost: HashMap<String, u64>,
     // default cost of an instruction (if not present in the cost table)
     default_cost: u64,
+    // default cost of a SIMD instruction (if not present in the cost table)
+    default_simd_cost: u64,
 }
 
 impl InstructionCostTable {
@@ -154,6 +158,11 @@ impl InstructionCostTable {
         self
     }
 
+    pub fn with_default_simd_cost(mut self, cost: u64) -> Self {
+        self.default_simd_cost = cost;
+        self
+    }
+
     pub fn with_instruction_cost(mut self, id: String, cost: u64) -> Self {
         self.instruction_cost.insert(id, cost);
         self
@@ -163,10 +172,13 @@ impl InstructionCostTable {
     // cost if the instruction is not in the cost table.
     fn cost(&self, i: &Instruction) -> u64 {
         let mnemonic = instruction_to_mnemonic(i);
-        *self
-            .instruction_cost
-            .get(&mnemonic)
-            .unwrap_or(&self.default_cost)
+        match self.instruction_cost.get(&mnemonic) {
+            Some(cost) => *cost,
+            None => match i {
+                Instruction::Simd(_) => self.default_simd_cost,
+                _ => self.default_cost,
+            },
+        }
     }
 }
 
@@ -187,8 +199,23 @@ impl Default for InstructionCostTable {
             0,
         );
 
+        // Floating point division and square root are considerably slower than
+        // the other SIMD instructions, which all use the default SIMD cost.
+        for simd in &[
+            SimdInstruction::F32x4Div,
+            SimdInstruction::F64x2Div,
+            SimdInstruction::F32x4Sqrt,
+            SimdInstruction::F64x2Sqrt,
+        ] {
+            instruction_cost.insert(
+                instruction_to_mnemonic(&Instruction::Simd(Box::new(simd.clone()))),
+                8,
+            );
+        }
+
         Self {
             default_cost: 1,
+            default_simd_cost: 2,
             instruction_cost,
         }
     }
@@ -368,6 +395,7 @@ pub struct InstrumentationOutput {
     /// All exported methods that are relevant to the IC.
     /// Methods relevant to the IC are:
     ///     - Queries (e.g. canister_query ___)
+    ///     - Composite queries (e.g. canister_composite_query ___)
     ///     - Updates (e.g. canister_update ___)
     ///     - System methods (e.g. canister_init)
     /// Other methods are assumed to be private to the module and are ignored.
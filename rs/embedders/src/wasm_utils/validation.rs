@@ -222,6 +222,16 @@ fn get_valid_system_apis(
                 },
             )],
         ),
+        (
+            "is_controller",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I32, ValueType::I32],
+                    return_type: vec![ValueType::I32],
+                },
+            )],
+        ),
         // Inter-canister method calls
         (
             "public",
@@ -293,6 +303,16 @@ fn get_valid_system_apis(
                 },
             )],
         ),
+        (
+            "call_with_best_effort_response",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I32],
+                    return_type: vec![],
+                },
+            )],
+        ),
         (
             "call_cycles_add",
             vec![(
@@ -514,6 +534,16 @@ fn get_valid_system_apis(
                 },
             )],
         ),
+        (
+            "canister_version",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![],
+                    return_type: vec![ValueType::I64],
+                },
+            )],
+        ),
         (
             "mint_cycles",
             vec![(
@@ -524,6 +554,26 @@ fn get_valid_system_apis(
                 },
             )],
         ),
+        (
+            "performance_counter",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I32],
+                    return_type: vec![ValueType::I64],
+                },
+            )],
+        ),
+        (
+            "global_timer_set",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I64],
+                    return_type: vec![ValueType::I64],
+                },
+            )],
+        ),
     ];
 
     let experimental_apis = match feature_flags.api_cycles_u128_flag {
@@ -622,6 +672,13 @@ fn get_valid_exported_functions() -> HashMap<String, FunctionSignature> {
                 return_type: vec![],
             },
         ),
+        (
+            "canister_composite_query",
+            FunctionSignature {
+                param_types: vec![],
+                return_type: vec![],
+            },
+        ),
         (
             "canister_pre_upgrade",
             FunctionSignature {
@@ -650,6 +707,13 @@ fn get_valid_exported_functions() -> HashMap<String, FunctionSignature> {
                 return_type: vec![],
             },
         ),
+        (
+            "canister_global_timer",
+            FunctionSignature {
+                param_types: vec![],
+                return_type: vec![],
+            },
+        ),
     ];
 
     valid_exported_functions
@@ -774,8 +838,8 @@ fn validate_import_section(
 }
 
 // Performs the following checks:
-// * Validates signatures of exported canister_update and canister_query
-//   methods.
+// * Validates signatures of exported canister_update, canister_query and
+//   canister_composite_query methods.
 // * Validates the signatures of other allowed exported functions (like
 //   `canister_init` or `canister_pre_upgrade`) if present.
 // * Validates that the canister doesn't export any reserved symbols
@@ -799,15 +863,17 @@ fn validate_export_section(module: &Module) -> Result<usize, WasmValidationError
                 let mut func_name = export.field();
                 // func_name holds either:
                 // - the entire exported non-IC function names, or
-                // - canister_query or canister_update part in case of the IC functions.
+                // - canister_query, canister_composite_query or canister_update part in
+                //   case of the IC functions.
                 if func_name.starts_with("canister_query ")
+                    || func_name.starts_with("canister_composite_query ")
                     || func_name.starts_with("canister_update ")
                 {
                     let parts: Vec<&str> = func_name.splitn(2, ' ').collect();
                     let unmangled_func_name = parts[1];
                     if seen_funcs.contains(unmangled_func_name) {
                         return Err(WasmValidationError::InvalidExportSection(format!(
-                            "Duplicate function '{}' exported for both update calls and queries.",
+                            "Duplicate function '{}' exported multiple times with different call types: update, query, or composite_query.",
                             unmangled_func_name
                         )));
                     }
@@ -1010,16 +1076,23 @@ pub fn validate_custom_section(
 }
 
 /// Sets Wasmtime flags to ensure deterministic execution.
-pub fn ensure_determinism(config: &mut Config) {
+///
+/// NaN canonicalization also applies to the lanes of SIMD float operations,
+/// so enabling SIMD does not introduce non-determinism.
+pub fn ensure_determinism(config: &mut Config, feature_flags: &FeatureFlags) {
     config
         .wasm_threads(false)
-        .wasm_simd(false)
+        .wasm_bulk_memory(true)
+        .wasm_simd(feature_flags.wasm_simd == FeatureStatus::Enabled)
         .cranelift_nan_canonicalization(true);
 }
 
-fn can_compile(wasm: &BinaryEncodedWasm) -> Result<(), WasmValidationError> {
+fn can_compile(
+    wasm: &BinaryEncodedWasm,
+    feature_flags: &FeatureFlags,
+) -> Result<(), WasmValidationError> {
     let mut config = wasmtime::Config::default();
-    ensure_determinism(&mut config);
+    ensure_determinism(&mut config, feature_flags);
     let engine = wasmtime::Engine::new(&config).map_err(|_| {
         WasmValidationError::WasmtimeValidation(String::from("Failed to initialize Wasm engine"))
     })?;
@@ -1074,7 +1147,7 @@ pub fn validate_wasm_binary(
     wasm: &BinaryEncodedWasm,
     config: &EmbeddersConfig,
 ) -> Result<WasmValidationDetails, WasmValidationError> {
-    can_compile(wasm)?;
+    can_compile(wasm, &config.feature_flags)?;
     let module = parity_wasm::deserialize_buffer::<Module>(wasm.as_slice())
         .map_err(|err| WasmValidationError::ParityDeserializeError(into_parity_wasm_error(err)))?;
     let imports_details = validate_import_section(&module, &config.feature_flags)?;
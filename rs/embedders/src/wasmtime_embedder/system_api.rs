@@ -2,7 +2,7 @@ use crate::wasmtime_embedder::{system_api_charges, StoreData};
 
 use ic_interfaces::execution_environment::{HypervisorError, HypervisorResult, SystemApi};
 use ic_logger::{error, info, ReplicaLogger};
-use ic_types::{CanisterId, Cycles, NumBytes, NumInstructions};
+use ic_types::{CanisterId, Cycles, NumBytes, NumInstructions, Time};
 
 use wasmtime::{AsContextMut, Caller, Linker, Store, Trap, Val};
 
@@ -111,6 +111,44 @@ fn charge_for_system_api_call<S: SystemApi>(
     }
 }
 
+/// Returns the current value of the instruction counter, i.e. the number of
+/// instructions left in the current execution. Errors are handled the same way
+/// as in `charge_for_system_api_call`.
+fn get_instruction_counter<S: SystemApi>(
+    log: &ReplicaLogger,
+    canister_id: CanisterId,
+    mut caller: &mut Caller<'_, StoreData<S>>,
+) -> Result<i64, Trap> {
+    let num_instructions_global = match caller.data().num_instructions_global {
+        None => {
+            error!(
+                log,
+                "[EXC-BUG] Canister {}: instructions counter is set to None.", canister_id,
+            );
+            return Err(process_err(
+                caller,
+                HypervisorError::InstructionLimitExceeded,
+            ));
+        }
+        Some(global) => global,
+    };
+    match num_instructions_global.get(&mut caller) {
+        Val::I64(current_instructions) => Ok(current_instructions),
+        others => {
+            error!(
+                log,
+                "[EXC-BUG] Canister {}: expected value of type I64 instead got {:?}",
+                canister_id,
+                others,
+            );
+            Err(process_err(
+                caller,
+                HypervisorError::InstructionLimitExceeded,
+            ))
+        }
+    }
+}
+
 pub(crate) fn syscalls<S: SystemApi>(
     log: ReplicaLogger,
     canister_id: CanisterId,
@@ -406,6 +444,16 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "is_controller", {
+            move |caller: Caller<'_, StoreData<S>>, src: u32, size: u32| {
+                with_memory_and_system_api(caller, |system_api, memory| {
+                    system_api.ic0_is_controller(src, size, memory)
+                })
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "debug_print", {
             let log = log.clone();
@@ -540,6 +588,17 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "call_with_best_effort_response", {
+            move |mut caller: Caller<'_, StoreData<S>>, timeout_seconds: i32| {
+                with_system_api(&mut caller, |s| {
+                    s.ic0_call_with_best_effort_response(timeout_seconds as u32)
+                })
+                .map_err(|e| process_err(caller, e))
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "call_cycles_add", {
             move |mut caller: Caller<'_, StoreData<S>>, amount: i64| {
@@ -676,6 +735,7 @@ pub(crate) fn syscalls<S: SystemApi>(
 
     linker
         .func_wrap("ic0", "stable64_write", {
+            let log = log.clone();
             move |mut caller: Caller<'_, StoreData<S>>, offset: i64, src: i64, size: i64| {
                 charge_for_system_api_call(
                     &log,
@@ -818,6 +878,19 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "performance_counter", {
+            move |mut caller: Caller<'_, StoreData<S>>, counter_type: u32| {
+                let instruction_counter = get_instruction_counter(&log, canister_id, &mut caller)?;
+                with_system_api(&mut caller, |s| {
+                    s.ic0_performance_counter(counter_type, instruction_counter)
+                })
+                .map_err(|e| process_err(caller, e))
+                .map(|counter| counter as i64)
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "canister_status", {
             move |mut caller: Caller<'_, StoreData<S>>| {
@@ -827,6 +900,15 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "canister_version", {
+            move |mut caller: Caller<'_, StoreData<S>>| {
+                with_system_api(&mut caller, |s| s.ic0_canister_version())
+                    .map_err(|e| process_err(caller, e))
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "certified_data_set", {
             move |caller: Caller<'_, StoreData<S>>, src: u32, size: u32| {
@@ -837,6 +919,18 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "global_timer_set", {
+            move |mut caller: Caller<'_, StoreData<S>>, time: u64| {
+                with_system_api(&mut caller, |s| {
+                    s.ic0_global_timer_set(Time::from_nanos_since_unix_epoch(time))
+                })
+                .map_err(|e| process_err(caller, e))
+                .map(|s| s.as_nanos_since_unix_epoch())
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "data_certificate_present", {
             move |mut caller: Caller<'_, StoreData<S>>| {
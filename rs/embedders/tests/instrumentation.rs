@@ -138,6 +138,33 @@ fn metering_memory_fill() {
     inject_and_cmp("memory_fill", &InstructionCostTable::new());
 }
 
+#[test]
+fn metering_simd() {
+    let mut features = Features::new();
+    features.enable_simd();
+    let wasm = wabt::wat2wasm_with_features(
+        r#"(module
+            (func (export "simd") (result v128)
+                (i32x4.add
+                    (v128.const i32x4 1 2 3 4)
+                    (f32x4.div
+                        (v128.const f32x4 1 2 3 4)
+                        (v128.const f32x4 1 1 1 1)))))"#,
+        features.clone(),
+    )
+    .unwrap();
+    let output = instrument(&BinaryEncodedWasm::new(wasm), &InstructionCostTable::new()).unwrap();
+    let module: Module = parity_wasm::elements::deserialize_buffer(output.binary.as_slice())
+        .expect("couldn't deserialize module");
+    // The function is decremented by three `v128.const` and one `i32x4.add`
+    // at the default SIMD cost of 2 each plus one `f32x4.div` at cost 8.
+    let code = module.code_section().unwrap().bodies()[0].code().elements();
+    assert_eq!(code[1], elements::Instruction::I64Const(16));
+    let out = wabt::wasm2wat_with_features(output.binary.as_slice(), features).unwrap();
+    assert!(out.contains("i32x4.add"));
+    assert!(out.contains("f32x4.div"));
+}
+
 #[test]
 fn test_get_data() {
     let output = instrument(
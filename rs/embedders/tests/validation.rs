@@ -1,5 +1,5 @@
 use assert_matches::assert_matches;
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::embedders::{Config as EmbeddersConfig, FeatureStatus};
 use ic_embedders::wasm_utils::validation::{
     extract_custom_section_name, validate_custom_section, validate_wasm_binary, WasmImportsDetails,
     WasmValidationDetails, RESERVED_SYMBOLS,
@@ -9,6 +9,8 @@ use ic_wasm_types::{BinaryEncodedWasm, WasmValidationError};
 fn wat2wasm(wat: &str) -> Result<BinaryEncodedWasm, wabt::Error> {
     let mut features = wabt::Features::new();
     features.enable_multi_value();
+    features.enable_bulk_memory();
+    features.enable_simd();
     wabt::wat2wasm_with_features(wat, features).map(BinaryEncodedWasm::new)
 }
 use ic_replicated_state::canister_state::execution_state::{
@@ -70,7 +72,8 @@ fn can_validate_valid_export_section() {
                   (export "canister_heartbeat" (func $x))
                   (export "canister_pre_upgrade" (func $x))
                   (export "canister_post_upgrade" (func $x))
-                  (export "canister_query read" (func $x)))"#,
+                  (export "canister_query read" (func $x))
+                  (export "canister_composite_query write" (func $x)))"#,
     )
     .unwrap();
 
@@ -245,6 +248,35 @@ fn can_validate_duplicate_method_for_canister_query_and_canister_update() {
     );
 }
 
+#[test]
+fn can_validate_invalid_canister_composite_query() {
+    let wasm = wat2wasm(
+        r#"(module
+                    (func $read (param i64 i32) (result i32) (local.get 1))
+                    (export "canister_composite_query read" (func $read)))"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Err(WasmValidationError::InvalidFunctionSignature(_))
+    );
+}
+
+#[test]
+fn can_validate_duplicate_method_for_canister_query_and_canister_composite_query() {
+    let wasm = wat2wasm(
+        r#"(module
+                    (func $read)
+                    (export "canister_query read" (func $read))
+                    (export "canister_composite_query read" (func $read)))"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Err(WasmValidationError::InvalidExportSection(_))
+    );
+}
+
 #[test]
 fn can_validate_canister_query_update_method_name_with_whitespace() {
     let wasm = wat2wasm(
@@ -688,3 +720,41 @@ fn can_validate_module_cycles_u128_related_imports() {
         Ok(WasmValidationDetails::default())
     );
 }
+
+#[test]
+fn can_validate_module_with_bulk_memory_instructions() {
+    let wasm = wat2wasm(
+        r#"(module
+            (memory 1)
+            (func (export "canister_update copy")
+                (memory.copy (i32.const 0) (i32.const 10) (i32.const 10))
+                (memory.fill (i32.const 0) (i32.const 42) (i32.const 10))
+            )
+        )"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Ok(_)
+    );
+}
+
+#[test]
+fn simd_instructions_are_accepted_only_if_enabled() {
+    let wasm = wat2wasm(
+        r#"(module
+            (func (export "canister_update add") (result v128)
+                (i32x4.add (v128.const i32x4 1 2 3 4) (v128.const i32x4 5 6 7 8))
+            )
+        )"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Err(WasmValidationError::WasmtimeValidation(_))
+    );
+
+    let mut config = EmbeddersConfig::default();
+    config.feature_flags.wasm_simd = FeatureStatus::Enabled;
+    assert_matches!(validate_wasm_binary(&wasm, &config), Ok(_));
+}
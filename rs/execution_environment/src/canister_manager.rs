@@ -9,8 +9,10 @@ use ic_base_types::NumSeconds;
 use ic_cow_state::CowMemoryManager;
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_ic00_types::{
-    CanisterIdRecord, CanisterStatusResultV2, InstallCodeArgs, Method as Ic00Method,
-    SetControllerArgs, UpdateSettingsArgs,
+    CanisterChangeDetails, CanisterChangeOrigin, CanisterIdRecord, CanisterInfoRequest,
+    CanisterInfoResponse, CanisterSnapshotArgs, CanisterSnapshotResponse, CanisterStatusResultV2,
+    InstallChunkedCodeArgs, InstallCodeArgs, LogVisibility, Method as Ic00Method,
+    SetControllerArgs, TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
 };
 use ic_interfaces::execution_environment::{
     CanisterOutOfCyclesError, ExecutionParameters, HypervisorError, IngressHistoryWriter,
@@ -19,7 +21,9 @@ use ic_logger::{error, fatal, info, ReplicaLogger};
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    CallOrigin, CanisterState, CanisterStatus, Memory, ReplicatedState, SchedulerState, SystemState,
+    canister_state::system_state::CHUNK_SIZE, page_map::PageMap, CallOrigin, CanisterSnapshot,
+    CanisterState, CanisterStatus, ExecutionState, Memory, ReplicatedState, SchedulerState,
+    SystemState, WasmChunkHash, WasmChunkStoreError,
 };
 use ic_state_layout::{CanisterLayout, CheckpointLayout, RwPolicy};
 use ic_types::{
@@ -31,13 +35,17 @@ use ic_types::{
     },
     user_error::{ErrorCode, RejectCode, UserError},
     CanisterId, CanisterStatusType, ComputeAllocation, Cycles, Height, InstallCodeContext,
-    MemoryAllocation, NumBytes, NumInstructions, PrincipalId, SubnetId, Time, UserId,
+    MemoryAllocation, NumBytes, NumInstructions, PrincipalId, SnapshotId, SubnetId, Time, UserId,
 };
 use ic_utils::ic_features::cow_state_feature;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::{collections::BTreeSet, convert::TryFrom, str::FromStr, sync::Arc};
 
+/// The maximum number of snapshots a canister can have at any time. Taking
+/// another snapshot requires replacing an existing one.
+pub(crate) const MAX_SNAPSHOTS_PER_CANISTER: usize = 1;
+
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct InstallCodeResult {
     pub heap_delta: NumBytes,
@@ -165,8 +173,14 @@ impl CanisterManager {
             | Ok(Ic00Method::CreateCanister)
             | Ok(Ic00Method::SetupInitialDKG)
             | Ok(Ic00Method::SignWithECDSA)
-            | Ok(Ic00Method::GetMockECDSAPublicKey)
-            | Ok(Ic00Method::SignWithMockECDSA)
+            | Ok(Ic00Method::HttpRequest)
+            | Ok(Ic00Method::BitcoinGetBalance)
+            | Ok(Ic00Method::BitcoinGetUtxos)
+            | Ok(Ic00Method::BitcoinSendTransaction)
+            | Ok(Ic00Method::ECDSAPublicKey)
+            | Ok(Ic00Method::CanisterInfo)
+            // "FetchCanisterLogs" is only available as a query.
+            | Ok(Ic00Method::FetchCanisterLogs)
             // "DepositCycles" can be called by anyone however as ingress message
             // cannot carry cycles, it does not make sense to allow them from users.
             | Ok(Ic00Method::DepositCycles) => rejected_canister_err,
@@ -178,7 +192,10 @@ impl CanisterManager {
             | Ok(Ic00Method::StartCanister)
             | Ok(Ic00Method::UninstallCode)
             | Ok(Ic00Method::StopCanister)
-            | Ok(Ic00Method::DeleteCanister) => match Decode!(payload, CanisterIdRecord) {
+            | Ok(Ic00Method::DeleteCanister)
+            | Ok(Ic00Method::ListCanisterSnapshots)
+            | Ok(Ic00Method::ClearChunkStore)
+            | Ok(Ic00Method::StoredChunks) => match Decode!(payload, CanisterIdRecord) {
                 Err(_) => rejected_canister_err,
                 Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
             },
@@ -194,6 +211,28 @@ impl CanisterManager {
                 Err(_) => rejected_canister_err,
                 Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
             },
+            Ok(Ic00Method::UploadChunk) => match Decode!(payload, UploadChunkArgs) {
+                Err(_) => rejected_canister_err,
+                Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
+            },
+            Ok(Ic00Method::InstallChunkedCode) => {
+                match Decode!(payload, InstallChunkedCodeArgs) {
+                    Err(_) => rejected_canister_err,
+                    Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
+                }
+            }
+            Ok(Ic00Method::TakeCanisterSnapshot) => {
+                match Decode!(payload, TakeCanisterSnapshotArgs) {
+                    Err(_) => rejected_canister_err,
+                    Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
+                }
+            }
+            Ok(Ic00Method::LoadCanisterSnapshot) | Ok(Ic00Method::DeleteCanisterSnapshot) => {
+                match Decode!(payload, CanisterSnapshotArgs) {
+                    Err(_) => rejected_canister_err,
+                    Ok(args) => is_sender_controller(args.get_canister_id(), sender, state),
+                }
+            }
 
             // Nobody pays for `raw_rand`, so this cannot be used via ingress messages
             Ok(Ic00Method::RawRand) => rejected_canister_err,
@@ -288,6 +327,15 @@ impl CanisterManager {
         if let Some(freezing_threshold) = settings.freezing_threshold {
             canister.system_state.freeze_threshold = freezing_threshold;
         }
+        if let Some(log_visibility) = settings.log_visibility {
+            canister.system_state.log_visibility = log_visibility;
+        }
+        if let Some(wasm_memory_limit) = settings.wasm_memory_limit {
+            canister.system_state.wasm_memory_limit = match wasm_memory_limit.get() {
+                0 => None,
+                _ => Some(wasm_memory_limit),
+            };
+        }
     }
 
     /// Tries to apply the requested settings on the canister identified by
@@ -296,10 +344,17 @@ impl CanisterManager {
         &self,
         sender: PrincipalId,
         settings: CanisterSettings,
-        canister: &mut CanisterState,
-        total_subnet_compute_allocation_used: u64,
-        total_subnet_memory_taken: NumBytes,
+        canister_id: CanisterId,
+        state: &mut ReplicatedState,
     ) -> Result<(), CanisterManagerError> {
+        let time = state.time();
+        let origin = canister_change_origin(sender, state);
+        let total_subnet_compute_allocation_used = state.total_compute_allocation();
+        let total_subnet_memory_taken = state.total_memory_taken();
+        let canister = state
+            .canister_state_mut(&canister_id)
+            .ok_or(CanisterManagerError::CanisterNotFound(canister_id))?;
+
         // Verify controller.
         self.validate_controller(canister, &sender)?;
         self.validate_compute_allocation(
@@ -315,7 +370,20 @@ impl CanisterManager {
 
         let validated_settings =
             ValidatedCanisterSettings::try_from((settings, self.config.max_controllers))?;
+        let controllers_changed =
+            validated_settings.controller.is_some() || validated_settings.controllers.is_some();
         self.do_update_settings(validated_settings, canister);
+        canister.system_state.canister_version += 1;
+
+        if controllers_changed {
+            let controllers = canister.controllers().iter().copied().collect();
+            canister.system_state.canister_history.add_canister_change(
+                time,
+                canister.system_state.canister_version,
+                origin,
+                CanisterChangeDetails::ControllersChange { controllers },
+            );
+        }
 
         Ok(())
     }
@@ -412,6 +480,7 @@ impl CanisterManager {
         // is held inside state. Then Rust's borrow checker prevents us from
         // calling further methods on the state.
         let time = state.time();
+        let origin = canister_change_origin(context.sender, state);
         let canister_layout_path = state.path().to_path_buf();
         let compute_allocation_used = state.total_compute_allocation();
         let memory_taken = state.total_memory_taken();
@@ -505,6 +574,21 @@ impl CanisterManager {
                 let new_wasm_hash = self.get_wasm_hash(&new_canister);
                 self.cycles_account_manager
                     .refund_execution_cycles(&mut new_canister.system_state, instructions_left);
+                let canister_version = new_canister.system_state.canister_version;
+                new_canister
+                    .system_state
+                    .canister_history
+                    .add_canister_change(
+                        time,
+                        canister_version,
+                        origin,
+                        CanisterChangeDetails::CodeDeployment {
+                            mode,
+                            module_hash: new_wasm_hash
+                                .map(|hash| hash.to_vec())
+                                .unwrap_or_default(),
+                        },
+                    );
                 state.put_canister_state(new_canister);
                 // We managed to create a new canister and will be dropping the
                 // older one. So we get rid of the previous heap to make sure it
@@ -543,6 +627,7 @@ impl CanisterManager {
         state: &mut ReplicatedState,
     ) -> Result<(), CanisterManagerError> {
         let time = state.time();
+        let origin = canister_change_origin(sender, state);
         let path = state.path().to_owned();
         let canister = match state.canister_state_mut(&canister_id) {
             Some(canister) => canister,
@@ -558,6 +643,15 @@ impl CanisterManager {
             }
         }
 
+        // The chunks uploaded for a module are of no use once it is gone.
+        canister.system_state.wasm_chunk_store.clear();
+        canister.system_state.canister_history.add_canister_change(
+            time,
+            canister.system_state.canister_version,
+            origin,
+            CanisterChangeDetails::CodeUninstall,
+        );
+
         let rejects = uninstall_canister(&self.log, canister, &path, time);
         crate::util::process_responses(
             rejects,
@@ -707,6 +801,11 @@ impl CanisterManager {
             canister.scheduler_state.compute_allocation.as_percent(),
             Some(canister.memory_allocation().bytes().get()),
             canister.system_state.freeze_threshold.get(),
+            canister.system_state.canister_version,
+            canister
+                .system_state
+                .wasm_memory_limit
+                .map(|limit| limit.get()),
         ))
     }
 
@@ -719,20 +818,9 @@ impl CanisterManager {
         new_controller: PrincipalId,
         state: &mut ReplicatedState,
     ) -> Result<(), CanisterManagerError> {
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_taken = state.total_memory_taken();
-        let canister = state
-            .canister_state_mut(&canister_id)
-            .ok_or(CanisterManagerError::CanisterNotFound(canister_id))?;
-
-        let settings = CanisterSettings::new(Some(new_controller), None, None, None, None);
-        self.update_settings(
-            sender,
-            settings,
-            canister,
-            compute_allocation_used,
-            memory_taken,
-        )
+        let settings =
+            CanisterSettings::new(Some(new_controller), None, None, None, None, None, None);
+        self.update_settings(sender, settings, canister_id, state)
     }
 
     /// Permanently deletes a canister from `ReplicatedState`.
@@ -804,6 +892,11 @@ impl CanisterManager {
         // Take out the canister from `ReplicatedState`.
         let _canister_to_delete = state.take_canister_state(&canister_id_to_delete).unwrap();
 
+        // The snapshots of the canister are deleted along with it.
+        state
+            .canister_snapshots
+            .remove_canister_snapshots(canister_id_to_delete);
+
         let layout = canister_layout(state.path(), &canister_id_to_delete);
         layout
             .mark_deleted()
@@ -814,6 +907,296 @@ impl CanisterManager {
         Ok(())
     }
 
+    /// Takes a snapshot of the Wasm module, memories and certified data of a
+    /// stopped canister.
+    ///
+    /// If `replace_snapshot` is given, the snapshot with that id is deleted
+    /// and the new one takes its place in the snapshot count of the canister.
+    /// Otherwise, the canister must have less than
+    /// `MAX_SNAPSHOTS_PER_CANISTER` snapshots.
+    pub(crate) fn take_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        replace_snapshot: Option<SnapshotId>,
+        state: &mut ReplicatedState,
+    ) -> Result<CanisterSnapshotResponse, CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+        self.validate_canister_is_stopped_for_snapshot(canister)?;
+
+        let snapshot = CanisterSnapshot::from_canister(canister, state.time())
+            .ok_or(CanisterManagerError::CanisterSnapshotNoModule(canister_id))?;
+
+        match replace_snapshot {
+            Some(snapshot_id) => {
+                self.validate_snapshot_belongs_to_canister(state, canister_id, snapshot_id)?;
+                state.canister_snapshots.remove(snapshot_id);
+            }
+            None => {
+                if state.canister_snapshots.count(canister_id) >= MAX_SNAPSHOTS_PER_CANISTER {
+                    return Err(CanisterManagerError::CanisterSnapshotLimitExceeded {
+                        canister_id,
+                        limit: MAX_SNAPSHOTS_PER_CANISTER,
+                    });
+                }
+            }
+        }
+
+        let snapshot_id = SnapshotId::from(state.metadata.next_snapshot_id);
+        state.metadata.next_snapshot_id += 1;
+        let response = CanisterSnapshotResponse {
+            id: snapshot_id.get(),
+            taken_at_timestamp: snapshot.taken_at_timestamp.as_nanos_since_unix_epoch(),
+            total_size: snapshot.size().get(),
+        };
+        state
+            .canister_snapshots
+            .insert(snapshot_id, Arc::new(snapshot));
+        Ok(response)
+    }
+
+    /// Replaces the Wasm module, memories and certified data of a stopped
+    /// canister with the ones captured by the given snapshot.
+    pub(crate) fn load_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        snapshot_id: SnapshotId,
+        state: &mut ReplicatedState,
+    ) -> Result<(), CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+        self.validate_canister_is_stopped_for_snapshot(canister)?;
+        let snapshot =
+            self.validate_snapshot_belongs_to_canister(state, canister_id, snapshot_id)?;
+
+        // The page maps of the snapshot are backed by the files of the
+        // snapshot (or by the canister's files at the time the snapshot was
+        // taken), so only fresh copies can be persisted as the canister's
+        // memory in the next checkpoint.
+        let canister_root = canister_layout(state.path(), &canister_id).raw_path();
+        let mut execution_state = ExecutionState::new(
+            canister_root,
+            Arc::clone(&snapshot.wasm_binary),
+            snapshot.exports.clone(),
+            Memory::new(
+                snapshot.wasm_memory.page_map.copy_without_checkpoint(),
+                snapshot.wasm_memory.size,
+            ),
+            Memory::new(
+                snapshot.stable_memory.page_map.copy_without_checkpoint(),
+                snapshot.stable_memory.size,
+            ),
+            snapshot.exported_globals.clone(),
+        );
+        execution_state.metadata = snapshot.metadata.clone();
+        let certified_data = snapshot.certified_data.clone();
+
+        let canister = state.canister_state_mut(&canister_id).unwrap();
+        let old_execution_state = canister.execution_state.replace(execution_state);
+        if let MemoryAllocation::Reserved(bytes) = canister.memory_allocation() {
+            let memory_usage = canister.memory_usage(self.config.own_subnet_type);
+            if memory_usage > bytes {
+                canister.execution_state = old_execution_state;
+                return Err(CanisterManagerError::NotEnoughMemoryAllocationGiven {
+                    canister_id,
+                    memory_allocation_given: MemoryAllocation::Reserved(bytes),
+                    memory_usage_needed: memory_usage,
+                });
+            }
+        }
+        canister.system_state.certified_data = certified_data;
+        Ok(())
+    }
+
+    /// Returns the snapshots of the given canister, ordered by id.
+    pub(crate) fn list_canister_snapshots(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &ReplicatedState,
+    ) -> Result<Vec<CanisterSnapshotResponse>, CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+
+        Ok(state
+            .canister_snapshots
+            .list_snapshots(canister_id)
+            .map(|(snapshot_id, snapshot)| CanisterSnapshotResponse {
+                id: snapshot_id.get(),
+                taken_at_timestamp: snapshot.taken_at_timestamp.as_nanos_since_unix_epoch(),
+                total_size: snapshot.size().get(),
+            })
+            .collect())
+    }
+
+    /// Deletes a snapshot of the given canister.
+    pub(crate) fn delete_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        snapshot_id: SnapshotId,
+        state: &mut ReplicatedState,
+    ) -> Result<(), CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+        self.validate_snapshot_belongs_to_canister(state, canister_id, snapshot_id)?;
+
+        state.canister_snapshots.remove(snapshot_id);
+        Ok(())
+    }
+
+    /// Stores a chunk of a Wasm module in the chunk store of the canister and
+    /// returns its SHA-256 hash.
+    ///
+    /// A new chunk must fit into the memory allocation of the canister, or
+    /// into the remaining memory capacity of the subnet if the canister has no
+    /// memory allocation.
+    pub(crate) fn upload_chunk(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        chunk: Vec<u8>,
+        state: &mut ReplicatedState,
+    ) -> Result<WasmChunkHash, CanisterManagerError> {
+        let memory_taken = state.total_memory_taken();
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+
+        let canister = state.canister_state_mut(&canister_id).unwrap();
+        let num_chunks = canister.system_state.wasm_chunk_store.len();
+        let hash = canister
+            .system_state
+            .wasm_chunk_store
+            .insert_chunk(chunk)
+            .map_err(|err| CanisterManagerError::WasmChunkStoreError { canister_id, err })?;
+        if canister.system_state.wasm_chunk_store.len() == num_chunks {
+            // The chunk was already stored.
+            return Ok(hash);
+        }
+
+        let chunk_memory = NumBytes::from(CHUNK_SIZE);
+        let err = match canister.memory_allocation() {
+            MemoryAllocation::Reserved(bytes) => {
+                let memory_usage = canister.memory_usage(self.config.own_subnet_type);
+                if memory_usage > bytes {
+                    Some(CanisterManagerError::NotEnoughMemoryAllocationGiven {
+                        canister_id,
+                        memory_allocation_given: MemoryAllocation::Reserved(bytes),
+                        memory_usage_needed: memory_usage,
+                    })
+                } else {
+                    None
+                }
+            }
+            MemoryAllocation::BestEffort => {
+                if memory_taken + chunk_memory > self.config.subnet_memory_capacity {
+                    Some(CanisterManagerError::SubnetMemoryCapacityOverSubscribed {
+                        requested: chunk_memory,
+                        available: NumBytes::from(
+                            self.config
+                                .subnet_memory_capacity
+                                .get()
+                                .saturating_sub(memory_taken.get()),
+                        ),
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+        if let Some(err) = err {
+            canister.system_state.wasm_chunk_store.remove_chunk(&hash);
+            return Err(err);
+        }
+        Ok(hash)
+    }
+
+    /// Removes all chunks from the chunk store of the canister.
+    pub(crate) fn clear_chunk_store(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &mut ReplicatedState,
+    ) -> Result<(), CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+
+        state
+            .canister_state_mut(&canister_id)
+            .unwrap()
+            .system_state
+            .wasm_chunk_store
+            .clear();
+        Ok(())
+    }
+
+    /// Returns the hashes of the chunks in the chunk store of the canister.
+    pub(crate) fn stored_chunks(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &ReplicatedState,
+    ) -> Result<Vec<WasmChunkHash>, CanisterManagerError> {
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+
+        Ok(canister
+            .system_state
+            .wasm_chunk_store
+            .keys()
+            .cloned()
+            .collect())
+    }
+
+    /// Assembles the Wasm module of an `install_chunked_code` message from the
+    /// chunk store of the canister and checks it against the expected module
+    /// hash. The returned arguments are then installed like the ones of a
+    /// regular `install_code` message.
+    pub(crate) fn assemble_chunked_code(
+        &self,
+        sender: PrincipalId,
+        args: InstallChunkedCodeArgs,
+        state: &ReplicatedState,
+    ) -> Result<InstallCodeArgs, CanisterManagerError> {
+        let canister_id = args.get_canister_id();
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        self.validate_controller(canister, &sender)?;
+
+        let store = &canister.system_state.wasm_chunk_store;
+        let mut wasm_module = Vec::new();
+        for chunk_hash in args.chunk_hashes_list.iter() {
+            let chunk = WasmChunkHash::try_from(chunk_hash.hash.as_slice())
+                .ok()
+                .and_then(|hash| store.get_chunk(&hash))
+                .ok_or_else(|| CanisterManagerError::WasmChunkNotFound {
+                    canister_id,
+                    hash: chunk_hash.hash.clone(),
+                })?;
+            wasm_module.extend_from_slice(chunk);
+        }
+
+        let wasm_module_hash = ic_crypto_sha::Sha256::hash(&wasm_module);
+        if wasm_module_hash.as_slice() != args.wasm_module_hash.as_slice() {
+            return Err(CanisterManagerError::WasmModuleHashMismatch {
+                canister_id,
+                expected: args.wasm_module_hash,
+                actual: wasm_module_hash.to_vec(),
+            });
+        }
+
+        Ok(InstallCodeArgs::new(
+            args.mode,
+            canister_id,
+            wasm_module,
+            args.arg,
+            None,
+            None,
+            None,
+        ))
+    }
+
     /// Deposits the amount of cycles specified from the sender to the target
     /// `canister_id`.
     ///
@@ -840,7 +1223,14 @@ impl CanisterManager {
         let canister_id = context.canister_id;
         let layout = canister_layout(&canister_layout_path, &canister_id);
 
-        let system_state = old_canister.system_state.clone();
+        let mut system_state = old_canister.system_state.clone();
+        // The global timer is deactivated when the code of the canister
+        // changes. `canister_init` may set it again.
+        system_state.global_timer = None;
+        // Bumped before `canister_init` runs so that it observes the new
+        // version. The bump is discarded along with `system_state` if the
+        // installation fails.
+        system_state.canister_version += 1;
         let execution_state = match self.hypervisor.create_execution_state(
             context.wasm_module,
             layout.raw_path(),
@@ -980,6 +1370,13 @@ impl CanisterManager {
                 .upgrade();
         }
 
+        // The global timer is deactivated when the code of the canister
+        // changes. `canister_post_upgrade` may set it again.
+        new_canister.system_state.global_timer = None;
+        // `canister_pre_upgrade` observes the old version and
+        // `canister_post_upgrade` the new one.
+        new_canister.system_state.canister_version += 1;
+
         // Replace the execution state of the canister with a new execution state, but
         // persist the stable memory (if it exists).
         let layout = canister_layout(&canister_layout_path, &canister_id);
@@ -1135,6 +1532,8 @@ impl CanisterManager {
 
         let new_canister_id = self.generate_new_canister_id(state)?;
         self.validate_canister_id_available(state, &new_canister_id)?;
+        let time = state.time();
+        let origin = canister_change_origin(sender, state);
 
         // Take the fee out of the cycles that are going to be added as the canister's
         // initial balance.
@@ -1157,6 +1556,17 @@ impl CanisterManager {
         let mut new_canister = CanisterState::new(system_state, None, scheduler_state);
 
         self.do_update_settings(settings, &mut new_canister);
+        let controllers = new_canister.controllers().iter().copied().collect();
+        let canister_version = new_canister.system_state.canister_version;
+        new_canister
+            .system_state
+            .canister_history
+            .add_canister_change(
+                time,
+                canister_version,
+                origin,
+                CanisterChangeDetails::Creation { controllers },
+            );
 
         // Add new canister to the replicated state.
         state.put_canister_state(new_canister);
@@ -1297,6 +1707,33 @@ impl CanisterManager {
         Ok(())
     }
 
+    fn validate_canister_is_stopped_for_snapshot(
+        &self,
+        canister: &CanisterState,
+    ) -> Result<(), CanisterManagerError> {
+        if canister.status() != CanisterStatusType::Stopped {
+            return Err(CanisterManagerError::CanisterSnapshotCanisterNotStopped(
+                canister.canister_id(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_snapshot_belongs_to_canister<'a>(
+        &self,
+        state: &'a ReplicatedState,
+        canister_id: CanisterId,
+        snapshot_id: SnapshotId,
+    ) -> Result<&'a Arc<CanisterSnapshot>, CanisterManagerError> {
+        match state.canister_snapshots.get(snapshot_id) {
+            Some(snapshot) if snapshot.canister_id == canister_id => Ok(snapshot),
+            _ => Err(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id,
+            }),
+        }
+    }
+
     // WARNING!!! If you change the logic here, please ensure that the sequence
     // of NNS canister ids as defined in nns/constants/src/constants.rs are also
     // updated.
@@ -1341,6 +1778,43 @@ impl CanisterManager {
             .as_ref()
             .map(|execution_state| execution_state.wasm_binary.binary.hash_sha256())
     }
+
+    /// Returns the module hash, the controllers and the most recent changes
+    /// of a canister. Unlike `canister_status`, this is available to any
+    /// canister, not only to the controllers.
+    pub(crate) fn get_canister_info(
+        &self,
+        args: CanisterInfoRequest,
+        state: &ReplicatedState,
+    ) -> Result<CanisterInfoResponse, CanisterManagerError> {
+        let canister_id = args.get_canister_id();
+        let canister = state
+            .canister_state(&canister_id)
+            .ok_or(CanisterManagerError::CanisterNotFound(canister_id))?;
+        let history = &canister.system_state.canister_history;
+        let num_requested_changes = args.num_requested_changes().unwrap_or(0);
+
+        Ok(CanisterInfoResponse {
+            total_num_changes: history.total_num_changes(),
+            recent_changes: history
+                .recent_changes(usize::try_from(num_requested_changes).unwrap_or(usize::MAX)),
+            module_hash: self.get_wasm_hash(canister).map(|hash| hash.to_vec()),
+            controllers: canister.controllers().iter().copied().collect(),
+        })
+    }
+}
+
+/// Returns the origin to record in the history of a canister for a change
+/// made by `sender`. Canister ids are always covered by the routing table
+/// while user ids never are, so this tells the two apart without knowing how
+/// the message was delivered.
+fn canister_change_origin(sender: PrincipalId, state: &ReplicatedState) -> CanisterChangeOrigin {
+    match state.metadata.network_topology.routing_table.route(sender) {
+        Some(_) => CanisterChangeOrigin::FromCanister {
+            canister_id: sender,
+        },
+        None => CanisterChangeOrigin::FromUser { user_id: sender },
+    }
 }
 #[doc(hidden)] // pub for usage in tests
 pub(crate) fn canister_layout(
@@ -1396,6 +1870,29 @@ pub(crate) enum CanisterManagerError {
         subnet_id: SubnetId,
         max_number_of_canisters: u64,
     },
+    CanisterSnapshotNotFound {
+        canister_id: CanisterId,
+        snapshot_id: SnapshotId,
+    },
+    CanisterSnapshotCanisterNotStopped(CanisterId),
+    CanisterSnapshotNoModule(CanisterId),
+    CanisterSnapshotLimitExceeded {
+        canister_id: CanisterId,
+        limit: usize,
+    },
+    WasmChunkStoreError {
+        canister_id: CanisterId,
+        err: WasmChunkStoreError,
+    },
+    WasmChunkNotFound {
+        canister_id: CanisterId,
+        hash: Vec<u8>,
+    },
+    WasmModuleHashMismatch {
+        canister_id: CanisterId,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
 }
 
 impl From<CanisterManagerError> for UserError {
@@ -1533,6 +2030,76 @@ impl From<CanisterManagerError> for UserError {
                     format!("Subnet {} has reached the allowed canister limit of {} canisters. Retry creating the canister.", subnet_id, max_number_of_canisters),
                 )
             }
+            CanisterSnapshotNotFound { canister_id, snapshot_id } => {
+                Self::new(
+                    ErrorCode::CanisterSnapshotNotFound,
+                    format!(
+                        "Could not find snapshot {} of canister {}.",
+                        snapshot_id, canister_id,
+                    ),
+                )
+            }
+            CanisterSnapshotCanisterNotStopped(canister_id) => {
+                Self::new(
+                    ErrorCode::CanisterNotStopped,
+                    format!(
+                        "Canister {} must be stopped before taking or loading a snapshot.",
+                        canister_id,
+                    ),
+                )
+            }
+            CanisterSnapshotNoModule(canister_id) => {
+                Self::new(
+                    ErrorCode::CanisterWasmModuleNotFound,
+                    format!(
+                        "Cannot take a snapshot of canister {} because it has no Wasm module installed.",
+                        canister_id,
+                    ),
+                )
+            }
+            CanisterSnapshotLimitExceeded { canister_id, limit } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!(
+                        "Canister {} has reached the limit of {} snapshots. Specify `replace_snapshot` to replace an existing one.",
+                        canister_id, limit,
+                    ),
+                )
+            }
+            WasmChunkStoreError { canister_id, err } => {
+                let message = match err {
+                    WasmChunkStoreError::ChunkTooLarge { size } => format!(
+                        "Chunk of {} bytes exceeds the maximum chunk size of {} bytes.",
+                        size, CHUNK_SIZE,
+                    ),
+                    WasmChunkStoreError::StoreFull { max_chunks } => format!(
+                        "Chunk store is full. At most {} chunks can be stored.",
+                        max_chunks,
+                    ),
+                };
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!("Could not upload chunk to canister {}: {}", canister_id, message),
+                )
+            }
+            WasmChunkNotFound { canister_id, hash } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!(
+                        "Chunk {} is not in the chunk store of canister {}.",
+                        hex::encode(hash), canister_id,
+                    ),
+                )
+            }
+            WasmModuleHashMismatch { canister_id, expected, actual } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!(
+                        "The assembled Wasm module for canister {} has hash {} but {} was expected.",
+                        canister_id, hex::encode(actual), hex::encode(expected),
+                    ),
+                )
+            }
         }
     }
 }
@@ -1556,19 +2123,7 @@ pub(crate) fn truncate_canister_heap(
     canister_id: CanisterId,
 ) {
     let layout = canister_layout(state_path, &canister_id);
-    let heap_file = layout.vmemory_0();
-    if let Err(err) = nix::unistd::truncate(&heap_file, 0) {
-        // It's OK if the file doesn't exist, everything else is a fatal error.
-        if err != nix::errno::Errno::ENOENT {
-            fatal!(
-                log,
-                "failed to truncate heap of canister {} stored at {}: {}",
-                canister_id,
-                heap_file.display(),
-                err
-            )
-        }
-    }
+    truncate_heap_file(log, &layout.vmemory_0(), canister_id, "heap");
 }
 
 pub(crate) fn truncate_canister_stable_memory(
@@ -1577,18 +2132,26 @@ pub(crate) fn truncate_canister_stable_memory(
     canister_id: CanisterId,
 ) {
     let layout = canister_layout(state_path, &canister_id);
-    let stable_memory_file = layout.stable_memory_blob();
-    if let Err(err) = nix::unistd::truncate(&stable_memory_file, 0) {
-        // It's OK if the file doesn't exist, everything else is a fatal error.
-        if err != nix::errno::Errno::ENOENT {
-            fatal!(
-                log,
-                "failed to truncate stable memory of canister {} stored at {}: {}",
-                canister_id,
-                stable_memory_file.display(),
-                err
-            )
-        }
+    truncate_heap_file(log, &layout.stable_memory_blob(), canister_id, "stable memory");
+}
+
+/// Replaces the heap file at `path` with an empty one and removes its
+/// overlays. The file is replaced rather than truncated in place because it
+/// might be shared with a checkpoint.
+fn truncate_heap_file(log: &ReplicaLogger, path: &Path, canister_id: CanisterId, what: &str) {
+    // It's OK if the file doesn't exist.
+    if !path.exists() {
+        return;
+    }
+    if let Err(err) = PageMap::new().persist_and_replace(path) {
+        fatal!(
+            log,
+            "failed to truncate {} of canister {} stored at {}: {}",
+            what,
+            canister_id,
+            path.display(),
+            err
+        )
     }
 }
 
@@ -1610,6 +2173,9 @@ pub fn uninstall_canister(
     // Drop its certified data.
     canister.system_state.certified_data = Vec::new();
 
+    // Deactivate its global timer.
+    canister.system_state.global_timer = None;
+
     truncate_canister_heap(log, state_path, canister.canister_id());
     truncate_canister_stable_memory(log, state_path, canister.canister_id());
 
@@ -1679,6 +2245,8 @@ struct ValidatedCanisterSettings {
     pub compute_allocation: Option<ComputeAllocation>,
     pub memory_allocation: Option<MemoryAllocation>,
     pub freezing_threshold: Option<NumSeconds>,
+    pub log_visibility: Option<LogVisibility>,
+    pub wasm_memory_limit: Option<NumBytes>,
 }
 
 impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
@@ -1714,6 +2282,8 @@ impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
             compute_allocation: settings.compute_allocation(),
             memory_allocation: settings.memory_allocation(),
             freezing_threshold: settings.freezing_threshold(),
+            log_visibility: settings.log_visibility(),
+            wasm_memory_limit: settings.wasm_memory_limit(),
         })
     }
 }
@@ -1,7 +1,7 @@
 use crate::{
     canister_manager::{
         canister_layout, uninstall_canister, CanisterManager, CanisterManagerError,
-        CanisterMgrConfig, StopCanisterResult,
+        CanisterMgrConfig, StopCanisterResult, MAX_SNAPSHOTS_PER_CANISTER,
     },
     canister_settings::CanisterSettings,
     hypervisor::Hypervisor,
@@ -22,8 +22,9 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_routing_table::{CanisterIdRange, RoutingTable};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    page_map, testing::CanisterQueuesTesting, CallContextAction, CallContextManager, CallOrigin,
-    CanisterStatus, NumWasmPages, PageMap, ReplicatedState,
+    canister_state::system_state::CHUNK_SIZE, page_map, testing::CanisterQueuesTesting,
+    CallContextAction, CallContextManager, CallOrigin, CanisterStatus, NumWasmPages, PageIndex,
+    PageMap, ReplicatedState,
 };
 use ic_test_utilities::{
     cycles_account_manager::CyclesAccountManagerBuilder,
@@ -46,11 +47,14 @@ use ic_test_utilities::{
 use ic_types::messages::StopCanisterContext;
 use ic_types::nominal_cycles::NominalCycles;
 use ic_types::{
+    ic00::{
+        CanisterChangeDetails, CanisterChangeOrigin, CanisterInfoRequest, InstallChunkedCodeArgs,
+    },
     ingress::{IngressStatus, WasmResult},
     messages::{CallbackId, CanisterInstallMode, RequestOrResponse},
     user_error::{ErrorCode, UserError},
     CanisterId, CanisterStatusType, ComputeAllocation, Cycles, InstallCodeContext,
-    MemoryAllocation, NumBytes, NumInstructions, QueryAllocation, SubnetId,
+    MemoryAllocation, NumBytes, NumInstructions, QueryAllocation, SnapshotId, SubnetId,
 };
 use ic_wasm_types::WasmValidationError;
 use lazy_static::lazy_static;
@@ -1913,6 +1917,150 @@ fn set_controller_with_correct_controller() {
     });
 }
 
+#[test]
+fn canister_version_is_bumped_on_code_and_settings_changes() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(1).get();
+        let canister_id = canister_manager
+            .create_canister(
+                sender,
+                subnet_test_id(1),
+                *INITIAL_CYCLES,
+                CanisterSettings::default(),
+                MAX_NUMBER_OF_CANISTERS,
+                &mut state,
+            )
+            .0
+            .unwrap();
+        let canister_version = |state: &ReplicatedState| {
+            state
+                .canister_state(&canister_id)
+                .unwrap()
+                .system_state
+                .canister_version
+        };
+        assert_eq!(canister_version(&state), 0);
+
+        canister_manager
+            .install_code(
+                InstallCodeContextBuilder::default()
+                    .sender(sender)
+                    .canister_id(canister_id)
+                    .build(),
+                &mut state,
+                EXECUTION_PARAMETERS.clone(),
+            )
+            .1
+            .unwrap();
+        assert_eq!(canister_version(&state), 1);
+
+        // A failed upgrade leaves the version unchanged.
+        assert!(canister_manager
+            .install_code(
+                InstallCodeContextBuilder::default()
+                    .sender(sender)
+                    .canister_id(canister_id)
+                    .wasm_module(vec![1, 2, 3])
+                    .mode(CanisterInstallMode::Upgrade)
+                    .build(),
+                &mut state,
+                EXECUTION_PARAMETERS.clone(),
+            )
+            .1
+            .is_err());
+        assert_eq!(canister_version(&state), 1);
+
+        canister_manager
+            .update_settings(
+                sender,
+                CanisterSettings::new(
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(NumSeconds::from(10)),
+                    None,
+                    None,
+                ),
+                canister_id,
+                &mut state,
+            )
+            .unwrap();
+        assert_eq!(canister_version(&state), 2);
+
+        canister_manager
+            .set_controller(sender, canister_id, user_test_id(1).get(), &mut state)
+            .unwrap();
+        assert_eq!(canister_version(&state), 3);
+
+        // The history refers to the version that resulted from each change.
+        let info = canister_manager
+            .get_canister_info(CanisterInfoRequest::new(canister_id, Some(10)), &state)
+            .unwrap();
+        assert_eq!(
+            info.recent_changes
+                .iter()
+                .map(|change| change.canister_version)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 3]
+        );
+    });
+}
+
+#[test]
+fn update_settings_sets_and_clears_wasm_memory_limit() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(1).get();
+        let canister_id = canister_manager
+            .create_canister(
+                sender,
+                subnet_test_id(1),
+                *INITIAL_CYCLES,
+                CanisterSettings::default(),
+                MAX_NUMBER_OF_CANISTERS,
+                &mut state,
+            )
+            .0
+            .unwrap();
+        let wasm_memory_limit = |state: &mut ReplicatedState| {
+            let canister = state.canister_state_mut(&canister_id).unwrap();
+            let status = canister_manager
+                .get_canister_status(sender, canister)
+                .unwrap();
+            (
+                canister.system_state.wasm_memory_limit,
+                status.settings().wasm_memory_limit(),
+            )
+        };
+        assert_eq!(wasm_memory_limit(&mut state), (None, 0));
+
+        let settings = CanisterSettings::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(NumBytes::from(1 << 20)),
+        );
+        canister_manager
+            .update_settings(sender, settings, canister_id, &mut state)
+            .unwrap();
+        assert_eq!(
+            wasm_memory_limit(&mut state),
+            (Some(NumBytes::from(1 << 20)), 1 << 20)
+        );
+
+        // A limit of 0 removes the limit.
+        let settings =
+            CanisterSettings::new(None, None, None, None, None, None, Some(NumBytes::from(0)));
+        canister_manager
+            .update_settings(sender, settings, canister_id, &mut state)
+            .unwrap();
+        assert_eq!(wasm_memory_limit(&mut state), (None, 0));
+    });
+}
+
 #[test]
 fn delete_non_existing_canister_fails() {
     with_setup(|canister_manager, mut state, _| {
@@ -2028,6 +2176,496 @@ fn delete_stopped_canister_succeeds() {
     });
 }
 
+/// Creates a canister controlled by `sender`, installs the universal canister
+/// into it, writes `stable_byte` into its stable memory and stops it.
+fn install_and_stop_canister(
+    canister_manager: &CanisterManager,
+    state: &mut ReplicatedState,
+    sender: PrincipalId,
+    stable_byte: u8,
+) -> CanisterId {
+    let canister_id = canister_manager
+        .create_canister(
+            sender,
+            subnet_test_id(1),
+            *INITIAL_CYCLES,
+            CanisterSettings::default(),
+            MAX_NUMBER_OF_CANISTERS,
+            state,
+        )
+        .0
+        .unwrap();
+    canister_manager
+        .install_code(
+            InstallCodeContextBuilder::default()
+                .sender(sender)
+                .canister_id(canister_id)
+                .build(),
+            state,
+            EXECUTION_PARAMETERS.clone(),
+        )
+        .1
+        .unwrap();
+    write_stable_memory(state, canister_id, stable_byte);
+    state
+        .canister_state_mut(&canister_id)
+        .unwrap()
+        .system_state
+        .status = CanisterStatus::Stopped;
+    canister_id
+}
+
+fn write_stable_memory(state: &mut ReplicatedState, canister_id: CanisterId, byte: u8) {
+    let execution_state = state
+        .canister_state_mut(&canister_id)
+        .unwrap()
+        .execution_state
+        .as_mut()
+        .unwrap();
+    execution_state.stable_memory.size = NumWasmPages::new(1);
+    let mut buf = page_map::Buffer::new(PageMap::default());
+    buf.write(&[byte; 10], 0);
+    execution_state
+        .stable_memory
+        .page_map
+        .update(&buf.dirty_pages().collect::<Vec<_>>());
+}
+
+fn read_stable_memory(state: &ReplicatedState, canister_id: CanisterId) -> u8 {
+    state
+        .canister_state(&canister_id)
+        .unwrap()
+        .execution_state
+        .as_ref()
+        .unwrap()
+        .stable_memory
+        .page_map
+        .get_page(PageIndex::new(0))[0]
+}
+
+#[test]
+fn take_and_load_canister_snapshot_restores_canister() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = install_and_stop_canister(&canister_manager, &mut state, sender, 1);
+        state
+            .canister_state_mut(&canister_id)
+            .unwrap()
+            .system_state
+            .certified_data = vec![1, 2, 3];
+
+        let snapshot = canister_manager
+            .take_canister_snapshot(sender, canister_id, None, &mut state)
+            .unwrap();
+        assert_eq!(
+            canister_manager.list_canister_snapshots(sender, canister_id, &state),
+            Ok(vec![snapshot.clone()])
+        );
+
+        // Modify the canister after the snapshot was taken.
+        write_stable_memory(&mut state, canister_id, 2);
+        state
+            .canister_state_mut(&canister_id)
+            .unwrap()
+            .system_state
+            .certified_data = vec![4, 5, 6];
+        assert_eq!(read_stable_memory(&state, canister_id), 2);
+
+        canister_manager
+            .load_canister_snapshot(
+                sender,
+                canister_id,
+                SnapshotId::from(snapshot.id),
+                &mut state,
+            )
+            .unwrap();
+
+        assert_eq!(read_stable_memory(&state, canister_id), 1);
+        assert_eq!(
+            state
+                .canister_state(&canister_id)
+                .unwrap()
+                .system_state
+                .certified_data,
+            vec![1, 2, 3]
+        );
+        // Loading a snapshot does not consume it.
+        assert_eq!(
+            canister_manager.list_canister_snapshots(sender, canister_id, &state),
+            Ok(vec![snapshot])
+        );
+    });
+}
+
+#[test]
+fn take_canister_snapshot_of_running_canister_fails() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = install_and_stop_canister(&canister_manager, &mut state, sender, 1);
+        state
+            .canister_state_mut(&canister_id)
+            .unwrap()
+            .system_state
+            .status = CanisterStatus::new_running();
+
+        assert_eq!(
+            canister_manager.take_canister_snapshot(sender, canister_id, None, &mut state),
+            Err(CanisterManagerError::CanisterSnapshotCanisterNotStopped(
+                canister_id
+            ))
+        );
+        assert_eq!(state.canister_snapshots.count(canister_id), 0);
+    });
+}
+
+#[test]
+fn canister_snapshot_methods_with_incorrect_controller_fail() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = install_and_stop_canister(&canister_manager, &mut state, sender, 1);
+        let snapshot_id = SnapshotId::from(
+            canister_manager
+                .take_canister_snapshot(sender, canister_id, None, &mut state)
+                .unwrap()
+                .id,
+        );
+
+        let wrong_controller = canister_test_id(1).get();
+        let expected_err = Err(CanisterManagerError::CanisterInvalidController {
+            canister_id,
+            controllers_expected: btreeset! {sender},
+            controller_provided: wrong_controller,
+        });
+        assert_eq!(
+            canister_manager
+                .take_canister_snapshot(
+                    wrong_controller,
+                    canister_id,
+                    Some(snapshot_id),
+                    &mut state
+                )
+                .map(|_| ()),
+            expected_err
+        );
+        assert_eq!(
+            canister_manager.load_canister_snapshot(
+                wrong_controller,
+                canister_id,
+                snapshot_id,
+                &mut state
+            ),
+            expected_err
+        );
+        assert_eq!(
+            canister_manager
+                .list_canister_snapshots(wrong_controller, canister_id, &state)
+                .map(|_| ()),
+            expected_err
+        );
+        assert_eq!(
+            canister_manager.delete_canister_snapshot(
+                wrong_controller,
+                canister_id,
+                snapshot_id,
+                &mut state
+            ),
+            expected_err
+        );
+        assert_eq!(state.canister_snapshots.count(canister_id), 1);
+    });
+}
+
+#[test]
+fn take_canister_snapshot_respects_limit_unless_replacing() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = install_and_stop_canister(&canister_manager, &mut state, sender, 1);
+        let mut snapshot_ids = vec![];
+        for _ in 0..MAX_SNAPSHOTS_PER_CANISTER {
+            snapshot_ids.push(SnapshotId::from(
+                canister_manager
+                    .take_canister_snapshot(sender, canister_id, None, &mut state)
+                    .unwrap()
+                    .id,
+            ));
+        }
+
+        assert_eq!(
+            canister_manager
+                .take_canister_snapshot(sender, canister_id, None, &mut state)
+                .map(|_| ()),
+            Err(CanisterManagerError::CanisterSnapshotLimitExceeded {
+                canister_id,
+                limit: MAX_SNAPSHOTS_PER_CANISTER,
+            })
+        );
+
+        let replaced = snapshot_ids[0];
+        let new_snapshot = canister_manager
+            .take_canister_snapshot(sender, canister_id, Some(replaced), &mut state)
+            .unwrap();
+        assert_ne!(SnapshotId::from(new_snapshot.id), replaced);
+        assert!(state.canister_snapshots.get(replaced).is_none());
+        assert_eq!(
+            state.canister_snapshots.count(canister_id),
+            MAX_SNAPSHOTS_PER_CANISTER
+        );
+
+        // The replaced snapshot can no longer be loaded.
+        assert_eq!(
+            canister_manager.load_canister_snapshot(sender, canister_id, replaced, &mut state),
+            Err(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id: replaced,
+            })
+        );
+    });
+}
+
+#[test]
+fn delete_canister_snapshot_and_canister_remove_snapshots() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = install_and_stop_canister(&canister_manager, &mut state, sender, 1);
+        let snapshot_id = SnapshotId::from(
+            canister_manager
+                .take_canister_snapshot(sender, canister_id, None, &mut state)
+                .unwrap()
+                .id,
+        );
+
+        assert_eq!(
+            canister_manager.delete_canister_snapshot(sender, canister_id, snapshot_id, &mut state),
+            Ok(())
+        );
+        assert_eq!(
+            canister_manager.list_canister_snapshots(sender, canister_id, &state),
+            Ok(vec![])
+        );
+        assert_eq!(
+            canister_manager.delete_canister_snapshot(sender, canister_id, snapshot_id, &mut state),
+            Err(CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id,
+            })
+        );
+
+        // Deleting the canister also deletes its snapshots.
+        canister_manager
+            .take_canister_snapshot(sender, canister_id, None, &mut state)
+            .unwrap();
+        assert_eq!(
+            canister_manager.delete_canister(sender, canister_id, &mut state),
+            Ok(())
+        );
+        assert_eq!(state.canister_snapshots.count(canister_id), 0);
+    });
+}
+
+fn create_canister_for_chunks(
+    canister_manager: &CanisterManager,
+    state: &mut ReplicatedState,
+    sender: PrincipalId,
+) -> CanisterId {
+    canister_manager
+        .create_canister(
+            sender,
+            subnet_test_id(1),
+            *INITIAL_CYCLES,
+            CanisterSettings::default(),
+            MAX_NUMBER_OF_CANISTERS,
+            state,
+        )
+        .0
+        .unwrap()
+}
+
+#[test]
+fn upload_chunks_and_install_chunked_code() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = create_canister_for_chunks(&canister_manager, &mut state, sender);
+        let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
+        let (first, second) = wasm.split_at(wasm.len() / 2);
+
+        let memory_usage_before = state
+            .canister_state(&canister_id)
+            .unwrap()
+            .memory_usage(SubnetType::Application);
+        let first_hash = canister_manager
+            .upload_chunk(sender, canister_id, first.to_vec(), &mut state)
+            .unwrap();
+        let second_hash = canister_manager
+            .upload_chunk(sender, canister_id, second.to_vec(), &mut state)
+            .unwrap();
+        let mut expected_hashes = vec![first_hash, second_hash];
+        expected_hashes.sort_unstable();
+        assert_eq!(
+            canister_manager.stored_chunks(sender, canister_id, &state),
+            Ok(expected_hashes)
+        );
+        // The stored chunks count toward the memory usage of the canister.
+        assert_eq!(
+            state
+                .canister_state(&canister_id)
+                .unwrap()
+                .memory_usage(SubnetType::Application),
+            memory_usage_before + NumBytes::from(2 * CHUNK_SIZE)
+        );
+
+        let args = canister_manager
+            .assemble_chunked_code(
+                sender,
+                InstallChunkedCodeArgs::new(
+                    CanisterInstallMode::Install,
+                    canister_id,
+                    vec![first_hash.to_vec(), second_hash.to_vec()],
+                    ic_crypto_sha::Sha256::hash(&wasm).to_vec(),
+                    vec![],
+                ),
+                &state,
+            )
+            .unwrap();
+        assert_eq!(args.wasm_module, wasm);
+        canister_manager
+            .install_code(
+                InstallCodeContext::try_from((sender, args)).unwrap(),
+                &mut state,
+                EXECUTION_PARAMETERS.clone(),
+            )
+            .1
+            .unwrap();
+
+        // The chunks survive the installation until the store is cleared.
+        assert_eq!(
+            canister_manager
+                .stored_chunks(sender, canister_id, &state)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            canister_manager.clear_chunk_store(sender, canister_id, &mut state),
+            Ok(())
+        );
+        assert_eq!(
+            canister_manager.stored_chunks(sender, canister_id, &state),
+            Ok(vec![])
+        );
+    });
+}
+
+#[test]
+fn install_chunked_code_with_wrong_module_hash_fails() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = create_canister_for_chunks(&canister_manager, &mut state, sender);
+        let hash = canister_manager
+            .upload_chunk(sender, canister_id, vec![1, 2, 3], &mut state)
+            .unwrap();
+
+        assert_eq!(
+            canister_manager
+                .assemble_chunked_code(
+                    sender,
+                    InstallChunkedCodeArgs::new(
+                        CanisterInstallMode::Install,
+                        canister_id,
+                        vec![hash.to_vec()],
+                        vec![0; 32],
+                        vec![],
+                    ),
+                    &state,
+                )
+                .map(|_| ()),
+            Err(CanisterManagerError::WasmModuleHashMismatch {
+                canister_id,
+                expected: vec![0; 32],
+                actual: ic_crypto_sha::Sha256::hash(&[1, 2, 3]).to_vec(),
+            })
+        );
+
+        // A chunk that was never uploaded cannot be used either.
+        assert_eq!(
+            canister_manager
+                .assemble_chunked_code(
+                    sender,
+                    InstallChunkedCodeArgs::new(
+                        CanisterInstallMode::Install,
+                        canister_id,
+                        vec![vec![7; 32]],
+                        vec![0; 32],
+                        vec![],
+                    ),
+                    &state,
+                )
+                .map(|_| ()),
+            Err(CanisterManagerError::WasmChunkNotFound {
+                canister_id,
+                hash: vec![7; 32],
+            })
+        );
+    });
+}
+
+#[test]
+fn upload_chunk_fails_if_memory_allocation_is_exceeded() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = create_canister_for_chunks(&canister_manager, &mut state, sender);
+        let memory_allocation = MemoryAllocation::try_from(NumBytes::from(CHUNK_SIZE / 2)).unwrap();
+        state
+            .canister_state_mut(&canister_id)
+            .unwrap()
+            .system_state
+            .memory_allocation = memory_allocation;
+
+        assert_eq!(
+            canister_manager.upload_chunk(sender, canister_id, vec![1, 2, 3], &mut state),
+            Err(CanisterManagerError::NotEnoughMemoryAllocationGiven {
+                canister_id,
+                memory_allocation_given: memory_allocation,
+                memory_usage_needed: NumBytes::from(CHUNK_SIZE),
+            })
+        );
+        assert_eq!(
+            canister_manager.stored_chunks(sender, canister_id, &state),
+            Ok(vec![])
+        );
+    });
+}
+
+#[test]
+fn chunk_store_methods_with_incorrect_controller_fail() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(42).get();
+        let canister_id = create_canister_for_chunks(&canister_manager, &mut state, sender);
+        let wrong_controller = canister_test_id(1).get();
+        let expected_err = Err(CanisterManagerError::CanisterInvalidController {
+            canister_id,
+            controllers_expected: btreeset! {sender},
+            controller_provided: wrong_controller,
+        });
+
+        assert_eq!(
+            canister_manager
+                .upload_chunk(wrong_controller, canister_id, vec![1], &mut state)
+                .map(|_| ()),
+            expected_err
+        );
+        assert_eq!(
+            canister_manager
+                .stored_chunks(wrong_controller, canister_id, &state)
+                .map(|_| ()),
+            expected_err
+        );
+        assert_eq!(
+            canister_manager.clear_chunk_store(wrong_controller, canister_id, &mut state),
+            expected_err
+        );
+    });
+}
+
 #[test]
 fn install_canister_with_query_allocation() {
     with_setup(|canister_manager, mut state, _| {
@@ -2874,20 +3512,12 @@ fn lower_memory_allocation_than_usage_fails() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(2)).unwrap()),
             None,
+            None,
+            None,
         );
 
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-        let mut canister = state.canister_state_mut(&canister_id).unwrap();
-
         assert_matches!(
-            canister_manager.update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used
-            ),
+            canister_manager.update_settings(sender, settings, canister_id, &mut state,),
             Err(CanisterManagerError::NotEnoughMemoryAllocationGiven { .. })
         );
     })
@@ -2905,6 +3535,8 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(2)).unwrap()),
             None,
+            None,
+            None,
         );
         let canister_id = canister_manager
             .create_canister(
@@ -2947,20 +3579,12 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(MEMORY_CAPACITY.get() / 2)).unwrap()),
             None,
+            None,
+            None,
         );
 
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-        let mut canister = state.canister_state_mut(&canister_id).unwrap();
-
         canister_manager
-            .update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used,
-            )
+            .update_settings(sender, settings, canister_id, &mut state)
             .unwrap();
 
         canister_manager
@@ -2995,6 +3619,8 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
                 MemoryAllocation::try_from(NumBytes::from(WASM_PAGE_SIZE_IN_BYTES + 100)).unwrap(),
             ),
             None,
+            None,
+            None,
         );
         let wat = r#"
         (module
@@ -3070,20 +3696,12 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
                     .unwrap(),
             ),
             None,
+            None,
+            None,
         );
 
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-        let mut canister = state.canister_state_mut(&canister_id).unwrap();
-
         canister_manager
-            .update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used,
-            )
+            .update_settings(sender, settings, canister_id, &mut state)
             .unwrap();
 
         canister_manager
@@ -3152,7 +3770,7 @@ fn test_install_when_setting_memory_allocation_to_zero() {
         let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
 
         let sender = canister_test_id(100).get();
-        let settings = CanisterSettings::new(None, None, None, None, None);
+        let settings = CanisterSettings::new(None, None, None, None, None, None, None);
         let canister_id = canister_manager
             .create_canister(
                 sender,
@@ -3172,20 +3790,12 @@ fn test_install_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(0)).unwrap()),
             None,
+            None,
+            None,
         );
 
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-        let mut canister = state.canister_state_mut(&canister_id).unwrap();
-
         canister_manager
-            .update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used,
-            )
+            .update_settings(sender, settings, canister_id, &mut state)
             .unwrap();
 
         canister_manager
@@ -3220,6 +3830,8 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(MEMORY_CAPACITY.get() / 2)).unwrap()),
             None,
+            None,
+            None,
         );
         let canister_id = canister_manager
             .create_canister(
@@ -3258,20 +3870,12 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(0)).unwrap()),
             None,
+            None,
+            None,
         );
 
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-        let mut canister = state.canister_state_mut(&canister_id).unwrap();
-
         canister_manager
-            .update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used,
-            )
+            .update_settings(sender, settings, canister_id, &mut state)
             .unwrap();
 
         canister_manager
@@ -3425,7 +4029,7 @@ fn hypervisor_sends_new_controller_to_canister() {
         // Verify that we read the proper length for the initial controller.
         let canister = state.take_canister_state(&canister_id).unwrap();
         let user_id = user_test_id(0);
-        let (mut new_canister, _, result) = hypervisor.execute_query(
+        let (new_canister, _, result) = hypervisor.execute_query(
             QueryExecutionType::Replicated,
             "controller",
             &[],
@@ -3443,16 +4047,13 @@ fn hypervisor_sends_new_controller_to_canister() {
         // Change to a new controller with a different length.
         let new_controller = PrincipalId::try_from(&[1, 2, 3][..]).unwrap();
         assert!(controller.to_vec().len() != new_controller.to_vec().len());
-        let new_settings = CanisterSettings::new(Some(new_controller), None, None, None, None);
+        let new_settings =
+            CanisterSettings::new(Some(new_controller), None, None, None, None, None, None);
+        state.put_canister_state(new_canister);
         canister_manager
-            .update_settings(
-                controller,
-                new_settings,
-                &mut new_canister,
-                0,
-                NumBytes::from(0),
-            )
+            .update_settings(controller, new_settings, canister_id, &mut state)
             .unwrap();
+        let new_canister = state.take_canister_state(&canister_id).unwrap();
 
         // Verify that the canister reads the length of the new controller.
         assert_eq!(
@@ -3608,3 +4209,99 @@ fn test_upgrade_preserves_stable_memory() {
         assert_eq!(result.unwrap(), Some(WasmResult::Reply(data)));
     })
 }
+
+#[test]
+fn canister_history_records_changes_and_is_returned_by_canister_info() {
+    with_setup(|canister_manager, mut state, _| {
+        let sender = canister_test_id(1).get();
+        let canister_id = canister_manager
+            .create_canister(
+                sender,
+                subnet_test_id(1),
+                *INITIAL_CYCLES,
+                CanisterSettings::default(),
+                MAX_NUMBER_OF_CANISTERS,
+                &mut state,
+            )
+            .0
+            .unwrap();
+
+        canister_manager
+            .install_code(
+                InstallCodeContextBuilder::default()
+                    .sender(sender)
+                    .canister_id(canister_id)
+                    .build(),
+                &mut state,
+                EXECUTION_PARAMETERS.clone(),
+            )
+            .1
+            .unwrap();
+
+        let new_controller = user_test_id(7).get();
+        canister_manager
+            .update_settings(
+                sender,
+                CanisterSettings::new(
+                    None,
+                    Some(vec![new_controller]),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                ),
+                canister_id,
+                &mut state,
+            )
+            .unwrap();
+
+        canister_manager
+            .uninstall_code(canister_id, new_controller, &mut state)
+            .unwrap();
+
+        let info = canister_manager
+            .get_canister_info(CanisterInfoRequest::new(canister_id, Some(3)), &state)
+            .unwrap();
+        assert_eq!(info.total_num_changes, 4);
+        assert_eq!(info.module_hash, None);
+        assert_eq!(info.controllers, vec![new_controller]);
+        assert_eq!(info.recent_changes.len(), 3);
+        assert_matches!(
+            &info.recent_changes[0].details,
+            CanisterChangeDetails::CodeDeployment {
+                mode: CanisterInstallMode::Install,
+                ..
+            }
+        );
+        assert_eq!(
+            info.recent_changes[1].details,
+            CanisterChangeDetails::ControllersChange {
+                controllers: vec![new_controller]
+            }
+        );
+        assert_eq!(
+            info.recent_changes[1].origin,
+            CanisterChangeOrigin::FromCanister {
+                canister_id: sender
+            }
+        );
+        assert_eq!(
+            info.recent_changes[2].details,
+            CanisterChangeDetails::CodeUninstall
+        );
+        assert_eq!(
+            info.recent_changes[2].origin,
+            CanisterChangeOrigin::FromUser {
+                user_id: new_controller
+            }
+        );
+
+        // Without `num_requested_changes`, only the totals are returned.
+        let info = canister_manager
+            .get_canister_info(CanisterInfoRequest::new(canister_id, None), &state)
+            .unwrap();
+        assert_eq!(info.total_num_changes, 4);
+        assert!(info.recent_changes.is_empty());
+    })
+}
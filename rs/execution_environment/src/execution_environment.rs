@@ -10,10 +10,14 @@ use ic_base_types::PrincipalId;
 use ic_config::execution_environment::Config as ExecutionConfig;
 use ic_cycles_account_manager::{CyclesAccountManager, IngressInductionCost};
 use ic_ic00_types::{
-    CanisterIdRecord, CanisterSettingsArgs, CreateCanisterArgs, EmptyBlob, InstallCodeArgs,
+    BitcoinGetBalanceArgs, BitcoinGetUtxosArgs, BitcoinSendTransactionArgs,
+    CanisterHttpRequestArgs, CanisterIdRecord, CanisterInfoRequest, CanisterSettingsArgs,
+    CanisterSnapshotArgs, ChunkHash, CreateCanisterArgs, ECDSAPublicKeyArgs,
+    ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, InstallChunkedCodeArgs, InstallCodeArgs,
     Method as Ic00Method, Payload as Ic00Payload, ProvisionalCreateCanisterWithCyclesArgs,
     ProvisionalTopUpCanisterArgs, SetControllerArgs, SetupInitialDKGArgs, SignWithECDSAArgs,
-    UpdateSettingsArgs, IC_00,
+    StoredChunksReply, TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
+    UploadChunkReply, IC_00,
 };
 use ic_interfaces::{
     execution_environment::{
@@ -28,20 +32,32 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_routing_table::RoutingTable;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    metadata_state::subnet_call_context_manager::{SetupInitialDkgContext, SignWithEcdsaContext},
+    bitcoin_state::BitcoinState,
+    metadata_state::subnet_call_context_manager::{
+        CanisterHttpRequestContext, SetupInitialDkgContext, SignWithEcdsaContext,
+    },
     CallContextAction, CallOrigin, CanisterState, ReplicatedState,
 };
 use ic_types::{
+    canister_http::{
+        MAX_CANISTER_HTTP_HEADER_NUM, MAX_CANISTER_HTTP_RESPONSE_BYTES, MAX_CANISTER_HTTP_URL_SIZE,
+    },
     canonical_error::{not_found_error, permission_denied_error, CanonicalError},
-    crypto::threshold_sig::ni_dkg::NiDkgTargetId,
+    crypto::{
+        canister_threshold_sig::{
+            error::ThresholdEcdsaGetPublicKeyError, EcdsaPublicKey, ExtendedDerivationPath,
+        },
+        threshold_sig::ni_dkg::NiDkgTargetId,
+    },
     ingress::{IngressStatus, WasmResult},
     messages::{
         is_subnet_message, CallbackId, Ingress, MessageId, Payload, RejectContext, Request,
         Response, SignedIngressContent, StopCanisterContext,
     },
+    methods::SystemMethod,
     user_error::{ErrorCode, RejectCode, UserError},
     CanisterId, CanisterStatusType, ComputeAllocation, Cycles, InstallCodeContext, NumBytes,
-    NumInstructions, SubnetId, Time, UserId,
+    NumInstructions, SnapshotId, SubnetId, Time, UserId,
 };
 #[cfg(test)]
 use mockall::automock;
@@ -107,6 +123,23 @@ pub trait ExecutionEnvironment: Sync + Send {
         Result<NumBytes, CanisterHeartbeatError>,
     );
 
+    /// Executes the global timer of a given canister. The timer is
+    /// deactivated before its execution.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_canister_global_timer(
+        &self,
+        canister_state: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    );
+
     /// Look up the current amount of memory available on the subnet.
     /// EXC-185 will make this method obsolete.
     fn subnet_available_memory(&self, state: &ReplicatedState) -> i64;
@@ -228,68 +261,96 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                     Err(err) => (Err(err.into()), instructions_limit),
                     Ok(args) => match InstallCodeContext::try_from((*msg.sender(), args)) {
                         Err(err) => (Err(err.into()), instructions_limit),
-                        Ok(install_context) => {
-                            let canister_id = install_context.canister_id;
-                            info!(
-                                self.log,
-                                "Start executing install_code message on canister {:?}, contains module {:?}",
-                                canister_id,
-                                install_context.wasm_module.is_empty().to_string(),
-                            );
-
-                            // Start logging execution time for `install_code`.
-                            let timer = Timer::start();
-
-                            let execution_parameters = ExecutionParameters {
-                                instruction_limit: instructions_limit,
-                                canister_memory_limit: self.config.max_canister_memory_size,
-                                subnet_available_memory,
-                                compute_allocation: ComputeAllocation::default(),
-                                subnet_type: state.metadata.own_subnet_type,
-                            };
+                        Ok(install_context) => self.install_code(
+                            install_context,
+                            &mut state,
+                            instructions_limit,
+                            subnet_available_memory,
+                        ),
+                    },
+                };
+                (Some((res, msg.take_cycles())), instructions_left)
+            }
 
-                            let (instructions_left, result) = self.canister_manager.install_code(
+            Ok(Ic00Method::InstallChunkedCode) => {
+                let (res, instructions_left) = match InstallChunkedCodeArgs::decode(payload) {
+                    Err(err) => (Err(err.into()), instructions_limit),
+                    Ok(args) => match self.canister_manager.assemble_chunked_code(
+                        *msg.sender(),
+                        args,
+                        &state,
+                    ) {
+                        Err(err) => (Err(err.into()), instructions_limit),
+                        Ok(args) => match InstallCodeContext::try_from((*msg.sender(), args)) {
+                            Err(err) => (Err(err.into()), instructions_limit),
+                            Ok(install_context) => self.install_code(
                                 install_context,
                                 &mut state,
-                                execution_parameters,
-                            );
-
-                            let execution_duration = timer.elapsed();
-
-                            let result = match result {
-                                Ok(result) => {
-                                    state.metadata.heap_delta_estimate += result.heap_delta;
-
-                                    info!(
-                                        self.log,
-                                        "Finished executing install_code message on canister {:?} after {:?}, old wasm hash {:?}, new wasm hash {:?}",
-                                        canister_id,
-                                        execution_duration,
-                                        result.old_wasm_hash,
-                                        result.new_wasm_hash,
-                                    );
-
-                                    (Ok(EmptyBlob::encode()), instructions_left)
-                                }
-                                Err(err) => {
-                                    info!(
-                                        self.log,
-                                        "Finished executing install_code message on canister {:?} after {:?} with error: {:?}",
-                                        canister_id,
-                                        execution_duration,
-                                        err
-                                    );
-                                    (Err(err.into()), instructions_left)
-                                }
-                            };
-
-                            result
-                        }
+                                instructions_limit,
+                                subnet_available_memory,
+                            ),
+                        },
                     },
                 };
                 (Some((res, msg.take_cycles())), instructions_left)
             }
 
+            Ok(Ic00Method::UploadChunk) => {
+                let res = match UploadChunkArgs::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .upload_chunk(
+                            *msg.sender(),
+                            args.get_canister_id(),
+                            args.chunk,
+                            &mut state,
+                        )
+                        .map(|hash| {
+                            UploadChunkReply {
+                                hash: hash.to_vec(),
+                            }
+                            .encode()
+                        })
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::ClearChunkStore) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .clear_chunk_store(*msg.sender(), args.get_canister_id(), &mut state)
+                        .map(|()| EmptyBlob::encode())
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::StoredChunks) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .stored_chunks(*msg.sender(), args.get_canister_id(), &state)
+                        .map(|hashes| {
+                            StoredChunksReply(
+                                hashes
+                                    .into_iter()
+                                    .map(|hash| ChunkHash {
+                                        hash: hash.to_vec(),
+                                    })
+                                    .collect(),
+                            )
+                            .encode()
+                        })
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
             Ok(Ic00Method::UninstallCode) => {
                 let res = match CanisterIdRecord::decode(payload) {
                     Err(err) => Err(err.into()),
@@ -302,6 +363,69 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                 (Some((res, msg.take_cycles())), instructions_limit)
             }
 
+            Ok(Ic00Method::TakeCanisterSnapshot) => {
+                let res = match TakeCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .take_canister_snapshot(
+                            *msg.sender(),
+                            args.get_canister_id(),
+                            args.replace_snapshot().map(SnapshotId::from),
+                            &mut state,
+                        )
+                        .map(|response| response.encode())
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::LoadCanisterSnapshot) => {
+                let res = match CanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .load_canister_snapshot(
+                            *msg.sender(),
+                            args.get_canister_id(),
+                            SnapshotId::from(args.snapshot_id()),
+                            &mut state,
+                        )
+                        .map(|()| EmptyBlob::encode())
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::ListCanisterSnapshots) => {
+                let res = match CanisterIdRecord::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .list_canister_snapshots(*msg.sender(), args.get_canister_id(), &state)
+                        .map(|snapshots| snapshots.encode())
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::DeleteCanisterSnapshot) => {
+                let res = match CanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err.into()),
+                    Ok(args) => self
+                        .canister_manager
+                        .delete_canister_snapshot(
+                            *msg.sender(),
+                            args.get_canister_id(),
+                            SnapshotId::from(args.snapshot_id()),
+                            &mut state,
+                        )
+                        .map(|()| EmptyBlob::encode())
+                        .map_err(|err| err.into()),
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
             Ok(Ic00Method::UpdateSettings) => {
                 let res = match UpdateSettingsArgs::decode(payload) {
                     Err(err) => Err(err.into()),
@@ -486,14 +610,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                     let res = match SignWithECDSAArgs::decode(payload) {
                         Err(err) => Some((Err(err.into()), msg.take_cycles())),
                         Ok(args) => self
-                            .sign_with_ecdsa(
-                                request,
-                                &args.message_hash,
-                                &args.derivation_path,
-                                false,
-                                &mut state,
-                                rng,
-                            )
+                            .sign_with_ecdsa(request, args, &mut state, rng)
                             .map_or_else(|err| Some((Err(err), msg.take_cycles())), |()| None),
                     };
                     (res, instructions_limit)
@@ -511,63 +628,158 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                 }
             },
 
-            Ok(Ic00Method::SignWithMockECDSA) => {
+            Ok(Ic00Method::HttpRequest) => match &msg {
+                RequestOrIngress::Request(request) => {
+                    match CanisterHttpRequestArgs::decode(payload) {
+                        Err(err) => (
+                            Some((Err(err.into()), msg.take_cycles())),
+                            instructions_limit,
+                        ),
+                        Ok(args) => match self.http_request(request, args, &mut state) {
+                            Ok(()) => (None, instructions_limit),
+                            Err(reject_message) => {
+                                use ic_types::messages;
+                                state.push_subnet_output_response(Response {
+                                    originator: request.sender,
+                                    respondent: CanisterId::from(self.own_subnet_id),
+                                    originator_reply_callback: request.sender_reply_callback,
+                                    refund: request.payment,
+                                    response_payload: messages::Payload::Reject(
+                                        messages::RejectContext {
+                                            code: RejectCode::CanisterReject,
+                                            message: reject_message,
+                                        },
+                                    ),
+                                });
+                                return (state, instructions_limit);
+                            }
+                        },
+                    }
+                }
+                RequestOrIngress::Ingress(_) => {
+                    error!(self.log, "[EXC-BUG] Ingress messages to HttpRequest should've been filtered earlier.");
+                    let error_string = format!(
+                        "HttpRequest is called by user {}. It can only be called by a canister.",
+                        msg.sender()
+                    );
+                    let user_error =
+                        UserError::new(ErrorCode::CanisterContractViolation, error_string);
+                    let res = Some((Err(user_error), msg.take_cycles()));
+                    (res, instructions_limit)
+                }
+            },
+
+            Ok(Ic00Method::ECDSAPublicKey) => {
                 let res = match &msg {
                     RequestOrIngress::Request(request) => {
-                        if !state.metadata.own_subnet_features.ecdsa_signatures {
-                            Some(UserError::new(ErrorCode::CanisterContractViolation,
-                            "This API is not enabled on this subnet".to_string()))
-                        }
-                        else {
-                            match SignWithECDSAArgs::decode(payload) {
-                                Err(err) => Some(err.into()),
-                                Ok(args) => self
-                                    .sign_with_ecdsa(
-                                        request,
-                                        &args.message_hash,
-                                        &args.derivation_path,
-                                        true,
-                                        &mut state,
-                                        rng,
-                                    ).map_or_else(Some, |()| None),
-                            }
-                        }
+                        self.ecdsa_public_key(payload, request, &state)
                     }
                     RequestOrIngress::Ingress(_) => {
-                        error!(self.log, "[EXC-BUG] Ingress messages to SignWithECDSA should've been filtered earlier.");
+                        error!(self.log, "[EXC-BUG] Ingress messages to ECDSAPublicKey should've been filtered earlier.");
                         let error_string = format!(
-                            "SignWithMockECDSA is called by user {}. It can only be called by a canister.",
+                            "ECDSAPublicKey is called by user {}. It can only be called by a canister.",
                             msg.sender()
                         );
-                        Some(UserError::new(ErrorCode::CanisterContractViolation, error_string))
+                        Err(UserError::new(
+                            ErrorCode::CanisterContractViolation,
+                            error_string,
+                        ))
                     }
-                }.map(|err| (Err(err), msg.take_cycles()));
-                (res, instructions_limit)
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
             }
 
-            Ok(Ic00Method::GetMockECDSAPublicKey) => {
+            Ok(Ic00Method::CanisterInfo) => {
                 let res = match &msg {
-                    RequestOrIngress::Request(_request) => {
-                        if !state.metadata.own_subnet_features.ecdsa_signatures {
-                            Some(Err(UserError::new(ErrorCode::CanisterContractViolation,
-                            "This API is not enabled on this subnet".to_string())))
-                        }
-                        else {
-                            Some(Ok([2, 185, 138, 127, 184, 204, 0, 112, 72, 98, 91,
-                                     100, 70, 173, 73, 161, 179, 167, 34, 223, 140, 28,
-                                     169, 117, 184, 113, 96, 2, 62, 20, 209, 144, 151].to_vec()))
-                        }
+                    RequestOrIngress::Request(_) => match CanisterInfoRequest::decode(payload) {
+                        Err(err) => Err(err.into()),
+                        Ok(args) => self
+                            .canister_manager
+                            .get_canister_info(args, &state)
+                            .map(|info| info.encode())
+                            .map_err(|err| err.into()),
+                    },
+                    RequestOrIngress::Ingress(_) => {
+                        error!(self.log, "[EXC-BUG] Ingress messages to CanisterInfo should've been filtered earlier.");
+                        let error_string = format!(
+                            "CanisterInfo is called by user {}. It can only be called by a canister.",
+                            msg.sender()
+                        );
+                        Err(UserError::new(
+                            ErrorCode::CanisterContractViolation,
+                            error_string,
+                        ))
                     }
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::FetchCanisterLogs) => {
+                let error_string = format!(
+                    "{} can only be called as a query by a user.",
+                    Ic00Method::FetchCanisterLogs
+                );
+                let user_error = UserError::new(ErrorCode::CanisterContractViolation, error_string);
+                (
+                    Some((Err(user_error), msg.take_cycles())),
+                    instructions_limit,
+                )
+            }
+
+            Ok(Ic00Method::BitcoinGetBalance) => {
+                let res = match &msg {
+                    RequestOrIngress::Request(_) => self.bitcoin_get_balance(payload, &state),
                     RequestOrIngress::Ingress(_) => {
-                        error!(self.log, "[EXC-BUG] Ingress messages to SignWithECDSA should've been filtered earlier.");
+                        error!(self.log, "[EXC-BUG] Ingress messages to BitcoinGetBalance should've been filtered earlier.");
                         let error_string = format!(
-                            "GetMockECDSAPublicKey is called by user {}. It can only be called by a canister.",
+                            "BitcoinGetBalance is called by user {}. It can only be called by a canister.",
                             msg.sender()
                         );
-                        Some(Err(UserError::new(ErrorCode::CanisterContractViolation, error_string)))
+                        Err(UserError::new(
+                            ErrorCode::CanisterContractViolation,
+                            error_string,
+                        ))
                     }
-                }.map(|res| (res, msg.take_cycles()));
-                (res, instructions_limit)
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::BitcoinGetUtxos) => {
+                let res = match &msg {
+                    RequestOrIngress::Request(_) => self.bitcoin_get_utxos(payload, &state),
+                    RequestOrIngress::Ingress(_) => {
+                        error!(self.log, "[EXC-BUG] Ingress messages to BitcoinGetUtxos should've been filtered earlier.");
+                        let error_string = format!(
+                            "BitcoinGetUtxos is called by user {}. It can only be called by a canister.",
+                            msg.sender()
+                        );
+                        Err(UserError::new(
+                            ErrorCode::CanisterContractViolation,
+                            error_string,
+                        ))
+                    }
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
+            }
+
+            Ok(Ic00Method::BitcoinSendTransaction) => {
+                let res = match &msg {
+                    RequestOrIngress::Request(_) => {
+                        self.bitcoin_send_transaction(payload, &mut state)
+                    }
+                    RequestOrIngress::Ingress(_) => {
+                        error!(self.log, "[EXC-BUG] Ingress messages to BitcoinSendTransaction should've been filtered earlier.");
+                        let error_string = format!(
+                            "BitcoinSendTransaction is called by user {}. It can only be called by a canister.",
+                            msg.sender()
+                        );
+                        Err(UserError::new(
+                            ErrorCode::CanisterContractViolation,
+                            error_string,
+                        ))
+                    }
+                };
+                (Some((res, msg.take_cycles())), instructions_limit)
             }
 
             Ok(Ic00Method::ProvisionalCreateCanisterWithCycles) => {
@@ -758,7 +970,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
 
     fn execute_canister_heartbeat(
         &self,
-        mut canister: CanisterState,
+        canister: CanisterState,
         instructions_limit: NumInstructions,
         routing_table: Arc<RoutingTable>,
         subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
@@ -769,54 +981,51 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
         NumInstructions,
         Result<NumBytes, CanisterHeartbeatError>,
     ) {
-        if canister.status() != CanisterStatusType::Running {
-            let status = canister.status();
-            return (
-                canister,
-                instructions_limit,
-                Err(CanisterHeartbeatError::CanisterNotRunning { status }),
-            );
-        }
+        self.execute_canister_system_task(
+            SystemMethod::CanisterHeartbeat,
+            canister,
+            instructions_limit,
+            routing_table,
+            subnet_records,
+            time,
+            subnet_available_memory,
+        )
+    }
 
-        let memory_usage = canister.memory_usage(self.own_subnet_type);
-        let compute_allocation = canister.scheduler_state.compute_allocation;
-        if let Err(err) = self.cycles_account_manager.withdraw_execution_cycles(
-            &mut canister.system_state,
-            memory_usage,
-            compute_allocation,
+    fn execute_canister_global_timer(
+        &self,
+        mut canister: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    ) {
+        // The timer fires only once. The canister can set it again while
+        // executing `canister_global_timer`.
+        let global_timer = canister.system_state.global_timer.take();
+        let (mut canister, num_instructions_left, result) = self.execute_canister_system_task(
+            SystemMethod::CanisterGlobalTimer,
+            canister,
             instructions_limit,
-        ) {
-            return (
-                canister,
-                instructions_limit,
-                Err(CanisterHeartbeatError::OutOfCycles(err)),
-            );
+            routing_table,
+            subnet_records,
+            time,
+            subnet_available_memory,
+        );
+        match result {
+            // `canister_global_timer` did not run, so the timer stays due and
+            // fires once the canister is able to execute it.
+            Err(CanisterHeartbeatError::CanisterNotRunning { .. })
+            | Err(CanisterHeartbeatError::OutOfCycles(_)) => {
+                canister.system_state.global_timer = global_timer;
+            }
+            Ok(_) | Err(CanisterHeartbeatError::CanisterExecutionFailed(_)) => {}
         }
-
-        let execution_parameters =
-            self.execution_parameters(&canister, instructions_limit, subnet_available_memory);
-
-        let (mut canister, num_instructions_left, result) =
-            self.hypervisor.execute_canister_heartbeat(
-                canister,
-                routing_table,
-                subnet_records,
-                time,
-                execution_parameters,
-            );
-
-        // Clone the `cycles_account_manager` to avoid having to require 'static
-        // lifetime bound on `self`.
-        let cycles_account_manager = Arc::clone(&self.cycles_account_manager);
-
-        // Refund the canister with any cycles left after message execution.
-        cycles_account_manager
-            .refund_execution_cycles(&mut canister.system_state, num_instructions_left);
-        let result = match result {
-            Ok(heap_delta) => Ok(heap_delta),
-            Err(err) => Err(CanisterHeartbeatError::CanisterExecutionFailed(err)),
-        };
-
         (canister, num_instructions_left, result)
     }
 
@@ -911,6 +1120,68 @@ impl ExecutionEnvironmentImpl {
         }
     }
 
+    /// Installs code into a canister and updates the heap delta estimate of
+    /// the subnet. Shared by `install_code` and `install_chunked_code`.
+    fn install_code(
+        &self,
+        install_context: InstallCodeContext,
+        state: &mut ReplicatedState,
+        instructions_limit: NumInstructions,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (Result<Vec<u8>, UserError>, NumInstructions) {
+        let canister_id = install_context.canister_id;
+        info!(
+            self.log,
+            "Start executing install_code message on canister {:?}, contains module {:?}",
+            canister_id,
+            install_context.wasm_module.is_empty().to_string(),
+        );
+
+        // Start logging execution time for `install_code`.
+        let timer = Timer::start();
+
+        let execution_parameters = ExecutionParameters {
+            instruction_limit: instructions_limit,
+            canister_memory_limit: self.config.max_canister_memory_size,
+            subnet_available_memory,
+            compute_allocation: ComputeAllocation::default(),
+            subnet_type: state.metadata.own_subnet_type,
+        };
+
+        let (instructions_left, result) =
+            self.canister_manager
+                .install_code(install_context, state, execution_parameters);
+
+        let execution_duration = timer.elapsed();
+
+        match result {
+            Ok(result) => {
+                state.metadata.heap_delta_estimate += result.heap_delta;
+
+                info!(
+                    self.log,
+                    "Finished executing install_code message on canister {:?} after {:?}, old wasm hash {:?}, new wasm hash {:?}",
+                    canister_id,
+                    execution_duration,
+                    result.old_wasm_hash,
+                    result.new_wasm_hash,
+                );
+
+                (Ok(EmptyBlob::encode()), instructions_left)
+            }
+            Err(err) => {
+                info!(
+                    self.log,
+                    "Finished executing install_code message on canister {:?} after {:?} with error: {:?}",
+                    canister_id,
+                    execution_duration,
+                    err
+                );
+                (Err(err.into()), instructions_left)
+            }
+        }
+    }
+
     fn update_settings(
         &self,
         sender: PrincipalId,
@@ -918,18 +1189,8 @@ impl ExecutionEnvironmentImpl {
         canister_id: CanisterId,
         state: &mut ReplicatedState,
     ) -> Result<Vec<u8>, UserError> {
-        let compute_allocation_used = state.total_compute_allocation();
-        let memory_allocation_used = state.total_memory_taken();
-
-        let mut canister = get_canister_mut(canister_id, state)?;
         self.canister_manager
-            .update_settings(
-                sender,
-                settings,
-                &mut canister,
-                compute_allocation_used,
-                memory_allocation_used,
-            )
+            .update_settings(sender, settings, canister_id, state)
             .map(|()| EmptyBlob::encode())
             .map_err(|err| err.into())
     }
@@ -1029,6 +1290,75 @@ impl ExecutionEnvironmentImpl {
             .map_err(|err| err.into())
     }
 
+    // Executes `canister_heartbeat` or `canister_global_timer` after charging
+    // the canister for the instruction limit.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_canister_system_task(
+        &self,
+        system_method: SystemMethod,
+        mut canister: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    ) {
+        if canister.status() != CanisterStatusType::Running {
+            let status = canister.status();
+            return (
+                canister,
+                instructions_limit,
+                Err(CanisterHeartbeatError::CanisterNotRunning { status }),
+            );
+        }
+
+        let memory_usage = canister.memory_usage(self.own_subnet_type);
+        let compute_allocation = canister.scheduler_state.compute_allocation;
+        if let Err(err) = self.cycles_account_manager.withdraw_execution_cycles(
+            &mut canister.system_state,
+            memory_usage,
+            compute_allocation,
+            instructions_limit,
+        ) {
+            return (
+                canister,
+                instructions_limit,
+                Err(CanisterHeartbeatError::OutOfCycles(err)),
+            );
+        }
+
+        let execution_parameters =
+            self.execution_parameters(&canister, instructions_limit, subnet_available_memory);
+
+        let (mut canister, num_instructions_left, result) =
+            self.hypervisor.execute_canister_system_task(
+                system_method,
+                canister,
+                routing_table,
+                subnet_records,
+                time,
+                execution_parameters,
+            );
+
+        // Clone the `cycles_account_manager` to avoid having to require 'static
+        // lifetime bound on `self`.
+        let cycles_account_manager = Arc::clone(&self.cycles_account_manager);
+
+        // Refund the canister with any cycles left after message execution.
+        cycles_account_manager
+            .refund_execution_cycles(&mut canister.system_state, num_instructions_left);
+        let result = match result {
+            Ok(heap_delta) => Ok(heap_delta),
+            Err(err) => Err(CanisterHeartbeatError::CanisterExecutionFailed(err)),
+        };
+
+        (canister, num_instructions_left, result)
+    }
+
     // Executes an inter-canister response.
     //
     // Returns a tuple with the result, along with a boolean indicating whether or
@@ -1266,7 +1596,9 @@ impl ExecutionEnvironmentImpl {
             );
         }
 
-        if canister.exports_query_method(req.method_name.clone()) {
+        if canister.exports_query_method(req.method_name.clone())
+            || canister.exports_composite_query_method(req.method_name.clone())
+        {
             self.execute_query_method_for_request(canister, req, cycles, time)
         } else {
             self.execute_update_method_for_request(
@@ -1521,7 +1853,9 @@ impl ExecutionEnvironmentImpl {
             };
         }
 
-        if canister.exports_query_method(ingress.method_name.clone()) {
+        if canister.exports_query_method(ingress.method_name.clone())
+            || canister.exports_composite_query_method(ingress.method_name.clone())
+        {
             self.execute_query_method_for_ingress(canister, ingress, num_instructions, time)
         } else {
             self.execute_update_method_for_ingress(
@@ -1792,15 +2126,24 @@ impl ExecutionEnvironmentImpl {
         }
     }
 
+    /// Validates a `sign_with_ecdsa` request and registers it with the subnet
+    /// call context manager, from where consensus picks it up. The response
+    /// is delivered by consensus once the signature is ready.
     fn sign_with_ecdsa(
         &self,
         request: &Request,
-        message_hash: &[u8],
-        derivation_path: &[u8],
-        is_mock: bool,
+        args: SignWithECDSAArgs,
         state: &mut ReplicatedState,
         rng: &mut (dyn RngCore + 'static),
     ) -> Result<(), UserError> {
+        if args.message_hash.len() != 32 {
+            return Err(UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                "message_hash must be 32 bytes",
+            ));
+        }
+        ecdsa_master_public_key(state, &args.key_id)?;
+
         let mut pseudo_random_id = [0u8; 32];
         rng.fill_bytes(&mut pseudo_random_id);
 
@@ -1810,22 +2153,164 @@ impl ExecutionEnvironmentImpl {
             pseudo_random_id,
             request.sender()
         );
+        let batch_time = state.metadata.batch_time;
         state
             .metadata
             .subnet_call_context_manager
-            .push_sign_with_ecdsa_request(
-                SignWithEcdsaContext {
-                    request: request.clone(),
-                    message_hash: message_hash.to_vec(),
-                    derivation_path: derivation_path.to_vec(),
-                    pseudo_random_id,
-                    batch_time: state.metadata.batch_time,
-                },
-                is_mock,
-            );
+            .push_sign_with_ecdsa_request(SignWithEcdsaContext {
+                request: request.clone(),
+                key_id: args.key_id,
+                message_hash: args.message_hash,
+                derivation_path: args.derivation_path,
+                pseudo_random_id,
+                batch_time,
+            });
+        Ok(())
+    }
+
+    /// Derives the public key of the given derivation path and canister (the
+    /// caller by default) from the master public key of the requested key.
+    fn ecdsa_public_key(
+        &self,
+        payload: &[u8],
+        request: &Request,
+        state: &ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        if !state.metadata.own_subnet_features.ecdsa_signatures {
+            return Err(UserError::new(
+                ErrorCode::CanisterContractViolation,
+                "This API is not enabled on this subnet",
+            ));
+        }
+        let args = ECDSAPublicKeyArgs::decode(payload)?;
+        let master_public_key = ecdsa_master_public_key(state, &args.key_id)?;
+        let caller = args.canister_id.unwrap_or(request.sender);
+        let derivation_path = ExtendedDerivationPath {
+            caller: caller.get(),
+            derivation_path: args.derivation_path,
+        };
+        let derivation_error = |err: ThresholdEcdsaGetPublicKeyError| {
+            UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                format!("Failed to derive the public key: {}", err),
+            )
+        };
+        let public_key = ic_crypto::derive_tecdsa_public_key(master_public_key, &derivation_path)
+            .map_err(derivation_error)?;
+        let chain_code =
+            ic_crypto::derive_tecdsa_chain_code(&derivation_path).map_err(derivation_error)?;
+        Ok(ECDSAPublicKeyResponse {
+            public_key: public_key.public_key,
+            chain_code,
+        }
+        .encode())
+    }
+
+    /// Validates a canister HTTP request, charges the request's fee from the
+    /// cycles attached to it and registers it with the subnet call context
+    /// manager, from where it is picked up by the canister HTTP payload
+    /// builder. Returns the reject message if the request is invalid.
+    fn http_request(
+        &self,
+        request: &Request,
+        args: CanisterHttpRequestArgs,
+        state: &mut ReplicatedState,
+    ) -> Result<(), String> {
+        if args.url.len() > MAX_CANISTER_HTTP_URL_SIZE {
+            return Err(format!(
+                "The url of the http request is longer than {} bytes",
+                MAX_CANISTER_HTTP_URL_SIZE
+            ));
+        }
+        if args.headers.len() > MAX_CANISTER_HTTP_HEADER_NUM {
+            return Err(format!(
+                "The http request has more than {} headers",
+                MAX_CANISTER_HTTP_HEADER_NUM
+            ));
+        }
+        let max_response_bytes = match args.max_response_bytes {
+            Some(max_response_bytes) if max_response_bytes > MAX_CANISTER_HTTP_RESPONSE_BYTES => {
+                return Err(format!(
+                    "max_response_bytes cannot exceed {} bytes",
+                    MAX_CANISTER_HTTP_RESPONSE_BYTES
+                ));
+            }
+            Some(max_response_bytes) => max_response_bytes,
+            None => MAX_CANISTER_HTTP_RESPONSE_BYTES,
+        };
+
+        let fee = self.cycles_account_manager.http_request_fee(
+            request.payload_size_bytes(),
+            NumBytes::from(max_response_bytes),
+        );
+        if request.payment < fee {
+            return Err(format!(
+                "http_request request sent with {} cycles, but {} cycles are required.",
+                request.payment, fee
+            ));
+        }
+        let mut request = request.clone();
+        request.payment -= fee;
+
+        state
+            .metadata
+            .subnet_call_context_manager
+            .push_http_request(CanisterHttpRequestContext {
+                request,
+                url: args.url,
+                max_response_bytes,
+                headers: args.headers,
+                body: args.body,
+                http_method: args.method,
+                transform_method_name: args.transform_method_name,
+                time: state.metadata.batch_time,
+            });
         Ok(())
     }
 
+    fn bitcoin_get_balance(
+        &self,
+        payload: &[u8],
+        state: &ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        let args = BitcoinGetBalanceArgs::decode(payload)?;
+        let balance = ic_btc_canister::get_balance(
+            bitcoin_state(state)?,
+            &args.address,
+            args.min_confirmations.unwrap_or(0),
+        )
+        .map_err(bitcoin_call_error)?;
+        Ok(Encode!(&balance).unwrap())
+    }
+
+    fn bitcoin_get_utxos(
+        &self,
+        payload: &[u8],
+        state: &ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        let args = BitcoinGetUtxosArgs::decode(payload)?;
+        ic_btc_canister::get_utxos(
+            bitcoin_state(state)?,
+            &args.address,
+            args.min_confirmations.unwrap_or(0),
+        )
+        .map(|response| response.encode())
+        .map_err(bitcoin_call_error)
+    }
+
+    fn bitcoin_send_transaction(
+        &self,
+        payload: &[u8],
+        state: &mut ReplicatedState,
+    ) -> Result<Vec<u8>, UserError> {
+        let args = BitcoinSendTransactionArgs::decode(payload)?;
+        let time = state.metadata.batch_time;
+        let bitcoin = state.bitcoin.as_mut().ok_or_else(bitcoin_disabled)?;
+        ic_btc_canister::send_transaction(bitcoin, args.transaction, time)
+            .map(|()| EmptyBlob::encode())
+            .map_err(bitcoin_call_error)
+    }
+
     fn get_ingress_status(
         &self,
         canister: &mut CanisterState,
@@ -1944,6 +2429,37 @@ fn get_canister_mut(
     }
 }
 
+fn ecdsa_master_public_key<'a>(
+    state: &'a ReplicatedState,
+    key_id: &EcdsaKeyId,
+) -> Result<&'a EcdsaPublicKey, UserError> {
+    state
+        .metadata
+        .ecdsa_subnet_public_keys
+        .get(key_id)
+        .ok_or_else(|| {
+            UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                format!("Unknown key ID: {}", key_id),
+            )
+        })
+}
+
+fn bitcoin_state(state: &ReplicatedState) -> Result<&BitcoinState, UserError> {
+    state.bitcoin.as_ref().ok_or_else(bitcoin_disabled)
+}
+
+fn bitcoin_disabled() -> UserError {
+    UserError::new(
+        ErrorCode::CanisterContractViolation,
+        "This API is not enabled on this subnet".to_string(),
+    )
+}
+
+fn bitcoin_call_error(err: ic_btc_canister::BitcoinCallError) -> UserError {
+    UserError::new(ErrorCode::CanisterRejectedMessage, err.to_string())
+}
+
 fn produce_inter_canister_response(
     canister: &mut CanisterState,
     action: CallContextAction,
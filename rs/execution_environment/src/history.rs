@@ -230,6 +230,7 @@ fn dashboard_label_value_from(code: ErrorCode) -> &'static str {
         CanisterWasmModuleNotFound => "Canister WASM Module Not Found",
         CanisterNonEmpty => "Canister Non-Empty",
         CanisterEmpty => "Canister Empty",
+        CanisterSnapshotNotFound => "Canister Snapshot Not Found",
         CanisterOutOfCycles => "Canister Out Of Cycles",
         CanisterTrapped => "Canister Trapped",
         CanisterCalledTrap => "Canister Called Trap",
@@ -257,5 +258,11 @@ fn dashboard_label_value_from(code: ErrorCode) -> &'static str {
         CanisterCyclesLimitExceeded => {
             "Canister Cycles Limit for Single Message Execution Exceeded"
         }
+        CompositeQueryCalledInReplicatedMode => "Composite query called in replicated mode",
+        QueryCallGraphTooDeep => "Query call graph too deep",
+        QueryCallGraphTotalInstructionLimitExceeded => {
+            "Query call graph total instruction limit exceeded"
+        }
+        CanisterWasmMemoryLimitExceeded => "Canister exceeded its Wasm memory limit",
     }
 }
@@ -28,6 +28,7 @@ use ic_types::{
     ingress::WasmResult,
     messages::Payload,
     methods::{Callback, FuncRef, SystemMethod, WasmMethod},
+    time::UNIX_EPOCH,
     CanisterId, CanisterStatusType, Cycles, NumBytes, NumInstructions, PrincipalId, SubnetId, Time,
 };
 use prometheus::{Histogram, IntCounterVec, IntGauge};
@@ -206,7 +207,10 @@ impl Hypervisor {
             )
         } else {
             // In contrast to other methods, an update methods ignores the
-            // Wasm execution error and returns 0 as the heap delta.
+            // Wasm execution error and returns 0 as the heap delta. The
+            // canister log is kept so that the trap can be inspected.
+            let mut system_state = system_state;
+            system_state.canister_log = output_system_state.canister_log;
             (system_state, NumBytes::from(0))
         };
 
@@ -241,6 +245,40 @@ impl Hypervisor {
         CanisterState,
         NumInstructions,
         HypervisorResult<Option<WasmResult>>,
+    ) {
+        let (canister, num_instructions_left, result, _reads_transient_state) = self
+            .execute_query_with_transient_state_reads(
+                query_execution_type,
+                method,
+                payload,
+                caller,
+                canister,
+                data_certificate,
+                time,
+                execution_parameters,
+            );
+        (canister, num_instructions_left, result)
+    }
+
+    /// Same as `execute_query`, but additionally returns whether the query
+    /// read the time, the cycle balance of the canister or the data
+    /// certificate, in which case its result must not be cached.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn execute_query_with_transient_state_reads(
+        &self,
+        query_execution_type: QueryExecutionType,
+        method: &str,
+        payload: &[u8],
+        caller: PrincipalId,
+        canister: CanisterState,
+        data_certificate: Option<Vec<u8>>,
+        time: Time,
+        execution_parameters: ExecutionParameters,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        HypervisorResult<Option<WasmResult>>,
+        bool,
     ) {
         // Validate that the canister is running.
         if CanisterStatusType::Running != canister.status() {
@@ -248,10 +286,20 @@ impl Hypervisor {
                 canister,
                 execution_parameters.instruction_limit,
                 Err(HypervisorError::CanisterStopped),
+                false,
             );
         }
 
-        let method = WasmMethod::Query(method.to_string());
+        // A method exported as `canister_query` takes precedence over a
+        // composite query with the same name (validation rejects such modules
+        // anyway).
+        let method = if canister.exports_composite_query_method(method.to_string())
+            && !canister.exports_query_method(method.to_string())
+        {
+            WasmMethod::CompositeQuery(method.to_string())
+        } else {
+            WasmMethod::Query(method.to_string())
+        };
         let memory_usage = canister.memory_usage(self.own_subnet_type);
         let (execution_state, system_state, scheduler_state) = canister.into_parts();
 
@@ -262,6 +310,7 @@ impl Hypervisor {
                     CanisterState::from_parts(None, system_state, scheduler_state),
                     execution_parameters.instruction_limit,
                     Err(HypervisorError::WasmModuleNotFound),
+                    false,
                 );
             }
             Some(state) => state,
@@ -273,11 +322,26 @@ impl Hypervisor {
                 CanisterState::from_parts(Some(execution_state), system_state, scheduler_state),
                 execution_parameters.instruction_limit,
                 Err(HypervisorError::MethodNotFound(method)),
+                false,
             );
         }
 
         match query_execution_type {
             QueryExecutionType::Replicated => {
+                // Composite queries may call other canisters and can only be
+                // executed in non-replicated mode.
+                if let WasmMethod::CompositeQuery(name) = method {
+                    return (
+                        CanisterState::from_parts(
+                            Some(execution_state),
+                            system_state,
+                            scheduler_state,
+                        ),
+                        execution_parameters.instruction_limit,
+                        Err(HypervisorError::CompositeQueryCalledInReplicatedMode(name)),
+                        false,
+                    );
+                }
                 if execution_state.cow_mem_mgr.is_valid() {
                     // Replicated queries are similar to update executions and they operate
                     // against the "current" canister state
@@ -303,7 +367,12 @@ impl Hypervisor {
 
                 let canister =
                     CanisterState::from_parts(Some(execution_state), system_state, scheduler_state);
-                (canister, output.num_instructions_left, output.wasm_result)
+                (
+                    canister,
+                    output.num_instructions_left,
+                    output.wasm_result,
+                    output.reads_transient_state,
+                )
             }
             QueryExecutionType::NonReplicated {
                 call_context_id,
@@ -356,7 +425,12 @@ impl Hypervisor {
                     output_system_state,
                     scheduler_state,
                 );
-                (canister, output.num_instructions_left, output.wasm_result)
+                (
+                    canister,
+                    output.num_instructions_left,
+                    output.wasm_result,
+                    output.reads_transient_state,
+                )
             }
         }
     }
@@ -487,6 +561,7 @@ impl Hypervisor {
             Err(callback_err) => {
                 // A trap has occurred when executing the reply/reject closure.
                 // Execute the cleanup if it exists.
+                canister.system_state.canister_log = output_system_state.canister_log;
                 match callback.on_cleanup {
                     None => {
                         // No cleanup closure present. Return the callback error as-is.
@@ -539,6 +614,8 @@ impl Hypervisor {
                             }
                             Err(cleanup_err) => {
                                 // Executing the cleanup call back failed.
+                                canister.system_state.canister_log =
+                                    output_system_state.canister_log;
                                 (
                                     canister,
                                     cleanup_output.num_instructions_left,
@@ -894,7 +971,31 @@ impl Hypervisor {
         time: Time,
         execution_parameters: ExecutionParameters,
     ) -> (CanisterState, NumInstructions, HypervisorResult<NumBytes>) {
-        let method = WasmMethod::System(SystemMethod::CanisterHeartbeat);
+        self.execute_canister_system_task(
+            SystemMethod::CanisterHeartbeat,
+            canister,
+            routing_table,
+            subnet_records,
+            time,
+            execution_parameters,
+        )
+    }
+
+    /// Executes a system method that is triggered by the system rather than
+    /// by a message, i.e. `canister_heartbeat` or `canister_global_timer`.
+    ///
+    /// Returns the same values as `execute_canister_heartbeat`.
+    #[allow(clippy::type_complexity)]
+    pub fn execute_canister_system_task(
+        &self,
+        system_method: SystemMethod,
+        canister: CanisterState,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        execution_parameters: ExecutionParameters,
+    ) -> (CanisterState, NumInstructions, HypervisorResult<NumBytes>) {
+        let method = WasmMethod::System(system_method);
         let memory_usage = canister.memory_usage(self.own_subnet_type);
         let (execution_state, mut old_system_state, scheduler_state) = canister.into_parts();
 
@@ -920,6 +1021,8 @@ impl Hypervisor {
             );
         }
 
+        // The global timer has the same execution context as the heartbeat, so
+        // both share the heartbeat call origin and API type.
         let call_context_id = old_system_state
             .call_context_manager_mut()
             .unwrap()
@@ -986,7 +1089,11 @@ impl Hypervisor {
                 let bytes = NumBytes::from((output.instance_stats.dirty_pages * PAGE_SIZE) as u64);
                 (output_system_state, Ok(bytes))
             }
-            Err(err) => (old_system_state, Err(err)),
+            Err(err) => {
+                let mut system_state = old_system_state;
+                system_state.canister_log = output_system_state.canister_log;
+                (system_state, Err(err))
+            }
         };
         let canister =
             CanisterState::from_parts(Some(execution_state), system_state, scheduler_state);
@@ -1046,6 +1153,7 @@ impl Hypervisor {
         embedder_config.persistence_type = config.persistence_type;
         embedder_config.num_runtime_generic_threads = num_runtime_threads;
         embedder_config.num_runtime_query_threads = std::cmp::min(num_runtime_threads, 4);
+        embedder_config.feature_flags.wasm_simd = config.wasm_simd;
 
         let wasm_embedder = WasmtimeEmbedder::new(embedder_config.clone(), log.clone());
         let wasm_executor = WasmExecutor::new(
@@ -1096,6 +1204,7 @@ impl Hypervisor {
         execution_state: ExecutionState,
     ) -> (WasmExecutionOutput, ExecutionState, SystemState) {
         let api_type_str = api_type.as_str();
+        let time = api_type.time().unwrap_or(UNIX_EPOCH);
         let static_system_state =
             StaticSystemState::new(&system_state, self.cycles_account_manager.subnet_type());
         let system_state_accessor =
@@ -1124,10 +1233,21 @@ impl Hypervisor {
                 })
             };
         self.metrics.observe(api_type_str, &output);
-        (
-            output,
-            execution_state,
-            system_state_accessor.release_system_state(),
-        )
+        let mut system_state = system_state_accessor.release_system_state();
+        let trap_message = match &output.wasm_result {
+            Err(HypervisorError::CalledTrap(msg)) => Some(msg.clone()),
+            Err(HypervisorError::Trapped(code)) => Some(code.to_string()),
+            Err(HypervisorError::WasmMemoryLimitExceeded { bytes, limit }) => Some(format!(
+                "Wasm memory limit exceeded: {} bytes used, limit is {} bytes",
+                bytes, limit
+            )),
+            _ => None,
+        };
+        if let Some(msg) = trap_message {
+            system_state
+                .canister_log
+                .add_record(time, format!("[TRAP]: {}", msg).into_bytes());
+        }
+        (output, execution_state, system_state)
     }
 }
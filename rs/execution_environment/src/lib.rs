@@ -128,7 +128,7 @@ pub fn setup_execution(
     let async_query_handler = HttpQueryHandler::new_service(
         QUERY_EXECUTION_MAX_BUFFERED_QUERIES,
         QUERY_EXECUTION_THREADS,
-        Arc::clone(&sync_query_handler) as Arc<_>,
+        Arc::clone(&sync_query_handler),
         Arc::clone(&threadpool),
         Arc::clone(&state_reader),
     );
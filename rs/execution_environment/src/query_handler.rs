@@ -2,6 +2,7 @@
 //! query methods via query calls.
 
 mod query_allocations;
+mod query_cache;
 mod query_context;
 #[cfg(test)]
 mod tests;
@@ -13,6 +14,10 @@ use crate::{
 };
 use ic_config::execution_environment::Config;
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
+use ic_ic00_types::{
+    CanisterIdRecord, FetchCanisterLogsResponse, LogVisibility, Method as Ic00Method, Payload,
+    IC_00,
+};
 use ic_interfaces::{
     execution_environment::{QueryExecutionService, QueryHandler, SubnetAvailableMemory},
     state_manager::StateReader,
@@ -28,14 +33,16 @@ use ic_types::{
         UserQuery,
     },
     user_error::{ErrorCode, RejectCode, UserError},
-    CanisterId, NumInstructions, SubnetId,
+    CanisterId, Height, NumInstructions, PrincipalId, SubnetId,
 };
 use query_allocations::QueryAllocationsUsed;
+use query_cache::{QueryCache, QueryCacheKey};
 use serde::Serialize;
 use std::{
     convert::Infallible,
     future::Future,
     pin::Pin,
+    str::FromStr,
     sync::{Arc, Mutex, RwLock},
     task::{Context, Poll},
 };
@@ -53,7 +60,7 @@ fn get_latest_certified_state_and_data_certificate(
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     certificate_delegation: Option<CertificateDelegation>,
     canister_id: CanisterId,
-) -> Option<(Arc<ReplicatedState>, Vec<u8>)> {
+) -> Option<(Arc<ReplicatedState>, Vec<u8>, Height)> {
     // The path to fetch the data certificate for the canister.
     let path = SubTree(flatmap! {
         label("canister") => SubTree(
@@ -78,6 +85,7 @@ fn get_latest_certified_state_and_data_certificate(
                     signature: Blob(cert.signed.signature.signature.get().0),
                     delegation: certificate_delegation,
                 }),
+                cert.height,
             )
         })
 }
@@ -86,6 +94,67 @@ fn label<T: Into<Label>>(t: T) -> Label {
     t.into()
 }
 
+/// Executes a query call to the management canister. The only management
+/// method that can be called as a query is `fetch_canister_logs`.
+fn execute_management_query(
+    query: &UserQuery,
+    state: &ReplicatedState,
+) -> Result<WasmResult, UserError> {
+    match Ic00Method::from_str(&query.method_name) {
+        Ok(Ic00Method::FetchCanisterLogs) => {
+            fetch_canister_logs(query.source.get(), state, &query.method_payload)
+        }
+        _ => Err(UserError::new(
+            ErrorCode::CanisterMethodNotFound,
+            format!(
+                "Management canister has no query method '{}'",
+                query.method_name
+            ),
+        )),
+    }
+}
+
+/// Returns the log of the canister given in `payload`, provided that its log
+/// visibility allows `sender` to see it.
+fn fetch_canister_logs(
+    sender: PrincipalId,
+    state: &ReplicatedState,
+    payload: &[u8],
+) -> Result<WasmResult, UserError> {
+    let canister_id = CanisterIdRecord::decode(payload)?.get_canister_id();
+    let canister = state.canister_state(&canister_id).ok_or_else(|| {
+        UserError::new(
+            ErrorCode::CanisterNotFound,
+            format!("Canister {} not found", canister_id),
+        )
+    })?;
+
+    match canister.system_state.log_visibility {
+        LogVisibility::Public => {}
+        LogVisibility::Controllers => {
+            if !canister.system_state.controllers.contains(&sender) {
+                return Err(UserError::new(
+                    ErrorCode::CanisterInvalidController,
+                    format!(
+                        "Caller {} is not allowed to fetch the logs of canister {}",
+                        sender, canister_id
+                    ),
+                ));
+            }
+        }
+    }
+
+    let response = FetchCanisterLogsResponse {
+        canister_log_records: canister
+            .system_state
+            .canister_log
+            .records()
+            .cloned()
+            .collect(),
+    };
+    Ok(WasmResult::Reply(response.encode()))
+}
+
 pub(crate) struct InternalHttpQueryHandler {
     log: ReplicaLogger,
     hypervisor: Arc<Hypervisor>,
@@ -95,11 +164,12 @@ pub(crate) struct InternalHttpQueryHandler {
     config: Config,
     metrics: QueryHandlerMetrics,
     max_instructions_per_message: NumInstructions,
+    query_cache: QueryCache,
 }
 
 /// Struct that is responsible for handling queries sent by user.
 pub(crate) struct HttpQueryHandler {
-    internal: Arc<dyn QueryHandler<State = ReplicatedState>>,
+    internal: Arc<InternalHttpQueryHandler>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     threadpool: Arc<Mutex<threadpool::ThreadPool>>,
 }
@@ -114,6 +184,7 @@ impl InternalHttpQueryHandler {
         metrics_registry: &MetricsRegistry,
         max_instructions_per_message: NumInstructions,
     ) -> Self {
+        let query_cache = QueryCache::new(config.query_cache_capacity, metrics_registry);
         Self {
             log,
             hypervisor,
@@ -123,19 +194,54 @@ impl InternalHttpQueryHandler {
             config,
             metrics: QueryHandlerMetrics::new(metrics_registry),
             max_instructions_per_message,
+            query_cache,
         }
     }
-}
-
-impl QueryHandler for InternalHttpQueryHandler {
-    type State = ReplicatedState;
 
-    fn query(
+    /// Like `query()`, but answers the query from the query cache if it was
+    /// already executed against the state at `certified_height` and the
+    /// receiver has not changed since. Results of queries that read the time,
+    /// a cycle balance or the data certificate, or that call other canisters,
+    /// are never cached.
+    pub(crate) fn query_with_cache(
         &self,
         query: UserQuery,
         state: Arc<ReplicatedState>,
         data_certificate: Vec<u8>,
+        certified_height: Height,
     ) -> Result<WasmResult, UserError> {
+        if self.config.query_cache_capacity.get() == 0 || query.receiver == IC_00 {
+            return self.query(query, state, data_certificate);
+        }
+        let canister_version = match state.canister_state(&query.receiver) {
+            Some(canister) => canister.system_state.canister_version,
+            None => return self.query(query, state, data_certificate),
+        };
+
+        let key = QueryCacheKey::new(&query);
+        if let Some(result) = self
+            .query_cache
+            .get(&key, certified_height, canister_version)
+        {
+            return Ok(result);
+        }
+
+        let (result, cacheable) = self.execute_query(query, state, data_certificate);
+        if let (Ok(result), true) = (&result, cacheable) {
+            self.query_cache
+                .insert(key, certified_height, canister_version, result.clone());
+        }
+        result
+    }
+
+    // Executes the query and returns its result together with whether the
+    // result may be cached.
+    fn execute_query(
+        &self,
+        query: UserQuery,
+        state: Arc<ReplicatedState>,
+        data_certificate: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, bool) {
         let measurement_scope = MeasurementScope::root(&self.metrics.query);
         // Note that This assumes that the QueryHandler is always called with the
         // "latest" state.  If and when we start supporting queries against older
@@ -147,6 +253,10 @@ impl QueryHandler for InternalHttpQueryHandler {
             .unwrap()
             .purge(state.metadata.batch_time);
 
+        if query.receiver == IC_00 {
+            return (execute_management_query(&query, &state), false);
+        }
+
         // Letting the canister grow arbitrarily when executing the
         // query is fine as we do not persist state modifications.
         let subnet_available_memory =
@@ -164,8 +274,24 @@ impl QueryHandler for InternalHttpQueryHandler {
             subnet_available_memory,
             max_canister_memory_size,
             self.max_instructions_per_message,
+            self.config.max_query_call_depth,
+            self.config.max_query_call_graph_instructions,
         );
-        context.run(query, &self.metrics, &measurement_scope)
+        let result = context.run(query, &self.metrics, &measurement_scope);
+        (result, context.is_result_cacheable())
+    }
+}
+
+impl QueryHandler for InternalHttpQueryHandler {
+    type State = ReplicatedState;
+
+    fn query(
+        &self,
+        query: UserQuery,
+        state: Arc<ReplicatedState>,
+        data_certificate: Vec<u8>,
+    ) -> Result<WasmResult, UserError> {
+        self.execute_query(query, state, data_certificate).0
     }
 }
 
@@ -173,7 +299,7 @@ impl HttpQueryHandler {
     pub(crate) fn new_service(
         max_buffered_queries: usize,
         threads: usize,
-        internal: Arc<dyn QueryHandler<State = ReplicatedState>>,
+        internal: Arc<InternalHttpQueryHandler>,
         threadpool: Arc<Mutex<threadpool::ThreadPool>>,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     ) -> QueryExecutionService {
@@ -249,7 +375,9 @@ impl Service<(UserQuery, Option<CertificateDelegation>)> for HttpQueryHandler {
                     certificate_delegation,
                     query.receiver,
                 ) {
-                    Some((state, cert)) => internal.query(query, state, cert),
+                    Some((state, cert, height)) => {
+                        internal.query_with_cache(query, state, cert, height)
+                    }
                     None => Err(UserError::new(
                         ErrorCode::CertifiedStateUnavailable,
                         "Certified state is not available yet. Please try again...",
@@ -0,0 +1,194 @@
+use ic_metrics::MetricsRegistry;
+use ic_types::{ingress::WasmResult, messages::UserQuery, CanisterId, Height, NumBytes, UserId};
+use prometheus::IntCounter;
+use std::{collections::BTreeMap, mem::size_of, sync::Mutex};
+
+/// Identifies a user query by its receiver, method, argument and caller.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct QueryCacheKey {
+    receiver: CanisterId,
+    method_name: String,
+    method_payload: Vec<u8>,
+    source: UserId,
+}
+
+impl QueryCacheKey {
+    pub(crate) fn new(query: &UserQuery) -> Self {
+        Self {
+            receiver: query.receiver,
+            method_name: query.method_name.clone(),
+            method_payload: query.method_payload.clone(),
+            source: query.source,
+        }
+    }
+
+    fn size_bytes(&self) -> usize {
+        size_of::<Self>() + self.method_name.len() + self.method_payload.len()
+    }
+}
+
+struct QueryCacheEntry {
+    // The certified height of the state that the result was computed against.
+    height: Height,
+    // The version of the receiver when the result was computed.
+    canister_version: u64,
+    result: WasmResult,
+    // The value of `QueryCacheEntries::next_use` when the entry was last
+    // inserted or returned. The entry with the lowest value is evicted first.
+    last_use: u64,
+    size_bytes: usize,
+}
+
+#[derive(Default)]
+struct QueryCacheEntries {
+    entries: BTreeMap<QueryCacheKey, QueryCacheEntry>,
+    // The keys of `entries` ordered from the least to the most recently used.
+    lru: BTreeMap<u64, QueryCacheKey>,
+    next_use: u64,
+    size_bytes: usize,
+}
+
+impl QueryCacheEntries {
+    fn remove(&mut self, key: &QueryCacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.last_use);
+            self.size_bytes -= entry.size_bytes;
+        }
+    }
+
+    fn touch(&mut self, key: &QueryCacheKey) {
+        let next_use = self.next_use;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.last_use);
+            entry.last_use = next_use;
+            self.lru.insert(next_use, key.clone());
+            self.next_use += 1;
+        }
+    }
+}
+
+pub(crate) struct QueryCacheMetrics {
+    pub hits: IntCounter,
+    pub misses: IntCounter,
+    pub evictions: IntCounter,
+}
+
+impl QueryCacheMetrics {
+    fn new(metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            hits: metrics_registry.int_counter(
+                "execution_query_cache_hits",
+                "Total number of user queries answered from the query cache.",
+            ),
+            misses: metrics_registry.int_counter(
+                "execution_query_cache_misses",
+                "Total number of user queries not found in the query cache.",
+            ),
+            evictions: metrics_registry.int_counter(
+                "execution_query_cache_evictions",
+                "Total number of query cache entries evicted to make room for \
+                new ones.",
+            ),
+        }
+    }
+}
+
+/// A bounded cache of the results of user queries.
+///
+/// A result is only returned for the certified height and the version of the
+/// receiver it was computed against, so that any change of the state makes
+/// the query execute again. Once the results exceed the capacity, the least
+/// recently used ones are evicted.
+pub(crate) struct QueryCache {
+    entries: Mutex<QueryCacheEntries>,
+    capacity: NumBytes,
+    pub(crate) metrics: QueryCacheMetrics,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: NumBytes, metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            entries: Mutex::new(QueryCacheEntries::default()),
+            capacity,
+            metrics: QueryCacheMetrics::new(metrics_registry),
+        }
+    }
+
+    /// Returns the cached result of the query, provided that it was computed
+    /// against the given height and canister version. Outdated entries are
+    /// dropped.
+    pub(crate) fn get(
+        &self,
+        key: &QueryCacheKey,
+        height: Height,
+        canister_version: u64,
+    ) -> Option<WasmResult> {
+        let mut entries = self.entries.lock().unwrap();
+        let is_up_to_date = entries
+            .entries
+            .get(key)
+            .map(|entry| entry.height == height && entry.canister_version == canister_version);
+        match is_up_to_date {
+            Some(true) => {
+                entries.touch(key);
+                self.metrics.hits.inc();
+                entries.entries.get(key).map(|entry| entry.result.clone())
+            }
+            Some(false) => {
+                entries.remove(key);
+                self.metrics.misses.inc();
+                None
+            }
+            None => {
+                self.metrics.misses.inc();
+                None
+            }
+        }
+    }
+
+    /// Caches the result of the query, evicting the least recently used
+    /// entries if the cache would exceed its capacity. Results larger than
+    /// the whole cache are not cached.
+    pub(crate) fn insert(
+        &self,
+        key: QueryCacheKey,
+        height: Height,
+        canister_version: u64,
+        result: WasmResult,
+    ) {
+        let result_size = match &result {
+            WasmResult::Reply(payload) => payload.len(),
+            WasmResult::Reject(message) => message.len(),
+        };
+        let size_bytes = key.size_bytes() + size_of::<QueryCacheEntry>() + result_size;
+        if size_bytes as u64 > self.capacity.get() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&key);
+        while (entries.size_bytes + size_bytes) as u64 > self.capacity.get() {
+            let oldest = match entries.lru.values().next() {
+                Some(oldest) => oldest.clone(),
+                None => break,
+            };
+            entries.remove(&oldest);
+            self.metrics.evictions.inc();
+        }
+
+        let last_use = entries.next_use;
+        entries.next_use += 1;
+        entries.size_bytes += size_bytes;
+        entries.lru.insert(last_use, key.clone());
+        entries.entries.insert(
+            key,
+            QueryCacheEntry {
+                height,
+                canister_version,
+                result,
+                last_use,
+                size_bytes,
+            },
+        );
+    }
+}
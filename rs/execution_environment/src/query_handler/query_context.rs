@@ -7,6 +7,12 @@
 //! execution, i.e. the originator of the processing is a Query from an end-user
 //! and not an Ingress message.
 //!
+//! - Outside of system subnets, only composite query methods (exported as
+//! `canister_composite_query`) can query other canisters.
+//!
+//! - The depth of the call graph and the total number of instructions executed
+//! by all canisters in the call graph are limited.
+//!
 //! - Loops are not allowed. E.g. call graphs like A -> B -> C -> A are not
 //! supported.
 //!
@@ -113,6 +119,16 @@ pub(super) struct QueryContext<'a> {
     subnet_available_memory: SubnetAvailableMemory,
     max_canister_memory_size: NumBytes,
     max_instructions_per_message: NumInstructions,
+    max_query_call_depth: usize,
+    // The depth in the call graph of the canisters that are waiting for
+    // responses. The canister executing the user query has depth 0.
+    call_depths: BTreeMap<CanisterId, usize>,
+    // The number of instructions that the remaining executions in the call
+    // graph can use together.
+    total_instructions_left: NumInstructions,
+    // False once an execution read the time, a cycle balance or the data
+    // certificate, or the query called other canisters.
+    result_cacheable: bool,
 }
 
 impl<'a> QueryContext<'a> {
@@ -128,6 +144,8 @@ impl<'a> QueryContext<'a> {
         subnet_available_memory: SubnetAvailableMemory,
         max_canister_memory_size: NumBytes,
         max_instructions_per_message: NumInstructions,
+        max_query_call_depth: usize,
+        max_query_call_graph_instructions: NumInstructions,
     ) -> Self {
         let routing_table = Arc::clone(&state.metadata.network_topology.routing_table);
         Self {
@@ -145,6 +163,10 @@ impl<'a> QueryContext<'a> {
             subnet_available_memory,
             max_canister_memory_size,
             max_instructions_per_message,
+            max_query_call_depth,
+            call_depths: BTreeMap::new(),
+            total_instructions_left: max_query_call_graph_instructions,
+            result_cacheable: true,
         }
     }
 
@@ -168,12 +190,18 @@ impl<'a> QueryContext<'a> {
         debug!(self.log, "Executing query for {}", canister_id);
         let old_canister = self.get_canister_from_state(&canister_id)?;
         let call_origin = CallOrigin::Query(query.source);
+        // Composite queries are expected to call other queries, so they are
+        // always executed as `Stateful` on any subnet.
+        let is_composite_query =
+            old_canister.exports_composite_query_method(query.method_name.clone());
         // EXC-500: Contain the usage of inter-canister query calls to the subnets
         // that currently use it until we decide on the future of this feature and
         // get a proper spec for it.
         let cross_canister_query_calls_enabled = self.own_subnet_type == SubnetType::System
             || self.own_subnet_type == SubnetType::VerifiedApplication;
-        let query_kind = if ENABLE_QUERY_OPTIMIZATION || !cross_canister_query_calls_enabled {
+        let query_kind = if is_composite_query {
+            NonReplicatedQueryKind::Stateful
+        } else if ENABLE_QUERY_OPTIMIZATION || !cross_canister_query_calls_enabled {
             NonReplicatedQueryKind::Pure
         } else {
             NonReplicatedQueryKind::Stateful
@@ -242,6 +270,10 @@ impl<'a> QueryContext<'a> {
                 )),
 
                 EnqueueRequestsResult::MessagesEnqueued => {
+                    // The result depends on the state of the called canisters
+                    // too, so it is not cached.
+                    self.result_cacheable = false;
+                    self.call_depths.insert(canister.canister_id(), 0);
                     self.canisters.insert(canister.canister_id(), canister);
                     self.run_loop(canister_id, metrics, measurement_scope)
                 }
@@ -249,6 +281,13 @@ impl<'a> QueryContext<'a> {
         }
     }
 
+    /// Returns true if the result of `run()` may be served from the query
+    /// cache, i.e. if it only depends on the state of the receiver and on the
+    /// query itself.
+    pub(super) fn is_result_cacheable(&self) -> bool {
+        self.result_cacheable
+    }
+
     // Keep processing the call graph till a result is achieved or no more
     // outstanding calls are left.
     fn run_loop<'b>(
@@ -260,6 +299,18 @@ impl<'a> QueryContext<'a> {
         let measurement_scope =
             MeasurementScope::nested(&metrics.query_spawned_calls, measurement_scope);
         loop {
+            let has_outstanding_calls =
+                self.outstanding_response.is_some() || !self.outstanding_requests.is_empty();
+            if has_outstanding_calls && self.total_instructions_left.get() == 0 {
+                return Err(UserError::new(
+                    ErrorCode::QueryCallGraphTotalInstructionLimitExceeded,
+                    format!(
+                        "Query call graph of canister {} exceeded the total instruction limit",
+                        starting_canister_id
+                    ),
+                ));
+            }
+
             if let Some(response) = self.outstanding_response.take() {
                 debug!(self.log, "Executing response for {}", response.originator);
                 // Any result returned by `handle_response` is a query context
@@ -404,29 +455,37 @@ impl<'a> QueryContext<'a> {
         measurement_scope: &MeasurementScope,
     ) -> (CanisterState, HypervisorResult<Option<WasmResult>>) {
         let call_context_id = self.new_call_context(&mut canister, call_origin);
-        let instruction_limit = self.max_instructions_per_message.min(
-            self.query_allocations_used
-                .write()
-                .unwrap()
-                .allocation_before_execution(&canister)
-                .into(),
-        );
+        let instruction_limit = self
+            .max_instructions_per_message
+            .min(self.total_instructions_left)
+            .min(
+                self.query_allocations_used
+                    .write()
+                    .unwrap()
+                    .allocation_before_execution(&canister)
+                    .into(),
+            );
         let execution_parameters = self.execution_parameters(&canister, instruction_limit);
-        let (canister, instructions_left, result) = self.hypervisor.execute_query(
-            QueryExecutionType::NonReplicated {
-                call_context_id,
-                routing_table: Arc::clone(&self.routing_table),
-                query_kind,
-            },
-            method_name,
-            method_payload,
-            source,
-            canister,
-            Some(self.data_certificate.clone()),
-            self.state.time(),
-            execution_parameters,
-        );
+        let (canister, instructions_left, result, reads_transient_state) =
+            self.hypervisor.execute_query_with_transient_state_reads(
+                QueryExecutionType::NonReplicated {
+                    call_context_id,
+                    routing_table: Arc::clone(&self.routing_table),
+                    query_kind,
+                },
+                method_name,
+                method_payload,
+                source,
+                canister,
+                Some(self.data_certificate.clone()),
+                self.state.time(),
+                execution_parameters,
+            );
+        if reads_transient_state {
+            self.result_cacheable = false;
+        }
         let instructions_executed = instruction_limit - instructions_left;
+        self.total_instructions_left -= instructions_executed;
         measurement_scope.add(instructions_executed, NumMessages::from(1));
         self.query_allocations_used
             .write()
@@ -480,13 +539,16 @@ impl<'a> QueryContext<'a> {
         subnet_records.insert(self.own_subnet_id, self.own_subnet_type);
         let subnet_records = Arc::new(subnet_records);
 
-        let instruction_limit = self.max_instructions_per_message.min(
-            self.query_allocations_used
-                .write()
-                .unwrap()
-                .allocation_before_execution(&canister)
-                .into(),
-        );
+        let instruction_limit = self
+            .max_instructions_per_message
+            .min(self.total_instructions_left)
+            .min(
+                self.query_allocations_used
+                    .write()
+                    .unwrap()
+                    .allocation_before_execution(&canister)
+                    .into(),
+            );
         let execution_parameters = self.execution_parameters(&canister, instruction_limit);
         let (canister, instructions_left, _heap_delta, execution_result) =
             self.hypervisor.execute_callback(
@@ -502,6 +564,7 @@ impl<'a> QueryContext<'a> {
                 execution_parameters,
             );
         let instructions_executed = instruction_limit - instructions_left;
+        self.total_instructions_left -= instructions_executed;
         measurement_scope.add(instructions_executed, NumMessages::from(1));
         self.query_allocations_used
             .write()
@@ -562,6 +625,23 @@ impl<'a> QueryContext<'a> {
             error!(self.log, "[EXC-BUG] The canister that we want to execute a request on should not already be loaded.");
         }
 
+        let call_depth = self
+            .call_depths
+            .get(&request.sender)
+            .map_or(1, |depth| depth + 1);
+        if call_depth > self.max_query_call_depth {
+            let err = UserError::new(
+                ErrorCode::QueryCallGraphTooDeep,
+                format!(
+                    "Canister {} exceeded the maximum depth of {} of the query call graph",
+                    request.sender, self.max_query_call_depth
+                ),
+            );
+            let payload = Payload::Reject(RejectContext::from(err));
+            self.outstanding_response = Some(generate_response(request, payload));
+            return None;
+        }
+
         let canister = match self.get_canister_from_state(&request.receiver) {
             Ok(canister) => canister,
             Err(err) => {
@@ -632,6 +712,7 @@ impl<'a> QueryContext<'a> {
                         // outgoing request(s). Save the canister for when the
                         // response(s) come back in.
                         EnqueueRequestsResult::MessagesEnqueued => {
+                            self.call_depths.insert(canister.canister_id(), call_depth);
                             self.canisters.insert(canister.canister_id(), canister);
                             None
                         }
@@ -6,6 +6,10 @@ use crate::{
 };
 use ic_base_types::NumSeconds;
 use ic_config::execution_environment::Config;
+use ic_ic00_types::{
+    CanisterIdRecord, FetchCanisterLogsResponse, LogVisibility, Method as Ic00Method, Payload,
+    IC_00,
+};
 use ic_interfaces::execution_environment::{
     ExecutionParameters, QueryHandler, SubnetAvailableMemory,
 };
@@ -23,9 +27,10 @@ use ic_test_utilities::{
     with_test_replica_logger,
 };
 use ic_types::{
-    ingress::WasmResult, messages::UserQuery, user_error::ErrorCode, ComputeAllocation,
+    ingress::WasmResult, messages::UserQuery, time::UNIX_EPOCH, user_error::ErrorCode,
+    ComputeAllocation, Height,
 };
-use ic_types::{CanisterId, Cycles, NumBytes, NumInstructions, SubnetId};
+use ic_types::{CanisterId, Cycles, NumBytes, NumInstructions, SubnetId, UserId};
 use maplit::btreemap;
 use std::{path::Path, sync::Arc};
 
@@ -34,7 +39,67 @@ const INSTRUCTION_LIMIT: NumInstructions = NumInstructions::new(1_000_000_000);
 const MEMORY_CAPACITY: NumBytes = NumBytes::new(1_000_000_000);
 const MAX_NUMBER_OF_CANISTERS: u64 = 0;
 
+// A canister exporting:
+// - a composite query `forward` that calls the `hello` query of the canister
+//   whose id is passed as the argument and replies with the response.
+// - a query `hello` that replies with "world".
+// - a query `loop` that never terminates.
+// - a query `time` that reads the time and replies with "world".
+const COMPOSITE_QUERY_WAT: &str = r#"(module
+              (import "ic0" "msg_arg_data_size" (func $msg_arg_data_size (result i32)))
+              (import "ic0" "msg_arg_data_copy"
+                (func $msg_arg_data_copy (param i32) (param i32) (param i32)))
+              (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32) (param i32)))
+              (import "ic0" "msg_reply" (func $msg_reply))
+              (import "ic0" "call_new"
+                (func $call_new
+                  (param i32 i32)
+                  (param $method_name_src i32)    (param $method_name_len i32)
+                  (param $reply_fun i32)          (param $reply_env i32)
+                  (param $reject_fun i32)         (param $reject_env i32)
+                ))
+              (import "ic0" "call_perform" (func $call_perform (result i32)))
+              (import "ic0" "time" (func $time (result i64)))
+              (func $on_reply (param i32)
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                (call $msg_reply_data_append (i32.const 100) (call $msg_arg_data_size))
+                (call $msg_reply))
+              (func $forward
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                (call $call_new
+                  (i32.const 100) (call $msg_arg_data_size)  ;; callee canister id
+                  (i32.const 0) (i32.const 5)                ;; refers to "hello" on the heap
+                  (i32.const 0) (i32.const 0)                ;; on_reply closure
+                  (i32.const 0) (i32.const 0)                ;; on_reject closure
+                )
+                (drop (call $call_perform)))
+              (func $hello
+                (call $msg_reply_data_append (i32.const 5) (i32.const 5)) ;; refers to "world"
+                (call $msg_reply))
+              (func $loop
+                (loop $loop (br $loop)))
+              (func $time_query
+                (drop (call $time))
+                (call $hello))
+              (table funcref (elem $on_reply))
+              (memory $memory 1)
+              (export "memory" (memory $memory))
+              (data (i32.const 0) "helloworld")
+              (export "canister_composite_query forward" (func $forward))
+              (export "canister_query hello" (func $hello))
+              (export "canister_query loop" (func $loop))
+              (export "canister_query time" (func $time_query))
+            )"#;
+
 fn with_setup<F>(subnet_type: SubnetType, f: F)
+where
+    F: FnOnce(InternalHttpQueryHandler, CanisterManager, ReplicatedState),
+{
+    with_config_setup(subnet_type, Config::default(), f)
+}
+
+fn with_config_setup<F>(subnet_type: SubnetType, config: Config, f: F)
 where
     F: FnOnce(InternalHttpQueryHandler, CanisterManager, ReplicatedState),
 {
@@ -93,7 +158,7 @@ where
             hypervisor,
             subnet_id,
             subnet_type,
-            Config::default(),
+            config,
             &metrics_registry,
             INSTRUCTION_LIMIT,
         );
@@ -104,6 +169,25 @@ where
 fn universal_canister(
     canister_manager: &CanisterManager,
     state: &mut ReplicatedState,
+) -> CanisterId {
+    install_canister(canister_manager, state, UNIVERSAL_CANISTER_WASM.to_vec())
+}
+
+fn composite_query_canister(
+    canister_manager: &CanisterManager,
+    state: &mut ReplicatedState,
+) -> CanisterId {
+    install_canister(
+        canister_manager,
+        state,
+        wabt::wat2wasm(COMPOSITE_QUERY_WAT).unwrap(),
+    )
+}
+
+fn install_canister(
+    canister_manager: &CanisterManager,
+    state: &mut ReplicatedState,
+    wasm_module: Vec<u8>,
 ) -> CanisterId {
     let sender = canister_test_id(1).get();
     let sender_subnet_id = subnet_test_id(1);
@@ -124,7 +208,7 @@ fn universal_canister(
             InstallCodeContextBuilder::default()
                 .sender(sender)
                 .canister_id(canister_id)
-                .wasm_module(UNIVERSAL_CANISTER_WASM.to_vec())
+                .wasm_module(wasm_module)
                 .build(),
             state,
             ExecutionParameters {
@@ -421,3 +505,302 @@ fn query_compilied_once() {
         },
     );
 }
+
+#[test]
+fn composite_query_calls_enabled_for_application_subnet() {
+    with_setup(
+        SubnetType::Application,
+        |query_handler, canister_manager, mut state| {
+            let canister_a = composite_query_canister(&canister_manager, &mut state);
+            let canister_b = composite_query_canister(&canister_manager, &mut state);
+            let output = query_handler.query(
+                UserQuery {
+                    source: user_test_id(2),
+                    receiver: canister_a,
+                    method_name: "forward".to_string(),
+                    method_payload: canister_b.get().into_vec(),
+                    ingress_expiry: 0,
+                    nonce: None,
+                },
+                Arc::new(state),
+                vec![],
+            );
+            assert_eq!(output, Ok(WasmResult::Reply(b"world".to_vec())));
+        },
+    );
+}
+
+#[test]
+fn query_call_graph_depth_is_limited() {
+    let config = Config {
+        max_query_call_depth: 1,
+        ..Config::default()
+    };
+    with_config_setup(
+        SubnetType::System,
+        config,
+        |query_handler, canister_manager, mut state| {
+            // In this test we have three canisters A, B and C.
+            // Canister A calls B which calls C. The call from B to C exceeds
+            // the maximum call depth and is rejected.
+            let canister_a = universal_canister(&canister_manager, &mut state);
+            let canister_b = universal_canister(&canister_manager, &mut state);
+            let canister_c = universal_canister(&canister_manager, &mut state);
+            let output = query_handler.query(
+                UserQuery {
+                    source: user_test_id(2),
+                    receiver: canister_a,
+                    method_name: "query".to_string(),
+                    method_payload: wasm()
+                        .inter_query(
+                            canister_b,
+                            call_args()
+                                .other_side(
+                                    wasm()
+                                        .inter_query(
+                                            canister_c,
+                                            call_args().on_reject(wasm().reject_message().reject()),
+                                        )
+                                        .build(),
+                                )
+                                .on_reject(wasm().reject_message().reject()),
+                        )
+                        .build(),
+                    ingress_expiry: 0,
+                    nonce: None,
+                },
+                Arc::new(state),
+                vec![],
+            );
+            match output {
+                Ok(WasmResult::Reject(message)) => assert!(
+                    message.contains("maximum depth"),
+                    "Unexpected reject message: {}",
+                    message
+                ),
+                _ => unreachable!("Unexpected output: {:?}", output),
+            }
+        },
+    );
+}
+
+#[test]
+fn query_call_graph_total_instructions_are_limited() {
+    let config = Config {
+        max_query_call_graph_instructions: INSTRUCTION_LIMIT,
+        ..Config::default()
+    };
+    with_config_setup(
+        SubnetType::System,
+        config,
+        |query_handler, canister_manager, mut state| {
+            // Canister A calls the never-ending query of canister B, which
+            // uses up all instructions left for the call graph.
+            let canister_a = universal_canister(&canister_manager, &mut state);
+            let canister_b = composite_query_canister(&canister_manager, &mut state);
+            let output = query_handler.query(
+                UserQuery {
+                    source: user_test_id(2),
+                    receiver: canister_a,
+                    method_name: "query".to_string(),
+                    method_payload: wasm().call_simple(canister_b, "loop", call_args()).build(),
+                    ingress_expiry: 0,
+                    nonce: None,
+                },
+                Arc::new(state),
+                vec![],
+            );
+            match output {
+                Ok(_) => unreachable!("The query was expected to fail, but it succeeded."),
+                Err(err) => assert_eq!(
+                    err.code(),
+                    ErrorCode::QueryCallGraphTotalInstructionLimitExceeded
+                ),
+            }
+        },
+    );
+}
+
+#[test]
+fn fetch_canister_logs_respects_log_visibility() {
+    with_setup(
+        SubnetType::Application,
+        |query_handler, canister_manager, mut state| {
+            let canister_id = universal_canister(&canister_manager, &mut state);
+            let controller = UserId::from(canister_test_id(1).get());
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .system_state
+                .canister_log
+                .add_record(UNIX_EPOCH, b"hello".to_vec());
+            let fetch_logs = |source: UserId| UserQuery {
+                source,
+                receiver: IC_00,
+                method_name: Ic00Method::FetchCanisterLogs.to_string(),
+                method_payload: CanisterIdRecord::from(canister_id).encode(),
+                ingress_expiry: 0,
+                nonce: None,
+            };
+
+            let output = query_handler
+                .query(fetch_logs(controller), Arc::new(state.clone()), vec![])
+                .unwrap();
+            let response = match output {
+                WasmResult::Reply(bytes) => FetchCanisterLogsResponse::decode(&bytes).unwrap(),
+                WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+            };
+            assert_eq!(response.canister_log_records.len(), 1);
+            assert_eq!(response.canister_log_records[0].content, b"hello".to_vec());
+
+            // Only controllers can fetch the logs by default.
+            let err = query_handler
+                .query(fetch_logs(user_test_id(2)), Arc::new(state.clone()), vec![])
+                .unwrap_err();
+            assert_eq!(err.code(), ErrorCode::CanisterInvalidController);
+
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .system_state
+                .log_visibility = LogVisibility::Public;
+            assert!(query_handler
+                .query(fetch_logs(user_test_id(2)), Arc::new(state), vec![])
+                .is_ok());
+        },
+    );
+}
+
+fn user_query(receiver: CanisterId, method_name: &str) -> UserQuery {
+    UserQuery {
+        source: user_test_id(2),
+        receiver,
+        method_name: method_name.to_string(),
+        method_payload: vec![],
+        ingress_expiry: 0,
+        nonce: None,
+    }
+}
+
+#[test]
+fn query_result_is_cached_until_certified_height_advances() {
+    with_setup(
+        SubnetType::Application,
+        |query_handler, canister_manager, mut state| {
+            let canister_id = composite_query_canister(&canister_manager, &mut state);
+            let state = Arc::new(state);
+            let metrics = &query_handler.query_cache.metrics;
+            for _ in 0..2 {
+                let output = query_handler.query_with_cache(
+                    user_query(canister_id, "hello"),
+                    Arc::clone(&state),
+                    vec![],
+                    Height::from(1),
+                );
+                assert_eq!(output, Ok(WasmResult::Reply(b"world".to_vec())));
+            }
+            assert_eq!(metrics.misses.get(), 1);
+            assert_eq!(metrics.hits.get(), 1);
+
+            let output = query_handler.query_with_cache(
+                user_query(canister_id, "hello"),
+                state,
+                vec![],
+                Height::from(2),
+            );
+            assert_eq!(output, Ok(WasmResult::Reply(b"world".to_vec())));
+            assert_eq!(metrics.misses.get(), 2);
+            assert_eq!(metrics.hits.get(), 1);
+        },
+    );
+}
+
+#[test]
+fn query_result_is_not_served_after_canister_version_changes() {
+    with_setup(
+        SubnetType::Application,
+        |query_handler, canister_manager, mut state| {
+            let canister_id = composite_query_canister(&canister_manager, &mut state);
+            let metrics = &query_handler.query_cache.metrics;
+            query_handler
+                .query_with_cache(
+                    user_query(canister_id, "hello"),
+                    Arc::new(state.clone()),
+                    vec![],
+                    Height::from(1),
+                )
+                .unwrap();
+
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .system_state
+                .canister_version += 1;
+            query_handler
+                .query_with_cache(
+                    user_query(canister_id, "hello"),
+                    Arc::new(state),
+                    vec![],
+                    Height::from(1),
+                )
+                .unwrap();
+            assert_eq!(metrics.misses.get(), 2);
+            assert_eq!(metrics.hits.get(), 0);
+        },
+    );
+}
+
+#[test]
+fn query_reading_time_is_not_cached() {
+    with_setup(
+        SubnetType::Application,
+        |query_handler, canister_manager, mut state| {
+            let canister_id = composite_query_canister(&canister_manager, &mut state);
+            let state = Arc::new(state);
+            for _ in 0..2 {
+                let output = query_handler.query_with_cache(
+                    user_query(canister_id, "time"),
+                    Arc::clone(&state),
+                    vec![],
+                    Height::from(1),
+                );
+                assert_eq!(output, Ok(WasmResult::Reply(b"world".to_vec())));
+            }
+            assert_eq!(query_handler.query_cache.metrics.misses.get(), 2);
+            assert_eq!(query_handler.query_cache.metrics.hits.get(), 0);
+        },
+    );
+}
+
+#[test]
+fn least_recently_used_query_result_is_evicted() {
+    let config = Config {
+        query_cache_capacity: NumBytes::from(400),
+        ..Config::default()
+    };
+    with_config_setup(
+        SubnetType::Application,
+        config,
+        |query_handler, canister_manager, mut state| {
+            let canister_id = universal_canister(&canister_manager, &mut state);
+            let state = Arc::new(state);
+            let query = |reply: &[u8]| UserQuery {
+                method_payload: wasm().reply_data(reply).build(),
+                ..user_query(canister_id, "query")
+            };
+            for reply in [b"a", b"b", b"c"].iter() {
+                query_handler
+                    .query_with_cache(query(*reply), Arc::clone(&state), vec![], Height::from(1))
+                    .unwrap();
+            }
+            let metrics = &query_handler.query_cache.metrics;
+            assert!(metrics.evictions.get() > 0);
+
+            // The most recent result is still cached.
+            query_handler
+                .query_with_cache(query(b"c"), state, vec![], Height::from(1))
+                .unwrap();
+            assert_eq!(metrics.hits.get(), 1);
+        },
+    );
+}
@@ -20,7 +20,7 @@ use ic_replicated_state::{
     ReplicatedState,
 };
 use ic_types::{
-    ic00::{EmptyBlob, InstallCodeArgs, Payload as _, IC_00},
+    ic00::{EmptyBlob, InstallChunkedCodeArgs, InstallCodeArgs, Payload as _, IC_00},
     ingress::{IngressStatus, WasmResult},
     messages::{Ingress, MessageId, Payload, Response, StopCanisterContext},
     user_error::{ErrorCode, UserError},
@@ -90,11 +90,11 @@ pub(crate) struct SchedulerImpl {
     thread_pool: RefCell<scoped_threadpool::Pool>,
 }
 
-// Indicates whether the heartbeat method of a canister should be run on not and
-// how errors should be tracked.
+// Indicates whether the heartbeat and global timer methods of a canister should
+// be run on not and how errors should be tracked.
 //
-// An execution round consists of multiple iterations. The heartbeat should
-// run only in the first iteration.
+// An execution round consists of multiple iterations. The heartbeat and the
+// global timer should run only in the first iteration.
 // Additionally, all errors should be tracked on system subnets, but on other
 // subnets only system errors should be tracked.
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
@@ -222,9 +222,11 @@ fn filter_idle_canisters(
     all_canister_states: &BTreeMap<CanisterId, CanisterState>,
     heartbeat_handling: HeartbeatHandling,
     heap_delta_rate_limit: NumBytes,
+    time: Time,
 ) -> (Vec<CanisterId>, BTreeSet<CanisterId>) {
     let mut rate_limited_canisters = BTreeSet::new();
-    // Consider only canisters with some input messages for execution.
+    // Consider only canisters with some input messages, a heartbeat or a due
+    // global timer for execution.
     let runnable_canisters = ordered_canister_ids
         .iter()
         .filter(|canister_id| {
@@ -235,7 +237,7 @@ fn filter_idle_canisters(
             }
             (canister.has_input()
                 || (heartbeat_handling.should_execute_heartbeat()
-                    && canister.exports_heartbeat_method()))
+                    && (canister.exports_heartbeat_method() || canister.is_global_timer_due(time))))
                 && is_under_limit
         })
         .cloned()
@@ -351,6 +353,7 @@ impl SchedulerImpl {
                     &canisters,
                     heartbeat_handling,
                     self.config.heap_delta_rate_limit,
+                    state.time(),
                 );
             rate_limited_canister_ids.extend(new_rate_limited_canister_ids);
 
@@ -681,6 +684,18 @@ impl SchedulerImpl {
         state.put_canister_states(canisters);
     }
 
+    // Completes the best-effort calls whose deadline has expired with a
+    // `SYS_UNKNOWN` reject.
+    fn time_out_callbacks(&self, state: &mut ReplicatedState) {
+        let current_time = state.time();
+        for canister in state.canisters_iter_mut() {
+            let timed_out = canister.system_state.time_out_callbacks(current_time);
+            self.metrics
+                .timed_out_callbacks_count
+                .inc_by(timed_out as u64);
+        }
+    }
+
     // Charge canisters for their resource allocation and usage. Canisters
     // that did not manage to pay are uninstalled.
     fn charge_canisters_for_resource_allocation_and_usage(&self, state: &mut ReplicatedState) {
@@ -701,6 +716,7 @@ impl SchedulerImpl {
 
         let state_path = state.root.clone();
         let state_time = state.time();
+        let snapshots_memory_usage = state.canister_snapshots.memory_taken_per_canister();
         let mut all_rejects = Vec::new();
         for canister in state.canisters_iter_mut() {
             if self
@@ -708,6 +724,10 @@ impl SchedulerImpl {
                 .charge_canister_for_resource_allocation_and_usage(
                     &self.log,
                     canister,
+                    snapshots_memory_usage
+                        .get(&canister.canister_id())
+                        .cloned()
+                        .unwrap_or_else(|| NumBytes::from(0)),
                     duration_since_last_charge,
                 )
                 .is_err()
@@ -835,6 +855,7 @@ impl Scheduler for SchedulerImpl {
             {
                 let _timer = self.metrics.round_preparation_ingress.start_timer();
                 self.purge_expired_ingress_messages(&mut state);
+                self.time_out_callbacks(&mut state);
             }
 
             // See documentation around definition of `heap_delta_estimate` for an
@@ -1045,6 +1066,7 @@ struct ExecutionThreadResult {
 
 // Executes the given canisters one by one. For each canister it
 // - runs the heartbeat handler of the canister if needed,
+// - runs the global timer handler of the canister if the timer is due,
 // - executes all messages of the canister.
 // The execution stops if `total_instruction_limit` is reached
 // or all canisters are processed.
@@ -1143,6 +1165,59 @@ fn execute_canisters_on_thread(
                 canister.scheduler_state.heap_delta_debit += heap_delta;
                 drop(timer);
             }
+
+            // Run the global timer if its deadline has passed.
+            if canister.is_global_timer_due(time) {
+                let measurement_scope = MeasurementScope::nested(
+                    &metrics.round_inner_iteration_thread_global_timer,
+                    &measurement_scope,
+                );
+                let timer = metrics.msg_execution_duration.start_timer();
+                let (new_canister, num_instructions_left, result) = exec_env
+                    .execute_canister_global_timer(
+                        canister,
+                        canister_execution_limits.instruction_limit_per_message,
+                        Arc::clone(&routing_table),
+                        Arc::clone(&subnet_records),
+                        time,
+                        subnet_available_memory.clone(),
+                    );
+                let heap_delta = match result {
+                    Ok(heap_delta) => heap_delta,
+                    Err(err) => {
+                        if only_track_system_errors || err.is_system_error() {
+                            let log_count = HEARTBEAT_ERROR_COUNT.fetch_add(1, Ordering::SeqCst);
+                            if log_count % LOG_ONE_HEARTBEAT_OUT_OF == 0 {
+                                info!(
+                                    logger,
+                                    "Error executing global timer on canister {} with failure `{}`",
+                                    new_canister.canister_id(),
+                                    err;
+                                    messaging.canister_id => new_canister.canister_id().to_string(),
+                                );
+                            }
+                            metrics.execution_round_failed_global_timer_executions.inc();
+                        }
+                        NumBytes::from(0)
+                    }
+                };
+                let instructions_consumed =
+                    canister_execution_limits.instruction_limit_per_message - num_instructions_left;
+                measurement_scope.add(instructions_consumed, NumMessages::from(1));
+                observe_instructions_consumed_per_message(
+                    &logger,
+                    &metrics,
+                    &new_canister,
+                    instructions_consumed,
+                    canister_execution_limits.instruction_limit_per_message,
+                );
+                canister = new_canister;
+                total_instructions_executed += instructions_consumed;
+                total_messages_executed.inc_assign();
+                total_heap_delta += heap_delta;
+                canister.scheduler_state.heap_delta_debit += heap_delta;
+                drop(timer);
+            }
         }
 
         // Process all messages of the canister until
@@ -1332,6 +1407,7 @@ fn get_instructions_limit_for_subnet_message(
     match Ic00Method::from_str(method_name) {
         Ok(method) => match method {
             CanisterStatus
+            | CanisterInfo
             | CreateCanister
             | DeleteCanister
             | DepositCycles
@@ -1339,12 +1415,23 @@ fn get_instructions_limit_for_subnet_message(
             | SetController
             | SetupInitialDKG
             | SignWithECDSA
-            | GetMockECDSAPublicKey
-            | SignWithMockECDSA
+            | HttpRequest
+            | BitcoinGetBalance
+            | BitcoinGetUtxos
+            | BitcoinSendTransaction
+            | ECDSAPublicKey
             | StartCanister
             | StopCanister
             | UninstallCode
             | UpdateSettings
+            | TakeCanisterSnapshot
+            | LoadCanisterSnapshot
+            | ListCanisterSnapshots
+            | DeleteCanisterSnapshot
+            | UploadChunk
+            | ClearChunkStore
+            | StoredChunks
+            | FetchCanisterLogs
             | ProvisionalCreateCanisterWithCycles
             | ProvisionalTopUpCanister => config.max_instructions_per_message,
             InstallCode => match InstallCodeArgs::decode(payload) {
@@ -1354,6 +1441,12 @@ fn get_instructions_limit_for_subnet_message(
                     Ok(_) => config.max_instructions_per_install_code,
                 },
             },
+            // The module is only assembled during execution, so the payload
+            // cannot be validated any further here.
+            InstallChunkedCode => match InstallChunkedCodeArgs::decode(payload) {
+                Err(_) => config.max_instructions_per_message,
+                Ok(_) => config.max_instructions_per_install_code,
+            },
         },
         Err(_) => config.max_instructions_per_message,
     }
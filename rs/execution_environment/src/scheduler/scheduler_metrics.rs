@@ -17,6 +17,7 @@ pub(super) struct SchedulerMetrics {
     pub(super) instructions_consumed_per_round: Histogram,
     pub(super) executable_canisters_per_round: Histogram,
     pub(super) expired_ingress_messages_count: IntCounter,
+    pub(super) timed_out_callbacks_count: IntCounter,
     pub(super) ingress_history_length: IntGauge,
     pub(super) msg_execution_duration: Histogram,
     pub(super) registered_canisters: IntGaugeVec,
@@ -45,6 +46,7 @@ pub(super) struct SchedulerMetrics {
     pub(super) round_inner_iteration_prep: Histogram,
     pub(super) round_inner_iteration_thread: ScopedMetrics,
     pub(super) round_inner_iteration_thread_heartbeat: ScopedMetrics,
+    pub(super) round_inner_iteration_thread_global_timer: ScopedMetrics,
     pub(super) round_inner_iteration_thread_message: ScopedMetrics,
     pub(super) round_inner_iteration_fin: Histogram,
     pub(super) round_inner_iteration_fin_induct: Histogram,
@@ -53,6 +55,7 @@ pub(super) struct SchedulerMetrics {
     pub(super) round_finalization_ingress: Histogram,
     pub(super) round_finalization_charge: Histogram,
     pub(super) execution_round_failed_heartbeat_executions: IntCounter,
+    pub(super) execution_round_failed_global_timer_executions: IntCounter,
     pub(super) canister_heap_delta_debits: Histogram,
     pub(super) heap_delta_rate_limited_canisters_per_round: Histogram,
 }
@@ -102,6 +105,11 @@ impl SchedulerMetrics {
                 "Total number of ingress messages that expired before \
                       reaching a terminal state.",
             ),
+            timed_out_callbacks_count: metrics_registry.int_counter(
+                "scheduler_timed_out_callbacks_count",
+                "Total number of best-effort calls completed with a SYS_UNKNOWN \
+                      reject because their deadline expired.",
+            ),
             ingress_history_length: metrics_registry.int_gauge(
                 "replicated_state_ingress_history_length",
                 "Total number of entries kept in the ingress history.",
@@ -342,6 +350,26 @@ impl SchedulerMetrics {
                     metrics_registry,
                 ),
             },
+            round_inner_iteration_thread_global_timer: ScopedMetrics {
+                duration: duration_histogram(
+                    "execution_round_inner_iteration_thread_global_timer_duration_seconds",
+                    "The duration of executing a global timer in a thread \
+                          spawned by an iteration of an inner round",
+                    metrics_registry,
+                ),
+                instructions: instructions_histogram(
+                    "execution_round_inner_iteration_thread_global_timer_instructions",
+                    "The number of instructions executed in a global timer \
+                          in a thread spawned by an iteration of an inner round",
+                    metrics_registry,
+                ),
+                messages: messages_histogram(
+                    "execution_round_inner_iteration_thread_global_timer_messages",
+                    "The number of messages executed in a global timer in a \
+                          thread spawned by an iteration of an inner round",
+                    metrics_registry,
+                ),
+            },
             round_inner_iteration_thread_message: ScopedMetrics {
                 duration: duration_histogram(
                     "execution_round_inner_iteration_thread_message_duration_seconds",
@@ -400,6 +428,10 @@ impl SchedulerMetrics {
                 "execution_round_failed_heartbeat_executions",
                 "Total number of heartbeat executions that completed in error",
             ),
+            execution_round_failed_global_timer_executions: metrics_registry.int_counter(
+                "execution_round_failed_global_timer_executions",
+                "Total number of global timer executions that completed in error",
+            ),
             canister_heap_delta_debits: metrics_registry.histogram(
                 "scheduler_canister_heap_delta_debits",
                 "The heap delta debit of a canister at the end of the round, before \
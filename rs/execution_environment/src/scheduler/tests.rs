@@ -1820,6 +1820,97 @@ fn execute_heartbeat_before_messages() {
     );
 }
 
+#[test]
+fn execute_global_timer_once_when_due() {
+    // This test sets up two canisters with a global timer method. The timer of
+    // the first canister is due, the timer of the second one is not. Only the
+    // first canister is expected to run its global timer and only once over
+    // two rounds, because the timer is deactivated when it fires.
+    let scheduler_test_fixture = SchedulerTestFixture {
+        scheduler_config: SchedulerConfig {
+            scheduler_cores: 1,
+            max_instructions_per_round: NumInstructions::from(1000),
+            max_instructions_per_message: NumInstructions::from(100),
+            ..SchedulerConfig::application_subnet()
+        },
+        metrics_registry: MetricsRegistry::new(),
+        canister_num: 2,
+        message_num_per_canister: 0,
+    };
+    let mut exec_env = default_exec_env_mock(
+        &scheduler_test_fixture,
+        0,
+        NumInstructions::from(1),
+        NumBytes::new(0),
+    );
+    exec_env
+        .expect_execute_canister_global_timer()
+        .times(1)
+        .returning(move |mut canister, instruction_limit, _, _, _, _| {
+            assert_eq!(canister.canister_id(), canister_test_id(0));
+            canister.system_state.global_timer = None;
+            (
+                canister,
+                instruction_limit - NumInstructions::from(1),
+                Ok(NumBytes::new(1)),
+            )
+        });
+    let exec_env = Arc::new(exec_env);
+
+    let ingress_history_writer = default_ingress_history_writer_mock(0);
+    let ingress_history_writer = Arc::new(ingress_history_writer);
+    scheduler_test(
+        &scheduler_test_fixture,
+        |scheduler| {
+            let mut state = get_initial_state(
+                scheduler_test_fixture.canister_num,
+                scheduler_test_fixture.message_num_per_canister,
+            );
+            let now = state.time();
+            for canister in state.canisters_iter_mut() {
+                if let Some(ref mut execution_state) = canister.execution_state {
+                    execution_state.exports = ExportedFunctions::new(
+                        [WasmMethod::System(SystemMethod::CanisterGlobalTimer)]
+                            .iter()
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                canister.system_state.global_timer =
+                    if canister.canister_id() == canister_test_id(0) {
+                        Some(now)
+                    } else {
+                        Some(now + Duration::from_secs(1))
+                    };
+            }
+            let state = scheduler.execute_round(
+                state,
+                Randomness::from([0; 32]),
+                ExecutionRound::from(1),
+                ProvisionalWhitelist::Set(BTreeSet::new()),
+                MAX_NUMBER_OF_CANISTERS,
+            );
+            let state = scheduler.execute_round(
+                state,
+                Randomness::from([0; 32]),
+                ExecutionRound::from(2),
+                ProvisionalWhitelist::Set(BTreeSet::new()),
+                MAX_NUMBER_OF_CANISTERS,
+            );
+            assert_eq!(
+                state
+                    .canister_state(&canister_test_id(1))
+                    .unwrap()
+                    .system_state
+                    .global_timer,
+                Some(now + Duration::from_secs(1))
+            );
+        },
+        ingress_history_writer,
+        exec_env,
+    );
+}
+
 #[test]
 fn execute_multiple_heartbeats() {
     // This tests multiple canisters with heartbeat methods running over multiple
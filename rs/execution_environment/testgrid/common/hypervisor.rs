@@ -33,7 +33,7 @@ use ic_types::{
     ingress::WasmResult,
     messages::{CallbackId, Payload, RejectContext, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
     methods::{Callback, FuncRef, SystemMethod, WasmClosure, WasmMethod},
-    user_error::RejectCode,
+    user_error::{ErrorCode, RejectCode},
     CanisterId, ComputeAllocation, Cycles, MemoryAllocation, NumBytes, NumInstructions,
     PrincipalId, SubnetId, Time, UserId,
 };
@@ -2049,6 +2049,52 @@ fn test_execute_query_msg_caller() {
     });
 }
 
+#[test]
+fn composite_query_cannot_be_executed_in_replicated_mode() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let wasm_binary = wabt::wat2wasm(
+            r#"(module
+                  (import "ic0" "msg_reply" (func $msg_reply))
+                  (func $composite (call $msg_reply))
+                  (export "canister_composite_query composite" (func $composite))
+                  (memory $memory 1)
+                  (export "memory" (memory $memory))
+                )"#,
+        )
+        .unwrap();
+        let canister_id = canister_test_id(42);
+        let execution_state = hypervisor
+            .create_execution_state(wasm_binary, tmp_path, canister_id)
+            .unwrap();
+        let canister = canister_from_exec_state(execution_state, canister_id);
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (canister, num_instructions_left, result) = hypervisor.execute_query(
+            QueryExecutionType::Replicated,
+            "composite",
+            EMPTY_PAYLOAD.as_slice(),
+            test_caller(),
+            canister,
+            None,
+            mock_time(),
+            execution_parameters,
+        );
+        assert_eq!(
+            result,
+            Err(HypervisorError::CompositeQueryCalledInReplicatedMode(
+                "composite".to_string()
+            ))
+        );
+        assert_eq!(num_instructions_left, MAX_NUM_INSTRUCTIONS);
+        assert_eq!(
+            result
+                .unwrap_err()
+                .into_user_error(&canister.canister_id())
+                .code(),
+            ErrorCode::CompositeQueryCalledInReplicatedMode
+        );
+    });
+}
+
 #[test]
 // Tests that ic0_msg_arg_data_copy cannot be accessed in a reject callback
 fn sys_api_call_arg_data_copy_fail() {
@@ -2734,6 +2780,89 @@ fn test_call_add_cycles_no_effect_when_perform_not_called() {
     });
 }
 
+#[test]
+fn test_performance_counter() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let (_, _, action, _) = execute_update(
+            &hypervisor,
+            r#"(module
+                  (import "ic0" "msg_reply_data_append"
+                            (func $msg_reply_data_append (param i32) (param i32)))
+                  (import "ic0" "msg_reply" (func $msg_reply))
+                  (import "ic0" "performance_counter"
+                            (func $performance_counter (param i32) (result i64)))
+
+                  (func $test
+                        (local $i i32)
+                        (i64.store (i32.const 0) (call $performance_counter (i32.const 0)))
+                        ;; burn some instructions between the two readings
+                        (loop $loop
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br_if $loop (i32.lt_u (local.get $i) (i32.const 1000)))
+                        )
+                        (i64.store (i32.const 8) (call $performance_counter (i32.const 0)))
+                        (call $msg_reply_data_append (i32.const 0) (i32.const 16))
+                        (call $msg_reply)
+                  )
+
+                  (export "canister_update test" (func $test))
+                  (memory $memory 1)
+                  (export "memory" (memory $memory))
+                )"#,
+            "test",
+            EMPTY_PAYLOAD,
+            tmp_path,
+        );
+
+        match action {
+            CallContextAction::Reply { payload, .. } => {
+                let first = u64::from_le_bytes(<[u8; 8]>::try_from(&payload[0..8]).unwrap());
+                let second = u64::from_le_bytes(<[u8; 8]>::try_from(&payload[8..16]).unwrap());
+                assert!(first > 0);
+                assert!(second > first + 1000);
+            }
+            _ => panic!("Unexpected action: {:?}", action),
+        }
+    });
+}
+
+#[test]
+fn test_canister_version() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let (_, _, action, _) = execute_update(
+            &hypervisor,
+            r#"(module
+                  (import "ic0" "msg_reply_data_append"
+                            (func $msg_reply_data_append (param i32) (param i32)))
+                  (import "ic0" "msg_reply" (func $msg_reply))
+                  (import "ic0" "canister_version"
+                            (func $canister_version (result i64)))
+
+                  (func $test
+                        (i64.store (i32.const 0) (call $canister_version))
+                        (call $msg_reply_data_append (i32.const 0) (i32.const 8))
+                        (call $msg_reply)
+                  )
+
+                  (export "canister_update test" (func $test))
+                  (memory $memory 1)
+                  (export "memory" (memory $memory))
+                )"#,
+            "test",
+            EMPTY_PAYLOAD,
+            tmp_path,
+        );
+
+        assert_eq!(
+            action,
+            CallContextAction::Reply {
+                payload: 0u64.to_le_bytes().to_vec(),
+                refund: Cycles::from(0),
+            }
+        );
+    });
+}
+
 const MINT_CYCLES: &str = r#"(module
                   (import "ic0" "msg_reply_data_append"
                             (func $msg_reply_data_append (param i32) (param i32)))
@@ -3172,6 +3301,192 @@ fn sys_api_call_update_available_memory_2() {
     });
 }
 
+// Creates a canister from `wast` whose Wasm memory limit is set to
+// `wasm_memory_limit` Wasm pages.
+fn canister_with_wasm_memory_limit(
+    hypervisor: &Hypervisor,
+    wast: &str,
+    wasm_memory_limit: NumWasmPages,
+    canister_root: std::path::PathBuf,
+) -> CanisterState {
+    let canister_id = canister_test_id(42);
+    let wasm_binary = wabt::wat2wasm(wast).unwrap();
+    let execution_state = hypervisor
+        .create_execution_state(wasm_binary, canister_root, canister_id)
+        .unwrap();
+    let mut canister = canister_from_exec_state(execution_state, canister_id);
+    canister.system_state.wasm_memory_limit =
+        Some(ic_replicated_state::num_bytes_try_from(wasm_memory_limit).unwrap());
+    canister
+}
+
+const GROW_MEMORY_WAT: &str = r#"
+    (module
+      (func $grow
+        (drop (memory.grow (i32.const 2)))
+      )
+      (memory (export "memory") 1 20)
+      (export "canister_update test" (func $grow))
+      (export "canister_pre_upgrade" (func $grow)))"#;
+
+#[test]
+fn growing_memory_above_wasm_memory_limit_in_update_traps() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let canister = canister_with_wasm_memory_limit(
+            &hypervisor,
+            GROW_MEMORY_WAT,
+            NumWasmPages::from(2),
+            tmp_path,
+        );
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (_, _, routing_table, subnet_records) = setup();
+        let (canister, _, action, heap_delta) = hypervisor.execute_update(
+            canister,
+            RequestOrIngress::Ingress(IngressBuilder::new().method_name("test").build()),
+            mock_time(),
+            routing_table,
+            subnet_records,
+            execution_parameters,
+        );
+        match action {
+            CallContextAction::Fail { error, .. } => {
+                assert_eq!(
+                    error,
+                    HypervisorError::WasmMemoryLimitExceeded {
+                        bytes: ic_replicated_state::num_bytes_try_from(NumWasmPages::from(3))
+                            .unwrap(),
+                        limit: ic_replicated_state::num_bytes_try_from(NumWasmPages::from(2))
+                            .unwrap(),
+                    }
+                );
+                assert_eq!(
+                    error.into_user_error(&canister.canister_id()).code(),
+                    ErrorCode::CanisterWasmMemoryLimitExceeded
+                );
+            }
+            action => panic!("Unexpected action {:?}", action),
+        }
+        assert_eq!(heap_delta, NumBytes::from(0));
+        assert_eq!(
+            canister.execution_state.unwrap().wasm_memory.size,
+            NumWasmPages::from(1)
+        );
+    });
+}
+
+#[test]
+fn growing_memory_up_to_wasm_memory_limit_in_update_succeeds() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let canister = canister_with_wasm_memory_limit(
+            &hypervisor,
+            GROW_MEMORY_WAT,
+            NumWasmPages::from(3),
+            tmp_path,
+        );
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (_, _, routing_table, subnet_records) = setup();
+        let (canister, _, action, _) = hypervisor.execute_update(
+            canister,
+            RequestOrIngress::Ingress(IngressBuilder::new().method_name("test").build()),
+            mock_time(),
+            routing_table,
+            subnet_records,
+            execution_parameters,
+        );
+        assert_eq!(
+            action,
+            CallContextAction::NoResponse {
+                refund: Cycles::from(0),
+            }
+        );
+        assert_eq!(
+            canister.execution_state.unwrap().wasm_memory.size,
+            NumWasmPages::from(3)
+        );
+    });
+}
+
+#[test]
+fn growing_memory_above_wasm_memory_limit_in_pre_upgrade_succeeds() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let canister = canister_with_wasm_memory_limit(
+            &hypervisor,
+            GROW_MEMORY_WAT,
+            NumWasmPages::from(2),
+            tmp_path,
+        );
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (canister, _, result) = hypervisor.execute_canister_pre_upgrade(
+            canister,
+            test_caller(),
+            mock_time(),
+            execution_parameters,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            canister.execution_state.unwrap().wasm_memory.size,
+            NumWasmPages::from(3)
+        );
+    });
+}
+
+#[test]
+fn writing_to_memory_above_wasm_memory_limit_traps() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let canister = canister_with_wasm_memory_limit(
+            &hypervisor,
+            r#"
+            (module
+              (func (export "canister_update write")
+                (i32.store (i32.const 0) (i32.const 42))
+              )
+              (func (export "canister_update read")
+                (drop (i32.load (i32.const 0)))
+              )
+              (memory (export "memory") 2))"#,
+            NumWasmPages::from(1),
+            tmp_path,
+        );
+        let (_, _, routing_table, subnet_records) = setup();
+
+        // Reading does not dirty any pages, so it succeeds.
+        let read_execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (canister, _, action, _) = hypervisor.execute_update(
+            canister,
+            RequestOrIngress::Ingress(IngressBuilder::new().method_name("read").build()),
+            mock_time(),
+            Arc::clone(&routing_table),
+            Arc::clone(&subnet_records),
+            read_execution_parameters,
+        );
+        assert_eq!(
+            action,
+            CallContextAction::NoResponse {
+                refund: Cycles::from(0),
+            }
+        );
+
+        // Writing does, so it traps.
+        let write_execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (_, _, action, heap_delta) = hypervisor.execute_update(
+            canister,
+            RequestOrIngress::Ingress(IngressBuilder::new().method_name("write").build()),
+            mock_time(),
+            routing_table,
+            subnet_records,
+            write_execution_parameters,
+        );
+        assert!(matches!(
+            action,
+            CallContextAction::Fail {
+                error: HypervisorError::WasmMemoryLimitExceeded { .. },
+                ..
+            }
+        ));
+        assert_eq!(heap_delta, NumBytes::from(0));
+    });
+}
+
 #[test]
 // Verifies that subnet available memory decreases when execution succeeds.
 fn available_memory_is_updated() {
@@ -3599,6 +3914,7 @@ fn test_system_method_is_callable(system_method: SystemMethod) {
             SystemMethod::CanisterInspectMessage => unimplemented!(),
             SystemMethod::Empty => unimplemented!(),
             SystemMethod::CanisterHeartbeat => unimplemented!("We don't need this test."),
+            SystemMethod::CanisterGlobalTimer => unimplemented!("We don't need this test."),
         };
 
         assert!(
@@ -3693,6 +4009,14 @@ fn test_non_existing_system_method(system_method: SystemMethod) {
                 mock_time(),
                 execution_parameters,
             ),
+            SystemMethod::CanisterGlobalTimer => hypervisor.execute_canister_system_task(
+                SystemMethod::CanisterGlobalTimer,
+                canister,
+                routing_table,
+                subnet_records,
+                mock_time(),
+                execution_parameters,
+            ),
         };
 
         assert!(
@@ -3733,6 +4057,11 @@ fn test_non_existing_canister_heartbeat() {
     test_non_existing_system_method(SystemMethod::CanisterHeartbeat);
 }
 
+#[test]
+fn test_non_existing_canister_global_timer() {
+    test_non_existing_system_method(SystemMethod::CanisterGlobalTimer);
+}
+
 #[test]
 fn canister_init_can_set_mutable_globals() {
     with_hypervisor(|hypervisor, tmp_path| {
@@ -4113,6 +4442,8 @@ fn cannot_execute_callback_on_stopped_canister() {
                         Cycles::from(0),
                         WasmClosure::new(0, 0),
                         WasmClosure::new(0, 0),
+                        None,
+                        None,
                         None
                     ),
                     Payload::Data(EMPTY_PAYLOAD),
@@ -4259,6 +4590,73 @@ fn execute_canister_heartbeat_produces_heap_delta() {
     });
 }
 
+// Tests that the global timer can set a new deadline for itself.
+#[test]
+fn canister_global_timer_can_set_timer() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let wasm = wabt::wat2wasm(
+            r#"
+            (module
+              (import "ic0" "global_timer_set"
+                (func $global_timer_set (param i64) (result i64)))
+              (func (export "canister_global_timer")
+                (drop (call $global_timer_set (i64.const 42)))
+              )
+              (memory (export "memory") 1))"#,
+        )
+        .unwrap();
+
+        let canister_id = canister_test_id(42);
+        let execution_state = hypervisor
+            .create_execution_state(wasm, tmp_path, canister_id)
+            .unwrap();
+        let canister = canister_from_exec_state(execution_state, canister_id);
+        let (_, _, routing_table, subnet_records) = setup();
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+
+        let (canister, _, result) = hypervisor.execute_canister_system_task(
+            SystemMethod::CanisterGlobalTimer,
+            canister,
+            routing_table,
+            subnet_records,
+            mock_time(),
+            execution_parameters,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            canister.system_state.global_timer,
+            Some(Time::from_nanos_since_unix_epoch(42))
+        );
+    });
+}
+
+// Tests that debug prints and the trap message of a failed update are kept in
+// the canister log.
+#[test]
+fn debug_print_and_trap_are_recorded_in_canister_log() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let wat = r#"
+            (module
+              (import "ic0" "debug_print" (func $debug_print (param i32) (param i32)))
+              (import "ic0" "trap" (func $trap (param i32) (param i32)))
+              (func (export "canister_update test")
+                (call $debug_print (i32.const 0) (i32.const 2))
+                (call $trap (i32.const 2) (i32.const 4))
+              )
+              (memory (export "memory") 1)
+              (data (i32.const 0) "hiboom"))"#;
+
+        let (canister, _, _, _) = execute_update(&hypervisor, wat, "test", vec![], tmp_path);
+        let contents: Vec<_> = canister
+            .system_state
+            .canister_log
+            .records()
+            .map(|record| record.content.clone())
+            .collect();
+        assert_eq!(contents, vec![b"hi".to_vec(), b"[TRAP]: boom".to_vec()]);
+    });
+}
+
 // Tests that execute_update produces a heap delta.
 #[test]
 fn execute_update_produces_heap_delta() {
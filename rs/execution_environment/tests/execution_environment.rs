@@ -755,6 +755,8 @@ fn test_response_message_side_effects_1() {
             WasmClosure::new(0, 2),
             WasmClosure::new(0, 2),
             None,
+            None,
+            None,
         ));
     assert_eq!(
         system_state
@@ -850,6 +852,8 @@ fn test_repeated_response() {
             WasmClosure::new(0, 2),
             WasmClosure::new(0, 2),
             None,
+            None,
+            None,
         ));
     // mark this call context as responded
     system_state
@@ -1403,6 +1407,8 @@ fn get_running_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
         ),
     )
 }
@@ -1427,6 +1433,8 @@ fn get_stopped_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
         ),
     );
 }
@@ -1451,6 +1459,8 @@ fn get_stopping_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
         ),
     );
 }
@@ -2729,6 +2739,64 @@ fn canister_heartbeat_doesnt_run_when_canister_is_stopping() {
     );
 }
 
+#[test]
+fn canister_global_timer_stays_due_when_canister_is_stopped() {
+    with_setup(
+        SubnetType::System,
+        |exec_env, _, _, routing_table, subnet_records| {
+            let mut canister = get_stopped_canister_on_system_subnet(canister_test_id(0));
+            canister.system_state.global_timer = Some(mock_time());
+
+            let (canister, _, result) = exec_env.execute_canister_global_timer(
+                canister,
+                MAX_NUM_INSTRUCTIONS,
+                routing_table,
+                subnet_records,
+                mock_time(),
+                MAX_SUBNET_AVAILABLE_MEMORY.clone(),
+            );
+
+            assert_eq!(
+                result,
+                Err(CanisterHeartbeatError::CanisterNotRunning {
+                    status: CanisterStatusType::Stopped,
+                })
+            );
+            assert_eq!(canister.system_state.global_timer, Some(mock_time()));
+        },
+    );
+}
+
+#[test]
+fn canister_global_timer_stays_due_when_canister_is_out_of_cycles() {
+    with_setup(
+        SubnetType::Application,
+        |exec_env, _, _, routing_table, subnet_records| {
+            let mut canister = CanisterStateBuilder::new()
+                .with_canister_id(canister_test_id(0))
+                .with_status(CanisterStatusType::Running)
+                .with_cycles(0)
+                .build();
+            canister.system_state.global_timer = Some(mock_time());
+
+            let (canister, _, result) = exec_env.execute_canister_global_timer(
+                canister,
+                MAX_NUM_INSTRUCTIONS,
+                routing_table,
+                subnet_records,
+                mock_time(),
+                MAX_SUBNET_AVAILABLE_MEMORY.clone(),
+            );
+
+            assert!(matches!(
+                result,
+                Err(CanisterHeartbeatError::OutOfCycles(_))
+            ));
+            assert_eq!(canister.system_state.global_timer, Some(mock_time()));
+        },
+    );
+}
+
 #[test]
 fn message_to_canister_with_not_enough_balance_is_rejected() {
     with_setup(SubnetType::Application, |exec_env, _, _, _, _| {
@@ -0,0 +1,42 @@
+//! The interface of the replica-side client of the Bitcoin adapter, i.e. the
+//! process that connects to the Bitcoin network.
+
+/// Asks the adapter for the blocks that extend the `anchor` and are not
+/// among the `processed_block_hashes` yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GetSuccessorsRequest {
+    /// The hash of the latest stable block.
+    pub anchor: Vec<u8>,
+    /// The hashes of the blocks above the `anchor` that are already known.
+    pub processed_block_hashes: Vec<Vec<u8>>,
+}
+
+/// The blocks returned by the adapter, parents before children.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetSuccessorsResponse {
+    /// Consensus-encoded Bitcoin blocks.
+    pub blocks: Vec<Vec<u8>>,
+}
+
+/// Errors returned by a [`BitcoinAdapterClient`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum BitcoinAdapterClientError {
+    /// The adapter is not reachable or did not answer in time.
+    Unavailable(String),
+    /// The adapter rejected the request.
+    Rejected(String),
+}
+
+/// The replica-side client of the Bitcoin adapter. Both methods must return
+/// quickly, as they are called from within the payload builder.
+pub trait BitcoinAdapterClient: Send + Sync {
+    /// Returns the blocks that extend the anchor of the request.
+    fn get_successors(
+        &self,
+        request: GetSuccessorsRequest,
+    ) -> Result<GetSuccessorsResponse, BitcoinAdapterClientError>;
+
+    /// Hands a consensus-encoded transaction over to the adapter, which
+    /// advertises it to the Bitcoin network.
+    fn send_transaction(&self, transaction: Vec<u8>) -> Result<(), BitcoinAdapterClientError>;
+}
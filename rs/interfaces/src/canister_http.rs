@@ -0,0 +1,189 @@
+use crate::{artifact_pool::UnvalidatedArtifact, validation::ValidationError};
+
+use ic_types::{
+    artifact::{CanisterHttpResponseAttribute, CanisterHttpResponseId, PriorityFn},
+    batch::ValidationContext,
+    canister_http::{
+        CanisterHttpAdapterResponse, CanisterHttpPayload, CanisterHttpRequest,
+        CanisterHttpResponse, CanisterHttpResponseShare,
+    },
+    crypto::{CryptoError, CryptoHashOf},
+    messages::CallbackId,
+    Height, NodeId, NumBytes, RegistryVersion,
+};
+
+/// A CanisterHttpPayload error from which it is not possible to recover.
+#[derive(Debug)]
+pub enum InvalidCanisterHttpPayload {
+    /// The payload contains a response to a request that does not exist at
+    /// the certified height.
+    UnknownCallbackId(CallbackId),
+    /// The payload contains a response that was already delivered, or more
+    /// than one response to the same request.
+    DuplicateResponse(CallbackId),
+    /// The timeout of the response does not match the one of the request.
+    InvalidTimeout(CallbackId),
+    /// The signed metadata does not match the response.
+    InvalidMetadata(CallbackId),
+    /// The hash in the signed metadata is not the hash of the response.
+    ContentHashMismatch(CallbackId),
+    /// The response is signed by a node that is not a member of the subnet.
+    SignerNotMember(CallbackId, NodeId),
+    /// Fewer nodes than required signed the response.
+    NotEnoughSignatures {
+        id: CallbackId,
+        expected: usize,
+        received: usize,
+    },
+    /// One of the signatures on the response is invalid.
+    InvalidSignature(CallbackId, CryptoError),
+    /// The payload reports a request as timed out before its timeout.
+    TimeoutNotReached(CallbackId),
+}
+
+/// A CanisterHttpPayload error from which it may be possible to recover.
+#[derive(Debug)]
+pub enum CanisterHttpTransientValidationError {
+    /// The state at the certified height is not available.
+    StateUnavailable(Height),
+    /// The subnet membership at the given registry version is not available.
+    RegistryUnavailable(RegistryVersion),
+}
+
+/// A CanisterHttpPayload error that results from payload validation.
+pub type CanisterHttpPayloadValidationError =
+    ValidationError<InvalidCanisterHttpPayload, CanisterHttpTransientValidationError>;
+
+pub trait CanisterHttpPayloadBuilder: Send + Sync {
+    /// Produces a `CanisterHttpPayload` of maximum byte size `byte_limit`
+    /// that is valid given a `ValidationContext` (certified height plus
+    /// registry version) and `past_payloads` (the `CanisterHttpPayloads`
+    /// from all blocks above the certified height, in descending block
+    /// height order).
+    fn get_canister_http_payload(
+        &self,
+        validation_context: &ValidationContext,
+        past_payloads: &[&CanisterHttpPayload],
+        byte_limit: NumBytes,
+    ) -> CanisterHttpPayload;
+
+    /// Checks whether the provided `CanisterHttpPayload` is valid given a
+    /// `ValidationContext` (certified height and registry version) and
+    /// `past_payloads` (the `CanisterHttpPayloads` from all blocks above the
+    /// certified height, in descending block height order).
+    ///
+    /// If valid, returns the payload's `CountBytes` size; else returns a
+    /// permanent or transient `ValidationError`.
+    fn validate_canister_http_payload(
+        &self,
+        payload: &CanisterHttpPayload,
+        validation_context: &ValidationContext,
+        past_payloads: &[&CanisterHttpPayload],
+    ) -> Result<NumBytes, CanisterHttpPayloadValidationError>;
+}
+
+/// Builder for replicas that do not execute canister HTTP requests.
+pub struct NoOpCanisterHttpPayloadBuilder {}
+
+impl CanisterHttpPayloadBuilder for NoOpCanisterHttpPayloadBuilder {
+    fn get_canister_http_payload(
+        &self,
+        _validation_context: &ValidationContext,
+        _past_payloads: &[&CanisterHttpPayload],
+        _byte_limit: NumBytes,
+    ) -> CanisterHttpPayload {
+        CanisterHttpPayload::default()
+    }
+
+    fn validate_canister_http_payload(
+        &self,
+        payload: &CanisterHttpPayload,
+        _validation_context: &ValidationContext,
+        _past_payloads: &[&CanisterHttpPayload],
+    ) -> Result<NumBytes, CanisterHttpPayloadValidationError> {
+        match payload
+            .responses
+            .iter()
+            .map(|response| response.content.id)
+            .chain(payload.timeouts.iter().cloned())
+            .next()
+        {
+            None => Ok(0.into()),
+            Some(id) => Err(ValidationError::Permanent(
+                InvalidCanisterHttpPayload::UnknownCallbackId(id),
+            )),
+        }
+    }
+}
+
+/// Changes to the canister HTTP pool.
+#[derive(Debug)]
+pub enum CanisterHttpChangeAction {
+    /// Adds a share signed by this node, together with the response it signs.
+    AddToValidated(CanisterHttpResponseShare, CanisterHttpResponse),
+    MoveToValidated(CanisterHttpResponseId),
+    RemoveValidated(CanisterHttpResponseId),
+    RemoveUnvalidated(CanisterHttpResponseId),
+    RemoveContent(CryptoHashOf<CanisterHttpResponse>),
+    HandleInvalid(CanisterHttpResponseId, String),
+}
+
+pub type CanisterHttpChangeSet = Vec<CanisterHttpChangeAction>;
+
+/// Artifact pool for the canister HTTP response shares (query interface).
+pub trait CanisterHttpPool: Send + Sync {
+    /// Returns the shares that were validated, including this node's own.
+    fn get_validated_shares(&self) -> Box<dyn Iterator<Item = &CanisterHttpResponseShare> + '_>;
+
+    /// Returns the shares received from peers that were not validated yet.
+    fn get_unvalidated_shares(&self) -> Box<dyn Iterator<Item = &CanisterHttpResponseShare> + '_>;
+
+    /// Returns the response with the given hash, if this node received it.
+    fn get_response_content_by_hash(
+        &self,
+        hash: &CryptoHashOf<CanisterHttpResponse>,
+    ) -> Option<CanisterHttpResponse>;
+}
+
+/// Artifact pool for the canister HTTP response shares (update interface).
+pub trait MutableCanisterHttpPool: CanisterHttpPool {
+    /// Adds the entry to the unvalidated section of the artifact pool.
+    fn insert(&mut self, share: UnvalidatedArtifact<CanisterHttpResponseShare>);
+
+    /// Mutates the artifact pool by applying the change set.
+    fn apply_changes(&mut self, change_set: CanisterHttpChangeSet);
+}
+
+/// Sends the canister HTTP requests to the adapter, signs the responses and
+/// validates the shares of the other nodes.
+pub trait CanisterHttpPoolManager: Send {
+    fn on_state_change(&self, canister_http_pool: &dyn CanisterHttpPool) -> CanisterHttpChangeSet;
+}
+
+pub trait CanisterHttpGossip: Send + Sync {
+    fn get_priority_function(
+        &self,
+        canister_http_pool: &dyn CanisterHttpPool,
+    ) -> PriorityFn<CanisterHttpResponseId, CanisterHttpResponseAttribute>;
+}
+
+/// Errors returned by a [`CanisterHttpAdapterClient`] when sending a request.
+#[derive(Debug)]
+pub enum CanisterHttpAdapterClientError {
+    /// The adapter cannot accept more requests at the moment.
+    Busy(CanisterHttpRequest),
+    /// The adapter is not reachable.
+    Unavailable(CanisterHttpRequest),
+}
+
+/// The replica-side client of the adapter that executes canister HTTP
+/// requests. Both methods must not block, as they are called from within the
+/// payload builder.
+pub trait CanisterHttpAdapterClient: Send + Sync {
+    /// Hands the request over to the adapter.
+    fn send(&self, request: CanisterHttpRequest) -> Result<(), CanisterHttpAdapterClientError>;
+
+    /// Returns one of the responses the adapter has received since the last
+    /// call, if any.
+    fn try_receive(&self) -> Option<CanisterHttpAdapterResponse>;
+}
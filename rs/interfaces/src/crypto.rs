@@ -32,6 +32,7 @@ pub use sign::{Signable, SignableMock};
 
 pub use sign::canister_threshold_sig::*;
 
+use ic_types::canister_http::CanisterHttpResponseMetadata;
 use ic_types::consensus::certification::CertificationContent;
 use ic_types::consensus::dkg as consensus_dkg;
 use ic_types::consensus::{
@@ -81,6 +82,9 @@ pub trait Crypto:
     // RandomTape
     + ThresholdSigner<RandomTapeContent>
     + ThresholdSigVerifier<RandomTapeContent>
+    // CanisterHttpResponse
+    + BasicSigner<CanisterHttpResponseMetadata>
+    + BasicSigVerifier<CanisterHttpResponseMetadata>
     // Traits for signing/verifying a MerkleRoot
     // (both Multi- and ThresholdSig) will be added at a later stage.
     //
@@ -127,5 +131,7 @@ impl<T> Crypto for T where
         + ThresholdSigVerifier<RandomBeaconContent>
         + ThresholdSigner<RandomTapeContent>
         + ThresholdSigVerifier<RandomTapeContent>
+        + BasicSigner<CanisterHttpResponseMetadata>
+        + BasicSigVerifier<CanisterHttpResponseMetadata>
 {
 }
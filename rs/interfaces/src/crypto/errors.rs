@@ -57,6 +57,8 @@ impl ErrorReplication for CryptoError {
                 RegistryClientError::PollLockFailed { .. } => false,
                 // may be transient errors
                 RegistryClientError::PollingLatestVersionFailed { .. } => false,
+                // true, as the registry is guaranteed to be consistent across replicas
+                RegistryClientError::DecodeError { .. } => true,
             },
             // true, as the registry is guaranteed to be consistent across replicas
             CryptoError::DkgTranscriptNotFound { .. } => true,
@@ -93,6 +95,8 @@ impl ErrorReplication for DkgVerifyDealingError {
                     RegistryClientError::PollLockFailed { .. } => false,
                     // may be transient errors
                     RegistryClientError::PollingLatestVersionFailed { .. } => false,
+                    // true, as the registry is guaranteed to be consistent across replicas
+                    RegistryClientError::DecodeError { .. } => true,
                 }
             }
             DkgVerifyDealingError::MalformedFsEncryptionPublicKey(_) => {
@@ -1,4 +1,5 @@
 use ic_types::artifact::StateSyncMessage;
+use ic_types::canister_http::{CanisterHttpResponse, CanisterHttpResponseShare};
 use ic_types::consensus::certification::CertificationMessage;
 use ic_types::consensus::dkg as consensus_dkg;
 use ic_types::consensus::{
@@ -69,6 +70,11 @@ const DOMAIN_ECDSA_VERIFIED_DEALING: &str = "ic-threshold-ecdsa-verified-dealing
 const DOMAIN_ECDSA_TRANSCRIPT: &str = "ic-idkg-transcript-domain";
 const DOMAIN_ECDSA_SIG_SHARE: &str = "ic-threshold-ecdsa-sig-share-domain";
 
+const DOMAIN_CANISTER_HTTP_RESPONSE: &str = "ic-canister-http-response-domain";
+pub(crate) const DOMAIN_CANISTER_HTTP_RESPONSE_METADATA: &str =
+    "ic-canister-http-response-metadata-domain";
+const DOMAIN_CANISTER_HTTP_RESPONSE_SHARE: &str = "ic-canister-http-response-share-domain";
+
 /// A cryptographically hashable type.
 pub trait CryptoHashable: CryptoHashDomain + Hash {}
 impl<T> CryptoHashable for T where T: CryptoHashDomain + Hash {}
@@ -153,6 +159,9 @@ mod private {
     impl CryptoHashDomainSeal for EcdsaTranscript {}
     impl CryptoHashDomainSeal for EcdsaSigShare {}
 
+    impl CryptoHashDomainSeal for CanisterHttpResponse {}
+    impl CryptoHashDomainSeal for CanisterHttpResponseShare {}
+
     impl CryptoHashDomainSeal for CryptoHashableTestDummy {}
 }
 
@@ -380,6 +389,18 @@ impl CryptoHashDomain for EcdsaSigShare {
     }
 }
 
+impl CryptoHashDomain for CanisterHttpResponse {
+    fn domain(&self) -> String {
+        DOMAIN_CANISTER_HTTP_RESPONSE.to_string()
+    }
+}
+
+impl CryptoHashDomain for CanisterHttpResponseShare {
+    fn domain(&self) -> String {
+        DOMAIN_CANISTER_HTTP_RESPONSE_SHARE.to_string()
+    }
+}
+
 impl CryptoHashDomain for CryptoHashableTestDummy {
     fn domain(&self) -> String {
         "test_struct_domain".to_string()
@@ -23,9 +23,10 @@
 //! Please refer to the trait documentation for details.
 
 use crate::crypto::hash::{
-    DOMAIN_BLOCK, DOMAIN_CATCH_UP_CONTENT, DOMAIN_CERTIFICATION_CONTENT, DOMAIN_DEALING_CONTENT,
-    DOMAIN_ECDSA_DEALING, DOMAIN_FINALIZATION_CONTENT, DOMAIN_NOTARIZATION_CONTENT,
-    DOMAIN_RANDOM_BEACON_CONTENT, DOMAIN_RANDOM_TAPE_CONTENT,
+    DOMAIN_BLOCK, DOMAIN_CANISTER_HTTP_RESPONSE_METADATA, DOMAIN_CATCH_UP_CONTENT,
+    DOMAIN_CERTIFICATION_CONTENT, DOMAIN_DEALING_CONTENT, DOMAIN_ECDSA_DEALING,
+    DOMAIN_FINALIZATION_CONTENT, DOMAIN_NOTARIZATION_CONTENT, DOMAIN_RANDOM_BEACON_CONTENT,
+    DOMAIN_RANDOM_TAPE_CONTENT,
 };
 use ic_types::crypto::{
     BasicSigOf, CanisterSigOf, CombinedMultiSigOf, CryptoResult, IndividualMultiSigOf,
@@ -33,6 +34,7 @@ use ic_types::crypto::{
 };
 use ic_types::messages::{Delegation, MessageId, WebAuthnEnvelope};
 use ic_types::{
+    canister_http::CanisterHttpResponseMetadata,
     consensus::{
         certification::CertificationContent,
         dkg::DealingContent,
@@ -102,6 +104,7 @@ mod private {
     impl SignatureDomainSeal for CatchUpContentProtobufBytes {}
     impl SignatureDomainSeal for RandomBeaconContent {}
     impl SignatureDomainSeal for RandomTapeContent {}
+    impl SignatureDomainSeal for CanisterHttpResponseMetadata {}
     impl SignatureDomainSeal for SignableMock {}
 }
 
@@ -167,6 +170,12 @@ impl SignatureDomain for CertificationContent {
     }
 }
 
+impl SignatureDomain for CanisterHttpResponseMetadata {
+    fn domain(&self) -> Vec<u8> {
+        domain_with_prepended_length(DOMAIN_CANISTER_HTTP_RESPONSE_METADATA)
+    }
+}
+
 impl SignatureDomain for CatchUpContent {
     fn domain(&self) -> Vec<u8> {
         domain_with_prepended_length(DOMAIN_CATCH_UP_CONTENT)
@@ -339,6 +339,14 @@ pub trait SystemApi {
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
+    /// Returns 1 if the principal whose id blob is in heap[src..src+size]
+    /// is a controller of the canister, and 0 otherwise. Unlike
+    /// `ic0_controller_copy`, this works for canisters with any number of
+    /// controllers.
+    ///
+    /// Traps if the blob is not a valid principal id.
+    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32>;
+
     /// Outputs the specified bytes on the heap as a string on STDOUT.
     fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]);
 
@@ -398,6 +406,14 @@ pub trait SystemApi {
     /// See https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-call
     fn ic0_call_on_cleanup(&mut self, fun: u32, env: u32) -> HypervisorResult<()>;
 
+    /// Turns the call under construction into a best-effort call that times
+    /// out after `timeout_seconds` (capped by the system). If no response
+    /// arrives by then, the call is rejected with `SYS_UNKNOWN`, and the
+    /// request or response may be dropped. The cycles attached to a call that
+    /// times out are not refunded, as the callee may have accepted them. Can be
+    /// called at most once between `ic0.call_new` and `ic0.call_perform`.
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()>;
+
     /// (deprecated) Please use `ic0_call_cycles_add128` instead, as this API
     /// can only add a 64-bit value.
     ///
@@ -627,6 +643,13 @@ pub trait SystemApi {
     /// See: https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-certified-data
     fn ic0_certified_data_set(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
+    /// Sets the deadline of the canister's global timer to `time`. Passing
+    /// `UNIX_EPOCH` (i.e. 0) deactivates the timer.
+    ///
+    /// Returns the previous deadline of the timer or `UNIX_EPOCH` if the
+    /// timer was not active.
+    fn ic0_global_timer_set(&mut self, time: Time) -> HypervisorResult<Time>;
+
     /// If run in non-replicated execution (i.e. query),
     /// returns 1 if the data certificate is present, 0 otherwise.
     /// If run in replicated execution (i.e. an update call or a certified
@@ -653,6 +676,10 @@ pub trait SystemApi {
     /// running, `2` indicates stopping, and `3` indicates stopped.
     fn ic0_canister_status(&self) -> HypervisorResult<u32>;
 
+    /// Returns the version of the canister, which is incremented every time
+    /// its code, settings or controllers change.
+    fn ic0_canister_version(&self) -> HypervisorResult<u64>;
+
     /// Mints the `amount` cycles
     /// Adds cycles to the canister's balance.
     ///
@@ -664,6 +691,21 @@ pub trait SystemApi {
     ///
     /// Returns the amount of cycles added to the canister's balance.
     fn ic0_mint_cycles(&mut self, amount: u64) -> HypervisorResult<u64>;
+
+    /// Returns the value of the performance counter identified by
+    /// `counter_type`. The only supported type is `0` which denotes the
+    /// number of instructions executed so far in the current message.
+    ///
+    /// `instruction_counter` is the current value of the instruction counter
+    /// maintained by the instrumentation, i.e. the number of instructions
+    /// left.
+    ///
+    /// Traps if `counter_type` is not supported.
+    fn ic0_performance_counter(
+        &self,
+        counter_type: u32,
+        instruction_counter: i64,
+    ) -> HypervisorResult<u64>;
 }
 
 pub trait Scheduler: Send {
@@ -1,6 +1,6 @@
 use ic_base_types::{CanisterIdError, PrincipalIdBlobParseError};
 use ic_types::{
-    methods::WasmMethod, user_error::UserError, CanisterId, CanisterStatusType, Cycles,
+    methods::WasmMethod, user_error::UserError, CanisterId, CanisterStatusType, Cycles, NumBytes,
 };
 use ic_wasm_types::{WasmEngineError, WasmInstrumentationError, WasmValidationError};
 use serde::{Deserialize, Serialize};
@@ -139,6 +139,12 @@ pub enum HypervisorError {
     /// An attempt was made to grow the canister's memory above its memory
     /// allocation.
     OutOfMemory,
+    /// An update call grew or wrote to the Wasm heap while its size exceeded
+    /// the `wasm_memory_limit` of the canister.
+    WasmMemoryLimitExceeded {
+        bytes: NumBytes,
+        limit: NumBytes,
+    },
     /// An attempt to perform an operation that isn't allowed when the canister
     /// is stopped.
     CanisterStopped,
@@ -162,6 +168,9 @@ pub enum HypervisorError {
         cleanup_err: Box<HypervisorError>,
     },
     WasmEngineError(WasmEngineError),
+    /// A composite query method was called in replicated mode, e.g. as an
+    /// update call or from another canister's update method.
+    CompositeQueryCalledInReplicatedMode(String),
 }
 
 impl From<WasmInstrumentationError> for HypervisorError {
@@ -215,6 +224,7 @@ impl HypervisorError {
                 let kind = match wasm_method {
                     WasmMethod::Update(_) => "update",
                     WasmMethod::Query(_) => "query",
+                    WasmMethod::CompositeQuery(_) => "composite query",
                     WasmMethod::System(_) => "system",
                 };
 
@@ -268,6 +278,13 @@ impl HypervisorError {
                     canister_id
                 ),
             ),
+            Self::WasmMemoryLimitExceeded { bytes, limit } => UserError::new(
+                E::CanisterWasmMemoryLimitExceeded,
+                format!(
+                    "Canister {} exceeded its Wasm memory limit: the Wasm heap of {} bytes is above the limit of {} bytes",
+                    canister_id, bytes, limit
+                ),
+            ),
             Self::CanisterStopped => UserError::new(
                 E::CanisterStopped,
                 format!("Canister {} is stopped", canister_id,),
@@ -315,6 +332,13 @@ impl HypervisorError {
                     "Canister {} encountered a Wasm engine error: {}", canister_id, err
                 ),
             ),
+            Self::CompositeQueryCalledInReplicatedMode(method) => UserError::new(
+                E::CompositeQueryCalledInReplicatedMode,
+                format!(
+                    "Canister {}: composite query method {} cannot be called in replicated mode",
+                    canister_id, method
+                ),
+            ),
         }
     }
 
@@ -332,6 +356,7 @@ impl HypervisorError {
             HypervisorError::CalledTrap(_) => "CalledTrap",
             HypervisorError::WasmModuleNotFound => "WasmModuleNotFound",
             HypervisorError::OutOfMemory => "OutOfMemory",
+            HypervisorError::WasmMemoryLimitExceeded { .. } => "WasmMemoryLimitExceeded",
             HypervisorError::CanisterStopped => "CanisterStopped",
             HypervisorError::InsufficientCyclesInCall { .. } => "InsufficientCyclesInCall",
             HypervisorError::InvalidPrincipalId(_) => "InvalidPrincipalId",
@@ -340,6 +365,9 @@ impl HypervisorError {
             HypervisorError::InsufficientCyclesBalance { .. } => "InsufficientCyclesBalance",
             HypervisorError::Cleanup { .. } => "Cleanup",
             HypervisorError::WasmEngineError(_) => "WasmEngineError",
+            HypervisorError::CompositeQueryCalledInReplicatedMode(_) => {
+                "CompositeQueryCalledInReplicatedMode"
+            }
         }
     }
 
@@ -361,6 +389,7 @@ impl HypervisorError {
             | HypervisorError::CalledTrap(_)
             | HypervisorError::WasmModuleNotFound
             | HypervisorError::OutOfMemory
+            | HypervisorError::WasmMemoryLimitExceeded { .. }
             | HypervisorError::CanisterStopped
             | HypervisorError::InsufficientCyclesInCall {
                 available: _,
@@ -369,7 +398,8 @@ impl HypervisorError {
             | HypervisorError::InvalidPrincipalId(_)
             | HypervisorError::InvalidCanisterId(_)
             | HypervisorError::MessageRejected
-            | HypervisorError::InsufficientCyclesBalance(_) => false,
+            | HypervisorError::InsufficientCyclesBalance(_)
+            | HypervisorError::CompositeQueryCalledInReplicatedMode(_) => false,
         }
     }
 }
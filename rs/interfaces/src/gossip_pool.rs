@@ -1,13 +1,16 @@
 //! The gossip pool public interface.
 use crate::{
-    artifact_pool::ArtifactPoolError, certification::ChangeSet as CertificationChangeSet,
+    artifact_pool::ArtifactPoolError, canister_http::CanisterHttpChangeSet,
+    certification::ChangeSet as CertificationChangeSet,
     consensus_pool::ChangeSet as ConsensusChangeSet, dkg::ChangeSet as DkgChangeSet,
     ecdsa::EcdsaChangeSet, ingress_pool::ChangeSet as IngressChangeSet,
 };
 use ic_types::{
     artifact::{
-        CertificationMessageId, ConsensusMessageId, DkgMessageId, EcdsaMessageId, IngressMessageId,
+        CanisterHttpResponseId, CertificationMessageId, ConsensusMessageId, DkgMessageId,
+        EcdsaMessageId, IngressMessageId,
     },
+    canister_http::CanisterHttpResponseShare,
     consensus::{certification::CertificationMessage, dkg, ecdsa::EcdsaMessage, ConsensusMessage},
     messages::SignedIngress,
     Height, NodeId, Time,
@@ -90,3 +93,14 @@ pub trait EcdsaGossipPool:
     GossipPool<EcdsaMessage, EcdsaChangeSet, MessageId = EcdsaMessageId, Filter = ()>
 {
 }
+
+/// GossipPool trait for CanisterHttpPool
+pub trait CanisterHttpGossipPool:
+    GossipPool<
+    CanisterHttpResponseShare,
+    CanisterHttpChangeSet,
+    MessageId = CanisterHttpResponseId,
+    Filter = (),
+>
+{
+}
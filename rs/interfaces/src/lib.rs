@@ -4,6 +4,8 @@
 //! helps reduce unnecessary dependencies between them.
 pub mod artifact_manager;
 pub mod artifact_pool;
+pub mod bitcoin_adapter_client;
+pub mod canister_http;
 pub mod certification;
 pub mod certified_stream_store;
 pub mod consensus;
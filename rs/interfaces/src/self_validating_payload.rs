@@ -2,16 +2,28 @@ use crate::validation::ValidationError;
 
 use ic_types::{
     batch::{SelfValidatingPayload, ValidationContext},
-    NumBytes,
+    Height, NumBytes,
 };
 
 /// A SelfValidatingPayload error from which it is not possible to recover.
 #[derive(Debug)]
-pub enum InvalidSelfValidatingPayload {}
+pub enum InvalidSelfValidatingPayload {
+    /// The payload contains Bitcoin blocks, but the subnet does not track a
+    /// Bitcoin network.
+    BitcoinDisabled,
+    /// A Bitcoin block cannot be decoded or its header or transactions are
+    /// invalid.
+    InvalidBitcoinBlock(String),
+    /// A Bitcoin block does not extend a known block or is already known.
+    UnexpectedBitcoinBlock(String),
+}
 
 /// A SelfValidatingPayload error from which it may be possible to recover.
 #[derive(Debug)]
-pub enum SelfValidatingTransientValidationError {}
+pub enum SelfValidatingTransientValidationError {
+    /// The state at the certified height is not available.
+    StateUnavailable(Height),
+}
 
 /// A SelfValidationPayload error that results from payload validation.
 pub type SelfValidatingPayloadValidationError =
@@ -62,10 +74,16 @@ impl SelfValidatingPayloadBuilder for NoOpSelfValidatingPayloadBuilder {
 
     fn validate_self_validating_payload(
         &self,
-        _payload: &SelfValidatingPayload,
+        payload: &SelfValidatingPayload,
         _validation_context: &ValidationContext,
         _past_payloads: &[&SelfValidatingPayload],
     ) -> Result<NumBytes, SelfValidatingPayloadValidationError> {
-        Ok(0.into())
+        if payload.is_empty() {
+            Ok(0.into())
+        } else {
+            Err(ValidationError::Permanent(
+                InvalidSelfValidatingPayload::BitcoinDisabled,
+            ))
+        }
     }
 }
@@ -40,7 +40,7 @@ use ic_types::{
 use ic_utils::thread::JoinOnDrop;
 #[cfg(test)]
 use mockall::automock;
-use prometheus::{Histogram, HistogramVec, IntCounterVec, IntGauge};
+use prometheus::{Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::convert::TryFrom;
 use std::ops::Range;
@@ -69,6 +69,9 @@ const PHASE_COMMIT: &str = "commit";
 
 const METRIC_PROCESS_BATCH_DURATION: &str = "mr_process_batch_duration_seconds";
 const METRIC_PROCESS_BATCH_PHASE_DURATION: &str = "mr_process_batch_phase_duration_seconds";
+const METRIC_MIGRATED_CANISTERS_REMOVED: &str = "mr_migrated_canisters_removed_count";
+
+pub(crate) const CRITICAL_ERROR_UNREGISTERED_MIGRATION: &str = "mr_unregistered_canister_migration";
 
 /// Records the timestamp when all messages before the given index (down to the
 /// previous `MessageTime`) were first added to / learned about in a stream.
@@ -237,6 +240,12 @@ pub(crate) struct MessageRoutingMetrics {
     /// for the extra copies of the state that the protocol has to store for
     /// correct operations.
     canisters_memory_usage_bytes: IntGauge,
+    /// Number of canisters removed from this subnet after their migration to
+    /// another subnet was completed.
+    pub migrated_canisters_removed: IntCounter,
+    /// Critical error counter for canisters that the routing table assigns to
+    /// another subnet without a registered migration.
+    pub critical_error_unregistered_migration: IntCounter,
 }
 
 impl MessageRoutingMetrics {
@@ -272,6 +281,12 @@ impl MessageRoutingMetrics {
                 "canister_memory_usage_bytes",
                 "Total memory footprint of all canisters on this subnet.",
             ),
+            migrated_canisters_removed: metrics_registry.int_counter(
+                METRIC_MIGRATED_CANISTERS_REMOVED,
+                "Number of canisters removed after their migration to another subnet completed.",
+            ),
+            critical_error_unregistered_migration: metrics_registry
+                .error_counter(CRITICAL_ERROR_UNREGISTERED_MIGRATION),
         }
     }
 }
@@ -475,11 +490,16 @@ impl BatchProcessorImpl {
 
         let routing_table_record = self.registry.get_routing_table(registry_version)?;
         let routing_table = routing_table_record.unwrap_or_default();
+        let canister_migrations = self
+            .registry
+            .get_canister_migrations(registry_version)?
+            .unwrap_or_default();
         let nns_subnet_id = self.get_nns_subnet_id(registry_version);
 
         Ok(NetworkTopology {
             subnets,
             routing_table: Arc::new(routing_table),
+            canister_migrations: Arc::new(canister_migrations),
             nns_subnet_id,
         })
     }
@@ -3,14 +3,15 @@ use ic_base_types::NumBytes;
 use ic_config::execution_environment::Config as HypervisorConfig;
 use ic_logger::{debug, trace, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
+use ic_registry_routing_table::CanisterMigrations;
 use ic_replicated_state::{
     canister_state::QUEUE_INDEX_NONE,
     metadata_state::StreamHandle,
     replicated_state::{
         ReplicatedStateMessageRouting, LABEL_VALUE_CANISTER_NOT_FOUND,
         LABEL_VALUE_CANISTER_OUT_OF_CYCLES, LABEL_VALUE_CANISTER_STOPPED,
-        LABEL_VALUE_CANISTER_STOPPING, LABEL_VALUE_INVALID_SUBNET_PAYLOAD, LABEL_VALUE_QUEUE_FULL,
-        LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
+        LABEL_VALUE_CANISTER_STOPPING, LABEL_VALUE_INVALID_SUBNET_PAYLOAD,
+        LABEL_VALUE_LATE_RESPONSE, LABEL_VALUE_QUEUE_FULL, LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
     },
     ReplicatedState, StateError,
 };
@@ -49,6 +50,9 @@ const LABEL_STATUS: &str = "status";
 const LABEL_VALUE_SUCCESS: &str = "success";
 const LABEL_VALUE_SENDER_SUBNET_MISMATCH: &str = "SenderSubnetMismatch";
 const LABEL_VALUE_SENDER_SUBNET_UNKNOWN: &str = "SenderSubnetUnknown";
+const LABEL_VALUE_REQUEST_EXPIRED: &str = "RequestExpired";
+const LABEL_VALUE_CANISTER_MIGRATED: &str = "CanisterMigrated";
+const LABEL_VALUE_RESPONSE_REROUTED: &str = "ResponseRerouted";
 const LABEL_TYPE: &str = "type";
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
@@ -89,8 +93,12 @@ impl StreamHandlerMetrics {
                 LABEL_VALUE_QUEUE_FULL,
                 LABEL_VALUE_SENDER_SUBNET_MISMATCH,
                 LABEL_VALUE_SENDER_SUBNET_UNKNOWN,
+                LABEL_VALUE_REQUEST_EXPIRED,
+                LABEL_VALUE_CANISTER_MIGRATED,
+                LABEL_VALUE_RESPONSE_REROUTED,
                 LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
                 LABEL_VALUE_INVALID_SUBNET_PAYLOAD,
+                LABEL_VALUE_LATE_RESPONSE,
             ] {
                 inducted_xnet_messages.with_label_values(&[msg_type, status]);
             }
@@ -337,6 +345,9 @@ impl StreamHandlerImpl {
         let mut subnet_available_memory =
             self.subnet_memory_capacity.get() as i64 - state.total_memory_taken().get() as i64;
         let mut streams = state.take_streams();
+        // Responses for canisters that were migrated away from this subnet, to be
+        // forwarded to their new host subnets.
+        let mut rerouted_responses = Vec::new();
 
         for (remote_subnet_id, mut stream_slice) in stream_slices {
             // Output stream, for resulting signals and (in the initial iteration) reject
@@ -344,17 +355,23 @@ impl StreamHandlerImpl {
             let mut stream = streams.get_mut_or_insert(remote_subnet_id);
 
             while let Some((stream_index, msg)) = stream_slice.pop_message() {
-                self.induct_message(
+                if let Some(rerouted) = self.induct_message(
                     msg,
                     remote_subnet_id,
                     stream_index,
                     &mut state,
                     &mut stream,
                     &mut subnet_available_memory,
-                );
+                ) {
+                    rerouted_responses.push(rerouted);
+                }
             }
         }
 
+        for (destination_subnet_id, msg) in rerouted_responses {
+            streams.get_mut_or_insert(destination_subnet_id).push(msg);
+        }
+
         state.put_streams(streams);
         state
     }
@@ -366,7 +383,13 @@ impl StreamHandlerImpl {
     ///
     ///  * enqueuing the message into the corresponding input queue;
     ///  * a reject response enqueued into the reverse stream: if enqueuing of a
-    ///    request failed (queue full, canister not found, out of memory);
+    ///    request failed (queue full, canister not found, out of memory); or the
+    ///    request is addressed to a canister that was migrated away from this
+    ///    subnet; or the request is a best-effort request whose deadline has
+    ///    passed;
+    ///  * the message returned together with the receiver's new host subnet: if
+    ///    it is a response addressed to a canister that was migrated away from
+    ///    this subnet, for the caller to forward it;
     ///  * no other action: if the sender canister and source subnet do not
     ///    match; or enqueuing of a response failed.
     ///
@@ -379,7 +402,7 @@ impl StreamHandlerImpl {
         state: &mut ReplicatedState,
         stream: &mut StreamHandle,
         subnet_available_memory: &mut i64,
-    ) {
+    ) -> Option<(SubnetId, RequestOrResponse)> {
         let payload_size = match &msg {
             RequestOrResponse::Request(req) => req.payload_size_bytes().get(),
             RequestOrResponse::Response(res) => res.response_payload.size_of().get(),
@@ -388,16 +411,100 @@ impl StreamHandlerImpl {
             RequestOrResponse::Request(_) => LABEL_VALUE_TYPE_REQUEST,
             RequestOrResponse::Response(_) => LABEL_VALUE_TYPE_RESPONSE,
         };
+        let is_expired = match &msg {
+            RequestOrResponse::Request(req) => req.is_expired(state.time()),
+            RequestOrResponse::Response(_) => false,
+        };
 
-        match state
-            .metadata
-            .network_topology
-            .routing_table
-            .route(msg.sender().get())
-        {
+        let routing_table = Arc::clone(&state.metadata.network_topology.routing_table);
+        let canister_migrations = Arc::clone(&state.metadata.network_topology.canister_migrations);
+        let mut rerouted = None;
+
+        match routing_table.route(msg.sender().get()) {
             Some(host_subnet) => {
-                if host_subnet == remote_subnet_id {
-                    // Sender is hosted by `remote_subnet_id`, proceed with induction.
+                let is_valid_source =
+                    is_valid_source(&msg, host_subnet, remote_subnet_id, &canister_migrations);
+                // The new host subnet of the receiver, if it was migrated away from this
+                // subnet while the message was in flight.
+                let migrated_to = routing_table
+                    .route(msg.receiver().get())
+                    .filter(|receiver_host| *receiver_host != self.subnet_id)
+                    .filter(|_| {
+                        canister_migrations
+                            .lookup(msg.receiver())
+                            .map_or(false, |trace| trace.contains(&self.subnet_id))
+                    });
+
+                if !is_valid_source {
+                    // Sender is hosted by a subnet other than `remote_subnet_id`.
+                    //
+                    // Do not enqueue a reject response as remote subnet is likely malicious and
+                    // trying to cause a memory leak by sending bogus messages and never consuming
+                    // reject responses.
+                    warn!(self.log,
+                        "Dropping message from subnet {} claiming to be from sender {} hosted by subnet {}: {:?}",
+                        remote_subnet_id,
+                        msg.sender(),
+                        host_subnet,
+                        msg);
+                    self.observe_inducted_message_status(
+                        msg_type,
+                        LABEL_VALUE_SENDER_SUBNET_MISMATCH,
+                    );
+                } else if is_expired {
+                    // The deadline of a best-effort request has passed: drop it and reject
+                    // it with `SYS_UNKNOWN`. The caller may have timed out the callback
+                    // already, in which case the reject is what releases it.
+                    debug!(self.log, "Rejecting expired request {:?}", &msg);
+                    self.observe_inducted_message_status(msg_type, LABEL_VALUE_REQUEST_EXPIRED);
+                    let message = format!(
+                        "Request deadline expired before it was delivered to canister {}",
+                        msg.receiver()
+                    );
+                    self.try_enqueue_reject_response(msg, RejectCode::SysUnknown, message, stream);
+                } else if let Some(receiver_host) = migrated_to {
+                    // Receiver was migrated away from this subnet: reject requests (the
+                    // caller may retry, which will route to the new host) and forward
+                    // responses to the new host.
+                    match msg {
+                        RequestOrResponse::Request(_) => {
+                            debug!(
+                                self.log,
+                                "Rejecting request to migrated canister: {:?}", &msg
+                            );
+                            self.observe_inducted_message_status(
+                                msg_type,
+                                LABEL_VALUE_CANISTER_MIGRATED,
+                            );
+                            let message = format!(
+                                "Canister {} is being migrated to subnet {}",
+                                msg.receiver(),
+                                receiver_host
+                            );
+                            self.try_enqueue_reject_response(
+                                msg,
+                                RejectCode::SysTransient,
+                                message,
+                                stream,
+                            );
+                        }
+                        RequestOrResponse::Response(_) => {
+                            debug!(
+                                self.log,
+                                "Rerouting response to migrated canister to subnet {}: {:?}",
+                                receiver_host,
+                                &msg
+                            );
+                            self.observe_inducted_message_status(
+                                msg_type,
+                                LABEL_VALUE_RESPONSE_REROUTED,
+                            );
+                            rerouted = Some((receiver_host, msg));
+                        }
+                    }
+                } else {
+                    // Sender is hosted by `remote_subnet_id` (or is being migrated away
+                    // from it), proceed with induction.
                     match state.push_input(
                         QUEUE_INDEX_NONE,
                         msg,
@@ -419,22 +526,6 @@ impl StreamHandlerImpl {
                             self.try_enqueue_reject_response(msg, code, err.to_string(), stream);
                         }
                     }
-                } else {
-                    // Sender is hosted by a subnet other than `remote_subnet_id`.
-                    //
-                    // Do not enqueue a reject response as remote subnet is likely malicious and
-                    // trying to cause a memory leak by sending bogus messages and never consuming
-                    // reject responses.
-                    warn!(self.log,
-                        "Dropping message from subnet {} claiming to be from sender {} hosted by subnet {}: {:?}",
-                        remote_subnet_id,
-                        msg.sender(),
-                        host_subnet,
-                        msg);
-                    self.observe_inducted_message_status(
-                        msg_type,
-                        LABEL_VALUE_SENDER_SUBNET_MISMATCH,
-                    );
                 }
             }
 
@@ -460,6 +551,8 @@ impl StreamHandlerImpl {
             stream_index
         );
         stream.increment_signals_end();
+
+        rerouted
     }
 
     /// Enqueues a reject `Response` for the provided `msg` (iff it is a
@@ -509,6 +602,29 @@ impl StreamHandlerImpl {
     }
 }
 
+/// Returns true if `remote_subnet_id` is a legitimate source of `msg`: it is
+/// the sender's host subnet; or the sender is being migrated away from it; or
+/// `msg` is a response that it rerouted to a receiver being migrated away from
+/// it.
+fn is_valid_source(
+    msg: &RequestOrResponse,
+    host_subnet: SubnetId,
+    remote_subnet_id: SubnetId,
+    canister_migrations: &CanisterMigrations,
+) -> bool {
+    let on_migration_trace = |canister_id| {
+        canister_migrations
+            .lookup(canister_id)
+            .map_or(false, |trace| trace.contains(&remote_subnet_id))
+    };
+    host_subnet == remote_subnet_id
+        || on_migration_trace(msg.sender())
+        || match msg {
+            RequestOrResponse::Response(_) => on_migration_trace(msg.receiver()),
+            RequestOrResponse::Request(_) => false,
+        }
+}
+
 /// Generates a reject `Response` for a `Request` message with the provided
 /// `RejectContext`.
 fn generate_reject_response(msg: RequestOrResponse, context: RejectContext) -> RequestOrResponse {
@@ -537,5 +653,7 @@ fn reject_code_for_state_error(err: &StateError) -> RejectCode {
         StateError::UnknownSubnetMethod(_) => RejectCode::CanisterReject,
         StateError::InvalidSubnetPayload => RejectCode::CanisterReject,
         StateError::OutOfMemory { .. } => RejectCode::SysTransient,
+        // Only ever returned for responses, which are not rejected.
+        StateError::LateResponse(_) => RejectCode::SysUnknown,
     }
 }
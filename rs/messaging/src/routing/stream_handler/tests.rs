@@ -8,7 +8,7 @@ use crate::message_routing::{LABEL_REMOTE, METRIC_TIME_IN_BACKLOG, METRIC_TIME_I
 use ic_base_types::NumSeconds;
 use ic_config::execution_environment::Config as HypervisorConfig;
 use ic_metrics::MetricsRegistry;
-use ic_registry_routing_table::{CanisterIdRange, RoutingTable};
+use ic_registry_routing_table::{CanisterIdRange, CanisterMigrations, RoutingTable};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
     canister_state::{ENFORCE_MESSAGE_MEMORY_USAGE, QUEUE_INDEX_NONE},
@@ -22,7 +22,7 @@ use ic_test_utilities::{
         fetch_int_gauge_vec, metric_vec, nonzero_values, HistogramStats, MetricVec,
     },
     state::new_canister_state,
-    types::ids::{user_test_id, SUBNET_12, SUBNET_23},
+    types::ids::{user_test_id, SUBNET_12, SUBNET_23, SUBNET_27},
     types::messages::{RequestBuilder, ResponseBuilder},
     types::xnet::{StreamHeaderBuilder, StreamSliceBuilder},
     with_test_replica_logger,
@@ -30,7 +30,7 @@ use ic_test_utilities::{
 use ic_types::{
     messages::{CallbackId, Payload, Request, MAX_RESPONSE_COUNT_BYTES},
     xnet::{testing::StreamSliceTesting, StreamIndex, StreamIndexedQueue},
-    CanisterId, Cycles,
+    CanisterId, Cycles, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreemap;
@@ -186,6 +186,71 @@ fn induct_loopback_stream_reject_response() {
     });
 }
 
+#[test]
+fn induct_loopback_stream_rejects_expired_request() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        let initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        initial_state.put_canister_state(initial_canister_state);
+        initial_state.metadata.batch_time = Time::from_nanos_since_unix_epoch(1_000);
+
+        // A loopback stream with 1 best-effort request whose deadline has passed.
+        let mut loopback_stream = generate_loopback_stream(StreamConfig {
+            messages_begin: 21,
+            message_count: 0,
+            signals_end: 21,
+        });
+        let msg: RequestOrResponse = RequestBuilder::new()
+            .sender(*LOCAL_CANISTER)
+            .receiver(*LOCAL_CANISTER)
+            .deadline(Some(Time::from_nanos_since_unix_epoch(999)))
+            .build()
+            .into();
+        loopback_stream.push(msg.clone());
+        initial_state.with_streams(btreemap![LOCAL_SUBNET => loopback_stream]);
+
+        // Expecting an unchanged canister state...
+        let mut expected_state = initial_state.clone();
+
+        // ...and a loopback stream with begin indices advanced...
+        let mut expected_loopback_stream = generate_loopback_stream(StreamConfig {
+            messages_begin: 22,
+            message_count: 0,
+            signals_end: 22,
+        });
+        // ...plus a `SYS_UNKNOWN` reject response.
+        let context = RejectContext::new(
+            RejectCode::SysUnknown,
+            format!(
+                "Request deadline expired before it was delivered to canister {}",
+                *LOCAL_CANISTER
+            ),
+        );
+        expected_loopback_stream.push(generate_reject_response(msg, context));
+        expected_state.with_streams(btreemap![LOCAL_SUBNET => expected_loopback_stream]);
+
+        let inducted_state = stream_handler.induct_loopback_stream(initial_state);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[(
+                &[
+                    (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                    (LABEL_STATUS, LABEL_VALUE_REQUEST_EXPIRED),
+                ],
+                1,
+            )]),
+            &metrics_registry,
+        );
+    });
+}
+
 #[test]
 fn induct_loopback_stream_success() {
     with_test_replica_logger(|log| {
@@ -724,6 +789,99 @@ fn induct_stream_slices_partial_success() {
     });
 }
 
+#[test]
+fn induct_stream_slices_with_migrated_canister() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        // `LOCAL_CANISTER` is being migrated from `LOCAL_SUBNET` to `OTHER_SUBNET`.
+        const OTHER_SUBNET: SubnetId = SUBNET_27;
+        let migrated_range = CanisterIdRange {
+            start: CanisterId::from(0x0),
+            end: CanisterId::from(0xff),
+        };
+        initial_state.metadata.network_topology.routing_table = Arc::new(RoutingTable::new(
+            btreemap! {
+                migrated_range => OTHER_SUBNET,
+                CanisterIdRange{ start: CanisterId::from(0x100), end: CanisterId::from(0x1ff) } => REMOTE_SUBNET,
+            },
+        ));
+        initial_state.metadata.network_topology.canister_migrations =
+            Arc::new(CanisterMigrations::new(btreemap! {
+                migrated_range => vec![LOCAL_SUBNET, OTHER_SUBNET],
+            }));
+
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 43,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // A request and a response for `LOCAL_CANISTER` still in flight.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 31,
+        });
+        let request: RequestOrResponse = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER).into();
+        stream_slice.push_message(request.clone());
+        let response: RequestOrResponse = test_response(*REMOTE_CANISTER, *LOCAL_CANISTER).into();
+        stream_slice.push_message(response.clone());
+
+        // Expect two signals and a reject response for the request in the reverse
+        // stream; and the response forwarded to `OTHER_SUBNET`.
+        let mut expected_state = initial_state.clone();
+        let mut expected_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 45,
+        });
+        expected_stream.push(generate_reject_response(
+            request,
+            RejectContext::new(
+                RejectCode::SysTransient,
+                format!(
+                    "Canister {} is being migrated to subnet {}",
+                    *LOCAL_CANISTER, OTHER_SUBNET
+                ),
+            ),
+        ));
+        let mut expected_rerouted_stream = Stream::default();
+        expected_rerouted_stream.push(response);
+        expected_state.with_streams(btreemap![
+            REMOTE_SUBNET => expected_stream,
+            OTHER_SUBNET => expected_rerouted_stream,
+        ]);
+
+        let inducted_state = stream_handler
+            .induct_stream_slices(initial_state, btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_CANISTER_MIGRATED),
+                    ],
+                    1,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_RESPONSE),
+                        (LABEL_STATUS, LABEL_VALUE_RESPONSE_REROUTED),
+                    ],
+                    1,
+                ),
+            ]),
+            &metrics_registry,
+        );
+    });
+}
+
 /// Tests that canister memory limit is enforced when inducting stream slices.
 ///
 /// Sets up a stream handler with only enough canister memory for one in-flight
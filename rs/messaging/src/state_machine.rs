@@ -1,12 +1,20 @@
-use crate::message_routing::MessageRoutingMetrics;
+use crate::message_routing::{MessageRoutingMetrics, CRITICAL_ERROR_UNREGISTERED_MIGRATION};
 use crate::routing::{demux::Demux, stream_builder::StreamBuilder};
 use ic_interfaces::execution_environment::Scheduler;
-use ic_logger::{fatal, ReplicaLogger};
+use ic_logger::{fatal, info, warn, ReplicaLogger};
 use ic_metrics::Timer;
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
+use ic_registry_routing_table::{CanisterIdRange, CanisterMigrations};
 use ic_registry_subnet_features::SubnetFeatures;
-use ic_replicated_state::{NetworkTopology, ReplicatedState};
-use ic_types::{batch::Batch, ExecutionRound};
+use ic_replicated_state::{
+    bitcoin_state::{to_bitcoin_network, BitcoinState},
+    metadata_state::StreamMap,
+    replicated_state::ReplicatedStateMessageRouting,
+    CanisterState, NetworkTopology, ReplicatedState,
+};
+use ic_state_layout::{CheckpointLayout, RwPolicy};
+use ic_types::{batch::Batch, xnet::StreamIndex, CanisterId, ExecutionRound, Height, SubnetId};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 #[cfg(test)]
@@ -66,8 +74,8 @@ impl StateMachine for StateMachineImpl {
     fn execute_round(
         &self,
         mut state: ReplicatedState,
-        network_topology: NetworkTopology,
-        batch: Batch,
+        mut network_topology: NetworkTopology,
+        mut batch: Batch,
         provisional_whitelist: ProvisionalWhitelist,
         subnet_features: SubnetFeatures,
         max_number_of_canisters: u64,
@@ -76,10 +84,45 @@ impl StateMachine for StateMachineImpl {
 
         let mut metadata = state.system_metadata().clone();
         metadata.batch_time = batch.time;
+        let previous_migrations = Arc::clone(&metadata.network_topology.canister_migrations);
+        network_topology.canister_migrations = retain_undrained_migrations(
+            &previous_migrations,
+            network_topology.canister_migrations,
+            metadata.own_subnet_id,
+            state.streams(),
+            &mut metadata.draining_migrations,
+        );
         metadata.network_topology = network_topology;
         metadata.own_subnet_features = subnet_features;
+        metadata.ecdsa_subnet_public_keys = std::mem::take(&mut batch.ecdsa_subnet_public_keys);
+        // The Bitcoin state is created once the feature is enabled on the
+        // subnet and starts over if the tracked network changes.
+        if let Some(network) = subnet_features.bitcoin_network.map(to_bitcoin_network) {
+            if state.bitcoin.as_ref().map(|bitcoin| bitcoin.network) != Some(network) {
+                state.bitcoin = Some(BitcoinState::new(network));
+            }
+        }
+        if let Some(bitcoin) = state.bitcoin.as_mut() {
+            let blocks = std::mem::take(&mut batch.payload.self_validating.bitcoin_blocks);
+            for err in ic_btc_canister::process_blocks(bitcoin, &blocks) {
+                warn!(self.log, "Failed to process Bitcoin block: {:?}", err);
+            }
+            ic_btc_canister::remove_expired_transactions(bitcoin, batch.time);
+        }
         state.set_system_metadata(metadata);
 
+        // Canisters that were migrated away from this subnet are not executed here
+        // anymore. The ones still being migrated are put back after execution, so
+        // that they are persisted in the next checkpoint for export.
+        let migrating_canisters =
+            take_migrated_canisters(&mut state, &previous_migrations, &self.log, &self.metrics);
+
+        // Responses to canister HTTP requests are delivered just like the
+        // other responses to subnet calls that require consensus' involvement.
+        let canister_http_responses =
+            std::mem::take(&mut batch.payload.canister_http).into_responses();
+        batch.consensus_responses.extend(canister_http_responses);
+
         // Preprocess messages and add messages to the induction pool through the Demux.
         let mut state_with_messages = self.demux.process_payload(state, batch.payload);
         if !state_with_messages.consensus_queue.is_empty() {
@@ -94,13 +137,14 @@ impl StateMachine for StateMachineImpl {
 
         let phase_timer = Timer::start();
         // Process messages from the induction pool through the Scheduler.
-        let state_after_execution = self.scheduler.execute_round(
+        let mut state_after_execution = self.scheduler.execute_round(
             state_with_messages,
             batch.randomness,
             ExecutionRound::from(batch.batch_number.get()),
             provisional_whitelist,
             max_number_of_canisters,
         );
+        state_after_execution.put_canister_states(migrating_canisters);
         self.observe_phase_duration(PHASE_EXECUTION, &phase_timer);
 
         let phase_timer = Timer::start();
@@ -111,3 +155,159 @@ impl StateMachine for StateMachineImpl {
         state_after_stream_builder
     }
 }
+
+/// Returns `current` (the canister migrations in the registry) extended with
+/// the migrations in `previous` that this subnet is on the trace of and that
+/// have been completed since, for as long as this subnet's streams still hold
+/// messages enqueued before the completion. Until then, messages to or from
+/// the migrated canisters are still inducted, rejected or rerouted as during
+/// the migration.
+///
+/// The streams are only scanned once, when a migration is first found to be
+/// completed: the index just past the last message to or from the migrated
+/// canisters in each stream is recorded in `draining_migrations`. In later
+/// rounds, only the streams' begin indices are compared against these.
+fn retain_undrained_migrations(
+    previous: &CanisterMigrations,
+    current: Arc<CanisterMigrations>,
+    own_subnet_id: SubnetId,
+    streams: &StreamMap,
+    draining_migrations: &mut BTreeMap<CanisterIdRange, BTreeMap<SubnetId, StreamIndex>>,
+) -> Arc<CanisterMigrations> {
+    let is_completed = |range: &CanisterIdRange| {
+        !current
+            .iter()
+            .any(|(existing, _)| existing.intersection(range).is_some())
+    };
+    // Forget about migrations that are registered again or no longer retained.
+    draining_migrations.retain(|range, _| {
+        is_completed(range) && previous.iter().any(|(retained, _)| retained == range)
+    });
+
+    let mut undrained = Vec::new();
+    for (range, trace) in previous.iter() {
+        if !trace.contains(&own_subnet_id) || !is_completed(range) {
+            continue;
+        }
+        let stream_ends = draining_migrations.entry(*range).or_insert_with(|| {
+            streams
+                .iter()
+                .filter_map(|(subnet_id, stream)| {
+                    stream
+                        .messages()
+                        .iter()
+                        .filter(|(_, msg)| {
+                            range.contains(&msg.sender()) || range.contains(&msg.receiver())
+                        })
+                        .last()
+                        .map(|(index, _)| (*subnet_id, index.increment()))
+                })
+                .collect()
+        });
+        stream_ends.retain(|subnet_id, messages_end| {
+            streams
+                .get(subnet_id)
+                .map_or(false, |stream| stream.messages_begin() < *messages_end)
+        });
+        if stream_ends.is_empty() {
+            draining_migrations.remove(range);
+        } else {
+            undrained.push((range, trace));
+        }
+    }
+    if undrained.is_empty() {
+        return current;
+    }
+
+    let mut migrations: BTreeMap<_, _> = current
+        .iter()
+        .map(|(range, trace)| (*range, trace.clone()))
+        .collect();
+    for (range, trace) in undrained {
+        migrations.insert(*range, trace.clone());
+    }
+    Arc::new(CanisterMigrations::new(migrations))
+}
+
+/// Takes out of `state` the canisters that the routing table assigns to
+/// another subnet. Returns the ones that are still being migrated away from
+/// this subnet (their state is exported from a checkpoint of this subnet and
+/// imported by their new host subnet), to be put back after execution.
+///
+/// The others are deleted, together with their snapshots, but only if the
+/// migration that moved them away from this subnet was registered, i.e. it
+/// was part of `previous_migrations` and has been completed since. Canisters
+/// that were routed to another subnet without a registered migration are
+/// kept (but not executed) and reported as a critical error.
+fn take_migrated_canisters(
+    state: &mut ReplicatedState,
+    previous_migrations: &CanisterMigrations,
+    log: &ReplicaLogger,
+    metrics: &MessageRoutingMetrics,
+) -> BTreeMap<CanisterId, CanisterState> {
+    let own_subnet_id = state.metadata.own_subnet_id;
+    let routing_table = state.routing_table();
+    let canister_migrations = Arc::clone(&state.metadata.network_topology.canister_migrations);
+
+    let migrated: Vec<CanisterId> = state
+        .canisters_iter()
+        .map(|canister| canister.canister_id())
+        .filter(|canister_id| {
+            routing_table
+                .route(canister_id.get())
+                .map_or(false, |host_subnet| host_subnet != own_subnet_id)
+        })
+        .collect();
+
+    let mut migrating = BTreeMap::new();
+    for canister_id in migrated {
+        let canister = state.take_canister_state(&canister_id).unwrap();
+        let is_migrating = canister_migrations
+            .lookup(canister_id)
+            .map_or(false, |trace| trace.contains(&own_subnet_id));
+        if is_migrating {
+            migrating.insert(canister_id, canister);
+            continue;
+        }
+
+        let completed_migration = previous_migrations
+            .lookup(canister_id)
+            .filter(|trace| trace.contains(&own_subnet_id));
+        let trace = match completed_migration {
+            Some(trace) => trace,
+            None => {
+                warn!(
+                    log,
+                    "{}: Canister {} is routed to subnet {:?} without a registered migration",
+                    CRITICAL_ERROR_UNREGISTERED_MIGRATION,
+                    canister_id,
+                    routing_table.route(canister_id.get())
+                );
+                metrics.critical_error_unregistered_migration.inc();
+                migrating.insert(canister_id, canister);
+                continue;
+            }
+        };
+
+        info!(
+            log,
+            "Removing canister {} migrated to another subnet along {:?}", canister_id, trace
+        );
+        metrics.migrated_canisters_removed.inc();
+        state
+            .canister_snapshots
+            .remove_canister_snapshots(canister_id);
+        CheckpointLayout::<RwPolicy>::new(state.path().into(), Height::from(0))
+            .and_then(|layout| layout.canister(&canister_id))
+            .and_then(|layout| layout.mark_deleted())
+            .unwrap_or_else(|err| {
+                fatal!(
+                    log,
+                    "Failed to mark migrated canister {} as deleted: {:?}",
+                    canister_id,
+                    err
+                )
+            });
+    }
+    migrating
+}
@@ -5,17 +5,21 @@ use crate::{
 };
 use ic_interfaces::{execution_environment::Scheduler, state_manager::StateManager};
 use ic_metrics::MetricsRegistry;
+use ic_registry_routing_table::RoutingTable;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::{ReplicatedState, SubnetTopology};
+use ic_replicated_state::{ReplicatedState, Stream, SubnetTopology};
 use ic_test_utilities::{
+    state::{canister_ids, CanisterStateBuilder},
     state_manager::FakeStateManager,
     types::batch::{BatchBuilder, IngressPayloadBuilder, PayloadBuilder},
-    types::ids::subnet_test_id,
-    types::messages::SignedIngressBuilder,
+    types::ids::{canister_test_id, subnet_test_id},
+    types::messages::{RequestBuilder, SignedIngressBuilder},
     with_test_replica_logger,
 };
 use ic_types::messages::SignedIngress;
+use ic_types::xnet::{StreamIndex, StreamIndexedQueue};
 use ic_types::{Height, PrincipalId, SubnetId};
+use maplit::btreemap;
 use mockall::{mock, predicate::*, Sequence};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -105,6 +109,7 @@ fn test_fixture(provided_batch: &Batch) -> StateMachineTestFixture {
     let network_topology = NetworkTopology {
         subnets,
         routing_table: Default::default(),
+        canister_migrations: Default::default(),
         nns_subnet_id: SubnetId::from(PrincipalId::new_subnet_test_id(0)),
     };
 
@@ -213,3 +218,202 @@ fn test_delivered_batch_interface() {
         param_batch_test(Height::from(27), i);
     }
 }
+
+fn canister_id_range(start: u64, end: u64) -> CanisterIdRange {
+    CanisterIdRange {
+        start: canister_test_id(start),
+        end: canister_test_id(end),
+    }
+}
+
+#[test]
+fn state_machine_does_not_execute_migrated_canisters() {
+    let own_subnet_id = subnet_test_id(1);
+    let other_subnet_id = subnet_test_id(2);
+    let provided_batch = BatchBuilder::new().batch_number(Height::new(1)).build();
+
+    // Canister 1 is hosted by this subnet, canister 2 is being migrated to the
+    // other subnet and the migration of canister 3 there has just been completed.
+    // Canister 4 is routed to the other subnet without a registered migration.
+    let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+    let mut state = ReplicatedState::new_rooted_at(
+        own_subnet_id,
+        SubnetType::Application,
+        tmpdir.path().into(),
+    );
+    state.metadata.network_topology.canister_migrations =
+        Arc::new(CanisterMigrations::new(btreemap! {
+            canister_id_range(2, 3) => vec![own_subnet_id, other_subnet_id],
+        }));
+    for canister_id in 1..=4 {
+        state.put_canister_state(
+            CanisterStateBuilder::new()
+                .with_canister_id(canister_test_id(canister_id))
+                .build(),
+        );
+    }
+    let network_topology = NetworkTopology {
+        routing_table: Arc::new(RoutingTable::new(btreemap! {
+            canister_id_range(0, 1) => own_subnet_id,
+            canister_id_range(2, 4) => other_subnet_id,
+        })),
+        canister_migrations: Arc::new(CanisterMigrations::new(btreemap! {
+            canister_id_range(2, 2) => vec![own_subnet_id, other_subnet_id],
+        })),
+        ..Default::default()
+    };
+
+    let mut demux = Box::new(MockDemux::new());
+    demux
+        .expect_process_payload()
+        .times(1)
+        .returning(|state, _| state);
+    let mut scheduler = Box::new(MockScheduler::new());
+    scheduler
+        .expect_execute_round()
+        .times(1)
+        .returning(|state, _, _, _, _| {
+            assert_eq!(canister_ids(&state), vec![canister_test_id(1)]);
+            state
+        });
+    let mut stream_builder = Box::new(MockStreamBuilder::new());
+    stream_builder
+        .expect_build_streams()
+        .times(1)
+        .returning(|state| state);
+
+    with_test_replica_logger(|log| {
+        let metrics = Arc::new(MessageRoutingMetrics::new(&MetricsRegistry::new()));
+        let state_machine =
+            StateMachineImpl::new(scheduler, demux, stream_builder, log, Arc::clone(&metrics));
+
+        let state = state_machine.execute_round(
+            state,
+            network_topology,
+            provided_batch,
+            ProvisionalWhitelist::Set(BTreeSet::new()),
+            Default::default(),
+            MAX_NUMBER_OF_CANISTERS,
+        );
+
+        // The canister being migrated is kept for export, the migrated one is gone,
+        // also from the next checkpoint. The one without a registered migration is
+        // kept and reported.
+        assert_eq!(
+            canister_ids(&state),
+            vec![
+                canister_test_id(1),
+                canister_test_id(2),
+                canister_test_id(4)
+            ]
+        );
+        assert_eq!(1, metrics.migrated_canisters_removed.get());
+        assert_eq!(1, metrics.critical_error_unregistered_migration.get());
+        let tip = CheckpointLayout::<RwPolicy>::new(tmpdir.path().into(), Height::from(0)).unwrap();
+        assert!(tip
+            .canister(&canister_test_id(3))
+            .unwrap()
+            .is_marked_deleted());
+        for canister_id in &[2, 4] {
+            assert!(!tip
+                .canister(&canister_test_id(*canister_id))
+                .unwrap()
+                .is_marked_deleted());
+        }
+    });
+}
+
+#[test]
+fn completed_migrations_are_retained_until_streams_are_drained() {
+    let own_subnet_id = subnet_test_id(1);
+    let other_subnet_id = subnet_test_id(2);
+    let previous = CanisterMigrations::new(btreemap! {
+        canister_id_range(2, 2) => vec![own_subnet_id, other_subnet_id],
+        canister_id_range(5, 5) => vec![own_subnet_id, other_subnet_id],
+    });
+    let current = Arc::new(CanisterMigrations::default());
+
+    // A request from canister 2 is still in the stream to the other subnet.
+    let mut messages = StreamIndexedQueue::with_begin(StreamIndex::new(0));
+    messages.push(
+        RequestBuilder::new()
+            .sender(canister_test_id(2))
+            .receiver(canister_test_id(7))
+            .build()
+            .into(),
+    );
+    let mut streams = StreamMap::new();
+    streams.insert(other_subnet_id, Stream::new(messages, StreamIndex::new(0)));
+
+    // Subnets that are not on the trace do not retain the migration.
+    let mut draining_migrations = BTreeMap::new();
+    assert_eq!(
+        *retain_undrained_migrations(
+            &previous,
+            Arc::clone(&current),
+            subnet_test_id(3),
+            &streams,
+            &mut draining_migrations
+        ),
+        CanisterMigrations::default()
+    );
+    assert!(draining_migrations.is_empty());
+
+    // The migration with a message in flight is retained, with the index just
+    // past that message recorded.
+    let retained = CanisterMigrations::new(btreemap! {
+        canister_id_range(2, 2) => vec![own_subnet_id, other_subnet_id],
+    });
+    assert_eq!(
+        *retain_undrained_migrations(
+            &previous,
+            Arc::clone(&current),
+            own_subnet_id,
+            &streams,
+            &mut draining_migrations
+        ),
+        retained
+    );
+    assert_eq!(
+        draining_migrations,
+        btreemap! {
+            canister_id_range(2, 2) => btreemap! { other_subnet_id => StreamIndex::new(1) },
+        }
+    );
+
+    // Messages enqueued after the completion do not hold back the migration: it
+    // is dropped once the stream is garbage collected up to the recorded index.
+    let stream = streams.get_mut(&other_subnet_id).unwrap();
+    stream.push(
+        RequestBuilder::new()
+            .sender(canister_test_id(7))
+            .receiver(canister_test_id(2))
+            .build()
+            .into(),
+    );
+    assert_eq!(
+        *retain_undrained_migrations(
+            &retained,
+            Arc::clone(&current),
+            own_subnet_id,
+            &streams,
+            &mut draining_migrations
+        ),
+        retained
+    );
+    streams
+        .get_mut(&other_subnet_id)
+        .unwrap()
+        .discard_before(StreamIndex::new(1));
+    assert_eq!(
+        *retain_undrained_migrations(
+            &retained,
+            current,
+            own_subnet_id,
+            &streams,
+            &mut draining_migrations
+        ),
+        CanisterMigrations::default()
+    );
+    assert!(draining_migrations.is_empty());
+}
@@ -71,7 +71,7 @@ use ic_protobuf::p2p::v1 as pb;
 use ic_protobuf::proxy::ProtoProxy;
 use ic_types::{
     artifact::{Artifact, ArtifactId},
-    chunkable::{ArtifactErrorCode, ChunkId},
+    chunkable::{ArtifactChunk, ArtifactChunkData, ArtifactErrorCode, ChunkId},
     crypto::CryptoHash,
     p2p::GossipAdvert,
     transport::{FlowTag, TransportClientType, TransportPayload},
@@ -103,7 +103,7 @@ use std::{
     collections::HashMap,
     error::Error,
     sync::{Arc, Mutex, RwLock},
-    time::{Instant, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use ic_interfaces::artifact_manager::OnArtifactError::ArtifactPoolError;
@@ -221,6 +221,66 @@ struct GossipRequestTracker {
     requested_instant: Instant,
 }
 
+/// An estimate of the rate at which a peer delivers the state sync chunks
+/// requested from it.
+///
+/// The estimate is an exponentially weighted moving average over the
+/// delivered (and timed-out) chunks. It is used to split the chunks of a
+/// state sync among the peers advertising it proportionally to their
+/// throughput.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PeerThroughput {
+    /// The estimated throughput in bytes per millisecond, or `None` if no
+    /// chunk was requested from the peer yet.
+    bytes_per_ms: Option<f64>,
+}
+
+impl PeerThroughput {
+    /// The weight of the latest observation in the moving average.
+    const SMOOTHING_FACTOR: f64 = 0.2;
+
+    /// Records that the peer delivered `bytes` in `elapsed` time. A timed-out
+    /// request is recorded as 0 bytes delivered.
+    fn observe(&mut self, bytes: usize, elapsed: Duration) {
+        let sample = bytes as f64 / (elapsed.as_secs_f64() * 1000.0).max(1.0);
+        self.bytes_per_ms = Some(match self.bytes_per_ms {
+            Some(estimate) => estimate + Self::SMOOTHING_FACTOR * (sample - estimate),
+            None => sample,
+        });
+    }
+}
+
+/// Returns how many of the `max_streams` concurrent chunk requests for a
+/// state sync can be sent to `peer_id`.
+///
+/// Every peer advertising the state gets a share proportional to its
+/// throughput relative to the fastest of the `advertisers`, but at least one
+/// stream, so that the throughput of slow peers keeps being probed. Peers
+/// without an estimate yet get all the streams.
+fn weighted_state_sync_streams(
+    peers: &PeerContextDictionary,
+    peer_id: &NodeId,
+    advertisers: &[NodeId],
+    max_streams: usize,
+) -> usize {
+    let throughput = match peers
+        .get(peer_id)
+        .and_then(|peer_context| peer_context.throughput.bytes_per_ms)
+    {
+        Some(throughput) => throughput,
+        None => return max_streams,
+    };
+    let fastest = advertisers
+        .iter()
+        .filter_map(|advertiser| peers.get(advertiser))
+        .filter_map(|peer_context| peer_context.throughput.bytes_per_ms)
+        .fold(throughput, f64::max);
+    if fastest <= 0.0 {
+        return max_streams;
+    }
+    ((max_streams as f64 * throughput / fastest).ceil() as usize).clamp(1, max_streams)
+}
+
 /// The peer context for a certain peer.
 /// It keeps track of the requested chunks at any point in time.
 #[allow(dead_code)]
@@ -230,6 +290,8 @@ pub struct PeerContext {
     peer_id: NodeId,
     /// The dictionary containing the requested chunks.
     requested: HashMap<GossipRequestTrackerKey, GossipRequestTracker>,
+    /// The throughput of the peer when serving state sync chunks.
+    throughput: PeerThroughput,
     /// The time when the peer was disconnected.
     disconnect_time: Option<SystemTime>,
     /// The time of the last processed retransmission request from this peer.
@@ -244,6 +306,7 @@ impl From<NodeId> for PeerContext {
         PeerContext {
             peer_id,
             requested: HashMap::new(),
+            throughput: PeerThroughput::default(),
             disconnect_time: None,
             last_retransmission_request_processed_time: Instant::now(),
         }
@@ -446,11 +509,24 @@ impl DownloadManager for DownloadManagerImpl {
                     ArtifactId::EcdsaMessage(_) => "ecdsa",
                     ArtifactId::FileTreeSync(_) => "file_tree_sync",
                     ArtifactId::StateSync(_) => "state_sync",
+                    ArtifactId::CanisterHttpMessage(_) => "canister_http",
                 };
                 self.metrics
                     .chunk_delivery_time
                     .with_label_values(&[artifact_type])
                     .observe(tracker.requested_instant.elapsed().as_millis() as f64);
+                if let (
+                    ArtifactId::StateSync(_),
+                    Ok(ArtifactChunk {
+                        artifact_chunk_data: ArtifactChunkData::SemiStructuredChunkData(data),
+                        ..
+                    }),
+                ) = (&gossip_chunk.artifact_id, &gossip_chunk.artifact_chunk)
+                {
+                    peer_context
+                        .throughput
+                        .observe(data.len(), tracker.requested_instant.elapsed());
+                }
             } else {
                 trace!(
                     self.log,
@@ -592,6 +668,7 @@ impl DownloadManager for DownloadManagerImpl {
             // Thus, we make up the integrity_hash.
             Artifact::FileTreeSync(_msg) => CryptoHash(vec![]),
             Artifact::StateSync(msg) => ic_crypto::crypto_hash(msg).get(),
+            Artifact::CanisterHttpMessage(msg) => ic_crypto::crypto_hash(msg).get(),
         };
 
         if expected_ih != advert.integrity_hash {
@@ -1242,6 +1319,30 @@ impl DownloadManagerImpl {
                 current_peers.len() as u32,
                 self.artifact_manager.as_ref(),
             ) {
+                // The chunks of a state sync are spread over all the peers advertising the
+                // same state: each peer only gets a share of its streams for the artifact
+                // that is weighted by its throughput, so that faster peers serve more
+                // chunks while the remaining ones are left to the other advertisers.
+                let max_artifact_requests = match advert_tracker.advert.artifact_id {
+                    ArtifactId::StateSync(_) => {
+                        let in_flight = peer_context
+                            .requested
+                            .keys()
+                            .filter(|key| {
+                                key.integrity_hash == advert_tracker.advert.integrity_hash
+                            })
+                            .count();
+                        weighted_state_sync_streams(
+                            &current_peers,
+                            &peer_id,
+                            &advert_tracker.peers,
+                            max_streams_per_peer,
+                        )
+                        .saturating_sub(in_flight)
+                    }
+                    _ => max_streams_per_peer,
+                };
+
                 // Collect gossip requests that can be initiated for this artifact.
                 // The function get_chunk_request() returns requests for chunks that satisfy
                 // chunk download constraints. These requests are collected and download
@@ -1256,7 +1357,10 @@ impl DownloadManagerImpl {
                                 req
                             })
                     })
-                    .take(num_downloadable_chunks - requests.len());
+                    .take(std::cmp::min(
+                        num_downloadable_chunks - requests.len(),
+                        max_artifact_requests,
+                    ));
 
                 // Extend the requests to be send out to this peer
                 requests.extend(new_chunk_requests);
@@ -1346,12 +1450,16 @@ impl DownloadManagerImpl {
     fn process_timed_out_requests(&self, node_id: &NodeId, peer_context: &mut PeerContext) -> bool {
         // Mark time-out chunks.
         let mut timed_out_chunks: Vec<_> = Vec::new();
+        let mut timed_out_state_sync_chunks = 0;
         let mut peer_timed_out: bool = false;
         peer_context.requested.retain(|key, tracker| {
             let timed_out = tracker.requested_instant.elapsed().as_millis()
                 >= self.gossip_config.max_chunk_wait_ms as u128;
             if timed_out {
                 self.metrics.chunks_timed_out.inc();
+                if let ArtifactId::StateSync(_) = key.artifact_id {
+                    timed_out_state_sync_chunks += 1;
+                }
                 timed_out_chunks.push((
                     *node_id,
                     key.chunk_id,
@@ -1373,6 +1481,13 @@ impl DownloadManagerImpl {
             !timed_out
         });
 
+        // A timed-out state sync chunk counts as nothing delivered, lowering the
+        // peer's share of future state sync chunk requests.
+        let max_chunk_wait = Duration::from_millis(self.gossip_config.max_chunk_wait_ms as u64);
+        for _ in 0..timed_out_state_sync_chunks {
+            peer_context.throughput.observe(0, max_chunk_wait);
+        }
+
         for (node_id, chunk_id, artifact_id, integrity_hash) in timed_out_chunks.into_iter() {
             self.process_timed_out_chunk(&node_id, artifact_id, integrity_hash, chunk_id)
         }
@@ -2554,6 +2669,58 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_state_sync_streams_weighted_by_throughput() {
+        let mut current_peers = PeerContextDictionary::default();
+        let advertisers: Vec<_> = (1..4).map(node_test_id).collect();
+        for node_id in &advertisers {
+            current_peers.insert(*node_id, (*node_id).into());
+        }
+
+        // Peers without a throughput estimate get all the streams.
+        assert_eq!(
+            weighted_state_sync_streams(&current_peers, &advertisers[0], &advertisers, 20),
+            20
+        );
+
+        let throughput = |peers: &mut PeerContextDictionary, id: NodeId, bytes: usize| {
+            peers
+                .get_mut(&id)
+                .unwrap()
+                .throughput
+                .observe(bytes, Duration::from_millis(100))
+        };
+        throughput(&mut current_peers, advertisers[0], 100_000);
+        throughput(&mut current_peers, advertisers[1], 25_000);
+        throughput(&mut current_peers, advertisers[2], 0);
+
+        assert_eq!(
+            weighted_state_sync_streams(&current_peers, &advertisers[0], &advertisers, 20),
+            20
+        );
+        assert_eq!(
+            weighted_state_sync_streams(&current_peers, &advertisers[1], &advertisers, 20),
+            5
+        );
+        // Peers that timed out keep one stream to be probed again.
+        assert_eq!(
+            weighted_state_sync_streams(&current_peers, &advertisers[2], &advertisers, 20),
+            1
+        );
+    }
+
+    #[test]
+    fn test_peer_throughput_is_a_moving_average() {
+        let mut throughput = PeerThroughput::default();
+        assert_eq!(throughput.bytes_per_ms, None);
+
+        throughput.observe(1000, Duration::from_millis(10));
+        assert_eq!(throughput.bytes_per_ms, Some(100.0));
+
+        throughput.observe(0, Duration::from_millis(10));
+        assert_eq!(throughput.bytes_per_ms, Some(80.0));
+    }
+
     #[test]
     fn test_advert_random_subset_with_no_peers() {
         let peer_manager = PeerManagerImpl::new(
@@ -374,6 +374,7 @@ struct ClientAdvertMap {
     ecdsa: ClientAdvertMapInt,
     file_tree_sync: ClientAdvertMapInt,
     state: ClientAdvertMapInt,
+    canister_http: ClientAdvertMapInt,
 }
 
 /// A single client advert tracking data structure
@@ -404,6 +405,7 @@ impl Index<&ArtifactId> for ClientAdvertMap {
             ArtifactId::EcdsaMessage(_) => &self.ecdsa,
             ArtifactId::FileTreeSync(_) => &self.file_tree_sync,
             ArtifactId::StateSync(_) => &self.state,
+            ArtifactId::CanisterHttpMessage(_) => &self.canister_http,
         }
     }
 }
@@ -418,6 +420,7 @@ impl IndexMut<&ArtifactId> for ClientAdvertMap {
             ArtifactId::EcdsaMessage(_) => &mut self.ecdsa,
             ArtifactId::FileTreeSync(_) => &mut self.file_tree_sync,
             ArtifactId::StateSync(_) => &mut self.state,
+            ArtifactId::CanisterHttpMessage(_) => &mut self.canister_http,
         }
     }
 }
@@ -433,6 +436,7 @@ impl Index<ArtifactTag> for ClientAdvertMap {
             ArtifactTag::EcdsaArtifact => &self.ecdsa,
             ArtifactTag::FileTreeSyncArtifact => &self.file_tree_sync,
             ArtifactTag::StateSyncArtifact => &self.state,
+            ArtifactTag::CanisterHttpArtifact => &self.canister_http,
         }
     }
 }
@@ -447,6 +451,7 @@ impl IndexMut<ArtifactTag> for ClientAdvertMap {
             ArtifactTag::EcdsaArtifact => &mut self.ecdsa,
             ArtifactTag::FileTreeSyncArtifact => &mut self.file_tree_sync,
             ArtifactTag::StateSyncArtifact => &mut self.state,
+            ArtifactTag::CanisterHttpArtifact => &mut self.canister_http,
         }
     }
 }
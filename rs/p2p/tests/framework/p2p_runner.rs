@@ -1,4 +1,5 @@
 use crate::framework::file_tree_artifact_mgr::ArtifactChunkingTestImpl;
+use ic_artifact_pool::canister_http_pool::CanisterHttpPoolImpl;
 use ic_config::execution_environment::Config as HypervisorConfig;
 use ic_config::subnet_config::SubnetConfigs;
 use ic_cycles_account_manager::CyclesAccountManager;
@@ -10,6 +11,7 @@ use ic_registry_client::client::RegistryClientImpl;
 use ic_registry_subnet_type::SubnetType;
 use ic_replica_setup_ic_network::{create_networking_stack, P2PStateSyncClient};
 use ic_test_utilities::{
+    canister_http_payload_builder::FakeCanisterHttpPayloadBuilder,
     consensus::make_catch_up_package_with_empty_transcript,
     crypto::fake_tls_handshake::FakeTlsHandshake,
     crypto::CryptoReturningOk,
@@ -24,7 +26,7 @@ use ic_test_utilities::{
     xnet_payload_builder::FakeXNetPayloadBuilder,
 };
 use ic_types::{consensus::catchup::CUPWithOriginalProtobuf, replica_config::ReplicaConfig};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use tempfile::Builder;
 
@@ -70,6 +72,8 @@ fn execute_test(
         let xnet_payload_builder = Arc::new(xnet_payload_builder);
         let self_validating_payload_builder = FakeSelfValidatingPayloadBuilder::new();
         let self_validating_payload_builder = Arc::new(self_validating_payload_builder);
+        let canister_http_payload_builder = FakeCanisterHttpPayloadBuilder::new();
+        let canister_http_payload_builder = Arc::new(canister_http_payload_builder);
         let no_state_sync_client = P2PStateSyncClient::TestClient();
         let ingress_hist_reader = Box::new(IngressHistoryReaderImpl::new(
             Arc::clone(&state_manager) as Arc<_>,
@@ -99,6 +103,11 @@ fn execute_test(
             no_state_sync_client,
             xnet_payload_builder as Arc<_>,
             self_validating_payload_builder as Arc<_>,
+            canister_http_payload_builder as Arc<_>,
+            Arc::new(RwLock::new(CanisterHttpPoolImpl::new(
+                metrics_registry.clone(),
+            ))),
+            None,
             message_router as Arc<_>,
             Arc::clone(&fake_crypto) as Arc<_>,
             Arc::clone(&fake_crypto) as Arc<_>,
@@ -221,6 +230,8 @@ fn execute_test_chunking_pool(
         let xnet_payload_builder = Arc::new(xnet_payload_builder);
         let self_validating_payload_builder = FakeSelfValidatingPayloadBuilder::new();
         let self_validating_payload_builder = Arc::new(self_validating_payload_builder);
+        let canister_http_payload_builder = FakeCanisterHttpPayloadBuilder::new();
+        let canister_http_payload_builder = Arc::new(canister_http_payload_builder);
         let fake_crypto = CryptoReturningOk::default();
         let fake_crypto = Arc::new(fake_crypto);
         let node_pool_dir = test_synchronizer.get_test_group_directory();
@@ -252,6 +263,11 @@ fn execute_test_chunking_pool(
             state_sync_client,
             xnet_payload_builder,
             self_validating_payload_builder,
+            canister_http_payload_builder,
+            Arc::new(RwLock::new(CanisterHttpPoolImpl::new(
+                metrics_registry.clone(),
+            ))),
+            None,
             message_router,
             Arc::clone(&fake_crypto) as Arc<_>,
             Arc::clone(&fake_crypto) as Arc<_>,
@@ -226,6 +226,10 @@ fn build_registry_proto() {
         ".registry.subnet.v1.EcdsaConfig",
         "#[derive(candid::CandidType, Eq)]",
     );
+    config.type_attribute(
+        ".registry.subnet.v1.EcdsaKeyId",
+        "#[derive(candid::CandidType, Eq)]",
+    );
     config.type_attribute(
         ".registry.replica_version",
         "#[derive(serde::Serialize, serde::Deserialize)]",
@@ -62,7 +62,10 @@ use ic_protobuf::registry::{
     provisional_whitelist::v1::ProvisionalWhitelist as ProvisionalWhitelistProto,
     replica_version::v1::{BlessedReplicaVersions, ReplicaVersionRecord},
     routing_table::v1::RoutingTable,
-    subnet::v1::{EcdsaConfig, SubnetListRecord, SubnetRecord as SubnetRecordProto},
+    subnet::v1::{
+        EcdsaConfig, EcdsaCurve as EcdsaCurveProto, EcdsaKeyId as EcdsaKeyIdProto,
+        SubnetListRecord, SubnetRecord as SubnetRecordProto,
+    },
     unassigned_nodes_config::v1::UnassignedNodesConfigRecord,
 };
 use ic_registry_client::client::RegistryClientImpl;
@@ -1148,6 +1151,11 @@ struct ProposeToUpdateSubnetCmd {
     #[clap(long)]
     pub ecdsa_quadruples_to_create_in_advance: Option<u32>,
 
+    /// The names of the secp256k1 threshold ECDSA keys held by the subnet.
+    /// Only used together with `--ecdsa-quadruples-to-create-in-advance`.
+    #[clap(long)]
+    pub ecdsa_key_names: Vec<String>,
+
     /// The features that are enabled and disabled on the subnet.
     #[clap(long)]
     pub features: Option<SubnetFeatures>,
@@ -1216,6 +1224,14 @@ impl ProposalTitleAndPayload<UpdateSubnetPayload> for ProposeToUpdateSubnetCmd {
                 .ecdsa_quadruples_to_create_in_advance
                 .map(|val| EcdsaConfig {
                     quadruples_to_create_in_advance: val,
+                    key_ids: self
+                        .ecdsa_key_names
+                        .iter()
+                        .map(|name| EcdsaKeyIdProto {
+                            curve: EcdsaCurveProto::Secp256k1 as i32,
+                            name: name.clone(),
+                        })
+                        .collect(),
                 }),
             ssh_readonly_access: self.ssh_readonly_access.clone(),
             ssh_backup_access: self.ssh_backup_access.clone(),
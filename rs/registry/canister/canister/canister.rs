@@ -36,10 +36,13 @@ use registry_canister::{
         do_add_node::AddNodePayload, do_add_node_operator::AddNodeOperatorPayload,
         do_add_nodes_to_subnet::AddNodesToSubnetPayload,
         do_bless_replica_version::BlessReplicaVersionPayload,
+        do_complete_canister_migration::CompleteCanisterMigrationPayload,
         do_create_subnet::CreateSubnetPayload, do_delete_subnet::DeleteSubnetPayload,
         do_recover_subnet::RecoverSubnetPayload,
         do_remove_node_directly::RemoveNodeDirectlyPayload, do_remove_nodes::RemoveNodesPayload,
         do_remove_nodes_from_subnet::RemoveNodesFromSubnetPayload,
+        do_reroute_canister_ranges::RerouteCanisterRangesPayload,
+        do_split_subnet::SplitSubnetPayload,
         do_update_node_operator_config::UpdateNodeOperatorConfigPayload,
         do_update_subnet::UpdateSubnetPayload,
         do_update_subnet_replica::UpdateSubnetReplicaVersionPayload,
@@ -558,6 +561,33 @@ fn update_unassigned_nodes_config() {
     });
 }
 
+#[export_name = "canister_update reroute_canister_ranges"]
+fn reroute_canister_ranges() {
+    check_caller_is_governance_and_log("reroute_canister_ranges");
+    over(candid_one, |payload: RerouteCanisterRangesPayload| {
+        registry_mut().do_reroute_canister_ranges(payload);
+        recertify_registry();
+    });
+}
+
+#[export_name = "canister_update split_subnet"]
+fn split_subnet() {
+    check_caller_is_governance_and_log("split_subnet");
+    over(candid_one, |payload: SplitSubnetPayload| {
+        registry_mut().do_split_subnet(payload);
+        recertify_registry();
+    });
+}
+
+#[export_name = "canister_update complete_canister_migration"]
+fn complete_canister_migration() {
+    check_caller_is_governance_and_log("complete_canister_migration");
+    over(candid_one, |payload: CompleteCanisterMigrationPayload| {
+        registry_mut().do_complete_canister_migration(payload);
+        recertify_registry();
+    });
+}
+
 #[export_name = "canister_query get_node_providers_monthly_xdr_rewards"]
 fn get_node_providers_monthly_xdr_rewards() {
     check_caller_is_governance_and_log("get_node_providers_monthly_xdr_rewards");
@@ -3,11 +3,14 @@ use crate::invariants::common::{InvariantCheckError, RegistrySnapshot};
 use std::convert::TryFrom;
 
 use ic_nns_common::registry::decode_or_panic;
-use ic_protobuf::registry::routing_table::v1::RoutingTable as pbRoutingTable;
-use ic_registry_keys::make_routing_table_record_key;
-use ic_registry_routing_table::RoutingTable;
+use ic_protobuf::registry::routing_table::v1::{
+    CanisterMigrations as pbCanisterMigrations, RoutingTable as pbRoutingTable,
+};
+use ic_registry_keys::{make_canister_migrations_record_key, make_routing_table_record_key};
+use ic_registry_routing_table::{CanisterMigrations, RoutingTable};
 
-/// Routing table invariants hold if it is well formed
+/// Routing table invariants hold if it is well formed and so are the canister
+/// migrations, if any
 pub(crate) fn check_routing_table_invariants(
     snapshot: &RegistrySnapshot,
 ) -> Result<(), InvariantCheckError> {
@@ -16,7 +19,18 @@ pub(crate) fn check_routing_table_invariants(
         .map_err(|e| InvariantCheckError {
             msg: format!("routing table is not well formed {:?}", e),
             source: None,
-        })
+        })?;
+    match get_canister_migrations(snapshot) {
+        Some(canister_migrations) => {
+            canister_migrations
+                .well_formed()
+                .map_err(|e| InvariantCheckError {
+                    msg: format!("canister migrations are not well formed {:?}", e),
+                    source: None,
+                })
+        }
+        None => Ok(()),
+    }
 }
 
 // Return routing table from snapshot
@@ -29,3 +43,15 @@ fn get_routing_table(snapshot: &RegistrySnapshot) -> RoutingTable {
         None => panic!("No routing table in snapshot"),
     }
 }
+
+// Return canister migrations from snapshot, if present
+fn get_canister_migrations(snapshot: &RegistrySnapshot) -> Option<CanisterMigrations> {
+    snapshot
+        .get(make_canister_migrations_record_key().as_bytes())
+        .map(|canister_migrations_vec| {
+            CanisterMigrations::try_from(decode_or_panic::<pbCanisterMigrations>(
+                (*canister_migrations_vec).clone(),
+            ))
+            .unwrap()
+        })
+}
@@ -0,0 +1,102 @@
+use crate::{
+    common::LOG_PREFIX,
+    mutations::{
+        common::decode_registry_value, routing_table::canister_migrations_into_registry_mutation,
+    },
+    registry::Registry,
+};
+
+use std::convert::TryFrom;
+
+use candid::{CandidType, Deserialize};
+use ic_base_types::{subnet_id_try_from_protobuf, PrincipalId, SubnetId};
+use ic_protobuf::types::v1::SubnetId as SubnetIdProto;
+use ic_registry_keys::make_subnet_split_record_key;
+use ic_registry_routing_table::{CanisterIdRange, CanisterIdRanges};
+use ic_registry_transport::{delete, pb::v1::registry_mutation};
+use serde::Serialize;
+
+impl Registry {
+    /// Removes the given canister ID ranges from the set of ongoing canister
+    /// migrations. The ranges must already be assigned to the last subnet of
+    /// the migration trace.
+    ///
+    /// Completion is gated on the streams being drained by Message Routing:
+    /// every subnet on the trace keeps applying the migration (rerouting
+    /// responses and rejecting requests to the migrated canisters) until its
+    /// own streams no longer hold messages to or from them. Only then does it
+    /// drop the migration and, on the source subnet, the migrated canisters.
+    ///
+    /// If the migration is the one of a subnet split, the record of the split
+    /// is removed as well.
+    pub fn do_complete_canister_migration(&mut self, payload: CompleteCanisterMigrationPayload) {
+        println!(
+            "{}do_complete_canister_migration: {:?}",
+            LOG_PREFIX, payload
+        );
+
+        let ranges = CanisterIdRanges::try_from(payload.canister_id_ranges).unwrap_or_else(|err| {
+            panic!(
+                "{}do_complete_canister_migration: invalid canister ID ranges: {:?}",
+                LOG_PREFIX, err
+            )
+        });
+        let migration_trace: Vec<SubnetId> = payload
+            .migration_trace
+            .into_iter()
+            .map(SubnetId::from)
+            .collect();
+
+        let version = self.latest_version();
+        if let Some(destination_subnet) = migration_trace.last() {
+            let routing_table = self.get_routing_table_or_panic(version);
+            for range in ranges.iter() {
+                if !routing_table.is_assigned_to(range, *destination_subnet) {
+                    panic!(
+                        "{}do_complete_canister_migration: range {:?} is not assigned to subnet {}",
+                        LOG_PREFIX, range, destination_subnet
+                    );
+                }
+            }
+        }
+
+        let mut canister_migrations = self.get_canister_migrations(version);
+        canister_migrations
+            .remove_ranges(ranges, &migration_trace)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "{}do_complete_canister_migration: cannot remove migration: {:?}",
+                    LOG_PREFIX, err
+                )
+            });
+
+        let mut mutations = vec![canister_migrations_into_registry_mutation(
+            canister_migrations,
+            registry_mutation::Type::Upsert as i32,
+        )];
+        if let (Some(source_subnet), Some(destination_subnet)) =
+            (migration_trace.first(), migration_trace.last())
+        {
+            let split_key = make_subnet_split_record_key(*source_subnet);
+            let is_split = self
+                .get(split_key.as_bytes(), version)
+                .map(|value| decode_registry_value::<SubnetIdProto>(value.value.clone()))
+                .and_then(|split_destination| subnet_id_try_from_protobuf(split_destination).ok())
+                == Some(*destination_subnet);
+            if is_split {
+                mutations.push(delete(split_key.as_bytes()));
+            }
+        }
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+    }
+}
+
+/// The payload of a request to complete the migration of canister ID ranges
+/// along the given trace of subnets (source first, destination last).
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CompleteCanisterMigrationPayload {
+    pub canister_id_ranges: Vec<CanisterIdRange>,
+    pub migration_trace: Vec<PrincipalId>,
+}
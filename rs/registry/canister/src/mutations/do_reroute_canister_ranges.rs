@@ -0,0 +1,107 @@
+use crate::{
+    common::LOG_PREFIX,
+    mutations::routing_table::{
+        canister_migrations_into_registry_mutation, into_registry_mutation,
+    },
+    registry::Registry,
+};
+
+use std::convert::TryFrom;
+
+use candid::{CandidType, Deserialize};
+use ic_base_types::{PrincipalId, SubnetId};
+use ic_registry_routing_table::{CanisterIdRange, CanisterIdRanges};
+use ic_registry_transport::pb::v1::registry_mutation;
+use serde::Serialize;
+
+impl Registry {
+    /// Reassigns the given canister ID ranges from the source subnet to the
+    /// destination subnet in the routing table, and records the migration
+    /// so that Message Routing can reroute or reject messages that are still
+    /// in flight to or from the source subnet.
+    ///
+    /// From the next round on, the source subnet no longer executes the
+    /// canisters in the ranges. Their state is exported from the source
+    /// subnet's next checkpoint and imported into the destination subnet's
+    /// state (see `ic_state_manager::canister_archive`). The migration is then
+    /// completed with `do_complete_canister_migration`.
+    pub fn do_reroute_canister_ranges(&mut self, payload: RerouteCanisterRangesPayload) {
+        println!("{}do_reroute_canister_ranges: {:?}", LOG_PREFIX, payload);
+
+        let source_subnet = SubnetId::from(payload.source_subnet);
+        let destination_subnet = SubnetId::from(payload.destination_subnet);
+        if source_subnet == destination_subnet {
+            panic!(
+                "{}do_reroute_canister_ranges: source and destination subnet are both {}",
+                LOG_PREFIX, source_subnet
+            );
+        }
+
+        let subnet_list = self.get_subnet_list_record();
+        for subnet_id in &[source_subnet, destination_subnet] {
+            if !subnet_list.subnets.contains(&subnet_id.get().to_vec()) {
+                panic!(
+                    "{}do_reroute_canister_ranges: subnet {} does not exist",
+                    LOG_PREFIX, subnet_id
+                );
+            }
+        }
+
+        let ranges =
+            CanisterIdRanges::try_from(payload.reassigned_canister_ranges).unwrap_or_else(|err| {
+                panic!(
+                    "{}do_reroute_canister_ranges: invalid canister ID ranges: {:?}",
+                    LOG_PREFIX, err
+                )
+            });
+
+        let version = self.latest_version();
+        let mut routing_table = self.get_routing_table_or_panic(version);
+        for range in ranges.iter() {
+            if !routing_table.is_assigned_to(range, source_subnet) {
+                panic!(
+                    "{}do_reroute_canister_ranges: range {:?} is not entirely hosted by subnet {}",
+                    LOG_PREFIX, range, source_subnet
+                );
+            }
+        }
+
+        let mut canister_migrations = self.get_canister_migrations(version);
+        canister_migrations
+            .insert_ranges(ranges.clone(), source_subnet, destination_subnet)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "{}do_reroute_canister_ranges: cannot record migration: {:?}",
+                    LOG_PREFIX, err
+                )
+            });
+        routing_table
+            .assign_ranges(ranges, destination_subnet)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "{}do_reroute_canister_ranges: cannot reassign ranges: {:?}",
+                    LOG_PREFIX, err
+                )
+            });
+
+        let mutations = vec![
+            into_registry_mutation(routing_table, registry_mutation::Type::Upsert as i32),
+            canister_migrations_into_registry_mutation(
+                canister_migrations,
+                registry_mutation::Type::Upsert as i32,
+            ),
+        ];
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+    }
+}
+
+/// The payload of a request to move canister ID ranges from one subnet to
+/// another.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RerouteCanisterRangesPayload {
+    pub reassigned_canister_ranges: Vec<CanisterIdRange>,
+    pub source_subnet: PrincipalId,
+    pub destination_subnet: PrincipalId,
+}
@@ -0,0 +1,75 @@
+use crate::{
+    common::LOG_PREFIX,
+    mutations::{
+        common::encode_or_panic, do_reroute_canister_ranges::RerouteCanisterRangesPayload,
+    },
+    registry::Registry,
+};
+
+use candid::{CandidType, Deserialize};
+use ic_base_types::{subnet_id_into_protobuf, PrincipalId, SubnetId};
+use ic_registry_keys::make_subnet_split_record_key;
+use ic_registry_transport::upsert;
+use serde::Serialize;
+
+impl Registry {
+    /// Splits a subnet in two by moving the upper half of its canister ID
+    /// ranges to the destination subnet.
+    ///
+    /// The destination subnet is expected to have been created with the
+    /// source subnet's membership split off and to start from a copy of the
+    /// source subnet's state. The split is recorded in the registry. Both
+    /// halves are then halted at the same height and every node prunes the
+    /// parts of that checkpoint that no longer belong to its half offline
+    /// (`state_tool split-checkpoint`), based on the updated routing table,
+    /// writing the result as a new checkpoint at an agreed height; each half
+    /// restarts from a recovery CUP carrying the hash of that state. The moved
+    /// ranges are recorded as canister migrations, so messages in flight
+    /// during the split are rerouted; the migration is completed with
+    /// `do_complete_canister_migration`, as for any other migration, which
+    /// also removes the record of the split.
+    pub fn do_split_subnet(&mut self, payload: SplitSubnetPayload) {
+        println!("{}do_split_subnet: {:?}", LOG_PREFIX, payload);
+
+        let source_subnet = SubnetId::from(payload.source_subnet_id);
+        let routing_table = self.get_routing_table_or_panic(self.latest_version());
+        let (_, moved_ranges) = routing_table
+            .ranges(source_subnet)
+            .bisect()
+            .unwrap_or_else(|| {
+                panic!(
+                    "{}do_split_subnet: subnet {} does not host enough canister ID ranges to be split",
+                    LOG_PREFIX, source_subnet
+                )
+            });
+
+        self.do_reroute_canister_ranges(RerouteCanisterRangesPayload {
+            reassigned_canister_ranges: moved_ranges.iter().cloned().collect(),
+            source_subnet: payload.source_subnet_id,
+            destination_subnet: payload.destination_subnet_id,
+        });
+
+        let mutations = vec![upsert(
+            make_subnet_split_record_key(source_subnet)
+                .as_bytes()
+                .to_vec(),
+            encode_or_panic(&subnet_id_into_protobuf(SubnetId::from(
+                payload.destination_subnet_id,
+            ))),
+        )];
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+    }
+}
+
+/// The payload of a request to split a subnet in two.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SplitSubnetPayload {
+    /// The subnet being split. It retains the lower half of its canister ID
+    /// ranges.
+    pub source_subnet_id: PrincipalId,
+    /// The subnet that takes over the upper half of the source subnet's
+    /// canister ID ranges.
+    pub destination_subnet_id: PrincipalId,
+}
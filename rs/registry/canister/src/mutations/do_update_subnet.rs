@@ -311,9 +311,11 @@ mod tests {
             features: Some(SubnetFeatures {
                 ecdsa_signatures: false,
                 canister_sandboxing: false,
+                bitcoin_network: None,
             }),
             ecdsa_config: Some(EcdsaConfig {
                 quadruples_to_create_in_advance: 10,
+                key_ids: vec![],
             }),
             max_number_of_canisters: Some(10),
             ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
@@ -356,11 +358,13 @@ mod tests {
                     SubnetFeatures {
                         ecdsa_signatures: false,
                         canister_sandboxing: false,
+                        bitcoin_network: None,
                     }
                     .into()
                 ),
                 ecdsa_config: Some(EcdsaConfig {
                     quadruples_to_create_in_advance: 10,
+                    key_ids: vec![],
                 }),
                 max_number_of_canisters: 10,
                 ssh_readonly_access: vec!["pub_key_0".to_string()],
@@ -6,13 +6,16 @@ pub mod do_add_nodes_to_subnet;
 mod do_add_or_remove_data_centers;
 pub mod do_bless_replica_version;
 pub mod do_clear_provisional_whitelist;
+pub mod do_complete_canister_migration;
 pub mod do_create_subnet;
 pub mod do_delete_subnet;
 pub mod do_recover_subnet;
 pub mod do_remove_node_directly;
 pub mod do_remove_nodes;
 pub mod do_remove_nodes_from_subnet;
+pub mod do_reroute_canister_ranges;
 pub mod do_set_firewall_config;
+pub mod do_split_subnet;
 pub mod do_update_icp_xdr_conversion_rate;
 pub mod do_update_node_operator_config;
 pub mod do_update_node_rewards_table;
@@ -4,12 +4,15 @@ use std::{collections::BTreeMap, convert::TryFrom};
 
 use ic_base_types::SubnetId;
 use ic_protobuf::registry::routing_table::v1 as pb;
-use ic_registry_keys::make_routing_table_record_key;
-use ic_registry_routing_table::{routing_table_insert_subnet, RoutingTable};
+use ic_registry_keys::{make_canister_migrations_record_key, make_routing_table_record_key};
+use ic_registry_routing_table::{routing_table_insert_subnet, CanisterMigrations, RoutingTable};
 use ic_registry_transport::pb::v1::{RegistryMutation, RegistryValue};
 use prost::Message;
 
-fn into_registry_mutation(routing_table: RoutingTable, mutation_type: i32) -> RegistryMutation {
+pub(crate) fn into_registry_mutation(
+    routing_table: RoutingTable,
+    mutation_type: i32,
+) -> RegistryMutation {
     let routing_table = pb::RoutingTable::from(routing_table);
     let mut buf = vec![];
     routing_table.encode(&mut buf).unwrap();
@@ -20,7 +23,52 @@ fn into_registry_mutation(routing_table: RoutingTable, mutation_type: i32) -> Re
     }
 }
 
+pub(crate) fn canister_migrations_into_registry_mutation(
+    canister_migrations: CanisterMigrations,
+    mutation_type: i32,
+) -> RegistryMutation {
+    let canister_migrations = pb::CanisterMigrations::from(canister_migrations);
+    let mut buf = vec![];
+    canister_migrations.encode(&mut buf).unwrap();
+    RegistryMutation {
+        mutation_type,
+        key: make_canister_migrations_record_key().as_bytes().to_vec(),
+        value: buf,
+    }
+}
+
 impl Registry {
+    /// Returns the routing table at the given version.
+    pub fn get_routing_table_or_panic(&self, version: u64) -> RoutingTable {
+        let RegistryValue {
+            value: routing_table_vec,
+            version: _,
+            deletion_marker: _,
+        } = self
+            .get(make_routing_table_record_key().as_bytes(), version)
+            .unwrap();
+        RoutingTable::try_from(decode_registry_value::<pb::RoutingTable>(
+            routing_table_vec.clone(),
+        ))
+        .unwrap()
+    }
+
+    /// Returns the canister migrations at the given version, or an empty set
+    /// of migrations if there is no such record.
+    pub fn get_canister_migrations(&self, version: u64) -> CanisterMigrations {
+        match self.get(make_canister_migrations_record_key().as_bytes(), version) {
+            Some(RegistryValue {
+                value: canister_migrations_vec,
+                version: _,
+                deletion_marker: _,
+            }) => CanisterMigrations::try_from(decode_registry_value::<pb::CanisterMigrations>(
+                canister_migrations_vec.clone(),
+            ))
+            .unwrap(),
+            None => CanisterMigrations::default(),
+        }
+    }
+
     /// Handle adding a subnet to the routing table.
     pub fn add_subnet_to_routing_table(
         &self,
@@ -1,16 +1,44 @@
 use ic_interfaces::registry::{RegistryClient, RegistryClientResult};
 use ic_protobuf::registry::routing_table::v1 as pb;
+use ic_protobuf::types::v1::SubnetId as SubnetIdProto;
 use ic_registry_common::values::deserialize_registry_value;
-use ic_registry_keys::make_routing_table_record_key;
-use ic_registry_routing_table::RoutingTable;
-use ic_types::RegistryVersion;
+use ic_registry_keys::{
+    make_canister_migrations_record_key, make_routing_table_record_key,
+    make_subnet_split_record_key, SUBNET_SPLIT_KEY_PREFIX,
+};
+use ic_registry_routing_table::{CanisterMigrations, RoutingTable};
+use ic_types::{registry::RegistryClientError, PrincipalId, RegistryVersion, SubnetId};
 use std::convert::TryFrom;
+use std::str::FromStr;
 
 /// A trait that allows access to `RoutingTable`.  The expectation for the
 /// forseeable future is that the `RoutingTable` will remain small enough so
 /// that we can simply return the entire struct here.
 pub trait RoutingTableRegistry {
     fn get_routing_table(&self, version: RegistryVersion) -> RegistryClientResult<RoutingTable>;
+
+    /// Returns the canister ID ranges that are being migrated between
+    /// subnets, if any.
+    fn get_canister_migrations(
+        &self,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<CanisterMigrations>;
+
+    /// Returns the subnet that takes over part of the canister ID ranges of
+    /// `subnet_id`, if `subnet_id` is being split.
+    fn get_subnet_split_destination(
+        &self,
+        subnet_id: SubnetId,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<SubnetId>;
+
+    /// Returns the subnet being split and the subnet it is being split into,
+    /// if `subnet_id` is either of them.
+    fn get_subnet_split(
+        &self,
+        subnet_id: SubnetId,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<(SubnetId, SubnetId)>;
 }
 
 impl<T: RegistryClient + ?Sized> RoutingTableRegistry for T {
@@ -21,4 +49,61 @@ impl<T: RegistryClient + ?Sized> RoutingTableRegistry for T {
                 .map(|pb_routing_table| RoutingTable::try_from(pb_routing_table).unwrap())
         })
     }
+
+    fn get_canister_migrations(
+        &self,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<CanisterMigrations> {
+        let bytes = self.get_value(&make_canister_migrations_record_key(), version);
+        deserialize_registry_value::<pb::CanisterMigrations>(bytes).map(
+            |option_pb_canister_migrations| {
+                option_pb_canister_migrations.map(|pb_canister_migrations| {
+                    CanisterMigrations::try_from(pb_canister_migrations).unwrap()
+                })
+            },
+        )
+    }
+
+    fn get_subnet_split_destination(
+        &self,
+        subnet_id: SubnetId,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<SubnetId> {
+        let bytes = self.get_value(&make_subnet_split_record_key(subnet_id), version);
+        let pr_id = match deserialize_registry_value::<SubnetIdProto>(bytes)?
+            .and_then(|subnet_id_proto| subnet_id_proto.principal_id)
+        {
+            Some(pr_id) => pr_id,
+            None => return Ok(None),
+        };
+        PrincipalId::try_from(pr_id.raw)
+            .map(|principal_id| Some(SubnetId::from(principal_id)))
+            .map_err(|err| RegistryClientError::DecodeError {
+                error: format!("invalid destination of the split of {}: {}", subnet_id, err),
+            })
+    }
+
+    fn get_subnet_split(
+        &self,
+        subnet_id: SubnetId,
+        version: RegistryVersion,
+    ) -> RegistryClientResult<(SubnetId, SubnetId)> {
+        for key in self.get_key_family(SUBNET_SPLIT_KEY_PREFIX, version)? {
+            let source_subnet_id = SubnetId::from(
+                PrincipalId::from_str(&key[SUBNET_SPLIT_KEY_PREFIX.len()..]).map_err(|err| {
+                    RegistryClientError::DecodeError {
+                        error: format!("invalid subnet split record key {}: {}", key, err),
+                    }
+                })?,
+            );
+            if let Some(destination_subnet_id) =
+                self.get_subnet_split_destination(source_subnet_id, version)?
+            {
+                if subnet_id == source_subnet_id || subnet_id == destination_subnet_id {
+                    return Ok(Some((source_subnet_id, destination_subnet_id)));
+                }
+            }
+        }
+        Ok(None)
+    }
 }
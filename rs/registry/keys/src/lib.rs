@@ -26,6 +26,7 @@ pub const CRYPTO_RECORD_KEY_PREFIX: &str = "crypto_record_";
 pub const CRYPTO_TLS_CERT_KEY_PREFIX: &str = "crypto_tls_cert_";
 pub const CRYPTO_THRESHOLD_SIGNING_KEY_PREFIX: &str = "crypto_threshold_signing_public_key_";
 pub const DATA_CENTER_KEY_PREFIX: &str = "data_center_record_";
+pub const SUBNET_SPLIT_KEY_PREFIX: &str = "subnet_split_";
 
 /// Returns the only key whose payload is the ICP/XDR conversion rate.
 pub fn make_icp_xdr_conversion_rate_record_key() -> String {
@@ -59,6 +60,19 @@ pub fn make_routing_table_record_key() -> String {
     "routing_table".to_string()
 }
 
+/// Returns the only key whose payload is the set of canister ID ranges that
+/// are being migrated between subnets.
+pub fn make_canister_migrations_record_key() -> String {
+    "canister_migrations".to_string()
+}
+
+/// Makes a key for the record of the split of subnet `subnet_id`, whose
+/// payload is the ID of the subnet that takes over part of its canister ID
+/// ranges.
+pub fn make_subnet_split_record_key(subnet_id: SubnetId) -> String {
+    format!("{}{}", SUBNET_SPLIT_KEY_PREFIX, subnet_id)
+}
+
 pub fn make_firewall_config_record_key() -> String {
     "firewall_config".to_string()
 }
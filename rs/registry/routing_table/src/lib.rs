@@ -1,10 +1,11 @@
 mod proto;
 
-use candid::Decode;
+use candid::{CandidType, Decode};
 use ic_base_types::{CanisterId, PrincipalId, SubnetId};
 use ic_ic00_types::{
-    CanisterIdRecord, InstallCodeArgs, Method as Ic00Method, Payload, ProvisionalTopUpCanisterArgs,
-    SetControllerArgs, UpdateSettingsArgs,
+    CanisterIdRecord, CanisterInfoRequest, CanisterSnapshotArgs, InstallChunkedCodeArgs,
+    InstallCodeArgs, Method as Ic00Method, Payload, ProvisionalTopUpCanisterArgs,
+    SetControllerArgs, TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, convert::TryFrom, str::FromStr, sync::Arc};
@@ -35,9 +36,12 @@ pub fn resolve_destination(
         Ok(Ic00Method::CreateCanister)
         | Ok(Ic00Method::RawRand)
         | Ok(Ic00Method::ProvisionalCreateCanisterWithCycles)
-        | Ok(Ic00Method::GetMockECDSAPublicKey)
-        | Ok(Ic00Method::SignWithMockECDSA)
-        | Ok(Ic00Method::SignWithECDSA) => Ok(own_subnet),
+        | Ok(Ic00Method::SignWithECDSA)
+        | Ok(Ic00Method::ECDSAPublicKey)
+        | Ok(Ic00Method::HttpRequest)
+        | Ok(Ic00Method::BitcoinGetBalance)
+        | Ok(Ic00Method::BitcoinGetUtxos)
+        | Ok(Ic00Method::BitcoinSendTransaction) => Ok(own_subnet),
         // This message needs to be routed to the NNS subnet.  We assume that
         // this message can only be sent by canisters on the NNS subnet hence
         // returning `own_subnet` here is fine.
@@ -61,6 +65,20 @@ pub fn resolve_destination(
                 ResolveDestinationError::SubnetNotFound(canister_id, Ic00Method::InstallCode)
             })
         }
+        Ok(Ic00Method::InstallChunkedCode) => {
+            let args = InstallChunkedCodeArgs::decode(payload)?;
+            let canister_id = args.get_canister_id();
+            routing_table.route(canister_id.get()).ok_or({
+                ResolveDestinationError::SubnetNotFound(canister_id, Ic00Method::InstallChunkedCode)
+            })
+        }
+        Ok(Ic00Method::UploadChunk) => {
+            let args = UploadChunkArgs::decode(payload)?;
+            let canister_id = args.get_canister_id();
+            routing_table.route(canister_id.get()).ok_or({
+                ResolveDestinationError::SubnetNotFound(canister_id, Ic00Method::UploadChunk)
+            })
+        }
         Ok(Ic00Method::SetController) => {
             let args = Decode!(payload, SetControllerArgs)?;
             let canister_id = args.get_canister_id();
@@ -73,13 +91,41 @@ pub fn resolve_destination(
         | Ok(Ic00Method::StopCanister)
         | Ok(Ic00Method::DeleteCanister)
         | Ok(Ic00Method::UninstallCode)
-        | Ok(Ic00Method::DepositCycles) => {
+        | Ok(Ic00Method::DepositCycles)
+        | Ok(Ic00Method::ListCanisterSnapshots)
+        | Ok(Ic00Method::ClearChunkStore)
+        | Ok(Ic00Method::StoredChunks)
+        | Ok(Ic00Method::FetchCanisterLogs) => {
             let args = Decode!(payload, CanisterIdRecord)?;
             let canister_id = args.get_canister_id();
             routing_table.route(canister_id.get()).ok_or_else(|| {
                 ResolveDestinationError::SubnetNotFound(canister_id, method.unwrap())
             })
         }
+        Ok(Ic00Method::CanisterInfo) => {
+            let args = CanisterInfoRequest::decode(payload)?;
+            let canister_id = args.get_canister_id();
+            routing_table.route(canister_id.get()).ok_or({
+                ResolveDestinationError::SubnetNotFound(canister_id, Ic00Method::CanisterInfo)
+            })
+        }
+        Ok(Ic00Method::TakeCanisterSnapshot) => {
+            let args = TakeCanisterSnapshotArgs::decode(payload)?;
+            let canister_id = args.get_canister_id();
+            routing_table.route(canister_id.get()).ok_or({
+                ResolveDestinationError::SubnetNotFound(
+                    canister_id,
+                    Ic00Method::TakeCanisterSnapshot,
+                )
+            })
+        }
+        Ok(Ic00Method::LoadCanisterSnapshot) | Ok(Ic00Method::DeleteCanisterSnapshot) => {
+            let args = CanisterSnapshotArgs::decode(payload)?;
+            let canister_id = args.get_canister_id();
+            routing_table.route(canister_id.get()).ok_or_else(|| {
+                ResolveDestinationError::SubnetNotFound(canister_id, method.unwrap())
+            })
+        }
         Ok(Ic00Method::ProvisionalTopUpCanister) => {
             let args = ProvisionalTopUpCanisterArgs::decode(payload)?;
             let canister_id = args.get_canister_id();
@@ -118,7 +164,9 @@ fn canister_id_into_u128(canister_id: CanisterId) -> u128 {
     canister_id_into_u64(canister_id) as u128
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, CandidType, Serialize, Deserialize,
+)]
 pub struct CanisterIdRange {
     pub start: CanisterId,
     pub end: CanisterId,
@@ -134,12 +182,48 @@ pub enum WellFormedError {
     RoutingTableNonEmptyRange(String),
     RoutingTableAppGroupSplit(String),
     RoutingTableNotDisjoint(String),
+    CanisterMigrationsNotDisjoint(String),
+    CanisterMigrationsInvalidTrace(String),
+}
+
+impl CanisterIdRange {
+    /// The number of canister IDs included in this (closed) range.
+    fn len(&self) -> u128 {
+        1_u128 + canister_id_into_u128(self.end) - canister_id_into_u128(self.start)
+    }
+
+    /// Returns true if `canister_id` belongs to this range.
+    pub fn contains(&self, canister_id: &CanisterId) -> bool {
+        self.start <= *canister_id && *canister_id <= self.end
+    }
+
+    /// Returns the intersection of the two ranges, if they overlap.
+    pub fn intersection(&self, other: &CanisterIdRange) -> Option<CanisterIdRange> {
+        let start = std::cmp::max(self.start, other.start);
+        let end = std::cmp::min(self.end, other.end);
+        if start <= end {
+            Some(CanisterIdRange { start, end })
+        } else {
+            None
+        }
+    }
 }
 
 /// A list of closed `CanisterId` ranges that are present in the `RoutingTable`
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CanisterIdRanges(Vec<CanisterIdRange>);
 
+impl TryFrom<Vec<CanisterIdRange>> for CanisterIdRanges {
+    type Error = WellFormedError;
+
+    fn try_from(mut ranges: Vec<CanisterIdRange>) -> Result<Self, Self::Error> {
+        ranges.sort();
+        let ranges = Self(ranges);
+        ranges.well_formed()?;
+        Ok(ranges)
+    }
+}
+
 impl CanisterIdRanges {
     /// Returns true if this collection of canister ID ranges is well-formed.
     fn well_formed(&self) -> Result<(), WellFormedError> {
@@ -193,11 +277,7 @@ impl CanisterIdRanges {
     /// valid space of canister ids is exactly (1<<64) which cannot be
     /// represented in a u64, therefore this function returns a u128.
     pub fn total_count(&self) -> u128 {
-        let mut sum = 0;
-        for range in self.0.iter() {
-            sum += 1_u128 + canister_id_into_u128(range.end) - canister_id_into_u128(range.start);
-        }
-        sum
+        self.0.iter().map(CanisterIdRange::len).sum()
     }
 
     /// Given location 'loc' in the range [0, total_count()), select a Canister
@@ -206,8 +286,7 @@ impl CanisterIdRanges {
         let mut loc = loc as u128;
         assert!(loc < self.total_count());
         for range in self.0.iter() {
-            let len =
-                1_u128 + canister_id_into_u128(range.end) - canister_id_into_u128(range.start);
+            let len = range.len();
             if loc < len {
                 return CanisterId::from(canister_id_into_u64(range.start) + loc as u64);
             }
@@ -219,6 +298,43 @@ impl CanisterIdRanges {
             self.total_count()
         );
     }
+
+    pub fn iter(&self) -> impl std::iter::Iterator<Item = &CanisterIdRange> {
+        self.0.iter()
+    }
+
+    /// Splits the ranges into a lower and an upper half of (roughly) equal
+    /// size, without splitting application groups. Returns `None` if the
+    /// ranges cover fewer than two application groups.
+    pub fn bisect(&self) -> Option<(CanisterIdRanges, CanisterIdRanges)> {
+        const APP_GROUP_SIZE: u128 = 0x100;
+        let half = self.total_count() / 2 / APP_GROUP_SIZE * APP_GROUP_SIZE;
+        if half == 0 {
+            return None;
+        }
+        // The first canister ID of the upper half.
+        let split_point = self.locate(half as u64);
+
+        let mut lower = Vec::new();
+        let mut upper = Vec::new();
+        for range in self.0.iter() {
+            if range.end < split_point {
+                lower.push(*range);
+            } else if range.start >= split_point {
+                upper.push(*range);
+            } else {
+                lower.push(CanisterIdRange {
+                    start: range.start,
+                    end: CanisterId::from(canister_id_into_u64(split_point) - 1),
+                });
+                upper.push(CanisterIdRange {
+                    start: split_point,
+                    end: range.end,
+                });
+            }
+        }
+        Some((CanisterIdRanges(lower), CanisterIdRanges(upper)))
+    }
 }
 
 /// A helper function to help insert a new subnet to the routing table
@@ -395,6 +511,100 @@ impl RoutingTable {
         assert_eq!(res.well_formed(), Ok(()));
         res
     }
+
+    /// Returns true if every canister ID in `range` is assigned to
+    /// `subnet_id`.
+    pub fn is_assigned_to(&self, range: &CanisterIdRange, subnet_id: SubnetId) -> bool {
+        let covered: u128 = self
+            .0
+            .iter()
+            .filter(|(_, range_subnet_id)| **range_subnet_id == subnet_id)
+            .filter_map(|(assigned, _)| assigned.intersection(range))
+            .map(|intersection| intersection.len())
+            .sum();
+        covered == range.len()
+    }
+
+    /// Reassigns the given canister ID ranges to `destination`, carving them
+    /// out of whatever ranges they are currently part of. Adjacent ranges
+    /// assigned to the same subnet are merged afterwards.
+    pub fn assign_ranges(
+        &mut self,
+        ranges: CanisterIdRanges,
+        destination: SubnetId,
+    ) -> Result<(), WellFormedError> {
+        ranges.well_formed()?;
+        for range in ranges.0 {
+            self.assign_range(range, destination);
+        }
+        self.optimize();
+        self.well_formed()
+    }
+
+    fn assign_range(&mut self, range: CanisterIdRange, destination: SubnetId) {
+        let overlapping: Vec<(CanisterIdRange, SubnetId)> = self
+            .0
+            .iter()
+            .filter(|(existing, _)| existing.intersection(&range).is_some())
+            .map(|(existing, subnet_id)| (*existing, *subnet_id))
+            .collect();
+        for (existing, subnet_id) in overlapping {
+            self.0.remove(&existing);
+            if existing.start < range.start {
+                let end = CanisterId::from(canister_id_into_u64(range.start) - 1);
+                self.0.insert(
+                    CanisterIdRange {
+                        start: existing.start,
+                        end,
+                    },
+                    subnet_id,
+                );
+            }
+            if existing.end > range.end {
+                let start = CanisterId::from(canister_id_into_u64(range.end) + 1);
+                self.0.insert(
+                    CanisterIdRange {
+                        start,
+                        end: existing.end,
+                    },
+                    subnet_id,
+                );
+            }
+        }
+        self.0.insert(range, destination);
+    }
+
+    /// Merges adjacent ranges that are assigned to the same subnet.
+    fn optimize(&mut self) {
+        let mut merged: BTreeMap<CanisterIdRange, SubnetId> = BTreeMap::new();
+        let mut current: Option<(CanisterIdRange, SubnetId)> = None;
+        for (range, subnet_id) in std::mem::take(&mut self.0) {
+            current = match current {
+                Some((previous, previous_subnet_id))
+                    if previous_subnet_id == subnet_id
+                        && canister_id_into_u128(previous.end) + 1
+                            == canister_id_into_u128(range.start) =>
+                {
+                    Some((
+                        CanisterIdRange {
+                            start: previous.start,
+                            end: range.end,
+                        },
+                        subnet_id,
+                    ))
+                }
+                Some((previous, previous_subnet_id)) => {
+                    merged.insert(previous, previous_subnet_id);
+                    Some((range, subnet_id))
+                }
+                None => Some((range, subnet_id)),
+            };
+        }
+        if let Some((range, subnet_id)) = current {
+            merged.insert(range, subnet_id);
+        }
+        self.0 = merged;
+    }
 }
 
 impl IntoIterator for RoutingTable {
@@ -406,6 +616,123 @@ impl IntoIterator for RoutingTable {
     }
 }
 
+/// Canister ID ranges that are being migrated between subnets, each mapped to
+/// the trace of subnets it is being moved through (source first, destination
+/// last).
+///
+/// While a range is present here, Message Routing accepts messages for and
+/// from the range's canisters from any subnet on its trace, so that messages
+/// that were in flight when the routing table changed can still be inducted,
+/// rejected or rerouted. An entry is removed from the registry by
+/// `complete_canister_migration`; each subnet on the trace keeps applying it
+/// until its own streams hold no more messages to or from the range's
+/// canisters.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanisterMigrations(BTreeMap<CanisterIdRange, Vec<SubnetId>>);
+
+impl CanisterMigrations {
+    pub fn new(map: BTreeMap<CanisterIdRange, Vec<SubnetId>>) -> Self {
+        let ret = Self(map);
+        assert_eq!(ret.well_formed(), Ok(()));
+        ret
+    }
+
+    pub fn iter(&self) -> impl std::iter::Iterator<Item = (&CanisterIdRange, &Vec<SubnetId>)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Records the migration of the given ranges from `source` to
+    /// `destination`. Fails if any of the ranges overlaps a range that is
+    /// already being migrated.
+    pub fn insert_ranges(
+        &mut self,
+        ranges: CanisterIdRanges,
+        source: SubnetId,
+        destination: SubnetId,
+    ) -> Result<(), WellFormedError> {
+        ranges.well_formed()?;
+        for range in ranges.0 {
+            if let Some((existing, _)) = self
+                .0
+                .iter()
+                .find(|(existing, _)| existing.intersection(&range).is_some())
+            {
+                return Err(WellFormedError::CanisterMigrationsNotDisjoint(format!(
+                    "range {:?} overlaps range {:?} that is already being migrated",
+                    range, existing
+                )));
+            }
+            self.0.insert(range, vec![source, destination]);
+        }
+        self.well_formed()
+    }
+
+    /// Removes the given ranges, which must have been recorded with exactly
+    /// the given `trace`.
+    pub fn remove_ranges(
+        &mut self,
+        ranges: CanisterIdRanges,
+        trace: &[SubnetId],
+    ) -> Result<(), WellFormedError> {
+        for range in ranges.0.iter() {
+            match self.0.get(range) {
+                Some(existing_trace) if existing_trace.as_slice() == trace => {}
+                Some(existing_trace) => {
+                    return Err(WellFormedError::CanisterMigrationsInvalidTrace(format!(
+                        "range {:?} is being migrated along {:?}, not {:?}",
+                        range, existing_trace, trace
+                    )))
+                }
+                None => {
+                    return Err(WellFormedError::CanisterMigrationsInvalidTrace(format!(
+                        "range {:?} is not being migrated",
+                        range
+                    )))
+                }
+            }
+        }
+        for range in ranges.0.iter() {
+            self.0.remove(range);
+        }
+        Ok(())
+    }
+
+    /// Returns the migration trace of the range that `canister_id` belongs
+    /// to, if it is being migrated.
+    pub fn lookup(&self, canister_id: CanisterId) -> Option<&[SubnetId]> {
+        self.0
+            .range(
+                ..=(CanisterIdRange {
+                    start: canister_id,
+                    end: CanisterId::from(u64::MAX),
+                }),
+            )
+            .next_back()
+            .filter(|(range, _)| canister_id <= range.end)
+            .map(|(_, trace)| trace.as_slice())
+    }
+
+    /// Returns true if the canister migrations are well-formed: ranges are
+    /// well-formed and disjoint and every trace consists of at least two
+    /// distinct subnets.
+    pub fn well_formed(&self) -> Result<(), WellFormedError> {
+        CanisterIdRanges(self.0.keys().cloned().collect()).well_formed()?;
+        for (range, trace) in self.0.iter() {
+            if trace.len() < 2 || trace.first() == trace.last() {
+                return Err(WellFormedError::CanisterMigrationsInvalidTrace(format!(
+                    "invalid trace {:?} for range {:?}",
+                    trace, range
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -634,4 +961,121 @@ mod test {
         assert_eq!(rt.route(subnet_id5.get()), None);
         assert_eq!(rt.route(subnet_id12.get()), None);
     }
+
+    #[test]
+    fn assign_ranges_carves_out_and_merges() {
+        let mut rt = new_routing_table(
+            [
+                ((0x0, 0xfff), 1),
+                ((0x1000, 0x1fff), 1),
+                ((0x2000, 0x2fff), 2),
+            ]
+            .to_vec(),
+        );
+
+        let range = CanisterIdRange {
+            start: CanisterId::from(0x800),
+            end: CanisterId::from(0x17ff),
+        };
+        assert!(rt.is_assigned_to(&range, subnet_test_id(1)));
+        assert!(!rt.is_assigned_to(&range, subnet_test_id(2)));
+
+        rt.assign_ranges(
+            new_canister_id_ranges(vec![(0x800, 0x17ff)]),
+            subnet_test_id(2),
+        )
+        .unwrap();
+        assert_eq!(
+            rt,
+            new_routing_table(
+                [
+                    ((0x0, 0x7ff), 1),
+                    ((0x800, 0x17ff), 2),
+                    ((0x1800, 0x1fff), 1),
+                    ((0x2000, 0x2fff), 2),
+                ]
+                .to_vec(),
+            )
+        );
+        assert_eq!(
+            rt.route(CanisterId::from(0x1234).get()),
+            Some(subnet_test_id(2))
+        );
+
+        // Moving the remainder back and forth merges adjacent ranges.
+        rt.assign_ranges(
+            new_canister_id_ranges(vec![(0x1800, 0x1fff)]),
+            subnet_test_id(2),
+        )
+        .unwrap();
+        assert_eq!(
+            rt,
+            new_routing_table([((0x0, 0x7ff), 1), ((0x800, 0x2fff), 2)].to_vec())
+        );
+
+        // Ranges must not split application groups.
+        assert_matches!(
+            rt.assign_ranges(
+                new_canister_id_ranges(vec![(0x10, 0xff)]),
+                subnet_test_id(2)
+            ),
+            Err(WellFormedError::CanisterIdRangeAppGroupSplit(_))
+        );
+    }
+
+    #[test]
+    fn bisect_canister_id_ranges() {
+        let ranges = new_canister_id_ranges(vec![(0x0, 0x2ff), (0x1000, 0x10ff)]);
+        let (lower, upper) = ranges.bisect().unwrap();
+        assert_eq!(lower, new_canister_id_ranges(vec![(0x0, 0x1ff)]));
+        assert_eq!(
+            upper,
+            new_canister_id_ranges(vec![(0x200, 0x2ff), (0x1000, 0x10ff)])
+        );
+        assert_eq!(lower.well_formed(), Ok(()));
+        assert_eq!(upper.well_formed(), Ok(()));
+
+        // A single application group cannot be split.
+        assert_eq!(new_canister_id_ranges(vec![(0x100, 0x1ff)]).bisect(), None);
+        assert_eq!(CanisterIdRanges::default().bisect(), None);
+    }
+
+    #[test]
+    fn canister_migrations_insert_lookup_remove() {
+        let mut migrations = CanisterMigrations::default();
+        let ranges = new_canister_id_ranges(vec![(0x100, 0x1ff), (0x400, 0x4ff)]);
+        migrations
+            .insert_ranges(ranges.clone(), subnet_test_id(1), subnet_test_id(2))
+            .unwrap();
+
+        let trace = vec![subnet_test_id(1), subnet_test_id(2)];
+        assert_eq!(migrations.lookup(CanisterId::from(0x0)), None);
+        assert_eq!(
+            migrations.lookup(CanisterId::from(0x150)),
+            Some(trace.as_slice())
+        );
+        assert_eq!(migrations.lookup(CanisterId::from(0x200)), None);
+        assert_eq!(
+            migrations.lookup(CanisterId::from(0x4ff)),
+            Some(trace.as_slice())
+        );
+
+        // Overlapping migrations are rejected.
+        assert_matches!(
+            migrations.insert_ranges(
+                new_canister_id_ranges(vec![(0x0, 0x1ff)]),
+                subnet_test_id(2),
+                subnet_test_id(3)
+            ),
+            Err(WellFormedError::CanisterMigrationsNotDisjoint(_))
+        );
+
+        // Removal requires a matching trace.
+        assert_matches!(
+            migrations.remove_ranges(ranges.clone(), &[subnet_test_id(1), subnet_test_id(3)]),
+            Err(WellFormedError::CanisterMigrationsInvalidTrace(_))
+        );
+        migrations.remove_ranges(ranges, &trace).unwrap();
+        assert!(migrations.is_empty());
+    }
 }
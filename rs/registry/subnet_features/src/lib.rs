@@ -10,6 +10,27 @@ pub struct SubnetFeatures {
     /// This feature flag controls whether canister execution happens
     /// in sandboxed process or not. It is disabled by default.
     pub canister_sandboxing: bool,
+    /// The Bitcoin network whose UTXO set the subnet maintains and exposes
+    /// through the management canister. Disabled by default.
+    pub bitcoin_network: Option<BitcoinNetwork>,
+}
+
+/// The Bitcoin networks a subnet can track.
+#[derive(CandidType, Clone, Copy, Deserialize, Debug, Eq, PartialEq, Serialize)]
+pub enum BitcoinNetwork {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl From<BitcoinNetwork> for pb::BitcoinNetwork {
+    fn from(network: BitcoinNetwork) -> Self {
+        match network {
+            BitcoinNetwork::Mainnet => pb::BitcoinNetwork::Mainnet,
+            BitcoinNetwork::Testnet => pb::BitcoinNetwork::Testnet,
+            BitcoinNetwork::Regtest => pb::BitcoinNetwork::Regtest,
+        }
+    }
 }
 
 impl From<SubnetFeatures> for pb::SubnetFeatures {
@@ -17,6 +38,10 @@ impl From<SubnetFeatures> for pb::SubnetFeatures {
         Self {
             ecdsa_signatures: features.ecdsa_signatures,
             canister_sandboxing: features.canister_sandboxing,
+            bitcoin_network: features
+                .bitcoin_network
+                .map(|network| pb::BitcoinNetwork::from(network) as i32)
+                .unwrap_or(pb::BitcoinNetwork::Unspecified as i32),
         }
     }
 }
@@ -26,6 +51,12 @@ impl From<pb::SubnetFeatures> for SubnetFeatures {
         Self {
             ecdsa_signatures: features.ecdsa_signatures,
             canister_sandboxing: features.canister_sandboxing,
+            bitcoin_network: match pb::BitcoinNetwork::from_i32(features.bitcoin_network) {
+                Some(pb::BitcoinNetwork::Mainnet) => Some(BitcoinNetwork::Mainnet),
+                Some(pb::BitcoinNetwork::Testnet) => Some(BitcoinNetwork::Testnet),
+                Some(pb::BitcoinNetwork::Regtest) => Some(BitcoinNetwork::Regtest),
+                Some(pb::BitcoinNetwork::Unspecified) | None => None,
+            },
         }
     }
 }
@@ -45,6 +76,9 @@ impl FromStr for SubnetFeatures {
             match feature {
                 "ecdsa_signatures" => features.ecdsa_signatures = true,
                 "canister_sandboxing" => features.canister_sandboxing = true,
+                "bitcoin_mainnet" => features.bitcoin_network = Some(BitcoinNetwork::Mainnet),
+                "bitcoin_testnet" => features.bitcoin_network = Some(BitcoinNetwork::Testnet),
+                "bitcoin_regtest" => features.bitcoin_network = Some(BitcoinNetwork::Regtest),
                 _ => return Err(format!("Unknown feature {:?} in {:?}", feature, string)),
             }
         }
@@ -55,7 +89,8 @@ impl FromStr for SubnetFeatures {
 
 #[cfg(test)]
 mod tests {
-    use crate::SubnetFeatures;
+    use crate::{BitcoinNetwork, SubnetFeatures};
+    use ic_protobuf::registry::subnet::v1 as pb;
     use std::str::FromStr;
 
     #[test]
@@ -84,7 +119,24 @@ mod tests {
             SubnetFeatures {
                 ecdsa_signatures: true,
                 canister_sandboxing: true,
+                bitcoin_network: None,
             }
         );
     }
+
+    #[test]
+    fn test_bitcoin_network_can_be_set() {
+        let result = SubnetFeatures::from_str("bitcoin_regtest").unwrap();
+        assert_eq!(
+            result,
+            SubnetFeatures {
+                bitcoin_network: Some(BitcoinNetwork::Regtest),
+                ..SubnetFeatures::default()
+            }
+        );
+        assert_eq!(
+            SubnetFeatures::from(pb::SubnetFeatures::from(result)),
+            result
+        );
+    }
 }
@@ -66,6 +66,7 @@ use ic_types::{
 };
 use ic_utils::ic_features::cow_state_feature;
 use std::{
+    collections::BTreeMap,
     path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
@@ -552,6 +553,7 @@ impl Player {
             registry_version,
             time,
             consensus_responses: Vec::new(),
+            ecdsa_subnet_public_keys: BTreeMap::new(),
         };
         let context_time = extra_batch.time;
         let extra_msgs = extra(self, context_time);
@@ -17,6 +17,7 @@ use ic_test_utilities::{
 };
 use ic_types::{
     batch::{Batch, BatchPayload, IngressPayload, SelfValidatingPayload, XNetPayload},
+    canister_http::CanisterHttpPayload,
     ic00,
     ic00::Payload,
     ingress::{IngressStatus, WasmResult},
@@ -25,6 +26,7 @@ use ic_types::{
     Randomness, RegistryVersion,
 };
 use ic_types::{messages::MessageId, replica_config::ReplicaConfig, CanisterId};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -117,11 +119,13 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
                 stream_slices: Default::default(),
             },
             self_validating: SelfValidatingPayload::default(),
+            canister_http: CanisterHttpPayload::default(),
         },
         randomness: Randomness::from([0; 32]),
         registry_version: RegistryVersion::from(1),
         time: mock_time(),
         consensus_responses: vec![],
+        ecdsa_subnet_public_keys: BTreeMap::new(),
     }
 }
 
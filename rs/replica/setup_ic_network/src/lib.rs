@@ -5,11 +5,12 @@
 
 use ic_artifact_manager::{manager, processors};
 use ic_artifact_pool::{
-    certification_pool::CertificationPoolImpl, consensus_pool::ConsensusPoolImpl,
-    dkg_pool::DkgPoolImpl, ecdsa_pool::EcdsaPoolImpl,
+    canister_http_pool::CanisterHttpPoolImpl, certification_pool::CertificationPoolImpl,
+    consensus_pool::ConsensusPoolImpl, dkg_pool::DkgPoolImpl, ecdsa_pool::EcdsaPoolImpl,
     ensure_persistent_pool_replica_version_compatibility, ingress_pool::IngressPoolImpl,
 };
 use ic_base_thread::async_safe_block_on_await;
+use ic_canister_http::{CanisterHttpGossipImpl, CanisterHttpPoolManagerImpl};
 use ic_config::{artifact_pool::ArtifactPoolConfig, consensus::ConsensusConfig};
 use ic_consensus::{
     certification,
@@ -22,6 +23,7 @@ use ic_ingress_manager::IngressManager;
 use ic_interfaces::registry::LocalStoreCertifiedTimeReader;
 use ic_interfaces::{
     artifact_manager::{ArtifactClient, ArtifactManager, ArtifactProcessor},
+    canister_http::CanisterHttpPayloadBuilder,
     consensus_pool::ConsensusPoolCache,
     crypto::{Crypto, IngressSigVerifier},
     execution_environment::IngressHistoryReader,
@@ -140,6 +142,12 @@ pub fn create_networking_stack(
     state_sync_client: P2PStateSyncClient,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
+    // The pool the canister HTTP payload builder reads the agreed responses
+    // from. The pool manager is `None` on replicas that do not execute
+    // canister HTTP requests.
+    canister_http_pool: Arc<RwLock<CanisterHttpPoolImpl>>,
+    canister_http_pool_manager: Option<CanisterHttpPoolManagerImpl>,
     message_router: Arc<dyn MessageRouting>,
     crypto: Arc<dyn Crypto + Send + Sync>,
     consensus_crypto: Arc<dyn ConsensusCrypto + Send + Sync>,
@@ -204,6 +212,9 @@ pub fn create_networking_stack(
         state_sync_client,
         xnet_payload_builder,
         self_validating_payload_builder,
+        canister_http_payload_builder,
+        canister_http_pool,
+        canister_http_pool_manager,
         message_router,
         ingress_history_reader,
         catch_up_package,
@@ -314,6 +325,9 @@ fn setup_artifact_manager(
     state_sync_client: P2PStateSyncClient,
     xnet_payload_builder: Arc<dyn XNetPayloadBuilder>,
     self_validating_payload_builder: Arc<dyn SelfValidatingPayloadBuilder>,
+    canister_http_payload_builder: Arc<dyn CanisterHttpPayloadBuilder>,
+    canister_http_pool: Arc<RwLock<CanisterHttpPoolImpl>>,
+    canister_http_pool_manager: Option<CanisterHttpPoolManagerImpl>,
     message_router: Arc<dyn MessageRouting>,
     ingress_history_reader: Box<dyn IngressHistoryReader>,
     catch_up_package: CUPWithOriginalProtobuf,
@@ -418,6 +432,7 @@ fn setup_artifact_manager(
                     Arc::clone(&ingress_manager) as Arc<_>,
                     Arc::clone(&xnet_payload_builder) as Arc<_>,
                     Arc::clone(&self_validating_payload_builder) as Arc<_>,
+                    Arc::clone(&canister_http_payload_builder) as Arc<_>,
                     Arc::clone(&dkg_pool) as Arc<_>,
                     Arc::clone(&ecdsa_pool) as Arc<_>,
                     Arc::clone(&dkg_key_manager) as Arc<_>,
@@ -506,6 +521,25 @@ fn setup_artifact_manager(
         artifact_manager_maker.add_client(dkg_client, actor);
     }
 
+    if let Some(canister_http_pool_manager) = canister_http_pool_manager {
+        // Create the canister HTTP client.
+        let event_handler = event_handler.clone();
+        let (canister_http_client, actor) = processors::CanisterHttpProcessor::build(
+            move |req| event_handler.broadcast_advert(req.advert.into(), req.advert_class),
+            || {
+                (
+                    canister_http_pool_manager,
+                    CanisterHttpGossipImpl::new(Arc::clone(&state_manager)),
+                )
+            },
+            Arc::clone(&time_source) as Arc<_>,
+            canister_http_pool,
+            metrics_registry.clone(),
+            replica_logger.clone(),
+        );
+        artifact_manager_maker.add_client(canister_http_client, actor);
+    }
+
     {
         // Create the ECDSA client if enabled by the config
         if registry_client
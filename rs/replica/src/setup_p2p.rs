@@ -1,3 +1,7 @@
+use ic_artifact_pool::canister_http_pool::CanisterHttpPoolImpl;
+use ic_canister_http::{
+    CanisterHttpAdapterClientImpl, CanisterHttpPayloadBuilderImpl, CanisterHttpPoolManagerImpl,
+};
 use ic_config::{artifact_pool::ArtifactPoolConfig, subnet_config::SubnetConfig, Config};
 use ic_consensus::certification::VerifierImpl;
 use ic_crypto::CryptoComponent;
@@ -19,7 +23,7 @@ use ic_replica_setup_ic_network::{create_networking_stack, P2PStateSyncClient};
 use ic_replicated_state::ReplicatedState;
 use ic_state_manager::StateManagerImpl;
 use ic_types::{consensus::catchup::CUPWithOriginalProtobuf, NodeId, SubnetId};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 #[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn construct_ic_stack(
@@ -143,6 +147,33 @@ pub fn construct_ic_stack(
     let self_validating_payload_builder = NoOpSelfValidatingPayloadBuilder {};
     let self_validating_payload_builder = Arc::new(self_validating_payload_builder);
 
+    let canister_http_pool = Arc::new(RwLock::new(CanisterHttpPoolImpl::new(
+        metrics_registry.clone(),
+    )));
+    let canister_http_adapter_client =
+        CanisterHttpAdapterClientImpl::new(tokio::runtime::Handle::current());
+    let canister_http_pool_manager = CanisterHttpPoolManagerImpl::new(
+        Arc::clone(&state_manager) as Arc<_>,
+        Arc::clone(&sync_query_handler),
+        Arc::new(canister_http_adapter_client),
+        Arc::clone(&crypto) as Arc<_>,
+        Arc::clone(&registry) as Arc<_>,
+        node_id,
+        subnet_id,
+        &metrics_registry,
+        replica_logger.clone(),
+    );
+    let canister_http_payload_builder = CanisterHttpPayloadBuilderImpl::new(
+        Arc::clone(&canister_http_pool) as Arc<_>,
+        Arc::clone(&state_manager) as Arc<_>,
+        Arc::clone(&crypto) as Arc<_>,
+        Arc::clone(&registry) as Arc<_>,
+        subnet_id,
+        &metrics_registry,
+        replica_logger.clone(),
+    );
+    let canister_http_payload_builder = Arc::new(canister_http_payload_builder);
+
     let artifact_pool_config = ArtifactPoolConfig::from(config.artifact_pool);
 
     // Determine the correct catch-up package.
@@ -229,6 +260,9 @@ pub fn construct_ic_stack(
         P2PStateSyncClient::Client(Arc::clone(&state_manager) as Arc<_>),
         xnet_payload_builder as Arc<_>,
         self_validating_payload_builder as Arc<_>,
+        canister_http_payload_builder as Arc<_>,
+        canister_http_pool,
+        Some(canister_http_pool_manager),
         message_router as Arc<_>,
         // TODO(SCL-213)
         Arc::clone(&crypto) as Arc<_>,
@@ -694,7 +694,9 @@ fn can_get_canister_information() {
                 num_cycles.get(),
                 ComputeAllocation::default().as_percent(),
                 None,
-                2592000
+                2592000,
+                0,
+                None,
             )
         );
 
@@ -744,7 +746,9 @@ fn can_get_canister_information() {
                     num_cycles.get(),
                     ComputeAllocation::default().as_percent(),
                     None,
-                    2592000
+                    2592000,
+                    1,
+                    None,
                 ),
                 CanisterStatusResultV2::decode(&res).unwrap(),
                 2 * BALANCE_EPSILON,
@@ -915,6 +919,7 @@ fn assert_canister_status_result_equals(
     assert_eq!(expected.status(), actual.status());
     assert_eq!(expected.module_hash(), actual.module_hash());
     assert_eq!(expected.controller(), actual.controller());
+    assert_eq!(expected.canister_version(), actual.canister_version());
     assert_balance_equals(
         Cycles::from(expected.cycles()),
         Cycles::from(actual.cycles()),
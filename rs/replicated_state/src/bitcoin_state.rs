@@ -0,0 +1,528 @@
+use crate::page_map::{PageIndex, PageMap, PAGE_SIZE};
+use bitcoin::{
+    blockdata::constants::genesis_block,
+    consensus::{deserialize, serialize},
+    Address, Block, BlockHash, Network, OutPoint, Script, TxOut, Txid,
+};
+use ic_protobuf::{
+    proxy::{try_from_option_field, ProxyDecodeError},
+    registry::subnet::v1 as pb_subnet,
+    state::system_metadata::v1 as pb_metadata,
+};
+use ic_registry_subnet_features::BitcoinNetwork;
+use ic_sys::PageBytes;
+use ic_types::Time;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::{TryFrom, TryInto};
+
+#[cfg(test)]
+mod tests;
+
+/// The number of blocks that have to be built on top of a block (including
+/// the block itself) before it is considered stable.
+pub const DEFAULT_STABILITY_THRESHOLD: u32 = 6;
+
+/// The maximum number of transactions submitted through
+/// `bitcoin_send_transaction` that can wait to be handed over to the Bitcoin
+/// adapter at the same time.
+pub const MAX_OUTGOING_TRANSACTIONS: usize = 1_000;
+
+/// The Bitcoin state of a subnet: the UTXO set of all stable blocks and the
+/// tree of blocks that are not stable yet.
+///
+/// The state is not part of the `SystemMetadata`: it is checkpointed to its
+/// own files, with the UTXO set stored in a `PageMap` so that only the pages
+/// that changed since the previous checkpoint have to be written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitcoinState {
+    pub network: Network,
+
+    /// The outputs that are unspent as of the root of `unstable_blocks`.
+    pub utxos: UtxoSet,
+
+    /// The tree of blocks on top of the latest stable block, which is the
+    /// root of the tree. The outputs of the root are part of `utxos`.
+    pub unstable_blocks: BlockTree,
+
+    /// The height of the latest stable block.
+    pub stable_height: u32,
+
+    /// See [`DEFAULT_STABILITY_THRESHOLD`].
+    pub stability_threshold: u32,
+
+    /// Transactions submitted through `bitcoin_send_transaction` that are
+    /// waiting to be handed over to the Bitcoin adapter, at most
+    /// [`MAX_OUTGOING_TRANSACTIONS`].
+    pub outgoing_transactions: Vec<OutgoingTransaction>,
+}
+
+impl BitcoinState {
+    /// Creates the state of a subnet that has not seen any block except for
+    /// the genesis block of `network`. The outputs of the genesis block are
+    /// unspendable and hence not part of the UTXO set.
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            utxos: UtxoSet::new(network),
+            unstable_blocks: BlockTree::new(genesis_block(network)),
+            stable_height: 0,
+            stability_threshold: DEFAULT_STABILITY_THRESHOLD,
+            outgoing_transactions: vec![],
+        }
+    }
+}
+
+/// Maps the network of the subnet features to the one of the `bitcoin` crate.
+pub fn to_bitcoin_network(network: BitcoinNetwork) -> Network {
+    match network {
+        BitcoinNetwork::Mainnet => Network::Bitcoin,
+        BitcoinNetwork::Testnet => Network::Testnet,
+        BitcoinNetwork::Regtest => Network::Regtest,
+    }
+}
+
+// The outputs are stored in fixed-size slots of a `PageMap`, which never
+// span pages. A slot starts with a byte that tells whether it is occupied,
+// followed by the txid (32 bytes), vout (4), value (8), height (4), the length
+// of the script (1) and the script itself.
+const UTXO_SLOT_SIZE: usize = 128;
+const UTXO_SLOTS_PER_PAGE: u64 = (PAGE_SIZE / UTXO_SLOT_SIZE) as u64;
+const UTXO_SLOT_HEADER_SIZE: usize = 1 + 32 + 4 + 8 + 4 + 1;
+
+/// The maximum length of a script that fits into a slot of the UTXO page map.
+/// This covers all standard scripts; outputs with longer scripts are kept on
+/// the heap, see [`UtxoSet::large_utxos`].
+pub const MAX_SLOT_SCRIPT_SIZE: usize = UTXO_SLOT_SIZE - UTXO_SLOT_HEADER_SIZE;
+
+/// The set of unspent transaction outputs, indexed by address.
+///
+/// The outputs live in a `PageMap`; the indexes are derived from its contents
+/// when the set is loaded from a checkpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UtxoSet {
+    network: Network,
+    /// The outputs whose script is at most `MAX_SLOT_SCRIPT_SIZE` bytes long,
+    /// one per slot.
+    page_map: PageMap,
+    /// The slot of each output stored in `page_map`.
+    slots: BTreeMap<OutPoint, u64>,
+    /// The unoccupied slots below `num_slots`.
+    free_slots: BTreeSet<u64>,
+    /// The number of slots in use, including the free ones in between.
+    num_slots: u64,
+    /// The outputs whose script does not fit into a slot.
+    large_utxos: BTreeMap<OutPoint, (TxOut, u32)>,
+    address_to_outpoints: BTreeMap<String, BTreeSet<OutPoint>>,
+}
+
+impl UtxoSet {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            page_map: PageMap::new(),
+            slots: BTreeMap::new(),
+            free_slots: BTreeSet::new(),
+            num_slots: 0,
+            large_utxos: BTreeMap::new(),
+            address_to_outpoints: BTreeMap::new(),
+        }
+    }
+
+    /// Restores the UTXO set from the page map and the large outputs of a
+    /// checkpoint.
+    pub fn from_checkpoint(
+        network: Network,
+        page_map: PageMap,
+        large_utxos: BTreeMap<OutPoint, (TxOut, u32)>,
+    ) -> Result<Self, ProxyDecodeError> {
+        let mut utxos = Self {
+            page_map,
+            ..Self::new(network)
+        };
+        let num_slots = utxos.page_map.num_host_pages() as u64 * UTXO_SLOTS_PER_PAGE;
+        let mut free_slots = BTreeSet::new();
+        for slot in 0..num_slots {
+            match utxos.read_slot(slot)? {
+                Some((outpoint, output, _)) => {
+                    utxos.index(outpoint, &output);
+                    utxos.slots.insert(outpoint, slot);
+                    utxos.num_slots = slot + 1;
+                }
+                None => {
+                    free_slots.insert(slot);
+                }
+            }
+        }
+        utxos.free_slots = free_slots
+            .into_iter()
+            .filter(|slot| *slot < utxos.num_slots)
+            .collect();
+        for (outpoint, (output, height)) in large_utxos {
+            utxos.insert(outpoint, output, height);
+        }
+        Ok(utxos)
+    }
+
+    /// Adds an output created at the given height.
+    pub fn insert(&mut self, outpoint: OutPoint, output: TxOut, height: u32) {
+        // An output that is created again replaces the existing one.
+        self.remove(&outpoint);
+        self.index(outpoint, &output);
+        if output.script_pubkey.len() > MAX_SLOT_SCRIPT_SIZE {
+            self.large_utxos.insert(outpoint, (output, height));
+            return;
+        }
+        let slot = match self.free_slots.iter().next().cloned() {
+            Some(slot) => {
+                self.free_slots.remove(&slot);
+                slot
+            }
+            None => {
+                self.num_slots += 1;
+                self.num_slots - 1
+            }
+        };
+        self.write_slot(slot, &encode_slot(&outpoint, &output, height));
+        self.slots.insert(outpoint, slot);
+    }
+
+    /// Removes a spent output, returning it together with its height.
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<(TxOut, u32)> {
+        let (output, height) = match self.slots.remove(outpoint) {
+            Some(slot) => {
+                let utxo = self.get_from_slot(slot);
+                self.write_slot(slot, &[0; UTXO_SLOT_SIZE]);
+                self.free_slots.insert(slot);
+                utxo
+            }
+            None => self.large_utxos.remove(outpoint)?,
+        };
+        if let Some(address) = address_of(&output.script_pubkey, self.network) {
+            if let Some(outpoints) = self.address_to_outpoints.get_mut(&address) {
+                outpoints.remove(outpoint);
+                if outpoints.is_empty() {
+                    self.address_to_outpoints.remove(&address);
+                }
+            }
+        }
+        Some((output, height))
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<(TxOut, u32)> {
+        match self.slots.get(outpoint) {
+            Some(slot) => Some(self.get_from_slot(*slot)),
+            None => self.large_utxos.get(outpoint).cloned(),
+        }
+    }
+
+    /// Returns the outputs that belong to `address`, in outpoint order.
+    pub fn get_by_address<'a>(
+        &'a self,
+        address: &str,
+    ) -> impl Iterator<Item = (OutPoint, (TxOut, u32))> + 'a {
+        self.address_to_outpoints
+            .get(address)
+            .into_iter()
+            .flatten()
+            .map(move |outpoint| {
+                let utxo = self
+                    .get(outpoint)
+                    .expect("the address index refers to a missing output");
+                (*outpoint, utxo)
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() + self.large_utxos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The page map backing the outputs that fit into a slot.
+    pub fn page_map(&self) -> &PageMap {
+        &self.page_map
+    }
+
+    /// Gives access to the page map for managing its deltas and checkpoint
+    /// files. The contents of the page map must not be changed.
+    pub fn page_map_mut(&mut self) -> &mut PageMap {
+        &mut self.page_map
+    }
+
+    /// The outputs whose script is longer than [`MAX_SLOT_SCRIPT_SIZE`].
+    pub fn large_utxos(&self) -> &BTreeMap<OutPoint, (TxOut, u32)> {
+        &self.large_utxos
+    }
+
+    fn index(&mut self, outpoint: OutPoint, output: &TxOut) {
+        if let Some(address) = address_of(&output.script_pubkey, self.network) {
+            self.address_to_outpoints
+                .entry(address)
+                .or_default()
+                .insert(outpoint);
+        }
+    }
+
+    fn get_from_slot(&self, slot: u64) -> (TxOut, u32) {
+        match self.read_slot(slot) {
+            Ok(Some((_, output, height))) => (output, height),
+            _ => unreachable!("slot {} of the UTXO page map is not a valid output", slot),
+        }
+    }
+
+    fn read_slot(&self, slot: u64) -> Result<Option<(OutPoint, TxOut, u32)>, ProxyDecodeError> {
+        let (page_index, offset) = slot_position(slot);
+        let bytes = &self.page_map.get_page(page_index)[offset..offset + UTXO_SLOT_SIZE];
+        if bytes[0] == 0 {
+            return Ok(None);
+        }
+        let malformed = |err: String| ProxyDecodeError::ValueOutOfRange {
+            typ: "UtxoSet::page_map",
+            err: format!("Malformed output in slot {}: {}", slot, err),
+        };
+        let txid: Txid = deserialize(&bytes[1..33]).map_err(|e| malformed(e.to_string()))?;
+        let vout = u32::from_le_bytes(bytes[33..37].try_into().unwrap());
+        let value = u64::from_le_bytes(bytes[37..45].try_into().unwrap());
+        let height = u32::from_le_bytes(bytes[45..49].try_into().unwrap());
+        let script_len = bytes[49] as usize;
+        if script_len > MAX_SLOT_SCRIPT_SIZE {
+            return Err(malformed(format!("script length {}", script_len)));
+        }
+        let script = bytes[UTXO_SLOT_HEADER_SIZE..UTXO_SLOT_HEADER_SIZE + script_len].to_vec();
+        Ok(Some((
+            OutPoint::new(txid, vout),
+            TxOut {
+                value,
+                script_pubkey: Script::from(script),
+            },
+            height,
+        )))
+    }
+
+    fn write_slot(&mut self, slot: u64, bytes: &[u8; UTXO_SLOT_SIZE]) {
+        let (page_index, offset) = slot_position(slot);
+        let mut page: PageBytes = *self.page_map.get_page(page_index);
+        page[offset..offset + UTXO_SLOT_SIZE].copy_from_slice(bytes);
+        self.page_map.update(&[(page_index, &page)]);
+    }
+}
+
+// Returns the page and the offset into the page of a slot of the UTXO page
+// map.
+fn slot_position(slot: u64) -> (PageIndex, usize) {
+    (
+        PageIndex::new(slot / UTXO_SLOTS_PER_PAGE),
+        (slot % UTXO_SLOTS_PER_PAGE) as usize * UTXO_SLOT_SIZE,
+    )
+}
+
+fn encode_slot(outpoint: &OutPoint, output: &TxOut, height: u32) -> [u8; UTXO_SLOT_SIZE] {
+    let script = output.script_pubkey.as_bytes();
+    let mut bytes = [0; UTXO_SLOT_SIZE];
+    bytes[0] = 1;
+    bytes[1..33].copy_from_slice(&serialize(&outpoint.txid));
+    bytes[33..37].copy_from_slice(&outpoint.vout.to_le_bytes());
+    bytes[37..45].copy_from_slice(&output.value.to_le_bytes());
+    bytes[45..49].copy_from_slice(&height.to_le_bytes());
+    bytes[49] = script.len() as u8;
+    bytes[UTXO_SLOT_HEADER_SIZE..UTXO_SLOT_HEADER_SIZE + script.len()].copy_from_slice(script);
+    bytes
+}
+
+/// Returns the address `script` pays to, if it is a standard one.
+pub fn address_of(script: &Script, network: Network) -> Option<String> {
+    Address::from_script(script, network).map(|address| address.to_string())
+}
+
+/// A tree of blocks, each child extending the block of its parent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockTree {
+    pub root: Block,
+    pub children: Vec<BlockTree>,
+}
+
+impl BlockTree {
+    pub fn new(root: Block) -> Self {
+        Self {
+            root,
+            children: vec![],
+        }
+    }
+
+    /// Returns the subtree rooted at the block with the given hash.
+    pub fn find_mut(&mut self, hash: &BlockHash) -> Option<&mut BlockTree> {
+        if self.root.block_hash() == *hash {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(hash))
+    }
+
+    /// Returns the length of the longest chain in the tree, including the
+    /// root.
+    pub fn depth(&self) -> u32 {
+        1 + self.children.iter().map(|c| c.depth()).max().unwrap_or(0)
+    }
+
+    /// Returns the longest chain in the tree, starting at the root. Ties are
+    /// broken in favour of the child that was added first.
+    pub fn main_chain(&self) -> Vec<&Block> {
+        let mut chain = vec![&self.root];
+        let mut tree = self;
+        loop {
+            let mut deepest: Option<(&BlockTree, u32)> = None;
+            for child in tree.children.iter() {
+                let depth = child.depth();
+                if deepest.map_or(true, |(_, d)| depth > d) {
+                    deepest = Some((child, depth));
+                }
+            }
+            match deepest {
+                Some((child, _)) => {
+                    chain.push(&child.root);
+                    tree = child;
+                }
+                None => return chain,
+            }
+        }
+    }
+
+    /// Returns all blocks of the tree, parents before children.
+    pub fn blocks(&self) -> Vec<&Block> {
+        let mut blocks = vec![&self.root];
+        for child in self.children.iter() {
+            blocks.extend(child.blocks());
+        }
+        blocks
+    }
+}
+
+/// A transaction waiting to be sent to the Bitcoin network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingTransaction {
+    /// The consensus-encoded transaction.
+    pub transaction: Vec<u8>,
+    /// The time at which the transaction was submitted.
+    pub time: Time,
+}
+
+impl From<&BitcoinState> for pb_metadata::BitcoinState {
+    fn from(state: &BitcoinState) -> Self {
+        Self {
+            network: pb_subnet::BitcoinNetwork::from(match state.network {
+                Network::Bitcoin => BitcoinNetwork::Mainnet,
+                Network::Testnet | Network::Signet => BitcoinNetwork::Testnet,
+                Network::Regtest => BitcoinNetwork::Regtest,
+            }) as i32,
+            large_utxos: state
+                .utxos
+                .large_utxos()
+                .iter()
+                .map(|(outpoint, (output, height))| pb_metadata::BitcoinUtxo {
+                    txid: serialize(&outpoint.txid),
+                    vout: outpoint.vout,
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.to_bytes(),
+                    height: *height,
+                })
+                .collect(),
+            unstable_blocks: Some((&state.unstable_blocks).into()),
+            stable_height: state.stable_height,
+            stability_threshold: state.stability_threshold,
+            outgoing_transactions: state
+                .outgoing_transactions
+                .iter()
+                .map(|tx| pb_metadata::BitcoinOutgoingTransaction {
+                    transaction: tx.transaction.clone(),
+                    time_nanos: tx.time.as_nanos_since_unix_epoch(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Restores the state from its protobuf representation and the page map
+/// holding the UTXO set.
+impl TryFrom<(pb_metadata::BitcoinState, PageMap)> for BitcoinState {
+    type Error = ProxyDecodeError;
+    fn try_from(
+        (state, utxos_page_map): (pb_metadata::BitcoinState, PageMap),
+    ) -> Result<Self, Self::Error> {
+        let network = match pb_subnet::BitcoinNetwork::from_i32(state.network) {
+            Some(pb_subnet::BitcoinNetwork::Mainnet) => Network::Bitcoin,
+            Some(pb_subnet::BitcoinNetwork::Testnet) => Network::Testnet,
+            Some(pb_subnet::BitcoinNetwork::Regtest) => Network::Regtest,
+            _ => {
+                return Err(ProxyDecodeError::ValueOutOfRange {
+                    typ: "BitcoinState::network",
+                    err: format!("Unknown Bitcoin network: {}", state.network),
+                })
+            }
+        };
+        let mut large_utxos = BTreeMap::new();
+        for utxo in state.large_utxos {
+            let txid: Txid =
+                deserialize(&utxo.txid).map_err(|e| ProxyDecodeError::ValueOutOfRange {
+                    typ: "BitcoinUtxo::txid",
+                    err: e.to_string(),
+                })?;
+            large_utxos.insert(
+                OutPoint::new(txid, utxo.vout),
+                (
+                    TxOut {
+                        value: utxo.value,
+                        script_pubkey: Script::from(utxo.script_pubkey),
+                    },
+                    utxo.height,
+                ),
+            );
+        }
+        Ok(Self {
+            network,
+            utxos: UtxoSet::from_checkpoint(network, utxos_page_map, large_utxos)?,
+            unstable_blocks: BlockTree::try_from(try_from_option_field(
+                state.unstable_blocks,
+                "BitcoinState::unstable_blocks",
+            )?)?,
+            stable_height: state.stable_height,
+            stability_threshold: state.stability_threshold,
+            outgoing_transactions: state
+                .outgoing_transactions
+                .into_iter()
+                .map(|tx| OutgoingTransaction {
+                    transaction: tx.transaction,
+                    time: Time::from_nanos_since_unix_epoch(tx.time_nanos),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl From<&BlockTree> for pb_metadata::BitcoinBlockTree {
+    fn from(tree: &BlockTree) -> Self {
+        Self {
+            root: serialize(&tree.root),
+            children: tree.children.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb_metadata::BitcoinBlockTree> for BlockTree {
+    type Error = ProxyDecodeError;
+    fn try_from(tree: pb_metadata::BitcoinBlockTree) -> Result<Self, Self::Error> {
+        Ok(Self {
+            root: deserialize(&tree.root).map_err(|e| ProxyDecodeError::ValueOutOfRange {
+                typ: "BitcoinBlockTree::root",
+                err: e.to_string(),
+            })?,
+            children: tree
+                .children
+                .into_iter()
+                .map(BlockTree::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
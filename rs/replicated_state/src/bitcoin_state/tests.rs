@@ -0,0 +1,58 @@
+use super::*;
+
+fn outpoint(i: u8) -> OutPoint {
+    OutPoint::new(deserialize(&[i; 32]).unwrap(), i as u32)
+}
+
+fn output(value: u64, script_len: usize) -> TxOut {
+    TxOut {
+        value,
+        script_pubkey: Script::from(vec![0x51; script_len]),
+    }
+}
+
+#[test]
+fn utxo_set_can_be_restored_from_its_page_map() {
+    let mut utxos = UtxoSet::new(Network::Regtest);
+    for i in 0..100 {
+        utxos.insert(outpoint(i), output(i as u64, 25), i as u32);
+    }
+    utxos.insert(outpoint(100), output(100, MAX_SLOT_SCRIPT_SIZE + 1), 100);
+    assert_eq!(utxos.large_utxos().len(), 1);
+
+    assert_eq!(utxos.remove(&outpoint(42)), Some((output(42, 25), 42)));
+    assert_eq!(utxos.remove(&outpoint(42)), None);
+    assert_eq!(utxos.len(), 100);
+
+    let restored = UtxoSet::from_checkpoint(
+        Network::Regtest,
+        utxos.page_map().clone(),
+        utxos.large_utxos().clone(),
+    )
+    .unwrap();
+    assert_eq!(restored, utxos);
+
+    // The slot of the removed output is reused.
+    let num_host_pages = utxos.page_map().num_host_pages();
+    let mut restored = restored;
+    restored.insert(outpoint(200), output(200, 25), 200);
+    assert_eq!(restored.page_map().num_host_pages(), num_host_pages);
+    assert_eq!(restored.get(&outpoint(200)), Some((output(200, 25), 200)));
+}
+
+#[test]
+fn bitcoin_state_roundtrips_through_proto_and_page_map() {
+    let mut state = BitcoinState::new(Network::Regtest);
+    state.utxos.insert(outpoint(1), output(1, 25), 1);
+    state
+        .utxos
+        .insert(outpoint(2), output(2, MAX_SLOT_SCRIPT_SIZE + 10), 2);
+    state.outgoing_transactions.push(OutgoingTransaction {
+        transaction: vec![1, 2, 3],
+        time: Time::from_nanos_since_unix_epoch(7),
+    });
+
+    let proto = pb_metadata::BitcoinState::from(&state);
+    let restored = BitcoinState::try_from((proto, state.utxos.page_map().clone())).unwrap();
+    assert_eq!(restored, state);
+}
@@ -0,0 +1,176 @@
+use crate::canister_state::execution_state::{
+    ExportedFunctions, Global, Memory, WasmBinary, WasmMetadata,
+};
+use crate::{num_bytes_try_from, CanisterState};
+use ic_types::{CanisterId, NumBytes, SnapshotId, Time};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// A snapshot of the state of a stopped canister, as captured by the
+/// `take_canister_snapshot` management method.
+///
+/// The memories share their `PageMap`s with the canister at the time the
+/// snapshot was taken, so taking a snapshot is cheap and the pages are only
+/// copied once either side modifies them.
+#[derive(Clone, Debug)]
+pub struct CanisterSnapshot {
+    /// The canister this snapshot belongs to.
+    pub canister_id: CanisterId,
+    /// The time at which the snapshot was taken.
+    pub taken_at_timestamp: Time,
+    /// The Wasm module installed at the time the snapshot was taken.
+    pub wasm_binary: Arc<WasmBinary>,
+    /// The Wasm heap of the canister.
+    pub wasm_memory: Memory,
+    /// The stable memory of the canister.
+    pub stable_memory: Memory,
+    /// The state of the exported globals.
+    pub exported_globals: Vec<Global>,
+    /// The functions exported by the Wasm module.
+    pub exports: ExportedFunctions,
+    /// The metadata extracted from the Wasm module.
+    pub metadata: WasmMetadata,
+    /// The certified data of the canister.
+    pub certified_data: Vec<u8>,
+}
+
+impl PartialEq for CanisterSnapshot {
+    fn eq(&self, rhs: &Self) -> bool {
+        (
+            &self.canister_id,
+            &self.taken_at_timestamp,
+            &self.wasm_binary.binary,
+            &self.wasm_memory,
+            &self.stable_memory,
+            &self.exported_globals,
+            &self.exports,
+            &self.metadata,
+            &self.certified_data,
+        ) == (
+            &rhs.canister_id,
+            &rhs.taken_at_timestamp,
+            &rhs.wasm_binary.binary,
+            &rhs.wasm_memory,
+            &rhs.stable_memory,
+            &rhs.exported_globals,
+            &rhs.exports,
+            &rhs.metadata,
+            &rhs.certified_data,
+        )
+    }
+}
+
+impl CanisterSnapshot {
+    /// Captures a snapshot of the given canister. Returns `None` if the
+    /// canister has no Wasm module installed.
+    pub fn from_canister(canister: &CanisterState, taken_at_timestamp: Time) -> Option<Self> {
+        let execution_state = canister.execution_state.as_ref()?;
+        Some(Self {
+            canister_id: canister.canister_id(),
+            taken_at_timestamp,
+            wasm_binary: Arc::clone(&execution_state.wasm_binary),
+            // A fresh `Memory` is created so that the sandbox memory handle of
+            // the canister is not shared with the snapshot.
+            wasm_memory: Memory::new(
+                execution_state.wasm_memory.page_map.clone(),
+                execution_state.wasm_memory.size,
+            ),
+            stable_memory: Memory::new(
+                execution_state.stable_memory.page_map.clone(),
+                execution_state.stable_memory.size,
+            ),
+            exported_globals: execution_state.exported_globals.clone(),
+            exports: execution_state.exports.clone(),
+            metadata: execution_state.metadata.clone(),
+            certified_data: canister.system_state.certified_data.clone(),
+        })
+    }
+
+    /// Returns the memory taken by this snapshot. It is computed the same way
+    /// as the memory usage of an `ExecutionState`, plus the certified data.
+    pub fn size(&self) -> NumBytes {
+        // We use 8 bytes per global.
+        let globals_size_bytes = 8 * self.exported_globals.len() as u64;
+        let wasm_binary_size_bytes = self.wasm_binary.binary.len() as u64;
+        num_bytes_try_from(self.wasm_memory.size)
+            .expect("could not convert from wasm memory number of pages to bytes")
+            + num_bytes_try_from(self.stable_memory.size)
+                .expect("could not convert from stable memory number of pages to bytes")
+            + NumBytes::from(globals_size_bytes)
+            + NumBytes::from(wasm_binary_size_bytes)
+            + NumBytes::from(self.certified_data.len() as u64)
+    }
+}
+
+/// The collection of all canister snapshots on the subnet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CanisterSnapshots {
+    snapshots: BTreeMap<SnapshotId, Arc<CanisterSnapshot>>,
+}
+
+impl CanisterSnapshots {
+    /// Returns the snapshot with the given id, if any.
+    pub fn get(&self, snapshot_id: SnapshotId) -> Option<&Arc<CanisterSnapshot>> {
+        self.snapshots.get(&snapshot_id)
+    }
+
+    /// Inserts a snapshot under the given id.
+    pub fn insert(&mut self, snapshot_id: SnapshotId, snapshot: Arc<CanisterSnapshot>) {
+        self.snapshots.insert(snapshot_id, snapshot);
+    }
+
+    /// Removes the snapshot with the given id and returns it, if any.
+    pub fn remove(&mut self, snapshot_id: SnapshotId) -> Option<Arc<CanisterSnapshot>> {
+        self.snapshots.remove(&snapshot_id)
+    }
+
+    /// Iterates over all snapshots, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (&SnapshotId, &Arc<CanisterSnapshot>)> {
+        self.snapshots.iter()
+    }
+
+    /// Iterates over the snapshots of the given canister, ordered by id.
+    pub fn list_snapshots(
+        &self,
+        canister_id: CanisterId,
+    ) -> impl Iterator<Item = (&SnapshotId, &Arc<CanisterSnapshot>)> {
+        self.snapshots
+            .iter()
+            .filter(move |(_, snapshot)| snapshot.canister_id == canister_id)
+    }
+
+    /// Returns the number of snapshots of the given canister.
+    pub fn count(&self, canister_id: CanisterId) -> usize {
+        self.list_snapshots(canister_id).count()
+    }
+
+    /// Returns the total memory taken by the snapshots of the given canister.
+    pub fn memory_taken(&self, canister_id: CanisterId) -> NumBytes {
+        self.list_snapshots(canister_id)
+            .map(|(_, snapshot)| snapshot.size())
+            .fold(NumBytes::from(0), |acc, size| acc + size)
+    }
+
+    /// Returns the total memory taken by the snapshots of every canister that
+    /// has at least one snapshot.
+    pub fn memory_taken_per_canister(&self) -> BTreeMap<CanisterId, NumBytes> {
+        let mut result = BTreeMap::new();
+        for snapshot in self.snapshots.values() {
+            *result
+                .entry(snapshot.canister_id)
+                .or_insert_with(|| NumBytes::from(0)) += snapshot.size();
+        }
+        result
+    }
+
+    /// Removes all snapshots of the given canister and returns their ids.
+    pub fn remove_canister_snapshots(&mut self, canister_id: CanisterId) -> Vec<SnapshotId> {
+        let ids: Vec<SnapshotId> = self
+            .list_snapshots(canister_id)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &ids {
+            self.snapshots.remove(id);
+        }
+        ids
+    }
+}
@@ -15,7 +15,7 @@ use ic_types::{
     messages::{Ingress, Request, RequestOrResponse, Response},
     methods::WasmMethod,
     AccumulatedPriority, CanisterId, CanisterStatusType, ComputeAllocation, ExecutionRound,
-    MemoryAllocation, NumBytes, PrincipalId, QueueIndex,
+    MemoryAllocation, NumBytes, PrincipalId, QueueIndex, Time,
 };
 use phantom_newtype::AmountOf;
 pub use queues::{CanisterQueues, DEFAULT_QUEUE_CAPACITY, QUEUE_INDEX_NONE};
@@ -228,6 +228,7 @@ impl CanisterState {
             .as_ref()
             .map_or(NumBytes::from(0), |es| es.memory_usage())
             + self.system_state.memory_usage(own_subnet_type)
+            + self.system_state.wasm_chunk_store.memory_usage()
     }
 
     /// Hack to get the dashboard templating working.
@@ -270,6 +271,21 @@ impl CanisterState {
         }
     }
 
+    /// Returns true if the canister exports the `canister_global_timer` system
+    /// method, is running and the deadline of its global timer has passed.
+    pub fn is_global_timer_due(&self, time: Time) -> bool {
+        let is_due = match self.system_state.global_timer {
+            Some(deadline) => deadline <= time,
+            None => false,
+        };
+        let exports_global_timer_method = match &self.execution_state {
+            Some(execution_state) => execution_state
+                .exports_method(&WasmMethod::System(SystemMethod::CanisterGlobalTimer)),
+            None => false,
+        };
+        is_due && exports_global_timer_method && self.status() == CanisterStatusType::Running
+    }
+
     /// Returns true if the canister contains an exported query method with the
     /// name provided, false otherwise.
     pub fn exports_query_method(&self, method_name: String) -> bool {
@@ -281,6 +297,17 @@ impl CanisterState {
         }
     }
 
+    /// Returns true if the canister contains an exported composite query
+    /// method with the name provided, false otherwise.
+    pub fn exports_composite_query_method(&self, method_name: String) -> bool {
+        match &self.execution_state {
+            Some(execution_state) => {
+                execution_state.exports_method(&WasmMethod::CompositeQuery(method_name))
+            }
+            None => false,
+        }
+    }
+
     /// Returns the number of global variables in the Wasm module.
     pub fn num_wasm_globals(&self) -> usize {
         match &self.execution_state {
@@ -10,7 +10,9 @@ use ic_protobuf::{
     types::v1 as pb_types,
 };
 use ic_types::{
-    messages::{Ingress, Request, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES},
+    messages::{
+        CallbackId, Ingress, Request, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
+    },
     xnet::{QueueId, SessionId},
     CanisterId, CountBytes, QueueIndex,
 };
@@ -362,6 +364,18 @@ impl CanisterQueues {
                 .any(|(_, queue)| queue.num_messages() > 0)
     }
 
+    /// Returns `true` if the input queue from `respondent` holds a response
+    /// for the given callback; `false` otherwise.
+    pub(super) fn has_input_response(
+        &self,
+        respondent: &CanisterId,
+        callback_id: CallbackId,
+    ) -> bool {
+        self.input_queues
+            .get(respondent)
+            .map_or(false, |queue| queue.has_response_for(callback_id))
+    }
+
     /// Returns `true` if at least one output queue is not empty; false
     /// otherwise.
     pub fn has_output(&self) -> bool {
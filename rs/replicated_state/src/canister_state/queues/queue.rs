@@ -6,7 +6,7 @@ use ic_protobuf::proxy::ProxyDecodeError;
 use ic_protobuf::state::{ingress::v1 as pb_ingress, queues::v1 as pb_queues};
 use ic_types::CountBytes;
 use ic_types::{
-    messages::{Ingress, Request, RequestOrResponse, Response},
+    messages::{CallbackId, Ingress, Request, RequestOrResponse, Response},
     QueueIndex,
 };
 use std::{
@@ -217,6 +217,18 @@ impl InputQueue {
         self.queue.pop()
     }
 
+    /// Returns `true` if the queue holds a response for the given callback.
+    ///
+    /// Time complexity: O(num_messages).
+    pub(super) fn has_response_for(&self, callback_id: CallbackId) -> bool {
+        self.queue.queue.iter().any(|msg| match msg.as_ref() {
+            RequestOrResponse::Response(response) => {
+                response.originator_reply_callback == callback_id
+            }
+            RequestOrResponse::Request(_) => false,
+        })
+    }
+
     /// Returns the number of actual messages in the queue.
     pub(super) fn num_messages(&self) -> usize {
         self.queue.num_messages()
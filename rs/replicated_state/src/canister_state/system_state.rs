@@ -1,10 +1,18 @@
 mod call_context_manager;
+mod canister_history;
+mod canister_log;
+mod wasm_chunk_store;
 
 pub use super::queues::memory_required_to_push_request;
-use super::{queues::can_push, ENFORCE_MESSAGE_MEMORY_USAGE};
+use super::{
+    queues::{can_push, QUEUE_INDEX_NONE},
+    ENFORCE_MESSAGE_MEMORY_USAGE,
+};
 pub use crate::canister_state::queues::CanisterOutputQueuesIterator;
 use crate::{CanisterQueues, InputQueueType, StateError};
 pub use call_context_manager::{CallContext, CallContextAction, CallContextManager, CallOrigin};
+pub use canister_history::{CanisterHistory, MAX_CANISTER_HISTORY_CHANGES};
+pub use canister_log::{CanisterLog, MAX_CANISTER_LOG_BUFFER_SIZE};
 use ic_base_types::NumSeconds;
 use ic_interfaces::messages::CanisterInputMessage;
 use ic_protobuf::{
@@ -13,9 +21,13 @@ use ic_protobuf::{
 };
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
-    messages::{Ingress, Request, RequestOrResponse, Response, StopCanisterContext},
+    ic00::LogVisibility,
+    messages::{
+        Ingress, Payload, RejectContext, Request, RequestOrResponse, Response, StopCanisterContext,
+    },
     nominal_cycles::NominalCycles,
-    CanisterId, Cycles, MemoryAllocation, NumBytes, PrincipalId, QueueIndex,
+    user_error::RejectCode,
+    CanisterId, Cycles, MemoryAllocation, NumBytes, PrincipalId, QueueIndex, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreeset;
@@ -23,6 +35,9 @@ use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 use std::{collections::BTreeSet, sync::Arc};
+pub use wasm_chunk_store::{
+    WasmChunkHash, WasmChunkStore, WasmChunkStoreError, CHUNK_SIZE, DEFAULT_MAX_CHUNKS,
+};
 
 lazy_static! {
     static ref DEFAULT_PRINCIPAL_MULTIPLE_CONTROLLERS: PrincipalId =
@@ -73,6 +88,32 @@ pub struct SystemState {
     /// See also:
     ///   * https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-certified-data
     pub certified_data: Vec<u8>,
+    /// The deadline of the canister's global timer, if it is active.
+    ///
+    /// The timer is set by the canister by calling ic0.global_timer_set. Once
+    /// the deadline has passed, the timer is deactivated and the
+    /// `canister_global_timer` method of the canister is executed.
+    pub global_timer: Option<Time>,
+    /// The chunks uploaded via `upload_chunk` for use by
+    /// `install_chunked_code`. The chunks count toward the memory usage of
+    /// the canister.
+    pub wasm_chunk_store: WasmChunkStore,
+    /// The most recent `ic0.debug_print` and trap messages of the canister,
+    /// as returned by `fetch_canister_logs`.
+    pub canister_log: CanisterLog,
+    /// Who is allowed to fetch the canister log.
+    pub log_visibility: LogVisibility,
+    /// The most recent changes of the canister, as returned by
+    /// `canister_info`.
+    pub canister_history: CanisterHistory,
+    /// A counter that is incremented every time the code, the settings or
+    /// the controllers of the canister change. Exposed to the canister via
+    /// `ic0.canister_version`.
+    pub canister_version: u64,
+    /// An upper bound on the size of the Wasm heap that update calls may
+    /// grow to. `canister_pre_upgrade` is exempt so that a canister that
+    /// outgrew its limit can still be upgraded.
+    pub wasm_memory_limit: Option<NumBytes>,
     pub canister_metrics: CanisterMetrics,
 
     /// A canister's state has an associated cycles balance, and may `send` a
@@ -235,6 +276,13 @@ impl SystemState {
             freeze_threshold,
             status,
             certified_data: Default::default(),
+            global_timer: None,
+            wasm_chunk_store: WasmChunkStore::default(),
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
             canister_metrics: CanisterMetrics::default(),
         }
     }
@@ -264,6 +312,13 @@ impl SystemState {
         freeze_threshold: NumSeconds,
         status: CanisterStatus,
         certified_data: Vec<u8>,
+        global_timer: Option<Time>,
+        wasm_chunk_store: WasmChunkStore,
+        canister_log: CanisterLog,
+        log_visibility: LogVisibility,
+        canister_history: CanisterHistory,
+        canister_version: u64,
+        wasm_memory_limit: Option<NumBytes>,
         canister_metrics: CanisterMetrics,
         cycles_balance: Cycles,
     ) -> Self {
@@ -275,6 +330,13 @@ impl SystemState {
             freeze_threshold,
             status,
             certified_data,
+            global_timer,
+            wasm_chunk_store,
+            canister_log,
+            log_visibility,
+            canister_history,
+            canister_version,
+            wasm_memory_limit,
             canister_metrics,
             cycles_balance,
         }
@@ -425,6 +487,18 @@ impl SystemState {
             msg.receiver()
         );
 
+        // Drop the late response to a timed-out best-effort call: the timeout
+        // reject already used up the input queue slot reserved for it, so
+        // enqueuing it would take the slot reserved for another call.
+        if let RequestOrResponse::Response(response) = &msg {
+            let callback_id = response.originator_reply_callback;
+            if let Some(call_context_manager) = self.call_context_manager_mut() {
+                if call_context_manager.take_timed_out(callback_id) {
+                    return Err((StateError::LateResponse(callback_id), msg));
+                }
+            }
+        }
+
         match (&msg, &self.status) {
             // Requests and responses are both rejected when stopped.
             (_, CanisterStatus::Stopped { .. }) => {
@@ -512,6 +586,69 @@ impl SystemState {
         self.queues.filter_ingress_messages(filter);
     }
 
+    /// Completes the best-effort calls whose deadline is before `current_time`
+    /// by enqueuing a `SYS_UNKNOWN` reject response for each of them into the
+    /// slot reserved for the actual response. Calls whose response is already
+    /// enqueued are left alone. The calls are recorded as timed out, so that
+    /// their late responses are dropped on induction.
+    ///
+    /// The cycles attached to a timed-out call are not refunded: the callee may
+    /// have accepted some of them before the deadline, so refunding them could
+    /// create cycles. As the late response carrying the actual refund is
+    /// dropped, the attached cycles are lost.
+    ///
+    /// Returns the number of calls that were timed out.
+    pub fn time_out_callbacks(&mut self, current_time: Time) -> usize {
+        let call_context_manager = match self.call_context_manager() {
+            Some(call_context_manager) => call_context_manager,
+            None => return 0,
+        };
+        let own_canister_id = self.canister_id;
+        let rejects: Vec<Response> = call_context_manager
+            .expired_callbacks(current_time)
+            .filter_map(|(callback_id, callback)| {
+                let respondent = callback.respondent?;
+                if self.queues.has_input_response(&respondent, *callback_id) {
+                    return None;
+                }
+                // It is unknown whether the callee accepted any of the cycles
+                // sent, so none are refunded (see above).
+                Some(Response {
+                    originator: own_canister_id,
+                    respondent,
+                    originator_reply_callback: *callback_id,
+                    refund: Cycles::zero(),
+                    response_payload: Payload::Reject(RejectContext::new(
+                        RejectCode::SysUnknown,
+                        "Call deadline has expired.".to_string(),
+                    )),
+                })
+            })
+            .collect();
+
+        let mut timed_out = 0;
+        for response in rejects {
+            let callback_id = response.originator_reply_callback;
+            // Only fails if no slot was reserved, i.e. there is nothing to
+            // time out.
+            if self
+                .queues
+                .push_input(
+                    QUEUE_INDEX_NONE,
+                    RequestOrResponse::Response(response),
+                    InputQueueType::LocalSubnet,
+                )
+                .is_ok()
+            {
+                if let Some(call_context_manager) = self.call_context_manager_mut() {
+                    call_context_manager.mark_timed_out(callback_id);
+                }
+                timed_out += 1;
+            }
+        }
+        timed_out
+    }
+
     /// Returns the memory that is currently used by the `SystemState`.
     pub fn memory_usage(&self, own_subnet_type: SubnetType) -> NumBytes {
         if ENFORCE_MESSAGE_MEMORY_USAGE && own_subnet_type != SubnetType::System {
@@ -9,10 +9,10 @@ use ic_types::{
     ingress::WasmResult,
     messages::{CallContextId, CallbackId, MessageId},
     methods::Callback,
-    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, UserId,
+    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, Time, UserId,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{From, TryFrom, TryInto};
 
 /// Call context contains all context information related to an incoming call.
@@ -192,6 +192,12 @@ pub struct CallContextManager {
     // maps call context to its responded status
     call_contexts: BTreeMap<CallContextId, CallContext>,
     callbacks: BTreeMap<CallbackId, Callback>,
+    // Best-effort calls that were completed with a timeout reject and whose
+    // actual response has not arrived yet. The reject used up the input queue
+    // slot reserved for the response, so the late response must be dropped.
+    // Requests that expire in flight are rejected by the receiving subnet, so
+    // a late response always arrives eventually and removes the entry.
+    timed_out_callbacks: BTreeSet<CallbackId>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -453,6 +459,32 @@ impl CallContextManager {
             .map(|cc| cc.responded)
     }
 
+    /// Returns the callbacks of best-effort calls whose deadline is before
+    /// `current_time`.
+    pub fn expired_callbacks(
+        &self,
+        current_time: Time,
+    ) -> impl Iterator<Item = (&CallbackId, &Callback)> {
+        self.callbacks.iter().filter(move |(_, callback)| {
+            callback
+                .deadline
+                .map_or(false, |deadline| deadline < current_time)
+        })
+    }
+
+    /// Records that the call with the given callback was completed with a
+    /// timeout reject, so that its late response, if any, is dropped.
+    pub fn mark_timed_out(&mut self, callback_id: CallbackId) {
+        self.timed_out_callbacks.insert(callback_id);
+    }
+
+    /// Returns `true` if the call with the given callback was timed out,
+    /// i.e. a response for it is a late response that must be dropped. Only
+    /// one late response is expected per call, so the record is removed.
+    pub fn take_timed_out(&mut self, callback_id: CallbackId) -> bool {
+        self.timed_out_callbacks.remove(&callback_id)
+    }
+
     pub fn outstanding_calls(&self, call_context_id: CallContextId) -> usize {
         self.callbacks
             .iter()
@@ -495,6 +527,11 @@ impl From<&CallContextManager> for pb::CallContextManager {
                     callback: Some(callback.into()),
                 })
                 .collect(),
+            timed_out_callbacks: item
+                .timed_out_callbacks
+                .iter()
+                .map(|callback_id| callback_id.get())
+                .collect(),
         }
     }
 }
@@ -530,6 +567,11 @@ impl TryFrom<pb::CallContextManager> for CallContextManager {
             next_callback_id: value.next_callback_id,
             call_contexts,
             callbacks,
+            timed_out_callbacks: value
+                .timed_out_callbacks
+                .into_iter()
+                .map(CallbackId::from)
+                .collect(),
         })
     }
 }
@@ -55,6 +55,8 @@ fn call_context_handling() {
         WasmClosure::new(0, 1),
         WasmClosure::new(2, 3),
         None,
+        None,
+        None,
     ));
     let cb_id2 = ccm.register_callback(Callback::new(
         cc_id,
@@ -62,6 +64,8 @@ fn call_context_handling() {
         WasmClosure::new(4, 5),
         WasmClosure::new(6, 7),
         None,
+        None,
+        None,
     ));
 
     // There are 2 ougoing calls
@@ -74,6 +78,8 @@ fn call_context_handling() {
         WasmClosure::new(8, 9),
         WasmClosure::new(10, 11),
         None,
+        None,
+        None,
     ));
     // There is 1 outgoing call
     assert_eq!(ccm.outstanding_calls(cc_id2), 1);
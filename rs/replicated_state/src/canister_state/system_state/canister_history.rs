@@ -0,0 +1,121 @@
+use ic_types::{
+    ic00::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin},
+    Time,
+};
+use std::collections::VecDeque;
+
+/// The maximum number of changes kept in the history of a canister. Older
+/// changes are dropped, but still counted in `total_num_changes`.
+pub const MAX_CANISTER_HISTORY_CHANGES: usize = 20;
+
+/// The most recent changes of a canister, i.e. its creation, code
+/// deployments, code uninstallations and controller changes, as returned by
+/// `canister_info`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanisterHistory {
+    changes: VecDeque<CanisterChange>,
+    total_num_changes: u64,
+}
+
+impl CanisterHistory {
+    /// Creates a history from the given changes, e.g. when loading a
+    /// checkpoint.
+    pub fn new(changes: Vec<CanisterChange>, total_num_changes: u64) -> Self {
+        Self {
+            changes: changes.into(),
+            total_num_changes,
+        }
+    }
+
+    /// Records a change that resulted in the given canister version, dropping
+    /// the oldest one if the history already holds
+    /// `MAX_CANISTER_HISTORY_CHANGES` changes.
+    pub fn add_canister_change(
+        &mut self,
+        timestamp: Time,
+        canister_version: u64,
+        origin: CanisterChangeOrigin,
+        details: CanisterChangeDetails,
+    ) {
+        if self.changes.len() >= MAX_CANISTER_HISTORY_CHANGES {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(CanisterChange {
+            timestamp_nanos: timestamp.as_nanos_since_unix_epoch(),
+            canister_version,
+            origin,
+            details,
+        });
+        self.total_num_changes += 1;
+    }
+
+    /// Returns the kept changes, oldest first.
+    pub fn changes(&self) -> impl Iterator<Item = &CanisterChange> {
+        self.changes.iter()
+    }
+
+    /// Returns up to `num_requested_changes` of the most recent changes,
+    /// oldest first.
+    pub fn recent_changes(&self, num_requested_changes: usize) -> Vec<CanisterChange> {
+        let num_changes = num_requested_changes.min(self.changes.len());
+        self.changes
+            .iter()
+            .skip(self.changes.len() - num_changes)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the number of changes ever recorded, including the dropped
+    /// ones.
+    pub fn total_num_changes(&self) -> u64 {
+        self.total_num_changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::{time::UNIX_EPOCH, PrincipalId};
+
+    fn add_uninstall(history: &mut CanisterHistory) {
+        history.add_canister_change(
+            UNIX_EPOCH,
+            history.total_num_changes(),
+            CanisterChangeOrigin::FromUser {
+                user_id: PrincipalId::new_user_test_id(1),
+            },
+            CanisterChangeDetails::CodeUninstall,
+        );
+    }
+
+    #[test]
+    fn oldest_changes_are_dropped_but_counted() {
+        let mut history = CanisterHistory::default();
+        for _ in 0..MAX_CANISTER_HISTORY_CHANGES + 5 {
+            add_uninstall(&mut history);
+        }
+        assert_eq!(history.changes().count(), MAX_CANISTER_HISTORY_CHANGES);
+        assert_eq!(
+            history.total_num_changes(),
+            MAX_CANISTER_HISTORY_CHANGES as u64 + 5
+        );
+        assert_eq!(history.changes().next().unwrap().canister_version, 5);
+    }
+
+    #[test]
+    fn recent_changes_returns_most_recent_changes_oldest_first() {
+        let mut history = CanisterHistory::default();
+        for _ in 0..3 {
+            add_uninstall(&mut history);
+        }
+        let versions = |changes: Vec<CanisterChange>| {
+            changes
+                .iter()
+                .map(|change| change.canister_version)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions(history.recent_changes(2)), vec![1, 2]);
+        assert_eq!(versions(history.recent_changes(10)), vec![0, 1, 2]);
+        assert!(history.recent_changes(0).is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use ic_types::{ic00::CanisterLogRecord, Time};
+use std::collections::VecDeque;
+
+/// The maximum total size of the contents of the records in a canister log.
+/// Once a new record would exceed it, the oldest records are dropped.
+pub const MAX_CANISTER_LOG_BUFFER_SIZE: usize = 4 * 1024;
+
+/// A bounded buffer of the `ic0.debug_print` and trap messages of a canister,
+/// as returned by `fetch_canister_logs`.
+///
+/// Every record is assigned an index that is unique across the lifetime of
+/// the canister, so that clients polling the log can tell which records they
+/// have already seen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanisterLog {
+    records: VecDeque<CanisterLogRecord>,
+    next_idx: u64,
+    size_bytes: usize,
+}
+
+impl CanisterLog {
+    /// Creates a log from the given records, e.g. when loading a checkpoint.
+    pub fn new(records: Vec<CanisterLogRecord>, next_idx: u64) -> Self {
+        let size_bytes = records.iter().map(|record| record.content.len()).sum();
+        Self {
+            records: records.into(),
+            next_idx,
+            size_bytes,
+        }
+    }
+
+    /// Appends a record with the given content, evicting the oldest records
+    /// if the buffer would exceed `MAX_CANISTER_LOG_BUFFER_SIZE`. Content
+    /// longer than the whole buffer is truncated.
+    pub fn add_record(&mut self, timestamp: Time, mut content: Vec<u8>) {
+        content.truncate(MAX_CANISTER_LOG_BUFFER_SIZE);
+        self.size_bytes += content.len();
+        self.records.push_back(CanisterLogRecord {
+            idx: self.next_idx,
+            timestamp_nanos: timestamp.as_nanos_since_unix_epoch(),
+            content,
+        });
+        self.next_idx += 1;
+        while self.size_bytes > MAX_CANISTER_LOG_BUFFER_SIZE {
+            match self.records.pop_front() {
+                Some(record) => self.size_bytes -= record.content.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Returns the records in the order in which they were added.
+    pub fn records(&self) -> impl Iterator<Item = &CanisterLogRecord> {
+        self.records.iter()
+    }
+
+    /// Returns the index that the next record will be assigned.
+    pub fn next_idx(&self) -> u64 {
+        self.next_idx
+    }
+
+    /// Returns the total size of the contents of the records.
+    pub fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::time::UNIX_EPOCH;
+
+    #[test]
+    fn records_get_consecutive_indices() {
+        let mut log = CanisterLog::default();
+        log.add_record(UNIX_EPOCH, b"a".to_vec());
+        log.add_record(UNIX_EPOCH, b"b".to_vec());
+        assert_eq!(
+            log.records().map(|record| record.idx).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(log.next_idx(), 2);
+        assert_eq!(log.size_bytes(), 2);
+    }
+
+    #[test]
+    fn oldest_records_are_evicted_when_buffer_is_full() {
+        let mut log = CanisterLog::default();
+        let half = MAX_CANISTER_LOG_BUFFER_SIZE / 2;
+        log.add_record(UNIX_EPOCH, vec![0; half]);
+        log.add_record(UNIX_EPOCH, vec![1; half]);
+        log.add_record(UNIX_EPOCH, vec![2; 1]);
+        assert_eq!(
+            log.records().map(|record| record.idx).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(log.size_bytes(), half + 1);
+    }
+
+    #[test]
+    fn oversized_record_is_truncated() {
+        let mut log = CanisterLog::default();
+        log.add_record(UNIX_EPOCH, vec![0; MAX_CANISTER_LOG_BUFFER_SIZE + 10]);
+        let records: Vec<_> = log.records().collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].content.len(), MAX_CANISTER_LOG_BUFFER_SIZE);
+    }
+}
@@ -0,0 +1,150 @@
+use ic_types::NumBytes;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// The maximum size of a single chunk in the Wasm chunk store.
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// The maximum number of chunks a canister can store at any time.
+pub const DEFAULT_MAX_CHUNKS: usize = 100;
+
+/// The SHA-256 hash of a chunk.
+pub type WasmChunkHash = [u8; 32];
+
+/// Errors that can occur when uploading a chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WasmChunkStoreError {
+    /// The chunk is larger than `CHUNK_SIZE`.
+    ChunkTooLarge { size: usize },
+    /// The store already contains `DEFAULT_MAX_CHUNKS` chunks.
+    StoreFull { max_chunks: usize },
+}
+
+/// Content-addressed storage of the chunks uploaded via `upload_chunk`, from
+/// which `install_chunked_code` assembles a Wasm module that is too large to
+/// fit in a single message.
+///
+/// The chunks are kept behind `Arc`s so that cloning the state between
+/// rounds does not copy their contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WasmChunkStore {
+    chunks: BTreeMap<WasmChunkHash, Arc<Vec<u8>>>,
+}
+
+impl WasmChunkStore {
+    /// Returns the chunk with the given hash, if it is in the store.
+    pub fn get_chunk(&self, hash: &WasmChunkHash) -> Option<&[u8]> {
+        self.chunks.get(hash).map(|chunk| chunk.as_slice())
+    }
+
+    /// Inserts `chunk` into the store and returns its hash. Uploading a chunk
+    /// that is already stored always succeeds and leaves the store unchanged.
+    pub fn insert_chunk(&mut self, chunk: Vec<u8>) -> Result<WasmChunkHash, WasmChunkStoreError> {
+        if chunk.len() as u64 > CHUNK_SIZE {
+            return Err(WasmChunkStoreError::ChunkTooLarge { size: chunk.len() });
+        }
+        let hash = ic_crypto_sha::Sha256::hash(&chunk);
+        if !self.chunks.contains_key(&hash) {
+            if self.chunks.len() >= DEFAULT_MAX_CHUNKS {
+                return Err(WasmChunkStoreError::StoreFull {
+                    max_chunks: DEFAULT_MAX_CHUNKS,
+                });
+            }
+            self.chunks.insert(hash, Arc::new(chunk));
+        }
+        Ok(hash)
+    }
+
+    /// Removes the chunk with the given hash from the store.
+    pub fn remove_chunk(&mut self, hash: &WasmChunkHash) {
+        self.chunks.remove(hash);
+    }
+
+    /// Returns the hashes of all stored chunks in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = &WasmChunkHash> {
+        self.chunks.keys()
+    }
+
+    /// Returns the stored chunks in ascending order of their hashes.
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.values().map(|chunk| chunk.as_slice())
+    }
+
+    /// Returns the number of stored chunks.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns true if the store contains no chunks.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Removes all chunks from the store.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Returns the memory taken by the stored chunks. Every chunk takes a full
+    /// `CHUNK_SIZE`, regardless of its actual size, so that the memory usage
+    /// of the store does not depend on how the module was split.
+    pub fn memory_usage(&self) -> NumBytes {
+        NumBytes::from(self.chunks.len() as u64 * CHUNK_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_chunk_returns_sha256_of_chunk() {
+        let mut store = WasmChunkStore::default();
+        let chunk = vec![1, 2, 3];
+        let hash = store.insert_chunk(chunk.clone()).unwrap();
+        assert_eq!(hash, ic_crypto_sha::Sha256::hash(&chunk));
+        assert_eq!(store.get_chunk(&hash), Some(chunk.as_slice()));
+    }
+
+    #[test]
+    fn inserting_same_chunk_twice_stores_it_once() {
+        let mut store = WasmChunkStore::default();
+        store.insert_chunk(vec![1, 2, 3]).unwrap();
+        store.insert_chunk(vec![1, 2, 3]).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.memory_usage(), NumBytes::from(CHUNK_SIZE));
+    }
+
+    #[test]
+    fn chunk_larger_than_chunk_size_is_rejected() {
+        let mut store = WasmChunkStore::default();
+        let chunk = vec![0; CHUNK_SIZE as usize + 1];
+        assert_eq!(
+            store.insert_chunk(chunk),
+            Err(WasmChunkStoreError::ChunkTooLarge {
+                size: CHUNK_SIZE as usize + 1
+            })
+        );
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn full_store_rejects_new_chunks_but_accepts_known_ones() {
+        let mut store = WasmChunkStore::default();
+        for i in 0..DEFAULT_MAX_CHUNKS {
+            store
+                .insert_chunk((i as u64).to_le_bytes().to_vec())
+                .unwrap();
+        }
+        assert_eq!(
+            store.insert_chunk(vec![42; 100]),
+            Err(WasmChunkStoreError::StoreFull {
+                max_chunks: DEFAULT_MAX_CHUNKS
+            })
+        );
+        assert!(store.insert_chunk(0u64.to_le_bytes().to_vec()).is_ok());
+
+        store.clear();
+        assert!(store.is_empty());
+        assert_eq!(store.memory_usage(), NumBytes::from(0));
+    }
+}
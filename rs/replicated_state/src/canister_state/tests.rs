@@ -1,11 +1,20 @@
 use super::*;
+use crate::CallOrigin;
 use ic_base_types::NumSeconds;
-use ic_test_utilities::types::{
-    ids::user_test_id,
-    messages::{RequestBuilder, ResponseBuilder},
+use ic_test_utilities::{
+    mock_time,
+    types::{
+        ids::user_test_id,
+        messages::{RequestBuilder, ResponseBuilder},
+    },
+};
+use ic_types::{
+    messages::{CallbackId, MAX_RESPONSE_COUNT_BYTES},
+    methods::{Callback, WasmClosure},
+    CountBytes, Cycles,
 };
-use ic_types::{messages::MAX_RESPONSE_COUNT_BYTES, CountBytes, Cycles};
 use ic_wasm_types::BinaryEncodedWasm;
+use std::time::Duration;
 
 const INITIAL_CYCLES: Cycles = Cycles::new(1 << 36);
 const MAX_CANISTER_MEMORY_SIZE: NumBytes = NumBytes::new(u64::MAX / 2);
@@ -100,6 +109,95 @@ fn canister_state_push_input_response_success() {
     })
 }
 
+#[test]
+fn canister_state_push_input_late_response_is_dropped() {
+    canister_state_test(|mut canister_state| {
+        let deadline = mock_time() + Duration::from_secs(10);
+        let call_context_manager = canister_state
+            .system_state
+            .call_context_manager_mut()
+            .unwrap();
+        let call_context_id = call_context_manager.new_call_context(
+            CallOrigin::CanisterUpdate(OTHER_CANISTER_ID, CallbackId::from(1)),
+            Cycles::zero(),
+        );
+        // A best-effort and a guaranteed-response call to the same callee.
+        let mut register_callback = |deadline| {
+            call_context_manager.register_callback(Callback::new(
+                call_context_id,
+                Cycles::zero(),
+                WasmClosure::new(0, 0),
+                WasmClosure::new(0, 0),
+                None,
+                Some(OTHER_CANISTER_ID),
+                deadline,
+            ))
+        };
+        let best_effort_callback = register_callback(Some(deadline));
+        let guaranteed_callback = register_callback(None);
+        for (callback, deadline) in &[
+            (best_effort_callback, Some(deadline)),
+            (guaranteed_callback, None),
+        ] {
+            canister_state
+                .push_output_request(
+                    RequestBuilder::default()
+                        .sender(CANISTER_ID)
+                        .receiver(OTHER_CANISTER_ID)
+                        .sender_reply_callback(*callback)
+                        .deadline(*deadline)
+                        .build(),
+                )
+                .unwrap();
+        }
+        canister_state.output_into_iter().count();
+
+        // Time out the best-effort call and execute the reject.
+        assert_eq!(
+            1,
+            canister_state
+                .system_state
+                .time_out_callbacks(deadline + Duration::from_secs(1))
+        );
+        match canister_state.pop_input() {
+            Some(CanisterInputMessage::Response(response)) => {
+                assert_eq!(best_effort_callback, response.originator_reply_callback)
+            }
+            msg => panic!("Expected a response, got {:?}", msg),
+        }
+        canister_state
+            .system_state
+            .call_context_manager_mut()
+            .unwrap()
+            .unregister_callback(best_effort_callback);
+
+        let mut push_response = |callback| {
+            canister_state.push_input(
+                QueueIndex::from(0),
+                ResponseBuilder::default()
+                    .respondent(OTHER_CANISTER_ID)
+                    .originator(CANISTER_ID)
+                    .originator_reply_callback(callback)
+                    .build()
+                    .into(),
+                MAX_CANISTER_MEMORY_SIZE,
+                &mut SUBNET_AVAILABLE_MEMORY.clone(),
+                SubnetType::Application,
+                InputQueueType::RemoteSubnet,
+            )
+        };
+        // The late response is dropped instead of taking the slot reserved for
+        // the response to the other call.
+        match push_response(best_effort_callback) {
+            Err((StateError::LateResponse(callback), _)) => {
+                assert_eq!(best_effort_callback, callback)
+            }
+            res => panic!("Expected a late response error, got {:?}", res),
+        }
+        push_response(guaranteed_callback).unwrap();
+    })
+}
+
 #[test]
 #[should_panic(expected = "Expected `RequestOrResponse` to be targeted to canister ID")]
 fn canister_state_push_input_request_mismatched_receiver() {
@@ -1,3 +1,5 @@
+pub mod bitcoin_state;
+pub mod canister_snapshots;
 pub mod canister_state;
 pub mod metadata_state;
 pub mod page_map;
@@ -7,12 +9,14 @@ pub mod testing {
     pub use super::canister_state::testing::CanisterQueuesTesting;
     pub use super::replicated_state::testing::ReplicatedStateTesting;
 }
+pub use canister_snapshots::{CanisterSnapshot, CanisterSnapshots};
 pub use canister_state::{
     execution_state::Memory,
     num_bytes_try_from,
     system_state::{
         memory_required_to_push_request, CallContext, CallContextAction, CallContextManager,
-        CallOrigin, CanisterMetrics, CanisterStatus, SystemState,
+        CallOrigin, CanisterHistory, CanisterLog, CanisterMetrics, CanisterStatus, SystemState,
+        WasmChunkHash, WasmChunkStore, WasmChunkStoreError,
     },
     CanisterQueues, CanisterState, EmbedderCache, ExecutionState, ExportedFunctions, Global,
     NumWasmPages, SchedulerState,
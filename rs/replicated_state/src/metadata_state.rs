@@ -12,11 +12,12 @@ use ic_protobuf::{
         system_metadata::v1::{self as pb_metadata, TimeOfLastAllocationCharge},
     },
 };
-use ic_registry_routing_table::RoutingTable;
+use ic_registry_routing_table::{CanisterIdRange, CanisterMigrations, RoutingTable};
 use ic_registry_subnet_features::SubnetFeatures;
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
-    crypto::CryptoHash,
+    crypto::{canister_threshold_sig::EcdsaPublicKey, CryptoHash},
+    ic00::EcdsaKeyId,
     ingress::{IngressStatus, MAX_INGRESS_TTL},
     messages::{MessageId, RequestOrResponse},
     node_id_into_protobuf, node_id_try_from_protobuf, subnet_id_into_protobuf,
@@ -51,6 +52,10 @@ pub struct SystemMetadata {
     /// Used for canister creation.
     pub generated_id_counter: u64,
 
+    /// A counter used for generating new snapshot ids.
+    /// Used for taking canister snapshots.
+    pub next_snapshot_id: u64,
+
     /// The hash of the previous partial canonical state.
     /// The initial state doesn't have any previous state.
     pub prev_state_hash: Option<CryptoHashOfPartialState>,
@@ -115,12 +120,26 @@ pub struct SystemMetadata {
     /// needed to calculate how much time should be charged for when charging
     /// does occur.
     pub time_of_last_allocation_charge: Time,
+
+    /// The threshold ECDSA master public keys of the subnet, as delivered
+    /// with the latest batch. Not persisted, as Message Routing sets it from
+    /// every batch before any message is executed.
+    pub ecdsa_subnet_public_keys: BTreeMap<EcdsaKeyId, EcdsaPublicKey>,
+
+    /// Canister migrations that were completed in the registry while this
+    /// subnet's streams still held messages to or from the migrated canisters,
+    /// mapped to the index just past the last such message in each stream.
+    /// Message Routing keeps these migrations in `network_topology` until all
+    /// of these streams have been garbage collected up to the recorded index.
+    pub draining_migrations: BTreeMap<CanisterIdRange, BTreeMap<SubnetId, StreamIndex>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct NetworkTopology {
     pub subnets: BTreeMap<SubnetId, SubnetTopology>,
     pub routing_table: Arc<RoutingTable>,
+    /// Canister ID ranges that are being migrated between subnets.
+    pub canister_migrations: Arc<CanisterMigrations>,
     pub nns_subnet_id: SubnetId,
 }
 
@@ -129,6 +148,7 @@ impl Default for NetworkTopology {
         Self {
             subnets: Default::default(),
             routing_table: Default::default(),
+            canister_migrations: Default::default(),
             nns_subnet_id: SubnetId::new(PrincipalId::new_anonymous()),
         }
     }
@@ -147,6 +167,7 @@ impl From<&NetworkTopology> for pb_metadata::NetworkTopology {
                 .collect(),
             routing_table: Some(item.routing_table.as_ref().into()),
             nns_subnet_id: Some(subnet_id_into_protobuf(item.nns_subnet_id)),
+            canister_migrations: Some(item.canister_migrations.as_ref().into()),
         }
     }
 }
@@ -179,6 +200,12 @@ impl TryFrom<pb_metadata::NetworkTopology> for NetworkTopology {
                 "NetworkTopology::routing_table",
             )
             .map(Arc::new)?,
+            canister_migrations: item
+                .canister_migrations
+                .map(CanisterMigrations::try_from)
+                .transpose()?
+                .map(Arc::new)
+                .unwrap_or_default(),
             nns_subnet_id,
         })
     }
@@ -292,6 +319,23 @@ impl From<&SystemMetadata> for pb_metadata::SystemMetadata {
                     .time_of_last_allocation_charge
                     .as_nanos_since_unix_epoch(),
             }),
+            next_snapshot_id: item.next_snapshot_id,
+            draining_migrations: item
+                .draining_migrations
+                .iter()
+                .map(|(range, stream_ends)| pb_metadata::DrainingMigration {
+                    range: Some((*range).into()),
+                    stream_ends: stream_ends
+                        .iter()
+                        .map(|(subnet_id, messages_end)| {
+                            pb_metadata::draining_migration::StreamEnd {
+                                subnet_id: Some(subnet_id_into_protobuf(*subnet_id)),
+                                messages_end: messages_end.get(),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect(),
         }
     }
 }
@@ -309,6 +353,23 @@ impl TryFrom<pb_metadata::SystemMetadata> for SystemMetadata {
                 try_from_option_field(entry.subnet_stream, "SystemMetadata::streams::V")?,
             );
         }
+        let mut draining_migrations = BTreeMap::new();
+        for entry in item.draining_migrations {
+            let mut stream_ends = BTreeMap::new();
+            for stream_end in entry.stream_ends {
+                stream_ends.insert(
+                    subnet_id_try_from_protobuf(try_from_option_field(
+                        stream_end.subnet_id,
+                        "DrainingMigration::stream_ends::K",
+                    )?)?,
+                    stream_end.messages_end.into(),
+                );
+            }
+            draining_migrations.insert(
+                try_from_option_field(entry.range, "DrainingMigration::range")?,
+                stream_ends,
+            );
+        }
         Ok(Self {
             own_subnet_id: subnet_id_try_from_protobuf(try_from_option_field(
                 item.own_subnet_id,
@@ -348,6 +409,9 @@ impl TryFrom<pb_metadata::SystemMetadata> for SystemMetadata {
                 ),
                 None => Time::from_nanos_since_unix_epoch(item.batch_time_nanos),
             },
+            next_snapshot_id: item.next_snapshot_id,
+            ecdsa_subnet_public_keys: BTreeMap::new(),
+            draining_migrations,
         })
     }
 }
@@ -372,6 +436,9 @@ impl SystemMetadata {
             certification_version: 0,
             heap_delta_estimate: NumBytes::from(0),
             time_of_last_allocation_charge: UNIX_EPOCH,
+            next_snapshot_id: 0,
+            ecdsa_subnet_public_keys: BTreeMap::new(),
+            draining_migrations: BTreeMap::new(),
         }
     }
 
@@ -398,6 +465,39 @@ impl SystemMetadata {
     pub fn streams(&self) -> &Streams {
         &self.streams
     }
+
+    /// Adjusts the metadata of a subnet that is being split for the half
+    /// `subnet_id`, given the post-split `routing_table`.
+    ///
+    /// The half that retains the original subnet ID keeps its streams and
+    /// subnet-level state, as it remains responsible for all messages in
+    /// flight, except for the streams to subnets that no longer host any
+    /// canister ID range according to `routing_table`: none of their
+    /// messages could be inducted by their destination. The new half starts
+    /// with no streams, no subnet call contexts and no draining migrations,
+    /// under its own subnet ID. Both halves only retain the ingress history of
+    /// the canisters they host.
+    pub fn split(mut self, subnet_id: SubnetId, routing_table: &RoutingTable) -> Self {
+        if subnet_id != self.own_subnet_id {
+            self.own_subnet_id = subnet_id;
+            self.streams = Default::default();
+            self.subnet_call_context_manager = Default::default();
+            self.draining_migrations = Default::default();
+            self.prev_state_hash = None;
+        } else {
+            self.streams
+                .retain(|destination| !routing_table.ranges(*destination).is_empty());
+        }
+
+        self.network_topology.routing_table = Arc::new(routing_table.clone());
+        self.ingress_history
+            .retain(|status| match status.receiver() {
+                Some(receiver) => routing_table.route(receiver.get()) == Some(subnet_id),
+                None => true,
+            });
+
+        self
+    }
 }
 
 /// Stream is the state of bi-directional communication session with a remote
@@ -654,6 +754,12 @@ impl Streams {
         )
     }
 
+    /// Retains only the streams whose destination subnet satisfies `f`.
+    pub fn retain<F: FnMut(&SubnetId) -> bool>(&mut self, mut f: F) {
+        self.streams.retain(|destination, _| f(destination));
+        self.responses_size_bytes = Streams::calculate_stats(&self.streams);
+    }
+
     /// Returns the response sizes by responder canister stat.
     pub fn responses_size_bytes(&self) -> &BTreeMap<CanisterId, usize> {
         &self.responses_size_bytes
@@ -864,6 +970,30 @@ impl IngressHistoryState {
         self.statuses.is_empty()
     }
 
+    /// Retains only the ingress history entries whose status satisfies the
+    /// given predicate.
+    pub fn retain<F: Fn(&IngressStatus) -> bool>(&mut self, predicate: F) {
+        let removed: BTreeSet<MessageId> = self
+            .statuses
+            .iter()
+            .filter(|(_, status)| !predicate(status))
+            .map(|(message_id, _)| message_id.clone())
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+
+        let statuses = Arc::make_mut(&mut self.statuses);
+        for message_id in removed.iter() {
+            statuses.remove(message_id);
+        }
+        let pruning_times = Arc::make_mut(&mut self.pruning_times);
+        for messages in pruning_times.values_mut() {
+            messages.retain(|message_id| !removed.contains(message_id));
+        }
+        pruning_times.retain(|_, messages| !messages.is_empty());
+    }
+
     /// Removes ingress history entries that are associated with a pruning_time
     /// that's older than the given time.
     pub fn prune(&mut self, time: Time) {
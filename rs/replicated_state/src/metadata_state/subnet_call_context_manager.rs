@@ -5,6 +5,7 @@ use ic_protobuf::{
 };
 use ic_types::{
     crypto::threshold_sig::ni_dkg::{id::ni_dkg_target_id, NiDkgTargetId},
+    ic00::{EcdsaKeyId, HttpHeader, HttpMethod},
     messages::{CallbackId, Request},
     node_id_into_protobuf, node_id_try_from_protobuf, NodeId, RegistryVersion, Time,
 };
@@ -18,10 +19,15 @@ pub struct SubnetCallContextManager {
     next_callback_id: u64,
     pub setup_initial_dkg_contexts: BTreeMap<CallbackId, SetupInitialDkgContext>,
     pub sign_with_ecdsa_contexts: BTreeMap<CallbackId, SignWithEcdsaContext>,
-    pub sign_with_mock_ecdsa_contexts: BTreeMap<CallbackId, SignWithEcdsaContext>,
+    pub canister_http_request_contexts: BTreeMap<CallbackId, CanisterHttpRequestContext>,
 }
 
 impl SubnetCallContextManager {
+    /// Returns the callback id that will be assigned to the next request.
+    pub fn next_callback_id(&self) -> CallbackId {
+        CallbackId::new(self.next_callback_id)
+    }
+
     pub fn push_setup_initial_dkg_request(&mut self, context: SetupInitialDkgContext) {
         let callback_id = CallbackId::new(self.next_callback_id);
         self.next_callback_id += 1;
@@ -29,15 +35,19 @@ impl SubnetCallContextManager {
         self.setup_initial_dkg_contexts.insert(callback_id, context);
     }
 
-    pub fn push_sign_with_ecdsa_request(&mut self, context: SignWithEcdsaContext, is_mock: bool) {
+    pub fn push_sign_with_ecdsa_request(&mut self, context: SignWithEcdsaContext) {
+        let callback_id = CallbackId::new(self.next_callback_id);
+        self.next_callback_id += 1;
+
+        self.sign_with_ecdsa_contexts.insert(callback_id, context);
+    }
+
+    pub fn push_http_request(&mut self, context: CanisterHttpRequestContext) {
         let callback_id = CallbackId::new(self.next_callback_id);
         self.next_callback_id += 1;
-        match is_mock {
-            true => self
-                .sign_with_mock_ecdsa_contexts
-                .insert(callback_id, context),
-            false => self.sign_with_ecdsa_contexts.insert(callback_id, context),
-        };
+
+        self.canister_http_request_contexts
+            .insert(callback_id, context);
     }
 
     pub fn retrieve_request(
@@ -69,15 +79,15 @@ impl SubnetCallContextManager {
                     })
             })
             .or_else(|| {
-                self.sign_with_mock_ecdsa_contexts
+                self.canister_http_request_contexts
                     .remove(&callback_id)
                     .map(|context| {
                         info!(
-                    logger,
-                    "Received the response for SignWithMockECDSA request with id {:?} from {:?}",
-                    context.pseudo_random_id,
-                    context.request.sender
-                );
+                            logger,
+                            "Received the response for HttpRequest with callback id {:?} from {:?}",
+                            context.request.sender_reply_callback,
+                            context.request.sender
+                        );
                         context.request
                     })
             })
@@ -108,11 +118,11 @@ impl From<&SubnetCallContextManager> for pb_metadata::SubnetCallContextManager {
                     },
                 )
                 .collect(),
-            sign_with_mock_ecdsa_contexts: item
-                .sign_with_mock_ecdsa_contexts
+            canister_http_request_contexts: item
+                .canister_http_request_contexts
                 .iter()
                 .map(
-                    |(callback_id, context)| pb_metadata::SignWithEcdsaContextTree {
+                    |(callback_id, context)| pb_metadata::CanisterHttpRequestContextTree {
                         callback_id: callback_id.get(),
                         context: Some(context.into()),
                     },
@@ -137,17 +147,18 @@ impl TryFrom<pb_metadata::SubnetCallContextManager> for SubnetCallContextManager
                 try_from_option_field(entry.context, "SystemMetadata::SignWithEcdsaContext")?;
             sign_with_ecdsa_contexts.insert(CallbackId::new(entry.callback_id), context);
         }
-        let mut sign_with_mock_ecdsa_contexts = BTreeMap::<CallbackId, SignWithEcdsaContext>::new();
-        for entry in item.sign_with_mock_ecdsa_contexts {
-            let context: SignWithEcdsaContext =
-                try_from_option_field(entry.context, "SystemMetadata::SignWithMockEcdsaContext")?;
-            sign_with_mock_ecdsa_contexts.insert(CallbackId::new(entry.callback_id), context);
+        let mut canister_http_request_contexts =
+            BTreeMap::<CallbackId, CanisterHttpRequestContext>::new();
+        for entry in item.canister_http_request_contexts {
+            let context: CanisterHttpRequestContext =
+                try_from_option_field(entry.context, "SystemMetadata::CanisterHttpRequestContext")?;
+            canister_http_request_contexts.insert(CallbackId::new(entry.callback_id), context);
         }
         Ok(Self {
             next_callback_id: item.next_callback_id,
             setup_initial_dkg_contexts,
             sign_with_ecdsa_contexts,
-            sign_with_mock_ecdsa_contexts,
+            canister_http_request_contexts,
         })
     }
 }
@@ -197,8 +208,9 @@ impl TryFrom<pb_metadata::SetupInitialDkgContext> for SetupInitialDkgContext {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SignWithEcdsaContext {
     pub request: Request,
+    pub key_id: EcdsaKeyId,
     pub message_hash: Vec<u8>,
-    pub derivation_path: Vec<u8>,
+    pub derivation_path: Vec<Vec<u8>>,
     pub pseudo_random_id: [u8; 32],
     pub batch_time: Time,
 }
@@ -207,8 +219,9 @@ impl From<&SignWithEcdsaContext> for pb_metadata::SignWithEcdsaContext {
     fn from(context: &SignWithEcdsaContext) -> Self {
         pb_metadata::SignWithEcdsaContext {
             request: Some((&context.request).into()),
+            key_id: Some((&context.key_id).into()),
             message_hash: context.message_hash.to_vec(),
-            derivation_path: context.derivation_path.to_vec(),
+            derivation_path_vec: context.derivation_path.clone(),
             pseudo_random_id: context.pseudo_random_id.to_vec(),
             batch_time: context.batch_time.as_nanos_since_unix_epoch(),
         }
@@ -221,8 +234,9 @@ impl TryFrom<pb_metadata::SignWithEcdsaContext> for SignWithEcdsaContext {
         let request: Request =
             try_from_option_field(context.request, "SignWithEcdsaContext::request")?;
         Ok(SignWithEcdsaContext {
+            key_id: try_from_option_field(context.key_id, "SignWithEcdsaContext::key_id")?,
             message_hash: context.message_hash,
-            derivation_path: context.derivation_path,
+            derivation_path: context.derivation_path_vec,
             request,
             pseudo_random_id: {
                 if context.pseudo_random_id.len() != 32 {
@@ -238,3 +252,76 @@ impl TryFrom<pb_metadata::SignWithEcdsaContext> for SignWithEcdsaContext {
         })
     }
 }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanisterHttpRequestContext {
+    pub request: Request,
+    pub url: String,
+    pub max_response_bytes: u64,
+    pub headers: Vec<HttpHeader>,
+    pub body: Option<Vec<u8>>,
+    pub http_method: HttpMethod,
+    pub transform_method_name: Option<String>,
+    pub time: Time,
+}
+
+impl From<&CanisterHttpRequestContext> for pb_metadata::CanisterHttpRequestContext {
+    fn from(context: &CanisterHttpRequestContext) -> Self {
+        pb_metadata::CanisterHttpRequestContext {
+            request: Some((&context.request).into()),
+            url: context.url.clone(),
+            max_response_bytes: context.max_response_bytes,
+            headers: context
+                .headers
+                .iter()
+                .map(|header| pb_metadata::HttpHeader {
+                    name: header.name.clone(),
+                    value: header.value.clone(),
+                })
+                .collect(),
+            body: context.body.clone(),
+            http_method: match context.http_method {
+                HttpMethod::GET => 1,
+                HttpMethod::POST => 2,
+                HttpMethod::HEAD => 3,
+            },
+            transform_method_name: context.transform_method_name.clone(),
+            time: context.time.as_nanos_since_unix_epoch(),
+        }
+    }
+}
+
+impl TryFrom<pb_metadata::CanisterHttpRequestContext> for CanisterHttpRequestContext {
+    type Error = ProxyDecodeError;
+    fn try_from(context: pb_metadata::CanisterHttpRequestContext) -> Result<Self, Self::Error> {
+        let request: Request =
+            try_from_option_field(context.request, "CanisterHttpRequestContext::request")?;
+        Ok(CanisterHttpRequestContext {
+            request,
+            url: context.url,
+            max_response_bytes: context.max_response_bytes,
+            headers: context
+                .headers
+                .into_iter()
+                .map(|header| HttpHeader {
+                    name: header.name,
+                    value: header.value,
+                })
+                .collect(),
+            body: context.body,
+            http_method: match context.http_method {
+                1 => HttpMethod::GET,
+                2 => HttpMethod::POST,
+                3 => HttpMethod::HEAD,
+                other => {
+                    return Err(ProxyDecodeError::ValueOutOfRange {
+                        typ: "CanisterHttpRequestContext::http_method",
+                        err: other.to_string(),
+                    })
+                }
+            },
+            transform_method_name: context.transform_method_name,
+            time: Time::from_nanos_since_unix_epoch(context.time),
+        })
+    }
+}
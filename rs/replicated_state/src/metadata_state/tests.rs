@@ -14,6 +14,7 @@ use ic_types::{
     ingress::{WasmResult, MAX_INGRESS_TTL},
     messages::Payload,
 };
+use maplit::btreemap;
 
 #[test]
 fn can_prune_old_ingress_history_entries() {
@@ -194,3 +195,24 @@ fn streams_stats_after_deserialization() {
         deserialized_system_metadata.streams.responses_size_bytes()
     );
 }
+
+#[test]
+fn draining_migrations_roundtrip_through_protobuf() {
+    let mut system_metadata = SystemMetadata::new(SUBNET_0, SubnetType::Application);
+    system_metadata.draining_migrations.insert(
+        CanisterIdRange {
+            start: canister_test_id(2),
+            end: canister_test_id(5),
+        },
+        btreemap! {
+            SUBNET_1 => StreamIndex::new(7),
+            SUBNET_2 => StreamIndex::new(13),
+        },
+    );
+
+    let system_metadata_proto: ic_protobuf::state::system_metadata::v1::SystemMetadata =
+        (&system_metadata).into();
+    let deserialized_system_metadata: SystemMetadata = system_metadata_proto.try_into().unwrap();
+
+    assert_eq!(system_metadata, deserialized_system_metadata);
+}
@@ -21,9 +21,10 @@ use page_allocator::{Page, PageAllocator};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
 use std::ops::Range;
 use std::os::unix::io::RawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// `PageDelta` represents a changeset of the module heap.
 #[derive(Clone, Default, Debug)]
@@ -122,12 +123,143 @@ impl PageDelta {
         Ok(())
     }
 
+    /// Persists this delta as a new overlay file at the specified
+    /// destination, see `checkpoint::Overlays` for the file format.
+    fn persist_as_overlay(&self, dst: &Path) -> Result<(), PersistenceError> {
+        write_and_sync_new_file(dst, |writer| {
+            let mut page_indices = Vec::new();
+            for (index, page) in self.iter() {
+                writer.write_all(page.contents())?;
+                page_indices.push(index.get());
+            }
+            writer.write_all(&checkpoint::overlay_file_trailer(&page_indices))
+        })
+    }
+
     /// Returns true if the page delta contains no pages.
     fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 }
 
+/// Writes a new file with the contents produced by `write`, syncs it and
+/// atomically moves it to `dst`. The file previously stored at `dst`, which
+/// might be shared with a checkpoint, is never modified.
+fn write_and_sync_new_file<F>(dst: &Path, write: F) -> Result<(), PersistenceError>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> std::io::Result<()>,
+{
+    let fs_error =
+        |path: &Path, context: &str, err: std::io::Error| PersistenceError::FileSystemError {
+            path: path.display().to_string(),
+            context: context.to_string(),
+            internal_error: err.to_string(),
+        };
+    let tmp = dst.with_extension("tmp");
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .map_err(|err| fs_error(&tmp, "Failed to open file", err))?;
+    let mut writer = BufWriter::new(&file);
+    write(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(|err| fs_error(&tmp, "Failed to write file", err))?;
+    drop(writer);
+    file.sync_all()
+        .map_err(|err| fs_error(&tmp, "Failed to sync file", err))?;
+    std::fs::rename(&tmp, dst).map_err(|err| fs_error(dst, "Failed to rename file", err))?;
+    match dst.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => ic_utils::fs::sync_path(dir)
+            .map_err(|err| fs_error(dir, "Failed to sync directory", err)),
+        _ => Ok(()),
+    }
+}
+
+/// The extension of overlay files, see `PageMap::persist_delta_as_overlay()`.
+const OVERLAY_EXTENSION: &str = "overlay";
+
+fn heap_file_stem(base: &Path) -> String {
+    base.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns the path of the overlay file holding the pages of the heap file
+/// `base` that changed in the checkpoint at `height`, e.g.
+/// `vmemory_0_000000000000002a.overlay` for `vmemory_0.bin` at height 42.
+pub fn overlay_path(base: &Path, height: Height) -> PathBuf {
+    base.with_file_name(format!(
+        "{}_{:016x}.{}",
+        heap_file_stem(base),
+        height.get(),
+        OVERLAY_EXTENSION
+    ))
+}
+
+/// Returns true if `path` is the path of an overlay file.
+pub fn is_overlay_file(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == OVERLAY_EXTENSION)
+}
+
+/// Returns the paths of the overlay files of the heap file `base`, ordered
+/// from the oldest to the newest one.
+pub fn overlay_paths(base: &Path) -> Result<Vec<PathBuf>, PersistenceError> {
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let read_dir_error = |err: std::io::Error| PersistenceError::FileSystemError {
+        path: dir.display().to_string(),
+        context: "Failed to read directory".to_string(),
+        internal_error: err.to_string(),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(read_dir_error(err)),
+    };
+    let prefix = format!("{}_", heap_file_stem(base));
+    let mut overlays = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(read_dir_error)?.path();
+        if !is_overlay_file(&path) {
+            continue;
+        }
+        let height = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(prefix.as_str()))
+            .filter(|height| height.len() == 16)
+            .and_then(|height| u64::from_str_radix(height, 16).ok());
+        if let Some(height) = height {
+            overlays.push((height, path));
+        }
+    }
+    overlays.sort();
+    Ok(overlays.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Removes all the overlay files of the heap file `base`.
+pub fn remove_overlays(base: &Path) -> Result<(), PersistenceError> {
+    for path in overlay_paths(base)? {
+        std::fs::remove_file(&path).map_err(|err| PersistenceError::FileSystemError {
+            path: path.display().to_string(),
+            context: "Failed to remove overlay file".to_string(),
+            internal_error: err.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Merges the overlay files of the heap file `base` into a new heap file
+/// that replaces `base`, see `PageMap::persist_and_replace()`.
+pub fn merge_overlays(base: &Path) -> Result<(), PersistenceError> {
+    PageMap::open(base, None)?.persist_and_replace(base)
+}
+
 impl<I> From<I> for PageDelta
 where
     I: IntoIterator<Item = (PageIndex, Page)>,
@@ -160,6 +292,8 @@ pub enum PersistenceError {
     },
     /// (Slice) size is not equal to page size.
     BadPageSize { expected: usize, actual: usize },
+    /// Overlay file does not match the expected format.
+    InvalidOverlayFile { path: String, message: String },
 }
 
 impl PersistenceError {
@@ -197,6 +331,9 @@ impl std::fmt::Display for PersistenceError {
                 "Bad slice size: expected {}, actual {}",
                 expected, actual
             ),
+            PersistenceError::InvalidOverlayFile { path, message } => {
+                write!(f, "Invalid overlay file {}: {}", path, message)
+            }
         }
     }
 }
@@ -249,6 +386,9 @@ pub struct PageMap {
     /// The height of the checkpoint that backs the page map.
     pub base_height: Option<Height>,
 
+    /// The heap file that backs the page map, if it was opened from one.
+    heap_file: Option<PathBuf>,
+
     /// The map containing pages overriding pages from the `checkpoint`.
     /// We need these pages to be able to reconstruct the full heap.
     /// It is reset when `strip_all_deltas()` method is called.
@@ -274,14 +414,16 @@ impl PageMap {
         Default::default()
     }
 
-    /// Creates a page map backed by the provided heap file.
+    /// Creates a page map backed by the provided heap file and by the overlay
+    /// files stacked on top of it, see `overlay_paths()`.
     ///
-    /// Note that the file is assumed to be read-only.
+    /// Note that the files are assumed to be read-only.
     pub fn open(heap_file: &Path, base_height: Option<Height>) -> Result<Self, PersistenceError> {
-        let checkpoint = Checkpoint::open(heap_file)?;
+        let checkpoint = Checkpoint::open(heap_file, &overlay_paths(heap_file)?)?;
         Ok(Self {
             checkpoint,
             base_height,
+            heap_file: Some(heap_file.to_path_buf()),
             page_delta: Default::default(),
             round_delta: Default::default(),
             page_allocator: Default::default(),
@@ -293,6 +435,7 @@ impl PageMap {
         PageMapSerialization {
             checkpoint: self.checkpoint.serialize(),
             base_height: self.base_height,
+            heap_file: self.heap_file.clone(),
             page_delta: self
                 .page_allocator
                 .serialize_page_delta(self.page_delta.iter()),
@@ -314,12 +457,19 @@ impl PageMap {
         Ok(Self {
             checkpoint,
             base_height: page_map.base_height,
+            heap_file: page_map.heap_file,
             page_delta,
             round_delta,
             page_allocator,
         })
     }
 
+    /// Returns the heap file that backs this page map, if it was opened from
+    /// one (see `open()`).
+    pub fn heap_file(&self) -> Option<&Path> {
+        self.heap_file.as_deref()
+    }
+
     /// Returns a serialization-friendly representation of the page allocator.
     pub fn serialize_allocator(&self) -> PageAllocatorSerialization {
         self.page_allocator.serialize()
@@ -369,12 +519,62 @@ impl PageMap {
         self.page_delta.persist_and_sync(dst)
     }
 
+    /// Persists all the pages of this page map, including the ones backed by
+    /// the checkpoint file, to the specified destination and fsync the file to
+    /// disk. Used when `dst` does not contain a copy of the checkpoint file.
+    pub fn persist_and_sync_all(&self, dst: &Path) -> Result<(), PersistenceError> {
+        self.copy_without_checkpoint().persist_and_sync_delta(dst)
+    }
+
+    /// Returns a page map with the same contents that is not backed by any
+    /// checkpoint file: all its pages are part of its page delta and its
+    /// `base_height` is `None`. Used when the contents are moved to a heap
+    /// file other than the one backing this page map.
+    pub fn copy_without_checkpoint(&self) -> Self {
+        let pages: Vec<_> = self.host_pages_iter().collect();
+        let mut copy = PageMap::default();
+        copy.update(&pages);
+        copy
+    }
+
     /// Persists the round delta contained in this page map to the specified
     /// destination.
     pub fn persist_round_delta(&self, dst: &Path) -> Result<(), PersistenceError> {
         self.round_delta.persist(dst)
     }
 
+    /// Persists the heap delta contained in this page map as a new overlay
+    /// file on top of the heap file `base`, holding the pages that changed in
+    /// the checkpoint at `height`. Does nothing if the delta is empty.
+    ///
+    /// Precondition: `base` and its overlays hold the contents of the
+    /// checkpoint backing this page map.
+    pub fn persist_delta_as_overlay(
+        &self,
+        base: &Path,
+        height: Height,
+    ) -> Result<(), PersistenceError> {
+        if self.page_delta.is_empty() {
+            return Ok(());
+        }
+        self.page_delta
+            .persist_as_overlay(&overlay_path(base, height))
+    }
+
+    /// Writes all the pages of this page map to a new file that replaces the
+    /// heap file `base`, and removes the overlays of `base`. The existing
+    /// files are never modified in place, so they can be shared with
+    /// checkpoints.
+    pub fn persist_and_replace(&self, base: &Path) -> Result<(), PersistenceError> {
+        write_and_sync_new_file(base, |writer| {
+            for (_, page) in self.host_pages_iter() {
+                writer.write_all(page)?;
+            }
+            Ok(())
+        })?;
+        remove_overlays(base)
+    }
+
     /// Returns the iterator over host pages managed by this `PageMap`.
     pub fn host_pages_iter(&self) -> impl Iterator<Item = (PageIndex, &PageBytes)> + '_ {
         (0..self.num_host_pages()).map(move |i| {
@@ -421,12 +621,14 @@ impl PageMap {
         }
     }
 
-    /// Returns the whole checkpoint memory region.
+    /// Returns the whole memory region of the checkpoint heap file. Pages
+    /// stored in overlay files are not taken into account, callers must use
+    /// `get_memory_region()` to find out which pages are backed by the file.
     pub fn get_checkpoint_memory_region(&self) -> MemoryRegion {
         let start = PageIndex::new(0);
         let end = PageIndex::new(u64::MAX);
         self.checkpoint
-            .get_memory_region(start, Range { start, end })
+            .get_base_memory_region(start, Range { start, end })
     }
 
     /// Removes the page delta from this page map.
@@ -619,6 +821,7 @@ impl std::fmt::Debug for PageMap {
 pub struct PageMapSerialization {
     pub checkpoint: CheckpointSerialization,
     pub base_height: Option<Height>,
+    pub heap_file: Option<PathBuf>,
     pub page_delta: PageDeltaSerialization,
     pub round_delta: PageDeltaSerialization,
     pub page_allocator: PageAllocatorSerialization,
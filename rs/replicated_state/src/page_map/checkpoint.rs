@@ -3,11 +3,13 @@ use ic_sys::{mmap::ScopedMmap, PAGE_SIZE};
 use ic_sys::{page_bytes_from_ptr, PageBytes};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
 use std::fs::{File, OpenOptions};
 use std::ops::Range;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::prelude::FromRawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use super::FileOffset;
@@ -20,10 +22,12 @@ lazy_static! {
 /// module.
 ///
 /// Conceptually it's an immutable byte array backed by a file and
-/// aligned to a page boundary.
+/// aligned to a page boundary. The pages of the file can be overridden by a
+/// stack of overlay files, see `Overlays`.
 #[derive(Clone)]
 pub(crate) struct Checkpoint {
     mapping: Option<Arc<Mapping>>,
+    overlays: Option<Arc<Overlays>>,
 }
 
 struct Mapping {
@@ -130,7 +134,7 @@ impl Mapping {
         let num_pages = (self.mmap.len() / PAGE_SIZE) as u64;
         if page_index.get() >= num_pages {
             MemoryRegion::Zeros(Range {
-                start: PageIndex::new(num_pages),
+                start: PageIndex::new(num_pages.max(page_range.start.get())),
                 end: page_range.end,
             })
         } else {
@@ -149,24 +153,177 @@ impl Mapping {
     }
 }
 
+/// The overlay files stacked on top of the base file of a checkpoint. Each
+/// overlay file stores the pages that changed in one checkpoint, so that
+/// checkpoints do not have to rewrite the pages that did not change.
+///
+/// An overlay file storing `N` pages has the following layout:
+///
+/// ```text
+/// ┌──────────┬─────┬────────────┬────────────┬─────┬──────────────┬─────────┬─────┐
+/// │ page 0   │ ... │ page N - 1 │ index of   │ ... │ index of     │ padding │  N  │
+/// │          │     │            │ page 0     │     │ page N - 1   │         │     │
+/// └──────────┴─────┴────────────┴────────────┴─────┴──────────────┴─────────┴─────┘
+/// ```
+///
+/// The page contents come first so that they are page-aligned. They are
+/// followed by the indices of the pages in the heap, encoded as little-endian
+/// `u64`, and the number of pages `N`, encoded as a little-endian `u64` in
+/// the last 8 bytes of the file. The padding makes the file size a multiple
+/// of the page size.
+struct Overlays {
+    /// The mappings of the overlay files, from the oldest to the newest one.
+    mappings: Vec<Mapping>,
+    /// For every page stored in an overlay file, the position of the newest
+    /// overlay file containing it in `mappings` and the position of the page
+    /// within that file.
+    pages: BTreeMap<u64, (usize, u64)>,
+}
+
+impl Overlays {
+    fn new(mappings: Vec<(Mapping, String)>) -> Result<Overlays, PersistenceError> {
+        let mut pages = BTreeMap::new();
+        for (overlay, (mapping, path)) in mappings.iter().enumerate() {
+            for (slot, page_index) in overlay_page_indices(mapping, path)?.into_iter().enumerate() {
+                pages.insert(page_index, (overlay, slot as u64));
+            }
+        }
+        Ok(Overlays {
+            mappings: mappings.into_iter().map(|(mapping, _)| mapping).collect(),
+            pages,
+        })
+    }
+
+    fn get_page(&self, page_index: PageIndex) -> Option<&PageBytes> {
+        self.pages
+            .get(&page_index.get())
+            .map(|(overlay, slot)| self.mappings[*overlay].get_page(PageIndex::new(*slot)))
+    }
+
+    /// Shrinks `page_range` to the largest range containing `page_index`
+    /// that does not contain any page stored in an overlay file.
+    fn exclude_overlay_pages(
+        &self,
+        page_index: PageIndex,
+        page_range: Range<PageIndex>,
+    ) -> Range<PageIndex> {
+        let start = self
+            .pages
+            .range(page_range.start.get()..page_index.get())
+            .next_back()
+            .map(|(index, _)| PageIndex::new(index + 1))
+            .unwrap_or(page_range.start);
+        let end = self
+            .pages
+            .range(page_index.get() + 1..page_range.end.get())
+            .next()
+            .map(|(index, _)| PageIndex::new(*index))
+            .unwrap_or(page_range.end);
+        Range { start, end }
+    }
+
+    fn num_pages(&self) -> usize {
+        self.pages
+            .keys()
+            .next_back()
+            .map(|index| *index as usize + 1)
+            .unwrap_or(0)
+    }
+}
+
+/// Parses the trailer of an overlay file and returns the indices of the
+/// pages it stores, see `Overlays` for the file format.
+fn overlay_page_indices(mapping: &Mapping, path: &str) -> Result<Vec<u64>, PersistenceError> {
+    let invalid = |message: String| PersistenceError::InvalidOverlayFile {
+        path: path.to_string(),
+        message,
+    };
+    let bytes = mapping.mmap.as_slice();
+    let len = bytes.len();
+    if len < PAGE_SIZE {
+        return Err(invalid(format!("file size {} is too small", len)));
+    }
+    let num_pages = u64::from_le_bytes(bytes[len - 8..].try_into().unwrap()) as usize;
+    let index_start = num_pages.saturating_mul(PAGE_SIZE);
+    let index_end = index_start.saturating_add(num_pages.saturating_mul(8));
+    if num_pages > len / PAGE_SIZE || index_end > len - 8 {
+        return Err(invalid(format!(
+            "{} pages do not fit into a file of size {}",
+            num_pages, len
+        )));
+    }
+    Ok(bytes[index_start..index_end]
+        .chunks_exact(8)
+        .map(|index| u64::from_le_bytes(index.try_into().unwrap()))
+        .collect())
+}
+
+/// Returns the part of an overlay file storing the given pages that follows
+/// the page contents, see `Overlays` for the file format.
+pub(crate) fn overlay_file_trailer(page_indices: &[u64]) -> Vec<u8> {
+    let index_len = (page_indices.len() + 1) * 8;
+    let trailer_len = (index_len + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    let mut trailer = Vec::with_capacity(trailer_len);
+    for index in page_indices {
+        trailer.extend_from_slice(&index.to_le_bytes());
+    }
+    trailer.resize(trailer_len - 8, 0);
+    trailer.extend_from_slice(&(page_indices.len() as u64).to_le_bytes());
+    trailer
+}
+
 impl Checkpoint {
     /// Returns an empty checkpoint, not backed by any file. It serves
     /// zeroed pages.
     pub fn empty() -> Checkpoint {
-        Checkpoint { mapping: None }
+        Checkpoint {
+            mapping: None,
+            overlays: None,
+        }
     }
 
-    /// Opens an existing heap file located at the specified path.
-    pub fn open(path: &Path) -> Result<Checkpoint, PersistenceError> {
-        Mapping::open(path).map(|mapping| Checkpoint {
+    /// Opens an existing heap file located at the specified path, together
+    /// with the given overlay files, ordered from the oldest to the newest.
+    pub fn open(path: &Path, overlay_paths: &[PathBuf]) -> Result<Checkpoint, PersistenceError> {
+        let mapping = Mapping::open(path)?;
+        let mut overlay_mappings = Vec::with_capacity(overlay_paths.len());
+        for overlay_path in overlay_paths {
+            let path = overlay_path.display().to_string();
+            match Mapping::open(overlay_path)? {
+                Some(mapping) => overlay_mappings.push((mapping, path)),
+                None => {
+                    return Err(PersistenceError::InvalidOverlayFile {
+                        path,
+                        message: "file is empty".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(Checkpoint {
             mapping: mapping.map(Arc::new),
+            overlays: Self::overlays(overlay_mappings)?,
         })
     }
 
+    fn overlays(
+        mappings: Vec<(Mapping, String)>,
+    ) -> Result<Option<Arc<Overlays>>, PersistenceError> {
+        if mappings.is_empty() {
+            Ok(None)
+        } else {
+            Overlays::new(mappings).map(|overlays| Some(Arc::new(overlays)))
+        }
+    }
+
     /// Returns a serialization-friendly representation of `Checkpoint`.
     pub fn serialize(&self) -> CheckpointSerialization {
         CheckpointSerialization {
             mapping: self.mapping.as_ref().map(|mapping| mapping.serialize()),
+            overlays: self
+                .overlays
+                .as_ref()
+                .map(|overlays| overlays.mappings.iter().map(Mapping::serialize).collect())
+                .unwrap_or_default(),
         }
     }
 
@@ -179,13 +336,34 @@ impl Checkpoint {
             None => None,
             Some(mapping) => Mapping::deserialize(mapping)?,
         };
+        let mut overlay_mappings = Vec::with_capacity(serialized_checkpoint.overlays.len());
+        for overlay in serialized_checkpoint.overlays {
+            let path = format!("/proc/self/fd/{}", overlay.file_descriptor.fd);
+            match Mapping::deserialize(overlay)? {
+                Some(mapping) => overlay_mappings.push((mapping, path)),
+                None => {
+                    return Err(PersistenceError::InvalidOverlayFile {
+                        path,
+                        message: "file is empty".to_string(),
+                    })
+                }
+            }
+        }
         Ok(Checkpoint {
             mapping: mapping.map(Arc::new),
+            overlays: Self::overlays(overlay_mappings)?,
         })
     }
 
     /// Returns the page with the specified `page_number`.
     pub fn get_page(&self, page_index: PageIndex) -> &PageBytes {
+        if let Some(page) = self
+            .overlays
+            .as_ref()
+            .and_then(|overlays| overlays.get_page(page_index))
+        {
+            return page;
+        }
         match self.mapping {
             Some(ref mapping) => mapping.get_page(page_index),
             None => &ZEROED_PAGE,
@@ -197,6 +375,26 @@ impl Checkpoint {
         &self,
         page_index: PageIndex,
         page_range: Range<PageIndex>,
+    ) -> MemoryRegion {
+        assert!(page_range.contains(&page_index));
+        let page_range = match self.overlays {
+            Some(ref overlays) => {
+                if let Some(page) = overlays.get_page(page_index) {
+                    return MemoryRegion::BackedByPage(page);
+                }
+                overlays.exclude_overlay_pages(page_index, page_range)
+            }
+            None => page_range,
+        };
+        self.get_base_memory_region(page_index, page_range)
+    }
+
+    /// Same as `get_memory_region()`, but ignores the overlay files, i.e. the
+    /// returned region describes the contents of the base heap file only.
+    pub fn get_base_memory_region(
+        &self,
+        page_index: PageIndex,
+        page_range: Range<PageIndex>,
     ) -> MemoryRegion {
         assert!(page_range.contains(&page_index));
         match self.mapping {
@@ -208,9 +406,13 @@ impl Checkpoint {
     /// Returns the max number of (possibly) non-zero pages in this
     /// checkpoint.
     pub fn num_pages(&self) -> usize {
-        match self.mapping {
+        let base_pages = match self.mapping {
             Some(ref mapping) => mapping.num_pages(),
             None => 0,
+        };
+        match self.overlays {
+            Some(ref overlays) => base_pages.max(overlays.num_pages()),
+            None => base_pages,
         }
     }
 }
@@ -238,4 +440,5 @@ pub struct MappingSerialization {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CheckpointSerialization {
     pub mapping: Option<MappingSerialization>,
+    pub overlays: Vec<MappingSerialization>,
 }
@@ -1,11 +1,14 @@
 use super::{
     checkpoint::{Checkpoint, MappingSerialization},
+    merge_overlays, overlay_path, overlay_paths,
     page_allocator::PageAllocatorSerialization,
-    Buffer, FileDescriptor, PageIndex, PageMap, PageMapSerialization,
+    Buffer, FileDescriptor, MemoryRegion, PageIndex, PageMap, PageMapSerialization,
 };
 use ic_sys::PAGE_SIZE;
+use ic_types::Height;
 use nix::unistd::dup;
 use std::fs::OpenOptions;
+use std::path::Path;
 
 fn assert_equal_page_maps(page_map1: &PageMap, page_map2: &PageMap) {
     assert_eq!(page_map1.num_host_pages(), page_map2.num_host_pages());
@@ -22,16 +25,19 @@ fn assert_equal_page_maps(page_map1: &PageMap, page_map2: &PageMap) {
 fn duplicate_file_descriptors(
     mut serialized_page_map: PageMapSerialization,
 ) -> PageMapSerialization {
-    serialized_page_map.checkpoint.mapping =
-        serialized_page_map
-            .checkpoint
-            .mapping
-            .map(|mapping| MappingSerialization {
-                file_descriptor: FileDescriptor {
-                    fd: dup(mapping.file_descriptor.fd).unwrap(),
-                },
-                ..mapping
-            });
+    let duplicate = |mapping: MappingSerialization| MappingSerialization {
+        file_descriptor: FileDescriptor {
+            fd: dup(mapping.file_descriptor.fd).unwrap(),
+        },
+        ..mapping
+    };
+    serialized_page_map.checkpoint.mapping = serialized_page_map.checkpoint.mapping.map(duplicate);
+    serialized_page_map.checkpoint.overlays = serialized_page_map
+        .checkpoint
+        .overlays
+        .into_iter()
+        .map(duplicate)
+        .collect();
     serialized_page_map.page_allocator = match serialized_page_map.page_allocator {
         PageAllocatorSerialization::Mmap(file_descriptor) => {
             PageAllocatorSerialization::Mmap(FileDescriptor {
@@ -221,3 +227,172 @@ fn serialize_page_map() {
     let deserialized_page_map = PageMap::deserialize(serialized_page_map).unwrap();
     assert_equal_page_maps(&original_page_map, &deserialized_page_map);
 }
+
+/// Writes a heap file with pages 0..4 and two overlays on top of it: the
+/// first one overrides pages 1 and 6, the second one overrides page 1 again.
+/// Returns the expected page map.
+fn write_heap_file_with_overlays(heap_file: &Path) -> PageMap {
+    let base_pages: Vec<_> = (0..4u8).map(|i| [i + 10; PAGE_SIZE]).collect();
+    let base_delta: Vec<_> = base_pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| (PageIndex::new(i as u64), page))
+        .collect();
+    let mut expected = PageMap::default();
+    expected.update(&base_delta);
+    expected.persist_delta(heap_file).unwrap();
+
+    let page_1 = [1u8; PAGE_SIZE];
+    let page_6 = [6u8; PAGE_SIZE];
+    let mut page_map = PageMap::open(heap_file, Some(Height::new(0))).unwrap();
+    page_map.update(&[(PageIndex::new(1), &page_1), (PageIndex::new(6), &page_6)]);
+    page_map
+        .persist_delta_as_overlay(heap_file, Height::new(10))
+        .unwrap();
+    expected.update(&[(PageIndex::new(1), &page_1), (PageIndex::new(6), &page_6)]);
+
+    let page_1 = [11u8; PAGE_SIZE];
+    let mut page_map = PageMap::open(heap_file, Some(Height::new(10))).unwrap();
+    page_map.update(&[(PageIndex::new(1), &page_1)]);
+    page_map
+        .persist_delta_as_overlay(heap_file, Height::new(20))
+        .unwrap();
+    expected.update(&[(PageIndex::new(1), &page_1)]);
+
+    expected
+}
+
+#[test]
+fn page_map_reads_overlay_stack() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("vmemory_0.bin");
+    let expected = write_heap_file_with_overlays(&heap_file);
+
+    assert_eq!(
+        overlay_paths(&heap_file).unwrap(),
+        vec![
+            overlay_path(&heap_file, Height::new(10)),
+            overlay_path(&heap_file, Height::new(20))
+        ]
+    );
+    assert_eq!(
+        overlay_path(&heap_file, Height::new(42)),
+        tmp.path().join("vmemory_0_000000000000002a.overlay")
+    );
+
+    let page_map = PageMap::open(&heap_file, None).unwrap();
+    assert_eq!(page_map.num_host_pages(), 7);
+    assert_eq!(page_map, expected);
+
+    let serialized_page_map = duplicate_file_descriptors(page_map.serialize());
+    let deserialized_page_map = PageMap::deserialize(serialized_page_map).unwrap();
+    assert_equal_page_maps(&page_map, &deserialized_page_map);
+}
+
+#[test]
+fn empty_delta_does_not_create_an_overlay() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("vmemory_0.bin");
+    PageMap::default().persist_delta(&heap_file).unwrap();
+
+    PageMap::open(&heap_file, None)
+        .unwrap()
+        .persist_delta_as_overlay(&heap_file, Height::new(1))
+        .unwrap();
+
+    assert!(overlay_paths(&heap_file).unwrap().is_empty());
+}
+
+#[test]
+fn merging_overlays_preserves_contents() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("vmemory_0.bin");
+    let expected = write_heap_file_with_overlays(&heap_file);
+
+    merge_overlays(&heap_file).unwrap();
+
+    assert!(overlay_paths(&heap_file).unwrap().is_empty());
+    assert_eq!(
+        std::fs::metadata(&heap_file).unwrap().len(),
+        7 * PAGE_SIZE as u64
+    );
+    assert_eq!(PageMap::open(&heap_file, None).unwrap(), expected);
+}
+
+#[test]
+fn memory_regions_skip_overlay_pages() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("vmemory_0.bin");
+    write_heap_file_with_overlays(&heap_file);
+    let page_map = PageMap::open(&heap_file, None).unwrap();
+
+    match page_map.get_memory_region(PageIndex::new(0)) {
+        MemoryRegion::BackedByFile(range, _) => {
+            assert_eq!(range, PageIndex::new(0)..PageIndex::new(1))
+        }
+        _ => panic!("page 0 must be backed by the heap file"),
+    }
+    match page_map.get_memory_region(PageIndex::new(1)) {
+        MemoryRegion::BackedByPage(page) => assert_eq!(page, &[11u8; PAGE_SIZE]),
+        _ => panic!("page 1 must be backed by an overlay"),
+    }
+    match page_map.get_memory_region(PageIndex::new(2)) {
+        MemoryRegion::BackedByFile(range, _) => {
+            assert_eq!(range, PageIndex::new(2)..PageIndex::new(4))
+        }
+        _ => panic!("page 2 must be backed by the heap file"),
+    }
+    match page_map.get_memory_region(PageIndex::new(5)) {
+        MemoryRegion::Zeros(range) => assert_eq!(range, PageIndex::new(4)..PageIndex::new(6)),
+        _ => panic!("page 5 must be zero"),
+    }
+    match page_map.get_memory_region(PageIndex::new(6)) {
+        MemoryRegion::BackedByPage(page) => assert_eq!(page, &[6u8; PAGE_SIZE]),
+        _ => panic!("page 6 must be backed by an overlay"),
+    }
+    match page_map.get_checkpoint_memory_region() {
+        MemoryRegion::BackedByFile(range, _) => {
+            assert_eq!(range, PageIndex::new(0)..PageIndex::new(4))
+        }
+        _ => panic!("the checkpoint region must be backed by the heap file"),
+    }
+}
+
+#[test]
+fn returns_an_error_if_overlay_file_is_corrupted() {
+    use std::io::Write;
+
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let heap_file = tmp.path().join("vmemory_0.bin");
+    PageMap::default().persist_delta(&heap_file).unwrap();
+    let mut contents = vec![0; PAGE_SIZE];
+    contents[PAGE_SIZE - 8..].copy_from_slice(&100u64.to_le_bytes());
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(overlay_path(&heap_file, Height::new(1)))
+        .unwrap()
+        .write_all(&contents)
+        .unwrap();
+
+    match PageMap::open(&heap_file, None) {
+        Err(super::PersistenceError::InvalidOverlayFile { .. }) => (),
+        Err(err) => panic!("Expected an invalid overlay file error, got {:?}", err),
+        Ok(_) => panic!("Expected an invalid overlay file error, got Ok(_)"),
+    }
+}
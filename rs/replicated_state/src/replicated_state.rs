@@ -1,4 +1,6 @@
 use super::{
+    bitcoin_state::BitcoinState,
+    canister_snapshots::CanisterSnapshots,
     canister_state::CanisterState,
     metadata_state::{IngressHistoryState, Stream, Streams, SystemMetadata},
 };
@@ -18,7 +20,9 @@ use ic_registry_routing_table::RoutingTable;
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
     ingress::IngressStatus,
-    messages::{is_subnet_message, MessageId, RequestOrResponse, Response, SignedIngressContent},
+    messages::{
+        is_subnet_message, CallbackId, MessageId, RequestOrResponse, Response, SignedIngressContent,
+    },
     user_error::{ErrorCode, UserError},
     xnet::QueueId,
     CanisterId, MemoryAllocation, NumBytes, QueueIndex, SubnetId, Time,
@@ -80,6 +84,10 @@ pub enum StateError {
     /// Message enqueuing would have caused the canister or subnet to run over
     /// their memory limit.
     OutOfMemory { requested: NumBytes, available: i64 },
+
+    /// Response enqueuing failed because the best-effort call it belongs to
+    /// already timed out.
+    LateResponse(CallbackId),
 }
 
 /// Circular iterator that consumes messages from all canisters' and the
@@ -204,6 +212,7 @@ pub const LABEL_VALUE_CANISTER_OUT_OF_CYCLES: &str = "CanisterOutOfCycles";
 pub const LABEL_VALUE_UNKNOWN_SUBNET_METHOD: &str = "UnknownSubnetMethod";
 pub const LABEL_VALUE_INVALID_SUBNET_PAYLOAD: &str = "InvalidSubnetPayload";
 pub const LABEL_VALUE_OUT_OF_MEMORY: &str = "OutOfMemory";
+pub const LABEL_VALUE_LATE_RESPONSE: &str = "LateResponse";
 
 impl StateError {
     /// Returns a string representation of the `StateError` variant name to be
@@ -218,6 +227,7 @@ impl StateError {
             StateError::UnknownSubnetMethod(_) => LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
             StateError::InvalidSubnetPayload => LABEL_VALUE_INVALID_SUBNET_PAYLOAD,
             StateError::OutOfMemory { .. } => LABEL_VALUE_OUT_OF_MEMORY,
+            StateError::LateResponse(_) => LABEL_VALUE_LATE_RESPONSE,
         }
     }
 }
@@ -257,6 +267,11 @@ impl std::fmt::Display for StateError {
                 "Cannot enqueue message. Out of memory: requested {}, available {}",
                 requested, available
             ),
+            StateError::LateResponse(callback_id) => write!(
+                f,
+                "Cannot enqueue response. The call with callback {} already timed out",
+                callback_id
+            ),
         }
     }
 }
@@ -288,6 +303,13 @@ pub struct ReplicatedState {
     // TODO(EXE-109): Move this queue into `subnet_queues`
     pub consensus_queue: Vec<Response>,
 
+    /// Snapshots of canisters taken via `take_canister_snapshot`.
+    pub canister_snapshots: CanisterSnapshots,
+
+    /// The Bitcoin state, if the subnet tracks a Bitcoin network (see
+    /// `SubnetFeatures::bitcoin_network`).
+    pub bitcoin: Option<BitcoinState>,
+
     pub root: PathBuf,
 }
 
@@ -299,11 +321,15 @@ impl PartialEq for ReplicatedState {
             &self.metadata,
             &self.subnet_queues,
             &self.consensus_queue,
+            &self.canister_snapshots,
+            &self.bitcoin,
         ) == (
             &rhs.canister_states,
             &rhs.metadata,
             &rhs.subnet_queues,
             &rhs.consensus_queue,
+            &rhs.canister_snapshots,
+            &rhs.bitcoin,
         )
     }
 }
@@ -321,6 +347,8 @@ impl ReplicatedState {
             metadata: SystemMetadata::new(own_subnet_id, own_subnet_type),
             subnet_queues: CanisterQueues::default(),
             consensus_queue: Vec::new(),
+            canister_snapshots: CanisterSnapshots::default(),
+            bitcoin: None,
         }
     }
 
@@ -329,6 +357,8 @@ impl ReplicatedState {
         metadata: SystemMetadata,
         subnet_queues: CanisterQueues,
         consensus_queue: Vec<Response>,
+        canister_snapshots: CanisterSnapshots,
+        bitcoin: Option<BitcoinState>,
         root: PathBuf,
     ) -> Self {
         let mut res = Self {
@@ -336,6 +366,8 @@ impl ReplicatedState {
             metadata,
             subnet_queues,
             consensus_queue,
+            canister_snapshots,
+            bitcoin,
             root,
         };
         res.update_stream_responses_size_bytes();
@@ -346,6 +378,41 @@ impl ReplicatedState {
         &self.root
     }
 
+    /// Splits the state of a subnet that is being split in two, retaining only
+    /// the part that belongs to the half `subnet_id` according to the
+    /// post-split `routing_table`: its canisters, their snapshots and ingress
+    /// history.
+    ///
+    /// Both halves start from the same checkpoint of the original subnet. The
+    /// half that keeps the original subnet ID also keeps the subnet queues,
+    /// streams and Bitcoin state; the new half starts without them. See
+    /// `SystemMetadata::split`.
+    pub fn split(mut self, subnet_id: SubnetId, routing_table: &RoutingTable) -> Self {
+        let is_new_subnet = subnet_id != self.metadata.own_subnet_id;
+
+        self.canister_states
+            .retain(|canister_id, _| routing_table.route(canister_id.get()) == Some(subnet_id));
+        let dropped_snapshots: Vec<_> = self
+            .canister_snapshots
+            .iter()
+            .filter(|(_, snapshot)| !self.canister_states.contains_key(&snapshot.canister_id))
+            .map(|(snapshot_id, _)| *snapshot_id)
+            .collect();
+        for snapshot_id in dropped_snapshots {
+            self.canister_snapshots.remove(snapshot_id);
+        }
+
+        if is_new_subnet {
+            self.subnet_queues = CanisterQueues::default();
+            self.consensus_queue.clear();
+            self.bitcoin = None;
+        }
+        self.metadata = self.metadata.split(subnet_id, routing_table);
+        self.update_stream_responses_size_bytes();
+
+        self
+    }
+
     pub fn canister_state(&self, canister_id: &CanisterId) -> Option<&CanisterState> {
         self.canister_states.get(canister_id)
     }
@@ -10,14 +10,18 @@ use ic_replicated_state::{
     replicated_state::ReplicatedStateMessageRouting, CanisterState, ReplicatedState,
     SchedulerState, StateError, SystemState,
 };
-use ic_test_utilities::state::{arb_replicated_state_with_queues, assert_next_eq};
+use ic_test_utilities::state::{
+    arb_replicated_state_with_queues, assert_next_eq, get_running_canister,
+};
 use ic_test_utilities::types::{
     ids::{subnet_test_id, user_test_id},
     messages::{RequestBuilder, ResponseBuilder},
 };
 use ic_types::{
-    messages::{RequestOrResponse, MAX_RESPONSE_COUNT_BYTES},
-    CountBytes, Cycles, QueueIndex,
+    ingress::IngressStatus,
+    messages::{MessageId, RequestOrResponse, MAX_RESPONSE_COUNT_BYTES},
+    xnet::StreamIndex,
+    CountBytes, Cycles, QueueIndex, Time,
 };
 use proptest::prelude::*;
 
@@ -592,3 +596,109 @@ proptest! {
         }
     }
 }
+
+#[test]
+fn split_retains_the_state_of_each_half() {
+    let own_canister_id = CanisterId::from_u64(1);
+    let moved_canister_id = CanisterId::from_u64(0x101);
+    let new_subnet_id = subnet_test_id(0x102);
+    let remote_subnet_id = subnet_test_id(0x103);
+    let removed_subnet_id = subnet_test_id(0x104);
+
+    let mut state =
+        ReplicatedState::new_rooted_at(SUBNET_ID, SubnetType::Application, "unused".into());
+    state.put_canister_state(get_running_canister(own_canister_id));
+    state.put_canister_state(get_running_canister(moved_canister_id));
+
+    // One ingress history entry and one outgoing stream message per canister.
+    for (i, canister_id) in [own_canister_id, moved_canister_id].iter().enumerate() {
+        state.set_ingress_status(
+            MessageId::from([i as u8; 32]),
+            IngressStatus::Received {
+                receiver: canister_id.get(),
+                user_id: user_test_id(24),
+                time: Time::from_nanos_since_unix_epoch(0),
+            },
+        );
+        let mut streams = state.take_streams();
+        streams.push(
+            remote_subnet_id,
+            RequestBuilder::default()
+                .sender(*canister_id)
+                .receiver(CANISTER_ID)
+                .build()
+                .into(),
+        );
+        state.put_streams(streams);
+    }
+    // And one to a subnet that no longer hosts any canisters.
+    let mut streams = state.take_streams();
+    streams.push(
+        removed_subnet_id,
+        RequestBuilder::default()
+            .sender(own_canister_id)
+            .receiver(CanisterId::from_u64(0x301))
+            .build()
+            .into(),
+    );
+    state.put_streams(streams);
+    state.metadata.draining_migrations.insert(
+        CanisterIdRange {
+            start: CanisterId::from(0x100),
+            end: CanisterId::from(0x1ff),
+        },
+        maplit::btreemap! { remote_subnet_id => StreamIndex::new(2) },
+    );
+
+    // Post-split routing table.
+    let routing_table = RoutingTable::new(maplit::btreemap! {
+        CanisterIdRange {
+            start: CanisterId::from(0x0),
+            end: CanisterId::from(0xff),
+        } => SUBNET_ID,
+        CanisterIdRange {
+            start: CanisterId::from(0x100),
+            end: CanisterId::from(0x1ff),
+        } => new_subnet_id,
+        CanisterIdRange {
+            start: CanisterId::from(0x200),
+            end: CanisterId::from(0x2ff),
+        } => remote_subnet_id,
+    });
+
+    // The half that keeps the subnet ID keeps its canister and the streams to
+    // subnets still in the routing table.
+    let own_half = state.clone().split(SUBNET_ID, &routing_table);
+    assert_eq!(
+        vec![own_canister_id],
+        own_half.canister_states.keys().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(SUBNET_ID, own_half.metadata.own_subnet_id);
+    assert_eq!(
+        2,
+        own_half
+            .get_stream(&remote_subnet_id)
+            .unwrap()
+            .messages()
+            .len()
+    );
+    assert!(own_half.get_stream(&removed_subnet_id).is_none());
+    assert_eq!(1, own_half.metadata.streams().iter().count());
+    assert_eq!(1, own_half.metadata.draining_migrations.len());
+    assert_eq!(1, own_half.get_ingress_history().len());
+
+    // The new half keeps the moved canister only, with no streams.
+    let new_half = state.split(new_subnet_id, &routing_table);
+    assert_eq!(
+        vec![moved_canister_id],
+        new_half.canister_states.keys().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(new_subnet_id, new_half.metadata.own_subnet_id);
+    assert!(new_half.get_stream(&remote_subnet_id).is_none());
+    assert!(new_half.metadata.draining_migrations.is_empty());
+    assert_eq!(1, new_half.get_ingress_history().len());
+    assert_eq!(
+        IngressStatus::Unknown,
+        new_half.get_ingress_status(&MessageId::from([0; 32]))
+    );
+}
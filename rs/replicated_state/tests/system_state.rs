@@ -4,13 +4,23 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
     canister_state::{DEFAULT_QUEUE_CAPACITY, ENFORCE_MESSAGE_MEMORY_USAGE, QUEUE_INDEX_NONE},
     testing::{CanisterQueuesTesting, SystemStateTesting},
-    InputQueueType, SystemState,
+    CallOrigin, InputQueueType, SystemState,
 };
-use ic_test_utilities::types::{
-    ids::{canister_test_id, user_test_id},
-    messages::{RequestBuilder, ResponseBuilder},
+use ic_test_utilities::{
+    mock_time,
+    types::{
+        ids::{canister_test_id, user_test_id},
+        messages::{RequestBuilder, ResponseBuilder},
+    },
 };
-use ic_types::{freeze_threshold_cycles, messages::RequestOrResponse, Cycles, QueueIndex};
+use ic_types::{
+    freeze_threshold_cycles,
+    messages::{CallbackId, Payload, RequestOrResponse},
+    methods::{Callback, WasmClosure},
+    user_error::RejectCode,
+    Cycles, QueueIndex,
+};
+use std::time::Duration;
 
 const CANISTER_AVAILABLE_MEMORY: i64 = 4 << 30;
 const SUBNET_AVAILABLE_MEMORY: i64 = 300 << 30;
@@ -279,3 +289,66 @@ fn induct_messages_to_self_full_queue() {
         system_state.queues().output_message_count()
     );
 }
+
+#[test]
+fn time_out_callbacks_enqueues_sys_unknown_reject_once() {
+    let canister_id = canister_test_id(1);
+    let callee = canister_test_id(2);
+    let mut system_state = SystemState::new_running(
+        canister_id,
+        user_test_id(1).get(),
+        Cycles::new(5_000_000_000_000),
+        NumSeconds::new(0),
+    );
+    let deadline = mock_time() + Duration::from_secs(10);
+    let call_context_manager = system_state.call_context_manager_mut().unwrap();
+    let call_context_id = call_context_manager.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(3), CallbackId::from(1)),
+        Cycles::zero(),
+    );
+    let callback_id = call_context_manager.register_callback(Callback::new(
+        call_context_id,
+        Cycles::new(100),
+        WasmClosure::new(0, 0),
+        WasmClosure::new(0, 0),
+        None,
+        Some(callee),
+        Some(deadline),
+    ));
+    // Sending the request reserves a slot for the response.
+    system_state
+        .queues_mut()
+        .push_output_request(
+            RequestBuilder::default()
+                .sender(canister_id)
+                .receiver(callee)
+                .sender_reply_callback(callback_id)
+                .deadline(Some(deadline))
+                .build(),
+        )
+        .unwrap();
+
+    // Nothing to do before the deadline.
+    assert_eq!(0, system_state.time_out_callbacks(deadline));
+    assert_eq!(None, system_state.pop_input());
+
+    let after_deadline = deadline + Duration::from_secs(1);
+    assert_eq!(1, system_state.time_out_callbacks(after_deadline));
+    // The reject is only enqueued once.
+    assert_eq!(0, system_state.time_out_callbacks(after_deadline));
+
+    match system_state.pop_input() {
+        Some(CanisterInputMessage::Response(response)) => {
+            assert_eq!(callback_id, response.originator_reply_callback);
+            assert_eq!(callee, response.respondent);
+            // The cycles attached to the call are not refunded.
+            assert_eq!(Cycles::zero(), response.refund);
+            assert!(matches!(
+                response.response_payload,
+                Payload::Reject(context) if context.code() == RejectCode::SysUnknown
+            ));
+        }
+        msg => panic!("Expected a response, got {:?}", msg),
+    }
+    assert_eq!(None, system_state.pop_input());
+}
@@ -0,0 +1,163 @@
+//! The SIMD counterpart of `nan_canonicalized`: checks that the NaNs produced
+//! by the float operations on the lanes of `f32x4` and `f64x2` vectors are
+//! canonicalized as well.
+use dfn_macro::query;
+
+#[query]
+fn simd_nans_are_canonicalized(_: ()) -> Result<(), String> {
+    simd::nans_are_canonicalized()
+}
+
+#[cfg(target_arch = "wasm32")]
+mod simd {
+    // Canonical 32-bit and 64-bit NaN values from cranelift-codegen, see
+    // `nan_canonicalized.rs`.
+    static CANON_32BIT_NAN: u32 = 0b0111_1111_1100_0000_0000_0000_0000_0000;
+    static CANON_64BIT_NAN: u64 =
+        0b0111_1111_1111_1000_0000_0000_0000_0000_0000_0000_0000_0000_0000_0000_0000_0000;
+
+    use std::arch::wasm32::*;
+    use std::f32;
+    use std::f64;
+
+    type Floats = Vec<(f32, f64)>;
+
+    // A `f32x4` and a `f64x2` vector, each with all lanes set to the result of
+    // the same operation.
+    type Vectors = Vec<(v128, v128)>;
+
+    // combine +x and -x to make ±x
+    fn or(plus: &Floats, minus: &Floats) -> Floats {
+        let mut p = plus.clone();
+        p.extend(minus);
+        p
+    }
+
+    fn splat(floats: &Floats) -> Vectors {
+        floats
+            .iter()
+            .map(|&(f, d)| (f32x4_splat(f), f64x2_splat(d)))
+            .collect()
+    }
+
+    fn product<F1, F2>(float: F1, double: F2, floats1: &Floats, floats2: &Floats) -> Vectors
+    where
+        F1: Fn(v128, v128) -> v128,
+        F2: Fn(v128, v128) -> v128,
+    {
+        let mut output = Vec::with_capacity(floats1.len() * floats2.len());
+        for (f1, d1) in splat(floats1) {
+            for (f2, d2) in splat(floats2) {
+                output.push((float(f1, f2), double(d1, d2)))
+            }
+        }
+        output
+    }
+
+    fn divide(f1: &Floats, f2: &Floats) -> Vectors {
+        product(|x, y| f32x4_div(x, y), |x, y| f64x2_div(x, y), f1, f2)
+    }
+
+    fn multiply(f1: &Floats, f2: &Floats) -> Vectors {
+        product(|x, y| f32x4_mul(x, y), |x, y| f64x2_mul(x, y), f1, f2)
+    }
+
+    fn add(f1: &Floats, f2: &Floats) -> Vectors {
+        product(|x, y| f32x4_add(x, y), |x, y| f64x2_add(x, y), f1, f2)
+    }
+
+    fn subtract(f1: &Floats, f2: &Floats) -> Vectors {
+        product(|x, y| f32x4_sub(x, y), |x, y| f64x2_sub(x, y), f1, f2)
+    }
+
+    fn sqrt(floats: &Floats) -> Vectors {
+        splat(floats)
+            .into_iter()
+            .map(|(f, d)| (f32x4_sqrt(f), f64x2_sqrt(d)))
+            .collect()
+    }
+
+    pub(super) fn nans_are_canonicalized() -> Result<(), String> {
+        let nan: Floats = vec![(f32::NAN, f64::NAN)];
+
+        let inf = vec![(f32::INFINITY, f64::INFINITY)];
+        let neg_inf = vec![(f32::NEG_INFINITY, f64::NEG_INFINITY)];
+        let pm_inf = or(&inf, &neg_inf);
+
+        let zero = vec![(0.0, 0.0)];
+        let neg_zero = vec![(-0.0, -0.0)];
+        let pm_zero = or(&zero, &neg_zero);
+
+        let one = vec![(1.0, 1.0)];
+        let neg_one = vec![(-1.0, -1.0)];
+
+        is_canon("Regular NaN", &splat(&nan))?;
+        is_canon("NaN × 1", &multiply(&nan, &one))?;
+
+        is_canon("(±0) / (±0)", &divide(&pm_zero, &pm_zero))?;
+        is_canon("(±∞) / (±∞)", &divide(&pm_inf, &pm_inf))?;
+
+        is_canon("(±0) × (±∞)", &multiply(&pm_zero, &pm_inf))?;
+        is_canon("(±∞) × (±0)", &multiply(&pm_inf, &pm_zero))?;
+
+        is_canon("(+∞) + (−∞)", &add(&inf, &neg_inf))?;
+        is_canon("(-∞) + (+∞)", &add(&neg_inf, &inf))?;
+
+        is_canon("(+∞) - (+∞)", &subtract(&inf, &inf))?;
+        is_canon("(-∞) - (-∞)", &subtract(&neg_inf, &neg_inf))?;
+
+        // Unlike the scalar `sqrt` of the standard library, the SIMD
+        // instruction is used directly, so the NaN comes from the engine.
+        is_canon("sqrt(-1)", &sqrt(&neg_one))?;
+
+        Ok(())
+    }
+
+    /// Are all lanes of these vectors canonicalized NaNs?
+    fn is_canon(msg: &str, inputs: &Vectors) -> Result<(), String> {
+        for (i, &(floats, doubles)) in inputs.iter().enumerate() {
+            let float_lanes = [
+                f32x4_extract_lane::<0>(floats),
+                f32x4_extract_lane::<1>(floats),
+                f32x4_extract_lane::<2>(floats),
+                f32x4_extract_lane::<3>(floats),
+            ];
+            for (lane, float) in float_lanes.iter().enumerate() {
+                let bits = float.to_bits();
+                let target = CANON_32BIT_NAN;
+                if bits != target {
+                    return Err(format!(
+                        "In {} f32x4 element {} lane {} \nExpected: \t0x{:x}\nFound: \t0x{:x}",
+                        msg, i, lane, target, bits
+                    ));
+                }
+            }
+            let double_lanes = [
+                f64x2_extract_lane::<0>(doubles),
+                f64x2_extract_lane::<1>(doubles),
+            ];
+            for (lane, double) in double_lanes.iter().enumerate() {
+                let bits = double.to_bits();
+                let target = CANON_64BIT_NAN;
+                if bits != target {
+                    return Err(format!(
+                        "In {} f64x2 element {} lane {} \nExpected: \t0x{:x}\nFound: \t0x{:x}",
+                        msg, i, lane, target, bits
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The SIMD intrinsics are only available when building for Wasm.
+#[cfg(not(target_arch = "wasm32"))]
+mod simd {
+    pub(super) fn nans_are_canonicalized() -> Result<(), String> {
+        Err("SIMD instructions are only available on wasm32".to_string())
+    }
+}
+
+fn main() {}
@@ -1,6 +1,7 @@
 use canister_test::*;
 use dfn_core::bytes;
 use dfn_json::json;
+use ic_config::{feature_status::FeatureStatus, subnet_config::SubnetConfig};
 use std::time::{Duration, SystemTime};
 
 #[test]
@@ -21,6 +22,30 @@ fn nan_canonicalized() {
     })
 }
 
+#[test]
+fn simd_nan_canonicalized() {
+    let (mut config, _tmpdir) = ic_config::Config::temp_config();
+    config.hypervisor.wasm_simd = FeatureStatus::Enabled;
+    local_test_with_config_e(
+        config,
+        SubnetConfig::default_application_subnet(),
+        |r| async move {
+            let proj = Project::new(env!("CARGO_MANIFEST_DIR"));
+
+            let canister = proj
+                .cargo_bin("nan_canonicalized_simd", &[])
+                .install_(&r, Vec::new())
+                .await?;
+
+            let res: Result<(), String> = canister
+                .query_("simd_nans_are_canonicalized", dfn_json::json, ())
+                .await?;
+            assert_eq!(res, Ok(()));
+            Ok(())
+        },
+    )
+}
+
 #[test]
 fn stable() {
     local_test_e(|r| async move {
@@ -1,6 +1,7 @@
 use crate::state_layout::CheckpointManager;
 use crate::utils::do_copy;
 use ic_logger::ReplicaLogger;
+use ic_replicated_state::page_map::is_overlay_file;
 use ic_utils::fs::{sync_and_mark_files_readonly, sync_path};
 use ic_utils::thread::parallel_map;
 use std::convert::identity;
@@ -59,7 +60,8 @@ use std::{
 ///   2. Reflink/copy all the files from "<state_root>/tip" to
 ///      "<state_root>/fs_tmp/scratchpad_<height>", sync both files and
 ///      directories under the scratchpad directory, including the scratchpad
-///      directory itself.
+///      directory itself.  Heap files and their overlays are hard-linked
+///      instead, as they are never modified in place.
 ///
 ///   3. Rename "<state_root>/fs_tmp/scratchpad_<height>" to
 ///      "<state_root>/checkpoints/<height>", sync "<state_root>/checkpoints".
@@ -423,15 +425,33 @@ fn copy_recursively_respecting_tombstones(
     Ok(())
 }
 
+/// Returns true if `path` is a canister heap file or an overlay file on top
+/// of one. Such files are never modified in place once written, see
+/// `PageMap::persist_and_replace()`.
+fn is_immutable_page_map_file(path: &Path) -> bool {
+    is_overlay_file(path)
+        || path
+            .file_name()
+            .map_or(false, |name| name == "vmemory_0.bin" || name == "stable_memory.bin")
+}
+
 /// Copies the given file and ensures that the `read/write` permission of the
 /// target file match the given permission.
+///
+/// Read-only copies of heap files and overlay files are hard links to the
+/// source, so that checkpoints only pay for the pages that changed.
 fn copy_and_sync_file(
     log: &ReplicaLogger,
     src: &Path,
     dst: &Path,
     dst_permissions: FilePermissions,
 ) -> std::io::Result<()> {
-    do_copy(log, src, dst)?;
+    match dst_permissions {
+        FilePermissions::ReadOnly if is_immutable_page_map_file(src) => {
+            fs::hard_link(src, dst).or_else(|_| do_copy(log, src, dst))?
+        }
+        _ => do_copy(log, src, dst)?,
+    }
 
     // We keep the directory writable though to make sure we can rename
     // them or delete the files.
@@ -11,12 +11,14 @@ use ic_protobuf::{
     },
 };
 use ic_replicated_state::{
-    canister_state::execution_state::WasmMetadata, CallContextManager, CanisterStatus,
-    ExportedFunctions, Global, NumWasmPages,
+    canister_state::execution_state::WasmMetadata, CallContextManager, CanisterHistory,
+    CanisterLog, CanisterStatus, ExportedFunctions, Global, NumWasmPages,
 };
 use ic_types::{
-    nominal_cycles::NominalCycles, AccumulatedPriority, CanisterId, ComputeAllocation, Cycles,
-    ExecutionRound, Height, MemoryAllocation, PrincipalId,
+    ic00::{CanisterChange, CanisterLogRecord, LogVisibility},
+    nominal_cycles::NominalCycles,
+    AccumulatedPriority, CanisterId, ComputeAllocation, Cycles, ExecutionRound, Height,
+    MemoryAllocation, PrincipalId, SnapshotId, Time,
 };
 use ic_wasm_types::BinaryEncodedWasm;
 use std::convert::{From, TryFrom, TryInto};
@@ -181,6 +183,23 @@ pub struct CanisterStateBits {
     pub consumed_cycles_since_replica_started: NominalCycles,
     pub stable_memory_size: NumWasmPages,
     pub heap_delta_debit: NumBytes,
+    pub global_timer: Option<Time>,
+    pub canister_log: CanisterLog,
+    pub log_visibility: LogVisibility,
+    pub canister_history: CanisterHistory,
+    pub canister_version: u64,
+    pub wasm_memory_limit: Option<NumBytes>,
+}
+
+/// This struct contains bits of the `CanisterSnapshot` that are not already
+/// covered somewhere else and are too small to be serialized separately.
+#[derive(Debug)]
+pub struct CanisterSnapshotBits {
+    pub canister_id: CanisterId,
+    pub taken_at_timestamp: Time,
+    pub execution_state_bits: ExecutionStateBits,
+    pub stable_memory_size: NumWasmPages,
+    pub certified_data: Vec<u8>,
 }
 
 /// `StateLayout` provides convenience functions to construct correct
@@ -199,29 +218,63 @@ pub struct CanisterStateBits {
 /// │── tip
 /// │   ├── system_metadata.pbuf
 /// │   ├── subnet_queues.pbuf
-/// │   └── canister_states
-/// │       └── <hex(canister_id)>
-/// │           ├── queues.pbuf
-/// │           ├── vmemory_0.bin
-/// │           ├── canister.pbuf
-/// │           ├── stable_memory.(pbuf|bin)
-/// │           └── software.wasm
+/// │   ├── canister_states
+/// │   │   └── <hex(canister_id)>
+/// │   │       ├── queues.pbuf
+/// │   │       ├── vmemory_0.bin
+/// │   │       ├── vmemory_0_<hex(height)>.overlay
+/// │   │       ├── canister.pbuf
+/// │   │       ├── stable_memory.(pbuf|bin)
+/// │   │       ├── stable_memory_<hex(height)>.overlay
+/// │   │       ├── software.wasm
+/// │   │       └── wasm_chunk_store
+/// │   │           └── <hex(chunk_hash)>
+/// │   ├── snapshots
+/// │   │   └── <hex(snapshot_id)>
+/// │   │       ├── snapshot.pbuf
+/// │   │       ├── vmemory_0.bin
+/// │   │       ├── stable_memory.bin
+/// │   │       └── software.wasm
+/// │   └── bitcoin
+/// │       ├── state.pbuf
+/// │       ├── utxos.bin
+/// │       └── utxos_<hex(height)>.overlay
 /// │
 /// ├── [checkpoints] {owned and varies by checkpoint manager}
 /// │   └──<hex(round)>
 /// │      ├── system_metadata.pbuf
 /// │      ├── subnet_queues.pbuf
-/// │      └── canister_states
-/// │          └── <hex(canister_id)>
-/// │              ├── queues.pbuf
-/// │              ├── vmemory_0.bin
-/// │              ├── canister.pbuf
-/// │              ├── stable_memory.(pbuf|bin)
-/// │              └── software.wasm
+/// │      ├── canister_states
+/// │      │   └── <hex(canister_id)>
+/// │      │       ├── queues.pbuf
+/// │      │       ├── vmemory_0.bin
+/// │      │       ├── vmemory_0_<hex(height)>.overlay
+/// │      │       ├── canister.pbuf
+/// │      │       ├── stable_memory.(pbuf|bin)
+/// │      │       ├── stable_memory_<hex(height)>.overlay
+/// │      │       ├── software.wasm
+/// │      │       └── wasm_chunk_store
+/// │      │           └── <hex(chunk_hash)>
+/// │      ├── snapshots
+/// │      │   └── <hex(snapshot_id)>
+/// │      │       ├── snapshot.pbuf
+/// │      │       ├── vmemory_0.bin
+/// │      │       ├── stable_memory.bin
+/// │      │       └── software.wasm
+/// │      └── bitcoin
+/// │          ├── state.pbuf
+/// │          ├── utxos.bin
+/// │          └── utxos_<hex(height)>.overlay
 /// │
 /// └── tmp
 /// ```
 ///
+/// An `*.overlay` file holds the pages of the corresponding heap file that
+/// changed in the checkpoint at `height`, see
+/// `ic_replicated_state::page_map::overlay_path()`. The logical contents of a
+/// heap file are the contents of the file with all its overlays applied in
+/// order of increasing height.
+///
 /// Needs to be pub for criterion performance regression tests.
 #[derive(Clone)]
 pub struct StateLayout {
@@ -272,6 +325,13 @@ impl StateLayout {
         Ok(tmp.join(format!("state_sync_scratchpad_{:016x}", height.get())))
     }
 
+    /// Returns the path to the progress record of the statesync at `height`,
+    /// which allows resuming the statesync from its scratchpad after a restart
+    pub fn state_sync_progress(&self, height: Height) -> Result<PathBuf, LayoutError> {
+        let tmp = self.tmp()?;
+        Ok(tmp.join(format!("state_sync_progress_{:016x}.pbuf", height.get())))
+    }
+
     /// Returns the path to cache an unfinished statesync at `height`
     pub fn state_sync_cache(&self, height: Height) -> Result<PathBuf, LayoutError> {
         let tmp = self.tmp()?;
@@ -390,8 +450,7 @@ impl StateLayout {
     /// Returns the layout of the checkpoint with the given height (if
     /// there is one).
     pub fn checkpoint(&self, height: Height) -> Result<CheckpointLayout<ReadOnly>, LayoutError> {
-        let cp_name = self.checkpoint_name(height);
-        let path = self.cp_manager.get_checkpoint_path(&cp_name);
+        let path = self.checkpoint_path(height);
         if !path.exists() {
             return Err(LayoutError::NotFound(height));
         }
@@ -452,6 +511,15 @@ impl StateLayout {
             .get_diverged_checkpoint_path(self.checkpoint_name(h).as_str())
     }
 
+    /// Returns a path to the checkpoint with the given height.
+    ///
+    /// If there is no checkpoint with the specified height, the returned path
+    /// doesn't exist on the filesystem.
+    pub fn checkpoint_path(&self, h: Height) -> PathBuf {
+        self.cp_manager
+            .get_checkpoint_path(self.checkpoint_name(h).as_str())
+    }
+
     /// Returns a path to a backed up state given its height.
     ///
     /// Precondition:
@@ -682,6 +750,37 @@ impl<Permissions: AccessPolicy> CheckpointLayout<Permissions> {
         )
     }
 
+    pub fn snapshot_ids(&self) -> Result<Vec<SnapshotId>, LayoutError> {
+        let snapshots_dir = self.root.join("snapshots");
+        Permissions::check_dir(&snapshots_dir)?;
+        collect_subdirs(snapshots_dir.as_path(), |p| {
+            let id = u64::from_str_radix(p, 16).unwrap_or_else(|err| {
+                panic!(
+                    "Failed to convert directory name {} into a snapshot id: {}",
+                    p, err
+                )
+            });
+            SnapshotId::from(id)
+        })
+    }
+
+    pub fn snapshot(
+        &self,
+        snapshot_id: &SnapshotId,
+    ) -> Result<SnapshotLayout<Permissions>, LayoutError> {
+        SnapshotLayout::new(
+            self.root
+                .join("snapshots")
+                .join(format!("{:016x}", snapshot_id.get())),
+        )
+    }
+
+    /// The Bitcoin state of the subnet. The directory only exists if the
+    /// Bitcoin feature is enabled on the subnet.
+    pub fn bitcoin(&self) -> Result<BitcoinStateLayout<Permissions>, LayoutError> {
+        BitcoinStateLayout::new(self.root.join("bitcoin"))
+    }
+
     pub fn height(&self) -> Height {
         self.height
     }
@@ -731,6 +830,13 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
         self.canister_root.join("stable_memory.bin")
     }
 
+    /// The directory holding the chunks of the canister's Wasm chunk store,
+    /// one file per chunk named after the hex-encoded hash of the chunk. The
+    /// directory only exists if the store is not empty.
+    pub fn wasm_chunk_store(&self) -> PathBuf {
+        self.canister_root.join("wasm_chunk_store")
+    }
+
     pub fn tombstone(&self) -> PathBuf {
         self.canister_root.join("tombstone")
     }
@@ -753,6 +859,91 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
     }
 }
 
+pub struct SnapshotLayout<Permissions: AccessPolicy> {
+    snapshot_root: PathBuf,
+    permissions_tag: PhantomData<Permissions>,
+}
+
+impl<Permissions: AccessPolicy> SnapshotLayout<Permissions> {
+    pub fn new(snapshot_root: PathBuf) -> Result<Self, LayoutError> {
+        Permissions::check_dir(&snapshot_root)?;
+        Ok(Self {
+            snapshot_root,
+            permissions_tag: PhantomData,
+        })
+    }
+
+    pub fn raw_path(&self) -> PathBuf {
+        self.snapshot_root.clone()
+    }
+
+    pub fn wasm(&self) -> WasmFile<Permissions> {
+        self.snapshot_root.join("software.wasm").into()
+    }
+
+    pub fn snapshot(
+        &self,
+    ) -> ProtoFileWith<pb_canister_state_bits::CanisterSnapshotBits, Permissions> {
+        self.snapshot_root.join("snapshot.pbuf").into()
+    }
+
+    pub fn vmemory_0(&self) -> PathBuf {
+        self.snapshot_root.join("vmemory_0.bin")
+    }
+
+    pub fn stable_memory_blob(&self) -> PathBuf {
+        self.snapshot_root.join("stable_memory.bin")
+    }
+
+    pub fn tombstone(&self) -> PathBuf {
+        self.snapshot_root.join("tombstone")
+    }
+
+    /// Marks this snapshot as deleted by creating a 'tombstone' file in the
+    /// snapshot directory.  Such directories will be excluded when a
+    /// checkpoint is created.
+    pub fn mark_deleted(&self) -> Result<(), LayoutError> {
+        let path = self.tombstone();
+        let _ = std::fs::File::create(&path).map_err(|err| LayoutError::IoError {
+            path,
+            message: "Failed to create a file".to_string(),
+            io_err: err,
+        })?;
+        Ok(())
+    }
+
+    pub fn is_marked_deleted(&self) -> bool {
+        Path::new(&self.tombstone()).exists()
+    }
+}
+
+pub struct BitcoinStateLayout<Permissions: AccessPolicy> {
+    bitcoin_root: PathBuf,
+    permissions_tag: PhantomData<Permissions>,
+}
+
+impl<Permissions: AccessPolicy> BitcoinStateLayout<Permissions> {
+    pub fn new(bitcoin_root: PathBuf) -> Result<Self, LayoutError> {
+        Permissions::check_dir(&bitcoin_root)?;
+        Ok(Self {
+            bitcoin_root,
+            permissions_tag: PhantomData,
+        })
+    }
+
+    pub fn raw_path(&self) -> PathBuf {
+        self.bitcoin_root.clone()
+    }
+
+    pub fn bitcoin_state(&self) -> ProtoFileWith<pb_metadata::BitcoinState, Permissions> {
+        self.bitcoin_root.join("state.pbuf").into()
+    }
+
+    pub fn utxos(&self) -> PathBuf {
+        self.bitcoin_root.join("utxos.bin")
+    }
+}
+
 fn open_for_write(path: &Path) -> Result<std::fs::File, LayoutError> {
     OpenOptions::new()
         .write(true)
@@ -997,6 +1188,34 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
             },
             stable_memory_size64: item.stable_memory_size.get() as u64,
             heap_delta_debit: item.heap_delta_debit.get(),
+            global_timer_nanos: item
+                .global_timer
+                .map_or(0, |time| time.as_nanos_since_unix_epoch()),
+            canister_log_records: item
+                .canister_log
+                .records()
+                .map(|record| pb_canister_state_bits::CanisterLogRecord {
+                    idx: record.idx,
+                    timestamp_nanos: record.timestamp_nanos,
+                    content: record.content.clone(),
+                })
+                .collect(),
+            next_canister_log_record_idx: item.canister_log.next_idx(),
+            log_visibility: match item.log_visibility {
+                LogVisibility::Controllers => pb_canister_state_bits::LogVisibility::Controllers,
+                LogVisibility::Public => pb_canister_state_bits::LogVisibility::Public,
+            }
+            .into(),
+            canister_history: Some(pb_canister_state_bits::CanisterHistory {
+                changes: item
+                    .canister_history
+                    .changes()
+                    .map(|change| change.into())
+                    .collect(),
+                total_num_changes: item.canister_history.total_num_changes(),
+            }),
+            canister_version: item.canister_version,
+            wasm_memory_limit: item.wasm_memory_limit.map(|limit| limit.get()).unwrap_or(0),
         }
     }
 }
@@ -1005,6 +1224,42 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
     type Error = ProxyDecodeError;
 
     fn try_from(value: pb_canister_state_bits::CanisterStateBits) -> Result<Self, Self::Error> {
+        let canister_log = CanisterLog::new(
+            value
+                .canister_log_records
+                .into_iter()
+                .map(|record| CanisterLogRecord {
+                    idx: record.idx,
+                    timestamp_nanos: record.timestamp_nanos,
+                    content: record.content,
+                })
+                .collect(),
+            value.next_canister_log_record_idx,
+        );
+
+        // Checkpoints written before the visibility was introduced do not have
+        // it set, in which case the log is only visible to controllers.
+        let log_visibility =
+            match pb_canister_state_bits::LogVisibility::from_i32(value.log_visibility)
+                .unwrap_or_default()
+            {
+                pb_canister_state_bits::LogVisibility::Unspecified
+                | pb_canister_state_bits::LogVisibility::Controllers => LogVisibility::Controllers,
+                pb_canister_state_bits::LogVisibility::Public => LogVisibility::Public,
+            };
+
+        let canister_history = match value.canister_history {
+            Some(history) => CanisterHistory::new(
+                history
+                    .changes
+                    .into_iter()
+                    .map(CanisterChange::try_from)
+                    .collect::<Result<_, _>>()?,
+                history.total_num_changes,
+            ),
+            None => CanisterHistory::default(),
+        };
+
         let execution_state_bits = value
             .execution_state_bits
             .map(|b| b.try_into())
@@ -1087,6 +1342,18 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             consumed_cycles_since_replica_started,
             stable_memory_size: NumWasmPages::from(stable_memory_size as usize),
             heap_delta_debit: NumBytes::from(value.heap_delta_debit),
+            global_timer: match value.global_timer_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
+            canister_log,
+            log_visibility,
+            canister_history,
+            canister_version: value.canister_version,
+            wasm_memory_limit: match value.wasm_memory_limit {
+                0 => None,
+                limit => Some(NumBytes::from(limit)),
+            },
         })
     }
 }
@@ -1129,6 +1396,38 @@ impl TryFrom<pb_canister_state_bits::ExecutionStateBits> for ExecutionStateBits
     }
 }
 
+impl From<CanisterSnapshotBits> for pb_canister_state_bits::CanisterSnapshotBits {
+    fn from(item: CanisterSnapshotBits) -> Self {
+        Self {
+            canister_id: Some(item.canister_id.into()),
+            taken_at_timestamp_nanos: item.taken_at_timestamp.as_nanos_since_unix_epoch(),
+            execution_state_bits: Some((&item.execution_state_bits).into()),
+            stable_memory_size: item.stable_memory_size.get() as u64,
+            certified_data: item.certified_data,
+        }
+    }
+}
+
+impl TryFrom<pb_canister_state_bits::CanisterSnapshotBits> for CanisterSnapshotBits {
+    type Error = ProxyDecodeError;
+
+    fn try_from(value: pb_canister_state_bits::CanisterSnapshotBits) -> Result<Self, Self::Error> {
+        Ok(Self {
+            canister_id: try_from_option_field(
+                value.canister_id,
+                "CanisterSnapshotBits::canister_id",
+            )?,
+            taken_at_timestamp: Time::from_nanos_since_unix_epoch(value.taken_at_timestamp_nanos),
+            execution_state_bits: try_from_option_field(
+                value.execution_state_bits,
+                "CanisterSnapshotBits::execution_state_bits",
+            )?,
+            stable_memory_size: NumWasmPages::from(value.stable_memory_size as usize),
+            certified_data: value.certified_data,
+        })
+    }
+}
+
 // A principal used to indicate that there are no controllers present.
 // Note the "no controller" substring in the principal.
 fn no_controllers_marker() -> PrincipalId {
@@ -1140,7 +1439,10 @@ mod test {
     use super::*;
 
     use ic_test_utilities::types::ids::canister_test_id;
-    use ic_types::ic00::IC_00;
+    use ic_types::{
+        ic00::{CanisterChangeDetails, CanisterChangeOrigin, IC_00},
+        messages::CanisterInstallMode,
+    };
 
     #[test]
     fn test_encode_decode_empty_controllers() {
@@ -1164,6 +1466,12 @@ mod test {
             consumed_cycles_since_replica_started: NominalCycles::from(0),
             stable_memory_size: NumWasmPages::from(0),
             heap_delta_debit: NumBytes::from(0),
+            global_timer: None,
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1203,6 +1511,12 @@ mod test {
             consumed_cycles_since_replica_started: NominalCycles::from(0),
             stable_memory_size: NumWasmPages::from(0),
             heap_delta_debit: NumBytes::from(0),
+            global_timer: None,
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1244,6 +1558,12 @@ mod test {
             consumed_cycles_since_replica_started: NominalCycles::from(0),
             stable_memory_size: NumWasmPages::from(0),
             heap_delta_debit: NumBytes::from(0),
+            global_timer: None,
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1252,4 +1572,154 @@ mod test {
 
         assert_eq!(canister_state_bits.controllers, controllers)
     }
+
+    #[test]
+    fn test_encode_decode_global_timer() {
+        let global_timer = Some(Time::from_nanos_since_unix_epoch(1_234_567));
+
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            stable_memory_size: NumWasmPages::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            global_timer,
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+
+        assert_eq!(pb_bits.global_timer_nanos, 1_234_567);
+
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.global_timer, global_timer);
+    }
+
+    #[test]
+    fn test_encode_decode_canister_log() {
+        let mut canister_log = CanisterLog::default();
+        canister_log.add_record(Time::from_nanos_since_unix_epoch(7), b"hello".to_vec());
+        canister_log.add_record(Time::from_nanos_since_unix_epoch(8), b"world".to_vec());
+
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            stable_memory_size: NumWasmPages::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            global_timer: None,
+            canister_log: canister_log.clone(),
+            log_visibility: LogVisibility::Public,
+            canister_history: CanisterHistory::default(),
+            canister_version: 0,
+            wasm_memory_limit: None,
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+
+        assert_eq!(pb_bits.canister_log_records.len(), 2);
+        assert_eq!(pb_bits.next_canister_log_record_idx, 2);
+
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.canister_log, canister_log);
+        assert_eq!(canister_state_bits.log_visibility, LogVisibility::Public);
+    }
+
+    #[test]
+    fn test_encode_decode_canister_history() {
+        let mut canister_history = CanisterHistory::default();
+        canister_history.add_canister_change(
+            Time::from_nanos_since_unix_epoch(7),
+            0,
+            CanisterChangeOrigin::FromUser {
+                user_id: PrincipalId::new_user_test_id(1),
+            },
+            CanisterChangeDetails::Creation {
+                controllers: vec![PrincipalId::new_user_test_id(1)],
+            },
+        );
+        canister_history.add_canister_change(
+            Time::from_nanos_since_unix_epoch(8),
+            1,
+            CanisterChangeOrigin::FromCanister {
+                canister_id: CanisterId::from_u64(42).get(),
+            },
+            CanisterChangeDetails::CodeDeployment {
+                mode: CanisterInstallMode::Upgrade,
+                module_hash: vec![1; 32],
+            },
+        );
+
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            stable_memory_size: NumWasmPages::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            global_timer: None,
+            canister_log: CanisterLog::default(),
+            log_visibility: LogVisibility::default(),
+            canister_history: canister_history.clone(),
+            canister_version: 1,
+            wasm_memory_limit: Some(NumBytes::from(4096)),
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+
+        assert_eq!(pb_bits.canister_history.as_ref().unwrap().changes.len(), 2);
+
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(canister_state_bits.canister_history, canister_history);
+        assert_eq!(canister_state_bits.canister_version, 1);
+        assert_eq!(
+            canister_state_bits.wasm_memory_limit,
+            Some(NumBytes::from(4096))
+        );
+    }
 }
@@ -1,10 +1,17 @@
+use ic_artifact_pool::canister_http_pool::CanisterHttpPoolImpl;
+use ic_canister_http::{CanisterHttpPayloadBuilderImpl, CanisterHttpPoolManagerImpl};
 use ic_config::subnet_config::SubnetConfig;
 use ic_config::subnet_config::SubnetConfigs;
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_execution_environment::setup_execution;
 use ic_interfaces::{
+    canister_http::{
+        CanisterHttpAdapterClient, CanisterHttpPayloadBuilder, CanisterHttpPoolManager,
+        MutableCanisterHttpPool,
+    },
     execution_environment::{IngressHistoryReader, QueryHandler},
     messaging::MessageRouting,
+    registry::RegistryClient,
     state_manager::{StateHashError, StateManager, StateReader},
 };
 use ic_logger::ReplicaLogger;
@@ -23,34 +30,58 @@ use ic_registry_keys::{
 };
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_routing_table::{routing_table_insert_subnet, RoutingTable};
+use ic_registry_subnet_features::SubnetFeatures;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::ReplicatedState;
-use ic_state_manager::StateManagerImpl;
+use ic_state_manager::{
+    canister_archive::export_canister_from_latest_checkpoint, StateManagerImpl,
+};
 use ic_test_utilities::{
     consensus::fake::FakeVerifier,
+    crypto::CryptoReturningOk,
     mock_time,
     registry::{add_subnet_record, insert_initial_dkg_transcript, SubnetRecordBuilder},
     types::messages::SignedIngressBuilder,
 };
 use ic_types::batch::SelfValidatingPayload;
+use ic_types::canister_http::CanisterHttpPayload;
 use ic_types::{
-    batch::{Batch, BatchPayload, IngressPayload, XNetPayload},
+    batch::{Batch, BatchPayload, IngressPayload, ValidationContext, XNetPayload},
+    crypto::{
+        canister_threshold_sig::{EcdsaPublicKey, ExtendedDerivationPath},
+        AlgorithmId,
+    },
     ic00,
-    ic00::{CanisterIdRecord, CanisterSettingsArgs, InstallCodeArgs, Method, Payload},
+    ic00::{
+        CanisterIdRecord, CanisterSettingsArgs, EcdsaCurve, EcdsaKeyId, InstallCodeArgs, Method,
+        Payload, SignWithECDSAReply,
+    },
     ingress::{IngressStatus, WasmResult},
-    messages::{CanisterInstallMode, MessageId, SignedIngress, UserQuery},
+    messages::{self, CanisterInstallMode, MessageId, Response, SignedIngress, UserQuery},
     time::Time,
     user_error::UserError,
-    CanisterId, CryptoHashOfState, NodeId, PrincipalId, Randomness, RegistryVersion, SubnetId,
-    UserId,
+    CanisterId, CryptoHashOfState, Cycles, Height, NodeId, NumBytes, PrincipalId, Randomness,
+    RegistryVersion, SubnetId, UserId,
 };
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
 use std::collections::BTreeMap;
 use std::fmt;
+use std::path::Path;
 use std::string::ToString;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tempfile::TempDir;
 
+/// The maximum size of the canister HTTP payload of a batch.
+const MAX_CANISTER_HTTP_PAYLOAD_BYTES: u64 = 4 * 1024 * 1024;
+
+/// The name of the only ECDSA key of the state machine.
+const ECDSA_KEY_NAME: &str = "master_ecdsa_public_key";
+
+/// The master secret key the state machine signs ECDSA requests with, in
+/// place of a threshold key shared among the nodes of a real subnet.
+const ECDSA_SECRET_KEY: [u8; 32] = [0xcd; 32];
+
 /// Constructs the initial version of the registry containing a subnet with the
 /// specified SUBNET_ID, with the node with the specified NODE_ID assigned to
 /// it.
@@ -98,6 +129,13 @@ fn make_single_node_registry(
     // Set subnetwork list(needed for filling network_topology.nns_subnet_id)
     let mut record = SubnetRecordBuilder::from(&[node_id]).build();
     record.subnet_type = i32::from(subnet_type);
+    record.features = Some(
+        SubnetFeatures {
+            ecdsa_signatures: true,
+            ..SubnetFeatures::default()
+        }
+        .into(),
+    );
 
     insert_initial_dkg_transcript(registry_version.get(), subnet_id, &record, &data_provider);
     add_subnet_record(&data_provider, registry_version.get(), subnet_id, record);
@@ -120,6 +158,34 @@ pub struct StateMachine {
     state_dir: TempDir,
     nonce: std::cell::Cell<u64>,
     time: std::cell::Cell<Time>,
+    registry_client: Arc<dyn RegistryClient>,
+    subnet_id: SubnetId,
+    node_id: NodeId,
+    canister_http: Option<CanisterHttp>,
+}
+
+/// The components of a single node subnet that execute canister HTTP
+/// requests and agree on their responses.
+struct CanisterHttp {
+    pool: Arc<RwLock<CanisterHttpPoolImpl>>,
+    pool_manager: CanisterHttpPoolManagerImpl,
+    payload_builder: CanisterHttpPayloadBuilderImpl,
+}
+
+impl CanisterHttp {
+    /// Hands the new requests to the adapter, signs the responses received in
+    /// the meantime and builds a payload from them.
+    fn build_payload(&self, validation_context: &ValidationContext) -> CanisterHttpPayload {
+        let change_set = self
+            .pool_manager
+            .on_state_change(&*self.pool.read().unwrap());
+        self.pool.write().unwrap().apply_changes(change_set);
+        self.payload_builder.get_canister_http_payload(
+            validation_context,
+            &[],
+            NumBytes::from(MAX_CANISTER_HTTP_PAYLOAD_BYTES),
+        )
+    }
 }
 
 impl Default for StateMachine {
@@ -233,14 +299,87 @@ impl StateMachine {
             state_dir,
             nonce: std::cell::Cell::new(nonce),
             time: std::cell::Cell::new(time),
+            registry_client: registry as Arc<_>,
+            subnet_id,
+            node_id,
+            canister_http: None,
         }
     }
 
+    /// Makes the state machine execute the canister HTTP requests of its
+    /// canisters using the specified adapter client. The responses are
+    /// delivered as part of the batches created by [tick] and [send_ingress].
+    ///
+    /// The adapter client is not preserved across node restarts.
+    pub fn with_canister_http_adapter_client(
+        mut self,
+        adapter_client: Arc<dyn CanisterHttpAdapterClient>,
+    ) -> Self {
+        let logger: ReplicaLogger = slog::Logger::root(slog::Discard, slog::o!()).into();
+        let metrics_registry = MetricsRegistry::new();
+        let crypto = Arc::new(CryptoReturningOk::default());
+        let pool = Arc::new(RwLock::new(CanisterHttpPoolImpl::new(
+            metrics_registry.clone(),
+        )));
+        let pool_manager = CanisterHttpPoolManagerImpl::new(
+            Arc::clone(&self.state_manager) as Arc<_>,
+            Arc::clone(&self.query_handler),
+            adapter_client,
+            Arc::clone(&crypto) as Arc<_>,
+            Arc::clone(&self.registry_client),
+            self.node_id,
+            self.subnet_id,
+            &metrics_registry,
+            logger.clone(),
+        );
+        let payload_builder = CanisterHttpPayloadBuilderImpl::new(
+            Arc::clone(&pool) as Arc<_>,
+            Arc::clone(&self.state_manager) as Arc<_>,
+            crypto,
+            Arc::clone(&self.registry_client),
+            self.subnet_id,
+            &metrics_registry,
+            logger,
+        );
+        self.canister_http = Some(CanisterHttp {
+            pool,
+            pool_manager,
+            payload_builder,
+        });
+        self
+    }
+
     /// Emulates a node restart, including checkpoint recovery.
     pub fn restart_node(self) -> Self {
         Self::setup_from_dir(self.state_dir, self.nonce.get(), self.time.get(), None)
     }
 
+    /// Writes the state of `canister_id` persisted in the latest checkpoint
+    /// to a new archive at `archive`, as the source subnet of a canister
+    /// migration does. The checkpoint must be at the latest state height, i.e.
+    /// include all messages executed so far.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the latest state is not checkpointed or if
+    /// the canister cannot be exported.
+    pub fn export_canister_state(&self, canister_id: CanisterId, archive: &Path) {
+        export_canister_from_latest_checkpoint(
+            self.state_manager.state_layout(),
+            &canister_id,
+            self.state_manager.latest_state_height(),
+            archive,
+        )
+        .unwrap_or_else(|err| {
+            panic!(
+                "failed to export canister {} to {}: {}",
+                canister_id,
+                archive.display(),
+                err
+            )
+        });
+    }
+
     pub fn restart_node_with_config(self, config: SubnetConfig) -> Self {
         Self::setup_from_dir(
             self.state_dir,
@@ -253,27 +392,143 @@ impl StateMachine {
     /// Creates a new batch containing a single ingress message and sends it for
     /// processing to the replicated state machine.
     fn send_signed_ingress(&self, msg: SignedIngress) {
+        self.deliver_batch(IngressPayload::from(vec![msg]));
+    }
+
+    /// Creates a new batch without ingress messages, sends it for processing
+    /// to the replicated state machine and waits until it has been executed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the batch was not executed in a reasonable
+    /// amount of time (typically, a few seconds).
+    pub fn tick(&self) {
+        let batch_number = self.deliver_batch(IngressPayload::default());
+        self.await_batch_execution(batch_number);
+    }
+
+    /// Blocks until the batch with the specified number has been executed.
+    fn await_batch_execution(&self, batch_number: Height) {
+        let mut tries = 0;
+        while self.state_manager.latest_state_height() < batch_number {
+            tries += 1;
+            if tries > 100 {
+                panic!("Batch {} was not executed in time", batch_number);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Returns the id of the ECDSA key that the state machine signs
+    /// `sign_with_ecdsa` requests with.
+    pub fn ecdsa_key_id(&self) -> EcdsaKeyId {
+        EcdsaKeyId {
+            curve: EcdsaCurve::Secp256k1,
+            name: ECDSA_KEY_NAME.to_string(),
+        }
+    }
+
+    /// Returns the ECDSA master public keys of the subnet.
+    fn ecdsa_subnet_public_keys(&self) -> BTreeMap<EcdsaKeyId, EcdsaPublicKey> {
+        let secret_key = SecretKey::from_slice(&ECDSA_SECRET_KEY).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        let mut public_keys = BTreeMap::new();
+        public_keys.insert(
+            self.ecdsa_key_id(),
+            EcdsaPublicKey {
+                algorithm_id: AlgorithmId::ThresholdEcdsaSecp256k1,
+                public_key: public_key.serialize().to_vec(),
+            },
+        );
+        public_keys
+    }
+
+    /// Signs the pending `sign_with_ecdsa` requests of the latest state with
+    /// the keys derived from the master secret key, just like the threshold
+    /// protocol would, and returns the responses to deliver.
+    fn sign_with_ecdsa_responses(&self) -> Vec<Response> {
+        let secp = Secp256k1::new();
+        let state = self.state_manager.get_latest_state();
+        state
+            .get_ref()
+            .metadata
+            .subnet_call_context_manager
+            .sign_with_ecdsa_contexts
+            .iter()
+            .map(|(callback_id, context)| {
+                let derivation_path = tecdsa::DerivationPath::from(&ExtendedDerivationPath {
+                    caller: context.request.sender.get(),
+                    derivation_path: context.derivation_path.clone(),
+                });
+                let tweak = derivation_path
+                    .derive_tweak(tecdsa::EccCurveType::K256)
+                    .expect("failed to derive the key tweak");
+                let mut secret_key = SecretKey::from_slice(&ECDSA_SECRET_KEY).unwrap();
+                secret_key
+                    .add_assign(&tweak.serialize())
+                    .expect("failed to derive the secret key");
+                let message = Message::from_slice(&context.message_hash)
+                    .expect("the message hash is not 32 bytes long");
+                let signature = secp.sign(&message, &secret_key);
+                Response {
+                    originator: CanisterId::ic_00(),
+                    respondent: CanisterId::ic_00(),
+                    originator_reply_callback: *callback_id,
+                    refund: Cycles::zero(),
+                    response_payload: messages::Payload::Data(
+                        SignWithECDSAReply {
+                            signature: signature.serialize_compact().to_vec(),
+                        }
+                        .encode(),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Creates a new batch with the specified ingress payload, the available
+    /// canister HTTP responses and the signatures of the pending ECDSA
+    /// signing requests, if any, and sends it for processing to the
+    /// replicated state machine. Returns the batch number.
+    fn deliver_batch(&self, ingress: IngressPayload) -> Height {
         // Move the block time forward by 1 second.
         self.time.set(self.time.get() + Duration::from_secs(1));
 
+        let batch_number = self.message_routing.expected_batch_height();
+        // The responses are built from the latest state, so all previous
+        // batches must have been executed to not deliver a response twice.
+        self.await_batch_execution(batch_number.decrement());
+        let consensus_responses = self.sign_with_ecdsa_responses();
+        let canister_http = match &self.canister_http {
+            Some(canister_http) => canister_http.build_payload(&ValidationContext {
+                registry_version: RegistryVersion::from(1),
+                certified_height: self.state_manager.latest_state_height(),
+                time: self.time.get(),
+            }),
+            None => CanisterHttpPayload::default(),
+        };
+
         let batch = Batch {
-            batch_number: self.message_routing.expected_batch_height(),
+            batch_number,
             requires_full_state_hash: true,
             payload: BatchPayload {
-                ingress: IngressPayload::from(vec![msg]),
+                ingress,
                 xnet: XNetPayload {
                     stream_slices: Default::default(),
                 },
                 self_validating: SelfValidatingPayload::default(),
+                canister_http,
             },
             randomness: Randomness::from([0; 32]),
             registry_version: RegistryVersion::from(1),
             time: self.time.get(),
-            consensus_responses: vec![],
+            consensus_responses,
+            ecdsa_subnet_public_keys: self.ecdsa_subnet_public_keys(),
         };
         self.message_routing
             .deliver_batch(batch)
-            .expect("MR queue overflow")
+            .expect("MR queue overflow");
+        batch_number
     }
 
     /// Blocks until the hash of the latest state is computed.
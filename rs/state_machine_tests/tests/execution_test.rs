@@ -1,9 +1,23 @@
+use ic_canister_http::CanisterHttpAdapterClientImpl;
 use ic_config::subnet_config::{CyclesAccountManagerConfig, SubnetConfigs};
 use ic_error_types::ErrorCode;
 use ic_registry_subnet_type::SubnetType;
+use ic_state_layout::{CheckpointLayout, RwPolicy};
 use ic_state_machine_tests::StateMachine;
-use ic_types::ic00::CanisterSettingsArgs;
-use ic_types::Cycles;
+use ic_state_manager::canister_archive::import_canister;
+use ic_types::ic00::{
+    CanisterHttpRequestArgs, CanisterHttpResponsePayload, CanisterIdRecord, CanisterSettingsArgs,
+    CanisterSnapshotArgs, CanisterSnapshotResponse, ECDSAPublicKeyArgs, ECDSAPublicKeyResponse,
+    EcdsaKeyId, HttpMethod, Method, Payload, SignWithECDSAArgs, SignWithECDSAReply,
+    TakeCanisterSnapshotArgs, IC_00,
+};
+use ic_types::ingress::{IngressStatus, WasmResult};
+use ic_types::messages::MessageId;
+use ic_types::{CanisterId, Cycles, Height};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// This is a canister that keeps a counter on the heap and exposes various test
 /// methods. Exposed methods:
@@ -261,6 +275,8 @@ async fn test_canister_out_of_cycles() {
             compute_allocation: Some(candid::Nat::from(1)),
             memory_allocation: None,
             freezing_threshold: None,
+            log_visibility: None,
+            wasm_memory_limit: None,
         }),
     );
 
@@ -354,3 +370,377 @@ async fn test_manifest_computation_memory_expand() {
     let state_hash_3 = env.await_state_hash();
     assert_ne!(state_hash_2, state_hash_3);
 }
+
+/// Executes a management canister call and returns its reply.
+fn execute_ic00(env: &StateMachine, method: Method, payload: Vec<u8>) -> Vec<u8> {
+    match env.execute_ingress(IC_00, method, payload).unwrap() {
+        WasmResult::Reply(bytes) => bytes,
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    }
+}
+
+/// The test checks that the memory of a canister restored from a snapshot
+/// that was loaded from a checkpoint is checkpointed correctly, i.e. that the
+/// canister heap is the one of the snapshot (plus the changes made after
+/// loading it) both before and after a checkpoint recovery.
+#[tokio::test]
+async fn test_load_canister_snapshot_restart() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(TEST_CANISTER, vec![], None);
+    let canister_record = CanisterIdRecord::from(canister_id).encode();
+
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    env.execute_ingress(canister_id, "copy_to", from_int(40_000))
+        .unwrap();
+    execute_ic00(&env, Method::StopCanister, canister_record.clone());
+    let snapshot = CanisterSnapshotResponse::decode(&execute_ic00(
+        &env,
+        Method::TakeCanisterSnapshot,
+        TakeCanisterSnapshotArgs::new(canister_id, None).encode(),
+    ))
+    .unwrap();
+    execute_ic00(&env, Method::StartCanister, canister_record.clone());
+
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    env.execute_ingress(canister_id, "copy_to", from_int(8_000))
+        .unwrap();
+
+    // The snapshot is now backed by the files of the checkpoint.
+    let env = env.restart_node();
+
+    execute_ic00(&env, Method::StopCanister, canister_record.clone());
+    execute_ic00(
+        &env,
+        Method::LoadCanisterSnapshot,
+        CanisterSnapshotArgs::new(canister_id, snapshot.id).encode(),
+    );
+    execute_ic00(&env, Method::StartCanister, canister_record);
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+
+    let read_heap = |env: &StateMachine| {
+        (
+            to_int(env.query(canister_id, "read", vec![]).unwrap().bytes()),
+            to_int(
+                env.query(canister_id, "read_at", from_int(8_000))
+                    .unwrap()
+                    .bytes(),
+            ),
+            to_int(
+                env.query(canister_id, "read_at", from_int(40_000))
+                    .unwrap()
+                    .bytes(),
+            ),
+        )
+    };
+    assert_eq!(read_heap(&env), (2, 0, 1));
+
+    let env = env.restart_node();
+    assert_eq!(read_heap(&env), (2, 0, 1));
+}
+
+/// The test exports a canister from the latest checkpoint of a state machine
+/// the way the source subnet of a canister migration does and checks that the
+/// archive can be imported offline into another checkpoint.
+#[tokio::test]
+async fn test_canister_export_import() {
+    let source = StateMachine::new();
+    let canister_id = source.install_canister_wat(TEST_CANISTER, vec![], None);
+    source.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    source
+        .execute_ingress(canister_id, "grow_page", vec![])
+        .unwrap();
+    source
+        .execute_ingress(canister_id, "persist", vec![])
+        .unwrap();
+    source.execute_ingress(canister_id, "inc", vec![]).unwrap();
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let archive = tmp.path().join("canister.tar");
+    source.export_canister_state(canister_id, &archive);
+
+    let checkpoint =
+        CheckpointLayout::<RwPolicy>::new(tmp.path().join("checkpoint"), Height::new(0)).unwrap();
+    assert_eq!(import_canister(&archive, &checkpoint).unwrap(), canister_id);
+    assert_eq!(checkpoint.canister_ids().unwrap(), vec![canister_id]);
+}
+
+/// This is a canister that forwards its argument to the `http_request` method
+/// of the management canister and replies with the response. Exposed methods:
+///  * "fetch"     call `http_request` with the Candid encoded argument
+const HTTP_CANISTER: &str = r#"
+            (module
+              (import "ic0" "msg_arg_data_size" (func $msg_arg_data_size (result i32)))
+              (import "ic0" "msg_arg_data_copy"
+                (func $msg_arg_data_copy (param $dst i32) (param $offset i32) (param $size i32)))
+              (import "ic0" "msg_reject_msg_size" (func $msg_reject_msg_size (result i32)))
+              (import "ic0" "msg_reject_msg_copy"
+                (func $msg_reject_msg_copy (param $dst i32) (param $offset i32) (param $size i32)))
+              (import "ic0" "msg_reply" (func $msg_reply))
+              (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32)))
+              (import "ic0" "msg_reject" (func $msg_reject (param i32 i32)))
+              (import "ic0" "call_new"
+                (func $call_new
+                  (param $callee_src i32) (param $callee_size i32)
+                  (param $name_src i32) (param $name_len i32)
+                  (param $reply_fun i32) (param $reply_env i32)
+                  (param $reject_fun i32) (param $reject_env i32)))
+              (import "ic0" "call_data_append"
+                (func $call_data_append (param $src i32) (param $size i32)))
+              (import "ic0" "call_perform" (func $call_perform (result i32)))
+
+              (func $fetch
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                ;; the management canister has an empty principal
+                (call $call_new
+                  (i32.const 0) (i32.const 0)   ;; callee
+                  (i32.const 0) (i32.const 12)  ;; "http_request"
+                  (i32.const 0) (i32.const 0)   ;; reply callback
+                  (i32.const 1) (i32.const 0))  ;; reject callback
+                (call $call_data_append (i32.const 100) (call $msg_arg_data_size))
+                (drop (call $call_perform))
+              )
+
+              (func $on_reply (param $env i32)
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                (call $msg_reply_data_append (i32.const 100) (call $msg_arg_data_size))
+                (call $msg_reply)
+              )
+
+              (func $on_reject (param $env i32)
+                (call $msg_reject_msg_copy (i32.const 100) (i32.const 0) (call $msg_reject_msg_size))
+                (call $msg_reject (i32.const 100) (call $msg_reject_msg_size))
+              )
+
+              (table funcref (elem $on_reply $on_reject))
+              (memory $memory 1)
+              (data (i32.const 0) "http_request")
+              (export "memory" (memory $memory))
+              (export "canister_update fetch" (func $fetch)))"#;
+
+/// Verifies that a canister HTTP request is executed and its response is
+/// delivered to the calling canister.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_canister_http_request() {
+    // A server that answers a single request with "hello".
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+    });
+
+    let adapter_client = CanisterHttpAdapterClientImpl::new(tokio::runtime::Handle::current());
+    let env = StateMachine::new().with_canister_http_adapter_client(Arc::new(adapter_client));
+
+    let canister_id = env.install_canister_wat(HTTP_CANISTER, vec![], None);
+    let args = CanisterHttpRequestArgs {
+        url,
+        max_response_bytes: None,
+        headers: vec![],
+        body: None,
+        method: HttpMethod::GET,
+        transform_method_name: None,
+    };
+    let msg_id = env.send_ingress(canister_id, "fetch", args.encode());
+
+    let mut tries = 0;
+    let result = loop {
+        match env.ingress_status(&msg_id) {
+            IngressStatus::Completed { result, .. } => break result,
+            IngressStatus::Failed { error, .. } => panic!("Unexpected error: {}", error),
+            _ => (),
+        }
+        tries += 1;
+        assert!(tries < 100, "The http request did not complete in time");
+        std::thread::sleep(Duration::from_millis(50));
+        env.tick();
+    };
+
+    let response = CanisterHttpResponsePayload::decode(&result.bytes()).unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"hello".to_vec());
+}
+
+/// This is a canister that forwards its argument to the ECDSA methods of the
+/// management canister and replies with the response. Exposed methods:
+///  * "sign"       call `sign_with_ecdsa` with the Candid encoded argument
+///  * "public_key" call `ecdsa_public_key` with the Candid encoded argument
+const ECDSA_CANISTER: &str = r#"
+            (module
+              (import "ic0" "msg_arg_data_size" (func $msg_arg_data_size (result i32)))
+              (import "ic0" "msg_arg_data_copy"
+                (func $msg_arg_data_copy (param $dst i32) (param $offset i32) (param $size i32)))
+              (import "ic0" "msg_reject_msg_size" (func $msg_reject_msg_size (result i32)))
+              (import "ic0" "msg_reject_msg_copy"
+                (func $msg_reject_msg_copy (param $dst i32) (param $offset i32) (param $size i32)))
+              (import "ic0" "msg_reply" (func $msg_reply))
+              (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32)))
+              (import "ic0" "msg_reject" (func $msg_reject (param i32 i32)))
+              (import "ic0" "call_new"
+                (func $call_new
+                  (param $callee_src i32) (param $callee_size i32)
+                  (param $name_src i32) (param $name_len i32)
+                  (param $reply_fun i32) (param $reply_env i32)
+                  (param $reject_fun i32) (param $reject_env i32)))
+              (import "ic0" "call_data_append"
+                (func $call_data_append (param $src i32) (param $size i32)))
+              (import "ic0" "call_perform" (func $call_perform (result i32)))
+
+              (func $forward (param $name_src i32) (param $name_len i32)
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                ;; the management canister has an empty principal
+                (call $call_new
+                  (i32.const 0) (i32.const 0)                ;; callee
+                  (local.get $name_src) (local.get $name_len) ;; method name
+                  (i32.const 0) (i32.const 0)                ;; reply callback
+                  (i32.const 1) (i32.const 0))               ;; reject callback
+                (call $call_data_append (i32.const 100) (call $msg_arg_data_size))
+                (drop (call $call_perform))
+              )
+
+              (func $sign
+                (call $forward (i32.const 0) (i32.const 15)))   ;; "sign_with_ecdsa"
+
+              (func $public_key
+                (call $forward (i32.const 16) (i32.const 16)))  ;; "ecdsa_public_key"
+
+              (func $on_reply (param $env i32)
+                (call $msg_arg_data_copy (i32.const 100) (i32.const 0) (call $msg_arg_data_size))
+                (call $msg_reply_data_append (i32.const 100) (call $msg_arg_data_size))
+                (call $msg_reply)
+              )
+
+              (func $on_reject (param $env i32)
+                (call $msg_reject_msg_copy (i32.const 100) (i32.const 0) (call $msg_reject_msg_size))
+                (call $msg_reject (i32.const 100) (call $msg_reject_msg_size))
+              )
+
+              (table funcref (elem $on_reply $on_reject))
+              (memory $memory 1)
+              (data (i32.const 0) "sign_with_ecdsa")
+              (data (i32.const 16) "ecdsa_public_key")
+              (export "memory" (memory $memory))
+              (export "canister_update sign" (func $sign))
+              (export "canister_update public_key" (func $public_key)))"#;
+
+/// Ticks the state machine until the ingress message with the specified id
+/// completes and returns its result.
+fn tick_until_completed(env: &StateMachine, msg_id: &MessageId) -> WasmResult {
+    let mut tries = 0;
+    loop {
+        match env.ingress_status(msg_id) {
+            IngressStatus::Completed { result, .. } => return result,
+            IngressStatus::Failed { error, .. } => panic!("Unexpected error: {}", error),
+            _ => (),
+        }
+        tries += 1;
+        assert!(tries < 100, "The ingress message did not complete in time");
+        env.tick();
+    }
+}
+
+fn ecdsa_public_key(env: &StateMachine, canister_id: CanisterId, key_id: EcdsaKeyId) -> Vec<u8> {
+    let args = ECDSAPublicKeyArgs {
+        canister_id: None,
+        derivation_path: vec![b"path".to_vec()],
+        key_id,
+    };
+    let msg_id = env.send_ingress(canister_id, "public_key", args.encode());
+    match tick_until_completed(env, &msg_id) {
+        WasmResult::Reply(bytes) => ECDSAPublicKeyResponse::decode(&bytes).unwrap().public_key,
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    }
+}
+
+/// Verifies that a canister gets a signature for its `sign_with_ecdsa`
+/// request that verifies under the public key returned by
+/// `ecdsa_public_key` for the same derivation path.
+#[test]
+fn test_sign_with_ecdsa() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(ECDSA_CANISTER, vec![], None);
+    let public_key = ecdsa_public_key(&env, canister_id, env.ecdsa_key_id());
+
+    let message_hash = [1; 32];
+    let args = SignWithECDSAArgs::new(
+        message_hash.to_vec(),
+        vec![b"path".to_vec()],
+        env.ecdsa_key_id(),
+    );
+    let msg_id = env.send_ingress(canister_id, "sign", args.encode());
+    let signature = match tick_until_completed(&env, &msg_id) {
+        WasmResult::Reply(bytes) => SignWithECDSAReply::decode(&bytes).unwrap().signature,
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    };
+
+    let secp = secp256k1::Secp256k1::new();
+    secp.verify(
+        &secp256k1::Message::from_slice(&message_hash).unwrap(),
+        &secp256k1::Signature::from_compact(&signature).unwrap(),
+        &secp256k1::PublicKey::from_slice(&public_key).unwrap(),
+    )
+    .expect("the signature does not verify under the derived public key");
+
+    // The public key of another derivation path does not verify the
+    // signature.
+    let other_args = ECDSAPublicKeyArgs {
+        canister_id: Some(canister_id),
+        derivation_path: vec![],
+        key_id: env.ecdsa_key_id(),
+    };
+    let msg_id = env.send_ingress(canister_id, "public_key", other_args.encode());
+    let other_public_key = match tick_until_completed(&env, &msg_id) {
+        WasmResult::Reply(bytes) => ECDSAPublicKeyResponse::decode(&bytes).unwrap().public_key,
+        WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+    };
+    assert_ne!(public_key, other_public_key);
+}
+
+/// Verifies that `ecdsa_public_key` returns a chain code that depends on the
+/// derivation path.
+#[test]
+fn test_ecdsa_public_key_returns_chain_code() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(ECDSA_CANISTER, vec![], None);
+    let chain_code = |derivation_path: Vec<Vec<u8>>| {
+        let args = ECDSAPublicKeyArgs {
+            canister_id: None,
+            derivation_path,
+            key_id: env.ecdsa_key_id(),
+        };
+        let msg_id = env.send_ingress(canister_id, "public_key", args.encode());
+        match tick_until_completed(&env, &msg_id) {
+            WasmResult::Reply(bytes) => ECDSAPublicKeyResponse::decode(&bytes).unwrap().chain_code,
+            WasmResult::Reject(msg) => panic!("Unexpected reject: {}", msg),
+        }
+    };
+
+    let chain_code_1 = chain_code(vec![b"path".to_vec()]);
+    assert_eq!(chain_code_1.len(), 32);
+    assert_eq!(chain_code_1, chain_code(vec![b"path".to_vec()]));
+    assert_ne!(chain_code_1, chain_code(vec![b"other path".to_vec()]));
+}
+
+/// Verifies that signing requests for a key the subnet does not hold are
+/// rejected.
+#[test]
+fn test_sign_with_ecdsa_unknown_key() {
+    let env = StateMachine::new();
+    let canister_id = env.install_canister_wat(ECDSA_CANISTER, vec![], None);
+    let mut key_id = env.ecdsa_key_id();
+    key_id.name = "unknown_key".to_string();
+
+    let args = SignWithECDSAArgs::new([1; 32].to_vec(), vec![], key_id);
+    let msg_id = env.send_ingress(canister_id, "sign", args.encode());
+    match tick_until_completed(&env, &msg_id) {
+        WasmResult::Reject(msg) => assert!(msg.contains("Unknown key ID"), "{}", msg),
+        WasmResult::Reply(_) => panic!("Unexpected reply"),
+    }
+}
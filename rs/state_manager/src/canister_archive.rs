@@ -0,0 +1,530 @@
+//! Self-contained archives of the persisted state of a single canister.
+//!
+//! An archive is an uncompressed tar file holding, in this order:
+//!
+//!   * `VERSION`: the version of the archive format, in decimal.
+//!   * `CANISTER_ID`: the textual representation of the canister ID.
+//!   * The files of the canister's directory in a checkpoint: the
+//!     `CanisterStateBits` (`canister.pbuf`), the queues (`queues.pbuf`) and,
+//!     if the canister is not empty, its Wasm module (`software.wasm`), heap
+//!     (`vmemory_0.bin`) and stable memory (`stable_memory.bin`).
+//!   * The chunks of the canister's Wasm chunk store, if any, as
+//!     `wasm_chunk_store/<hex(chunk_hash)>`.
+//!
+//! Archives allow to move a canister out of one checkpoint and into another
+//! one, e.g. to reproduce locally the behavior of a production canister or to
+//! migrate a canister to another subnet: the canister is exported from the
+//! latest checkpoint of the source subnet with
+//! `export_canister_from_latest_checkpoint`, once that checkpoint is at or
+//! above the height at which the canister's range was rerouted. On the
+//! destination subnet, the archive is imported offline into a copy of the
+//! checkpoint that the subnet is recovered from (`state_tool import-canister`),
+//! so that the resulting state is agreed upon through the recovery CUP.
+
+use crate::{checkpoint::io_error, manifest::FileContents, CheckpointError};
+use ic_state_layout::{
+    CanisterLayout, CanisterStateBits, CheckpointLayout, ReadPolicy, RwPolicy, StateLayout,
+};
+use ic_types::{CanisterId, Height, PrincipalId};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The version of the archive format written by `export_canister`.
+pub const CANISTER_ARCHIVE_VERSION: u32 = 2;
+
+const VERSION_ENTRY: &str = "VERSION";
+const CANISTER_ID_ENTRY: &str = "CANISTER_ID";
+
+/// The directory of the Wasm chunk store in a canister directory, see
+/// `CanisterLayout::wasm_chunk_store()`.
+const WASM_CHUNK_STORE_DIR: &str = "wasm_chunk_store";
+
+/// The files of a canister directory that are included in an archive, besides
+/// the chunks in `WASM_CHUNK_STORE_DIR`.
+const CANISTER_FILES: [&str; 5] = [
+    "canister.pbuf",
+    "queues.pbuf",
+    "software.wasm",
+    "vmemory_0.bin",
+    "stable_memory.bin",
+];
+
+fn corrupted_archive(path: &Path, message: String) -> CheckpointError {
+    CheckpointError::CorruptedLayout {
+        path: path.to_path_buf(),
+        message,
+    }
+}
+
+/// Writes the state of canister `canister_id` persisted in `checkpoint_layout`
+/// to a new archive at `archive_path`.
+pub fn export_canister<P: ReadPolicy>(
+    checkpoint_layout: &CheckpointLayout<P>,
+    canister_id: &CanisterId,
+    archive_path: &Path,
+) -> Result<(), CheckpointError> {
+    if !checkpoint_layout.canister_ids()?.contains(canister_id) {
+        return Err(CheckpointError::CorruptedLayout {
+            path: checkpoint_layout.raw_path().to_path_buf(),
+            message: format!("canister {} is not part of the checkpoint", canister_id),
+        });
+    }
+    let canister_layout = checkpoint_layout.canister(canister_id)?;
+
+    let file = File::create(archive_path)
+        .map_err(|err| io_error(archive_path, "failed to create archive", err))?;
+    let mut builder = tar::Builder::new(file);
+    let append_data = |builder: &mut tar::Builder<File>, name: &str, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, data)
+            .map_err(|err| io_error(archive_path, "failed to write to archive", err))
+    };
+    append_data(
+        &mut builder,
+        VERSION_ENTRY,
+        CANISTER_ARCHIVE_VERSION.to_string().as_bytes(),
+    )?;
+    append_data(
+        &mut builder,
+        CANISTER_ID_ENTRY,
+        canister_id.to_string().as_bytes(),
+    )?;
+
+    for name in CANISTER_FILES.iter() {
+        let path = canister_layout.raw_path().join(name);
+        let has_overlays = FileContents::has_overlays(&path)
+            .map_err(|err| io_error(&path, "failed to list overlay files", err))?;
+        if has_overlays {
+            // The archive stores the logical contents of heap files, so that
+            // the overlays don't need to be part of it.
+            let contents = FileContents::open(&path)
+                .map_err(|err| io_error(&path, "failed to open file", err))?;
+            append_data(&mut builder, name, &contents.read(0..contents.size_bytes()))?;
+        } else if path.exists() {
+            builder
+                .append_path_with_name(&path, name)
+                .map_err(|err| io_error(&path, "failed to add file to archive", err))?;
+        }
+    }
+
+    let chunk_store_dir = canister_layout.wasm_chunk_store();
+    if chunk_store_dir.exists() {
+        let mut chunk_paths = std::fs::read_dir(&chunk_store_dir)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<_>>>()
+            })
+            .map_err(|err| io_error(&chunk_store_dir, "failed to list Wasm chunks", err))?;
+        chunk_paths.sort();
+        for path in chunk_paths {
+            let name = Path::new(WASM_CHUNK_STORE_DIR).join(path.file_name().unwrap());
+            builder
+                .append_path_with_name(&path, name)
+                .map_err(|err| io_error(&path, "failed to add file to archive", err))?;
+        }
+    }
+    builder
+        .into_inner()
+        .and_then(|file| file.sync_all())
+        .map_err(|err| io_error(archive_path, "failed to finish archive", err))
+}
+
+/// Reads the next entry of the archive at `archive_path`, which must be
+/// called `name`, into a string.
+fn read_string_entry<R: Read>(
+    entries: &mut tar::Entries<'_, R>,
+    name: &str,
+    archive_path: &Path,
+) -> Result<String, CheckpointError> {
+    let mut entry = entries
+        .next()
+        .ok_or_else(|| corrupted_archive(archive_path, format!("missing entry {}", name)))?
+        .map_err(|err| io_error(archive_path, "failed to read archive entry", err))?;
+    let entry_path = entry
+        .path()
+        .map_err(|err| io_error(archive_path, "failed to read archive entry", err))?
+        .into_owned();
+    if entry_path != Path::new(name) {
+        return Err(corrupted_archive(
+            archive_path,
+            format!("expected entry {}, found {}", name, entry_path.display()),
+        ));
+    }
+    let mut contents = String::new();
+    entry
+        .read_to_string(&mut contents)
+        .map_err(|err| io_error(archive_path, "failed to read archive entry", err))?;
+    Ok(contents.trim().to_string())
+}
+
+/// Writes the canister stored in the archive at `archive_path` into the
+/// checkpoint `checkpoint_layout`, replacing any existing state of a
+/// canister with the same ID. Returns the ID of the imported canister.
+///
+/// The checkpoint is typically a scratchpad obtained via
+/// `StateLayout::checkpoint_to_scratchpad`, that is promoted to a proper
+/// checkpoint with `StateLayout::scratchpad_to_checkpoint` afterwards.
+pub fn import_canister(
+    archive_path: &Path,
+    checkpoint_layout: &CheckpointLayout<RwPolicy>,
+) -> Result<CanisterId, CheckpointError> {
+    let file = File::open(archive_path)
+        .map_err(|err| io_error(archive_path, "failed to open archive", err))?;
+    let mut archive = tar::Archive::new(file);
+    let mut entries = archive
+        .entries()
+        .map_err(|err| io_error(archive_path, "failed to read archive", err))?;
+
+    let version = read_string_entry(&mut entries, VERSION_ENTRY, archive_path)?;
+    if version != CANISTER_ARCHIVE_VERSION.to_string() {
+        return Err(corrupted_archive(
+            archive_path,
+            format!(
+                "unsupported archive version {}, expected {}",
+                version, CANISTER_ARCHIVE_VERSION
+            ),
+        ));
+    }
+    let canister_id = read_string_entry(&mut entries, CANISTER_ID_ENTRY, archive_path)?;
+    let canister_id = PrincipalId::from_str(&canister_id)
+        .ok()
+        .and_then(|principal_id| CanisterId::new(principal_id).ok())
+        .ok_or_else(|| {
+            corrupted_archive(archive_path, format!("invalid canister ID {}", canister_id))
+        })?;
+
+    let canister_root = checkpoint_layout.canister(&canister_id)?.raw_path();
+    std::fs::remove_dir_all(&canister_root)
+        .map_err(|err| io_error(&canister_root, "failed to remove existing canister", err))?;
+    let canister_layout = checkpoint_layout.canister(&canister_id)?;
+
+    for entry in entries {
+        let mut entry =
+            entry.map_err(|err| io_error(archive_path, "failed to read archive entry", err))?;
+        let name = entry
+            .path()
+            .map_err(|err| io_error(archive_path, "failed to read archive entry", err))?
+            .to_str()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        if !CANISTER_FILES.contains(&name.as_str()) && !is_wasm_chunk_entry(&name) {
+            return Err(corrupted_archive(
+                archive_path,
+                format!("unexpected entry {}", name),
+            ));
+        }
+        let path = canister_layout.raw_path().join(&name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| io_error(parent, "failed to create directory", err))?;
+        }
+        entry
+            .unpack(&path)
+            .map_err(|err| io_error(&path, "failed to unpack archive entry", err))?;
+    }
+
+    validate_canister(&canister_layout, &canister_id)?;
+    Ok(canister_id)
+}
+
+/// Returns true if `name` is the archive entry of a Wasm chunk, i.e.
+/// `wasm_chunk_store/<hex(chunk_hash)>`.
+fn is_wasm_chunk_entry(name: &str) -> bool {
+    match name.split_once('/') {
+        Some((dir, hash)) => {
+            dir == WASM_CHUNK_STORE_DIR
+                && hash.len() == 64
+                && hash.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// Checks that the imported `CanisterStateBits` can be decoded.
+fn validate_canister(
+    canister_layout: &CanisterLayout<RwPolicy>,
+    canister_id: &CanisterId,
+) -> Result<(), CheckpointError> {
+    CanisterStateBits::try_from(canister_layout.canister().deserialize()?)
+        .map(|_| ())
+        .map_err(|err| CheckpointError::ProtoError {
+            path: canister_layout.raw_path(),
+            field: format!("canister_states[{}]::canister_state_bits", canister_id),
+            proto_err: err.to_string(),
+        })
+}
+
+/// Returns the height of the latest checkpoint in `state_layout`.
+fn latest_checkpoint_height(state_layout: &StateLayout) -> Result<Height, CheckpointError> {
+    state_layout
+        .checkpoint_heights()?
+        .last()
+        .cloned()
+        .ok_or(CheckpointError::NotFound(Height::new(0)))
+}
+
+/// Writes the state of canister `canister_id` persisted in the latest
+/// checkpoint of `state_layout` to a new archive at `archive_path`. Returns
+/// the height of that checkpoint.
+///
+/// This is the source subnet's side of a canister migration: from the batch
+/// at `reroute_height` on, the canister's range is routed to another subnet
+/// and Message Routing no longer executes the canister, so a checkpoint at or
+/// above that height holds its final state on the source subnet. Fails with
+/// `CheckpointError::NotFound(reroute_height)` if there is no such checkpoint
+/// yet.
+pub fn export_canister_from_latest_checkpoint(
+    state_layout: &StateLayout,
+    canister_id: &CanisterId,
+    reroute_height: Height,
+    archive_path: &Path,
+) -> Result<Height, CheckpointError> {
+    let height = latest_checkpoint_height(state_layout)?;
+    if height < reroute_height {
+        return Err(CheckpointError::NotFound(reroute_height));
+    }
+    export_canister(&state_layout.checkpoint(height)?, canister_id, archive_path)?;
+    Ok(height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        checkpoint::{load_checkpoint, make_checkpoint},
+        CheckpointMetrics, NUMBER_OF_CHECKPOINT_THREADS,
+    };
+    use ic_base_types::NumSeconds;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_replicated_state::{
+        canister_state::execution_state::{WasmBinary, WasmMetadata},
+        page_map::PageMap,
+        ExecutionState, ExportedFunctions, Memory, NumWasmPages, ReplicatedState,
+    };
+    use ic_state_layout::StateLayout;
+    use ic_test_utilities::{
+        state::{canister_ids, new_canister_state},
+        types::ids::{canister_test_id, subnet_test_id, user_test_id},
+        with_test_replica_logger,
+    };
+    use ic_types::{Cycles, ExecutionRound, Height};
+    use ic_wasm_types::BinaryEncodedWasm;
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use tempfile::Builder;
+
+    fn checkpoint(state: &ReplicatedState, height: Height, layout: &StateLayout) {
+        make_checkpoint(
+            state,
+            height,
+            layout,
+            &CheckpointMetrics::new(&ic_metrics::MetricsRegistry::new()),
+            &mut scoped_threadpool::Pool::new(NUMBER_OF_CHECKPOINT_THREADS),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn can_export_and_import_a_canister() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let canister_id = canister_test_id(10);
+
+            // A checkpoint holding a canister with some heap and stable memory
+            // and a Wasm chunk.
+            let src_root = tmp.path().join("src");
+            let src_layout = StateLayout::new(log.clone(), src_root.clone());
+            let mut canister_state = new_canister_state(
+                canister_id,
+                user_test_id(24).get(),
+                Cycles::new(1 << 36),
+                NumSeconds::from(100_000),
+            );
+            let chunk_hash = canister_state
+                .system_state
+                .wasm_chunk_store
+                .insert_chunk(vec![7, 8, 9])
+                .unwrap();
+            let canister_root = src_layout
+                .tip()
+                .unwrap()
+                .canister(&canister_id)
+                .unwrap()
+                .raw_path();
+            let wasm_memory = Memory::new(PageMap::from(&[1, 2, 3][..]), NumWasmPages::new(1));
+            let stable_memory = Memory::new(PageMap::from(&[4, 5, 6][..]), NumWasmPages::new(1));
+            canister_state.execution_state = Some(ExecutionState {
+                canister_root: src_root.clone(),
+                session_nonce: None,
+                wasm_binary: WasmBinary::new(BinaryEncodedWasm::new(vec![
+                    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+                ])),
+                wasm_memory: wasm_memory.clone(),
+                stable_memory: stable_memory.clone(),
+                exported_globals: vec![],
+                exports: ExportedFunctions::new(BTreeSet::new()),
+                metadata: WasmMetadata::default(),
+                last_executed_round: ExecutionRound::from(0),
+                cow_mem_mgr: Arc::new(ic_cow_state::CowMemoryManagerImpl::open_readwrite(
+                    canister_root,
+                )),
+                mapped_state: None,
+            });
+            let mut state = ReplicatedState::new_rooted_at(
+                subnet_test_id(1),
+                SubnetType::Application,
+                src_root,
+            );
+            state.put_canister_state(canister_state);
+            checkpoint(&state, Height::new(1), &src_layout);
+
+            let archive = tmp.path().join("canister.tar");
+            export_canister(
+                &src_layout.checkpoint(Height::new(1)).unwrap(),
+                &canister_id,
+                &archive,
+            )
+            .unwrap();
+
+            // An empty checkpoint to import the canister into.
+            let dst_root = tmp.path().join("dst");
+            let dst_layout = StateLayout::new(log, dst_root.clone());
+            let state = ReplicatedState::new_rooted_at(
+                subnet_test_id(1),
+                SubnetType::Application,
+                dst_root,
+            );
+            checkpoint(&state, Height::new(1), &dst_layout);
+
+            let scratchpad = dst_layout.checkpoint_to_scratchpad(Height::new(1)).unwrap();
+            assert_eq!(import_canister(&archive, &scratchpad).unwrap(), canister_id);
+            let imported = dst_layout
+                .scratchpad_to_checkpoint(scratchpad, Height::new(2))
+                .unwrap();
+
+            let state = load_checkpoint(&imported, SubnetType::Application, None).unwrap();
+            assert_eq!(canister_ids(&state), vec![canister_id]);
+            let canister_state = state.canister_state(&canister_id).unwrap();
+            let execution_state = canister_state.execution_state.as_ref().unwrap();
+            assert_eq!(execution_state.wasm_memory, wasm_memory);
+            assert_eq!(execution_state.stable_memory, stable_memory);
+            assert_eq!(
+                canister_state
+                    .system_state
+                    .wasm_chunk_store
+                    .get_chunk(&chunk_hash),
+                Some(&[7, 8, 9][..])
+            );
+        });
+    }
+
+    #[test]
+    fn exports_from_latest_checkpoint_at_or_above_reroute_height() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let canister_id = canister_test_id(10);
+
+            let src_root = tmp.path().join("src");
+            let src_layout = StateLayout::new(log.clone(), src_root.clone());
+            let archive = tmp.path().join("canister.tar");
+            assert!(matches!(
+                export_canister_from_latest_checkpoint(
+                    &src_layout,
+                    &canister_id,
+                    Height::new(0),
+                    &archive
+                ),
+                Err(CheckpointError::NotFound(_))
+            ));
+
+            let mut state = ReplicatedState::new_rooted_at(
+                subnet_test_id(1),
+                SubnetType::Application,
+                src_root,
+            );
+            state.put_canister_state(new_canister_state(
+                canister_id,
+                user_test_id(24).get(),
+                Cycles::new(1 << 36),
+                NumSeconds::from(100_000),
+            ));
+            checkpoint(&state, Height::new(1), &src_layout);
+            checkpoint(&state, Height::new(2), &src_layout);
+
+            // The latest checkpoint predates the reroute.
+            assert!(matches!(
+                export_canister_from_latest_checkpoint(
+                    &src_layout,
+                    &canister_id,
+                    Height::new(3),
+                    &archive
+                ),
+                Err(CheckpointError::NotFound(height)) if height == Height::new(3)
+            ));
+            assert!(!archive.exists());
+
+            assert_eq!(
+                export_canister_from_latest_checkpoint(
+                    &src_layout,
+                    &canister_id,
+                    Height::new(2),
+                    &archive
+                )
+                .unwrap(),
+                Height::new(2)
+            );
+
+            let dst_root = tmp.path().join("dst");
+            let dst_layout = StateLayout::new(log, dst_root.clone());
+            let state = ReplicatedState::new_rooted_at(
+                subnet_test_id(2),
+                SubnetType::Application,
+                dst_root,
+            );
+            checkpoint(&state, Height::new(5), &dst_layout);
+            let scratchpad = dst_layout.checkpoint_to_scratchpad(Height::new(5)).unwrap();
+            assert_eq!(import_canister(&archive, &scratchpad).unwrap(), canister_id);
+            let state = load_checkpoint(&scratchpad, SubnetType::Application, None).unwrap();
+            assert_eq!(canister_ids(&state), vec![canister_id]);
+            assert_eq!(state.metadata.own_subnet_id, subnet_test_id(2));
+        });
+    }
+
+    #[test]
+    fn import_rejects_unknown_archive_version() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let archive = tmp.path().join("canister.tar");
+            let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(1);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, VERSION_ENTRY, &b"9"[..])
+                .unwrap();
+            builder.into_inner().unwrap();
+
+            let layout = StateLayout::new(log, tmp.path().join("state"));
+            let state = ReplicatedState::new_rooted_at(
+                subnet_test_id(1),
+                SubnetType::Application,
+                tmp.path().join("state"),
+            );
+            checkpoint(&state, Height::new(1), &layout);
+            let scratchpad = layout.checkpoint_to_scratchpad(Height::new(1)).unwrap();
+
+            assert!(matches!(
+                import_canister(&archive, &scratchpad),
+                Err(CheckpointError::CorruptedLayout { .. })
+            ));
+        });
+    }
+}
@@ -4,20 +4,28 @@ use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::Memory;
 use ic_replicated_state::{
-    canister_state::execution_state::WasmBinary, page_map::PageMap, CanisterMetrics, CanisterState,
-    ExecutionState, NumWasmPages, ReplicatedState, SchedulerState, SystemState,
+    bitcoin_state::BitcoinState,
+    canister_state::execution_state::WasmBinary,
+    page_map::{self, PageMap},
+    CanisterMetrics, CanisterSnapshot, CanisterSnapshots, CanisterState, ExecutionState,
+    NumWasmPages, ReplicatedState, SchedulerState, SystemState, WasmChunkHash, WasmChunkStore,
 };
 use ic_state_layout::{
-    CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadPolicy, ReadWritePolicy, RwPolicy,
-    StateLayout,
+    CanisterSnapshotBits, CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadPolicy,
+    ReadWritePolicy, RwPolicy, StateLayout,
 };
-use ic_types::Height;
+use ic_types::{ExecutionRound, Height, SnapshotId};
 use ic_utils::ic_features::*;
 use ic_utils::thread::parallel_map;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{From, TryFrom};
+use std::path::Path;
 use std::sync::Arc;
 
+/// The overlays of a heap file are merged into it once there are more than
+/// this many of them, to bound the cost of loading the file.
+const MAX_OVERLAYS_PER_FILE: usize = 16;
+
 /// Creates a checkpoint of the node state using specified directory
 /// layout. Returns a new state that is equivalent the to given one
 /// and a result of the operation.
@@ -28,6 +36,12 @@ use std::sync::Arc;
 /// If the result is `Ok`, the returned state is "rebased" to use
 /// files from the newly created checkpoint. If the result is `Err`,
 /// the returned state is exactly the one that was passed as argument.
+///
+/// Only the pages that changed since the previous checkpoint are written, as
+/// overlay files on top of the heap files in the tip. The overlays are not
+/// merged here, so as not to delay the checkpoint: the state manager merges
+/// them in the background once the checkpoint is published, see
+/// `merge_overlays_in_tip()`.
 pub fn make_checkpoint(
     state: &ReplicatedState,
     height: Height,
@@ -42,7 +56,7 @@ pub fn make_checkpoint(
             .step_duration
             .with_label_values(&["serialize_to_tip"])
             .start_timer();
-        serialize_to_tip(state, &tip, thread_pool)?;
+        serialize_to_tip(state, layout, &tip, height, thread_pool)?;
     }
 
     let cp = {
@@ -64,9 +78,82 @@ pub fn make_checkpoint(
     Ok(state)
 }
 
+/// Returns true if the overlays of the heap file `base` should be merged
+/// into it: either there are more than `MAX_OVERLAYS_PER_FILE` of them, or
+/// together they are larger than the heap file itself.
+fn should_merge_overlays(base: &Path) -> Result<bool, CheckpointError> {
+    let overlays = page_map::overlay_paths(base)?;
+    if overlays.is_empty() {
+        return Ok(false);
+    }
+    if overlays.len() > MAX_OVERLAYS_PER_FILE {
+        return Ok(true);
+    }
+    let file_size = |path: &Path| {
+        path.metadata()
+            .map(|metadata| metadata.len())
+            .map_err(|err| CheckpointError::IoError {
+                path: path.to_path_buf(),
+                message: "failed to get metadata".to_string(),
+                io_err: err.to_string(),
+            })
+    };
+    let mut overlays_size = 0;
+    for overlay in overlays.iter() {
+        overlays_size += file_size(overlay)?;
+    }
+    Ok(overlays_size > file_size(base)?)
+}
+
+/// Merges the overlays of the heap files in the tip that satisfy
+/// `should_merge_overlays()`. The tip must already have been copied to a
+/// checkpoint: the merged files are new files that will only be picked up by
+/// the next checkpoint, while the existing checkpoints keep the old ones.
+pub(crate) fn merge_overlays_in_tip(
+    layout: &StateLayout,
+    metrics: &CheckpointMetrics,
+    thread_pool: &mut scoped_threadpool::Pool,
+) -> Result<(), CheckpointError> {
+    let _timer = metrics
+        .step_duration
+        .with_label_values(&["merge_overlays"])
+        .start_timer();
+    let tip = layout.tip()?;
+    let canister_ids = tip.canister_ids()?;
+    let results = parallel_map(
+        thread_pool,
+        canister_ids.iter(),
+        |canister_id| -> Result<(), CheckpointError> {
+            let canister_layout = tip.canister(canister_id)?;
+            for base in [
+                canister_layout.vmemory_0(),
+                canister_layout.stable_memory_blob(),
+            ]
+            .iter()
+            {
+                if base.exists() && should_merge_overlays(base)? {
+                    page_map::merge_overlays(base)?;
+                }
+            }
+            Ok(())
+        },
+    );
+    results.into_iter().collect::<Result<(), _>>()?;
+
+    if tip.raw_path().join("bitcoin").exists() {
+        let utxos = tip.bitcoin()?.utxos();
+        if utxos.exists() && should_merge_overlays(&utxos)? {
+            page_map::merge_overlays(&utxos)?;
+        }
+    }
+    Ok(())
+}
+
 fn serialize_to_tip(
     state: &ReplicatedState,
+    layout: &StateLayout,
     tip: &CheckpointLayout<RwPolicy>,
+    height: Height,
     thread_pool: &mut scoped_threadpool::Pool,
 ) -> Result<(), CheckpointError> {
     tip.system_metadata()
@@ -76,37 +163,262 @@ fn serialize_to_tip(
         .serialize((state.subnet_queues()).into())?;
 
     let results = parallel_map(thread_pool, state.canisters_iter(), |canister_state| {
-        serialize_canister_to_tip(canister_state, tip)
+        serialize_canister_to_tip(canister_state, layout, tip, height)
     });
 
     for result in results.into_iter() {
         result?;
     }
+
+    serialize_snapshots_to_tip(&state.canister_snapshots, tip)?;
+
+    serialize_bitcoin_state_to_tip(state.bitcoin.as_ref(), layout, tip, height)
+}
+
+/// Writes the Bitcoin state to the tip, or removes it from the tip if the
+/// Bitcoin feature is not enabled (anymore).
+fn serialize_bitcoin_state_to_tip(
+    bitcoin: Option<&BitcoinState>,
+    layout: &StateLayout,
+    tip: &CheckpointLayout<RwPolicy>,
+    height: Height,
+) -> Result<(), CheckpointError> {
+    let bitcoin = match bitcoin {
+        Some(bitcoin) => bitcoin,
+        None => {
+            let bitcoin_root = tip.raw_path().join("bitcoin");
+            if bitcoin_root.exists() {
+                std::fs::remove_dir_all(&bitcoin_root).map_err(|err| CheckpointError::IoError {
+                    path: bitcoin_root,
+                    message: "failed to remove the Bitcoin state".to_string(),
+                    io_err: err.to_string(),
+                })?;
+            }
+            return Ok(());
+        }
+    };
+    let bitcoin_layout = tip.bitcoin()?;
+    bitcoin_layout.bitcoin_state().serialize(bitcoin.into())?;
+    persist_page_map_to_tip(
+        bitcoin.utxos.page_map(),
+        layout,
+        tip,
+        &bitcoin_layout.utxos(),
+        height,
+    )
+}
+
+fn serialize_snapshots_to_tip(
+    snapshots: &CanisterSnapshots,
+    tip: &CheckpointLayout<RwPolicy>,
+) -> Result<(), CheckpointError> {
+    // Snapshots that were deleted since the last checkpoint must not be
+    // carried over.
+    for snapshot_id in tip.snapshot_ids()? {
+        if snapshots.get(snapshot_id).is_none() {
+            tip.snapshot(&snapshot_id)?.mark_deleted()?;
+        }
+    }
+
+    for (snapshot_id, snapshot) in snapshots.iter() {
+        let snapshot_layout = tip.snapshot(snapshot_id)?;
+        // Snapshots are immutable, so one that was already persisted by a
+        // previous checkpoint does not need to be written again.
+        if snapshot_layout.snapshot().raw_path().exists() {
+            continue;
+        }
+        snapshot_layout
+            .wasm()
+            .serialize(&snapshot.wasm_binary.binary)?;
+        // The snapshot directory does not contain a copy of the checkpoint
+        // files backing the page maps, so all pages have to be written.
+        snapshot
+            .wasm_memory
+            .page_map
+            .persist_and_sync_all(&snapshot_layout.vmemory_0())?;
+        snapshot
+            .stable_memory
+            .page_map
+            .persist_and_sync_all(&snapshot_layout.stable_memory_blob())?;
+        snapshot_layout.snapshot().serialize(
+            CanisterSnapshotBits {
+                canister_id: snapshot.canister_id,
+                taken_at_timestamp: snapshot.taken_at_timestamp,
+                execution_state_bits: ExecutionStateBits {
+                    exported_globals: snapshot.exported_globals.clone(),
+                    heap_size: snapshot.wasm_memory.size,
+                    exports: snapshot.exports.clone(),
+                    last_executed_round: ExecutionRound::from(0),
+                    metadata: snapshot.metadata.clone(),
+                },
+                stable_memory_size: snapshot.stable_memory.size,
+                certified_data: snapshot.certified_data.clone(),
+            }
+            .into(),
+        )?;
+    }
     Ok(())
 }
 
+/// Returns true if `page_map` is backed by the heap file at `relative_path`,
+/// either in the tip or in the checkpoint at the page map's `base_height`,
+/// which the tip was last copied to (or reset from). Only then does the page
+/// map differ from the heap file in the tip by just its delta.
+pub(crate) fn is_backed_by_heap_file(
+    page_map: &PageMap,
+    layout: &StateLayout,
+    relative_path: &Path,
+) -> bool {
+    match (page_map.base_height, page_map.heap_file()) {
+        (Some(base_height), Some(heap_file)) => {
+            heap_file == layout.tip_path().join(relative_path)
+                || heap_file == layout.checkpoint_path(base_height).join(relative_path)
+        }
+        _ => false,
+    }
+}
+
+/// Persists `page_map` to the heap file `base` in `tip`. If the page map is
+/// backed by the same heap file (see `is_backed_by_heap_file()`), only its
+/// delta is written, as an overlay for `height`. Otherwise, e.g. for a new
+/// canister or for a page map backed by the heap file of another canister,
+/// the heap file is replaced with the full contents.
+fn persist_page_map_to_tip(
+    page_map: &PageMap,
+    layout: &StateLayout,
+    tip: &CheckpointLayout<RwPolicy>,
+    base: &Path,
+    height: Height,
+) -> Result<(), CheckpointError> {
+    let backed_by_base = base
+        .strip_prefix(tip.raw_path())
+        .map(|relative_path| is_backed_by_heap_file(page_map, layout, relative_path))
+        .unwrap_or(false);
+    if backed_by_base && base.exists() {
+        page_map.persist_delta_as_overlay(base, height)?;
+    } else {
+        page_map.persist_and_replace(base)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn io_error(path: &Path, message: &str, err: std::io::Error) -> CheckpointError {
+    CheckpointError::IoError {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+        io_err: err.to_string(),
+    }
+}
+
+/// Brings the Wasm chunk store directory `dir` of a canister in the tip in
+/// line with `store`. Chunks are immutable and named after their hash, so
+/// only the files of new chunks are written and the files of chunks that are
+/// no longer in the store are removed.
+fn serialize_wasm_chunk_store_to_tip(
+    store: &WasmChunkStore,
+    dir: &Path,
+) -> Result<(), CheckpointError> {
+    if store.is_empty() {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)
+                .map_err(|err| io_error(dir, "failed to remove the Wasm chunk store", err))?;
+        }
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|err| io_error(dir, "failed to create the Wasm chunk store", err))?;
+
+    let mut persisted = BTreeSet::new();
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| io_error(dir, "failed to list the Wasm chunk store", err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| io_error(dir, "failed to list the Wasm chunk store", err))?
+            .path();
+        let hash = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| hex::decode(name).ok())
+            .and_then(|hash| <WasmChunkHash>::try_from(hash.as_slice()).ok())
+            .filter(|hash| store.get_chunk(hash).is_some());
+        match hash {
+            Some(hash) => {
+                persisted.insert(hash);
+            }
+            None => std::fs::remove_file(&path)
+                .map_err(|err| io_error(&path, "failed to remove a Wasm chunk", err))?,
+        }
+    }
+
+    for hash in store.keys().filter(|hash| !persisted.contains(*hash)) {
+        let path = dir.join(hex::encode(hash));
+        let chunk = store.get_chunk(hash).unwrap();
+        std::fs::write(&path, chunk)
+            .map_err(|err| io_error(&path, "failed to write a Wasm chunk", err))?;
+    }
+    Ok(())
+}
+
+/// Loads the Wasm chunk store of a canister from the directory `dir` written
+/// by `serialize_wasm_chunk_store_to_tip()`. The hashes of the chunks are
+/// recomputed from their contents.
+fn load_wasm_chunk_store(dir: &Path) -> Result<WasmChunkStore, CheckpointError> {
+    let mut store = WasmChunkStore::default();
+    if !dir.exists() {
+        return Ok(store);
+    }
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| io_error(dir, "failed to list the Wasm chunk store", err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| io_error(dir, "failed to list the Wasm chunk store", err))?
+            .path();
+        let chunk = std::fs::read(&path)
+            .map_err(|err| io_error(&path, "failed to read a Wasm chunk", err))?;
+        store
+            .insert_chunk(chunk)
+            .map_err(|err| CheckpointError::CorruptedLayout {
+                path: path.clone(),
+                message: format!("invalid Wasm chunk: {:?}", err),
+            })?;
+    }
+    Ok(store)
+}
+
 fn serialize_canister_to_tip(
     canister_state: &CanisterState,
+    layout: &StateLayout,
     tip: &CheckpointLayout<RwPolicy>,
+    height: Height,
 ) -> Result<(), CheckpointError> {
     let canister_layout = tip.canister(&canister_state.canister_id())?;
     canister_layout
         .queues()
         .serialize(canister_state.system_state.queues().into())?;
+    serialize_wasm_chunk_store_to_tip(
+        &canister_state.system_state.wasm_chunk_store,
+        &canister_layout.wasm_chunk_store(),
+    )?;
 
     let execution_state_bits = match &canister_state.execution_state {
         Some(execution_state) => {
             canister_layout
                 .wasm()
                 .serialize(&execution_state.wasm_binary.binary)?;
-            execution_state
-                .wasm_memory
-                .page_map
-                .persist_and_sync_delta(&canister_layout.vmemory_0())?;
-            execution_state
-                .stable_memory
-                .page_map
-                .persist_and_sync_delta(&canister_layout.stable_memory_blob())?;
+            persist_page_map_to_tip(
+                &execution_state.wasm_memory.page_map,
+                layout,
+                tip,
+                &canister_layout.vmemory_0(),
+                height,
+            )?;
+            persist_page_map_to_tip(
+                &execution_state.stable_memory.page_map,
+                layout,
+                tip,
+                &canister_layout.stable_memory_blob(),
+                height,
+            )?;
 
             execution_state.cow_mem_mgr.checkpoint();
 
@@ -158,6 +470,12 @@ fn serialize_canister_to_tip(
                     .map(|es| es.stable_memory.size)
                     .unwrap_or_else(|| NumWasmPages::from(0)),
                 heap_delta_debit: canister_state.scheduler_state.heap_delta_debit,
+                global_timer: canister_state.system_state.global_timer,
+                canister_log: canister_state.system_state.canister_log.clone(),
+                log_visibility: canister_state.system_state.log_visibility,
+                canister_history: canister_state.system_state.canister_history.clone(),
+                canister_version: canister_state.system_state.canister_version,
+                wasm_memory_limit: canister_state.system_state.wasm_memory_limit,
             }
             .into(),
         )
@@ -211,12 +529,32 @@ pub fn load_checkpoint<P: ReadPolicy + Send + Sync>(
         }
     }
 
+    let mut canister_snapshots = CanisterSnapshots::default();
+    for snapshot_id in checkpoint_layout.snapshot_ids()? {
+        let snapshot = load_canister_snapshot_from_checkpoint(checkpoint_layout, &snapshot_id)?;
+        canister_snapshots.insert(snapshot_id, Arc::new(snapshot));
+    }
+
+    let bitcoin_layout = checkpoint_layout.bitcoin()?;
+    let bitcoin = match bitcoin_layout.bitcoin_state().deserialize_opt()? {
+        Some(bitcoin_state) => {
+            let utxos = PageMap::open(&bitcoin_layout.utxos(), Some(checkpoint_layout.height()))?;
+            Some(
+                BitcoinState::try_from((bitcoin_state, utxos))
+                    .map_err(|err| into_checkpoint_error("BitcoinState".into(), err))?,
+            )
+        }
+        None => None,
+    };
+
     let state = ReplicatedState::new_from_checkpoint(
         canister_states,
         metadata,
         subnet_queues,
         // Consensus queue needs to be empty at the end of every round.
         Vec::new(),
+        canister_snapshots,
+        bitcoin,
         checkpoint_layout.raw_path().into(),
     );
 
@@ -304,6 +642,13 @@ fn load_canister_state_from_checkpoint<P: ReadPolicy>(
         canister_state_bits.freeze_threshold,
         canister_state_bits.status,
         canister_state_bits.certified_data,
+        canister_state_bits.global_timer,
+        load_wasm_chunk_store(&canister_layout.wasm_chunk_store())?,
+        canister_state_bits.canister_log,
+        canister_state_bits.log_visibility,
+        canister_state_bits.canister_history,
+        canister_state_bits.canister_version,
+        canister_state_bits.wasm_memory_limit,
         canister_metrics,
         canister_state_bits.cycles_balance,
     );
@@ -320,6 +665,41 @@ fn load_canister_state_from_checkpoint<P: ReadPolicy>(
     })
 }
 
+fn load_canister_snapshot_from_checkpoint<P: ReadPolicy>(
+    checkpoint_layout: &CheckpointLayout<P>,
+    snapshot_id: &SnapshotId,
+) -> Result<CanisterSnapshot, CheckpointError> {
+    let snapshot_layout = checkpoint_layout.snapshot(snapshot_id)?;
+    let snapshot_bits = CanisterSnapshotBits::try_from(snapshot_layout.snapshot().deserialize()?)
+        .map_err(|err| CheckpointError::ProtoError {
+        path: checkpoint_layout.raw_path().into(),
+        field: format!("snapshots[{}]::snapshot_bits", snapshot_id),
+        proto_err: err.to_string(),
+    })?;
+    let execution_state_bits = snapshot_bits.execution_state_bits;
+
+    // The page maps are not tracked as deltas of any checkpoint (hence no base
+    // height): the snapshot files are immutable and are only ever copied in
+    // full, see `serialize_snapshots_to_tip()`.
+    Ok(CanisterSnapshot {
+        canister_id: snapshot_bits.canister_id,
+        taken_at_timestamp: snapshot_bits.taken_at_timestamp,
+        wasm_binary: WasmBinary::new(snapshot_layout.wasm().deserialize()?),
+        wasm_memory: Memory::new(
+            PageMap::open(&snapshot_layout.vmemory_0(), None)?,
+            execution_state_bits.heap_size,
+        ),
+        stable_memory: Memory::new(
+            PageMap::open(&snapshot_layout.stable_memory_blob(), None)?,
+            snapshot_bits.stable_memory_size,
+        ),
+        exported_globals: execution_state_bits.exported_globals,
+        exports: execution_state_bits.exports,
+        metadata: execution_state_bits.metadata,
+        certified_data: snapshot_bits.certified_data,
+    })
+}
+
 pub fn handle_disk_format_changes<P: ReadWritePolicy>(
     layout: &CheckpointLayout<P>,
     state: &ReplicatedState,
@@ -364,6 +744,9 @@ pub fn handle_disk_format_changes<P: ReadWritePolicy>(
                     message: "Failed to overwrite file".to_string(),
                     io_err: err.to_string(),
                 })?;
+                // The heap file now holds the full contents, so the overlays
+                // copied from the checkpoint no longer apply.
+                page_map::remove_overlays(memory_path)?;
 
                 CowMemoryManagerImpl::purge(&canister_base);
 
@@ -416,6 +799,7 @@ mod tests {
     };
     use ic_types::messages::StopCanisterContext;
     use ic_types::{CanisterId, CanisterStatusType, Cycles, ExecutionRound, Height};
+    use ic_types::{SnapshotId, Time};
     use ic_wasm_types::BinaryEncodedWasm;
     use std::collections::BTreeSet;
     use tempfile::Builder;
@@ -666,6 +1050,279 @@ mod tests {
         });
     }
 
+    #[test]
+    fn persists_heap_deltas_as_overlays() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let root = tmp.path().to_path_buf();
+            let layout = StateLayout::new(log, root.clone());
+
+            let canister_id: CanisterId = canister_test_id(10);
+            let can_layout = layout.tip().unwrap().canister(&canister_id).unwrap();
+
+            let mut canister_state = new_canister_state(
+                canister_id,
+                user_test_id(24).get(),
+                INITIAL_CYCLES,
+                NumSeconds::from(100_000),
+            );
+            canister_state.execution_state = Some(ExecutionState {
+                canister_root: root.clone(),
+                session_nonce: None,
+                wasm_binary: WasmBinary::new(empty_wasm()),
+                wasm_memory: one_page_of(1),
+                stable_memory: Memory::default(),
+                exported_globals: vec![],
+                exports: ExportedFunctions::new(BTreeSet::new()),
+                metadata: WasmMetadata::default(),
+                last_executed_round: ExecutionRound::from(0),
+                cow_mem_mgr: Arc::new(CowMemoryManagerImpl::open_readwrite(can_layout.raw_path())),
+                mapped_state: None,
+            });
+
+            let own_subnet_type = SubnetType::Application;
+            let mut state =
+                ReplicatedState::new_rooted_at(subnet_test_id(1), own_subnet_type, root);
+            state.put_canister_state(canister_state);
+            let mut state = make_checkpoint_and_get_state(&state, Height::new(42), &layout);
+
+            let page = [2; PAGE_SIZE];
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .execution_state
+                .as_mut()
+                .unwrap()
+                .wasm_memory
+                .page_map
+                .update(&[(PageIndex::from(1), &page)]);
+            let _state = make_checkpoint_and_get_state(&state, Height::new(43), &layout);
+
+            let checkpoint = layout.checkpoint(Height::new(43)).unwrap();
+            let vmemory = checkpoint.canister(&canister_id).unwrap().vmemory_0();
+            assert_eq!(
+                page_map::overlay_paths(&vmemory).unwrap(),
+                vec![page_map::overlay_path(&vmemory, Height::new(43))]
+            );
+            assert_eq!(vmemory.metadata().unwrap().len(), PAGE_SIZE as u64);
+
+            let recovered_state =
+                load_checkpoint(&checkpoint, own_subnet_type, Some(&mut thread_pool())).unwrap();
+            let page_map = &recovered_state
+                .canister_state(&canister_id)
+                .unwrap()
+                .execution_state
+                .as_ref()
+                .unwrap()
+                .wasm_memory
+                .page_map;
+            assert_eq!(page_map.get_page(PageIndex::from(0)), &[1; PAGE_SIZE]);
+            assert_eq!(page_map.get_page(PageIndex::from(1)), &[2; PAGE_SIZE]);
+        });
+    }
+
+    #[test]
+    fn rewrites_heap_files_backed_by_another_canister() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let root = tmp.path().to_path_buf();
+            let layout = StateLayout::new(log, root.clone());
+
+            let own_subnet_type = SubnetType::Application;
+            let mut state =
+                ReplicatedState::new_rooted_at(subnet_test_id(1), own_subnet_type, root.clone());
+            for &(canister_id, byte) in
+                [(canister_test_id(10), 1), (canister_test_id(11), 3)].iter()
+            {
+                let can_layout = layout.tip().unwrap().canister(&canister_id).unwrap();
+                let mut canister_state = new_canister_state(
+                    canister_id,
+                    user_test_id(24).get(),
+                    INITIAL_CYCLES,
+                    NumSeconds::from(100_000),
+                );
+                canister_state.execution_state = Some(ExecutionState {
+                    canister_root: root.clone(),
+                    session_nonce: None,
+                    wasm_binary: WasmBinary::new(empty_wasm()),
+                    wasm_memory: one_page_of(byte),
+                    stable_memory: Memory::default(),
+                    exported_globals: vec![],
+                    exports: ExportedFunctions::new(BTreeSet::new()),
+                    metadata: WasmMetadata::default(),
+                    last_executed_round: ExecutionRound::from(0),
+                    cow_mem_mgr: Arc::new(CowMemoryManagerImpl::open_readwrite(
+                        can_layout.raw_path(),
+                    )),
+                    mapped_state: None,
+                });
+                state.put_canister_state(canister_state);
+            }
+            let mut state = make_checkpoint_and_get_state(&state, Height::new(42), &layout);
+
+            // Canister 11 takes over the memory of canister 10, which is backed
+            // by the heap file of canister 10 in checkpoint 42.
+            let wasm_memory = state
+                .canister_state(&canister_test_id(10))
+                .unwrap()
+                .execution_state
+                .as_ref()
+                .unwrap()
+                .wasm_memory
+                .clone();
+            state
+                .canister_state_mut(&canister_test_id(11))
+                .unwrap()
+                .execution_state
+                .as_mut()
+                .unwrap()
+                .wasm_memory = wasm_memory;
+            let _state = make_checkpoint_and_get_state(&state, Height::new(43), &layout);
+
+            let checkpoint = layout.checkpoint(Height::new(43)).unwrap();
+            let vmemory = checkpoint
+                .canister(&canister_test_id(11))
+                .unwrap()
+                .vmemory_0();
+            assert!(page_map::overlay_paths(&vmemory).unwrap().is_empty());
+
+            let recovered_state =
+                load_checkpoint(&checkpoint, own_subnet_type, Some(&mut thread_pool())).unwrap();
+            for canister_id in [canister_test_id(10), canister_test_id(11)].iter() {
+                let page_map = &recovered_state
+                    .canister_state(canister_id)
+                    .unwrap()
+                    .execution_state
+                    .as_ref()
+                    .unwrap()
+                    .wasm_memory
+                    .page_map;
+                assert_eq!(page_map.get_page(PageIndex::from(0)), &[1; PAGE_SIZE]);
+            }
+        });
+    }
+
+    #[test]
+    fn can_recover_canister_snapshots() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let root = tmp.path().to_path_buf();
+            let layout = StateLayout::new(log, root.clone());
+
+            const HEIGHT: Height = Height::new(42);
+            let canister_id: CanisterId = canister_test_id(10);
+            let snapshot_id = SnapshotId::from(7);
+
+            let snapshot = CanisterSnapshot {
+                canister_id,
+                taken_at_timestamp: Time::from_nanos_since_unix_epoch(1234),
+                wasm_binary: WasmBinary::new(empty_wasm()),
+                wasm_memory: one_page_of(1),
+                stable_memory: one_page_of(2),
+                exported_globals: vec![],
+                exports: ExportedFunctions::new(BTreeSet::new()),
+                metadata: WasmMetadata::default(),
+                certified_data: vec![1, 2, 3],
+            };
+
+            let own_subnet_type = SubnetType::Application;
+            let mut state =
+                ReplicatedState::new_rooted_at(subnet_test_id(1), own_subnet_type, root);
+            state
+                .canister_snapshots
+                .insert(snapshot_id, Arc::new(snapshot.clone()));
+            let _state = make_checkpoint_and_get_state(&state, HEIGHT, &layout);
+
+            let checkpoint = layout.checkpoint(HEIGHT).unwrap();
+            assert_eq!(checkpoint.snapshot_ids().unwrap(), vec![snapshot_id]);
+
+            let recovered_state =
+                load_checkpoint(&checkpoint, own_subnet_type, Some(&mut thread_pool())).unwrap();
+            assert_eq!(
+                recovered_state
+                    .canister_snapshots
+                    .get(snapshot_id)
+                    .map(|snapshot| snapshot.as_ref()),
+                Some(&snapshot)
+            );
+
+            // A deleted snapshot does not make it into the next checkpoint.
+            state.canister_snapshots.remove(snapshot_id);
+            let _state = make_checkpoint_and_get_state(&state, HEIGHT.increment(), &layout);
+            let checkpoint = layout.checkpoint(HEIGHT.increment()).unwrap();
+            assert_eq!(checkpoint.snapshot_ids().unwrap(), vec![]);
+        });
+    }
+
+    #[test]
+    fn can_recover_wasm_chunk_store() {
+        with_test_replica_logger(|log| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let root = tmp.path().to_path_buf();
+            let layout = StateLayout::new(log, root.clone());
+
+            const HEIGHT: Height = Height::new(42);
+            let canister_id = canister_test_id(10);
+            let own_subnet_type = SubnetType::Application;
+
+            let mut canister_state = new_canister_state(
+                canister_id,
+                user_test_id(24).get(),
+                INITIAL_CYCLES,
+                NumSeconds::from(100_000),
+            );
+            let store = &mut canister_state.system_state.wasm_chunk_store;
+            let hash_1 = store.insert_chunk(vec![1, 2, 3]).unwrap();
+            let hash_2 = store.insert_chunk(vec![4, 5, 6]).unwrap();
+            let mut state =
+                ReplicatedState::new_rooted_at(subnet_test_id(1), own_subnet_type, root);
+            state.put_canister_state(canister_state);
+            let _state = make_checkpoint_and_get_state(&state, HEIGHT, &layout);
+
+            // The chunks are stored in their own files, not in the canister
+            // state bits.
+            let checkpoint = layout.checkpoint(HEIGHT).unwrap();
+            let chunk_store_dir = checkpoint
+                .canister(&canister_id)
+                .unwrap()
+                .wasm_chunk_store();
+            assert!(chunk_store_dir.join(hex::encode(hash_1)).exists());
+            assert!(chunk_store_dir.join(hex::encode(hash_2)).exists());
+
+            let recovered_state =
+                load_checkpoint(&checkpoint, own_subnet_type, Some(&mut thread_pool())).unwrap();
+            assert_eq!(
+                recovered_state
+                    .canister_state(&canister_id)
+                    .unwrap()
+                    .system_state
+                    .wasm_chunk_store,
+                state
+                    .canister_state(&canister_id)
+                    .unwrap()
+                    .system_state
+                    .wasm_chunk_store
+            );
+
+            // A removed chunk does not make it into the next checkpoint.
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .system_state
+                .wasm_chunk_store
+                .remove_chunk(&hash_1);
+            let _state = make_checkpoint_and_get_state(&state, HEIGHT.increment(), &layout);
+            let chunk_store_dir = layout
+                .checkpoint(HEIGHT.increment())
+                .unwrap()
+                .canister(&canister_id)
+                .unwrap()
+                .wasm_chunk_store();
+            assert!(!chunk_store_dir.join(hex::encode(hash_1)).exists());
+            assert!(chunk_store_dir.join(hex::encode(hash_2)).exists());
+        });
+    }
+
     #[test]
     fn can_recover_an_empty_state() {
         with_test_replica_logger(|log| {
@@ -1,8 +1,10 @@
 // Needs to be `pub` so that the benchmarking code in `state_manager/benches`
 // can access it.
+pub mod canister_archive;
 pub mod checkpoint;
 pub mod labeled_tree_visitor;
 pub mod manifest;
+pub mod split;
 pub mod state_sync;
 pub mod stream_encoding;
 pub mod tree_diff;
@@ -467,6 +469,14 @@ struct ComputeManifestRequest {
     manifest_delta: Option<manifest::ManifestDelta>,
 }
 
+/// Requests handled by the background thread that maintains the tip.
+enum TipRequest {
+    /// Merges the overlays that accumulated in the tip (see
+    /// `checkpoint::merge_overlays_in_tip`), once the checkpoint at `height`
+    /// has been published.
+    MergeOverlays { height: Height },
+}
+
 /// StateSyncRefs keeps track of the ongoing and aborted state syncs.
 #[derive(Clone)]
 pub struct StateSyncRefs {
@@ -563,6 +573,7 @@ pub struct StateManagerImpl {
     own_subnet_id: SubnetId,
     own_subnet_type: SubnetType,
     compute_manifest_request_sender: Sender<ComputeManifestRequest>,
+    tip_request_sender: Sender<TipRequest>,
     deallocation_sender: Sender<Deallocation>,
     // Cached latest state height.  We cache it separately because it's
     // requested quite often and this causes high contention on the lock.
@@ -572,7 +583,12 @@ pub struct StateManagerImpl {
     requested_to_remove_states_below: AtomicU64,
     state_sync_refs: StateSyncRefs,
     checkpoint_thread_pool: Arc<Mutex<scoped_threadpool::Pool>>,
+    // Serializes the operations that modify the tip on disk: writing a
+    // checkpoint, resetting the tip to a checkpoint and merging overlays in
+    // the background.
+    tip_lock: Arc<Mutex<()>>,
     _state_hasher_handle: JoinOnDrop<()>,
+    _tip_handler_handle: JoinOnDrop<()>,
     _deallocation_handle: JoinOnDrop<()>,
 }
 
@@ -701,21 +717,42 @@ fn report_last_diverged_checkpoint(
 pub type DirtyPages = BTreeMap<CanisterId, (Height, Vec<PageIndex>)>;
 
 /// Get dirty pages of canister heap backed by a checkpoint file.
-pub fn get_dirty_pages(state: &ReplicatedState) -> DirtyPages {
+pub fn get_dirty_pages(state: &ReplicatedState, state_layout: &StateLayout) -> DirtyPages {
     let mut dirty_pages: DirtyPages = Default::default();
+    let tip = match state_layout.tip() {
+        Ok(tip) => tip,
+        Err(_) => return dirty_pages,
+    };
     for canister in state.canisters_iter() {
         if let Some(execution_state) = &canister.execution_state {
-            // When `base_height` is None, the page map is not backed by a checkpoint
-            // and we don't know what changed since the checkpoint.
-            if let Some(height) = execution_state.wasm_memory.page_map.base_height {
-                let page_delta_indexes = execution_state
-                    .wasm_memory
-                    .page_map
-                    .get_page_delta_indices();
-                dirty_pages.insert(
-                    canister.system_state.canister_id,
-                    (height, page_delta_indexes),
-                );
+            let page_map = &execution_state.wasm_memory.page_map;
+            // When the page map is not backed by the canister's own heap file
+            // in a checkpoint, we don't know what changed since the checkpoint.
+            let backed_by_heap_file = tip
+                .canister(&canister.canister_id())
+                .ok()
+                .and_then(|canister_layout| {
+                    canister_layout
+                        .vmemory_0()
+                        .strip_prefix(tip.raw_path())
+                        .map(|relative_path| {
+                            checkpoint::is_backed_by_heap_file(
+                                page_map,
+                                state_layout,
+                                relative_path,
+                            )
+                        })
+                        .ok()
+                })
+                .unwrap_or(false);
+            match page_map.base_height {
+                Some(height) if backed_by_heap_file => {
+                    dirty_pages.insert(
+                        canister.system_state.canister_id,
+                        (height, page_map.get_page_delta_indices()),
+                    );
+                }
+                _ => {}
             }
         }
     }
@@ -723,9 +760,8 @@ pub fn get_dirty_pages(state: &ReplicatedState) -> DirtyPages {
 }
 
 /// Strips away the deltas from all page maps of the replicated state.
-/// We execute this procedure before making a checkpoint because we
-/// don't want those deltas to be persisted to TIP as we apply deltas
-/// incrementally after every round.
+/// We execute this procedure after making a checkpoint, once the deltas
+/// have been persisted to TIP as overlay files.
 fn strip_page_map_deltas(state: &mut ReplicatedState) {
     for canister in state.canisters_iter_mut() {
         if let Some(execution_state) = &mut canister.execution_state {
@@ -737,6 +773,9 @@ fn strip_page_map_deltas(state: &mut ReplicatedState) {
             execution_state.stable_memory.sandbox_memory = SandboxMemory::new();
         }
     }
+    if let Some(bitcoin) = &mut state.bitcoin {
+        bitcoin.utxos.page_map_mut().strip_all_deltas();
+    }
 }
 
 /// Switches `tip` to the most recent checkpoint file provided by `src`.
@@ -776,6 +815,21 @@ fn switch_to_checkpoint(tip: &mut ReplicatedState, src: &ReplicatedState) {
             tip_state.stable_memory.sandbox_memory = SandboxMemory::new();
         }
     }
+    // Snapshots are immutable, so the ones loaded from the checkpoint can
+    // replace the ones in `tip` wholesale, dropping any page deltas they
+    // shared with their canisters.
+    tip.canister_snapshots = src.canister_snapshots.clone();
+    assert_eq!(
+        tip.bitcoin.is_some(),
+        src.bitcoin.is_some(),
+        "Bitcoin state unexpectedly (dis)appeared after creating a checkpoint"
+    );
+    if let (Some(tip_bitcoin), Some(src_bitcoin)) = (&mut tip.bitcoin, &src.bitcoin) {
+        tip_bitcoin
+            .utxos
+            .page_map_mut()
+            .switch_to_checkpoint(src_bitcoin.utxos.page_map());
+    }
 }
 
 impl StateManagerImpl {
@@ -965,6 +1019,47 @@ impl StateManagerImpl {
                 .expect("failed to spawn background state hasher"),
         );
 
+        let tip_lock = Arc::new(Mutex::new(()));
+        let (tip_request_sender, tip_request_receiver) = unbounded();
+        let _tip_handler_handle = JoinOnDrop::new(
+            std::thread::Builder::new()
+                .name("TipHandler".to_string())
+                .spawn({
+                    let log = log.clone();
+                    let state_layout = state_layout.clone();
+                    let metrics = metrics.clone();
+                    let tip_lock = Arc::clone(&tip_lock);
+                    move || {
+                        // NB. we don't use the checkpoint thread pool here: waiting for it
+                        // while holding the tip lock could deadlock with the state hasher
+                        // and `take_tip()`.
+                        let mut thread_pool =
+                            scoped_threadpool::Pool::new(NUMBER_OF_CHECKPOINT_THREADS);
+                        while let Ok(req) = tip_request_receiver.recv() {
+                            match req {
+                                TipRequest::MergeOverlays { height } => {
+                                    let _tip_guard = tip_lock.lock().unwrap();
+                                    checkpoint::merge_overlays_in_tip(
+                                        &state_layout,
+                                        &metrics.checkpoint_metrics,
+                                        &mut thread_pool,
+                                    )
+                                    .unwrap_or_else(|err| {
+                                        fatal!(
+                                            log,
+                                            "Failed to merge overlays in tip after checkpoint @{}: {}",
+                                            height,
+                                            err
+                                        )
+                                    });
+                                }
+                            }
+                        }
+                    }
+                })
+                .expect("failed to spawn background tip handler"),
+        );
+
         let (deallocation_sender, deallocation_receiver) = unbounded();
         let _deallocation_handle = JoinOnDrop::new(
             std::thread::Builder::new()
@@ -998,13 +1093,16 @@ impl StateManagerImpl {
             own_subnet_id,
             own_subnet_type,
             compute_manifest_request_sender,
+            tip_request_sender,
             deallocation_sender,
             latest_state_height,
             latest_certified_height,
             requested_to_remove_states_below: AtomicU64::new(oldest_required_state.get()),
             state_sync_refs: StateSyncRefs::new(log),
             checkpoint_thread_pool,
+            tip_lock,
             _state_hasher_handle,
+            _tip_handler_handle,
             _deallocation_handle,
         }
     }
@@ -1461,60 +1559,19 @@ impl StateManagerImpl {
         }
     }
 
-    /// Flushes to disk all the canister heap deltas accumulated in memory
-    /// during one round of execution.
-    fn flush_page_maps(&self, tip_state: &mut ReplicatedState) {
-        let tip_layout = self
-            .state_layout
-            .tip()
-            .unwrap_or_else(|err| fatal!(self.log, "Failed to access @TIP: {}", err));
-
+    /// Discards the round deltas of all the page maps of the state. The page
+    /// deltas themselves are kept in memory until the next checkpoint, which
+    /// writes them to the tip as overlay files, see `make_checkpoint`.
+    fn strip_round_deltas(&self, tip_state: &mut ReplicatedState) {
         for canister in tip_state.canisters_iter_mut() {
-            let canister_id = canister.canister_id();
-
-            let canister_layout = tip_layout.canister(&canister_id).unwrap_or_else(|err| {
-                fatal!(
-                    self.log,
-                    "Failed to access canister {} layout @TIP {}: {}",
-                    canister_id,
-                    tip_layout.raw_path().display(),
-                    err
-                )
-            });
             if let Some(execution_state) = &mut canister.execution_state {
-                let memory_path = &canister_layout.vmemory_0();
-                execution_state
-                    .wasm_memory
-                    .page_map
-                    .persist_round_delta(memory_path)
-                    .unwrap_or_else(|err| {
-                        fatal!(
-                            self.log,
-                            "Failed to persist page delta of canister {} to file {}: {}",
-                            canister_id,
-                            memory_path.display(),
-                            err
-                        )
-                    });
                 execution_state.wasm_memory.page_map.strip_round_delta();
-
-                let stable_memory_path = &canister_layout.stable_memory_blob();
-                execution_state
-                    .stable_memory
-                    .page_map
-                    .persist_round_delta(stable_memory_path)
-                    .unwrap_or_else(|err| {
-                        fatal!(
-                            self.log,
-                            "Failed to persist stable page delta of canister {} to file {}: {}",
-                            canister_id,
-                            stable_memory_path.display(),
-                            err
-                        )
-                    });
                 execution_state.stable_memory.page_map.strip_round_delta();
             }
         }
+        if let Some(bitcoin) = &mut tip_state.bitcoin {
+            bitcoin.utxos.page_map_mut().strip_round_delta();
+        }
     }
 
     fn clone_checkpoint(&self, from: Height, to: Height) -> Result<(), LayoutError> {
@@ -1713,6 +1770,7 @@ impl StateManager for StateManagerImpl {
             // This can happen if state sync fetched a fresh state in the
             // background.
             if *checkpoint_height > tip_height {
+                let _tip_guard = self.tip_lock.lock().unwrap();
                 let new_tip = load_checkpoint_as_tip(
                     &self.log,
                     &self.state_layout,
@@ -2224,19 +2282,17 @@ impl StateManager for StateManagerImpl {
         }
 
         self.populate_extra_metadata(&mut state, height);
-        self.flush_page_maps(&mut state);
+        self.strip_round_deltas(&mut state);
         let mut dirty_pages = None;
         let checkpointed_state = match scope {
             CertificationScope::Full => {
                 let start = Instant::now();
                 if !cow_state_feature::is_enabled(cow_state_feature::cow_state) {
                     // The api of dirty pages is only supported by PageMap currently.
-                    dirty_pages = Some(get_dirty_pages(&state));
+                    dirty_pages = Some(get_dirty_pages(&state, &self.state_layout));
                 }
-                // We don't need to persist the deltas to the tip because we
-                // flush deltas separately every round, see flush_page_maps.
-                strip_page_map_deltas(&mut state);
                 let result = {
+                    let _tip_guard = self.tip_lock.lock().unwrap();
                     let mut thread_pool = self.checkpoint_thread_pool.lock().unwrap();
                     checkpoint::make_checkpoint(
                         &state,
@@ -2246,6 +2302,9 @@ impl StateManager for StateManagerImpl {
                         &mut thread_pool,
                     )
                 };
+                // The deltas have been persisted to the tip as overlays by
+                // make_checkpoint.
+                strip_page_map_deltas(&mut state);
                 purge_cow_rounds_below(&mut state, self.first_known_height());
 
                 let elapsed = start.elapsed();
@@ -2253,6 +2312,9 @@ impl StateManager for StateManagerImpl {
                     Ok(checkpointed_state) => {
                         switch_to_checkpoint(&mut state, &checkpointed_state);
                         info!(self.log, "Created checkpoint @{} in {:?}", height, elapsed);
+                        self.tip_request_sender
+                            .send(TipRequest::MergeOverlays { height })
+                            .expect("failed to send TipRequest");
                         self.metrics
                             .checkpoint_op_duration
                             .with_label_values(&["create"])
@@ -2343,6 +2405,7 @@ impl StateManager for StateManagerImpl {
                     // Will crash if it's not a checkpoint, which is reasonable
                     // for now as we can only get fresher states from state
                     // sync.
+                    let _tip_guard = self.tip_lock.lock().unwrap();
                     (
                         latest_snapshot.height,
                         load_checkpoint_as_tip(
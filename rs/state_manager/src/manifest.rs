@@ -15,7 +15,8 @@ use hash::{
 use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_crypto_sha::Sha256;
 use ic_logger::{error, ReplicaLogger};
-use ic_state_layout::{CheckpointLayout, ReadOnly};
+use ic_replicated_state::page_map::{self, Buffer, PageMap, PersistenceError};
+use ic_state_layout::{utils::do_copy_overwrite, CheckpointLayout, ReadOnly};
 use ic_sys::{mmap::ScopedMmap, PAGE_SIZE};
 use ic_types::{
     state_sync::{ChunkInfo, FileInfo, Manifest},
@@ -23,7 +24,8 @@ use ic_types::{
 };
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
@@ -127,6 +129,77 @@ impl fmt::Display for ChunkValidationError {
 
 impl std::error::Error for ChunkValidationError {}
 
+/// A difference between the manifest recorded for a checkpoint and the one
+/// recomputed from the files on disk, as reported by `compare_manifests`.
+#[derive(Debug, PartialEq)]
+pub enum ManifestMismatch {
+    /// A file of the recorded manifest is missing on disk.
+    MissingFile { relative_path: PathBuf },
+    /// A file on disk is not part of the recorded manifest.
+    UnexpectedFile { relative_path: PathBuf },
+    /// A file has a different size or hash than recorded.
+    FileMismatch {
+        relative_path: PathBuf,
+        expected_size: u64,
+        actual_size: u64,
+        expected_hash: Vec<u8>,
+        actual_hash: Vec<u8>,
+    },
+    /// A chunk of a mismatching file has a different hash than recorded. The
+    /// hash is `None` on the side that doesn't have a chunk at that offset.
+    ChunkMismatch {
+        relative_path: PathBuf,
+        offset: u64,
+        expected_hash: Option<Vec<u8>>,
+        actual_hash: Option<Vec<u8>>,
+    },
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opt_hex = |hash: &Option<Vec<u8>>| match hash {
+            Some(hash) => hex::encode(&hash[..]),
+            None => "none".to_string(),
+        };
+        match self {
+            Self::MissingFile { relative_path } => {
+                write!(f, "file {} is missing", relative_path.display())
+            }
+            Self::UnexpectedFile { relative_path } => {
+                write!(f, "file {} is unexpected", relative_path.display())
+            }
+            Self::FileMismatch {
+                relative_path,
+                expected_size,
+                actual_size,
+                expected_hash,
+                actual_hash,
+            } => write!(
+                f,
+                "file {} mismatch, expected size {} and hash {}, got size {} and hash {}",
+                relative_path.display(),
+                expected_size,
+                hex::encode(&expected_hash[..]),
+                actual_size,
+                hex::encode(&actual_hash[..])
+            ),
+            Self::ChunkMismatch {
+                relative_path,
+                offset,
+                expected_hash,
+                actual_hash,
+            } => write!(
+                f,
+                "chunk of file {} at offset {} hash mismatch, expected {}, got {}",
+                relative_path.display(),
+                offset,
+                opt_hex(expected_hash),
+                opt_hex(actual_hash)
+            ),
+        }
+    }
+}
+
 /// Relative path to a file and the size of the file.
 #[derive(Clone)]
 struct FileWithSize(PathBuf, u64);
@@ -209,6 +282,74 @@ fn uses_chunk_size(manifest: &Manifest, max_chunk_size: u32) -> bool {
     })
 }
 
+/// Read-only view of the logical contents of a checkpoint file. Heap files
+/// with overlays are read through a `PageMap` that applies the overlays,
+/// all the other files are memory-mapped as a whole.
+pub(crate) enum FileContents {
+    Mmap(ScopedMmap),
+    PageMap(PageMap),
+}
+
+fn persistence_to_io_error(err: PersistenceError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+}
+
+impl FileContents {
+    /// Returns true if there are overlay files on top of the file at `path`.
+    pub(crate) fn has_overlays(path: &Path) -> std::io::Result<bool> {
+        page_map::overlay_paths(path)
+            .map(|paths| !paths.is_empty())
+            .map_err(persistence_to_io_error)
+    }
+
+    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+        if !Self::has_overlays(path)? {
+            ScopedMmap::from_path(path).map(FileContents::Mmap)
+        } else {
+            PageMap::open(path, None)
+                .map(FileContents::PageMap)
+                .map_err(persistence_to_io_error)
+        }
+    }
+
+    /// Returns the logical size of the file in bytes.
+    pub(crate) fn size_bytes(&self) -> usize {
+        match self {
+            FileContents::Mmap(mmap) => mmap.len(),
+            FileContents::PageMap(page_map) => page_map.num_host_pages() * PAGE_SIZE,
+        }
+    }
+
+    /// Returns the bytes of the file in the given range, which must not
+    /// exceed `size_bytes()`.
+    pub(crate) fn read(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        match self {
+            FileContents::Mmap(mmap) => Cow::Borrowed(&mmap.as_slice()[range]),
+            FileContents::PageMap(page_map) => {
+                let mut buf = vec![0; range.len()];
+                Buffer::new(page_map.clone()).read(&mut buf, range.start);
+                Cow::Owned(buf)
+            }
+        }
+    }
+
+    /// Replaces the file at `dst` with a plain file holding these contents,
+    /// that were read from the file at `src`.
+    pub(crate) fn copy_to(
+        &self,
+        log: &ReplicaLogger,
+        src: &Path,
+        dst: &Path,
+    ) -> std::io::Result<()> {
+        match self {
+            FileContents::Mmap(_) => do_copy_overwrite(log, src, dst),
+            FileContents::PageMap(page_map) => page_map
+                .persist_and_replace(dst)
+                .map_err(persistence_to_io_error),
+        }
+    }
+}
+
 // Computes file_table and chunk_table of a manifest using a parallel algorithm.
 // All the parallel work is spawned in the specified thread pool.
 fn build_chunk_table_parallel(
@@ -256,7 +397,7 @@ fn build_chunk_table_parallel(
     // and close the corresponding file.
     // This way we keep the number of files opened at the same time
     // low (it doesn't exceed the number of the threads).
-    let file_cache: Arc<Mutex<HashMap<u32, Weak<ScopedMmap>>>> =
+    let file_cache: Arc<Mutex<HashMap<u32, Weak<FileContents>>>> =
         Arc::new(Mutex::new(HashMap::new()));
 
     // Compute real chunk hashes in parallel.
@@ -270,29 +411,28 @@ fn build_chunk_table_parallel(
             let file_cache = Arc::clone(&file_cache);
             scope.execute(move || {
 		let recompute_chunk_hash = || {
-		    let mmap: Arc<ScopedMmap> = if file_size > max_chunk_size as u64 {
+		    let contents: Arc<FileContents> = if file_size > max_chunk_size as u64 {
 			// We only use the file cache if there is more than one chunk in the file,
 			// otherwise the synchronization cost is unnecessary.
 			let mut cache = file_cache.lock().unwrap();
 			match cache.get(&chunk_info.file_index).and_then(Weak::upgrade) {
-                            Some(mmap) => mmap,
+                            Some(contents) => contents,
                             None => {
-				let mmap = Arc::new(
-                                ScopedMmap::from_path(&file_path).expect("failed to open file"),
+				let contents = Arc::new(
+                                FileContents::open(&file_path).expect("failed to open file"),
 				);
-				cache.insert(chunk_info.file_index, Arc::downgrade(&mmap));
-				mmap
+				cache.insert(chunk_info.file_index, Arc::downgrade(&contents));
+				contents
                             }
 			}
                     } else {
-			Arc::new(ScopedMmap::from_path(&file_path).expect("failed to open file"))
+			Arc::new(FileContents::open(&file_path).expect("failed to open file"))
                     };
-                    let data = mmap.as_slice();
 
 		    let mut hasher = chunk_hasher();
                     let chunk_start = chunk_info.offset as usize;
                     let chunk_end = chunk_start + chunk_info.size_bytes as usize;
-                    hasher.write(&data[chunk_start..chunk_end]);
+                    hasher.write(&contents.read(chunk_start..chunk_end));
                     hasher.finish()
                 };
 
@@ -369,7 +509,10 @@ fn build_chunk_table_sequential(
 
         (num_chunks as u32).update_hash(&mut file_hash);
 
-        let compute_file_chunk_hashes = |data: &[u8]| {
+        // Chunks are hashed one at a time, by writing the given range of the
+        // file to the chunk hasher, so that the whole file never needs to be
+        // read into memory at once.
+        let compute_file_chunk_hashes = |write_range: &dyn Fn(&mut Sha256, Range<usize>)| {
             // It's OK to not have any chunks for 0-sized files (though it's unlikely that
             // we have any).
             while bytes_left > 0 {
@@ -382,7 +525,7 @@ fn build_chunk_table_sequential(
                     } else {
                         chunk_hasher()
                     };
-                    hasher.write(&data[offset as usize..(offset + chunk_size) as usize]);
+                    write_range(&mut hasher, offset as usize..(offset + chunk_size) as usize);
                     hasher.finish()
                 };
 
@@ -460,12 +603,11 @@ fn build_chunk_table_sequential(
             let data = unsafe {
                 std::slice::from_raw_parts(mapped_state.get_heap_base(), size_bytes as usize)
             };
-            compute_file_chunk_hashes(data);
+            compute_file_chunk_hashes(&|hasher, range| hasher.write(&data[range]));
         } else {
-            let mmap =
-                ScopedMmap::from_path(root.join(&relative_path)).expect("failed to open file");
-            let data = mmap.as_slice();
-            compute_file_chunk_hashes(data);
+            let contents =
+                FileContents::open(&root.join(&relative_path)).expect("failed to open file");
+            compute_file_chunk_hashes(&|hasher, range| hasher.write(&contents.read(range)));
         };
     }
 
@@ -491,13 +633,21 @@ fn files_with_sizes(
         })?;
 
     if metadata.is_file() {
+        // Overlay files are not part of the manifest, which describes the
+        // logical contents of the heap files they are applied to.
+        if page_map::is_overlay_file(&relative_path) {
+            return Ok(());
+        }
         let len = if relative_path.ends_with("state_file") {
             let cow_base_dir = absolute_path.parent().unwrap();
             let cow_mgr = CowMemoryManagerImpl::open_readonly(cow_base_dir.to_path_buf());
             let map = cow_mgr.get_map();
             map.get_heap_len() as u64
-        } else {
+        } else if page_map::overlay_paths(&absolute_path)?.is_empty() {
             metadata.len()
+        } else {
+            let page_map = PageMap::open(&absolute_path, None)?;
+            (page_map.num_host_pages() * PAGE_SIZE) as u64
         };
 
         files.push(FileWithSize(relative_path, len))
@@ -917,6 +1067,88 @@ pub fn validate_chunk(
     Ok(())
 }
 
+/// Compares the `expected` manifest of a checkpoint with the `actual` one
+/// recomputed from its files and returns all the differences: missing and
+/// unexpected files, files with a different size or hash and, for the latter,
+/// the chunks whose hashes differ.
+pub fn compare_manifests(expected: &Manifest, actual: &Manifest) -> Vec<ManifestMismatch> {
+    // Chunk hashes of the file with the given index, indexed by offset.
+    fn chunk_hashes(manifest: &Manifest, file_index: usize) -> BTreeMap<u64, [u8; 32]> {
+        manifest
+            .chunk_table
+            .iter()
+            .filter(|chunk| chunk.file_index as usize == file_index)
+            .map(|chunk| (chunk.offset, chunk.hash))
+            .collect()
+    }
+
+    let files_by_path = |manifest: &Manifest| -> BTreeMap<PathBuf, usize> {
+        manifest
+            .file_table
+            .iter()
+            .enumerate()
+            .map(|(ix, f)| (f.relative_path.clone(), ix))
+            .collect()
+    };
+    let expected_files = files_by_path(expected);
+    let actual_files = files_by_path(actual);
+
+    let mut mismatches = Vec::new();
+    for (relative_path, expected_ix) in expected_files.iter() {
+        let actual_ix = match actual_files.get(relative_path) {
+            Some(ix) => *ix,
+            None => {
+                mismatches.push(ManifestMismatch::MissingFile {
+                    relative_path: relative_path.clone(),
+                });
+                continue;
+            }
+        };
+        let expected_file = &expected.file_table[*expected_ix];
+        let actual_file = &actual.file_table[actual_ix];
+        if expected_file.size_bytes == actual_file.size_bytes
+            && expected_file.hash == actual_file.hash
+        {
+            continue;
+        }
+        mismatches.push(ManifestMismatch::FileMismatch {
+            relative_path: relative_path.clone(),
+            expected_size: expected_file.size_bytes,
+            actual_size: actual_file.size_bytes,
+            expected_hash: expected_file.hash.to_vec(),
+            actual_hash: actual_file.hash.to_vec(),
+        });
+
+        let expected_chunks = chunk_hashes(expected, *expected_ix);
+        let actual_chunks = chunk_hashes(actual, actual_ix);
+        let offsets: BTreeSet<u64> = expected_chunks
+            .keys()
+            .chain(actual_chunks.keys())
+            .cloned()
+            .collect();
+        for offset in offsets {
+            let expected_hash = expected_chunks.get(&offset);
+            let actual_hash = actual_chunks.get(&offset);
+            if expected_hash != actual_hash {
+                mismatches.push(ManifestMismatch::ChunkMismatch {
+                    relative_path: relative_path.clone(),
+                    offset,
+                    expected_hash: expected_hash.map(|hash| hash.to_vec()),
+                    actual_hash: actual_hash.map(|hash| hash.to_vec()),
+                });
+            }
+        }
+    }
+    for relative_path in actual_files.keys() {
+        if !expected_files.contains_key(relative_path) {
+            mismatches.push(ManifestMismatch::UnexpectedFile {
+                relative_path: relative_path.clone(),
+            });
+        }
+    }
+    mismatches
+}
+
 /// Computes root hash of the manifest.
 /// See note [Manifest Hash].
 pub fn manifest_hash(manifest: &Manifest) -> [u8; 32] {
@@ -1,7 +1,8 @@
 use super::{
-    compute_manifest, diff_manifest, file_chunk_range, filter_out_zero_chunks, hash::ManifestHash,
-    manifest_hash, validate_chunk, validate_manifest, ChunkValidationError, DiffScript,
-    ManifestValidationError, CURRENT_STATE_SYNC_VERSION, STATE_SYNC_V1,
+    compare_manifests, compute_manifest, diff_manifest, file_chunk_range, filter_out_zero_chunks,
+    hash::ManifestHash, manifest_hash, validate_chunk, validate_manifest, ChunkValidationError,
+    DiffScript, ManifestMismatch, ManifestValidationError, CURRENT_STATE_SYNC_VERSION,
+    STATE_SYNC_V1,
 };
 use crate::ManifestMetrics;
 
@@ -318,6 +319,47 @@ fn orphan_chunk_detected() {
     }
 }
 
+#[test]
+fn identical_manifests_have_no_mismatches() {
+    let (_, manifest) = simple_manifest();
+    assert_eq!(compare_manifests(&manifest, &manifest), vec![]);
+}
+
+#[test]
+fn manifest_mismatches_are_reported_per_file_and_chunk() {
+    let (_, expected) = simple_manifest();
+    let mut actual = expected.clone();
+    // Corrupt the second chunk of "subdir/memory" and drop "subdir/queue".
+    actual.chunk_table[2].hash = [7u8; 32];
+    actual.file_table[1].hash = [8u8; 32];
+    actual.file_table[3].relative_path = "subdir/unknown".into();
+
+    assert_eq!(
+        compare_manifests(&expected, &actual),
+        vec![
+            ManifestMismatch::FileMismatch {
+                relative_path: "subdir/memory".into(),
+                expected_size: 2048,
+                actual_size: 2048,
+                expected_hash: expected.file_table[1].hash.to_vec(),
+                actual_hash: vec![8u8; 32],
+            },
+            ManifestMismatch::ChunkMismatch {
+                relative_path: "subdir/memory".into(),
+                offset: 1024,
+                expected_hash: Some(expected.chunk_table[2].hash.to_vec()),
+                actual_hash: Some(vec![7u8; 32]),
+            },
+            ManifestMismatch::MissingFile {
+                relative_path: "subdir/queue".into(),
+            },
+            ManifestMismatch::UnexpectedFile {
+                relative_path: "subdir/unknown".into(),
+            },
+        ]
+    );
+}
+
 #[test]
 fn test_diff_simple_manifest() {
     let (_, manifest_old) = simple_manifest();
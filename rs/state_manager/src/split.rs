@@ -0,0 +1,76 @@
+//! Loads and writes the state of one half of a subnet that is being split in
+//! two.
+
+use crate::{
+    checkpoint::{load_checkpoint, make_checkpoint},
+    CheckpointError, CheckpointMetrics, NUMBER_OF_CHECKPOINT_THREADS,
+};
+use ic_registry_routing_table::RoutingTable;
+use ic_registry_subnet_type::SubnetType;
+use ic_replicated_state::ReplicatedState;
+use ic_state_layout::{CheckpointLayout, ReadPolicy, StateLayout};
+use ic_types::{Height, SubnetId};
+
+/// Loads a checkpoint of the subnet being split and retains only the part of
+/// the state that belongs to the half `subnet_id` according to the post-split
+/// `routing_table` (see `ReplicatedState::split`).
+///
+/// Both halves of a split subnet start from the same checkpoint: the half that
+/// keeps the original subnet ID by passing its own ID, the new half by passing
+/// the new subnet's ID.
+pub fn load_split_checkpoint<P: ReadPolicy + Send + Sync>(
+    checkpoint_layout: &CheckpointLayout<P>,
+    own_subnet_type: SubnetType,
+    subnet_id: SubnetId,
+    routing_table: &RoutingTable,
+    thread_pool: Option<&mut scoped_threadpool::Pool>,
+) -> Result<ReplicatedState, CheckpointError> {
+    let state = load_checkpoint(checkpoint_layout, own_subnet_type, thread_pool)?;
+    Ok(state.split(subnet_id, routing_table))
+}
+
+/// Writes the part of the checkpoint at `height` in `state_layout` that the
+/// half `subnet_id` retains according to the post-split `routing_table` as a
+/// new checkpoint at `split_height`. The manifest of the new checkpoint is
+/// computed when the state manager starts, like for any other checkpoint
+/// without one.
+///
+/// This is meant to be run offline (see `state_tool split-checkpoint`), while
+/// the replica is stopped, by all nodes of a half at the same agreed
+/// `split_height`. The half then restarts from a recovery CUP carrying the
+/// root hash of the new checkpoint. The tip is reset in the process.
+pub fn split_checkpoint(
+    state_layout: &StateLayout,
+    height: Height,
+    split_height: Height,
+    own_subnet_type: SubnetType,
+    subnet_id: SubnetId,
+    routing_table: &RoutingTable,
+    metrics: &CheckpointMetrics,
+) -> Result<(), CheckpointError> {
+    if state_layout.checkpoint(split_height).is_ok() {
+        return Err(CheckpointError::AlreadyExists(split_height));
+    }
+    // `reset_tip_to()` leaves an empty tip behind if there is no such checkpoint.
+    state_layout.checkpoint(height)?;
+    state_layout.reset_tip_to(height)?;
+    let tip = state_layout.tip()?;
+    let state = load_split_checkpoint(&tip, own_subnet_type, subnet_id, routing_table, None)?;
+
+    // The canisters retained by the other half must not be carried over.
+    for canister_id in tip.canister_ids()? {
+        if state.canister_state(&canister_id).is_none() {
+            tip.canister(&canister_id)?.mark_deleted()?;
+        }
+    }
+
+    let mut thread_pool = scoped_threadpool::Pool::new(NUMBER_OF_CHECKPOINT_THREADS);
+    make_checkpoint(
+        &state,
+        split_height,
+        state_layout,
+        metrics,
+        &mut thread_pool,
+    )?;
+    Ok(())
+}
@@ -1,13 +1,13 @@
 use crate::{
-    manifest::{filter_out_zero_chunks, DiffScript},
+    manifest::{filter_out_zero_chunks, DiffScript, FileContents},
     CheckpointRef, StateSyncMetrics, StateSyncRefs, CRITICAL_ERROR_STATE_SYNC_CORRUPTED_CHUNKS,
 };
 use ic_cow_state::{CowMemoryManager, CowMemoryManagerImpl, MappedState};
 use ic_logger::{debug, error, fatal, info, trace, warn, ReplicaLogger};
+use ic_protobuf::state::sync::v1 as pb;
 use ic_registry_subnet_type::SubnetType;
-use ic_state_layout::utils::do_copy_overwrite;
 use ic_state_layout::{error::LayoutError, CheckpointLayout, ReadOnly, RwPolicy, StateLayout};
-use ic_sys::{mmap::ScopedMmap, PAGE_SIZE};
+use ic_sys::PAGE_SIZE;
 use ic_types::{
     artifact::{Artifact, StateSyncMessage},
     chunkable::{
@@ -19,11 +19,14 @@ use ic_types::{
     state_sync::{decode_manifest, Manifest, MANIFEST_CHUNK},
     CryptoHashOfState, Height,
 };
-use std::os::unix::fs::FileExt;
+use lazy_static::lazy_static;
+use prost::Message;
+use std::convert::TryFrom;
+use std::os::unix::fs::{FileExt, MetadataExt};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
@@ -33,6 +36,22 @@ pub mod cache;
 // necessary
 const ALWAYS_VALIDATE: bool = true;
 
+/// The minimum time between two updates of the progress record of a state
+/// sync. Every update syncs the chunks written since the previous one to disk.
+const PROGRESS_RECORD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The maximum number of heap files with overlays whose contents
+/// `get_state_sync_chunk` keeps open, so that serving the chunks of a file
+/// does not reopen it and its overlays for every chunk.
+const FILE_CONTENTS_CACHE_SIZE: usize = 16;
+
+lazy_static! {
+    /// The contents of the files that chunks were most recently served from,
+    /// keyed by path and inode, the most recently used last.
+    static ref FILE_CONTENTS_CACHE: Mutex<Vec<((PathBuf, u64), Arc<FileContents>)>> =
+        Mutex::new(Vec::new());
+}
+
 /// The state of the communication with up-to-date nodes.
 #[derive(Clone)]
 enum DownloadState {
@@ -59,9 +78,19 @@ enum DownloadState {
 /// sync priority function.  When priority function returns "Fetch", P2P calls
 /// StateManager to construct an IncompleteState corresponding to the state
 /// artifact advert.
+///
+/// Once the manifest is received, the state sync periodically records which
+/// chunks are still missing next to the scratchpad (see
+/// `StateLayout::state_sync_progress`). If the replica restarts in the
+/// middle of the sync, the next IncompleteState for the same state resumes
+/// from that record instead of downloading everything again.
 pub struct IncompleteState {
     log: ReplicaLogger,
     root: PathBuf,
+    progress_path: PathBuf,
+    /// Indices of the files written to since the progress was last recorded.
+    unsynced_files: BTreeSet<usize>,
+    progress_recorded_at: Instant,
     state_layout: StateLayout,
     height: Height,
     root_hash: CryptoHashOfState,
@@ -103,6 +132,10 @@ impl Drop for IncompleteState {
 
         info!(self.log, "State sync @{} {}", self.height, description);
 
+        // The chunks on disk are either moved to the cache or deleted, so the
+        // state sync can't be resumed from them anymore.
+        Self::remove_progress(&self.log, &self.progress_path);
+
         // Pass self to the cache, taking ownership of chunks on disk
         let cache = Arc::clone(&self.state_sync_refs.cache);
         cache.write().push(self);
@@ -123,6 +156,23 @@ pub(crate) fn get_state_sync_chunk(
 
         let data = unsafe { std::slice::from_raw_parts(base, len as usize) };
         Ok(data.to_vec())
+    } else if FileContents::has_overlays(&file_path)? {
+        // Heap files with overlays are served with the overlays applied.
+        let contents = cached_file_contents(&file_path)?;
+        let start = offset as usize;
+        let end = start + len as usize;
+        if contents.size_bytes() < end {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "chunk {}..{} is out of range of file {}",
+                    start,
+                    end,
+                    file_path.display()
+                ),
+            ));
+        }
+        Ok(contents.read(start..end).into_owned())
     } else {
         let mut buf = vec![0; len as usize];
         let f = std::fs::File::open(&file_path)?;
@@ -131,6 +181,28 @@ pub(crate) fn get_state_sync_chunk(
     }
 }
 
+/// Returns the contents of the checkpoint file at `path`, which are only read
+/// from disk if they are not in `FILE_CONTENTS_CACHE` yet. Checkpoint files
+/// are never modified, so the inode tells apart a file that was replaced,
+/// e.g. by a checkpoint that was synced again at the same height.
+fn cached_file_contents(path: &Path) -> std::io::Result<Arc<FileContents>> {
+    let key = (path.to_path_buf(), std::fs::metadata(path)?.ino());
+    let mut cache = FILE_CONTENTS_CACHE.lock().unwrap();
+    if let Some(index) = cache.iter().position(|(cached_key, _)| *cached_key == key) {
+        let entry = cache.remove(index);
+        let contents = Arc::clone(&entry.1);
+        cache.push(entry);
+        return Ok(contents);
+    }
+
+    let contents = Arc::new(FileContents::open(path)?);
+    if cache.len() >= FILE_CONTENTS_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push((key, Arc::clone(&contents)));
+    Ok(contents)
+}
+
 impl IncompleteState {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -152,15 +224,55 @@ impl IncompleteState {
             fatal!(log, "There is already a live state sync @{}.", height);
         }
 
+        let root = state_layout
+            .state_sync_scratchpad(height)
+            .expect("failed to create directory for state sync scratchpad");
+        let progress_path = state_layout
+            .state_sync_progress(height)
+            .expect("failed to create directory for state sync progress");
+
+        let state = match Self::load_progress(&log, &progress_path, height, &root_hash) {
+            Some((manifest, fetch_chunks)) if root.exists() => {
+                info!(
+                    log,
+                    "Resuming state sync @{} with {} chunks left to fetch",
+                    height,
+                    fetch_chunks.len()
+                );
+                metrics.state_sync_remaining.add(fetch_chunks.len() as i64);
+                DownloadState::Loading {
+                    manifest,
+                    fetch_chunks,
+                }
+            }
+            _ => {
+                // Whatever was left on disk by an earlier sync at this height
+                // can't be resumed, start from scratch.
+                Self::remove_progress(&log, &progress_path);
+                if root.exists() {
+                    if let Err(err) = std::fs::remove_dir_all(&root) {
+                        fatal!(
+                            log,
+                            "Failed to remove stale state sync scratchpad {}: {}",
+                            root.display(),
+                            err
+                        );
+                    }
+                }
+                DownloadState::Blank
+            }
+        };
+
         Self {
             log,
-            root: state_layout
-                .state_sync_scratchpad(height)
-                .expect("failed to create directory for state sync scratchpad"),
+            root,
+            progress_path,
+            unsynced_files: BTreeSet::new(),
+            progress_recorded_at: Instant::now(),
             state_layout,
             height,
             root_hash,
-            state: DownloadState::Blank,
+            state,
             manifest_with_checkpoint_ref,
             metrics,
             started_at: Instant::now(),
@@ -170,6 +282,158 @@ impl IncompleteState {
         }
     }
 
+    /// Reads the progress record at `path` and returns the manifest and the
+    /// chunks still to fetch of the state sync it describes, if it is a
+    /// valid record of a state sync of state `root_hash` at `height`.
+    fn load_progress(
+        log: &ReplicaLogger,
+        path: &Path,
+        height: Height,
+        root_hash: &CryptoHashOfState,
+    ) -> Option<(Manifest, HashSet<usize>)> {
+        let discard = |reason: String| {
+            warn!(
+                log,
+                "Ignoring state sync progress record {}: {}",
+                path.display(),
+                reason
+            );
+            None
+        };
+
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => return discard(format!("failed to read: {}", err)),
+        };
+        let progress = match pb::StateSyncProgress::decode(&bytes[..]) {
+            Ok(progress) => progress,
+            Err(err) => return discard(format!("failed to decode: {}", err)),
+        };
+        if progress.height != height.get() || progress.root_hash != root_hash.get_ref().0 {
+            return discard(format!(
+                "it belongs to the state sync @{} of another state",
+                progress.height
+            ));
+        }
+        let manifest = match progress.manifest.map(Manifest::try_from) {
+            Some(Ok(manifest)) => manifest,
+            Some(Err(err)) => return discard(format!("invalid manifest: {}", err)),
+            None => return discard("missing manifest".to_string()),
+        };
+        if let Err(err) = crate::manifest::validate_manifest(&manifest, root_hash) {
+            return discard(format!("invalid manifest: {}", err));
+        }
+        if progress.missing_chunks.is_empty()
+            || progress
+                .missing_chunks
+                .iter()
+                .any(|ix| *ix >= manifest.chunk_table.len() as u64)
+        {
+            return discard("invalid set of missing chunks".to_string());
+        }
+
+        // The record stores indices into the manifest's chunk table, while
+        // fetch_chunks considers the manifest as chunk 0.
+        let fetch_chunks = progress
+            .missing_chunks
+            .iter()
+            .map(|ix| *ix as usize + 1)
+            .collect();
+        Some((manifest, fetch_chunks))
+    }
+
+    /// Syncs the files of the scratchpad written to since the last call to
+    /// disk and records the chunks still to fetch at `progress_path`, so that
+    /// the state sync can be resumed from the scratchpad after a restart.
+    ///
+    /// Failures are only logged: they make the state sync not resumable, but
+    /// don't affect its outcome.
+    #[allow(clippy::too_many_arguments)]
+    fn record_progress(
+        log: &ReplicaLogger,
+        progress_path: &Path,
+        root: &Path,
+        height: Height,
+        root_hash: &CryptoHashOfState,
+        manifest: &Manifest,
+        fetch_chunks: &HashSet<usize>,
+        unsynced_files: &mut BTreeSet<usize>,
+    ) {
+        if manifest
+            .file_table
+            .iter()
+            .any(|f| f.relative_path.ends_with("state_file"))
+        {
+            // The chunks of cow memory files are not written to the scratchpad
+            // files directly, so such a state sync can't be resumed.
+            return;
+        }
+
+        let mut dirs = BTreeSet::new();
+        for file_index in unsynced_files.iter() {
+            let path = root.join(&manifest.file_table[*file_index].relative_path);
+            if let Err(err) = std::fs::File::open(&path).and_then(|f| f.sync_all()) {
+                warn!(
+                    log,
+                    "Failed to sync file {} of state sync @{}: {}",
+                    path.display(),
+                    height,
+                    err
+                );
+                return;
+            }
+            dirs.extend(
+                path.ancestors()
+                    .skip(1)
+                    .take_while(|dir| dir.starts_with(root))
+                    .map(Path::to_path_buf),
+            );
+        }
+        for dir in dirs {
+            if let Err(err) = ic_utils::fs::sync_path(&dir) {
+                warn!(
+                    log,
+                    "Failed to sync directory {} of state sync @{}: {}",
+                    dir.display(),
+                    height,
+                    err
+                );
+                return;
+            }
+        }
+        unsynced_files.clear();
+
+        let progress = pb::StateSyncProgress {
+            height: height.get(),
+            root_hash: root_hash.get_ref().0.clone(),
+            manifest: Some(pb::Manifest::from(manifest.clone())),
+            missing_chunks: fetch_chunks.iter().map(|ix| *ix as u64 - 1).collect(),
+        };
+        if let Err(err) = ic_utils::fs::write_protobuf_using_tmp_file(progress_path, &progress) {
+            warn!(
+                log,
+                "Failed to record progress of state sync @{} at {}: {}",
+                height,
+                progress_path.display(),
+                err
+            );
+        }
+    }
+
+    /// Removes the progress record at `progress_path`, if any.
+    fn remove_progress(log: &ReplicaLogger, progress_path: &Path) {
+        match std::fs::remove_file(progress_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => warn!(
+                log,
+                "Failed to remove state sync progress record {}: {}",
+                progress_path.display(),
+                err
+            ),
+            _ => (),
+        }
+    }
+
     /// Creates all the files listed in the manifest and resizes them to their
     /// expected sizes.  This way we won't have to worry about creating parent
     /// directories when we receive chunks.
@@ -280,10 +544,9 @@ impl IncompleteState {
                         );
 
                         if validate_data || ALWAYS_VALIDATE {
-                            let src_map = ScopedMmap::from_path(&src_path).unwrap_or_else(|err| {
-                                fatal!(log, "Failed to mmap file {}: {}", src_path.display(), err)
+                            let src = FileContents::open(&src_path).unwrap_or_else(|err| {
+                                fatal!(log, "Failed to open file {}: {}", src_path.display(), err)
                             });
-                            let src_data = src_map.as_slice();
 
                             let old_chunk_range = crate::manifest::file_chunk_range(
                                 &manifest_old.chunk_table,
@@ -305,7 +568,7 @@ impl IncompleteState {
                                 let new_chunk_idx = new_chunk_range.start + chunk_offset;
                                 let byte_range = chunk.byte_range();
 
-                                if src_data.len() < byte_range.end {
+                                if src.size_bytes() < byte_range.end {
                                     warn!(
                                         log,
                                         "Local chunk {} ({}@{}—{}) is out of range (file len = {}), \
@@ -314,7 +577,7 @@ impl IncompleteState {
                                         src_path.display(),
                                         byte_range.start,
                                         byte_range.end,
-                                        src_data.len(),
+                                        src.size_bytes(),
                                         new_chunk_idx + 1
                                     );
                                     bad_chunks.push(idx);
@@ -333,7 +596,7 @@ impl IncompleteState {
 
                                 if let Err(err) = crate::manifest::validate_chunk(
                                     idx,
-                                    &src_data[byte_range.clone()],
+                                    &src.read(byte_range.clone()),
                                     manifest_old,
                                 ) {
                                     warn!(
@@ -363,13 +626,13 @@ impl IncompleteState {
                             }
 
                             if bad_chunks.is_empty()
-                                && src_data.len()
+                                && src.size_bytes()
                                     == manifest_old.file_table[*old_index].size_bytes as usize
                             {
                                 // All the hash sums and the file size match, so we can
                                 // simply copy the whole file.  That's much faster than
                                 // copying one chunk at a time.
-                                do_copy_overwrite(log, &src_path, &dst_path).unwrap_or_else(
+                                src.copy_to(log, &src_path, &dst_path).unwrap_or_else(
                                     |err| {
                                         fatal!(
                                             log,
@@ -404,9 +667,9 @@ impl IncompleteState {
                                     }
 
                                     let chunk = &manifest_old.chunk_table[idx];
-                                    let data = &src_data[chunk.byte_range()];
+                                    let data = src.read(chunk.byte_range());
 
-                                    dst.write_at(data, chunk.offset).unwrap_or_else(|err| {
+                                    dst.write_at(&data, chunk.offset).unwrap_or_else(|err| {
                                         fatal!(
                                             log,
                                             "Failed to write chunk (offset = {}, size = {}) to file {}: {}",
@@ -422,7 +685,9 @@ impl IncompleteState {
                         } else {
                             // Since we do not validate in this else branch, we can simply copy the
                             // file without any extra work
-                            do_copy_overwrite(log, &src_path, &dst_path).unwrap_or_else(|err| {
+                            FileContents::open(&src_path)
+                                .and_then(|src| src.copy_to(log, &src_path, &dst_path))
+                                .unwrap_or_else(|err| {
                                 fatal!(
                                     log,
                                     "Failed to copy file from {} to {}: {}",
@@ -543,8 +808,8 @@ impl IncompleteState {
                             fatal!(log, "Failed to open file {}: {}", dst_path.display(), err)
                         });
 
-                    let src_map = ScopedMmap::from_path(&src_path).unwrap_or_else(|err| {
-                        fatal!(log, "Failed to mmap file {}: {}", src_path.display(), err)
+                    let src = FileContents::open(&src_path).unwrap_or_else(|err| {
+                        fatal!(log, "Failed to open file {}: {}", src_path.display(), err)
                     });
 
                     // Validate each chunk that we happen to have locally.  If the
@@ -555,7 +820,7 @@ impl IncompleteState {
                         let src_chunk = &manifest_old.chunk_table[*src_chunk_index];
                         let byte_range = src_chunk.byte_range();
 
-                        if src_map.len() < byte_range.end {
+                        if src.size_bytes() < byte_range.end {
                             warn!(
                                 log,
                                 "Local chunk {} ({}@{}—{}) is out of range (file len = {}), \
@@ -564,18 +829,18 @@ impl IncompleteState {
                                 src_path.display(),
                                 byte_range.start,
                                 byte_range.end,
-                                src_map.len(),
+                                src.size_bytes(),
                                 *dst_chunk_index + 1
                             );
                             corrupted_chunks.lock().unwrap().push(*dst_chunk_index + 1);
                             continue;
                         }
 
-                        let src_data = &src_map.as_slice()[byte_range];
+                        let src_data = src.read(byte_range);
                         if validate_data || ALWAYS_VALIDATE {
                             if let Err(err) = crate::manifest::validate_chunk(
                                 *dst_chunk_index,
-                                src_data,
+                                &src_data,
                                 manifest_new,
                             ) {
                                 let byte_range = src_chunk.byte_range();
@@ -605,7 +870,7 @@ impl IncompleteState {
                             }
                         }
 
-                        dst.write_at(src_data, dst_chunk.offset)
+                        dst.write_at(&src_data, dst_chunk.offset)
                             .unwrap_or_else(|err| {
                                 fatal!(
                                     log,
@@ -1120,6 +1385,21 @@ impl Chunkable for IncompleteState {
                             .register_successful_sync(self.height);
                         Ok(artifact)
                     } else {
+                        // Everything in the scratchpad was written by
+                        // initialize_state_on_disk, so all the files need to be synced.
+                        self.unsynced_files = (0..manifest.file_table.len()).collect();
+                        Self::record_progress(
+                            &self.log,
+                            &self.progress_path,
+                            &self.root,
+                            self.height,
+                            &self.root_hash,
+                            &manifest,
+                            &fetch_chunks,
+                            &mut self.unsynced_files,
+                        );
+                        self.progress_recorded_at = Instant::now();
+
                         self.state = DownloadState::Loading {
                             manifest,
                             fetch_chunks,
@@ -1175,6 +1455,8 @@ impl Chunkable for IncompleteState {
                 );
 
                 fetch_chunks.remove(&ix);
+                self.unsynced_files
+                    .insert(manifest.chunk_table[chunk_table_index].file_index as usize);
 
                 if fetch_chunks.is_empty() {
                     debug!(
@@ -1184,6 +1466,8 @@ impl Chunkable for IncompleteState {
                         self.height
                     );
 
+                    Self::remove_progress(&self.log, &self.progress_path);
+
                     Self::make_checkpoint(
                         &self.log,
                         &self.metrics,
@@ -1209,6 +1493,20 @@ impl Chunkable for IncompleteState {
                     return Ok(artifact);
                 }
 
+                if self.progress_recorded_at.elapsed() >= PROGRESS_RECORD_INTERVAL {
+                    Self::record_progress(
+                        &self.log,
+                        &self.progress_path,
+                        &self.root,
+                        self.height,
+                        &self.root_hash,
+                        manifest,
+                        fetch_chunks,
+                        &mut self.unsynced_files,
+                    );
+                    self.progress_recorded_at = Instant::now();
+                }
+
                 Err(ChunksMoreNeeded)
             }
         }
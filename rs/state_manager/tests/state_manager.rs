@@ -8,11 +8,13 @@ use ic_interfaces::{
 };
 use ic_logger::replica_logger::no_op_logger;
 use ic_metrics::MetricsRegistry;
+use ic_registry_routing_table::{CanisterIdRange, RoutingTable};
 use ic_replicated_state::{
     page_map::PageIndex, testing::ReplicatedStateTesting, NumWasmPages, PageMap, ReplicatedState,
     Stream,
 };
-use ic_state_manager::StateManagerImpl;
+use ic_state_layout::StateLayout;
+use ic_state_manager::{split::split_checkpoint, CheckpointMetrics, StateManagerImpl};
 use ic_sys::PAGE_SIZE;
 use ic_test_utilities::{
     consensus::fake::FakeVerifier,
@@ -32,7 +34,7 @@ use ic_types::{
     ingress::{IngressStatus, WasmResult},
     messages::{CallbackId, RequestOrResponse},
     xnet::{StreamIndex, StreamIndexedQueue},
-    CanisterId, CryptoHashOfPartialState, CryptoHashOfState, Height, PrincipalId,
+    CanisterId, CryptoHashOfPartialState, CryptoHashOfState, Height, PrincipalId, SubnetId,
 };
 use proptest::prelude::*;
 use std::path::Path;
@@ -1452,6 +1454,59 @@ fn can_state_sync_from_cache() {
     })
 }
 
+#[test]
+fn can_resume_state_sync_after_restart() {
+    state_manager_test(|src_metrics, src_state_manager| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+
+        src_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&src_state_manager, height(1));
+        let id = StateSyncArtifactId {
+            height: height(1),
+            hash,
+        };
+
+        let state = src_state_manager.get_latest_state().take();
+
+        let msg = src_state_manager
+            .get_validated_by_identifier(&id)
+            .expect("failed to get state sync messages");
+
+        assert_error_counters(src_metrics);
+
+        state_manager_restart_test(|dst_state_manager, restart_fn| {
+            let mut chunkable = dst_state_manager.create_chunkable_state(&id);
+            let result = pipe_manifest(&msg, &mut *chunkable);
+            assert!(result.is_none());
+            let missing_chunks: HashSet<ChunkId> = chunkable.chunks_to_download().collect();
+
+            // Simulate a crash in the middle of the state sync: the state sync
+            // is never dropped, so its scratchpad stays on disk.
+            std::mem::forget(chunkable);
+            let dst_state_manager = restart_fn(dst_state_manager);
+
+            // The state sync resumes without fetching the manifest again.
+            let chunkable = dst_state_manager.create_chunkable_state(&id);
+            assert_eq!(
+                missing_chunks,
+                chunkable.chunks_to_download().collect::<HashSet<_>>()
+            );
+
+            let dst_msg = pipe_state_sync(msg, chunkable);
+            dst_state_manager
+                .check_artifact_acceptance(dst_msg, &node_test_id(0))
+                .expect("Failed to process state sync artifact");
+
+            let recovered_state = dst_state_manager
+                .get_state_at(height(1))
+                .expect("Destination state manager didn't receive the state")
+                .take();
+            assert_eq!(state, recovered_state);
+        })
+    })
+}
+
 #[test]
 fn can_state_sync_into_existing_checkpoint() {
     state_manager_test(|src_metrics, src_state_manager| {
@@ -1796,7 +1851,7 @@ fn can_get_dirty_pages() {
         insert_dummy_canister(&mut state, canister_test_id(100));
 
         update_state(&mut state, canister_test_id(80));
-        let dirty_pages = get_dirty_pages(&state);
+        let dirty_pages = get_dirty_pages(&state, state_manager.state_layout());
         // dirty_pages should be empty because there is no base checkpoint for the page
         // deltas.
         assert_eq!(dirty_pages, BTreeMap::new());
@@ -1806,7 +1861,7 @@ fn can_get_dirty_pages() {
         let (_height, mut state) = state_manager.take_tip();
         update_state(&mut state, canister_test_id(90));
         assert_eq!(
-            get_dirty_pages(&state),
+            get_dirty_pages(&state, state_manager.state_layout()),
             btreemap! {
                 canister_test_id(80) => (height(1), vec![]),
                 canister_test_id(90) => (height(1), vec![PageIndex::new(1), PageIndex::new(300)]),
@@ -1822,7 +1877,7 @@ fn can_get_dirty_pages() {
         drop_page_map(&mut state, canister_test_id(100));
         update_state(&mut state, canister_test_id(100));
         assert_eq!(
-            get_dirty_pages(&state),
+            get_dirty_pages(&state, state_manager.state_layout()),
             btreemap! {
                 canister_test_id(80) => (height(2), vec![]),
                 canister_test_id(90) => (height(2), vec![]),
@@ -2403,3 +2458,78 @@ proptest! {
         );
     }
 }
+
+#[test]
+fn checkpoint_is_split_offline_at_the_agreed_height_for_both_halves() {
+    let source_subnet_id = subnet_test_id(42);
+    let destination_subnet_id = subnet_test_id(43);
+    let routing_table = RoutingTable::new(maplit::btreemap! {
+        CanisterIdRange {
+            start: CanisterId::from(0x0),
+            end: CanisterId::from(0xff),
+        } => source_subnet_id,
+        CanisterIdRange {
+            start: CanisterId::from(0x100),
+            end: CanisterId::from(0x1ff),
+        } => destination_subnet_id,
+    });
+
+    with_test_replica_logger(|log| {
+        let make_state_manager = |config: &Config, own_subnet_id: SubnetId| {
+            StateManagerImpl::new(
+                Arc::new(FakeVerifier::new()),
+                own_subnet_id,
+                SubnetType::Application,
+                log.clone(),
+                &MetricsRegistry::new(),
+                config,
+                ic_types::malicious_flags::MaliciousFlags::default(),
+            )
+        };
+
+        // Every node, be it of the source or of the destination half, starts
+        // from the same checkpoint of the source subnet at height 1 and splits
+        // it at the agreed height 10 while its replica is stopped.
+        let split_node = |own_subnet_id: SubnetId, canister_id: CanisterId| {
+            let tmp = Builder::new().prefix("test").tempdir().unwrap();
+            let config = Config::new(tmp.path().into());
+            let state_manager = make_state_manager(&config, source_subnet_id);
+            let (_height, mut state) = state_manager.take_tip();
+            insert_dummy_canister(&mut state, CanisterId::from(0x1));
+            insert_dummy_canister(&mut state, CanisterId::from(0x101));
+            state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+            wait_for_checkpoint(&state_manager, height(1));
+            drop(state_manager);
+
+            split_checkpoint(
+                &StateLayout::new(log.clone(), config.state_root()),
+                height(1),
+                height(10),
+                SubnetType::Application,
+                own_subnet_id,
+                &routing_table,
+                &CheckpointMetrics::new(&MetricsRegistry::new()),
+            )
+            .unwrap();
+
+            let state_manager = make_state_manager(&config, own_subnet_id);
+            let hash = wait_for_checkpoint(&state_manager, height(10));
+            let (tip_height, tip) = state_manager.take_tip();
+            assert_eq!(tip_height, height(10));
+            assert_eq!(tip.metadata.own_subnet_id, own_subnet_id);
+            assert_eq!(canister_ids(&tip), vec![canister_id]);
+            hash
+        };
+
+        let source_hash = split_node(source_subnet_id, CanisterId::from(0x1));
+        let destination_hash = split_node(destination_subnet_id, CanisterId::from(0x101));
+        assert_ne!(source_hash, destination_hash);
+
+        // All nodes of a half end up with the same state, whose hash is the one
+        // recorded in the recovery CUP.
+        assert_eq!(
+            split_node(source_subnet_id, CanisterId::from(0x1)),
+            source_hash
+        );
+    });
+}
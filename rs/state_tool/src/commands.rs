@@ -1,8 +1,13 @@
 //! Command implementations.
+pub mod canisters;
 pub mod cdiff;
 pub mod chash;
 pub mod decode;
+pub mod export_canister;
+pub mod import_canister;
 pub mod import_state;
 pub mod list;
 pub mod manifest;
+pub mod split;
 mod utils;
+pub mod verify;
@@ -0,0 +1,198 @@
+//! Lists the canisters of a checkpoint with their sizes, cycles and status.
+
+use ic_protobuf::state::queues::v1 as pb_queues;
+use ic_replicated_state::{page_map::PageMap, CanisterStatus};
+use ic_state_layout::{CanisterLayout, CanisterStateBits, CompleteCheckpointLayout, ReadOnly};
+use ic_types::{CanisterId, Height};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+/// Inventory entry of a single canister.
+#[derive(Serialize)]
+struct CanisterInfo {
+    canister_id: String,
+    status: &'static str,
+    heap_bytes: u64,
+    stable_memory_bytes: u64,
+    wasm_bytes: u64,
+    total_bytes: u64,
+    cycles_balance: u128,
+    freezing_threshold_seconds: u64,
+    controllers: Vec<String>,
+    ingress_queue_messages: usize,
+    input_queue_messages: usize,
+    output_queue_messages: usize,
+    module_hash: Option<String>,
+}
+
+const CSV_HEADER: &str = "canister_id,status,heap_bytes,stable_memory_bytes,wasm_bytes,\
+total_bytes,cycles_balance,freezing_threshold_seconds,controllers,ingress_queue_messages,\
+input_queue_messages,output_queue_messages,module_hash";
+
+impl CanisterInfo {
+    /// Formats the entry as a CSV record. Controllers are separated by `;`.
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.canister_id,
+            self.status,
+            self.heap_bytes,
+            self.stable_memory_bytes,
+            self.wasm_bytes,
+            self.total_bytes,
+            self.cycles_balance,
+            self.freezing_threshold_seconds,
+            self.controllers.join(";"),
+            self.ingress_queue_messages,
+            self.input_queue_messages,
+            self.output_queue_messages,
+            self.module_hash.as_deref().unwrap_or("")
+        )
+    }
+}
+
+/// Prints an inventory of the canisters in the checkpoint at `path`, sorted
+/// by `sort_by` (in descending order, except for `id`) and formatted as
+/// `format` (`csv` or `json`).
+///
+/// Only the `CanisterStateBits`, the queues and the sizes of the canister
+/// files are loaded, so this is cheap even for large checkpoints.
+pub fn do_list_canisters(path: PathBuf, sort_by: String, format: String) -> Result<(), String> {
+    let cp_layout = CompleteCheckpointLayout::new(path, Height::new(0))
+        .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
+    let mut canister_ids = cp_layout
+        .canister_ids()
+        .map_err(|e| format!("failed to enumerate canisters: {}", e))?;
+    canister_ids.sort();
+
+    let mut canisters = canister_ids
+        .iter()
+        .map(|canister_id| {
+            let canister_layout = cp_layout
+                .canister(canister_id)
+                .map_err(|e| format!("failed to access canister {}: {}", canister_id, e))?;
+            canister_info(canister_id, &canister_layout)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    match sort_by.as_str() {
+        "id" => (),
+        "heap" => canisters.sort_by_key(|c| std::cmp::Reverse(c.heap_bytes)),
+        "stable" => canisters.sort_by_key(|c| std::cmp::Reverse(c.stable_memory_bytes)),
+        "wasm" => canisters.sort_by_key(|c| std::cmp::Reverse(c.wasm_bytes)),
+        "total" => canisters.sort_by_key(|c| std::cmp::Reverse(c.total_bytes)),
+        "cycles" => canisters.sort_by_key(|c| std::cmp::Reverse(c.cycles_balance)),
+        "queues" => canisters.sort_by_key(|c| {
+            std::cmp::Reverse(
+                c.ingress_queue_messages + c.input_queue_messages + c.output_queue_messages,
+            )
+        }),
+        other => return Err(format!("unknown sort key {}", other)),
+    }
+
+    match format.as_str() {
+        "csv" => {
+            println!("{}", CSV_HEADER);
+            for canister in canisters.iter() {
+                println!("{}", canister.to_csv());
+            }
+        }
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&canisters)
+                .map_err(|e| format!("failed to serialize canisters: {}", e))?
+        ),
+        other => return Err(format!("unknown output format {}", other)),
+    }
+
+    Ok(())
+}
+
+/// Collects the inventory entry of a single canister.
+fn canister_info(
+    canister_id: &CanisterId,
+    canister_layout: &CanisterLayout<ReadOnly>,
+) -> Result<CanisterInfo, String> {
+    let bits = canister_layout
+        .canister()
+        .deserialize()
+        .map_err(|e| format!("failed to read canister {}: {}", canister_id, e))
+        .and_then(|pb_bits| {
+            CanisterStateBits::try_from(pb_bits)
+                .map_err(|e| format!("failed to decode canister {}: {}", canister_id, e))
+        })?;
+    let queues: pb_queues::CanisterQueues = canister_layout
+        .queues()
+        .deserialize()
+        .map_err(|e| format!("failed to read queues of canister {}: {}", canister_id, e))?;
+    let messages_in = |entries: &[pb_queues::QueueEntry]| -> usize {
+        entries
+            .iter()
+            .filter_map(|entry| entry.queue.as_ref())
+            .map(|queue| queue.queue.len())
+            .sum()
+    };
+
+    let module_hash = if bits.execution_state_bits.is_some() {
+        let wasm = canister_layout.wasm().deserialize().map_err(|e| {
+            format!(
+                "failed to read Wasm module of canister {}: {}",
+                canister_id, e
+            )
+        })?;
+        Some(hex::encode(wasm.hash_sha256()))
+    } else {
+        None
+    };
+
+    let heap_bytes = heap_file_size(&canister_layout.vmemory_0())?;
+    let stable_memory_bytes = heap_file_size(&canister_layout.stable_memory_blob())?;
+    let wasm_bytes = file_size(&canister_layout.raw_path().join("software.wasm"))?;
+
+    Ok(CanisterInfo {
+        canister_id: canister_id.to_string(),
+        status: match bits.status {
+            CanisterStatus::Running { .. } => "running",
+            CanisterStatus::Stopping { .. } => "stopping",
+            CanisterStatus::Stopped => "stopped",
+        },
+        heap_bytes,
+        stable_memory_bytes,
+        wasm_bytes,
+        total_bytes: heap_bytes + stable_memory_bytes + wasm_bytes,
+        cycles_balance: bits.cycles_balance.get(),
+        freezing_threshold_seconds: bits.freeze_threshold.get(),
+        controllers: bits.controllers.iter().map(|c| c.to_string()).collect(),
+        ingress_queue_messages: queues.ingress_queue.len(),
+        input_queue_messages: messages_in(&queues.input_queues),
+        output_queue_messages: messages_in(&queues.output_queues),
+        module_hash,
+    })
+}
+
+/// Returns the logical size of the heap file at `path`, taking the overlay
+/// files on top of it into account, or 0 if it doesn't exist.
+fn heap_file_size(path: &Path) -> Result<u64, String> {
+    let overlays = ic_replicated_state::page_map::overlay_paths(path)
+        .map_err(|e| format!("failed to list overlays of {}: {}", path.display(), e))?;
+    if overlays.is_empty() {
+        return file_size(path);
+    }
+    let page_map = PageMap::open(path, None)
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    Ok((page_map.num_host_pages() * ic_sys::PAGE_SIZE) as u64)
+}
+
+/// Returns the size of the file at `path`, or 0 if it doesn't exist.
+fn file_size(path: &Path) -> Result<u64, String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(format!(
+            "failed to get metadata of {}: {}",
+            path.display(),
+            e
+        )),
+    }
+}
@@ -8,13 +8,18 @@ use std::path::PathBuf;
 
 /// Computes and prints partial state hash used for certification.
 pub fn do_hash(path: PathBuf) -> Result<(), String> {
+    println!("PARTIAL STATE HASH: {}", partial_state_hash(path)?);
+
+    Ok(())
+}
+
+/// Loads the checkpoint at `path` and computes its partial state hash.
+pub(crate) fn partial_state_hash(path: PathBuf) -> Result<String, String> {
     let cp_layout = CompleteCheckpointLayout::new(path.clone(), Height::new(0))
         .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
 
     let state = load_checkpoint(&cp_layout, SubnetType::Application, None)
         .map_err(|e| format!("failed to load checkpoint at {}: {}", path.display(), e))?;
 
-    println!("PARTIAL STATE HASH: {}", hash_state(&state).digest());
-
-    Ok(())
+    Ok(hash_state(&state).digest().to_string())
 }
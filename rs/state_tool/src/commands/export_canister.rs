@@ -0,0 +1,36 @@
+//! Exports the state of a single canister to an archive.
+
+use ic_state_layout::CompleteCheckpointLayout;
+use ic_state_manager::canister_archive::export_canister;
+use ic_types::{CanisterId, Height, PrincipalId};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Writes the Wasm module, heap, stable memory, queues and
+/// `CanisterStateBits` of canister `canister_id` persisted in the checkpoint
+/// at `path` to a self-contained archive at `output`.
+pub fn do_export_canister(
+    path: PathBuf,
+    canister_id: String,
+    output: PathBuf,
+) -> Result<(), String> {
+    let canister_id = PrincipalId::from_str(&canister_id)
+        .map_err(|e| format!("invalid canister ID {}: {}", canister_id, e))
+        .and_then(|principal_id| {
+            CanisterId::new(principal_id)
+                .map_err(|e| format!("invalid canister ID {}: {}", canister_id, e))
+        })?;
+    let cp_layout = CompleteCheckpointLayout::new(path, Height::new(0))
+        .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
+
+    export_canister(&cp_layout, &canister_id, &output)
+        .map_err(|e| format!("failed to export canister {}: {}", canister_id, e))?;
+
+    println!(
+        "Successfully exported canister {} to {}",
+        canister_id,
+        output.display()
+    );
+
+    Ok(())
+}
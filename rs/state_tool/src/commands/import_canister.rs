@@ -0,0 +1,50 @@
+//! Imports the state of a single canister from an archive into a checkpoint.
+
+use crate::commands::{manifest::checkpoint_manifest, utils};
+use ic_registry_subnet_type::SubnetType;
+use ic_state_layout::{CheckpointLayout, RwPolicy};
+use ic_state_manager::{canister_archive::import_canister, manifest::manifest_hash};
+use ic_types::Height;
+use std::path::PathBuf;
+
+/// Copies the checkpoint at `path` to `output` and imports the canister stored
+/// in `archive` into the copy, replacing any existing canister with the same
+/// ID. The resulting checkpoint is loaded to validate it and its root hash is
+/// recomputed.
+///
+/// Function is not crash-safe. Caller is responsible to follow guidelines
+/// regarding crash-safe I/O.
+pub fn do_import_canister(archive: PathBuf, path: PathBuf, output: PathBuf) -> Result<(), String> {
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()));
+    }
+    utils::copy_recursively(&path, &output)?;
+
+    let cp_layout = CheckpointLayout::<RwPolicy>::new(output.clone(), Height::new(0))
+        .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
+    let canister_id = import_canister(&archive, &cp_layout)
+        .map_err(|e| format!("failed to import {}: {}", archive.display(), e))?;
+
+    let state =
+        ic_state_manager::checkpoint::load_checkpoint(&cp_layout, SubnetType::Application, None)
+            .map_err(|e| format!("failed to load checkpoint at {}: {}", output.display(), e))?;
+    let own_subnet_id = state.metadata.own_subnet_id;
+    if state.routing_table().route(canister_id.get()) != Some(own_subnet_id) {
+        eprintln!(
+            "WARNING: canister {} is not routed to subnet {}",
+            canister_id, own_subnet_id
+        );
+    }
+    drop(state);
+
+    let manifest = checkpoint_manifest(output.clone())?;
+
+    println!(
+        "Successfully imported canister {} into checkpoint {}",
+        canister_id,
+        output.display()
+    );
+    println!("ROOT HASH: {}", hex::encode(manifest_hash(&manifest)));
+
+    Ok(())
+}
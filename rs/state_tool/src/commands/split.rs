@@ -0,0 +1,168 @@
+//! Simulates or performs splitting a subnet in two on a checkpoint.
+
+use crate::commands::{manifest::checkpoint_manifest, utils};
+use ic_metrics::MetricsRegistry;
+use ic_registry_routing_table::{CanisterIdRanges, RoutingTable};
+use ic_registry_subnet_type::SubnetType;
+use ic_replicated_state::ReplicatedState;
+use ic_state_layout::CompleteCheckpointLayout;
+use ic_state_manager::{
+    checkpoint::load_checkpoint,
+    manifest::manifest_hash,
+    split::{load_split_checkpoint, split_checkpoint},
+    CheckpointMetrics,
+};
+use ic_types::{Height, PrincipalId, SubnetId};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Loads the checkpoint at `path` twice, once for each half of the subnet as
+/// it would be split by the `split_subnet` registry mutation (the upper half
+/// of the subnet's canister ID ranges moving to `new_subnet_id`), and prints
+/// what each half retains. Nothing is written to disk.
+pub fn do_split(path: PathBuf, new_subnet_id: String) -> Result<(), String> {
+    let new_subnet_id = parse_subnet_id(&new_subnet_id)?;
+    let cp_layout = CompleteCheckpointLayout::new(path.clone(), Height::new(0))
+        .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
+
+    let state = load_checkpoint(&cp_layout, SubnetType::Application, None)
+        .map_err(|e| format!("failed to load checkpoint at {}: {}", path.display(), e))?;
+    let own_subnet_id = state.metadata.own_subnet_id;
+    let routing_table = split_routing_table(&state.routing_table(), own_subnet_id, new_subnet_id)?;
+    drop(state);
+
+    for subnet_id in &[own_subnet_id, new_subnet_id] {
+        let half = load_split_checkpoint(
+            &cp_layout,
+            SubnetType::Application,
+            *subnet_id,
+            &routing_table,
+            None,
+        )
+        .map_err(|e| format!("failed to load checkpoint at {}: {}", path.display(), e))?;
+        print_summary(&half, &routing_table.ranges(*subnet_id));
+    }
+
+    Ok(())
+}
+
+/// Splits the subnet of the checkpoint at `height` in the state root of the
+/// replica configured in `config` like `do_split()` does, and writes the part
+/// of the state retained by the half `subnet_id` as a new checkpoint at
+/// `split_height` (see `ic_state_manager::split::split_checkpoint`). Prints
+/// the root hash of the new checkpoint, to be recorded in the recovery CUP
+/// that the half restarts from.
+///
+/// All nodes of a half must run this with the same arguments while their
+/// replica is stopped; the nodes of the new subnet first import the
+/// checkpoint being split with `state_tool import`.
+pub fn do_split_checkpoint(
+    config: PathBuf,
+    height: u64,
+    split_height: u64,
+    new_subnet_id: String,
+    subnet_id: String,
+) -> Result<(), String> {
+    let new_subnet_id = parse_subnet_id(&new_subnet_id)?;
+    let subnet_id = parse_subnet_id(&subnet_id)?;
+    let height = Height::new(height);
+    let split_height = Height::new(split_height);
+    if split_height <= height {
+        return Err(format!(
+            "split height {} must be above the height {} of the checkpoint being split",
+            split_height, height
+        ));
+    }
+
+    let state_layout = utils::locate_state_root(config)?;
+    let cp_layout = state_layout
+        .checkpoint(height)
+        .map_err(|e| format!("failed to open checkpoint {}: {}", height, e))?;
+    let state = load_checkpoint(&cp_layout, SubnetType::Application, None)
+        .map_err(|e| format!("failed to load checkpoint {}: {}", height, e))?;
+    let own_subnet_id = state.metadata.own_subnet_id;
+    if subnet_id != own_subnet_id && subnet_id != new_subnet_id {
+        return Err(format!(
+            "subnet {} is neither the subnet being split ({}) nor the new subnet ({})",
+            subnet_id, own_subnet_id, new_subnet_id
+        ));
+    }
+    let routing_table = split_routing_table(&state.routing_table(), own_subnet_id, new_subnet_id)?;
+    drop(state);
+
+    let metrics_registry = MetricsRegistry::new();
+    split_checkpoint(
+        &state_layout,
+        height,
+        split_height,
+        SubnetType::Application,
+        subnet_id,
+        &routing_table,
+        &CheckpointMetrics::new(&metrics_registry),
+    )
+    .map_err(|e| format!("failed to split checkpoint {}: {}", height, e))?;
+
+    let cp_layout = state_layout
+        .checkpoint(split_height)
+        .map_err(|e| format!("failed to open checkpoint {}: {}", split_height, e))?;
+    let manifest = checkpoint_manifest(cp_layout.raw_path().to_path_buf())?;
+
+    println!(
+        "Successfully created checkpoint {} of subnet {} in state root {}",
+        split_height,
+        subnet_id,
+        state_layout.raw_path().display()
+    );
+    println!("ROOT HASH: {}", hex::encode(manifest_hash(&manifest)));
+
+    Ok(())
+}
+
+fn parse_subnet_id(subnet_id: &str) -> Result<SubnetId, String> {
+    PrincipalId::from_str(subnet_id)
+        .map(SubnetId::from)
+        .map_err(|e| format!("invalid subnet ID {}: {}", subnet_id, e))
+}
+
+/// Returns `routing_table` with the upper half of the canister ID ranges of
+/// `own_subnet_id` assigned to `new_subnet_id`, as the `split_subnet` registry
+/// mutation does.
+fn split_routing_table(
+    routing_table: &RoutingTable,
+    own_subnet_id: SubnetId,
+    new_subnet_id: SubnetId,
+) -> Result<RoutingTable, String> {
+    let mut routing_table = routing_table.clone();
+    let (_, moved_ranges) = routing_table
+        .ranges(own_subnet_id)
+        .bisect()
+        .ok_or_else(|| {
+            format!(
+                "subnet {} does not host enough canister ID ranges to be split",
+                own_subnet_id
+            )
+        })?;
+    routing_table
+        .assign_ranges(moved_ranges, new_subnet_id)
+        .map_err(|e| format!("failed to reassign canister ID ranges: {:?}", e))?;
+    Ok(routing_table)
+}
+
+/// Prints what one half of a split subnet retains.
+fn print_summary(state: &ReplicatedState, ranges: &CanisterIdRanges) {
+    println!("SUBNET: {}", state.metadata.own_subnet_id);
+    for range in ranges.iter() {
+        println!("  CANISTER ID RANGE: {} - {}", range.start, range.end);
+    }
+    println!("  CANISTERS: {}", state.num_canisters());
+    println!(
+        "  CANISTER SNAPSHOTS: {}",
+        state.canister_snapshots.iter().count()
+    );
+    println!("  STREAMS: {}", state.metadata.streams().iter().count());
+    println!(
+        "  INGRESS HISTORY ENTRIES: {}",
+        state.metadata.ingress_history.len()
+    );
+    println!("  MEMORY TAKEN: {} bytes", state.total_memory_taken().get());
+}
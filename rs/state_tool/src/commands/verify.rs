@@ -0,0 +1,344 @@
+//! Verifies a checkpoint against the manifest recorded in the states metadata
+//! or against an expected root hash.
+
+use crate::commands::{chash::partial_state_hash, manifest::checkpoint_manifest, utils};
+use ic_protobuf::state::v1 as pb;
+use ic_state_layout::{ProtoFileWith, ReadOnly, StateLayout};
+use ic_state_manager::manifest::{compare_manifests, manifest_hash, ManifestMismatch};
+use ic_types::{state_sync::Manifest, Height};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+/// Result of verifying a checkpoint, as printed in JSON mode.
+#[derive(Serialize)]
+struct VerificationReport {
+    height: u64,
+    checkpoint: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_root_hash: Option<String>,
+    actual_root_hash: String,
+    partial_state_hash: String,
+    mismatches: Vec<MismatchReport>,
+    #[serde(skip)]
+    manifest_mismatches: Vec<ManifestMismatch>,
+    /// The recomputed manifest, reported if none is recorded for the
+    /// checkpoint (e.g. because it diverged).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recomputed_manifest: Option<Manifest>,
+}
+
+impl VerificationReport {
+    /// Returns an error describing why the checkpoint failed verification, if
+    /// it did.
+    fn verdict(&self) -> Result<(), String> {
+        if !self.mismatches.is_empty() {
+            return Err(format!(
+                "checkpoint {} at {} does not match its manifest: {} mismatch(es)",
+                self.height,
+                self.checkpoint.display(),
+                self.mismatches.len()
+            ));
+        }
+        match &self.expected_root_hash {
+            Some(expected) if *expected != self.actual_root_hash => Err(format!(
+                "checkpoint {} at {} has root hash {}, expected {}",
+                self.height,
+                self.checkpoint.display(),
+                self.actual_root_hash,
+                expected
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A single manifest mismatch, with hashes encoded in hex.
+#[derive(Serialize)]
+struct MismatchReport {
+    kind: &'static str,
+    relative_path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual_hash: Option<String>,
+}
+
+impl From<&ManifestMismatch> for MismatchReport {
+    fn from(mismatch: &ManifestMismatch) -> Self {
+        let report = |kind, relative_path: &PathBuf| Self {
+            kind,
+            relative_path: relative_path.clone(),
+            offset: None,
+            expected_size: None,
+            actual_size: None,
+            expected_hash: None,
+            actual_hash: None,
+        };
+        match mismatch {
+            ManifestMismatch::MissingFile { relative_path } => {
+                report("missing_file", relative_path)
+            }
+            ManifestMismatch::UnexpectedFile { relative_path } => {
+                report("unexpected_file", relative_path)
+            }
+            ManifestMismatch::FileMismatch {
+                relative_path,
+                expected_size,
+                actual_size,
+                expected_hash,
+                actual_hash,
+            } => Self {
+                expected_size: Some(*expected_size),
+                actual_size: Some(*actual_size),
+                expected_hash: Some(hex::encode(expected_hash)),
+                actual_hash: Some(hex::encode(actual_hash)),
+                ..report("file_mismatch", relative_path)
+            },
+            ManifestMismatch::ChunkMismatch {
+                relative_path,
+                offset,
+                expected_hash,
+                actual_hash,
+            } => Self {
+                offset: Some(*offset),
+                expected_hash: expected_hash.as_ref().map(hex::encode),
+                actual_hash: actual_hash.as_ref().map(hex::encode),
+                ..report("chunk_mismatch", relative_path)
+            },
+        }
+    }
+}
+
+/// Recomputes the manifest of the checkpoint at `height` (looked up among the
+/// checkpoints, diverged checkpoints and backups of the state root indicated
+/// in the given configuration file) and compares it to the manifest recorded
+/// in the states metadata, if any, and its root hash to `root_hash`, if given.
+/// Prints every mismatching file and chunk, as well as the root hash and the
+/// partial state hash of the checkpoint, either as text or as JSON. If no
+/// manifest is recorded for the checkpoint (as is the case for diverged
+/// checkpoints), the recomputed manifest is printed instead.
+pub fn do_verify(
+    config: PathBuf,
+    height: u64,
+    root_hash: Option<String>,
+    json: bool,
+) -> Result<(), String> {
+    let state_layout = utils::locate_state_root(config)?;
+    let report = verify_checkpoint(&state_layout, Height::new(height), root_hash)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report)
+                .map_err(|e| format!("failed to serialize report: {}", e))?
+        );
+    } else {
+        if let Some(manifest) = &report.recomputed_manifest {
+            println!("NO MANIFEST RECORDED, RECOMPUTED MANIFEST:");
+            println!("{}", manifest);
+        }
+        for mismatch in report.manifest_mismatches.iter() {
+            println!("MISMATCH: {}", mismatch);
+        }
+        if let Some(expected_root_hash) = &report.expected_root_hash {
+            println!("EXPECTED ROOT HASH: {}", expected_root_hash);
+        }
+        println!("ACTUAL ROOT HASH: {}", report.actual_root_hash);
+        println!("PARTIAL STATE HASH: {}", report.partial_state_hash);
+    }
+
+    report.verdict()
+}
+
+/// Verifies the checkpoint at `height` in `state_layout`, see `do_verify`.
+/// An explicitly given `root_hash` takes precedence over the root hash of the
+/// recorded manifest.
+fn verify_checkpoint(
+    state_layout: &StateLayout,
+    height: Height,
+    root_hash: Option<String>,
+) -> Result<VerificationReport, String> {
+    let root_hash = root_hash.map(|h| parse_root_hash(&h)).transpose()?;
+    let checkpoint = locate_checkpoint(state_layout, height)?;
+
+    let expected = recorded_manifest(state_layout, height)?;
+    let actual = checkpoint_manifest(checkpoint.clone())?;
+    let mismatches = expected
+        .as_ref()
+        .map(|expected| compare_manifests(expected, &actual))
+        .unwrap_or_default();
+
+    Ok(VerificationReport {
+        height: height.get(),
+        checkpoint: checkpoint.clone(),
+        expected_root_hash: root_hash
+            .or_else(|| expected.as_ref().map(|m| hex::encode(manifest_hash(m)))),
+        actual_root_hash: hex::encode(manifest_hash(&actual)),
+        partial_state_hash: partial_state_hash(checkpoint)?,
+        mismatches: mismatches.iter().map(MismatchReport::from).collect(),
+        manifest_mismatches: mismatches,
+        recomputed_manifest: if expected.is_none() {
+            Some(actual)
+        } else {
+            None
+        },
+    })
+}
+
+/// Parses a hex-encoded root hash, normalizing it to lowercase.
+fn parse_root_hash(root_hash: &str) -> Result<String, String> {
+    let bytes =
+        hex::decode(root_hash).map_err(|e| format!("invalid root hash {}: {}", root_hash, e))?;
+    if bytes.len() != 32 {
+        return Err(format!(
+            "invalid root hash {}: expected 32 bytes, got {}",
+            root_hash,
+            bytes.len()
+        ));
+    }
+    Ok(hex::encode(bytes))
+}
+
+/// Returns the path of the checkpoint, diverged checkpoint or backup at
+/// `height`, in this order of preference.
+fn locate_checkpoint(state_layout: &StateLayout, height: Height) -> Result<PathBuf, String> {
+    if let Ok(cp_layout) = state_layout.checkpoint(height) {
+        return Ok(cp_layout.raw_path().to_path_buf());
+    }
+    let diverged = state_layout
+        .diverged_checkpoint_heights()
+        .map_err(|e| format!("failed to enumerate diverged checkpoints: {}", e))?;
+    if diverged.contains(&height) {
+        return Ok(state_layout.diverged_checkpoint_path(height));
+    }
+    let backups = state_layout
+        .backup_heights()
+        .map_err(|e| format!("failed to enumerate backed up checkpoints: {}", e))?;
+    if backups.contains(&height) {
+        return Ok(state_layout.backup_checkpoint_path(height));
+    }
+    Err(format!("no checkpoint found at height {}", height))
+}
+
+/// Reads the manifest recorded for `height` in the states metadata, if any.
+fn recorded_manifest(
+    state_layout: &StateLayout,
+    height: Height,
+) -> Result<Option<Manifest>, String> {
+    let path = state_layout.states_metadata();
+    let file: ProtoFileWith<pb::StatesMetadata, ReadOnly> = path.clone().into();
+    let metadata = file
+        .deserialize_opt()
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+        .unwrap_or_default();
+    metadata
+        .by_height
+        .get(&height.get())
+        .and_then(|state_metadata| state_metadata.manifest.clone())
+        .map(|manifest| {
+            Manifest::try_from(manifest)
+                .map_err(|e| format!("failed to decode manifest for height {}: {}", height, e))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_config::state_manager::Config;
+    use ic_interfaces::state_manager::{CertificationScope, StateHashError, StateManager};
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_metrics::MetricsRegistry;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_state_manager::StateManagerImpl;
+    use ic_test_utilities::{consensus::fake::FakeVerifier, types::ids::subnet_test_id};
+    use std::{path::Path, sync::Arc, time::Duration};
+
+    /// Commits a checkpoint at `height` under `root`, records its manifest in
+    /// the states metadata and returns its hex-encoded root hash.
+    fn commit_checkpoint(root: &Path, height: Height) -> String {
+        let state_manager = StateManagerImpl::new(
+            Arc::new(FakeVerifier::new()),
+            subnet_test_id(42),
+            SubnetType::Application,
+            no_op_logger(),
+            &MetricsRegistry::new(),
+            &Config::new(root.into()),
+            ic_types::malicious_flags::MaliciousFlags::default(),
+        );
+        let (_height, state) = state_manager.take_tip();
+        state_manager.commit_and_certify(state, height, CertificationScope::Full);
+        let root_hash = loop {
+            match state_manager.get_state_hash_at(height) {
+                Ok(hash) => break hex::encode(hash.get().0),
+                Err(StateHashError::Transient(_)) => std::thread::sleep(Duration::from_millis(100)),
+                Err(err) => panic!("failed to compute the manifest @{}: {:?}", height, err),
+            }
+        };
+        // Persists the states metadata, including the computed manifest.
+        state_manager.remove_states_below(height);
+        root_hash
+    }
+
+    #[test]
+    fn verifies_checkpoint_against_recorded_manifest() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let height = Height::new(1);
+        let root_hash = commit_checkpoint(tmp.path(), height);
+        let state_layout = StateLayout::new(no_op_logger(), tmp.path().into());
+
+        let report = verify_checkpoint(&state_layout, height, None).unwrap();
+        assert_eq!(report.expected_root_hash, Some(root_hash.clone()));
+        assert_eq!(report.actual_root_hash, root_hash);
+        assert!(report.mismatches.is_empty());
+        assert!(report.recomputed_manifest.is_none());
+        assert_eq!(report.verdict(), Ok(()));
+    }
+
+    #[test]
+    fn verifies_diverged_checkpoint_without_recorded_manifest() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let height = Height::new(1);
+        let root_hash = commit_checkpoint(tmp.path(), height);
+        let state_layout = StateLayout::new(no_op_logger(), tmp.path().into());
+
+        // Diverged checkpoints are dropped from the states metadata.
+        state_layout.mark_checkpoint_diverged(height).unwrap();
+        std::fs::remove_file(state_layout.states_metadata()).unwrap();
+
+        // Without an expected root hash, the recomputed manifest is reported.
+        let report = verify_checkpoint(&state_layout, height, None).unwrap();
+        assert_eq!(
+            report.checkpoint,
+            state_layout.diverged_checkpoint_path(height)
+        );
+        assert_eq!(report.expected_root_hash, None);
+        assert_eq!(report.actual_root_hash, root_hash);
+        assert_eq!(
+            report
+                .recomputed_manifest
+                .as_ref()
+                .map(|manifest| hex::encode(manifest_hash(manifest))),
+            Some(root_hash.clone())
+        );
+        assert_eq!(report.verdict(), Ok(()));
+
+        // The root hash is checked against the expected one, if given.
+        let report =
+            verify_checkpoint(&state_layout, height, Some(root_hash.to_uppercase())).unwrap();
+        assert_eq!(report.expected_root_hash, Some(root_hash.clone()));
+        assert_eq!(report.verdict(), Ok(()));
+
+        let report = verify_checkpoint(&state_layout, height, Some("00".repeat(32))).unwrap();
+        assert!(report.verdict().is_err());
+
+        assert!(verify_checkpoint(&state_layout, height, Some("00".into())).is_err());
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //! A command-line tool to manage Internet Computer replicated states (decode
 //! persisted state files, diff checkpoints, compute partial state hashes and
-//! checkpoint manifests, import state trees).
+//! checkpoint manifests, verify checkpoints against their manifests, import
+//! state trees, simulate and perform subnet splits, list, export and import
+//! individual canisters).
 
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -41,6 +43,59 @@ enum Opt {
         height: u64,
     },
 
+    /// Lists the canisters of a checkpoint with their sizes, cycles balance,
+    /// queue occupancy and status.
+    #[structopt(name = "canisters")]
+    Canisters {
+        /// Path to a checkpoint.
+        #[structopt(long = "state")]
+        path: PathBuf,
+
+        /// Field to sort canisters by.
+        #[structopt(
+            long = "sort-by",
+            default_value = "id",
+            possible_values = &["id", "heap", "stable", "wasm", "total", "cycles", "queues"]
+        )]
+        sort_by: String,
+
+        /// Output format.
+        #[structopt(long = "format", default_value = "csv", possible_values = &["csv", "json"])]
+        format: String,
+    },
+
+    /// Exports the state of a canister in a checkpoint to an archive.
+    #[structopt(name = "export-canister")]
+    ExportCanister {
+        /// Path to a checkpoint.
+        #[structopt(long = "state")]
+        path: PathBuf,
+
+        /// ID of the canister to export.
+        #[structopt(long = "canister-id")]
+        canister_id: String,
+
+        /// Path of the archive to create.
+        #[structopt(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Imports a canister archive into a copy of a checkpoint.
+    #[structopt(name = "import-canister")]
+    ImportCanister {
+        /// Path to the archive created by `export-canister`.
+        #[structopt(long = "archive")]
+        archive: PathBuf,
+
+        /// Path to the checkpoint to import the canister into.
+        #[structopt(long = "state")]
+        path: PathBuf,
+
+        /// Path to write the resulting checkpoint to.
+        #[structopt(long = "output")]
+        output: PathBuf,
+    },
+
     /// Computes manifest of a checkpoint.
     #[structopt(name = "manifest")]
     Manifest {
@@ -49,6 +104,67 @@ enum Opt {
         path: PathBuf,
     },
 
+    /// Simulates splitting the subnet of a checkpoint in two.
+    #[structopt(name = "split")]
+    Split {
+        /// Path to a checkpoint.
+        #[structopt(long = "state")]
+        path: PathBuf,
+
+        /// ID of the subnet taking over the upper half of the canister ranges.
+        #[structopt(long = "new-subnet-id")]
+        new_subnet_id: String,
+    },
+
+    /// Splits the subnet of a checkpoint in two, writing the part of the
+    /// state retained by one half as a new checkpoint at the agreed split
+    /// height, and prints its root hash for the recovery CUP.
+    #[structopt(name = "split-checkpoint")]
+    SplitCheckpoint {
+        /// Path to the replica configuration (ic.json).
+        #[structopt(long = "config")]
+        config: PathBuf,
+
+        /// The height of the checkpoint of the subnet being split.
+        #[structopt(long = "height", short = "h")]
+        height: u64,
+
+        /// The height to write the split checkpoint at.
+        #[structopt(long = "split-height")]
+        split_height: u64,
+
+        /// ID of the subnet taking over the upper half of the canister ranges.
+        #[structopt(long = "new-subnet-id")]
+        new_subnet_id: String,
+
+        /// ID of the half to write the checkpoint for: either the ID of the
+        /// subnet being split or the new subnet ID.
+        #[structopt(long = "subnet-id")]
+        subnet_id: String,
+    },
+
+    /// Verifies a checkpoint against the manifest recorded for it and/or an
+    /// expected root hash.
+    #[structopt(name = "verify")]
+    Verify {
+        /// Path to the replica configuration (ic.json).
+        #[structopt(long = "config")]
+        config: PathBuf,
+
+        /// The height of the checkpoint to verify.
+        #[structopt(long = "height", short = "h")]
+        height: u64,
+
+        /// The expected hex-encoded root hash of the checkpoint, e.g. for
+        /// diverged checkpoints, which have no recorded manifest.
+        #[structopt(long = "root-hash")]
+        root_hash: Option<String>,
+
+        /// Print the verification report as JSON.
+        #[structopt(long = "json")]
+        json: bool,
+    },
+
     /// Enumerates persisted states.
     #[structopt(name = "list")]
     ListStates {
@@ -76,7 +192,45 @@ fn main() {
             config,
             height,
         } => commands::import_state::do_import(state, config, height),
+        Opt::Canisters {
+            path,
+            sort_by,
+            format,
+        } => commands::canisters::do_list_canisters(path, sort_by, format),
+        Opt::ExportCanister {
+            path,
+            canister_id,
+            output,
+        } => commands::export_canister::do_export_canister(path, canister_id, output),
+        Opt::ImportCanister {
+            archive,
+            path,
+            output,
+        } => commands::import_canister::do_import_canister(archive, path, output),
         Opt::Manifest { path } => commands::manifest::do_compute_manifest(path),
+        Opt::Split {
+            path,
+            new_subnet_id,
+        } => commands::split::do_split(path, new_subnet_id),
+        Opt::SplitCheckpoint {
+            config,
+            height,
+            split_height,
+            new_subnet_id,
+            subnet_id,
+        } => commands::split::do_split_checkpoint(
+            config,
+            height,
+            split_height,
+            new_subnet_id,
+            subnet_id,
+        ),
+        Opt::Verify {
+            config,
+            height,
+            root_hash,
+            json,
+        } => commands::verify::do_verify(config, height, root_hash, json),
         Opt::ListStates { config } => commands::list::do_list(config),
         Opt::Decode { file } => commands::decode::do_decode(file),
     };
@@ -25,6 +25,7 @@ use ic_types::{
     ingress::WasmResult,
     messages::{CallContextId, RejectContext, Request, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
     methods::{Callback, WasmClosure},
+    time::UNIX_EPOCH,
     user_error::RejectCode,
     CanisterId, Cycles, NumBytes, NumInstructions, PrincipalId, SubnetId, Time,
 };
@@ -32,15 +33,20 @@ use request_in_prep::{into_request, RequestInPrep};
 use serde::{Deserialize, Serialize};
 use stable_memory::StableMemory;
 use std::{
+    cell::Cell,
     collections::BTreeMap,
     convert::{From, TryFrom},
     sync::Arc,
+    time::Duration,
 };
 pub use system_state_accessor::SystemStateAccessor;
 pub use system_state_accessor_direct::SystemStateAccessorDirect;
 
 const MULTIPLIER_MAX_SIZE_LOCAL_SUBNET: u64 = 5;
 const MAX_NON_REPLICATED_QUERY_REPLY_SIZE: NumBytes = NumBytes::new(3 << 20);
+/// The longest timeout that a best-effort call can have. Larger values passed
+/// to `ic0.call_with_best_effort_response` are capped to this.
+const MAX_CALL_TIMEOUT: Duration = Duration::from_secs(300);
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[doc(hidden)]
@@ -460,6 +466,24 @@ impl ApiType {
         }
     }
 
+    /// Returns the time of the execution, if one is available. There is no
+    /// time for `canister_start`.
+    pub fn time(&self) -> Option<Time> {
+        match self {
+            ApiType::Start { .. } => None,
+            ApiType::Init { time, .. }
+            | ApiType::Heartbeat { time, .. }
+            | ApiType::Update { time, .. }
+            | ApiType::Cleanup { time, .. }
+            | ApiType::NonReplicatedQuery { time, .. }
+            | ApiType::ReplicatedQuery { time, .. }
+            | ApiType::PreUpgrade { time, .. }
+            | ApiType::ReplyCallback { time, .. }
+            | ApiType::RejectCallback { time, .. }
+            | ApiType::InspectMessage { time, .. } => Some(*time),
+        }
+    }
+
     /// Returns a string slice representation of the enum variant name for use
     /// e.g. as a metric label.
     pub fn as_str(&self) -> &'static str {
@@ -613,6 +637,8 @@ pub struct StaticSystemState {
     controller: PrincipalId,
     status: CanisterStatusView,
     subnet_type: SubnetType,
+    canister_version: u64,
+    wasm_memory_limit: Option<NumBytes>,
 }
 
 impl StaticSystemState {
@@ -622,12 +648,16 @@ impl StaticSystemState {
         controller: PrincipalId,
         status: CanisterStatusView,
         subnet_type: SubnetType,
+        canister_version: u64,
+        wasm_memory_limit: Option<NumBytes>,
     ) -> Self {
         Self {
             canister_id,
             controller,
             status,
             subnet_type,
+            canister_version,
+            wasm_memory_limit,
         }
     }
 
@@ -637,6 +667,8 @@ impl StaticSystemState {
             *system_state.controller(),
             CanisterStatusView::from_full_status(&system_state.status),
             subnet_type,
+            system_state.canister_version,
+            system_state.wasm_memory_limit,
         )
     }
 
@@ -670,6 +702,11 @@ pub struct SystemApiImpl<A: SystemStateAccessor> {
     /// communication between the sandboxed canister process and the main
     /// replica process.
     static_system_state: StaticSystemState,
+
+    /// Set once the message reads the time, the cycle balance of the canister
+    /// or the data certificate. These can change without the canister
+    /// version changing, so the result of such a query must not be cached.
+    reads_transient_state: Cell<bool>,
 }
 
 impl<A: SystemStateAccessor> SystemApiImpl<A> {
@@ -700,6 +737,7 @@ impl<A: SystemStateAccessor> SystemApiImpl<A> {
             stable_memory,
             static_system_state,
             log,
+            reads_transient_state: Cell::new(false),
         }
     }
 
@@ -909,6 +947,7 @@ impl<A: SystemStateAccessor> SystemApiImpl<A> {
     }
 
     fn ic0_canister_cycles_balance_helper(&self, method_name: &str) -> HypervisorResult<Cycles> {
+        self.reads_transient_state.set(true);
         match &self.api_type {
             ApiType::Start {} => Err(self.error_for(method_name)),
             ApiType::Init { .. }
@@ -999,6 +1038,44 @@ impl<A: SystemStateAccessor> SystemApiImpl<A> {
         }
     }
 
+    /// Returns true if the message read the time, the cycle balance of the
+    /// canister or the data certificate.
+    pub fn reads_transient_state(&self) -> bool {
+        self.reads_transient_state.get()
+    }
+
+    /// Returns `Err(HypervisorError::WasmMemoryLimitExceeded)` if a Wasm heap
+    /// of the given size is above the `wasm_memory_limit` of the canister.
+    ///
+    /// The limit only applies to messages that run as part of an update
+    /// call. In particular, `canister_pre_upgrade` is exempt so that a
+    /// canister whose heap outgrew the limit can still be upgraded.
+    pub fn check_wasm_memory_limit(&self, wasm_memory_size: NumWasmPages) -> HypervisorResult<()> {
+        let limit = match self.static_system_state.wasm_memory_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        match self.api_type {
+            ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::Heartbeat { .. } => {}
+            ApiType::Start
+            | ApiType::Init { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => return Ok(()),
+        }
+        let bytes = ic_replicated_state::num_bytes_try_from(wasm_memory_size)
+            .unwrap_or_else(|_| NumBytes::from(u64::MAX));
+        if bytes > limit {
+            return Err(HypervisorError::WasmMemoryLimitExceeded { bytes, limit });
+        }
+        Ok(())
+    }
+
     pub fn release_system_state_accessor(self) -> A {
         self.system_state_accessor
     }
@@ -1485,6 +1562,27 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32> {
+        match &self.api_type {
+            ApiType::Start {} => Err(self.error_for("ic0_is_controller")),
+            ApiType::Init { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Update { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::InspectMessage { .. } => {
+                let id_bytes = valid_subslice("ic0.is_controller", src, size, heap)?;
+                let principal_id =
+                    PrincipalId::try_from(id_bytes).map_err(HypervisorError::InvalidPrincipalId)?;
+                Ok(self.system_state_accessor.is_controller(&principal_id) as u32)
+            }
+        }
+    }
+
     fn ic0_call_simple(
         &mut self,
         callee_src: u32,
@@ -1595,6 +1693,8 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
                     on_reply,
                     on_reject,
                     None,
+                    Some(callee),
+                    None,
                 ));
 
                 let msg = Request {
@@ -1604,6 +1704,7 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
                     method_payload: payload,
                     sender_reply_callback: callback_id,
                     payment: Cycles::zero(),
+                    deadline: None,
                 };
                 self.push_output_request(msg)
             }
@@ -1747,6 +1848,52 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()> {
+        let time = self.api_type.time();
+        match &mut self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery {
+                query_kind: NonReplicatedQueryKind::Pure,
+                ..
+            }
+            | ApiType::Cleanup { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => {
+                Err(self.error_for("ic0_call_with_best_effort_response"))
+            }
+            ApiType::Update {
+                outgoing_request, ..
+            }
+            | ApiType::NonReplicatedQuery {
+                outgoing_request,
+                query_kind: NonReplicatedQueryKind::Stateful,
+                ..
+            }
+            | ApiType::Heartbeat {
+                outgoing_request, ..
+            }
+            | ApiType::ReplyCallback {
+                outgoing_request, ..
+            }
+            | ApiType::RejectCallback {
+                outgoing_request, ..
+            } => match outgoing_request {
+                None => Err(HypervisorError::ContractViolation(
+                    "ic0.call_with_best_effort_response called when no call is under construction."
+                        .to_string(),
+                )),
+                Some(request) => {
+                    // All the API types matched above have a time.
+                    let time = time.unwrap();
+                    let timeout = Duration::from_secs(timeout_seconds as u64).min(MAX_CALL_TIMEOUT);
+                    request.set_deadline(time + timeout)
+                }
+            },
+        }
+    }
+
     fn ic0_call_cycles_add(&mut self, amount: u64) -> HypervisorResult<()> {
         self.ic0_call_cycles_add_helper("ic0_call_cycles_add", Cycles::from(amount))
     }
@@ -2063,19 +2210,10 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
     }
 
     fn ic0_time(&self) -> HypervisorResult<Time> {
-        match &self.api_type {
-            ApiType::Start { .. } => Err(self.error_for("ic0_time")),
-            ApiType::Init { time, .. }
-            | ApiType::Heartbeat { time, .. }
-            | ApiType::Update { time, .. }
-            | ApiType::Cleanup { time, .. }
-            | ApiType::NonReplicatedQuery { time, .. }
-            | ApiType::ReplicatedQuery { time, .. }
-            | ApiType::PreUpgrade { time, .. }
-            | ApiType::ReplyCallback { time, .. }
-            | ApiType::RejectCallback { time, .. }
-            | ApiType::InspectMessage { time, .. } => Ok(*time),
-        }
+        self.reads_transient_state.set(true);
+        self.api_type
+            .time()
+            .ok_or_else(|| self.error_for("ic0_time"))
     }
 
     fn out_of_instructions(&self) -> HypervisorError {
@@ -2090,6 +2228,9 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         if native_memory_grow_res == -1 {
             return Ok(-1);
         }
+        self.check_wasm_memory_limit(NumWasmPages::from(
+            native_memory_grow_res as usize + additional_pages as usize,
+        ))?;
         match self.memory_usage.allocate_pages(additional_pages as usize) {
             Ok(()) => Ok(native_memory_grow_res),
             Err(_err) => Err(HypervisorError::OutOfMemory),
@@ -2176,6 +2317,7 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
     }
 
     fn ic0_data_certificate_present(&self) -> HypervisorResult<i32> {
+        self.reads_transient_state.set(true);
         match &self.api_type {
             ApiType::Start { .. }
             | ApiType::Init { .. }
@@ -2199,6 +2341,7 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
     }
 
     fn ic0_data_certificate_size(&self) -> HypervisorResult<i32> {
+        self.reads_transient_state.set(true);
         match &self.api_type {
             ApiType::Start { .. }
             | ApiType::Init { .. }
@@ -2228,6 +2371,7 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         size: u32,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
+        self.reads_transient_state.set(true);
         match &self.api_type {
             ApiType::Start { .. }
             | ApiType::Init { .. }
@@ -2322,6 +2466,26 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_global_timer_set(&mut self, time: Time) -> HypervisorResult<Time> {
+        match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::InspectMessage { .. } => Err(self.error_for("ic0_global_timer_set")),
+            ApiType::Init { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::Cleanup { .. } => {
+                let timer = if time == UNIX_EPOCH { None } else { Some(time) };
+                let previous_timer = self.system_state_accessor.set_global_timer(timer);
+                Ok(previous_timer.unwrap_or(UNIX_EPOCH))
+            }
+        }
+    }
+
     fn ic0_canister_status(&self) -> HypervisorResult<u32> {
         match &self.api_type {
             ApiType::Start { .. } => Err(self.error_for("ic0_canister_status")),
@@ -2342,6 +2506,22 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_canister_version(&self) -> HypervisorResult<u64> {
+        match &self.api_type {
+            ApiType::Start { .. } => Err(self.error_for("ic0_canister_version")),
+            ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::Init { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => Ok(self.static_system_state.canister_version),
+        }
+    }
+
     fn ic0_mint_cycles(&mut self, amount: u64) -> HypervisorResult<u64> {
         match self.api_type {
             ApiType::Start { .. }
@@ -2363,6 +2543,30 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_performance_counter(
+        &self,
+        counter_type: u32,
+        instruction_counter: i64,
+    ) -> HypervisorResult<u64> {
+        match counter_type {
+            0 => {
+                // The instruction counter starts at the instruction limit and
+                // counts down. It may become negative right before execution
+                // runs out of instructions.
+                let instructions_left = std::cmp::max(instruction_counter, 0) as u64;
+                Ok(self
+                    .execution_parameters
+                    .instruction_limit
+                    .get()
+                    .saturating_sub(instructions_left))
+            }
+            _ => Err(HypervisorError::ContractViolation(format!(
+                "Error getting performance counter: unsupported counter type {}",
+                counter_type
+            ))),
+        }
+    }
+
     fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]) {
         let msg = match valid_subslice("ic0.debug_print", src, size, heap) {
             Ok(bytes) => String::from_utf8_lossy(bytes).to_string(),
@@ -2377,6 +2581,8 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
             "[Canister {}] {}",
             self.static_system_state.canister_id, msg
         );
+        self.system_state_accessor
+            .append_canister_log(self.api_type.time().unwrap_or(UNIX_EPOCH), msg.into_bytes());
     }
 
     fn ic0_trap(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorError {
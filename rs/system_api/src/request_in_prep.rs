@@ -7,7 +7,7 @@ use ic_registry_subnet_type::SubnetType;
 use ic_types::{
     messages::{CallContextId, Request},
     methods::{Callback, WasmClosure},
-    CanisterId, Cycles, NumBytes, PrincipalId, SubnetId,
+    CanisterId, Cycles, NumBytes, PrincipalId, SubnetId, Time,
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, convert::TryFrom, sync::Arc};
@@ -40,6 +40,9 @@ pub struct RequestInPrep {
     cycles: Cycles,
     method_name: String,
     method_payload: Vec<u8>,
+    /// The deadline of a best-effort call, set by
+    /// `ic0.call_with_best_effort_response`.
+    deadline: Option<Time>,
     /// The maximum size of a message that will go to a canister on another
     /// subnet.
     max_size_remote_subnet: NumBytes,
@@ -97,6 +100,7 @@ impl RequestInPrep {
             cycles: Cycles::from(0),
             method_name,
             method_payload: Vec::new(),
+            deadline: None,
             max_size_remote_subnet,
             multiplier_max_size_local_subnet,
         })
@@ -114,6 +118,18 @@ impl RequestInPrep {
         }
     }
 
+    pub(crate) fn set_deadline(&mut self, deadline: Time) -> HypervisorResult<()> {
+        if self.deadline.is_some() {
+            Err(HypervisorError::ContractViolation(
+                "ic0.call_with_best_effort_response can be called at most once between `ic0.call_new` and `ic0.call_perform`"
+                    .to_string(),
+            ))
+        } else {
+            self.deadline = Some(deadline);
+            Ok(())
+        }
+    }
+
     pub(crate) fn take_cycles(self) -> Cycles {
         self.cycles
     }
@@ -158,6 +174,7 @@ pub(crate) fn into_request(
         cycles,
         method_name,
         method_payload,
+        deadline,
         max_size_remote_subnet,
         multiplier_max_size_local_subnet,
     }: RequestInPrep,
@@ -249,6 +266,8 @@ pub(crate) fn into_request(
         on_reply,
         on_reject,
         on_cleanup,
+        Some(destination_canister),
+        deadline,
     ));
 
     Ok(Request {
@@ -258,6 +277,7 @@ pub(crate) fn into_request(
         method_payload,
         sender_reply_callback: callback_id,
         payment: cycles,
+        deadline,
     })
 }
 
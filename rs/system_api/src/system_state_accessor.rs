@@ -4,7 +4,7 @@ use ic_replicated_state::StateError;
 use ic_types::{
     messages::{CallContextId, CallbackId, Request},
     methods::Callback,
-    ComputeAllocation, Cycles,
+    ComputeAllocation, Cycles, PrincipalId, Time,
 };
 
 /// The abstract interface through which canister user code can
@@ -37,9 +37,19 @@ pub trait SystemStateAccessor {
     /// reclaim cycles from unfulfilled requests.
     fn canister_cycles_refund(&self, cycles: Cycles);
 
+    /// Returns true if `principal_id` is one of the controllers of the
+    /// canister.
+    fn is_controller(&self, principal_id: &PrincipalId) -> bool;
+
     /// Set certified data.
     fn set_certified_data(&self, data: Vec<u8>);
 
+    /// Sets the deadline of the global timer and returns the previous one.
+    fn set_global_timer(&self, timer: Option<Time>) -> Option<Time>;
+
+    /// Appends a record to the canister log.
+    fn append_canister_log(&self, time: Time, content: Vec<u8>);
+
     /// Registers callback for call return.
     fn register_callback(&self, callback: Callback) -> CallbackId;
 
@@ -4,7 +4,7 @@ use ic_replicated_state::{StateError, SystemState};
 use ic_types::{
     messages::{CallContextId, CallbackId, Request},
     methods::Callback,
-    ComputeAllocation, Cycles,
+    ComputeAllocation, Cycles, PrincipalId, Time,
 };
 use std::ops::DerefMut;
 use std::{cell::RefCell, sync::Arc};
@@ -113,10 +113,28 @@ impl SystemStateAccessor for SystemStateAccessorDirect {
             .add_cycles(&mut self.system_state.borrow_mut(), cycles);
     }
 
+    fn is_controller(&self, principal_id: &PrincipalId) -> bool {
+        self.system_state
+            .borrow()
+            .controllers
+            .contains(principal_id)
+    }
+
     fn set_certified_data(&self, data: Vec<u8>) {
         self.system_state.borrow_mut().certified_data = data;
     }
 
+    fn set_global_timer(&self, timer: Option<Time>) -> Option<Time> {
+        std::mem::replace(&mut self.system_state.borrow_mut().global_timer, timer)
+    }
+
+    fn append_canister_log(&self, time: Time, content: Vec<u8>) {
+        self.system_state
+            .borrow_mut()
+            .canister_log
+            .add_record(time, content);
+    }
+
     fn register_callback(&self, callback: Callback) -> CallbackId {
         let mut system_state = self.system_state.borrow_mut();
         // A call context manager exists as the canister is either in
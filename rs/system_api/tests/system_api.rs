@@ -22,9 +22,12 @@ use ic_test_utilities::{
     },
 };
 use ic_types::{
-    messages::{CallContextId, CallbackId, RejectContext, MAX_RESPONSE_COUNT_BYTES},
+    messages::{
+        CallContextId, CallbackId, RejectContext, RequestOrResponse, MAX_RESPONSE_COUNT_BYTES,
+    },
+    time::UNIX_EPOCH,
     user_error::RejectCode,
-    ComputeAllocation, CountBytes, Cycles, NumBytes, NumInstructions,
+    ComputeAllocation, CountBytes, Cycles, NumBytes, NumInstructions, Time,
 };
 use std::convert::TryInto;
 use std::{convert::From, sync::Arc};
@@ -84,10 +87,12 @@ fn test_canister_init_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -111,8 +116,11 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -141,10 +149,12 @@ fn test_canister_update_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -168,8 +178,11 @@ fn test_canister_update_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -198,10 +211,12 @@ fn test_canister_replicated_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -225,8 +240,11 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -255,10 +273,12 @@ fn test_canister_pure_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -282,8 +302,11 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -322,10 +345,12 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -349,8 +374,11 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_data_certificate_size());
     assert_api_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -379,10 +407,12 @@ fn test_reply_api_support_on_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -406,8 +436,11 @@ fn test_reply_api_support_on_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -436,10 +469,12 @@ fn test_reply_api_support_non_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -463,8 +498,11 @@ fn test_reply_api_support_non_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -496,10 +534,12 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -523,8 +563,11 @@ fn test_reject_api_support_on_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -556,10 +599,12 @@ fn test_reject_api_support_non_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -583,8 +628,11 @@ fn test_reject_api_support_non_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -613,10 +661,12 @@ fn test_pre_upgrade_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -640,8 +690,11 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -666,10 +719,12 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_controller_size());
     assert_api_not_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_not_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_not_supported(api.ic0_stable_size());
@@ -693,8 +748,11 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_not_supported(api.ic0_canister_status());
+    assert_api_not_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -723,10 +781,12 @@ fn test_cleanup_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -750,8 +810,11 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -785,10 +848,12 @@ fn test_inspect_message_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -812,8 +877,11 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -843,10 +911,12 @@ fn test_canister_heartbeat_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -870,8 +940,11 @@ fn test_canister_heartbeat_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -900,10 +973,12 @@ fn test_canister_heartbeat_support_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(0));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_stable_size());
@@ -927,8 +1002,11 @@ fn test_canister_heartbeat_support_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_global_timer_set(mock_time()));
     assert_api_supported(api.ic0_canister_status());
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_mint_cycles(0));
+    assert_api_supported(api.ic0_performance_counter(0, 0));
 }
 
 #[test]
@@ -1211,6 +1289,34 @@ fn certified_data_set() {
     )
 }
 
+#[test]
+fn global_timer_set() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let system_state = SystemStateBuilder::default().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::new().build_update_api(),
+        system_state,
+        cycles_account_manager,
+    );
+    let deadline = Time::from_nanos_since_unix_epoch(1_000);
+
+    // The timer is initially not active.
+    assert_eq!(api.ic0_global_timer_set(deadline).unwrap(), UNIX_EPOCH);
+
+    // Setting the timer again returns the previous deadline.
+    assert_eq!(api.ic0_global_timer_set(deadline).unwrap(), deadline);
+
+    // Setting the timer to 0 deactivates it.
+    assert_eq!(api.ic0_global_timer_set(UNIX_EPOCH).unwrap(), deadline);
+    assert_eq!(api.ic0_global_timer_set(deadline).unwrap(), UNIX_EPOCH);
+
+    let system_state_accessor = api.release_system_state_accessor();
+    assert_eq!(
+        system_state_accessor.release_system_state().global_timer,
+        Some(deadline)
+    );
+}
+
 #[test]
 fn data_certificate_copy() {
     let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
@@ -1282,6 +1388,115 @@ fn canister_status() {
     assert_eq!(api.ic0_canister_status(), Ok(3));
 }
 
+#[test]
+fn is_controller() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+
+    let mut system_state = get_system_state_with_cycles(INITIAL_CYCLES);
+    system_state.controllers.insert(user_test_id(25).get());
+    let api = get_system_api(
+        ApiTypeBuilder::new().build_update_api(),
+        system_state,
+        cycles_account_manager,
+    );
+    // Both controllers are recognized, not only the first one.
+    for controller in [user_test_id(24), user_test_id(25)] {
+        let heap = controller.get().to_vec();
+        assert_eq!(api.ic0_is_controller(0, heap.len() as u32, &heap), Ok(1));
+    }
+    let heap = user_test_id(26).get().to_vec();
+    assert_eq!(api.ic0_is_controller(0, heap.len() as u32, &heap), Ok(0));
+
+    // A blob that is too long to be a principal id traps.
+    let heap = vec![0; 30];
+    assert!(matches!(
+        api.ic0_is_controller(0, heap.len() as u32, &heap),
+        Err(HypervisorError::InvalidPrincipalId(_))
+    ));
+}
+
+#[test]
+fn canister_version() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+
+    let mut system_state = get_system_state_with_cycles(INITIAL_CYCLES);
+    system_state.canister_version = 7;
+    let api = get_system_api(
+        ApiTypeBuilder::new().build_update_api(),
+        system_state,
+        cycles_account_manager,
+    );
+    assert_eq!(api.ic0_canister_version(), Ok(7));
+}
+
+#[test]
+fn call_with_best_effort_response_sets_capped_deadline() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::new().build_update_api(),
+        get_system_state(),
+        cycles_account_manager,
+    );
+    assert!(matches!(
+        api.ic0_call_with_best_effort_response(10),
+        Err(HypervisorError::ContractViolation(_))
+    ));
+
+    let callee = canister_test_id(33);
+    let heap = callee.get().to_vec();
+    api.ic0_call_new(0, heap.len() as u32, 0, 0, 0, 0, 0, 0, &heap)
+        .unwrap();
+    api.ic0_call_with_best_effort_response(u32::MAX).unwrap();
+    // Can only be called once per call.
+    assert!(matches!(
+        api.ic0_call_with_best_effort_response(10),
+        Err(HypervisorError::ContractViolation(_))
+    ));
+    assert_eq!(api.ic0_call_perform(), Ok(0));
+
+    // The timeout is capped at 5 minutes.
+    let deadline = mock_time() + std::time::Duration::from_secs(300);
+    let mut system_state = api.release_system_state_accessor().release_system_state();
+    let own_canister_id = system_state.canister_id();
+    let callbacks = system_state.call_context_manager().unwrap().callbacks();
+    assert_eq!(callbacks.len(), 1);
+    let callback = callbacks.values().next().unwrap();
+    assert_eq!(callback.deadline, Some(deadline));
+    assert_eq!(callback.respondent, Some(callee));
+    match system_state.output_into_iter(own_canister_id).next() {
+        Some((_, _, RequestOrResponse::Request(request))) => {
+            assert_eq!(request.deadline, Some(deadline))
+        }
+        msg => panic!("Expected a request, got {:?}", msg),
+    }
+}
+
+#[test]
+fn performance_counter() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let api = get_system_api(
+        ApiTypeBuilder::new().build_update_api(),
+        get_system_state_with_cycles(INITIAL_CYCLES),
+        cycles_account_manager,
+    );
+    let instruction_limit = execution_parameters().instruction_limit.get() as i64;
+
+    assert_eq!(api.ic0_performance_counter(0, instruction_limit), Ok(0));
+    assert_eq!(
+        api.ic0_performance_counter(0, instruction_limit - 1_000),
+        Ok(1_000)
+    );
+    // A negative counter means that all instructions have been used up.
+    assert_eq!(
+        api.ic0_performance_counter(0, -1),
+        Ok(instruction_limit as u64)
+    );
+    match api.ic0_performance_counter(1, instruction_limit) {
+        Err(HypervisorError::ContractViolation(_)) => {}
+        res => panic!("Expected a contract violation, got {:?}", res),
+    }
+}
+
 /// msg_cycles_accept() can accept all cycles in call context
 #[test]
 fn msg_cycles_accept_all_cycles_in_call_context() {
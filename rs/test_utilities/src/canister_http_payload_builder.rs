@@ -0,0 +1,35 @@
+use ic_interfaces::canister_http::{
+    CanisterHttpPayloadBuilder, CanisterHttpPayloadValidationError,
+};
+use ic_types::{
+    batch::ValidationContext, canister_http::CanisterHttpPayload, CountBytes, NumBytes,
+};
+
+#[derive(Default)]
+pub struct FakeCanisterHttpPayloadBuilder {}
+
+impl FakeCanisterHttpPayloadBuilder {
+    pub fn new() -> FakeCanisterHttpPayloadBuilder {
+        FakeCanisterHttpPayloadBuilder {}
+    }
+}
+
+impl CanisterHttpPayloadBuilder for FakeCanisterHttpPayloadBuilder {
+    fn get_canister_http_payload(
+        &self,
+        _validation_context: &ValidationContext,
+        _past_payloads: &[&CanisterHttpPayload],
+        _byte_limit: NumBytes,
+    ) -> CanisterHttpPayload {
+        CanisterHttpPayload::default()
+    }
+
+    fn validate_canister_http_payload(
+        &self,
+        payload: &CanisterHttpPayload,
+        _validation_context: &ValidationContext,
+        _past_payloads: &[&CanisterHttpPayload],
+    ) -> Result<NumBytes, CanisterHttpPayloadValidationError> {
+        Ok(NumBytes::from(payload.count_bytes() as u64))
+    }
+}
@@ -1,5 +1,6 @@
 pub mod artifact_pool_config;
 pub mod assert_utils;
+pub mod canister_http_payload_builder;
 pub mod certified_stream_store;
 pub mod consensus;
 pub mod crypto;
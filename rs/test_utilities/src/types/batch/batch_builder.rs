@@ -3,6 +3,7 @@ use ic_types::{
     batch::{Batch, BatchPayload},
     Height, Randomness, RegistryVersion, Time,
 };
+use std::collections::BTreeMap;
 
 pub struct BatchBuilder {
     batch: Batch,
@@ -20,6 +21,7 @@ impl Default for BatchBuilder {
                 registry_version: RegistryVersion::from(1),
                 time: mock_time(),
                 consensus_responses: vec![],
+                ecdsa_subnet_public_keys: BTreeMap::new(),
             },
         }
     }
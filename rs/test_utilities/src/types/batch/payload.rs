@@ -1,4 +1,5 @@
 use ic_types::batch::{BatchPayload, IngressPayload, SelfValidatingPayload, XNetPayload};
+use ic_types::canister_http::CanisterHttpPayload;
 
 pub struct PayloadBuilder {
     payload: BatchPayload,
@@ -13,6 +14,7 @@ impl Default for PayloadBuilder {
                 xnet: super::xnet_payload::XNetPayloadBuilder::default().build(),
                 // TODO(MR-70): use payload builder
                 self_validating: SelfValidatingPayload::new(),
+                canister_http: CanisterHttpPayload::default(),
             },
         }
     }
@@ -51,6 +53,7 @@ fn batch_payload_serialize_then_deserialize() {
         ingress: IngressPayload::from(vec![ingress_0]),
         xnet: XNetPayload::default(),
         self_validating: SelfValidatingPayload::default(),
+        canister_http: CanisterHttpPayload::default(),
     };
     let vec = serde_cbor::ser::to_vec(&batch_payload_0).unwrap();
     let batch_payload_1: BatchPayload = serde_cbor::de::from_slice(&vec).unwrap();
@@ -93,6 +96,7 @@ fn payload_serialize_then_deserialize() {
         ingress: IngressPayload::from(vec![ingress_0]),
         xnet: XNetPayload::default(),
         self_validating: SelfValidatingPayload::default(),
+        canister_http: CanisterHttpPayload::default(),
     };
     let payload_0 = Payload::new(
         ic_crypto::crypto_hash,
@@ -300,8 +300,8 @@ fn get_test_suites() -> HashMap<String, Suite> {
                     "basic_pot_with_all_features_enabled",
                     feature_flags::basic_config_with_all_features_enabled(),
                     par(vec![t(
-                        "mock_ecdsa_signatures_are_supported",
-                        feature_flags::mock_ecdsa_signatures_are_supported,
+                        "ecdsa_signatures_with_unknown_key_are_rejected",
+                        feature_flags::ecdsa_signatures_with_unknown_key_are_rejected,
                     )]),
                 ),
             ],
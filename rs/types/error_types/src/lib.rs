@@ -19,6 +19,7 @@ pub enum RejectCode {
     DestinationInvalid = 3,
     CanisterReject = 4,
     CanisterError = 5,
+    SysUnknown = 6,
 }
 
 impl ToString for RejectCode {
@@ -35,6 +36,7 @@ impl RejectCode {
             RejectCode::DestinationInvalid => "DESTINATION_INVALID",
             RejectCode::CanisterReject => "CANISTER_REJECT",
             RejectCode::CanisterError => "CANISTER_ERROR",
+            RejectCode::SysUnknown => "SYS_UNKNOWN",
         }
     }
 }
@@ -48,6 +50,7 @@ impl TryFrom<u64> for RejectCode {
             3 => Ok(RejectCode::DestinationInvalid),
             4 => Ok(RejectCode::CanisterReject),
             5 => Ok(RejectCode::CanisterError),
+            6 => Ok(RejectCode::SysUnknown),
             _ => Err(ProxyDecodeError::ValueOutOfRange {
                 typ: "RejectCode",
                 err: code.to_string(),
@@ -70,6 +73,7 @@ impl From<ErrorCode> for RejectCode {
             CanisterWasmModuleNotFound => DestinationInvalid,
             CanisterAlreadyInstalled => DestinationInvalid,
             CanisterEmpty => DestinationInvalid,
+            CanisterSnapshotNotFound => DestinationInvalid,
             CanisterNonEmpty => CanisterError,
             CanisterOutOfCycles => CanisterError,
             CanisterTrapped => CanisterError,
@@ -96,6 +100,10 @@ impl From<ErrorCode> for RejectCode {
             InsufficientCyclesInCall => CanisterError,
             CanisterWasmEngineError => CanisterError,
             CanisterCyclesLimitExceeded => CanisterError,
+            CompositeQueryCalledInReplicatedMode => CanisterError,
+            QueryCallGraphTooDeep => CanisterError,
+            QueryCallGraphTotalInstructionLimitExceeded => CanisterError,
+            CanisterWasmMemoryLimitExceeded => CanisterError,
         }
     }
 }
@@ -117,6 +125,7 @@ pub enum ErrorCode {
     CanisterAlreadyInstalled = 303,
     CanisterWasmModuleNotFound = 304,
     CanisterEmpty = 305,
+    CanisterSnapshotNotFound = 306,
     InsufficientTransferFunds = 401,
     InsufficientMemoryAllocation = 402,
     InsufficientCyclesForCreateCanister = 403,
@@ -143,6 +152,10 @@ pub enum ErrorCode {
     InsufficientCyclesInCall = 520,
     CanisterWasmEngineError = 521,
     CanisterCyclesLimitExceeded = 522,
+    CompositeQueryCalledInReplicatedMode = 523,
+    QueryCallGraphTooDeep = 524,
+    QueryCallGraphTotalInstructionLimitExceeded = 525,
+    CanisterWasmMemoryLimitExceeded = 526,
 }
 
 impl From<candid::Error> for UserError {
@@ -167,6 +180,7 @@ impl TryFrom<u64> for ErrorCode {
             303 => Ok(ErrorCode::CanisterAlreadyInstalled),
             304 => Ok(ErrorCode::CanisterWasmModuleNotFound),
             305 => Ok(ErrorCode::CanisterEmpty),
+            306 => Ok(ErrorCode::CanisterSnapshotNotFound),
             401 => Ok(ErrorCode::InsufficientTransferFunds),
             402 => Ok(ErrorCode::InsufficientMemoryAllocation),
             403 => Ok(ErrorCode::InsufficientCyclesForCreateCanister),
@@ -193,6 +207,10 @@ impl TryFrom<u64> for ErrorCode {
             520 => Ok(ErrorCode::InsufficientCyclesInCall),
             521 => Ok(ErrorCode::CanisterWasmEngineError),
             522 => Ok(ErrorCode::CanisterCyclesLimitExceeded),
+            523 => Ok(ErrorCode::CompositeQueryCalledInReplicatedMode),
+            524 => Ok(ErrorCode::QueryCallGraphTooDeep),
+            525 => Ok(ErrorCode::QueryCallGraphTotalInstructionLimitExceeded),
+            526 => Ok(ErrorCode::CanisterWasmMemoryLimitExceeded),
             _ => Err(ProxyDecodeError::ValueOutOfRange {
                 typ: "ErrorCode",
                 err: err.to_string(),
@@ -5,8 +5,10 @@ use ic_base_types::{
     RegistryVersion, SubnetId,
 };
 use ic_error_types::{ErrorCode, UserError};
+use ic_protobuf::proxy::{try_from_option_field, ProxyDecodeError};
 use ic_protobuf::registry::crypto::v1::PublicKey;
-use ic_protobuf::registry::subnet::v1::InitialNiDkgTranscriptRecord;
+use ic_protobuf::registry::subnet::v1::{self as pb_subnet, InitialNiDkgTranscriptRecord};
+use ic_protobuf::state::canister_state_bits::v1 as pb_canister_state_bits;
 use num_traits::cast::ToPrimitive;
 use serde::Serialize;
 use std::{collections::BTreeSet, convert::TryFrom};
@@ -20,28 +22,39 @@ pub const MAX_CONTROLLERS: usize = 10;
 #[derive(Debug, EnumString, EnumIter, ToString, Copy, Clone)]
 #[strum(serialize_all = "snake_case")]
 pub enum Method {
+    BitcoinGetBalance,
+    BitcoinGetUtxos,
+    BitcoinSendTransaction,
+    CanisterInfo,
     CanisterStatus,
+    ClearChunkStore,
     CreateCanister,
     DeleteCanister,
+    DeleteCanisterSnapshot,
     DepositCycles,
+    ECDSAPublicKey,
+    FetchCanisterLogs,
+    HttpRequest,
+    InstallChunkedCode,
     InstallCode,
+    ListCanisterSnapshots,
+    LoadCanisterSnapshot,
     RawRand,
     SetController,
     SetupInitialDKG,
     SignWithECDSA,
     StartCanister,
     StopCanister,
+    StoredChunks,
+    TakeCanisterSnapshot,
     UninstallCode,
     UpdateSettings,
+    UploadChunk,
 
     // These methods are added for the Mercury I release.
     // They should be removed afterwards.
     ProvisionalCreateCanisterWithCycles,
     ProvisionalTopUpCanister,
-
-    // Mock implementations
-    GetMockECDSAPublicKey,
-    SignWithMockECDSA,
 }
 
 /// A trait to be implemented by all structs that are used as payloads
@@ -85,6 +98,7 @@ impl From<CanisterId> for CanisterIdRecord {
 ///     controller : principal;
 ///     compute_allocation: nat;
 ///     memory_allocation: opt nat;
+///     wasm_memory_limit: nat;
 /// })`
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
 pub struct DefiniteCanisterSettingsArgs {
@@ -93,6 +107,7 @@ pub struct DefiniteCanisterSettingsArgs {
     compute_allocation: candid::Nat,
     memory_allocation: candid::Nat,
     freezing_threshold: candid::Nat,
+    wasm_memory_limit: candid::Nat,
 }
 
 impl DefiniteCanisterSettingsArgs {
@@ -102,6 +117,7 @@ impl DefiniteCanisterSettingsArgs {
         compute_allocation: u64,
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
+        wasm_memory_limit: Option<u64>,
     ) -> Self {
         let memory_allocation = match memory_allocation {
             None => candid::Nat::from(0),
@@ -113,12 +129,18 @@ impl DefiniteCanisterSettingsArgs {
             compute_allocation: candid::Nat::from(compute_allocation),
             memory_allocation,
             freezing_threshold: candid::Nat::from(freezing_threshold),
+            wasm_memory_limit: candid::Nat::from(wasm_memory_limit.unwrap_or(0)),
         }
     }
 
     pub fn controllers(&self) -> Vec<PrincipalId> {
         self.controllers.clone()
     }
+
+    /// The Wasm memory limit of the canister, 0 if it has none.
+    pub fn wasm_memory_limit(&self) -> u64 {
+        self.wasm_memory_limit.0.to_u64().unwrap()
+    }
 }
 
 impl Payload<'_> for DefiniteCanisterSettingsArgs {}
@@ -187,6 +209,7 @@ impl Payload<'_> for CanisterStatusResult {}
 ///     controller: principal;
 ///     memory_size: nat;
 ///     cycles: nat;
+///     canister_version: nat64;
 /// })`
 #[derive(CandidType, Debug, Deserialize, Eq, PartialEq)]
 pub struct CanisterStatusResultV2 {
@@ -199,6 +222,7 @@ pub struct CanisterStatusResultV2 {
     // this is for compat with Spec 0.12/0.13
     balance: Vec<(Vec<u8>, candid::Nat)>,
     freezing_threshold: candid::Nat,
+    canister_version: u64,
 }
 
 impl CanisterStatusResultV2 {
@@ -213,6 +237,8 @@ impl CanisterStatusResultV2 {
         compute_allocation: u64,
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
+        canister_version: u64,
+        wasm_memory_limit: Option<u64>,
     ) -> Self {
         Self {
             status,
@@ -229,8 +255,10 @@ impl CanisterStatusResultV2 {
                 compute_allocation,
                 memory_allocation,
                 freezing_threshold,
+                wasm_memory_limit,
             ),
             freezing_threshold: candid::Nat::from(freezing_threshold),
+            canister_version,
         }
     }
 
@@ -257,6 +285,14 @@ impl CanisterStatusResultV2 {
     pub fn freezing_threshold(&self) -> u64 {
         self.freezing_threshold.0.to_u64().unwrap()
     }
+
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version
+    }
+
+    pub fn settings(&self) -> &DefiniteCanisterSettingsArgs {
+        &self.settings
+    }
 }
 
 impl Payload<'_> for CanisterStatusResultV2 {}
@@ -382,12 +418,32 @@ impl UpdateSettingsArgs {
 
 impl Payload<'_> for UpdateSettingsArgs {}
 
+/// Who may fetch the logs of a canister via `fetch_canister_logs`.
+///
+/// `(variant { controllers; public; })`
+#[derive(CandidType, Clone, Copy, Deserialize, Debug, PartialEq, Eq, Serialize)]
+pub enum LogVisibility {
+    #[serde(rename = "controllers")]
+    Controllers,
+    #[serde(rename = "public")]
+    Public,
+}
+
+impl Default for LogVisibility {
+    fn default() -> Self {
+        Self::Controllers
+    }
+}
+
 /// Struct used for encoding/decoding
 /// `(record {
 ///     controller : opt principal;
 ///     controllers: opt vec principal;
 ///     compute_allocation: opt nat;
 ///     memory_allocation: opt nat;
+///     freezing_threshold: opt nat;
+///     log_visibility: opt log_visibility;
+///     wasm_memory_limit: opt nat;
 /// })`
 #[derive(Default, Clone, CandidType, Deserialize, Debug)]
 pub struct CanisterSettingsArgs {
@@ -396,6 +452,10 @@ pub struct CanisterSettingsArgs {
     pub compute_allocation: Option<candid::Nat>,
     pub memory_allocation: Option<candid::Nat>,
     pub freezing_threshold: Option<candid::Nat>,
+    pub log_visibility: Option<LogVisibility>,
+    /// An upper bound on the Wasm heap of the canister. Update calls that
+    /// grow the heap beyond it trap. 0 removes the limit.
+    pub wasm_memory_limit: Option<candid::Nat>,
 }
 
 impl Payload<'_> for CanisterSettingsArgs {}
@@ -612,24 +672,749 @@ impl ProvisionalTopUpCanisterArgs {
 
 impl Payload<'_> for ProvisionalTopUpCanisterArgs {}
 
+/// The elliptic curves supported by threshold ECDSA.
+///
+/// `(variant { secp256k1; })`
+#[derive(CandidType, Clone, Copy, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EcdsaCurve {
+    #[serde(rename = "secp256k1")]
+    Secp256k1,
+}
+
+impl From<EcdsaCurve> for pb_subnet::EcdsaCurve {
+    fn from(curve: EcdsaCurve) -> Self {
+        match curve {
+            EcdsaCurve::Secp256k1 => pb_subnet::EcdsaCurve::Secp256k1,
+        }
+    }
+}
+
+impl TryFrom<pb_subnet::EcdsaCurve> for EcdsaCurve {
+    type Error = ProxyDecodeError;
+
+    fn try_from(curve: pb_subnet::EcdsaCurve) -> Result<Self, Self::Error> {
+        match curve {
+            pb_subnet::EcdsaCurve::Secp256k1 => Ok(EcdsaCurve::Secp256k1),
+            pb_subnet::EcdsaCurve::Unspecified => Err(ProxyDecodeError::ValueOutOfRange {
+                typ: "EcdsaCurve",
+                err: format!("Unable to convert {:?} to an EcdsaCurve", curve),
+            }),
+        }
+    }
+}
+
+/// The identifier of a threshold ECDSA key held by a subnet.
+///
+/// `(record {
+///     curve: ecdsa_curve;
+///     name: text;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EcdsaKeyId {
+    pub curve: EcdsaCurve,
+    pub name: String,
+}
+
+impl From<&EcdsaKeyId> for pb_subnet::EcdsaKeyId {
+    fn from(item: &EcdsaKeyId) -> Self {
+        Self {
+            curve: pb_subnet::EcdsaCurve::from(item.curve) as i32,
+            name: item.name.clone(),
+        }
+    }
+}
+
+impl TryFrom<pb_subnet::EcdsaKeyId> for EcdsaKeyId {
+    type Error = ProxyDecodeError;
+
+    fn try_from(item: pb_subnet::EcdsaKeyId) -> Result<Self, Self::Error> {
+        let curve = pb_subnet::EcdsaCurve::from_i32(item.curve).ok_or_else(|| {
+            ProxyDecodeError::ValueOutOfRange {
+                typ: "EcdsaKeyId::curve",
+                err: format!("Unknown ECDSA curve: {}", item.curve),
+            }
+        })?;
+        Ok(Self {
+            curve: EcdsaCurve::try_from(curve)?,
+            name: item.name,
+        })
+    }
+}
+
+impl std::fmt::Display for EcdsaKeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}:{}", self.curve, self.name)
+    }
+}
+
 /// Struct used for encoding/decoding
 /// `(record {
-/// message_hash : blob;
-/// derivation_path : blob;
+///     message_hash : blob;
+///     derivation_path : vec blob;
+///     key_id : ecdsa_key_id;
 /// })`
 #[derive(CandidType, Deserialize, Debug)]
 pub struct SignWithECDSAArgs {
+    #[serde(with = "serde_bytes")]
     pub message_hash: Vec<u8>,
-    pub derivation_path: Vec<u8>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: EcdsaKeyId,
 }
 
 impl Payload<'_> for SignWithECDSAArgs {}
 
 impl SignWithECDSAArgs {
-    pub fn new(message_hash: Vec<u8>, derivation_path: Vec<u8>) -> Self {
+    pub fn new(message_hash: Vec<u8>, derivation_path: Vec<Vec<u8>>, key_id: EcdsaKeyId) -> Self {
         Self {
             message_hash,
             derivation_path,
+            key_id,
         }
     }
 }
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     signature : blob;
+/// })`
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct SignWithECDSAReply {
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl Payload<'_> for SignWithECDSAReply {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id : opt canister_id;
+///     derivation_path : vec blob;
+///     key_id : ecdsa_key_id;
+/// })`
+#[derive(CandidType, Deserialize, Debug)]
+pub struct ECDSAPublicKeyArgs {
+    pub canister_id: Option<CanisterId>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: EcdsaKeyId,
+}
+
+impl Payload<'_> for ECDSAPublicKeyArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     public_key : blob;
+///     chain_code : blob;
+/// })`
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct ECDSAPublicKeyResponse {
+    #[serde(with = "serde_bytes")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub chain_code: Vec<u8>,
+}
+
+impl Payload<'_> for ECDSAPublicKeyResponse {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     name: text;
+///     value: text;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl Payload<'_> for HttpHeader {}
+
+/// The HTTP methods a canister may use with `http_request`.
+#[derive(CandidType, Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+pub enum HttpMethod {
+    #[serde(rename = "get")]
+    GET,
+    #[serde(rename = "post")]
+    POST,
+    #[serde(rename = "head")]
+    HEAD,
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     url : text;
+///     max_response_bytes: opt nat64;
+///     headers: vec http_header;
+///     body : opt blob;
+///     method: variant { get; head; post };
+///     transform_method_name : opt text;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug)]
+pub struct CanisterHttpRequestArgs {
+    pub url: String,
+    pub max_response_bytes: Option<u64>,
+    pub headers: Vec<HttpHeader>,
+    pub body: Option<Vec<u8>>,
+    pub method: HttpMethod,
+    pub transform_method_name: Option<String>,
+}
+
+impl Payload<'_> for CanisterHttpRequestArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     status: nat;
+///     headers: vec http_header;
+///     body: blob;
+/// })`
+///
+/// This is both the reply of `http_request` and the argument and reply of a
+/// canister's transform function.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterHttpResponsePayload {
+    pub status: u64,
+    pub headers: Vec<HttpHeader>,
+    pub body: Vec<u8>,
+}
+
+impl Payload<'_> for CanisterHttpResponsePayload {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     address: text;
+///     min_confirmations: opt nat32;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct BitcoinGetBalanceArgs {
+    pub address: String,
+    pub min_confirmations: Option<u32>,
+}
+
+impl Payload<'_> for BitcoinGetBalanceArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     address: text;
+///     min_confirmations: opt nat32;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct BitcoinGetUtxosArgs {
+    pub address: String,
+    pub min_confirmations: Option<u32>,
+}
+
+impl Payload<'_> for BitcoinGetUtxosArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     txid: blob;
+///     vout: nat32;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BitcoinOutPoint {
+    #[serde(with = "serde_bytes")]
+    pub txid: Vec<u8>,
+    pub vout: u32,
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     outpoint: outpoint;
+///     value: nat64;
+///     height: nat32;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct BitcoinUtxo {
+    pub outpoint: BitcoinOutPoint,
+    pub value: u64,
+    pub height: u32,
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     utxos: vec utxo;
+///     tip_height: nat32;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct BitcoinGetUtxosResponse {
+    pub utxos: Vec<BitcoinUtxo>,
+    pub tip_height: u32,
+}
+
+impl Payload<'_> for BitcoinGetUtxosResponse {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     transaction: blob;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct BitcoinSendTransactionArgs {
+    #[serde(with = "serde_bytes")]
+    pub transaction: Vec<u8>,
+}
+
+impl Payload<'_> for BitcoinSendTransactionArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     replace_snapshot: opt nat64;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct TakeCanisterSnapshotArgs {
+    canister_id: PrincipalId,
+    replace_snapshot: Option<u64>,
+}
+
+impl TakeCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, replace_snapshot: Option<u64>) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            replace_snapshot,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn replace_snapshot(&self) -> Option<u64> {
+        self.replace_snapshot
+    }
+}
+
+impl Payload<'_> for TakeCanisterSnapshotArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     snapshot_id: nat64;
+/// })`
+///
+/// Used as the argument of both `load_canister_snapshot` and
+/// `delete_canister_snapshot`.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterSnapshotArgs {
+    canister_id: PrincipalId,
+    snapshot_id: u64,
+}
+
+impl CanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, snapshot_id: u64) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            snapshot_id,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn snapshot_id(&self) -> u64 {
+        self.snapshot_id
+    }
+}
+
+impl Payload<'_> for CanisterSnapshotArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     id: nat64;
+///     taken_at_timestamp: nat64;
+///     total_size: nat64;
+/// })`
+///
+/// Returned by `take_canister_snapshot` and, as a vector, by
+/// `list_canister_snapshots`.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterSnapshotResponse {
+    pub id: u64,
+    pub taken_at_timestamp: u64,
+    pub total_size: u64,
+}
+
+impl Payload<'_> for CanisterSnapshotResponse {}
+
+impl Payload<'_> for Vec<CanisterSnapshotResponse> {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     chunk: blob;
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct UploadChunkArgs {
+    canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    pub chunk: Vec<u8>,
+}
+
+impl UploadChunkArgs {
+    pub fn new(canister_id: CanisterId, chunk: Vec<u8>) -> Self {
+        Self {
+            canister_id: canister_id.into(),
+            chunk,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+}
+
+impl Payload<'_> for UploadChunkArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     hash: blob;
+/// })`
+///
+/// The SHA-256 hash of a chunk in the Wasm chunk store of a canister.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct ChunkHash {
+    #[serde(with = "serde_bytes")]
+    pub hash: Vec<u8>,
+}
+
+impl Payload<'_> for ChunkHash {}
+
+/// The reply of `upload_chunk`: the hash of the uploaded chunk.
+pub type UploadChunkReply = ChunkHash;
+
+/// Struct used for encoding/decoding `(vec record { hash: blob })`.
+///
+/// The reply of `stored_chunks`: the hashes of all chunks in the Wasm chunk
+/// store of a canister.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct StoredChunksReply(pub Vec<ChunkHash>);
+
+impl Payload<'_> for StoredChunksReply {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     mode : variant { install; reinstall; upgrade };
+///     canister_id: principal;
+///     chunk_hashes_list: vec record { hash: blob };
+///     wasm_module_hash: blob;
+///     arg: blob;
+/// })`
+///
+/// The Wasm module is the concatenation of the chunks listed in
+/// `chunk_hashes_list`, taken from the chunk store of `canister_id`, and must
+/// hash to `wasm_module_hash`.
+#[derive(Clone, CandidType, Deserialize, Debug)]
+pub struct InstallChunkedCodeArgs {
+    pub mode: CanisterInstallMode,
+    canister_id: PrincipalId,
+    pub chunk_hashes_list: Vec<ChunkHash>,
+    #[serde(with = "serde_bytes")]
+    pub wasm_module_hash: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub arg: Vec<u8>,
+}
+
+impl InstallChunkedCodeArgs {
+    pub fn new(
+        mode: CanisterInstallMode,
+        canister_id: CanisterId,
+        chunk_hashes_list: Vec<Vec<u8>>,
+        wasm_module_hash: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Self {
+        Self {
+            mode,
+            canister_id: canister_id.into(),
+            chunk_hashes_list: chunk_hashes_list
+                .into_iter()
+                .map(|hash| ChunkHash { hash })
+                .collect(),
+            wasm_module_hash,
+            arg,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+}
+
+impl Payload<'_> for InstallChunkedCodeArgs {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     idx: nat64;
+///     timestamp_nanos: nat64;
+///     content: blob;
+/// })`
+///
+/// A `debug_print` or trap message of a canister.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq, Serialize)]
+pub struct CanisterLogRecord {
+    pub idx: u64,
+    pub timestamp_nanos: u64,
+    #[serde(with = "serde_bytes")]
+    pub content: Vec<u8>,
+}
+
+impl Payload<'_> for CanisterLogRecord {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_log_records: vec canister_log_record;
+/// })`
+///
+/// The reply of `fetch_canister_logs`. The argument of the method is a
+/// `CanisterIdRecord`.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct FetchCanisterLogsResponse {
+    pub canister_log_records: Vec<CanisterLogRecord>,
+}
+
+impl Payload<'_> for FetchCanisterLogsResponse {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     num_requested_changes: opt nat64;
+/// })`
+#[derive(CandidType, Deserialize, Debug)]
+pub struct CanisterInfoRequest {
+    canister_id: PrincipalId,
+    num_requested_changes: Option<u64>,
+}
+
+impl CanisterInfoRequest {
+    pub fn new(canister_id: CanisterId, num_requested_changes: Option<u64>) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+            num_requested_changes,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        // Safe as this was converted from CanisterId when Self was constructed.
+        CanisterId::new(self.canister_id).unwrap()
+    }
+
+    pub fn num_requested_changes(&self) -> Option<u64> {
+        self.num_requested_changes
+    }
+}
+
+impl Payload<'_> for CanisterInfoRequest {}
+
+/// `(variant {
+///     from_user: record { user_id: principal };
+///     from_canister: record { canister_id: principal };
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum CanisterChangeOrigin {
+    #[serde(rename = "from_user")]
+    FromUser { user_id: PrincipalId },
+    #[serde(rename = "from_canister")]
+    FromCanister { canister_id: PrincipalId },
+}
+
+/// `(variant {
+///     creation: record { controllers: vec principal };
+///     code_uninstall;
+///     code_deployment: record {
+///         mode: variant { install; reinstall; upgrade };
+///         module_hash: blob;
+///     };
+///     controllers_change: record { controllers: vec principal };
+/// })`
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub enum CanisterChangeDetails {
+    #[serde(rename = "creation")]
+    Creation { controllers: Vec<PrincipalId> },
+    #[serde(rename = "code_uninstall")]
+    CodeUninstall,
+    #[serde(rename = "code_deployment")]
+    CodeDeployment {
+        mode: CanisterInstallMode,
+        #[serde(with = "serde_bytes")]
+        module_hash: Vec<u8>,
+    },
+    #[serde(rename = "controllers_change")]
+    ControllersChange { controllers: Vec<PrincipalId> },
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     timestamp_nanos: nat64;
+///     canister_version: nat64;
+///     origin: change_origin;
+///     details: change_details;
+/// })`
+///
+/// A change of a canister as recorded in its history.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterChange {
+    pub timestamp_nanos: u64,
+    pub canister_version: u64,
+    pub origin: CanisterChangeOrigin,
+    pub details: CanisterChangeDetails,
+}
+
+impl From<&CanisterChange> for pb_canister_state_bits::CanisterChange {
+    fn from(item: &CanisterChange) -> Self {
+        use pb_canister_state_bits::canister_change::{ChangeDetails, ChangeOrigin};
+        let change_origin = match &item.origin {
+            CanisterChangeOrigin::FromUser { user_id } => ChangeOrigin::CanisterChangeFromUser(
+                pb_canister_state_bits::CanisterChangeFromUser {
+                    user_id: Some((*user_id).into()),
+                },
+            ),
+            CanisterChangeOrigin::FromCanister { canister_id } => {
+                ChangeOrigin::CanisterChangeFromCanister(
+                    pb_canister_state_bits::CanisterChangeFromCanister {
+                        canister_id: Some((*canister_id).into()),
+                    },
+                )
+            }
+        };
+        let change_details = match &item.details {
+            CanisterChangeDetails::Creation { controllers } => {
+                ChangeDetails::CanisterCreation(pb_canister_state_bits::CanisterChangeCreation {
+                    controllers: controllers.iter().map(|c| (*c).into()).collect(),
+                })
+            }
+            CanisterChangeDetails::CodeUninstall => ChangeDetails::CanisterCodeUninstall(
+                pb_canister_state_bits::CanisterChangeCodeUninstall {},
+            ),
+            CanisterChangeDetails::CodeDeployment { mode, module_hash } => {
+                let mode = match mode {
+                    CanisterInstallMode::Install => {
+                        pb_canister_state_bits::CanisterInstallMode::Install
+                    }
+                    CanisterInstallMode::Reinstall => {
+                        pb_canister_state_bits::CanisterInstallMode::Reinstall
+                    }
+                    CanisterInstallMode::Upgrade => {
+                        pb_canister_state_bits::CanisterInstallMode::Upgrade
+                    }
+                };
+                ChangeDetails::CanisterCodeDeployment(
+                    pb_canister_state_bits::CanisterChangeCodeDeployment {
+                        mode: mode as i32,
+                        module_hash: module_hash.clone(),
+                    },
+                )
+            }
+            CanisterChangeDetails::ControllersChange { controllers } => {
+                ChangeDetails::CanisterControllersChange(
+                    pb_canister_state_bits::CanisterChangeControllersChange {
+                        controllers: controllers.iter().map(|c| (*c).into()).collect(),
+                    },
+                )
+            }
+        };
+        Self {
+            timestamp_nanos: item.timestamp_nanos,
+            canister_version: item.canister_version,
+            change_origin: Some(change_origin),
+            change_details: Some(change_details),
+        }
+    }
+}
+
+impl TryFrom<pb_canister_state_bits::CanisterChange> for CanisterChange {
+    type Error = ProxyDecodeError;
+
+    fn try_from(item: pb_canister_state_bits::CanisterChange) -> Result<Self, Self::Error> {
+        use pb_canister_state_bits::canister_change::{ChangeDetails, ChangeOrigin};
+        let principals = |ids: Vec<ic_protobuf::types::v1::PrincipalId>| {
+            ids.into_iter()
+                .map(|id| PrincipalId::try_from(id).map_err(ProxyDecodeError::from))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let origin = match item.change_origin {
+            Some(ChangeOrigin::CanisterChangeFromUser(origin)) => CanisterChangeOrigin::FromUser {
+                user_id: try_from_option_field(origin.user_id, "CanisterChangeFromUser::user_id")?,
+            },
+            Some(ChangeOrigin::CanisterChangeFromCanister(origin)) => {
+                CanisterChangeOrigin::FromCanister {
+                    canister_id: try_from_option_field(
+                        origin.canister_id,
+                        "CanisterChangeFromCanister::canister_id",
+                    )?,
+                }
+            }
+            None => {
+                return Err(ProxyDecodeError::MissingField(
+                    "CanisterChange::change_origin",
+                ))
+            }
+        };
+        let details = match item.change_details {
+            Some(ChangeDetails::CanisterCreation(details)) => CanisterChangeDetails::Creation {
+                controllers: principals(details.controllers)?,
+            },
+            Some(ChangeDetails::CanisterCodeUninstall(_)) => CanisterChangeDetails::CodeUninstall,
+            Some(ChangeDetails::CanisterCodeDeployment(details)) => {
+                let mode = match pb_canister_state_bits::CanisterInstallMode::from_i32(details.mode)
+                {
+                    Some(pb_canister_state_bits::CanisterInstallMode::Install) => {
+                        CanisterInstallMode::Install
+                    }
+                    Some(pb_canister_state_bits::CanisterInstallMode::Reinstall) => {
+                        CanisterInstallMode::Reinstall
+                    }
+                    Some(pb_canister_state_bits::CanisterInstallMode::Upgrade) => {
+                        CanisterInstallMode::Upgrade
+                    }
+                    Some(pb_canister_state_bits::CanisterInstallMode::Unspecified) | None => {
+                        return Err(ProxyDecodeError::ValueOutOfRange {
+                            typ: "CanisterInstallMode",
+                            err: format!("Unknown canister install mode: {}", details.mode),
+                        })
+                    }
+                };
+                CanisterChangeDetails::CodeDeployment {
+                    mode,
+                    module_hash: details.module_hash,
+                }
+            }
+            Some(ChangeDetails::CanisterControllersChange(details)) => {
+                CanisterChangeDetails::ControllersChange {
+                    controllers: principals(details.controllers)?,
+                }
+            }
+            None => {
+                return Err(ProxyDecodeError::MissingField(
+                    "CanisterChange::change_details",
+                ))
+            }
+        };
+        Ok(Self {
+            timestamp_nanos: item.timestamp_nanos,
+            canister_version: item.canister_version,
+            origin,
+            details,
+        })
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     total_num_changes: nat64;
+///     recent_changes: vec change;
+///     module_hash: opt blob;
+///     controllers: vec principal;
+/// })`
+///
+/// The reply of `canister_info`.
+#[derive(CandidType, Clone, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterInfoResponse {
+    pub total_num_changes: u64,
+    pub recent_changes: Vec<CanisterChange>,
+    pub module_hash: Option<Vec<u8>>,
+    pub controllers: Vec<PrincipalId>,
+}
+
+impl Payload<'_> for CanisterInfoResponse {}
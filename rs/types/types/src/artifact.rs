@@ -14,12 +14,13 @@
 //! All [`Artifact`] sub-types must also implement [`ChunkableArtifact`] trait
 //! defined in the chunkable module.
 use crate::{
+    canister_http::CanisterHttpResponseShare,
     consensus::{certification::CertificationMessageHash, ConsensusMessageHash},
     crypto::{CryptoHash, CryptoHashOf},
     filetree_sync::{FileTreeSyncArtifact, FileTreeSyncId},
-    messages::{MessageId, SignedRequestBytes},
+    messages::{CallbackId, MessageId, SignedRequestBytes},
     p2p::GossipAdvert,
-    CryptoHashOfState, Height, Time,
+    CryptoHashOfState, Height, RegistryVersion, Time,
 };
 use derive_more::{AsMut, AsRef, From, TryInto};
 use ic_protobuf::p2p::v1 as pb;
@@ -50,6 +51,7 @@ pub enum Artifact {
     EcdsaMessage(EcdsaMessage),
     FileTreeSync(FileTreeSyncArtifact),
     StateSync(StateSyncMessage),
+    CanisterHttpMessage(CanisterHttpResponseShare),
 }
 
 /// Artifact attribute type.
@@ -63,6 +65,7 @@ pub enum ArtifactAttribute {
     EcdsaMessage(EcdsaMessageAttribute),
     FileTreeSync(FileTreeSyncAttribute),
     StateSync(StateSyncAttribute),
+    CanisterHttpMessage(CanisterHttpResponseAttribute),
 }
 
 /// Artifact identifier type.
@@ -76,6 +79,7 @@ pub enum ArtifactId {
     EcdsaMessage(EcdsaMessageId),
     FileTreeSync(FileTreeSyncId),
     StateSync(StateSyncArtifactId),
+    CanisterHttpMessage(CanisterHttpResponseId),
 }
 
 /// Artifact tags is used to select an artifact subtype when we do not have
@@ -90,6 +94,7 @@ pub enum ArtifactTag {
     EcdsaArtifact,
     FileTreeSyncArtifact,
     StateSyncArtifact,
+    CanisterHttpArtifact,
 }
 
 impl std::fmt::Display for ArtifactTag {
@@ -105,6 +110,7 @@ impl std::fmt::Display for ArtifactTag {
                 ArtifactTag::EcdsaArtifact => "ECDSA",
                 ArtifactTag::FileTreeSyncArtifact => "FileTreeSync",
                 ArtifactTag::StateSyncArtifact => "StateSync",
+                ArtifactTag::CanisterHttpArtifact => "CanisterHttp",
             }
         )
     }
@@ -120,6 +126,7 @@ impl From<&ArtifactId> for ArtifactTag {
             ArtifactId::EcdsaMessage(_) => ArtifactTag::EcdsaArtifact,
             ArtifactId::FileTreeSync(_) => ArtifactTag::FileTreeSyncArtifact,
             ArtifactId::StateSync(_) => ArtifactTag::StateSyncArtifact,
+            ArtifactId::CanisterHttpMessage(_) => ArtifactTag::CanisterHttpArtifact,
         }
     }
 }
@@ -136,6 +143,7 @@ impl From<&Artifact> for ArtifactTag {
             Artifact::EcdsaMessage(_) => ArtifactTag::EcdsaArtifact,
             Artifact::FileTreeSync(_) => ArtifactTag::FileTreeSyncArtifact,
             Artifact::StateSync(_) => ArtifactTag::StateSyncArtifact,
+            Artifact::CanisterHttpMessage(_) => ArtifactTag::CanisterHttpArtifact,
         }
     }
 }
@@ -462,6 +470,19 @@ pub struct DkgMessageAttribute {
 
 pub type EcdsaMessageId = EcdsaMessageHash;
 
+// -----------------------------------------------------------------------------
+// CanisterHttp artifacts
+
+/// Identifier of a canister HTTP response share.
+pub type CanisterHttpResponseId = CryptoHashOf<CanisterHttpResponseShare>;
+
+/// The canister HTTP response share attribute used by the priority function.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpResponseAttribute {
+    pub id: CallbackId,
+    pub registry_version: RegistryVersion,
+}
+
 // ------------------------------------------------------------------------------
 // StateSync artifacts.
 
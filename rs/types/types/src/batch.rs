@@ -2,6 +2,9 @@
 //! Consensus and Message Routing.
 use super::{
     artifact::IngressMessageId,
+    canister_http::CanisterHttpPayload,
+    crypto::canister_threshold_sig::EcdsaPublicKey,
+    ic00::EcdsaKeyId,
     messages::{MessageId, Response, SignedIngress, EXPECTED_MESSAGE_ID_LENGTH},
     xnet::CertifiedStreamSlice,
     CountBytes, Height, Randomness, RegistryVersion, SubnetId, Time,
@@ -31,6 +34,8 @@ pub struct Batch {
     pub time: Time,
     /// Responses to subnet calls that reqire consensus' involvement.
     pub consensus_responses: Vec<Response>,
+    /// ECDSA public keys of the subnet, indexed by key id.
+    pub ecdsa_subnet_public_keys: BTreeMap<EcdsaKeyId, EcdsaPublicKey>,
 }
 
 /// The context built by Consensus for deterministic processing. Captures all
@@ -59,12 +64,15 @@ impl ValidationContext {
 
 /// The payload of a batch.
 ///
-/// Contains ingress and XNet messages.
+/// Contains ingress and XNet messages, Bitcoin blocks, as well as the
+/// responses to canister HTTP requests.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BatchPayload {
     pub ingress: IngressPayload,
     pub xnet: XNetPayload,
     pub self_validating: SelfValidatingPayload,
+    #[serde(default)]
+    pub canister_http: CanisterHttpPayload,
 }
 
 /// Return ingress messages, xnet messages, and consensus responses.
@@ -75,11 +83,13 @@ impl BatchPayload {
         ingress: IngressPayload,
         xnet: XNetPayload,
         self_validating: SelfValidatingPayload,
+        canister_http: CanisterHttpPayload,
     ) -> Self {
         BatchPayload {
             ingress,
             xnet,
             self_validating,
+            canister_http,
         }
     }
 
@@ -91,37 +101,53 @@ impl BatchPayload {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.ingress.is_empty() && self.xnet.stream_slices.is_empty()
+        self.ingress.is_empty()
+            && self.xnet.stream_slices.is_empty()
+            && self.self_validating.is_empty()
+            && self.canister_http.is_empty()
     }
 }
 
-/// Payload that contains SelfValidating messages.
+/// Payload that contains SelfValidating messages, i.e. Bitcoin blocks that
+/// can be validated without trusting the block maker.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct SelfValidatingPayload {}
+pub struct SelfValidatingPayload {
+    /// Consensus-encoded Bitcoin blocks, parents before children.
+    #[serde(default)]
+    pub bitcoin_blocks: Vec<Vec<u8>>,
+}
 
 impl SelfValidatingPayload {
     pub fn new() -> SelfValidatingPayload {
-        SelfValidatingPayload {}
+        SelfValidatingPayload::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitcoin_blocks.is_empty()
     }
 }
 
 impl From<&SelfValidatingPayload> for pb::SelfValidatingPayload {
-    fn from(_self_validating_payload: &SelfValidatingPayload) -> Self {
-        Self {}
+    fn from(self_validating_payload: &SelfValidatingPayload) -> Self {
+        Self {
+            bitcoin_blocks: self_validating_payload.bitcoin_blocks.clone(),
+        }
     }
 }
 
 impl TryFrom<pb::SelfValidatingPayload> for SelfValidatingPayload {
     type Error = String;
 
-    fn try_from(_value: pb::SelfValidatingPayload) -> Result<Self, Self::Error> {
-        Ok(Self {})
+    fn try_from(value: pb::SelfValidatingPayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            bitcoin_blocks: value.bitcoin_blocks,
+        })
     }
 }
 
 impl CountBytes for SelfValidatingPayload {
     fn count_bytes(&self) -> usize {
-        0
+        self.bitcoin_blocks.iter().map(|block| block.len()).sum()
     }
 }
 
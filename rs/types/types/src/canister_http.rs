@@ -0,0 +1,370 @@
+//! Contains the types used for canister HTTP requests, i.e. the requests that
+//! canisters make to the outside world through the `http_request` method of
+//! the management canister.
+//!
+//! Every node of the subnet signs the metadata of the response it received
+//! (a [`CanisterHttpResponseShare`]) and gossips the share to its peers. Once
+//! enough nodes signed the same metadata, the response is included, together
+//! with the signatures proving the agreement, in the
+//! [`crate::batch::BatchPayload`] delivered to Message Routing.
+use crate::{
+    consensus::{BasicSignature, BasicSignatureBatch},
+    crypto::{
+        BasicSig, BasicSigOf, CryptoHash, CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator,
+    },
+    messages::{CallbackId, Payload, RejectContext, Response},
+    node_id_into_protobuf, node_id_try_from_protobuf, CanisterId, CountBytes, Cycles,
+    RegistryVersion, Time,
+};
+use ic_error_types::RejectCode;
+use ic_ic00_types::{CanisterHttpResponsePayload, HttpHeader, HttpMethod};
+use ic_protobuf::types::v1 as pb;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+    mem::size_of,
+    time::Duration,
+};
+
+/// Time after which a canister HTTP request that has not received a response
+/// yet is answered with a reject.
+pub const CANISTER_HTTP_TIMEOUT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of bytes the response to a canister HTTP request may have,
+/// including the headers. Also the default if the canister does not specify
+/// `max_response_bytes`.
+pub const MAX_CANISTER_HTTP_RESPONSE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Maximum length of the URL of a canister HTTP request.
+pub const MAX_CANISTER_HTTP_URL_SIZE: usize = 8192;
+
+/// Maximum number of headers in a canister HTTP request.
+pub const MAX_CANISTER_HTTP_HEADER_NUM: usize = 64;
+
+/// Payload that contains the responses to canister HTTP requests.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpPayload {
+    /// Responses that enough nodes of the subnet agreed upon.
+    pub responses: Vec<CanisterHttpResponseWithConsensus>,
+    /// Requests that timed out before the nodes agreed upon a response.
+    pub timeouts: Vec<CallbackId>,
+}
+
+impl CanisterHttpPayload {
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty() && self.timeouts.is_empty()
+    }
+
+    /// Converts the payload into the management canister's responses to the
+    /// `http_request` calls it answers.
+    pub fn into_responses(self) -> Vec<Response> {
+        self.responses
+            .into_iter()
+            .map(|response| Response::from(response.content))
+            .chain(self.timeouts.into_iter().map(|id| Response {
+                originator: CanisterId::ic_00(),
+                respondent: CanisterId::ic_00(),
+                originator_reply_callback: id,
+                refund: Cycles::zero(),
+                response_payload: Payload::Reject(RejectContext::new(
+                    RejectCode::SysTransient,
+                    "Canister http request timed out",
+                )),
+            }))
+            .collect()
+    }
+}
+
+impl From<&CanisterHttpPayload> for pb::CanisterHttpPayload {
+    fn from(payload: &CanisterHttpPayload) -> Self {
+        Self {
+            responses: payload.responses.iter().map(From::from).collect(),
+            timeouts: payload.timeouts.iter().map(|id| id.get()).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::CanisterHttpPayload> for CanisterHttpPayload {
+    type Error = String;
+
+    fn try_from(payload: pb::CanisterHttpPayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            responses: payload
+                .responses
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            timeouts: payload.timeouts.into_iter().map(CallbackId::new).collect(),
+        })
+    }
+}
+
+impl CountBytes for CanisterHttpPayload {
+    fn count_bytes(&self) -> usize {
+        self.responses
+            .iter()
+            .map(|r| r.count_bytes())
+            .sum::<usize>()
+            + self.timeouts.len() * size_of::<CallbackId>()
+    }
+}
+
+/// The metadata of a canister HTTP response. The nodes sign the metadata
+/// rather than the response itself, which keeps the shares they gossip small.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpResponseMetadata {
+    pub id: CallbackId,
+    pub timeout: Time,
+    pub content_hash: CryptoHashOf<CanisterHttpResponse>,
+    /// The registry version used to determine the nodes that may sign.
+    pub registry_version: RegistryVersion,
+}
+
+impl SignedBytesWithoutDomainSeparator for CanisterHttpResponseMetadata {
+    fn as_signed_bytes_without_domain_separator(&self) -> Vec<u8> {
+        serde_cbor::to_vec(&self).unwrap()
+    }
+}
+
+impl From<&CanisterHttpResponseMetadata> for pb::CanisterHttpResponseMetadata {
+    fn from(metadata: &CanisterHttpResponseMetadata) -> Self {
+        Self {
+            id: metadata.id.get(),
+            timeout: metadata.timeout.as_nanos_since_unix_epoch(),
+            content_hash: metadata.content_hash.clone().get().0,
+            registry_version: metadata.registry_version.get(),
+        }
+    }
+}
+
+impl From<pb::CanisterHttpResponseMetadata> for CanisterHttpResponseMetadata {
+    fn from(metadata: pb::CanisterHttpResponseMetadata) -> Self {
+        Self {
+            id: CallbackId::new(metadata.id),
+            timeout: Time::from_nanos_since_unix_epoch(metadata.timeout),
+            content_hash: CryptoHashOf::from(CryptoHash(metadata.content_hash)),
+            registry_version: RegistryVersion::new(metadata.registry_version),
+        }
+    }
+}
+
+/// A single node's signature on the metadata of the response it received.
+pub type CanisterHttpResponseShare =
+    Signed<CanisterHttpResponseMetadata, BasicSignature<CanisterHttpResponseMetadata>>;
+
+/// The signatures of enough nodes on the same metadata, proving that the
+/// subnet agreed upon the response.
+pub type CanisterHttpResponseProof =
+    Signed<CanisterHttpResponseMetadata, BasicSignatureBatch<CanisterHttpResponseMetadata>>;
+
+/// A canister HTTP response together with the proof that the subnet agreed
+/// upon it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpResponseWithConsensus {
+    pub content: CanisterHttpResponse,
+    pub proof: CanisterHttpResponseProof,
+}
+
+impl CountBytes for CanisterHttpResponseWithConsensus {
+    fn count_bytes(&self) -> usize {
+        self.content.count_bytes()
+            + size_of::<CanisterHttpResponseMetadata>()
+            + self
+                .proof
+                .signature
+                .signatures_map
+                .values()
+                .map(|signature| size_of::<crate::NodeId>() + signature.get_ref().0.len())
+                .sum::<usize>()
+    }
+}
+
+impl From<&CanisterHttpResponseWithConsensus> for pb::CanisterHttpResponseWithConsensus {
+    fn from(response: &CanisterHttpResponseWithConsensus) -> Self {
+        Self {
+            response: Some((&response.content).into()),
+            metadata: Some((&response.proof.content).into()),
+            signatures: response
+                .proof
+                .signature
+                .signatures_map
+                .iter()
+                .map(|(signer, signature)| pb::CanisterHttpResponseSignature {
+                    signer: Some(node_id_into_protobuf(*signer)),
+                    signature: signature.clone().get().0,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::CanisterHttpResponseWithConsensus> for CanisterHttpResponseWithConsensus {
+    type Error = String;
+
+    fn try_from(response: pb::CanisterHttpResponseWithConsensus) -> Result<Self, Self::Error> {
+        let content = response
+            .response
+            .ok_or_else(|| {
+                String::from("Error: CanisterHttpResponseWithConsensus missing response")
+            })?
+            .try_into()?;
+        let metadata = response
+            .metadata
+            .ok_or_else(|| {
+                String::from("Error: CanisterHttpResponseWithConsensus missing metadata")
+            })?
+            .into();
+        let mut signatures_map = BTreeMap::new();
+        for signature in response.signatures {
+            let signer = node_id_try_from_protobuf(signature.signer.ok_or_else(|| {
+                String::from("Error: CanisterHttpResponseSignature missing signer")
+            })?)
+            .map_err(|e| format!("{:?}", e))?;
+            signatures_map.insert(signer, BasicSigOf::from(BasicSig(signature.signature)));
+        }
+        Ok(Self {
+            content,
+            proof: Signed {
+                content: metadata,
+                signature: BasicSignatureBatch { signatures_map },
+            },
+        })
+    }
+}
+
+/// The response to a single canister HTTP request, identified by the callback
+/// id under which the request was registered in the subnet call context
+/// manager.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpResponse {
+    pub id: CallbackId,
+    /// The time at which the request times out.
+    pub timeout: Time,
+    pub content: CanisterHttpResponseContent,
+}
+
+impl CountBytes for CanisterHttpResponse {
+    fn count_bytes(&self) -> usize {
+        size_of::<CallbackId>() + size_of::<Time>() + self.content.count_bytes()
+    }
+}
+
+impl From<&CanisterHttpResponse> for pb::CanisterHttpResponse {
+    fn from(response: &CanisterHttpResponse) -> Self {
+        Self {
+            id: response.id.get(),
+            timeout: response.timeout.as_nanos_since_unix_epoch(),
+            content: Some(match &response.content {
+                CanisterHttpResponseContent::Success(data) => {
+                    pb::canister_http_response::Content::Success(data.clone())
+                }
+                CanisterHttpResponseContent::Reject(reject) => {
+                    pb::canister_http_response::Content::Reject(pb::CanisterHttpReject {
+                        reject_code: reject.reject_code as u64,
+                        message: reject.message.clone(),
+                    })
+                }
+            }),
+        }
+    }
+}
+
+impl TryFrom<pb::CanisterHttpResponse> for CanisterHttpResponse {
+    type Error = String;
+
+    fn try_from(response: pb::CanisterHttpResponse) -> Result<Self, Self::Error> {
+        let content = match response
+            .content
+            .ok_or_else(|| String::from("Error: CanisterHttpResponse missing content"))?
+        {
+            pb::canister_http_response::Content::Success(data) => {
+                CanisterHttpResponseContent::Success(data)
+            }
+            pb::canister_http_response::Content::Reject(reject) => {
+                CanisterHttpResponseContent::Reject(CanisterHttpReject {
+                    reject_code: reject
+                        .reject_code
+                        .try_into()
+                        .map_err(|e| format!("{:?}", e))?,
+                    message: reject.message,
+                })
+            }
+        };
+        Ok(Self {
+            id: CallbackId::new(response.id),
+            timeout: Time::from_nanos_since_unix_epoch(response.timeout),
+            content,
+        })
+    }
+}
+
+impl From<CanisterHttpResponse> for Response {
+    /// Converts the agreed upon response into the management canister's
+    /// response to the `http_request` call.
+    fn from(response: CanisterHttpResponse) -> Self {
+        Response {
+            originator: CanisterId::ic_00(),
+            respondent: CanisterId::ic_00(),
+            originator_reply_callback: response.id,
+            refund: Cycles::zero(),
+            response_payload: match response.content {
+                CanisterHttpResponseContent::Success(data) => Payload::Data(data),
+                CanisterHttpResponseContent::Reject(reject) => {
+                    Payload::Reject(RejectContext::new(reject.reject_code, reject.message))
+                }
+            },
+        }
+    }
+}
+
+/// The content of a canister HTTP response: either the Candid encoded
+/// `CanisterHttpResponsePayload` (after the canister's transform function
+/// has been applied) or a reject.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CanisterHttpResponseContent {
+    Success(#[serde(with = "serde_bytes")] Vec<u8>),
+    Reject(CanisterHttpReject),
+}
+
+impl CountBytes for CanisterHttpResponseContent {
+    fn count_bytes(&self) -> usize {
+        match self {
+            CanisterHttpResponseContent::Success(data) => data.len(),
+            CanisterHttpResponseContent::Reject(reject) => {
+                size_of::<RejectCode>() + reject.message.len()
+            }
+        }
+    }
+}
+
+/// A reject for a canister HTTP request, e.g. because the remote server
+/// could not be reached or the response was too large.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanisterHttpReject {
+    pub reject_code: RejectCode,
+    pub message: String,
+}
+
+/// A canister HTTP request as it is handed to the adapter that executes it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanisterHttpRequest {
+    pub id: CallbackId,
+    /// The time at which the request times out.
+    pub timeout: Time,
+    pub url: String,
+    pub method: HttpMethod,
+    pub headers: Vec<HttpHeader>,
+    pub body: Option<Vec<u8>>,
+    /// The adapter stops reading the response once it exceeds this many bytes.
+    pub max_response_bytes: u64,
+}
+
+/// The response to a [`CanisterHttpRequest`] as returned by the adapter,
+/// i.e. before the canister's transform function has been applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanisterHttpAdapterResponse {
+    pub id: CallbackId,
+    pub timeout: Time,
+    pub content: Result<CanisterHttpResponsePayload, CanisterHttpReject>,
+}
@@ -17,6 +17,7 @@
 //! that implement a common trait.
 use crate::{
     artifact::{Artifact, StateSyncMessage},
+    canister_http::CanisterHttpResponseShare,
     consensus::{
         certification::CertificationMessage, dkg::Message as DkgMessage, ecdsa::EcdsaMessage,
         ConsensusMessage,
@@ -92,6 +93,7 @@ pub enum SingleChunked {
     Certification,
     Dkg,
     Ecdsa,
+    CanisterHttp,
 }
 
 /// Interface providing access to artifact chunks.
@@ -133,6 +135,9 @@ chunkable_artifact_impl! {DkgMessage, |self|
 chunkable_artifact_impl! {EcdsaMessage, |self|
     ArtifactChunkData::UnitChunkData(Artifact::EcdsaMessage(*self))
 }
+chunkable_artifact_impl! {CanisterHttpResponseShare, |self|
+    ArtifactChunkData::UnitChunkData(Artifact::CanisterHttpMessage(*self))
+}
 
 impl ChunkableArtifact for StateSyncMessage {
     fn get_chunk(self: Box<Self>, chunk_id: ChunkId) -> Option<ArtifactChunk> {
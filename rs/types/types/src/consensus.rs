@@ -10,6 +10,7 @@ use ic_protobuf::log::block_log_entry::v1::BlockLogEntry;
 use ic_protobuf::types::v1 as pb;
 use serde::{Deserialize, Serialize};
 use std::cmp::PartialOrd;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::hash::Hash;
 
@@ -36,6 +37,13 @@ pub struct BasicSignature<T> {
 /// BasicSigned<T> captures a value of type T and a BasicSignature on it
 pub type BasicSigned<T> = Signed<T, BasicSignature<T>>;
 
+/// BasicSignatureBatch captures basic signatures of several replicas on the
+/// same value, indexed by the identity of the replica that signed it
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BasicSignatureBatch<T> {
+    pub signatures_map: BTreeMap<NodeId, BasicSigOf<T>>,
+}
+
 /// ThresholdSignature captures a threshold signature on a value and the
 /// DKG id of the threshold key material used to sign
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -1220,23 +1228,30 @@ pub fn get_faults_tolerated(n: usize) -> usize {
 impl From<&Block> for pb::Block {
     fn from(block: &Block) -> Self {
         let payload: &BlockPayload = block.payload.as_ref();
-        let (dkg_payload, xnet_payload, ingress_payload, self_validating_payload) =
-            if payload.is_summary() {
-                (
-                    pb::DkgPayload::from(&payload.as_summary().dkg),
-                    None,
-                    None,
-                    None,
-                )
-            } else {
-                let batch = &payload.as_data().batch;
-                (
-                    pb::DkgPayload::from(&payload.as_data().dealings),
-                    Some(pb::XNetPayload::from(&batch.xnet)),
-                    Some(pb::IngressPayload::from(&batch.ingress)),
-                    Some(pb::SelfValidatingPayload::from(&batch.self_validating)),
-                )
-            };
+        let (
+            dkg_payload,
+            xnet_payload,
+            ingress_payload,
+            self_validating_payload,
+            canister_http_payload,
+        ) = if payload.is_summary() {
+            (
+                pb::DkgPayload::from(&payload.as_summary().dkg),
+                None,
+                None,
+                None,
+                None,
+            )
+        } else {
+            let batch = &payload.as_data().batch;
+            (
+                pb::DkgPayload::from(&payload.as_data().dealings),
+                Some(pb::XNetPayload::from(&batch.xnet)),
+                Some(pb::IngressPayload::from(&batch.ingress)),
+                Some(pb::SelfValidatingPayload::from(&batch.self_validating)),
+                Some(pb::CanisterHttpPayload::from(&batch.canister_http)),
+            )
+        };
         Self {
             version: block.version.to_string(),
             parent: block.parent.clone().get().0,
@@ -1249,6 +1264,7 @@ impl From<&Block> for pb::Block {
             xnet_payload,
             ingress_payload,
             self_validating_payload,
+            canister_http_payload,
             payload_hash: block.payload.get_hash().clone().get().0,
         }
     }
@@ -1278,6 +1294,11 @@ impl TryFrom<pb::Block> for Block {
                 .map(crate::batch::SelfValidatingPayload::try_from)
                 .transpose()?
                 .unwrap_or_default(),
+            block
+                .canister_http_payload
+                .map(crate::canister_http::CanisterHttpPayload::try_from)
+                .transpose()?
+                .unwrap_or_default(),
         );
         let payload = match dkg_payload {
             dkg::Payload::Summary(summary) => {
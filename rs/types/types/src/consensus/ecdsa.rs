@@ -17,20 +17,28 @@ use crate::crypto::{
     canister_threshold_sig::{
         PreSignatureQuadruple, ThresholdEcdsaSigInputs, ThresholdEcdsaSigShare,
     },
-    CombinedMultiSigOf, CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator,
+    CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator,
 };
+use crate::messages::Response;
 use crate::{Height, NodeId};
 use phantom_newtype::Id;
 
-pub type EcdsaSignature = CombinedMultiSigOf<IDkgDealing>;
+/// A signature that consensus agreed upon. It is delivered to execution as
+/// the response to the signing request with the first batch that contains it,
+/// and kept afterwards only to avoid signing the same request again.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompletedSignature {
+    ReportedToExecution,
+    Unreported(Response),
+}
 
 /// The payload information necessary for ECDSA threshold signatures, that is
 /// published on every consensus round. It represents the current state of
 /// the protocol since the summary block.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct EcdsaDataPayload {
-    /// Signatures that we agreed upon in this round.
-    pub signature_agreements: BTreeMap<RequestId, EcdsaSignature>,
+    /// Signatures that we agreed upon.
+    pub signature_agreements: BTreeMap<RequestId, CompletedSignature>,
 
     /// The `RequestIds` for which we are currently generating signatures.
     pub ongoing_signatures: BTreeMap<RequestId, ThresholdEcdsaSigInputs>,
@@ -67,6 +75,10 @@ pub struct EcdsaSummaryPayload {
     /// The `RequestIds` for which we are currently generating signatures.
     pub ongoing_signatures: BTreeMap<RequestId, ThresholdEcdsaSigInputs>,
 
+    /// Signatures that were agreed upon (and delivered) in the previous
+    /// interval, whose requests may still be pending in the certified state.
+    pub signature_agreements: BTreeMap<RequestId, CompletedSignature>,
+
     /// The ECDSA transcript that we're currently using (if we have one).
     pub current_ecdsa_transcript: Option<UnmaskedTranscript>,
 
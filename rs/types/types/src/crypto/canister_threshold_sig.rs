@@ -179,10 +179,13 @@ impl PreSignatureQuadruple {
 }
 
 /// Metadata used to derive a specific ECDSA keypair.
+///
+/// The key of a caller is derived from the master key using the caller's
+/// principal followed by the elements of `derivation_path`.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ExtendedDerivationPath {
     pub caller: PrincipalId,
-    pub bip32_derivation_path: Vec<u32>,
+    pub derivation_path: Vec<Vec<u8>>,
 }
 
 /// All inputs required to generate a canister threshold signature.
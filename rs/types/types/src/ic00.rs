@@ -1,7 +1,15 @@
 //! Data types used for encoding/decoding the Candid payloads of ic:00.
 pub use ic_ic00_types::{
-    CanisterIdRecord, CanisterSettingsArgs, CanisterStatusResult, CanisterStatusResultV2,
-    CreateCanisterArgs, EmptyBlob, InstallCodeArgs, Method, Payload,
+    BitcoinGetBalanceArgs, BitcoinGetUtxosArgs, BitcoinGetUtxosResponse, BitcoinOutPoint,
+    BitcoinSendTransactionArgs, BitcoinUtxo, CanisterChange, CanisterChangeDetails,
+    CanisterChangeOrigin, CanisterHttpRequestArgs, CanisterHttpResponsePayload, CanisterIdRecord,
+    CanisterInfoRequest, CanisterInfoResponse, CanisterLogRecord, CanisterSettingsArgs,
+    CanisterSnapshotArgs, CanisterSnapshotResponse, CanisterStatusResult, CanisterStatusResultV2,
+    ChunkHash, CreateCanisterArgs, ECDSAPublicKeyArgs, ECDSAPublicKeyResponse, EcdsaCurve,
+    EcdsaKeyId, EmptyBlob, FetchCanisterLogsResponse, HttpHeader, HttpMethod,
+    InstallChunkedCodeArgs, InstallCodeArgs, LogVisibility, Method, Payload,
     ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs, SetControllerArgs,
-    SetupInitialDKGArgs, SetupInitialDKGResponse, UpdateSettingsArgs, IC_00,
+    SetupInitialDKGArgs, SetupInitialDKGResponse, SignWithECDSAArgs, SignWithECDSAReply,
+    StoredChunksReply, TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
+    UploadChunkReply, IC_00,
 };
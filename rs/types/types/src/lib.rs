@@ -56,6 +56,7 @@
 
 pub mod artifact;
 pub mod batch;
+pub mod canister_http;
 pub mod canonical_error;
 pub mod chunkable;
 pub mod consensus;
@@ -198,6 +199,11 @@ pub struct ExecutionRoundTag {}
 // Note [ExecutionRound vs Height]
 pub type ExecutionRound = Id<ExecutionRoundTag, u64>;
 
+pub struct SnapshotIdTag {}
+/// The id of a canister snapshot. Snapshot ids are allocated sequentially per
+/// subnet and are never reused.
+pub type SnapshotId = Id<SnapshotIdTag, u64>;
+
 pub enum CanonicalPartialStateTag {}
 /// A cryptographic hash of the part of the canonical replicated state at some
 /// height required for certification (cross-net streams, etc.).
@@ -1,4 +1,4 @@
-use crate::{ingress::WasmResult, CanisterId, CountBytes, Cycles, Funds, NumBytes};
+use crate::{ingress::WasmResult, CanisterId, CountBytes, Cycles, Funds, NumBytes, Time};
 use ic_error_types::{RejectCode, UserError};
 use ic_protobuf::{
     proxy::{try_from_option_field, ProxyDecodeError},
@@ -31,6 +31,10 @@ pub struct Request {
     pub method_name: String,
     #[serde(with = "serde_bytes")]
     pub method_payload: Vec<u8>,
+    /// The deadline of a best-effort call. Once it has passed, the request
+    /// may be dropped and the caller gets a `SYS_UNKNOWN` reject. `None` for
+    /// guaranteed response calls.
+    pub deadline: Option<Time>,
 }
 
 impl Request {
@@ -55,6 +59,13 @@ impl Request {
         let bytes = self.method_name.len() + self.method_payload.len();
         NumBytes::from(bytes as u64)
     }
+
+    /// Returns true if this is a best-effort request whose deadline is
+    /// before `current_time`.
+    pub fn is_expired(&self, current_time: Time) -> bool {
+        self.deadline
+            .map_or(false, |deadline| deadline < current_time)
+    }
 }
 
 /// The context attached when an inter-canister message is rejected.
@@ -221,6 +232,9 @@ impl From<&Request> for pb_queues::Request {
             method_name: req.method_name.clone(),
             method_payload: req.method_payload.clone(),
             cycles_payment: Some((req.payment).into()),
+            deadline_nanos: req
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -244,6 +258,10 @@ impl TryFrom<pb_queues::Request> for Request {
             payment,
             method_name: req.method_name,
             method_payload: req.method_payload,
+            deadline: match req.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
         })
     }
 }
@@ -1,7 +1,7 @@
 //! This module contains a collection of types and structs that define the
 //! various types of methods in the IC.
 
-use crate::{messages::CallContextId, Cycles};
+use crate::{messages::CallContextId, CanisterId, Cycles, Time};
 use ic_protobuf::proxy::{try_from_option_field, ProxyDecodeError};
 use ic_protobuf::state::{canister_state_bits::v1 as pb, queues::v1::Cycles as PbCycles};
 use serde::{Deserialize, Serialize};
@@ -24,6 +24,13 @@ pub enum WasmMethod {
     /// execution.
     Query(String),
 
+    /// An exported composite query method along with its name.
+    ///
+    /// Like query methods, but can call query methods of other canisters on
+    /// the same subnet. Composite queries can only be executed in
+    /// non-replicated mode.
+    CompositeQuery(String),
+
     /// An exported system method. Unlike query or update method, there
     /// are a few fixed system methods as defined in `SystemMethod`.
     System(SystemMethod),
@@ -34,6 +41,7 @@ impl WasmMethod {
         match self {
             Self::Update(name) => name.to_string(),
             Self::Query(name) => name.to_string(),
+            Self::CompositeQuery(name) => name.to_string(),
             Self::System(system_method) => system_method.to_string(),
         }
     }
@@ -44,6 +52,7 @@ impl fmt::Display for WasmMethod {
         match self {
             Self::Update(name) => write!(f, "canister_update {}", name),
             Self::Query(name) => write!(f, "canister_query {}", name),
+            Self::CompositeQuery(name) => write!(f, "canister_composite_query {}", name),
             Self::System(system_method) => system_method.fmt(f),
         }
     }
@@ -61,6 +70,10 @@ impl TryFrom<String> for WasmMethod {
             // Take the part after the first space
             let parts: Vec<&str> = name.splitn(2, ' ').collect();
             Ok(WasmMethod::Query(parts[1].to_string()))
+        } else if name.starts_with("canister_composite_query ") {
+            // Take the part after the first space
+            let parts: Vec<&str> = name.splitn(2, ' ').collect();
+            Ok(WasmMethod::CompositeQuery(parts[1].to_string()))
         } else {
             match SystemMethod::try_from(name.as_ref()) {
                 Ok(system_method) => Ok(WasmMethod::System(system_method)),
@@ -81,6 +94,9 @@ impl From<&WasmMethod> for pb::WasmMethod {
             WasmMethod::Query(value) => Self {
                 wasm_method: Some(PbWasmMethod::Query(value.clone())),
             },
+            WasmMethod::CompositeQuery(value) => Self {
+                wasm_method: Some(PbWasmMethod::CompositeQuery(value.clone())),
+            },
             WasmMethod::System(value) => Self {
                 wasm_method: Some(PbWasmMethod::System(match value {
                     SystemMethod::CanisterStart => PbSystemMethod::CanisterStart,
@@ -89,6 +105,7 @@ impl From<&WasmMethod> for pb::WasmMethod {
                     SystemMethod::CanisterPostUpgrade => PbSystemMethod::CanisterPostUpgrade,
                     SystemMethod::CanisterInspectMessage => PbSystemMethod::CanisterInspectMessage,
                     SystemMethod::CanisterHeartbeat => PbSystemMethod::CanisterHeartbeat,
+                    SystemMethod::CanisterGlobalTimer => PbSystemMethod::CanisterGlobalTimer,
                     SystemMethod::Empty => PbSystemMethod::Empty,
                 } as i32)),
             },
@@ -105,6 +122,7 @@ impl TryFrom<pb::WasmMethod> for WasmMethod {
         match try_from_option_field(method.wasm_method, "WasmMethod::wasm_method")? {
             PbWasmMethod::Update(update) => Ok(Self::Update(update)),
             PbWasmMethod::Query(query) => Ok(Self::Query(query)),
+            PbWasmMethod::CompositeQuery(query) => Ok(Self::CompositeQuery(query)),
             PbWasmMethod::System(system) => {
                 let method =
                     PbSystemMethod::from_i32(system).unwrap_or(PbSystemMethod::Unspecified);
@@ -122,6 +140,7 @@ impl TryFrom<pb::WasmMethod> for WasmMethod {
                     PbSystemMethod::CanisterPostUpgrade => SystemMethod::CanisterPostUpgrade,
                     PbSystemMethod::CanisterInspectMessage => SystemMethod::CanisterInspectMessage,
                     PbSystemMethod::CanisterHeartbeat => SystemMethod::CanisterHeartbeat,
+                    PbSystemMethod::CanisterGlobalTimer => SystemMethod::CanisterGlobalTimer,
                     PbSystemMethod::Empty => SystemMethod::Empty,
                 }))
             }
@@ -145,6 +164,9 @@ pub enum SystemMethod {
     CanisterInspectMessage,
     /// A system method that is run at regular intervals for cron support.
     CanisterHeartbeat,
+    /// A system method that is run once the deadline of the canister's global
+    /// timer has passed.
+    CanisterGlobalTimer,
     /// This is introduced as temporary scaffolding to aid in construction of
     /// the initial ExecutionState. This isn't used to execute any actual wasm
     /// but as a way to get to the wasm embedder from execution. Eventually, we
@@ -164,6 +186,7 @@ impl TryFrom<&str> for SystemMethod {
             "canister_start" => Ok(SystemMethod::CanisterStart),
             "canister_inspect_message" => Ok(SystemMethod::CanisterInspectMessage),
             "canister_heartbeat" => Ok(SystemMethod::CanisterHeartbeat),
+            "canister_global_timer" => Ok(SystemMethod::CanisterGlobalTimer),
             "empty" => Ok(SystemMethod::Empty),
             _ => Err(format!("Cannot convert {} to SystemMethod.", value)),
         }
@@ -179,6 +202,7 @@ impl fmt::Display for SystemMethod {
             Self::CanisterStart => write!(f, "canister_start"),
             Self::CanisterInspectMessage => write!(f, "canister_inspect_message"),
             Self::CanisterHeartbeat => write!(f, "canister_heartbeat"),
+            Self::CanisterGlobalTimer => write!(f, "canister_global_timer"),
             Self::Empty => write!(f, "empty"),
         }
     }
@@ -210,6 +234,11 @@ pub struct Callback {
     /// An optional closure to be executed if the execution of `on_reply` or
     /// `on_reject` traps.
     pub on_cleanup: Option<WasmClosure>,
+    /// The canister that the original request was sent to, if any.
+    pub respondent: Option<CanisterId>,
+    /// The deadline of a best-effort call. If no response was received by
+    /// then, the call is completed with a `SYS_UNKNOWN` reject.
+    pub deadline: Option<Time>,
 }
 
 impl Callback {
@@ -219,6 +248,8 @@ impl Callback {
         on_reply: WasmClosure,
         on_reject: WasmClosure,
         on_cleanup: Option<WasmClosure>,
+        respondent: Option<CanisterId>,
+        deadline: Option<Time>,
     ) -> Self {
         Self {
             call_context_id,
@@ -226,6 +257,8 @@ impl Callback {
             on_reply,
             on_reject,
             on_cleanup,
+            respondent,
+            deadline,
         }
     }
 }
@@ -247,6 +280,10 @@ impl From<&Callback> for pb::Callback {
                 func_idx: on_cleanup.func_idx,
                 env: on_cleanup.env,
             }),
+            respondent: item.respondent.map(|respondent| respondent.into()),
+            deadline_nanos: item
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -277,6 +314,11 @@ impl TryFrom<pb::Callback> for Callback {
                 func_idx: on_cleanup.func_idx,
                 env: on_cleanup.env,
             }),
+            respondent: value.respondent.map(CanisterId::try_from).transpose()?,
+            deadline: match value.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
         })
     }
 }
@@ -307,9 +349,11 @@ impl FuncRef {
             | Self::Method(WasmMethod::System(SystemMethod::CanisterPreUpgrade))
             | Self::Method(WasmMethod::System(SystemMethod::CanisterPostUpgrade))
             | Self::Method(WasmMethod::System(SystemMethod::CanisterHeartbeat))
+            | Self::Method(WasmMethod::System(SystemMethod::CanisterGlobalTimer))
             | Self::UpdateClosure(_) => true,
             Self::QueryClosure(_)
             | Self::Method(WasmMethod::Query(_))
+            | Self::Method(WasmMethod::CompositeQuery(_))
             | Self::Method(WasmMethod::System(SystemMethod::Empty))
             | Self::Method(WasmMethod::System(SystemMethod::CanisterInspectMessage)) => false,
         }
@@ -181,4 +181,7 @@ pub enum RegistryClientError {
 
     #[error("failed to report the same version twice after {retries} times")]
     PollingLatestVersionFailed { retries: usize },
+
+    #[error("failed to decode registry value: {error}")]
+    DecodeError { error: String },
 }
@@ -121,6 +121,7 @@ prop_compose! {
         method_name in "[a-zA-Z]{1,6}",
         callback in any::<u64>(),
         method_payload in prop::collection::vec(any::<u8>(), 0..16),
+        deadline_nanos in prop::option::of(1..u64::MAX),
     ) -> Request {
         Request {
             receiver,
@@ -129,6 +130,7 @@ prop_compose! {
             payment: Cycles::from(cycles_payment),
             method_name,
             method_payload,
+            deadline: deadline_nanos.map(Time::from_nanos_since_unix_epoch),
         }
     }
 }